@@ -3,5 +3,6 @@
 // ─────────────────────────────────────────────────────────────
 
 fn main() {
+    #[cfg(feature = "gui")]
     tauri_build::build();
 }
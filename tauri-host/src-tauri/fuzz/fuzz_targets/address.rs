@@ -0,0 +1,24 @@
+//! Socket table address parsing on arbitrary input:
+//! `cargo +nightly fuzz run address`.
+
+#![no_main]
+
+use abyss_lib::address::{is_private_ip, parse_address, split_address};
+use libfuzzer_sys::fuzz_target;
+use std::net::IpAddr;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(addr) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = split_address(addr);
+    let _ = is_private_ip(addr);
+    if let Some((ip, port)) = parse_address(addr) {
+        // Strict results are real addresses, and split_address agrees on them
+        let host = ip.split('%').next().unwrap_or_default();
+        assert!(ip == "*" || host.parse::<IpAddr>().is_ok(), "{addr:?} -> {ip:?}");
+        if ip != "*" && !addr.ends_with(":*") {
+            assert_eq!(split_address(addr), (ip, port), "{addr:?}");
+        }
+    }
+});
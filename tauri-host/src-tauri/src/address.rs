@@ -0,0 +1,246 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Longest sample of an unparseable line kept for diagnostics.
+const MAX_SAMPLE_LEN: usize = 200;
+
+// ─── Best-effort parsing ────────────────────────────────────────────────────
+
+/// Whether `ip` is a loopback, private (RFC 1918 / ULA), link-local or
+/// unspecified address, including IPv4-mapped forms.  Works on the textual
+/// form netstat and the socket table produce.
+pub fn is_private_ip(ip: &str) -> bool {
+    ip.starts_with("10.")
+        || ip.starts_with("192.168.")
+        || (ip.starts_with("172.") && {
+            let second: u8 = ip
+                .split('.')
+                .nth(1)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            (16..=31).contains(&second)
+        })
+        || ip.starts_with("127.")
+        || ip.starts_with("0.")
+        || ip == "::1"
+        || ip == "::"
+        || ip.starts_with("fe80:")
+        || ip.starts_with("fc00:")
+        || ip.starts_with("fd")
+        || ip == "*"
+        // IPv4-mapped IPv6: ::ffff:10.x, ::ffff:192.168.x, etc.
+        || (ip.starts_with("::ffff:") && {
+            let v4 = &ip[7..];
+            is_private_ip(v4)
+        })
+}
+
+/// Split `ip:port`, `[v6]:port` or bare `v6:port` into its parts, guessing
+/// where the input is malformed: anything that isn't a valid port becomes
+/// port 0 and the IP is returned unchecked.
+pub fn split_address(addr: &str) -> (String, u16) {
+    // Handle IPv6 in brackets: [::1]:443
+    if let Some(rest) = addr.strip_prefix('[') {
+        if let Some(close) = rest.find(']') {
+            let ip = rest[..close].to_string();
+            let port = rest
+                .get(close + 2..) // skip "]:"
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            return (ip, port);
+        }
+        // Malformed bracket — return as-is
+        return (addr.to_string(), 0);
+    }
+    // Count colons to distinguish IPv6 (bare, no brackets) from IPv4
+    let colon_count = addr.chars().filter(|&c| c == ':').count();
+    if colon_count > 1 {
+        // Bare IPv6 without brackets — last colon separates port
+        if let Some(pos) = addr.rfind(':') {
+            // Only treat as port if what follows is a valid u16
+            if let Ok(port) = addr[pos + 1..].parse::<u16>() {
+                return (addr[..pos].to_string(), port);
+            }
+        }
+        // No valid port found — entire string is the IP
+        return (addr.to_string(), 0);
+    }
+    // IPv4: last colon separates port
+    if let Some(pos) = addr.rfind(':') {
+        let ip = addr[..pos].to_string();
+        let port = addr[pos + 1..].parse().unwrap_or(0);
+        return (ip, port);
+    }
+    (addr.to_string(), 0)
+}
+
+// ─── Strict parsing ─────────────────────────────────────────────────────────
+
+/// Parse `ip:port`, `[v6]:port` or bare `v6:port` only when both parts are
+/// valid.  `*` stands for the unspecified address or port 0 (netstat's
+/// `*:*`), and an IPv6 zone (`fe80::1%eth0`) is kept as given.
+pub fn parse_address(addr: &str) -> Option<(String, u16)> {
+    let (ip, port) = match addr.strip_prefix('[') {
+        Some(rest) => {
+            let (ip, port) = rest.split_once("]:")?;
+            (ip, port)
+        }
+        None => addr.rsplit_once(':')?,
+    };
+    let host = ip.split_once('%').map_or(ip, |(host, _)| host);
+    if ip != "*" && host.parse::<IpAddr>().is_err() {
+        return None;
+    }
+    // Brackets are only for IPv6
+    if addr.starts_with('[') && !host.contains(':') {
+        return None;
+    }
+    let port = match port {
+        "*" => 0,
+        p if p.bytes().all(|b| b.is_ascii_digit()) => p.parse().ok()?,
+        _ => return None,
+    };
+    Some((ip.to_string(), port))
+}
+
+/// Process-wide copy of `Settings::strict_address_parsing`: drop socket
+/// table lines whose addresses don't parse instead of guessing.
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+pub fn strict() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+/// Parse a socket table line's local and remote address.  Lines that fail
+/// strict parsing are counted under `source`; in strict mode they are
+/// skipped (`None`), otherwise `split_address` guesses as before.
+pub fn parse_pair(source: &'static str, line: &str, local: &str, remote: &str) -> Option<((String, u16), (String, u16))> {
+    if let (Some(local), Some(remote)) = (parse_address(local), parse_address(remote)) {
+        return Some((local, remote));
+    }
+    record_unparsed(source, line);
+    if strict() {
+        return None;
+    }
+    Some((split_address(local), split_address(remote)))
+}
+
+// ─── Diagnostics ────────────────────────────────────────────────────────────
+
+/// Per-source counts of socket table lines that failed strict parsing,
+/// reported through `cmd_get_diagnostics`.
+static UNPARSED: Mutex<BTreeMap<&'static str, UnparsedLines>> = Mutex::new(BTreeMap::new());
+
+/// Socket table lines (`netstat`, `ss`) whose addresses didn't parse.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnparsedLines {
+    pub source: &'static str,
+    pub count: u64,
+    /// Most recent offending line, truncated.
+    pub last_line: String,
+    pub last_at: String,
+    /// Whether these lines were dropped (strict mode) or parsed best-effort.
+    pub dropped: bool,
+}
+
+fn record_unparsed(source: &'static str, line: &str) {
+    let mut map = UNPARSED.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = map.entry(source).or_insert_with(|| {
+        eprintln!("[Abyss] Unparseable {source} line: {line}");
+        UnparsedLines {
+            source,
+            count: 0,
+            last_line: String::new(),
+            last_at: String::new(),
+            dropped: false,
+        }
+    });
+    entry.count += 1;
+    entry.last_line = line.chars().take(MAX_SAMPLE_LEN).collect();
+    entry.last_at = chrono::Utc::now().to_rfc3339();
+    entry.dropped = strict();
+}
+
+/// Unparseable line counts since startup, ordered by source.
+pub fn unparsed_lines() -> Vec<UnparsedLines> {
+    UNPARSED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    #[test]
+    fn strict_parsing_accepts_socket_table_forms() {
+        let ok = |s: &str, ip: &str, port: u16| assert_eq!(parse_address(s), Some((ip.to_string(), port)), "{s}");
+        ok("93.184.216.34:443", "93.184.216.34", 443);
+        ok("[2606:4700::1111]:53", "2606:4700::1111", 53);
+        ok("2606:4700::1111:53", "2606:4700::1111", 53);
+        ok("[fe80::1%eth0]:22", "fe80::1%eth0", 22);
+        ok("*:*", "*", 0);
+        ok("0.0.0.0:*", "0.0.0.0", 0);
+        ok("[::]:0", "::", 0);
+    }
+
+    #[test]
+    fn strict_parsing_rejects_what_split_address_guesses() {
+        for s in ["", "*", "1.2.3.4", "1.2.3.4:", "1.2.3.4:http", "1.2.3.4:65536", "1.2.3.4:+80", "[::1", "[::1]", "[1.2.3.4]:80", "host:80", "::1"] {
+            assert_eq!(parse_address(s), None, "{s}");
+        }
+        assert_eq!(split_address("1.2.3.4:http"), ("1.2.3.4".to_string(), 0));
+    }
+
+    proptest! {
+        #[test]
+        fn split_address_never_panics(s in "\\PC*") {
+            let _ = split_address(&s);
+            let _ = parse_address(&s);
+            let _ = is_private_ip(&s);
+        }
+
+        #[test]
+        fn socket_addresses_round_trip(ip in any::<IpAddr>(), port in any::<u16>()) {
+            let formatted = SocketAddr::new(ip, port).to_string();
+            let expected = (ip.to_string(), port);
+            prop_assert_eq!(split_address(&formatted), expected.clone());
+            prop_assert_eq!(parse_address(&formatted), Some(expected));
+        }
+
+        #[test]
+        fn bare_ipv6_with_port_round_trips(ip in any::<Ipv6Addr>(), port in any::<u16>()) {
+            // netstat -n on some platforms prints IPv6 without brackets
+            let formatted = format!("{ip}:{port}");
+            prop_assert_eq!(parse_address(&formatted), Some((ip.to_string(), port)));
+        }
+
+        #[test]
+        fn strict_results_are_valid_addresses(s in "[0-9a-f:.\\[\\]%*]{0,48}") {
+            if let Some((ip, _)) = parse_address(&s) {
+                let host = ip.split('%').next().unwrap_or_default();
+                prop_assert!(ip == "*" || host.parse::<IpAddr>().is_ok());
+            }
+        }
+
+        #[test]
+        fn private_ipv4_matches_std(ip in any::<Ipv4Addr>()) {
+            let expected = ip.is_private() || ip.is_loopback() || ip.octets()[0] == 0;
+            prop_assert_eq!(is_private_ip(&ip.to_string()), expected);
+            prop_assert_eq!(is_private_ip(&format!("::ffff:{ip}")), expected);
+        }
+    }
+}
@@ -0,0 +1,729 @@
+use crate::db;
+use crate::error::AbyssError;
+use crate::units;
+use crate::{GeoFlow, TelemetryFrame};
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Cooldown applied when a rule doesn't specify one.
+const DEFAULT_COOLDOWN_SECS: u64 = 300;
+/// Remote ports `UnusualPort` never flags: web, DNS, mail, SSH, NTP, STUN
+/// and push services.
+const COMMON_PORTS: &[u16] = &[
+    20, 21, 22, 53, 80, 123, 143, 443, 465, 587, 853, 993, 995, 3478, 5223, 5228, 8080, 8443,
+];
+
+// ─── Rules ──────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Which side of the traffic a threshold applies to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    Up,
+    Down,
+    #[default]
+    Total,
+}
+
+/// Calendar window a quota resets on (local time; weeks start Monday).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuotaPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl QuotaPeriod {
+    /// First day of the period `today` falls in.
+    pub fn start(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            QuotaPeriod::Day => today,
+            QuotaPeriod::Week => today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64),
+            QuotaPeriod::Month => today.with_day(1).unwrap_or(today),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuotaPeriod::Day => "day",
+            QuotaPeriod::Week => "week",
+            QuotaPeriod::Month => "month",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            "month" => Some(Self::Month),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GeofenceMode {
+    /// Alert on any country not in the list.
+    Allow,
+    /// Alert on any country in the list.
+    Deny,
+}
+
+/// Minimum baseline anomaly severity (matches `db::Anomaly::severity`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnomalyLevel {
+    Low,
+    Medium,
+    High,
+}
+
+impl AnomalyLevel {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+}
+
+/// What a rule watches.  Stored as tagged JSON, e.g.
+/// `{ "type": "processRate", "process": "steam.exe", "maxBps": 50000000 }`.
+/// Rates are bits/s, quotas bytes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Condition {
+    /// Combined rate of one process's visible flows exceeds `max_bps`.
+    ProcessRate { process: String, max_bps: f64 },
+    /// Machine-wide throughput exceeds `max_bps`.
+    TotalRate {
+        max_bps: f64,
+        #[serde(default)]
+        direction: Direction,
+    },
+    /// Measured latency exceeds `max_ms`.
+    Latency { max_ms: f64 },
+    /// A flow reaches a country outside (allow) or inside (deny) the list.
+    Geofence { mode: GeofenceMode, countries: Vec<String> },
+    /// Bytes recorded since the start of the period exceed `max_bytes`.
+    Quota {
+        period: QuotaPeriod,
+        max_bytes: f64,
+        #[serde(default)]
+        direction: Direction,
+    },
+    /// The current session deviates from the baseline at `min_severity` or above.
+    Anomaly { min_severity: AnomalyLevel },
+    /// More than `max_pct` of the ephemeral port range is in use, or more
+    /// than `max_time_wait` sockets sit in TIME_WAIT when that is set.
+    SocketExhaustion {
+        max_pct: f64,
+        #[serde(default)]
+        max_time_wait: Option<u32>,
+    },
+    /// A flow reaches a country not recorded in any session before.
+    NewCountry,
+    /// A flow uses a remote port outside `COMMON_PORTS` and `allowed_ports`.
+    UnusualPort {
+        #[serde(default)]
+        allowed_ports: Vec<u16>,
+    },
+}
+
+impl Condition {
+    /// Whether the condition is evaluated against recorded history
+    /// (`evaluate_usage`) rather than each live frame.
+    pub fn uses_history(&self) -> bool {
+        matches!(self, Condition::Quota { .. } | Condition::Anomaly { .. })
+    }
+
+    /// Type name used to mute notifications, in the style of
+    /// `db::Anomaly::anomaly_type` (anomaly rules use the anomaly's own type).
+    pub fn finding_type(&self) -> &'static str {
+        match self {
+            Condition::ProcessRate { .. } => "PROCESS_RATE",
+            Condition::TotalRate { .. } => "TOTAL_RATE",
+            Condition::Latency { .. } => "LATENCY",
+            Condition::Geofence { .. } => "GEOFENCE",
+            Condition::Quota { .. } => "QUOTA",
+            Condition::Anomaly { .. } => "ANOMALY",
+            Condition::SocketExhaustion { .. } => "SOCKET_EXHAUSTION",
+            Condition::NewCountry => "NEW_COUNTRY",
+            Condition::UnusualPort { .. } => "UNUSUAL_PORT",
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_cooldown() -> u64 {
+    DEFAULT_COOLDOWN_SECS
+}
+
+/// What happens when a rule fires, besides recording it in `alert_events`
+/// and forwarding it to push targets.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AlertAction {
+    /// `alert-fired` event to the frontend.
+    Event,
+    /// OS desktop notification.
+    Notification,
+    /// POST the event as JSON (or the rendered payload template) to `url`.
+    Webhook { url: String },
+}
+
+fn default_actions() -> Vec<AlertAction> {
+    vec![AlertAction::Event]
+}
+
+/// A user-defined alert rule.  An empty `id` on create is assigned by the backend.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRule {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub severity: Severity,
+    pub condition: Condition,
+    /// Minimum seconds between two alerts for the same rule and subject.
+    #[serde(default = "default_cooldown")]
+    pub cooldown_secs: u64,
+    #[serde(default = "default_actions")]
+    pub actions: Vec<AlertAction>,
+}
+
+impl AlertRule {
+    /// Reject rules that could never fire or would fire on everything.
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        if self.name.trim().is_empty() {
+            return Err(AbyssError::InvalidInput("Alert rule name must not be empty".into()));
+        }
+        let positive = |v: f64, what: &str| {
+            if v.is_finite() && v > 0.0 {
+                Ok(())
+            } else {
+                Err(AbyssError::InvalidInput(format!("{what} must be greater than 0")))
+            }
+        };
+        for action in &self.actions {
+            if let AlertAction::Webhook { url } = action {
+                if !(url.starts_with("https://") || url.starts_with("http://")) {
+                    return Err(AbyssError::InvalidInput(format!("Webhook '{url}' must be an http(s) URL")));
+                }
+            }
+        }
+        match &self.condition {
+            Condition::ProcessRate { process, max_bps } => {
+                if process.trim().is_empty() {
+                    return Err(AbyssError::InvalidInput("Process name must not be empty".into()));
+                }
+                positive(*max_bps, "maxBps")
+            }
+            Condition::TotalRate { max_bps, .. } => positive(*max_bps, "maxBps"),
+            Condition::Latency { max_ms } => positive(*max_ms, "maxMs"),
+            Condition::Quota { max_bytes, .. } => positive(*max_bytes, "maxBytes"),
+            Condition::Geofence { countries, .. } => {
+                if countries.iter().all(|c| c.trim().is_empty()) {
+                    return Err(AbyssError::InvalidInput("Geofence needs at least one country".into()));
+                }
+                Ok(())
+            }
+            Condition::Anomaly { .. } => Ok(()),
+            Condition::NewCountry | Condition::UnusualPort { .. } => Ok(()),
+            Condition::SocketExhaustion { max_pct, max_time_wait } => {
+                if !(max_pct.is_finite() && *max_pct > 0.0 && *max_pct <= 100.0) {
+                    return Err(AbyssError::InvalidInput("maxPct must be between 0 and 100".into()));
+                }
+                if *max_time_wait == Some(0) {
+                    return Err(AbyssError::InvalidInput("maxTimeWait must be greater than 0".into()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// ─── Alert events ───────────────────────────────────────────────────────────
+
+/// Live metrics at the moment a frame rule fired.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertFrame {
+    pub t: f64,
+    pub bps: f64,
+    pub upload_bps: f64,
+    pub download_bps: f64,
+    pub latency_ms: f64,
+    pub active_flows: u32,
+}
+
+impl AlertFrame {
+    pub fn of(frame: &TelemetryFrame) -> Self {
+        Self {
+            t: frame.t,
+            bps: frame.net.bps,
+            upload_bps: frame.net.upload_bps,
+            download_bps: frame.net.download_bps,
+            latency_ms: frame.net.latency_ms,
+            active_flows: frame.net.active_flows,
+        }
+    }
+}
+
+/// What the rule saw when it fired, stored as JSON alongside the event.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlertContext {
+    /// Absent for history rules (quota, anomaly).
+    pub frame: Option<AlertFrame>,
+    /// `GeoFlow::id`s that matched the condition.
+    pub flow_ids: Vec<String>,
+    /// Process or destination country the rule matched, for rules about one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// Rate (bits/s) that tripped a throughput rule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bps: Option<f64>,
+}
+
+/// One firing of a rule, persisted to `alert_events` and routed to `notify`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertEvent {
+    pub id: String,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub severity: Severity,
+    /// What tripped the rule: a process, country, quota period or anomaly type.
+    pub subject: String,
+    pub message: String,
+    pub value: f64,
+    pub threshold: f64,
+    pub session_id: Option<String>,
+    pub triggered_at: String,
+    /// New breaches of this rule swallowed by its cooldown since it last fired.
+    pub suppressed: u32,
+    pub context: AlertContext,
+}
+
+/// A single subject breaching a rule during one evaluation.
+#[derive(Default)]
+struct Breach {
+    subject: String,
+    message: String,
+    value: f64,
+    threshold: f64,
+    flow_ids: Vec<String>,
+    process: Option<String>,
+    country: Option<String>,
+    bps: Option<f64>,
+}
+
+/// Recorded usage consulted by quota and anomaly rules.
+#[derive(Clone, Debug, Default)]
+pub struct UsageSnapshot {
+    /// (bytes up, bytes down) since the start of the current day/week/month.
+    pub day: (f64, f64),
+    pub week: (f64, f64),
+    pub month: (f64, f64),
+    pub anomalies: Vec<db::Anomaly>,
+}
+
+/// Read period totals and the current session's anomalies.
+pub fn load_usage(conn: &Connection, session_id: Option<&str>) -> rusqlite::Result<UsageSnapshot> {
+    let today = Local::now().date_naive();
+    let since = |period: QuotaPeriod| db::bytes_since(conn, &local_midnight(period.start(today)));
+    Ok(UsageSnapshot {
+        day: since(QuotaPeriod::Day)?,
+        week: since(QuotaPeriod::Week)?,
+        month: since(QuotaPeriod::Month)?,
+        anomalies: match session_id {
+            Some(id) => db::detect_anomalies(conn, id)?,
+            None => Vec::new(),
+        },
+    })
+}
+
+/// Local midnight of `date` as an RFC 3339 UTC timestamp.
+pub fn local_midnight(date: NaiveDate) -> String {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|dt| dt.and_local_timezone(Local).earliest())
+        .map(|dt| dt.with_timezone(&Utc).to_rfc3339())
+        .unwrap_or_else(|| format!("{date}T00:00:00Z"))
+}
+
+fn directional((up, down): (f64, f64), direction: Direction) -> f64 {
+    match direction {
+        Direction::Up => up,
+        Direction::Down => down,
+        Direction::Total => up + down,
+    }
+}
+
+// ─── Engine ─────────────────────────────────────────────────────────────────
+
+/// Evaluates rules and decides which breaches become alerts.  A subject only
+/// alerts when it starts breaching (not on every tick while it stays over),
+/// and never twice within the rule's cooldown.
+#[derive(Default)]
+pub struct RuleEngine {
+    /// (rule id, subject) pairs breaching as of the last evaluation.
+    active: HashSet<(String, String)>,
+    last_fired: HashMap<(String, String), Instant>,
+    /// Per rule: breaches suppressed by the cooldown since its last alert.
+    suppressed: HashMap<String, u32>,
+    /// Countries seen so far, for `NewCountry`; loaded from the database
+    /// on first use (see `needs_known_countries`).
+    known_countries: Option<HashSet<String>>,
+}
+
+impl RuleEngine {
+    /// Evaluate live-frame conditions (rates, latency, geofence).
+    pub fn evaluate_frame(
+        &mut self,
+        rules: &[AlertRule],
+        frame: &TelemetryFrame,
+        session_id: Option<&str>,
+    ) -> Vec<AlertEvent> {
+        let snapshot = AlertFrame::of(frame);
+        let mut fired = Vec::new();
+        for rule in rules.iter().filter(|r| r.enabled && !r.condition.uses_history()) {
+            let breaches = frame_breaches(&rule.condition, frame, self.known_countries.as_ref());
+            self.settle(rule, breaches, Some(snapshot), session_id, &mut fired);
+        }
+        if let Some(known) = self.known_countries.as_mut() {
+            known.extend(
+                frame
+                    .flows
+                    .iter()
+                    .map(|f| f.dst.country.as_str())
+                    .filter(|c| !c.is_empty() && *c != "??")
+                    .map(str::to_string),
+            );
+        }
+        self.prune(rules);
+        fired
+    }
+
+    /// Whether an enabled `NewCountry` rule is waiting for `set_known_countries`.
+    pub fn needs_known_countries(&self, rules: &[AlertRule]) -> bool {
+        self.known_countries.is_none()
+            && rules.iter().any(|r| r.enabled && matches!(r.condition, Condition::NewCountry))
+    }
+
+    /// Countries already recorded (`db::known_countries`).  With none yet
+    /// (a fresh install), the first frames only fill the set rather than
+    /// alerting on every country.
+    pub fn set_known_countries(&mut self, countries: HashSet<String>) {
+        self.known_countries = Some(countries);
+    }
+
+    /// Evaluate history conditions (quotas, anomalies).
+    pub fn evaluate_usage(
+        &mut self,
+        rules: &[AlertRule],
+        usage: &UsageSnapshot,
+        session_id: Option<&str>,
+    ) -> Vec<AlertEvent> {
+        let mut fired = Vec::new();
+        for rule in rules.iter().filter(|r| r.enabled && r.condition.uses_history()) {
+            let breaches = usage_breaches(&rule.condition, usage);
+            self.settle(rule, breaches, None, session_id, &mut fired);
+        }
+        fired
+    }
+
+    fn settle(
+        &mut self,
+        rule: &AlertRule,
+        breaches: Vec<Breach>,
+        frame: Option<AlertFrame>,
+        session_id: Option<&str>,
+        fired: &mut Vec<AlertEvent>,
+    ) {
+        let cooldown = Duration::from_secs(rule.cooldown_secs);
+        let current: HashSet<String> = breaches.iter().map(|b| b.subject.clone()).collect();
+        self.active
+            .retain(|(id, subject)| id != &rule.id || current.contains(subject));
+
+        for breach in breaches {
+            let key = (rule.id.clone(), breach.subject.clone());
+            if !self.active.insert(key.clone()) {
+                continue;
+            }
+            if self.last_fired.get(&key).is_some_and(|at| at.elapsed() < cooldown) {
+                *self.suppressed.entry(rule.id.clone()).or_default() += 1;
+                continue;
+            }
+            self.last_fired.insert(key, Instant::now());
+            fired.push(AlertEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                rule_id: rule.id.clone(),
+                rule_name: rule.name.clone(),
+                severity: rule.severity,
+                subject: breach.subject,
+                message: breach.message,
+                value: breach.value,
+                threshold: breach.threshold,
+                session_id: session_id.map(str::to_string),
+                triggered_at: Utc::now().to_rfc3339(),
+                suppressed: self.suppressed.remove(&rule.id).unwrap_or(0),
+                context: AlertContext {
+                    frame,
+                    flow_ids: breach.flow_ids,
+                    process: breach.process,
+                    country: breach.country,
+                    bps: breach.bps,
+                },
+            });
+        }
+    }
+
+    /// Forget state for deleted rules and cooldowns that have lapsed.
+    fn prune(&mut self, rules: &[AlertRule]) {
+        let cooldowns: HashMap<&str, Duration> = rules
+            .iter()
+            .map(|r| (r.id.as_str(), Duration::from_secs(r.cooldown_secs)))
+            .collect();
+        self.active.retain(|(id, _)| cooldowns.contains_key(id.as_str()));
+        self.suppressed.retain(|id, _| cooldowns.contains_key(id.as_str()));
+        self.last_fired.retain(|(id, _), at| {
+            cooldowns
+                .get(id.as_str())
+                .is_some_and(|cooldown| at.elapsed() < *cooldown)
+        });
+    }
+}
+
+fn frame_breaches(
+    condition: &Condition,
+    frame: &TelemetryFrame,
+    known_countries: Option<&HashSet<String>>,
+) -> Vec<Breach> {
+    match condition {
+        Condition::ProcessRate { process, max_bps } => {
+            let matched: Vec<_> = frame
+                .flows
+                .iter()
+                .filter(|f| f.process.as_deref().is_some_and(|p| p.eq_ignore_ascii_case(process)))
+                .collect();
+            let total: f64 = matched.iter().map(|f| f.bps).sum();
+            if total > *max_bps {
+                vec![Breach {
+                    subject: process.clone(),
+                    message: format!("{process} is using {}", format_bps(total)),
+                    value: total,
+                    threshold: *max_bps,
+                    flow_ids: matched.iter().map(|f| f.id.clone()).collect(),
+                    process: Some(process.clone()),
+                    bps: Some(total),
+                    ..Default::default()
+                }]
+            } else {
+                Vec::new()
+            }
+        }
+        Condition::TotalRate { max_bps, direction } => {
+            let rate = directional((frame.net.upload_bps, frame.net.download_bps), *direction);
+            if rate > *max_bps {
+                vec![Breach {
+                    subject: format!("{direction:?}").to_lowercase(),
+                    message: format!("Throughput reached {}", format_bps(rate)),
+                    value: rate,
+                    threshold: *max_bps,
+                    bps: Some(rate),
+                    ..Default::default()
+                }]
+            } else {
+                Vec::new()
+            }
+        }
+        Condition::Latency { max_ms } => {
+            if frame.net.latency_ms > *max_ms {
+                vec![Breach {
+                    subject: "latency".into(),
+                    message: format!("Latency reached {:.0} ms", frame.net.latency_ms),
+                    value: frame.net.latency_ms,
+                    threshold: *max_ms,
+                    ..Default::default()
+                }]
+            } else {
+                Vec::new()
+            }
+        }
+        Condition::Geofence { mode, countries } => {
+            let mut seen: HashMap<&str, Vec<String>> = HashMap::new();
+            for flow in &frame.flows {
+                let country = flow.dst.country.as_str();
+                if country.is_empty() || country == "??" {
+                    continue;
+                }
+                let listed = countries.iter().any(|c| c.eq_ignore_ascii_case(country));
+                let breaching = match mode {
+                    GeofenceMode::Allow => !listed,
+                    GeofenceMode::Deny => listed,
+                };
+                if breaching {
+                    seen.entry(country).or_default().push(flow.id.clone());
+                }
+            }
+            seen.into_iter()
+                .map(|(country, flow_ids)| Breach {
+                    subject: country.to_string(),
+                    message: format!("{} flow(s) to {country}", flow_ids.len()),
+                    value: flow_ids.len() as f64,
+                    threshold: 0.0,
+                    flow_ids,
+                    country: Some(country.to_string()),
+                    ..Default::default()
+                })
+                .collect()
+        }
+        Condition::NewCountry => {
+            let Some(known) = known_countries.filter(|k| !k.is_empty()) else {
+                return Vec::new();
+            };
+            let mut seen: HashMap<&str, Vec<String>> = HashMap::new();
+            for flow in &frame.flows {
+                let country = flow.dst.country.as_str();
+                if !country.is_empty() && country != "??" && !known.contains(country) {
+                    seen.entry(country).or_default().push(flow.id.clone());
+                }
+            }
+            seen.into_iter()
+                .map(|(country, flow_ids)| Breach {
+                    subject: country.to_string(),
+                    message: format!("First connection to {country}"),
+                    value: flow_ids.len() as f64,
+                    threshold: 0.0,
+                    flow_ids,
+                    country: Some(country.to_string()),
+                    ..Default::default()
+                })
+                .collect()
+        }
+        Condition::UnusualPort { allowed_ports } => {
+            let mut seen: HashMap<u16, Vec<&GeoFlow>> = HashMap::new();
+            for flow in &frame.flows {
+                if !COMMON_PORTS.contains(&flow.port) && !allowed_ports.contains(&flow.port) {
+                    seen.entry(flow.port).or_default().push(flow);
+                }
+            }
+            seen.into_iter()
+                .map(|(port, flows)| {
+                    let process = flows.iter().find_map(|f| f.process.clone());
+                    Breach {
+                        subject: port.to_string(),
+                        message: match &process {
+                            Some(process) => format!("{process} connected on unusual port {port}"),
+                            None => format!("{} flow(s) on unusual port {port}", flows.len()),
+                        },
+                        value: port as f64,
+                        threshold: 0.0,
+                        flow_ids: flows.iter().map(|f| f.id.clone()).collect(),
+                        process,
+                        ..Default::default()
+                    }
+                })
+                .collect()
+        }
+        Condition::SocketExhaustion { max_pct, max_time_wait } => {
+            let Some(usage) = frame.sockets else {
+                return Vec::new();
+            };
+            let mut breaches = Vec::new();
+            let pct = usage.ephemeral_pct();
+            if pct > *max_pct {
+                breaches.push(Breach {
+                    subject: "ephemeral".into(),
+                    message: format!(
+                        "{} of {} ephemeral ports in use ({pct:.0}%), {} in TIME_WAIT",
+                        usage.ephemeral_in_use, usage.ephemeral_total, usage.time_wait
+                    ),
+                    value: pct,
+                    threshold: *max_pct,
+                    ..Default::default()
+                });
+            }
+            if let Some(max) = max_time_wait.filter(|max| usage.time_wait > *max) {
+                breaches.push(Breach {
+                    subject: "timeWait".into(),
+                    message: format!("{} sockets in TIME_WAIT", usage.time_wait),
+                    value: usage.time_wait as f64,
+                    threshold: max as f64,
+                    ..Default::default()
+                });
+            }
+            breaches
+        }
+        Condition::Quota { .. } | Condition::Anomaly { .. } => Vec::new(),
+    }
+}
+
+fn usage_breaches(condition: &Condition, usage: &UsageSnapshot) -> Vec<Breach> {
+    match condition {
+        Condition::Quota { period, max_bytes, direction } => {
+            let (totals, label) = match period {
+                QuotaPeriod::Day => (usage.day, "today"),
+                QuotaPeriod::Week => (usage.week, "this week"),
+                QuotaPeriod::Month => (usage.month, "this month"),
+            };
+            let used = directional(totals, *direction);
+            if used > *max_bytes {
+                vec![Breach {
+                    subject: format!("{period:?}").to_lowercase(),
+                    message: format!(
+                        "Used {:.1} MB {label} (quota {:.1} MB)",
+                        used / 1_048_576.0,
+                        max_bytes / 1_048_576.0
+                    ),
+                    value: used,
+                    threshold: *max_bytes,
+                    ..Default::default()
+                }]
+            } else {
+                Vec::new()
+            }
+        }
+        Condition::Anomaly { min_severity } => usage
+            .anomalies
+            .iter()
+            .filter(|a| AnomalyLevel::parse(&a.severity).is_some_and(|level| level >= *min_severity))
+            .map(|a| Breach {
+                subject: a.anomaly_type.clone(),
+                message: a.message.clone(),
+                value: a.current_value,
+                threshold: a.baseline_avg,
+                ..Default::default()
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A bits/s rate in the user's preferred units (see `units`).
+pub fn format_bps(bps: f64) -> String {
+    units::current().rate(bps)
+}
@@ -0,0 +1,212 @@
+use crate::alerts::{AlertContext, AlertEvent};
+use crate::db::{DestinationRecord, FlowSnapshotRecord};
+use crate::error::AbyssError;
+use crate::exclusions::{hex, unhex, write_private};
+use crate::lifecycle::flow_id_ip;
+use crate::settings::Settings;
+use crate::writer::truncate_ip;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+
+/// Prefix of hashed addresses, so they aren't hashed again.
+const HASH_PREFIX: &str = "anon-";
+
+/// Bytes of the HMAC kept in a hashed address (12 hex digits).
+const HASH_BYTES: usize = 6;
+
+/// Stands in for process names in anonymized alerts.
+const REDACTED: &str = "redacted";
+
+// ─── Anonymizer ─────────────────────────────────────────────────────────────
+
+/// How `Settings::redact_at_rest` hides addresses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnonymizeMode {
+    /// Keep the /24 (IPv4) or /48 (IPv6) network: country and ISP level
+    /// analysis still works.
+    #[default]
+    Truncate,
+    /// Replace each address with a keyed hash (`anon-3f9c…`): hosts stay
+    /// distinguishable across sessions without revealing the address.
+    Hash,
+}
+
+/// Rewrites addresses for storage and exports; live telemetry is untouched.
+#[derive(Clone, Debug, Default)]
+pub struct Anonymizer {
+    mode: AnonymizeMode,
+    /// HMAC key for `Hash`; without one addresses become "redacted".
+    key: Option<AnonymizeKey>,
+}
+
+impl Anonymizer {
+    pub fn new(mode: AnonymizeMode, key: Option<AnonymizeKey>) -> Self {
+        Self { mode, key }
+    }
+
+    /// The anonymizer `settings` select, creating the hashing key beside
+    /// `db_path` the first time `Hash` is used.
+    pub fn from_settings(settings: &Settings, db_path: &Path) -> Result<Self, AbyssError> {
+        let key = match settings.anonymize_mode {
+            AnonymizeMode::Truncate => None,
+            AnonymizeMode::Hash => Some(AnonymizeKey::load_or_create(db_path)?),
+        };
+        Ok(Self::new(settings.anonymize_mode, key))
+    }
+
+    /// The anonymizer exports go through, if `redact_at_rest` is on.
+    pub fn for_exports(settings: &Settings, db_path: &Path) -> Result<Option<Self>, AbyssError> {
+        settings
+            .redact_at_rest
+            .then(|| Self::from_settings(settings, db_path))
+            .transpose()
+    }
+
+    /// Anonymize an address (or a hostname standing in for one).  Already
+    /// anonymized values are returned unchanged.
+    pub fn ip(&self, ip: &str) -> String {
+        match self.mode {
+            AnonymizeMode::Truncate => truncate_ip(ip),
+            AnonymizeMode::Hash if ip.starts_with(HASH_PREFIX) => ip.to_string(),
+            AnonymizeMode::Hash => {
+                let Some(Ok(mut mac)) = self.key.as_ref().map(|key| Hmac::<Sha256>::new_from_slice(&key.0)) else {
+                    return REDACTED.to_string();
+                };
+                mac.update(ip.as_bytes());
+                let digest = mac.finalize().into_bytes();
+                let hex: String = digest[..HASH_BYTES].iter().map(|b| format!("{b:02x}")).collect();
+                format!("{HASH_PREFIX}{hex}")
+            }
+        }
+    }
+
+    /// `flow_id` with `ip` in it replaced by its anonymized form.
+    pub fn flow_id(&self, flow_id: &str, ip: &str) -> String {
+        flow_id.replacen(ip, &self.ip(ip), 1)
+    }
+
+    pub fn flow(&self, flow: &mut FlowSnapshotRecord) {
+        flow.flow_id = self.flow_id(&flow.flow_id, &flow.dst_ip);
+        flow.dst_ip = self.ip(&flow.dst_ip);
+        flow.src_ip = flow.src_ip.as_deref().map(|ip| self.ip(ip));
+    }
+
+    pub fn destination(&self, destination: &mut DestinationRecord) {
+        destination.ip = self.ip(&destination.ip);
+    }
+
+    pub fn alert(&self, event: &mut AlertEvent) {
+        scrub_alert(&mut event.subject, &mut event.message, &mut event.context, &|ip| self.ip(ip));
+    }
+}
+
+/// Replace the addresses in an alert's flow ids with `anonymize(ip)` and
+/// the process it names, in its subject and message too.  Returns the
+/// addresses replaced.
+pub fn scrub_alert(
+    subject: &mut String,
+    message: &mut String,
+    context: &mut AlertContext,
+    anonymize: &dyn Fn(&str) -> String,
+) -> Vec<String> {
+    let mut replaced = Vec::new();
+    for flow_id in &mut context.flow_ids {
+        let Some(ip) = flow_id_ip(flow_id).map(str::to_string) else {
+            continue;
+        };
+        let anonymized = anonymize(&ip);
+        if anonymized != ip {
+            *flow_id = flow_id.replacen(&ip, &anonymized, 1);
+            replaced.push(ip);
+        }
+    }
+    if let Some(process) = context.process.take().filter(|p| !p.is_empty()) {
+        *subject = subject.replace(&process, REDACTED);
+        *message = message.replace(&process, REDACTED);
+    }
+    replaced
+}
+
+// ─── Key ────────────────────────────────────────────────────────────────────
+
+/// HMAC key for `AnonymizeMode::Hash`, kept in `sessions.db.anonkey` next
+/// to the database rather than in it: with only 2^32 IPv4 addresses, a key
+/// stored alongside the hashes would let anyone holding the file reverse
+/// them.  Readable by the owner only.
+#[derive(Clone)]
+pub struct AnonymizeKey([u8; 32]);
+
+impl std::fmt::Debug for AnonymizeKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AnonymizeKey(..)")
+    }
+}
+
+impl AnonymizeKey {
+    pub fn generate() -> Self {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// The key beside `db_path`, if hashing was ever selected for it.
+    pub fn load(db_path: &Path) -> Result<Option<Self>, AbyssError> {
+        let path = key_path(db_path);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => unhex(text.trim())
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .map(|bytes| Some(Self(bytes)))
+                .ok_or_else(|| AbyssError::Internal(format!("Corrupt key file {}", path.display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AbyssError::from(e).context("Failed to read anonymization key")),
+        }
+    }
+
+    /// Generated once and kept, so hashes stay comparable across sessions.
+    pub fn load_or_create(db_path: &Path) -> Result<Self, AbyssError> {
+        if let Some(key) = Self::load(db_path)? {
+            return Ok(key);
+        }
+        let key = Self::generate();
+        write_private(&key_path(db_path), hex(&key.0).as_bytes())
+            .map_err(|e| AbyssError::from(e).context("Failed to create anonymization key"))?;
+        Ok(key)
+    }
+}
+
+fn key_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".anonkey");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_are_keyed_stable_and_idempotent() {
+        let a = Anonymizer::new(AnonymizeMode::Hash, Some(AnonymizeKey::generate()));
+        let b = Anonymizer::new(AnonymizeMode::Hash, Some(AnonymizeKey::generate()));
+        let hashed = a.ip("142.250.72.14");
+        assert!(hashed.starts_with(HASH_PREFIX) && hashed.len() == HASH_PREFIX.len() + 2 * HASH_BYTES);
+        assert_eq!(a.ip("142.250.72.14"), hashed);
+        assert_ne!(a.ip("142.250.72.15"), hashed);
+        assert_ne!(b.ip("142.250.72.14"), hashed);
+        assert_eq!(a.ip(&hashed), hashed);
+        assert_eq!(
+            a.flow_id("tcp:10.0.0.2:50000-142.250.72.14:443", "142.250.72.14"),
+            format!("tcp:10.0.0.2:50000-{hashed}:443")
+        );
+        assert_eq!(Anonymizer::new(AnonymizeMode::Hash, None).ip("142.250.72.14"), REDACTED);
+
+        let truncate = Anonymizer::default();
+        assert_eq!(truncate.ip("142.250.72.14"), "142.250.72.0");
+        assert_eq!(truncate.ip("142.250.72.0"), "142.250.72.0");
+    }
+}
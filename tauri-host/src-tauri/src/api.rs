@@ -0,0 +1,360 @@
+use crate::db;
+use crate::error::AbyssError;
+use crate::locks::LockExt;
+use crate::server::constant_time_eq;
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Largest request head accepted (requests have no body).
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// Clients must send the whole request within this long.
+const REQUEST_TIMEOUT_SECS: u64 = 5;
+
+// ─── Server ─────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiServerStatus {
+    pub running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// `http://127.0.0.1:<port>/v1`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Send as `Authorization: Bearer <token>`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+struct Running {
+    addr: SocketAddr,
+    token: String,
+    accept: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// Opt-in read-only HTTP API over the recorded history, listening on
+/// loopback only.  Every request needs `Authorization: Bearer <token>`;
+/// responses are JSON, errors serialized like command errors.
+///
+/// Routes (all `GET`, query parameters camelCase like the commands):
+///
+/// - `/v1/sessions?limit&offset&q`
+/// - `/v1/sessions/{id}`
+/// - `/v1/sessions/{id}/frames?startT&endT&maxPoints`
+/// - `/v1/sessions/{id}/flows?process&country&limit`
+/// - `/v1/sessions/{id}/destinations?sortBy&limit`
+/// - `/v1/sessions/{id}/processes?process&limit`
+/// - `/v1/stats`
+/// - `/v1/usage/daily?rangeDays`
+/// - `/v1/usage/hourly?rangeDays`
+/// - `/v1/top/destinations?rangeDays&limit`
+/// - `/v1/top/apps?rangeDays&limit&byGroup`
+/// - `/v1/breakdown/countries?rangeDays&byProcess`
+/// - `/v1/breakdown/orgs?rangeDays&limit`
+pub struct ApiServer {
+    running: Mutex<Option<Running>>,
+}
+
+impl ApiServer {
+    pub fn new() -> Self {
+        Self {
+            running: Mutex::new(None),
+        }
+    }
+
+    /// (Re)start listening on `port` (0 picks a free one).
+    pub fn start(&self, port: u16, token: String, db_path: PathBuf) -> Result<ApiServerStatus, AbyssError> {
+        self.stop();
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+            .map_err(|e| AbyssError::Io(format!("Could not listen on port {port}: {e}")))?;
+        let addr = listener.local_addr()?;
+
+        let accept = tauri::async_runtime::spawn(accept_loop(listener, token.as_str().into(), db_path.into()));
+        println!("[Abyss] REST API listening on http://{addr}/v1");
+        *self.running.lock_or_recover("api_server") = Some(Running { addr, token, accept });
+        Ok(self.status())
+    }
+
+    pub fn stop(&self) {
+        if let Some(running) = self.running.lock_or_recover("api_server").take() {
+            running.accept.abort();
+            println!("[Abyss] REST API on {} stopped", running.addr);
+        }
+    }
+
+    pub fn status(&self) -> ApiServerStatus {
+        let running = self.running.lock_or_recover("api_server");
+        ApiServerStatus {
+            running: running.is_some(),
+            address: running.as_ref().map(|r| r.addr.to_string()),
+            base_url: running.as_ref().map(|r| format!("http://{}/v1", r.addr)),
+            token: running.as_ref().map(|r| r.token.clone()),
+        }
+    }
+}
+
+async fn accept_loop(listener: std::net::TcpListener, token: Arc<str>, db_path: Arc<Path>) {
+    let listener = match tokio::net::TcpListener::from_std(listener) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("[Abyss] REST API failed to start: {e}");
+            return;
+        }
+    };
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("[Abyss] REST API accept failed: {e}");
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                continue;
+            }
+        };
+        let token = token.clone();
+        let db_path = db_path.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_request(stream, &token, db_path).await {
+                eprintln!("[Abyss] REST API client {peer}: {e}");
+            }
+        });
+    }
+}
+
+/// One request per connection; the response closes it.
+async fn serve_request(mut stream: TcpStream, token: &str, db_path: Arc<Path>) -> std::io::Result<()> {
+    let head = tokio::time::timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS), read_head(&mut stream))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"))??;
+    let Some(head) = head else {
+        return respond(&mut stream, 431, r#"{"kind":"invalidInput","message":"Request too large","retryable":false}"#)
+            .await;
+    };
+
+    let request = String::from_utf8_lossy(&head);
+    let mut parts = request.split("\r\n").next().unwrap_or_default().split(' ');
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    let bearer = request.split("\r\n").skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case("Authorization").then(|| value.trim().strip_prefix("Bearer "))?
+    });
+
+    let result = if !bearer.is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes())) {
+        Err((401, AbyssError::InvalidInput("Missing or invalid bearer token".into())))
+    } else if method != "GET" {
+        Err((405, AbyssError::InvalidInput(format!("Method {method} not allowed"))))
+    } else {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let (path, query) = (path.to_string(), Query::from_raw(query));
+        match tokio::task::spawn_blocking(move || route(&db_path, &path, &query)).await {
+            Ok(result) => result.map_err(|e| (status_of(&e), e)),
+            Err(e) => Err((500, AbyssError::from(e))),
+        }
+    };
+
+    match result {
+        Ok(body) => respond(&mut stream, 200, &body).await,
+        Err((status, error)) => {
+            let body = serde_json::to_string(&error).unwrap_or_default();
+            respond(&mut stream, status, &body).await
+        }
+    }
+}
+
+/// Read up to the blank line ending the request head.  `None` if it's too large.
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<Vec<u8>>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Ok(None);
+        }
+    }
+    Ok(Some(buf))
+}
+
+async fn respond(stream: &mut TcpStream, status: u16, body: &str) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let head = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn status_of(error: &AbyssError) -> u16 {
+    match error {
+        AbyssError::NotFound(_) => 404,
+        AbyssError::InvalidInput(_) => 400,
+        AbyssError::DatabaseLocked(_) => 503,
+        _ => 500,
+    }
+}
+
+// ─── Routes ─────────────────────────────────────────────────────────────────
+
+/// Run the query for `path` and serialize its result.
+fn route(db_path: &Path, path: &str, query: &Query) -> Result<String, AbyssError> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let conn = db::open_database(db_path)?;
+    match segments.as_slice() {
+        ["v1", "sessions"] => match query.get("q") {
+            Some(q) => json(&db::search_sessions(&conn, q, query.parse("limit")?.unwrap_or(50))?),
+            None => json(&db::list_sessions(
+                &conn,
+                query.parse("limit")?.unwrap_or(50),
+                query.parse("offset")?.unwrap_or(0),
+            )?),
+        },
+        ["v1", "sessions", id] => {
+            let id = percent_decode(id);
+            match db::get_session(&conn, &id)? {
+                Some(session) => json(&session),
+                None => Err(AbyssError::NotFound(format!("Session '{id}' not found"))),
+            }
+        }
+        ["v1", "sessions", id, resource] => {
+            let id = percent_decode(id);
+            if db::get_session(&conn, &id)?.is_none() {
+                return Err(AbyssError::NotFound(format!("Session '{id}' not found")));
+            }
+            match *resource {
+                "frames" => json(&db::get_session_frames(
+                    &conn,
+                    &id,
+                    query.parse("startT")?,
+                    query.parse("endT")?,
+                    query.parse("maxPoints")?,
+                )?),
+                "flows" => json(&db::get_session_flows(
+                    &conn,
+                    &id,
+                    query.get("process"),
+                    query.get("country"),
+                    query.parse("limit")?.unwrap_or(100),
+                )?),
+                "destinations" => json(&db::get_session_destinations(
+                    &conn,
+                    &id,
+                    query.get("sortBy").unwrap_or("bytes"),
+                    query.parse("limit")?.unwrap_or(50),
+                )?),
+                "processes" => json(&db::get_process_usage(
+                    &conn,
+                    &id,
+                    query.get("process"),
+                    query.parse("limit")?.unwrap_or(500),
+                )?),
+                _ => Err(not_found(path)),
+            }
+        }
+        ["v1", "stats"] => json(&db::get_global_stats(&conn, db_path)?),
+        ["v1", "usage", "daily"] => json(&db::get_daily_usage(&conn, query.parse("rangeDays")?.unwrap_or(30))?),
+        ["v1", "usage", "hourly"] => json(&db::get_hourly_usage(&conn, query.parse("rangeDays")?.unwrap_or(30))?),
+        ["v1", "top", "destinations"] => json(&db::get_top_destinations(
+            &conn,
+            query.parse("rangeDays")?.unwrap_or(30),
+            query.parse("limit")?.unwrap_or(20),
+        )?),
+        ["v1", "top", "apps"] => json(&db::get_top_apps(
+            &conn,
+            query.parse("rangeDays")?.unwrap_or(30),
+            query.parse("limit")?.unwrap_or(20),
+            query.parse("byGroup")?.unwrap_or(false),
+        )?),
+        ["v1", "breakdown", "countries"] => json(&db::get_country_breakdown(
+            &conn,
+            query.parse("rangeDays")?.unwrap_or(30),
+            query.parse("byProcess")?.unwrap_or(false),
+        )?),
+        ["v1", "breakdown", "orgs"] => json(&db::get_org_breakdown(
+            &conn,
+            query.parse("rangeDays")?.unwrap_or(30),
+            query.parse("limit")?.unwrap_or(20),
+        )?),
+        _ => Err(not_found(path)),
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Result<String, AbyssError> {
+    serde_json::to_string(value).map_err(AbyssError::from)
+}
+
+fn not_found(path: &str) -> AbyssError {
+    AbyssError::NotFound(format!("No route for {path}"))
+}
+
+/// Decoded query string parameters.
+struct Query(Vec<(String, String)>);
+
+impl Query {
+    fn from_raw(raw: &str) -> Self {
+        Self(
+            raw.split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (percent_decode(key), percent_decode(value))
+                })
+                .collect(),
+        )
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, AbyssError> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| AbyssError::InvalidInput(format!("Invalid value for {name}: '{value}'")))
+            })
+            .transpose()
+    }
+}
+
+/// `%XX` escapes and `+` as space; invalid escapes are kept as-is.
+fn percent_decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => match raw.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    out.push(byte);
+                    i += 2;
+                }
+                None => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
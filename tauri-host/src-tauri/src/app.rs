@@ -0,0 +1,3580 @@
+//! The desktop app (the `gui` feature): Tauri state and commands, the
+//! monitor loop feeding the webview, and `run`.  `abyss-cli` is built
+//! without it.
+
+use crate::*;
+use tauri::{Emitter, Manager};
+use tauri_plugin_autostart::ManagerExt;
+
+/// Shared application state accessible by Tauri commands and the monitor loop.
+pub struct AppState {
+    /// Channel sender for dispatching write commands to the persistence thread.
+    pub writer_tx: writer::WriterSender,
+    /// The persistence thread; taken by `shutdown_writer`.
+    pub writer_thread: Mutex<Option<std::thread::JoinHandle<()>>>,
+    /// Writer backlog and flush counters (updated by the writer after each flush).
+    pub writer_stats: Mutex<writer::WriterStats>,
+    /// Path to the SQLite database file.
+    pub db_path: PathBuf,
+    /// Whether the writer owns the database or is waiting for another
+    /// instance to release it (updated by the writer thread).
+    pub db_lock: Mutex<dblock::DbLockStatus>,
+    /// Currently recording session ID (None if no active session).
+    pub current_session_id: Mutex<Option<String>>,
+    /// Last-known local geo position (set by monitor loop, read by manual starts).
+    pub local_geo: Mutex<LocalGeoCache>,
+    /// Focus filters applied to the live flow list (read by monitor loop each tick).
+    pub live_filters: Mutex<LiveFilters>,
+    /// Processes whose flows are geolocated and emitted (read by monitor loop each tick).
+    pub process_filter: Mutex<ProcessFilter>,
+    /// Per-window telemetry stream preferences, keyed by webview window label.
+    pub stream_subscriptions: Mutex<HashMap<String, StreamSubscription>>,
+    /// Frame schema version negotiated by each webview window, keyed by label.
+    pub schema_versions: Mutex<HashMap<String, u32>>,
+    /// Set by `cmd_request_keyframe`; the monitor loop emits a full frame next tick.
+    pub keyframe_requested: AtomicBool,
+    /// Set while the `Ask` startup prompt hasn't been answered.
+    pub startup_prompt: AtomicBool,
+    /// Launched by start-on-login (`--background`): the window starts
+    /// hidden and `Settings::record_on_login` applies.
+    pub launched_at_login: bool,
+    /// Live view only: frames are still emitted but nothing is persisted and
+    /// no session can be started.
+    pub monitor_only: AtomicBool,
+    /// Most recent full (material) frame, for hydrating new or reloaded windows.
+    pub last_frame: Mutex<Option<TelemetryFrame>>,
+    /// Geo lookup pipeline health (updated by monitor loop each tick).
+    pub geo_status: Mutex<GeoPipelineStatus>,
+    /// Current runtime settings.
+    pub settings: Mutex<settings::Settings>,
+    /// Publishes every committed settings change; the monitor loop
+    /// hot-reloads its tuning knobs from it.
+    pub settings_watch: tokio::sync::watch::Sender<settings::Settings>,
+    /// Optional packet capture backend (replaces socket polling when running).
+    pub capture: capture::Capture,
+    /// Local MaxMind databases consulted before the remote geo API.
+    pub geo_db: Arc<Mutex<geo::GeoDatabases>>,
+    /// Latest per-interface counters and rates (sampled by the monitor loop).
+    pub interfaces: Mutex<Vec<interfaces::InterfaceInfo>>,
+    /// Alert rules evaluated by the monitor loop (mirrors `alert_rules`).
+    pub alert_rules: Mutex<Vec<alerts::AlertRule>>,
+    /// Watched countries and ASNs (mirrors `watchlist`).
+    pub watchlist: Mutex<watchlist::Watchlist>,
+    /// Blocklists flows are checked against (rebuilt by `threat::watch`).
+    pub threats: Mutex<Arc<threat::ThreatIndex>>,
+    /// When monitoring was paused (RFC 3339), `None` while running.
+    pub paused_since: Mutex<Option<String>>,
+    /// Wakes the paused monitor loop on `cmd_resume_monitoring`.
+    pub monitor_resumed: tokio::sync::Notify,
+    /// Wakes `publicip::watch` early when the default route changes.
+    pub public_ip_recheck: tokio::sync::Notify,
+    /// Telemetry WebSocket server for external clients (off unless enabled).
+    pub ws_server: server::WsServer,
+    /// Loopback REST API over recorded history (off unless enabled).
+    pub api_server: api::ApiServer,
+}
+
+/// Emits this tick's `telemetry-frame`, focused by the live filters before
+/// the flow list is cut to `max_flows` (`frame` still holds every flow).  With
+/// no stream subscriptions or negotiated schemas this is a single broadcast;
+/// otherwise every webview window gets its own window-targeted event
+/// according to its subscription (default `Full`), on its negotiated
+/// `telemetry-frame-vN` channel.  Windows must listen window-scoped
+/// (`getCurrentWebviewWindow().listen`); a global listener also receives
+/// the events targeted at every other window.
+fn emit_telemetry(
+    app: &tauri::AppHandle,
+    frame: &TelemetryFrame,
+    max_flows: usize,
+    material: bool,
+    perf: &mut PerfStats,
+) {
+    let (subscriptions, versions, live_filters, process_filter) = app
+        .try_state::<AppState>()
+        .map(|state| {
+            (
+                state.stream_subscriptions.lock_or_recover("stream_subscriptions").clone(),
+                state.schema_versions.lock_or_recover("schema_versions").clone(),
+                state.live_filters.lock_or_recover("live_filters").clone(),
+                state.process_filter.lock_or_recover("process_filter").clone(),
+            )
+        })
+        .unwrap_or_default();
+    let full = frame;
+    let focused = focus_frame(full, &[&live_filters], &process_filter, max_flows);
+    let frame = &focused;
+
+    if subscriptions.is_empty() && versions.is_empty() {
+        if material {
+            // Compute payload size BEFORE emit to avoid double serialization
+            if cfg!(debug_assertions) {
+                perf.ws_payload_bytes += serde_json::to_vec(frame).map_or(0, |v| v.len());
+            }
+            let _ = app.emit("telemetry-frame", frame);
+        } else {
+            let heartbeat = heartbeat_of(frame);
+            if cfg!(debug_assertions) {
+                perf.ws_payload_bytes += serde_json::to_vec(&heartbeat).map_or(0, |v| v.len());
+            }
+            let _ = app.emit("telemetry-frame", &heartbeat);
+        }
+        return;
+    }
+
+    let heartbeat = heartbeat_of(frame);
+    for label in app.webview_windows().into_keys() {
+        let target = tauri::EventTarget::webview_window(label.as_str());
+        let filtered;
+        let payload = match subscriptions.get(&label).cloned().unwrap_or_default() {
+            StreamSubscription::Full if material => frame,
+            StreamSubscription::Filtered { filters } if material => {
+                filtered = focus_frame(full, &[&live_filters, &filters], &process_filter, max_flows);
+                &filtered
+            }
+            _ => &heartbeat,
+        };
+        let result = match versions.get(&label) {
+            Some(&version) => app.emit_to(target, &schema::event_name(version), schema::shape(payload, version)),
+            None => app.emit_to(target, schema::LEGACY_EVENT, payload),
+        };
+        if let Err(e) = result {
+            eprintln!("[Abyss] Failed to emit telemetry to window '{label}': {e}");
+        }
+    }
+}
+
+/// The desktop app's side of the monitor loop: reads `AppState` and emits
+/// to the webview, tray and WebSocket clients.
+struct AppHost {
+    app: tauri::AppHandle,
+    anomaly_notifier: Mutex<notify::AnomalyNotifier>,
+}
+
+impl AppHost {
+    fn new(app: tauri::AppHandle) -> Self {
+        Self {
+            app,
+            anomaly_notifier: Mutex::new(notify::AnomalyNotifier::default()),
+        }
+    }
+
+    fn state(&self) -> &AppState {
+        self.app.state::<AppState>().inner()
+    }
+}
+
+impl monitor::MonitorHost for AppHost {
+    fn settings(&self) -> settings::Settings {
+        self.state().settings.lock_or_recover("settings").clone()
+    }
+
+    fn subscribe_settings(&self) -> tokio::sync::watch::Receiver<settings::Settings> {
+        self.state().settings_watch.subscribe()
+    }
+
+    fn db_path(&self) -> PathBuf {
+        self.state().db_path.clone()
+    }
+
+    fn geo_db(&self) -> Arc<Mutex<geo::GeoDatabases>> {
+        self.state().geo_db.clone()
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.state().current_session_id.lock_or_recover("current_session_id").clone()
+    }
+
+    fn alert_rules(&self) -> Vec<alerts::AlertRule> {
+        self.state().alert_rules.lock_or_recover("alert_rules").clone()
+    }
+
+    fn watchlist(&self) -> watchlist::Watchlist {
+        self.state().watchlist.lock_or_recover("watchlist").clone()
+    }
+
+    fn threats(&self) -> Arc<threat::ThreatIndex> {
+        self.state().threats.lock_or_recover("threats").clone()
+    }
+
+    fn local_geo(&self) -> LocalGeo {
+        let cache = self.state().local_geo.lock_or_recover("local_geo");
+        LocalGeo {
+            lat: cache.lat,
+            lng: cache.lng,
+            city: cache.city.clone(),
+            country: cache.country.clone(),
+        }
+    }
+
+    // Cached for manual session starts
+    fn set_local_geo(&self, geo: &LocalGeo) {
+        let mut cache = self.state().local_geo.lock_or_recover("local_geo");
+        cache.city = geo.city.clone();
+        cache.country = geo.country.clone();
+        cache.lat = geo.lat;
+        cache.lng = geo.lng;
+    }
+
+    fn set_threats(&self, index: Arc<threat::ThreatIndex>) {
+        *self.state().threats.lock_or_recover("threats") = index;
+    }
+
+    fn public_ip_recheck(&self) -> &tokio::sync::Notify {
+        &self.state().public_ip_recheck
+    }
+
+    fn capture(&self) -> Option<&capture::Capture> {
+        Some(&self.state().capture)
+    }
+
+    fn process_filter(&self) -> ProcessFilter {
+        self.state().process_filter.lock_or_recover("process_filter").clone()
+    }
+
+    fn take_keyframe_request(&self) -> bool {
+        self.state().keyframe_requested.swap(false, Ordering::Relaxed)
+    }
+
+    fn geo_status(&self, status: GeoPipelineStatus) {
+        *self.state().geo_status.lock_or_recover("geo_status") = status;
+    }
+
+    fn interfaces(&self, sampled: Vec<interfaces::InterfaceInfo>) {
+        *self.state().interfaces.lock_or_recover("interfaces") = sampled;
+    }
+
+    /// Emitted as `flow-opened` / `flow-closed`.
+    fn flow_events(&self, events: &[lifecycle::FlowEvent]) {
+        for event in events {
+            let name = match event.kind {
+                lifecycle::FlowEventKind::Opened => "flow-opened",
+                lifecycle::FlowEventKind::Closed => "flow-closed",
+            };
+            let _ = self.app.emit(name, event);
+        }
+    }
+
+    fn mtu_finding(&self, finding: &mtu::MtuFinding) {
+        let _ = self.app.emit("mtu-finding", finding);
+    }
+
+    fn route_changed(&self, change: &routes::RouteChange) {
+        let _ = self.app.emit("route-changed", change);
+    }
+
+    fn network_changed(&self, change: &publicip::NetworkChange) {
+        let _ = self.app.emit("network-changed", change);
+    }
+
+    fn emit_frame(&self, frame: &TelemetryFrame, max_flows: usize, material: bool, perf: &mut PerfStats) {
+        emit_telemetry(&self.app, frame, max_flows, material, perf);
+    }
+
+    fn publish_frame(&self, frame: &TelemetryFrame, material: bool) {
+        let state = self.state();
+        state.ws_server.publish(frame);
+        // Emitted every tick, independent of material-change suppression
+        let _ = self.app.emit(
+            "metrics-mini",
+            MiniMetrics {
+                t: frame.t,
+                upload_bps: frame.net.upload_bps,
+                download_bps: frame.net.download_bps,
+                latency_ms: frame.net.latency_ms,
+                active_flows: frame.net.active_flows,
+            },
+        );
+        tray::update(&self.app, frame);
+        if material {
+            *state.last_frame.lock_or_recover("last_frame") = Some(frame.clone());
+        }
+    }
+
+    fn alert(&self, event: &alerts::AlertEvent, actions: &[alerts::AlertAction]) {
+        notify::dispatch(&self.app, event, actions);
+    }
+
+    fn findings(&self, session_id: Option<&str>, findings: Vec<notify::Finding>) {
+        self.anomaly_notifier
+            .lock_or_recover("anomaly_notifier")
+            .offer(&self.app, session_id, findings);
+    }
+}
+
+async fn monitor_loop(app: tauri::AppHandle, writer_tx: writer::WriterSender) {
+    let host = AppHost::new(app.clone());
+    let mut monitor = monitor::Monitor::new(&host, writer_tx).await;
+    let state = host.state();
+
+    // Start recording with the detected local geo, unless settings say to
+    // ask first or only monitor
+    let startup = state
+        .settings
+        .lock_or_recover("settings")
+        .startup_for(state.launched_at_login);
+    match startup {
+        settings::StartupSession::Record => match begin_session(state, None) {
+            Ok(session_id) => println!("[Abyss] Session started: {session_id}"),
+            Err(e) => eprintln!("[Abyss] Failed to start session: {e}"),
+        },
+        settings::StartupSession::Ask => {
+            state.startup_prompt.store(true, Ordering::Relaxed);
+            let _ = app.emit("startup-session-prompt", ());
+            println!("[Abyss] Waiting for the user to choose whether to record");
+        }
+        settings::StartupSession::MonitorOnly => {
+            println!("[Abyss] Monitor-only startup — nothing is persisted");
+        }
+    }
+
+    println!("[Abyss] Monitor started — emitting telemetry-frame events @ 1 Hz");
+
+    loop {
+        if state.paused_since.lock_or_recover("paused_since").is_some() {
+            // Nothing is polled, emitted or written until resumed; the
+            // session stays open.
+            println!("[Abyss] Monitoring paused");
+            monitor.close_flows(&host);
+            loop {
+                let resumed = state.monitor_resumed.notified();
+                if state.paused_since.lock_or_recover("paused_since").is_none() {
+                    break;
+                }
+                resumed.await;
+            }
+            monitor.resume();
+            println!("[Abyss] Monitoring resumed");
+        }
+
+        let tick = monitor.tick(&host).await;
+        tokio::time::sleep(tick).await;
+    }
+}
+
+/// Focus the live flow list on one process/country/protocol or a minimum rate.
+/// Pass an empty object to clear.
+#[tauri::command]
+fn cmd_set_live_filters(
+    state: tauri::State<'_, AppState>,
+    filters: LiveFilters,
+) -> Result<(), AbyssError> {
+    *state.live_filters.lock_or_recover("live_filters") = filters;
+    Ok(())
+}
+
+#[tauri::command]
+fn cmd_get_live_filters(state: tauri::State<'_, AppState>) -> Result<LiveFilters, AbyssError> {
+    let guard = state.live_filters.lock_or_recover("live_filters");
+    Ok(guard.clone())
+}
+
+/// Limit the live flow list to `include` processes (all when empty), minus
+/// `exclude`.  The selected processes' destinations are geolocated first;
+/// every flow is still recorded.
+#[tauri::command]
+fn cmd_set_process_filter(
+    state: tauri::State<'_, AppState>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+) -> Result<ProcessFilter, AbyssError> {
+    let filter = ProcessFilter::new(include, exclude);
+    if filter.is_empty() {
+        println!("[Abyss] Process filter cleared");
+    } else {
+        println!(
+            "[Abyss] Process filter: include [{}], exclude [{}]",
+            filter.include.join(", "),
+            filter.exclude.join(", ")
+        );
+    }
+    *state.process_filter.lock_or_recover("process_filter") = filter.clone();
+    Ok(filter)
+}
+
+#[tauri::command]
+fn cmd_get_process_filter(state: tauri::State<'_, AppState>) -> Result<ProcessFilter, AbyssError> {
+    Ok(state.process_filter.lock_or_recover("process_filter").clone())
+}
+
+/// Set the telemetry stream for a window (defaults to the calling window).
+/// Once any subscription exists, frames are emitted per window, so windows
+/// must listen with their window-scoped `listen` to avoid duplicate events.
+#[tauri::command]
+fn cmd_set_stream_subscription(
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, AppState>,
+    subscription: StreamSubscription,
+    label: Option<String>,
+) -> Result<(), AbyssError> {
+    let label = label.unwrap_or_else(|| window.label().to_string());
+    state
+        .stream_subscriptions
+        .lock_or_recover("stream_subscriptions")
+        .insert(label, subscription);
+    Ok(())
+}
+
+/// Remove a window's subscription; with none left, frames are broadcast again.
+#[tauri::command]
+fn cmd_clear_stream_subscription(
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, AppState>,
+    label: Option<String>,
+) -> Result<bool, AbyssError> {
+    let label = label.unwrap_or_else(|| window.label().to_string());
+    let removed = state
+        .stream_subscriptions
+        .lock_or_recover("stream_subscriptions")
+        .remove(&label);
+    Ok(removed.is_some())
+}
+
+#[tauri::command]
+fn cmd_list_stream_subscriptions(
+    state: tauri::State<'_, AppState>,
+) -> Result<HashMap<String, StreamSubscription>, AbyssError> {
+    let guard = state.stream_subscriptions.lock_or_recover("stream_subscriptions");
+    Ok(guard.clone())
+}
+
+/// Frame schema versions the backend can emit and the event each arrives on.
+#[tauri::command]
+fn cmd_get_schema_info() -> schema::SchemaInfo {
+    schema::info()
+}
+
+/// Pick the newest frame schema both sides support for a window (defaults
+/// to the calling window) from the versions its UI bundle `accepts`.  From
+/// then on that window's frames arrive on the returned channel instead of
+/// `telemetry-frame`, shaped for that version.
+#[tauri::command]
+fn cmd_negotiate_schema(
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, AppState>,
+    accepts: Vec<u32>,
+    label: Option<String>,
+) -> Result<schema::SchemaChannel, AbyssError> {
+    let version = schema::negotiate(&accepts).ok_or_else(|| {
+        AbyssError::InvalidInput(format!(
+            "No common frame schema: backend supports {:?}",
+            schema::SUPPORTED_VERSIONS
+        ))
+    })?;
+    let label = label.unwrap_or_else(|| window.label().to_string());
+    state
+        .schema_versions
+        .lock_or_recover("schema_versions")
+        .insert(label, version);
+    Ok(schema::SchemaChannel::new(version))
+}
+
+/// Ask the monitor loop to emit a full frame on its next tick (called by the
+/// frontend on mount so it doesn't wait for the next material change).
+#[tauri::command]
+fn cmd_request_keyframe(state: tauri::State<'_, AppState>) {
+    state.keyframe_requested.store(true, Ordering::Relaxed);
+}
+
+/// Current monitor state in one call: latest full frame, local geo, active
+/// session, geo pipeline status and settings.
+#[tauri::command]
+fn cmd_get_monitor_state(state: tauri::State<'_, AppState>) -> Result<MonitorState, AbyssError> {
+    Ok(MonitorState {
+        monitoring: monitoring_status(&state),
+        frame: state.last_frame.lock_or_recover("last_frame").clone(),
+        local_geo: state.local_geo.lock_or_recover("local_geo").clone(),
+        session_id: state.current_session_id.lock_or_recover("current_session_id").clone(),
+        geo: state.geo_status.lock_or_recover("geo_status").clone(),
+        live_filters: state.live_filters.lock_or_recover("live_filters").clone(),
+        process_filter: state.process_filter.lock_or_recover("process_filter").clone(),
+        settings: state.settings.lock_or_recover("settings").clone(),
+    })
+}
+
+/// Suspend the monitor loop: no polling, no frames, nothing written.  The
+/// current session stays open and continues on resume.
+#[tauri::command]
+fn cmd_pause_monitoring(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<MonitoringStatus, AbyssError> {
+    Ok(set_paused(&app, &state, true))
+}
+
+#[tauri::command]
+fn cmd_resume_monitoring(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<MonitoringStatus, AbyssError> {
+    Ok(set_paused(&app, &state, false))
+}
+
+/// Pause or resume the monitor loop and announce it (`monitoring-state`).
+pub(crate) fn set_paused(app: &tauri::AppHandle, state: &AppState, paused: bool) -> MonitoringStatus {
+    {
+        let mut since = state.paused_since.lock_or_recover("paused_since");
+        if !paused {
+            *since = None;
+        } else if since.is_none() {
+            *since = Some(chrono::Utc::now().to_rfc3339());
+        }
+    }
+    if !paused {
+        state.monitor_resumed.notify_waiters();
+    }
+    let status = monitoring_status(state);
+    let _ = app.emit("monitoring-state", &status);
+    tray::refresh(app);
+    status
+}
+
+fn monitoring_status(state: &AppState) -> MonitoringStatus {
+    let paused_since = state.paused_since.lock_or_recover("paused_since").clone();
+    MonitoringStatus {
+        paused: paused_since.is_some(),
+        paused_since,
+    }
+}
+
+/// Internal health counters (locks recovered after a panic, socket table
+/// lines that didn't parse).
+#[tauri::command]
+fn cmd_get_diagnostics() -> Diagnostics {
+    Diagnostics {
+        poison_recoveries: locks::poison_recoveries(),
+        unparsed_lines: address::unparsed_lines(),
+    }
+}
+
+/// All runtime settings.
+#[tauri::command]
+fn cmd_get_settings(state: tauri::State<'_, AppState>) -> Result<settings::Settings, AbyssError> {
+    Ok(state.settings.lock_or_recover("settings").clone())
+}
+
+/// Update any subset of settings (camelCase keys).  Tuning knobs (tick and
+/// poll intervals, flow limit, geo TTL, material-change thresholds) apply
+/// from the next tick.  Returns the full settings.  Persisted.
+#[tauri::command]
+async fn cmd_set_settings(
+    state: tauri::State<'_, AppState>,
+    patch: serde_json::Map<String, serde_json::Value>,
+) -> Result<settings::Settings, AbyssError> {
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        *settings = settings::apply_patch(&settings, patch)?;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot.clone()).await?;
+    Ok(snapshot)
+}
+
+/// Enable or disable privacy mode (no remote geo lookups).  Persisted.
+#[tauri::command]
+async fn cmd_set_privacy_mode(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), AbyssError> {
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.privacy_mode = enabled;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await
+}
+
+/// Enable or disable RTT probes to active destinations.  When off, flow
+/// RTTs and frame latency fall back to estimates.  Persisted.
+#[tauri::command]
+async fn cmd_set_latency_probes(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), AbyssError> {
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.latency_probes = enabled;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await
+}
+
+/// Privacy mode: store IPs truncated to /24 (IPv4) or /48 (IPv6), or as
+/// keyed hashes with `mode: "hash"`, and drop process names from flow
+/// snapshots and destinations written from now on.  Session exports are
+/// anonymized the same way; the live view keeps full addresses.  Persisted.
+#[tauri::command]
+async fn cmd_set_redact_at_rest(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+    mode: Option<anonymize::AnonymizeMode>,
+) -> Result<(), AbyssError> {
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.redact_at_rest = enabled;
+        if let Some(mode) = mode {
+            settings.anonymize_mode = mode;
+        }
+        settings.clone()
+    };
+    // Creates the hashing key beside the database when hashing is selected
+    let anonymizer = anonymize::Anonymizer::from_settings(&snapshot, &state.db_path)?;
+    state.writer_tx.send(writer::WriteCommand::SetRedaction { enabled, anonymizer })?;
+    commit_settings(&state, snapshot).await
+}
+
+/// Anonymize the addresses already stored for a finished session (the
+/// privacy mode's setting decides truncation or hashing) and clear the
+/// hostnames resolved for them, e.g. before sharing the database.
+/// Irreversible.  Returns how many distinct addresses were replaced.
+#[tauri::command]
+async fn cmd_anonymize_session(state: tauri::State<'_, AppState>, session_id: String) -> Result<usize, AbyssError> {
+    if state.current_session_id.lock_or_recover("current_session_id").as_deref() == Some(session_id.as_str()) {
+        return Err(AbyssError::Conflict("Stop the recording before anonymizing it".into()));
+    }
+    let db_path = state.db_path.clone();
+    let anonymizer = anonymize::Anonymizer::from_settings(&state.settings.lock_or_recover("settings"), &db_path)?;
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        if db::get_session(&conn, &session_id)?.is_none() {
+            return Err(AbyssError::NotFound("Session not found".into()));
+        }
+        let replaced = db::anonymize_session(&conn, &session_id, &|ip| anonymizer.ip(ip))?;
+        println!("[Abyss] Anonymized {replaced} addresses in session {session_id}");
+        Ok(replaced)
+    })
+    .await?
+}
+
+/// Choose how often frames, flows, destinations and process usage are
+/// persisted.  Named presets fill in their own intervals; `custom` keeps the
+/// ones given.  Applies from the next session.  Persisted.
+#[tauri::command]
+async fn cmd_set_persistence_profile(
+    state: tauri::State<'_, AppState>,
+    profile: writer::PersistenceProfile,
+) -> Result<writer::PersistenceProfile, AbyssError> {
+    let profile = profile.resolved();
+    profile.validate()?;
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.persistence = profile;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(profile)
+}
+
+#[tauri::command]
+fn cmd_get_persistence_exclusions(
+    state: tauri::State<'_, AppState>,
+) -> Result<exclusions::PersistenceExclusions, AbyssError> {
+    let key = exclusions::ExclusionKey::load(&state.db_path)?;
+    exclusions::PersistenceExclusions::load(&state.settings.lock_or_recover("settings"), key.as_ref())
+}
+
+/// Never persist flows of these processes (e.g. a password manager) or to
+/// these IPs/CIDR ranges; the writer drops them before insert and the live
+/// view still shows them.  Applies immediately.  Persisted encrypted, with
+/// a key kept beside the database.  Returns the list as stored.
+#[tauri::command]
+async fn cmd_set_persistence_exclusions(
+    state: tauri::State<'_, AppState>,
+    processes: Vec<String>,
+    ip_ranges: Vec<String>,
+) -> Result<exclusions::PersistenceExclusions, AbyssError> {
+    let list = exclusions::PersistenceExclusions { processes, ip_ranges }.normalized();
+    list.validate()?;
+    let key = exclusions::ExclusionKey::load_or_create(&state.db_path)?;
+    let sealed = if list == exclusions::PersistenceExclusions::default() {
+        None
+    } else {
+        Some(list.seal(&key)?)
+    };
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.persistence_exclusions = sealed;
+        settings.clone()
+    };
+    state
+        .writer_tx
+        .send(writer::WriteCommand::SetExclusions(exclusions::ExclusionFilter::new(&list)))?;
+    commit_settings(&state, snapshot).await?;
+    Ok(list)
+}
+
+/// Load a MaxMind GeoLite2/GeoIP2 City, Country or ASN `.mmdb` file as the
+/// primary geo source (the HTTP API is only asked about local misses).
+/// `None` unloads all local databases.  Persisted.
+#[tauri::command]
+async fn cmd_set_geoip_db(
+    state: tauri::State<'_, AppState>,
+    path: Option<String>,
+) -> Result<geo::GeoDbStatus, AbyssError> {
+    let (status, paths) = {
+        let mut geo_db = state.geo_db.lock_or_recover("geo_db");
+        match path {
+            Some(path) => {
+                geo_db.load(std::path::Path::new(&path))?;
+            }
+            None => geo_db.clear(),
+        }
+        (geo_db.status(), geo_db.paths())
+    };
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.geoip_db_paths = paths;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(status)
+}
+
+/// Choose the geolocation provider (and its API key/token, if any).  Takes
+/// effect on the next tick and clears the geo cache.  Persisted.
+#[tauri::command]
+async fn cmd_set_geo_provider(
+    state: tauri::State<'_, AppState>,
+    provider: geo::GeoProviderKind,
+    api_key: Option<String>,
+) -> Result<(), AbyssError> {
+    if provider == geo::GeoProviderKind::IpGeolocation && api_key.as_deref().unwrap_or("").is_empty() {
+        return Err(AbyssError::InvalidInput("ipgeolocation.io requires an API key".into()));
+    }
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.geo_provider = provider;
+        settings.geo_api_key = api_key.filter(|k| !k.is_empty());
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await
+}
+
+/// Switch between socket table polling and packet capture.  Persisted.  If
+/// capture can't start (not compiled in, no privileges) the poller stays in
+/// use and the returned status says why.
+#[tauri::command]
+async fn cmd_set_capture_mode(
+    state: tauri::State<'_, AppState>,
+    mode: capture::CaptureMode,
+) -> Result<capture::CaptureStatus, AbyssError> {
+    let status = state.capture.set_mode(mode);
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.capture_mode = mode;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(status)
+}
+
+/// Start (or restart) the telemetry WebSocket server and keep it enabled
+/// across restarts.  Omitted arguments keep their saved values.
+#[tauri::command]
+async fn cmd_start_ws_server(
+    state: tauri::State<'_, AppState>,
+    port: Option<u16>,
+    allow_lan: Option<bool>,
+    rotate_token: Option<bool>,
+) -> Result<server::WsServerStatus, AbyssError> {
+    let mut next = state.settings.lock_or_recover("settings").clone();
+    if let Some(port) = port {
+        next.ws_server_port = port;
+    }
+    if let Some(allow_lan) = allow_lan {
+        next.ws_server_lan = allow_lan;
+    }
+    if rotate_token.unwrap_or(false) || next.ws_server_token.is_none() {
+        next.ws_server_token = Some(uuid::Uuid::new_v4().simple().to_string());
+    }
+    let status = state.ws_server.start(server::WsConfig {
+        port: next.ws_server_port,
+        allow_lan: next.ws_server_lan,
+        token: next.ws_server_token.clone().unwrap_or_default(),
+    })?;
+
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.ws_server_enabled = true;
+        settings.ws_server_port = next.ws_server_port;
+        settings.ws_server_lan = next.ws_server_lan;
+        settings.ws_server_token = next.ws_server_token;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(status)
+}
+
+#[tauri::command]
+async fn cmd_stop_ws_server(state: tauri::State<'_, AppState>) -> Result<server::WsServerStatus, AbyssError> {
+    state.ws_server.stop();
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.ws_server_enabled = false;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(state.ws_server.status())
+}
+
+#[tauri::command]
+fn cmd_get_ws_server_status(state: tauri::State<'_, AppState>) -> server::WsServerStatus {
+    state.ws_server.status()
+}
+
+/// Start (or restart) the loopback REST API and keep it enabled across
+/// restarts.  An omitted port keeps the saved one.
+#[tauri::command]
+async fn cmd_start_api_server(
+    state: tauri::State<'_, AppState>,
+    port: Option<u16>,
+    rotate_token: Option<bool>,
+) -> Result<api::ApiServerStatus, AbyssError> {
+    let mut next = state.settings.lock_or_recover("settings").clone();
+    if let Some(port) = port {
+        next.api_server_port = port;
+    }
+    if rotate_token.unwrap_or(false) || next.api_server_token.is_none() {
+        next.api_server_token = Some(uuid::Uuid::new_v4().simple().to_string());
+    }
+    let status = state.api_server.start(
+        next.api_server_port,
+        next.api_server_token.clone().unwrap_or_default(),
+        state.db_path.clone(),
+    )?;
+
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.api_server_enabled = true;
+        settings.api_server_port = next.api_server_port;
+        settings.api_server_token = next.api_server_token;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(status)
+}
+
+#[tauri::command]
+async fn cmd_stop_api_server(state: tauri::State<'_, AppState>) -> Result<api::ApiServerStatus, AbyssError> {
+    state.api_server.stop();
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.api_server_enabled = false;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(state.api_server.status())
+}
+
+#[tauri::command]
+fn cmd_get_api_server_status(state: tauri::State<'_, AppState>) -> api::ApiServerStatus {
+    state.api_server.status()
+}
+
+#[tauri::command]
+fn cmd_get_capture_status(state: tauri::State<'_, AppState>) -> capture::CaptureStatus {
+    state.capture.status()
+}
+
+/// Network interfaces with link details and current rx/tx rates.
+#[tauri::command]
+async fn cmd_list_interfaces(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<interfaces::InterfaceInfo>, AbyssError> {
+    let sampled = state.interfaces.lock_or_recover("interfaces").clone();
+    if !sampled.is_empty() {
+        return Ok(sampled);
+    }
+    // Monitor loop hasn't sampled yet: counters only, no rates
+    let monitored = state.settings.lock_or_recover("settings").monitored_interfaces.clone();
+    let mut listed = tokio::task::spawn_blocking(interfaces::list).await?;
+    for iface in &mut listed {
+        iface.monitored = interfaces::is_monitored(iface, &monitored);
+    }
+    Ok(listed)
+}
+
+/// Only monitor connections on these interfaces (names from
+/// `cmd_list_interfaces`); an empty list monitors all.  Persisted.
+#[tauri::command]
+async fn cmd_set_monitored_interfaces(
+    state: tauri::State<'_, AppState>,
+    names: Vec<String>,
+) -> Result<(), AbyssError> {
+    let known = tokio::task::spawn_blocking(interfaces::list).await?;
+    let mut selected: Vec<String> = Vec::new();
+    for name in names.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        let Some(iface) = known.iter().find(|i| i.name.eq_ignore_ascii_case(name)) else {
+            return Err(AbyssError::InvalidInput(format!("Unknown interface '{name}'")));
+        };
+        if !selected.contains(&iface.name) {
+            selected.push(iface.name.clone());
+        }
+    }
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.monitored_interfaces = selected;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await
+}
+
+/// Close the writer's database connection so maintenance (restore,
+/// compaction, moves) can touch the file.  Live telemetry keeps flowing;
+/// frames are not persisted until `cmd_resume_writer`.
+#[tauri::command]
+async fn cmd_pause_writer(state: tauri::State<'_, AppState>) -> Result<(), AbyssError> {
+    writer_control(&state.writer_tx, |ack| writer::WriteCommand::Pause { ack }).await
+}
+
+/// Reopen the writer's database connection after `cmd_pause_writer`.
+#[tauri::command]
+async fn cmd_resume_writer(state: tauri::State<'_, AppState>) -> Result<(), AbyssError> {
+    writer_control(&state.writer_tx, |ack| writer::WriteCommand::Resume { ack }).await
+}
+
+/// Whether the database is encrypted and unlocked, and whether this build
+/// can encrypt it at all.
+#[tauri::command]
+fn cmd_get_db_encryption(state: tauri::State<'_, AppState>) -> Result<dbcrypt::EncryptionStatus, AbyssError> {
+    Ok(dbcrypt::status(&state.db_path))
+}
+
+/// Encrypt the database with `passphrase` (SQLCipher), change the
+/// passphrase, or store it as plaintext again with `None`.  The writer is
+/// paused while the file is rewritten.
+#[tauri::command]
+async fn cmd_set_db_encryption(
+    state: tauri::State<'_, AppState>,
+    passphrase: Option<String>,
+) -> Result<dbcrypt::EncryptionStatus, AbyssError> {
+    if !matches!(*state.db_lock.lock_or_recover("db_lock"), dblock::DbLockStatus::Held { .. }) {
+        return Err(AbyssError::Conflict("The writer doesn't have the database open".into()));
+    }
+    writer_control(&state.writer_tx, |ack| writer::WriteCommand::Pause { ack }).await?;
+    let db_path = state.db_path.clone();
+    let rewritten =
+        tokio::task::spawn_blocking(move || dbcrypt::set_encryption(&db_path, passphrase.as_deref())).await;
+    // Reopen either way; a failed rewrite leaves the original file in place
+    writer_control(&state.writer_tx, |ack| writer::WriteCommand::Resume { ack }).await?;
+    rewritten??;
+    println!("[Abyss] Database encryption updated");
+    Ok(dbcrypt::status(&state.db_path))
+}
+
+/// Give the passphrase of an encrypted database for this run (or set
+/// `ABYSS_DB_PASSPHRASE`).  Settings, alert rules and the watchlist are
+/// reloaded from it; the writer opens it on its next retry.
+#[tauri::command]
+async fn cmd_unlock_database(
+    state: tauri::State<'_, AppState>,
+    passphrase: String,
+) -> Result<dbcrypt::EncryptionStatus, AbyssError> {
+    let db_path = state.db_path.clone();
+    let (settings, rules, watchlist) = tokio::task::spawn_blocking(move || {
+        dbcrypt::unlock(&db_path, &passphrase)?;
+        let conn = db::open_database(&db_path)?;
+        Ok::<_, AbyssError>((settings::load(&conn), db::list_alert_rules(&conn)?, db::load_watchlist(&conn)?))
+    })
+    .await??;
+    units::set_current(settings.units);
+    address::set_strict(settings.strict_address_parsing);
+    *state.settings.lock_or_recover("settings") = settings.clone();
+    state.settings_watch.send_replace(settings);
+    *state.alert_rules.lock_or_recover("alert_rules") = rules;
+    *state.watchlist.lock_or_recover("watchlist") = watchlist;
+    println!("[Abyss] Database unlocked");
+    Ok(dbcrypt::status(&state.db_path))
+}
+
+/// Replace the database with `backup`, a copy of `sessions.db`.  The copy
+/// is checked and migrated beside the database first, then swapped in while
+/// the writer is paused; the writer reopens onto it and settings, alert
+/// rules and the watchlist are reloaded from it.  Not while recording.
+#[tauri::command]
+async fn cmd_restore_database(state: tauri::State<'_, AppState>, backup: String) -> Result<(), AbyssError> {
+    if state.current_session_id.lock_or_recover("current_session_id").is_some() {
+        return Err(AbyssError::Conflict("Stop recording before restoring a backup".into()));
+    }
+    if !matches!(*state.db_lock.lock_or_recover("db_lock"), dblock::DbLockStatus::Held { .. }) {
+        return Err(AbyssError::Conflict("The writer doesn't have the database open".into()));
+    }
+    let db_path = state.db_path.clone();
+    let staged = tokio::task::spawn_blocking(move || stage_restore(&db_path, std::path::Path::new(&backup))).await??;
+    writer_control(&state.writer_tx, |ack| writer::WriteCommand::Pause { ack }).await?;
+    let (from, to) = (staged.clone(), state.db_path.clone());
+    let swapped = tokio::task::spawn_blocking(move || {
+        for suffix in ["-wal", "-shm"] {
+            let mut name = to.as_os_str().to_owned();
+            name.push(suffix);
+            let _ = std::fs::remove_file(PathBuf::from(name));
+        }
+        std::fs::rename(&from, &to)
+    })
+    .await;
+    // Reopen either way; a failed swap leaves the original file in place
+    let db_path = state.db_path.clone();
+    writer_control(&state.writer_tx, |ack| writer::WriteCommand::Reopen { path: db_path, ack }).await?;
+    if let Err(e) = swapped? {
+        let _ = std::fs::remove_file(&staged);
+        return Err(AbyssError::from(e).context("Failed to replace database"));
+    }
+    let db_path = state.db_path.clone();
+    let (settings, rules, watchlist) = tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        Ok::<_, AbyssError>((settings::load(&conn), db::list_alert_rules(&conn)?, db::load_watchlist(&conn)?))
+    })
+    .await??;
+    units::set_current(settings.units);
+    address::set_strict(settings.strict_address_parsing);
+    *state.settings.lock_or_recover("settings") = settings.clone();
+    state.settings_watch.send_replace(settings);
+    *state.alert_rules.lock_or_recover("alert_rules") = rules;
+    *state.watchlist.lock_or_recover("watchlist") = watchlist;
+    println!("[Abyss] Database restored from backup");
+    Ok(())
+}
+
+/// Copy `backup` next to `db_path` and migrate the copy, so a file that
+/// isn't an Abyss database is rejected before the live one is touched.
+fn stage_restore(db_path: &std::path::Path, backup: &std::path::Path) -> Result<PathBuf, AbyssError> {
+    if dbcrypt::is_encrypted(backup) || dbcrypt::is_encrypted(db_path) {
+        return Err(AbyssError::InvalidInput(
+            "Encrypted databases can't be restored — turn encryption off first".into(),
+        ));
+    }
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".restore");
+    let staged = PathBuf::from(name);
+    std::fs::copy(backup, &staged).map_err(|e| AbyssError::from(e).context("Failed to copy backup"))?;
+    let checked = rusqlite::Connection::open(&staged)
+        .and_then(|conn| db::is_abyss_database(&conn))
+        .and_then(|ok| if ok { db::open_database(&staged).map(|_| true) } else { Ok(false) });
+    match checked {
+        Ok(true) => Ok(staged),
+        Ok(false) | Err(_) => {
+            let _ = std::fs::remove_file(&staged);
+            Err(AbyssError::InvalidInput(format!("{} is not an Abyss database", backup.display())))
+        }
+    }
+}
+
+/// Send a writer control command and wait until the writer acknowledges it.
+async fn writer_control(
+    writer_tx: &writer::WriterSender,
+    command: impl FnOnce(writer::ControlAck) -> writer::WriteCommand,
+) -> Result<(), AbyssError> {
+    let (ack, done) = tokio::sync::oneshot::channel();
+    writer_tx.send(command(ack))?;
+    done.await.map_err(|_| {
+        AbyssError::WriterUnavailable("writer exited before acknowledging".into())
+    })?
+}
+
+/// Publish a settings snapshot to the monitor loop and write it to the
+/// database on a blocking thread.
+async fn commit_settings(state: &AppState, snapshot: settings::Settings) -> Result<(), AbyssError> {
+    units::set_current(snapshot.units);
+    address::set_strict(snapshot.strict_address_parsing);
+    state.settings_watch.send_replace(snapshot.clone());
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        settings::save(&conn, &snapshot)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn fetch_cables() -> Result<String, AbyssError> {
+    let url = "https://www.submarinecablemap.com/api/v3/cable/cable-geo.json";
+    let resp = reqwest::get(url).await?;
+    if !resp.status().is_success() {
+        return Err(AbyssError::Network(format!(
+            "Cable fetch failed with status {}",
+            resp.status()
+        )));
+    }
+    let text = resp.text().await?;
+
+    // Simplify cable coordinates — keep every 3rd point to reduce JS heap by ~60%.
+    // Preserves first and last points of each line for correct endpoints.
+    let mut parsed: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| AbyssError::Network(format!("Failed to parse cable JSON: {e}")))?;
+
+    if let Some(features) = parsed.get_mut("features").and_then(|v| v.as_array_mut()) {
+        for feature in features.iter_mut() {
+            if let Some(coords) = feature
+                .get_mut("geometry")
+                .and_then(|g| g.get_mut("coordinates"))
+                .and_then(|c| c.as_array_mut())
+            {
+                for line in coords.iter_mut() {
+                    if let Some(points) = line.as_array() {
+                        let len = points.len();
+                        if len > 6 {
+                            let simplified: Vec<serde_json::Value> = points
+                                .iter()
+                                .enumerate()
+                                .filter(|(i, _)| *i == 0 || *i == len - 1 || *i % 3 == 0)
+                                .map(|(_, v)| v.clone())
+                                .collect();
+                            *line = serde_json::Value::Array(simplified);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let simplified = serde_json::to_string(&parsed)
+        .map_err(|e| AbyssError::from(e).context("Failed to serialize simplified cables"))?;
+    #[cfg(debug_assertions)]
+    println!(
+        "[Abyss] Fetched submarine cable data ({} bytes raw, {} bytes simplified)",
+        text.len(),
+        simplified.len()
+    );
+    Ok(simplified)
+}
+
+// ─── Session management Tauri commands ──────────────────────────────────────
+
+#[tauri::command]
+async fn cmd_list_sessions(
+    state: tauri::State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<db::SessionInfo>, AbyssError> {
+    let db_path = state.db_path.clone();
+    let limit = limit.unwrap_or(50);
+    let offset = offset.unwrap_or(0);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::list_sessions(&conn, limit, offset).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Preview data for the same page `cmd_list_sessions` returns (finished
+/// sessions only).
+#[tauri::command]
+async fn cmd_list_session_thumbnails(
+    state: tauri::State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<db::SessionThumbnail>, AbyssError> {
+    let db_path = state.db_path.clone();
+    let limit = limit.unwrap_or(50);
+    let offset = offset.unwrap_or(0);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::list_session_thumbnails(&conn, limit, offset).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_session(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<Option<db::SessionInfo>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_session(&conn, &id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_delete_session(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<bool, AbyssError> {
+    // Prevent deleting the currently recording session
+    {
+        let guard = state
+            .current_session_id
+            .lock_or_recover("current_session_id");
+        if guard.as_deref() == Some(id.as_str()) {
+            return Err(AbyssError::Conflict(
+                "Cannot delete the active recording session".into(),
+            ));
+        }
+    }
+
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::delete_session(&conn, &id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Combine finished sessions (e.g. fragments left by crashes) into one new
+/// session; the originals are removed.  Returns the merged session.
+#[tauri::command]
+async fn cmd_merge_sessions(
+    state: tauri::State<'_, AppState>,
+    ids: Vec<String>,
+    new_name: String,
+) -> Result<db::SessionInfo, AbyssError> {
+    let mut unique: Vec<String> = Vec::with_capacity(ids.len());
+    for id in ids {
+        if !unique.contains(&id) {
+            unique.push(id);
+        }
+    }
+    if unique.len() < 2 {
+        return Err(AbyssError::InvalidInput("Select at least two sessions to merge".into()));
+    }
+    let new_name = new_name.trim().to_string();
+    if new_name.is_empty() {
+        return Err(AbyssError::InvalidInput("Merged session needs a name".into()));
+    }
+    {
+        let guard = state
+            .current_session_id
+            .lock_or_recover("current_session_id");
+        if guard.as_ref().is_some_and(|current| unique.contains(current)) {
+            return Err(AbyssError::Conflict(
+                "Cannot merge the active recording session".into(),
+            ));
+        }
+    }
+
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        for id in &unique {
+            let session = db::get_session(&conn, id)?
+                .ok_or_else(|| AbyssError::NotFound(format!("Session '{id}' not found")))?;
+            if session.ended_at.is_none() {
+                return Err(AbyssError::Conflict(format!("Session '{}' is still recording", session.name)));
+            }
+        }
+        let new_id = uuid::Uuid::new_v4().to_string();
+        db::merge_sessions(&conn, &unique, &new_id, &new_name)?;
+        println!("[Abyss] Merged {} sessions into '{new_name}' ({new_id})", unique.len());
+        db::get_session(&conn, &new_id)?
+            .ok_or_else(|| AbyssError::NotFound("Merged session not found".into()))
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_session_frames(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    start_t: Option<f64>,
+    end_t: Option<f64>,
+    max_points: Option<u32>,
+) -> Result<Vec<db::FrameRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_session_frames(&conn, &session_id, start_t, end_t, max_points)
+            .map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_session_flows(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    process_filter: Option<String>,
+    country_filter: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<db::FlowSnapshotRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_session_flows(
+            &conn,
+            &session_id,
+            process_filter.as_deref(),
+            country_filter.as_deref(),
+            limit.unwrap_or(100),
+        )
+        .map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// bps/RTT history of one flow across a session's snapshots.
+#[tauri::command]
+async fn cmd_get_flow_timeseries(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    flow_id: String,
+    max_points: Option<u32>,
+) -> Result<Vec<db::FlowTimeseriesPoint>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_flow_timeseries(&conn, &session_id, &flow_id, max_points).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_query_flows(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    group_by: Option<String>,
+    filters: Option<db::FlowQueryFilters>,
+    limit: Option<u32>,
+) -> Result<Vec<db::FlowGroupRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::query_flows(
+            &conn,
+            &session_id,
+            group_by.as_deref().unwrap_or("process"),
+            &filters.unwrap_or_default(),
+            limit.unwrap_or(100),
+        )
+        .map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_session_destinations(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    sort_by: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<db::DestinationRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_session_destinations(
+            &conn,
+            &session_id,
+            sort_by.as_deref().unwrap_or("bytes"),
+            limit.unwrap_or(50),
+        )
+        .map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_process_usage(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    process_name: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<db::ProcessUsageRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_process_usage(
+            &conn,
+            &session_id,
+            process_name.as_deref(),
+            limit.unwrap_or(500),
+        )
+        .map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_global_stats(
+    state: tauri::State<'_, AppState>,
+) -> Result<db::GlobalStats, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_global_stats(&conn, &db_path).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+fn cmd_update_session_meta(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    name: Option<String>,
+    notes: Option<String>,
+    tags: Option<String>,
+) -> Result<(), AbyssError> {
+    state
+        .writer_tx
+        .send(writer::WriteCommand::UpdateMeta {
+            id,
+            name,
+            notes,
+            tags,
+        })
+        .map_err(AbyssError::from)
+}
+
+#[tauri::command]
+fn cmd_start_session(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    name: Option<String>,
+) -> Result<String, AbyssError> {
+    state.startup_prompt.store(false, Ordering::Relaxed);
+    let session_id = begin_session(&state, name)?;
+    tray::refresh(&app);
+    Ok(session_id)
+}
+
+/// End the current session (if any) and start recording a new one at the
+/// last detected local position.
+pub(crate) fn begin_session(state: &AppState, name: Option<String>) -> Result<String, AbyssError> {
+    if state.monitor_only.load(Ordering::Relaxed) {
+        return Err(AbyssError::Conflict(
+            "Monitor-only mode is on; turn it off to record a session".into(),
+        ));
+    }
+
+    // Stop any existing session first
+    {
+        let mut guard = state
+            .current_session_id
+            .lock_or_recover("current_session_id");
+        if let Some(old_id) = guard.take() {
+            let _ = state
+                .writer_tx
+                .send(writer::WriteCommand::EndSession { id: old_id });
+        }
+    }
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Local::now();
+    let session_name =
+        name.unwrap_or_else(|| now.format("Session \u{2014} %b %d, %Y %I:%M %p").to_string());
+
+    // Use cached geo data so manually-started sessions have correct map coordinates
+    let geo = state
+        .local_geo
+        .lock_or_recover("local_geo")
+        .clone();
+
+    state
+        .writer_tx
+        .send(writer::WriteCommand::StartSession {
+            id: session_id.clone(),
+            name: session_name,
+            local_city: geo.city,
+            local_country: geo.country,
+            local_lat: geo.lat,
+            local_lng: geo.lng,
+        })?;
+
+    *state
+        .current_session_id
+        .lock_or_recover("current_session_id") = Some(session_id.clone());
+
+    Ok(session_id)
+}
+
+#[tauri::command]
+fn cmd_stop_session(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<String>, AbyssError> {
+    let ended = end_session(&state);
+    tray::refresh(&app);
+    Ok(ended)
+}
+
+/// End the current session, if any, returning its id.
+pub(crate) fn end_session(state: &AppState) -> Option<String> {
+    let id = state
+        .current_session_id
+        .lock_or_recover("current_session_id")
+        .take()?;
+    let _ = state
+        .writer_tx
+        .send(writer::WriteCommand::EndSession { id: id.clone() });
+    Some(id)
+}
+
+/// Whether the startup prompt (`startup-session-prompt`) is still waiting
+/// for an answer, for windows that loaded after it was emitted.
+#[tauri::command]
+fn cmd_get_startup_prompt(state: tauri::State<'_, AppState>) -> Result<bool, AbyssError> {
+    Ok(state.startup_prompt.load(Ordering::Relaxed))
+}
+
+/// Answer the startup prompt: start recording, or keep monitoring without a
+/// session.  Returns the new session ID when recording.
+#[tauri::command]
+fn cmd_answer_startup_prompt(state: tauri::State<'_, AppState>, record: bool) -> Result<Option<String>, AbyssError> {
+    if !state.startup_prompt.swap(false, Ordering::Relaxed) {
+        return Err(AbyssError::InvalidInput("No startup prompt is pending".into()));
+    }
+    if !record {
+        return Ok(None);
+    }
+    begin_session(&state, None).map(Some)
+}
+
+/// Switch monitor-only mode at runtime.  Turning it on ends the current
+/// session and stops persisting traffic; the globe keeps updating.  Turning
+/// it off starts recording a new session, whose ID is returned.
+#[tauri::command]
+fn cmd_set_monitor_only(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<Option<String>, AbyssError> {
+    if state.monitor_only.swap(enabled, Ordering::Relaxed) == enabled {
+        return Ok(None);
+    }
+    state
+        .writer_tx
+        .send(writer::WriteCommand::SetMonitorOnly { enabled })?;
+    let _ = app.emit("monitor-only-changed", enabled);
+    if !enabled {
+        return begin_session(&state, None).map(Some);
+    }
+    state.startup_prompt.store(false, Ordering::Relaxed);
+    if let Some(id) = state
+        .current_session_id
+        .lock_or_recover("current_session_id")
+        .take()
+    {
+        let _ = state
+            .writer_tx
+            .send(writer::WriteCommand::EndSession { id });
+    }
+    Ok(None)
+}
+
+#[tauri::command]
+fn cmd_get_monitor_only(state: tauri::State<'_, AppState>) -> Result<bool, AbyssError> {
+    Ok(state.monitor_only.load(Ordering::Relaxed))
+}
+
+/// Launch Abyss (hidden, see `Settings::record_on_login`) when the user
+/// logs in.  Returns whether start-on-login is now registered.
+#[tauri::command]
+fn cmd_set_autostart(app: tauri::AppHandle, enabled: bool) -> Result<bool, AbyssError> {
+    let launcher = app.autolaunch();
+    if enabled {
+        launcher.enable()
+    } else {
+        launcher.disable()
+    }
+    .map_err(|e| AbyssError::Internal(format!("Failed to update start-on-login: {e}")))?;
+    launcher
+        .is_enabled()
+        .map_err(|e| AbyssError::Internal(format!("Failed to read start-on-login: {e}")))
+}
+
+#[tauri::command]
+fn cmd_get_autostart(app: tauri::AppHandle) -> Result<bool, AbyssError> {
+    app.autolaunch()
+        .is_enabled()
+        .map_err(|e| AbyssError::Internal(format!("Failed to read start-on-login: {e}")))
+}
+
+/// Writer queue depth, flush latency and rows/sec, as of its last flush.
+#[tauri::command]
+fn cmd_get_writer_stats(state: tauri::State<'_, AppState>) -> Result<writer::WriterStats, AbyssError> {
+    Ok(state.writer_stats.lock_or_recover("writer_stats").clone())
+}
+
+/// Finalize the open session and stop the writer thread, waiting up to
+/// `WRITER_SHUTDOWN_TIMEOUT_SECS`.  Returns false if it was already stopped.
+/// Later write commands are dropped.
+#[tauri::command]
+async fn cmd_shutdown_writer(app: tauri::AppHandle) -> Result<bool, AbyssError> {
+    Ok(tokio::task::spawn_blocking(move || shutdown_writer(&app.state::<AppState>())).await?)
+}
+
+fn shutdown_writer(state: &AppState) -> bool {
+    let Some(handle) = state.writer_thread.lock_or_recover("writer_thread").take() else {
+        return false;
+    };
+    let _ = state.writer_tx.send(writer::WriteCommand::Shutdown);
+    println!("[Abyss] Shutdown signal sent to writer");
+    let deadline = Instant::now() + Duration::from_secs(WRITER_SHUTDOWN_TIMEOUT_SECS);
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            eprintln!("[Abyss] Writer still busy after {WRITER_SHUTDOWN_TIMEOUT_SECS}s; exiting without it");
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let _ = handle.join();
+    true
+}
+
+/// Whether closing the main window should leave the app running.
+fn background_mode(app: &tauri::AppHandle) -> bool {
+    app.try_state::<AppState>()
+        .is_some_and(|state| state.settings.lock_or_recover("settings").background_mode)
+}
+
+/// A process with its parent chain and the app group its flows count under.
+#[tauri::command]
+async fn cmd_get_process_tree(pid: u32) -> Result<Option<proctree::ProcessLineage>, AbyssError> {
+    Ok(tokio::task::spawn_blocking(move || proctree::ProcessTree::snapshot().lineage(pid)).await?)
+}
+
+/// Whether this instance owns the database yet (see `db-lock-status`).
+#[tauri::command]
+fn cmd_get_db_lock_status(state: tauri::State<'_, AppState>) -> Result<dblock::DbLockStatus, AbyssError> {
+    Ok(state.db_lock.lock_or_recover("db_lock").clone())
+}
+
+#[tauri::command]
+fn cmd_get_current_session(state: tauri::State<'_, AppState>) -> Result<Option<String>, AbyssError> {
+    let guard = state
+        .current_session_id
+        .lock_or_recover("current_session_id");
+    Ok(guard.clone())
+}
+
+/// Prune frames and flow snapshots of sessions older than `days` (default
+/// 30), keeping them listed as summary-only.
+#[tauri::command]
+async fn cmd_compact_sessions(
+    state: tauri::State<'_, AppState>,
+    days: Option<u32>,
+) -> Result<u32, AbyssError> {
+    let db_path = state.db_path.clone();
+    let days = days.unwrap_or(30);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::compact_old_sessions(&conn, days).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Roll up raw frames of sessions older than `days` (default 7) into
+/// 1-minute and 1-hour buckets.  Returns the number of sessions rolled up.
+#[tauri::command]
+async fn cmd_rollup_sessions(
+    state: tauri::State<'_, AppState>,
+    days: Option<u32>,
+) -> Result<u32, AbyssError> {
+    let db_path = state.db_path.clone();
+    let days = days.unwrap_or(7);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::rollup_old_frames(&conn, days).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Set automatic frame retention (0 disables).  Applied by the background
+/// maintenance task every 6 hours.  Persisted.
+#[tauri::command]
+async fn cmd_set_frame_retention(
+    state: tauri::State<'_, AppState>,
+    days: u32,
+) -> Result<(), AbyssError> {
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.frame_retention_days = days;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await
+}
+
+/// Apply the stored retention policy now instead of waiting for the
+/// writer's daily pass.
+#[tauri::command]
+async fn cmd_run_retention(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<db::RetentionReport, AbyssError> {
+    let policy = state.settings.lock_or_recover("settings").retention;
+    let db_path = state.db_path.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::enforce_retention(&conn, &policy).map_err(AbyssError::from)
+    })
+    .await??;
+    if report.total() > 0 {
+        let _ = app.emit("retention-pruned", report);
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+async fn cmd_delete_all_sessions(
+    state: tauri::State<'_, AppState>,
+) -> Result<u32, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::delete_all_sessions(&conn).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_database_path(
+    state: tauri::State<'_, AppState>,
+) -> Result<String, AbyssError> {
+    Ok(db::get_database_path(&state.db_path))
+}
+
+#[tauri::command]
+async fn cmd_open_data_folder(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AbyssError> {
+    open_data_folder(&state.db_path)
+}
+
+/// Show the folder holding the database in the system file manager.
+pub(crate) fn open_data_folder(db_path: &std::path::Path) -> Result<(), AbyssError> {
+    let folder = db_path
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| db_path.to_string_lossy().to_string());
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("explorer")
+            .arg(&folder)
+            .spawn()?;
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(&folder)
+            .spawn()?;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&folder)
+            .spawn()?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn cmd_get_playback_data(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<db::PlaybackData, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_playback_data(&conn, &session_id)?
+            .ok_or_else(|| AbyssError::NotFound("Session not found".into()))
+    })
+    .await?
+}
+
+/// Frame index and markers for streamed playback; the frames and flows
+/// themselves come from `cmd_get_playback_chunk`.
+#[tauri::command]
+async fn cmd_get_playback_index(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<db::PlaybackIndex, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_playback_index(&conn, &session_id)?
+            .ok_or_else(|| AbyssError::NotFound("Session not found".into()))
+    })
+    .await?
+}
+
+/// Frames with `from_t <= t < to_t` and their flow snapshots.  Windows are
+/// limited to `MAX_PLAYBACK_CHUNK_SECS`.
+#[tauri::command]
+async fn cmd_get_playback_chunk(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    from_t: f64,
+    to_t: f64,
+) -> Result<db::PlaybackChunk, AbyssError> {
+    if !from_t.is_finite() || !to_t.is_finite() || from_t >= to_t {
+        return Err(AbyssError::InvalidInput("fromT must be before toT".into()));
+    }
+    if to_t - from_t > MAX_PLAYBACK_CHUNK_SECS {
+        return Err(AbyssError::InvalidInput(format!(
+            "Playback chunks span at most {MAX_PLAYBACK_CHUNK_SECS} seconds"
+        )));
+    }
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        if db::get_session(&conn, &session_id)?.is_none() {
+            return Err(AbyssError::NotFound("Session not found".into()));
+        }
+        db::get_playback_chunk(&conn, &session_id, from_t, to_t).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Playback with flows pre-aggregated per destination country and frame.
+#[tauri::command]
+async fn cmd_get_country_playback(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<db::CountryPlaybackData, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_country_playback_data(&conn, &session_id)?
+            .ok_or_else(|| AbyssError::NotFound("Session not found".into()))
+    })
+    .await?
+}
+
+/// A session resampled every `step` seconds with interpolated metrics and
+/// arcs, for rendering into a `fps` video.
+#[tauri::command]
+async fn cmd_export_timelapse(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    fps: u32,
+    step: f64,
+) -> Result<timelapse::Timelapse, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        let data = db::get_playback_data(&conn, &session_id)?
+            .ok_or_else(|| AbyssError::NotFound("Session not found".into()))?;
+        timelapse::build(&data, fps, step)
+    })
+    .await?
+}
+
+/// Longest marker label accepted.
+const MAX_MARKER_LABEL_CHARS: usize = 200;
+
+/// Annotate the moment `t` (frame time, seconds) of a session, live or
+/// recorded.  `color` is a `#rgb` / `#rrggbb` hex string.
+#[tauri::command]
+async fn cmd_add_session_marker(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    t: f64,
+    label: String,
+    color: Option<String>,
+) -> Result<db::SessionMarker, AbyssError> {
+    let label = label.trim().to_string();
+    if label.is_empty() || label.chars().count() > MAX_MARKER_LABEL_CHARS {
+        return Err(AbyssError::InvalidInput(format!(
+            "Marker label must be 1-{MAX_MARKER_LABEL_CHARS} characters"
+        )));
+    }
+    if !t.is_finite() || t < 0.0 {
+        return Err(AbyssError::InvalidInput("Marker time must be a non-negative number".into()));
+    }
+    let color = color.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    if let Some(c) = &color {
+        let hex = c.strip_prefix('#').unwrap_or("");
+        if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            return Err(AbyssError::InvalidInput(format!("Invalid marker color '{c}'")));
+        }
+    }
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        if db::get_session(&conn, &session_id)?.is_none() {
+            return Err(AbyssError::NotFound(format!("Session '{session_id}' not found")));
+        }
+        db::insert_marker(&conn, &session_id, t, &label, color.as_deref()).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_session_markers(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<db::SessionMarker>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_session_markers(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_delete_session_marker(state: tauri::State<'_, AppState>, id: i64) -> Result<bool, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::delete_marker(&conn, id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_daily_usage(
+    state: tauri::State<'_, AppState>,
+    range_days: u32,
+) -> Result<Vec<db::DailyUsage>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_daily_usage(&conn, range_days).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Hour-of-day × day usage matrix for the heatmap.
+#[tauri::command]
+async fn cmd_get_hourly_usage(
+    state: tauri::State<'_, AppState>,
+    range_days: u32,
+) -> Result<Vec<db::HourlyUsageDay>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_hourly_usage(&conn, range_days).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Validate a range bound: a `YYYY-MM-DD` date or an RFC 3339 timestamp.
+fn parse_range_bound(value: &str) -> Result<chrono::DateTime<chrono::Utc>, AbyssError> {
+    if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|d| d.and_time(chrono::NaiveTime::MIN).and_utc())
+        .map_err(|_| AbyssError::InvalidInput(format!("'{value}' is not a date or RFC 3339 timestamp")))
+}
+
+/// Compare bytes, top apps, top countries and latency between two time
+/// ranges, with B − A deltas.
+#[tauri::command]
+async fn cmd_compare_ranges(
+    state: tauri::State<'_, AppState>,
+    range_a: db::TimeRange,
+    range_b: db::TimeRange,
+    limit: Option<u32>,
+) -> Result<db::RangeComparison, AbyssError> {
+    for range in [&range_a, &range_b] {
+        if parse_range_bound(&range.end)? <= parse_range_bound(&range.start)? {
+            return Err(AbyssError::InvalidInput(format!(
+                "Range end {} is not after start {}",
+                range.end, range.start
+            )));
+        }
+    }
+    let db_path = state.db_path.clone();
+    let limit = limit.unwrap_or(10);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::compare_ranges(&conn, &range_a, &range_b, limit).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Label a destination IP (e.g. "NAS backup", category "backup").  Empty
+/// label and category remove it.
+#[tauri::command]
+async fn cmd_set_destination_label(
+    state: tauri::State<'_, AppState>,
+    ip: String,
+    label: String,
+    category: String,
+) -> Result<(), AbyssError> {
+    if ip.parse::<std::net::IpAddr>().is_err() {
+        return Err(AbyssError::InvalidInput(format!("'{ip}' is not an IP address")));
+    }
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::set_destination_label(&conn, &ip, label.trim(), category.trim()).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_top_destinations(
+    state: tauri::State<'_, AppState>,
+    range_days: u32,
+    limit: u32,
+) -> Result<Vec<db::TopDestination>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_top_destinations(&conn, range_days, limit).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Bytes, connections and destinations per destination country, optionally
+/// split per process.
+#[tauri::command]
+async fn cmd_get_country_breakdown(
+    state: tauri::State<'_, AppState>,
+    range_days: u32,
+    by_process: Option<bool>,
+) -> Result<Vec<db::CountryUsage>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_country_breakdown(&conn, range_days, by_process.unwrap_or(false)).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Bytes and connections per destination organisation (ASN owner).
+#[tauri::command]
+async fn cmd_get_org_breakdown(
+    state: tauri::State<'_, AppState>,
+    range_days: u32,
+    limit: u32,
+) -> Result<Vec<db::OrgUsage>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_org_breakdown(&conn, range_days, limit).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_top_apps(
+    state: tauri::State<'_, AppState>,
+    range_days: u32,
+    limit: u32,
+    by_group: Option<bool>,
+) -> Result<Vec<db::TopApp>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_top_apps(&conn, range_days, limit, by_group.unwrap_or(false)).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Import approximate per-app daily usage for the days before Abyss was
+/// recording from the OS data-usage store (Windows only).
+#[tauri::command]
+async fn cmd_backfill_os_usage(
+    state: tauri::State<'_, AppState>,
+    days: Option<u32>,
+) -> Result<datausage::BackfillReport, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let rows = datausage::read_os_usage(days.unwrap_or(datausage::MAX_BACKFILL_DAYS))?;
+        let conn = db::open_database(&db_path)?;
+        let report = db::import_usage_backfill(&conn, datausage::SOURCE, &rows)?;
+        println!(
+            "[Abyss] Backfilled {} app-days from {} ({} skipped)",
+            report.imported, report.source, report.skipped
+        );
+        Ok(report)
+    })
+    .await?
+}
+
+/// Executable path, version, signer and icon for a process name, for
+/// branded Top Apps entries.  Resolved from the running process and cached;
+/// names with no running process are resolved again on the next call.
+#[tauri::command]
+async fn cmd_get_process_metadata(
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> Result<procmeta::ProcessMetadata, AbyssError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AbyssError::InvalidInput("Process name is empty".into()));
+    }
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        if let Some(meta) = db::get_cached_process_metadata(&conn, &name, procmeta::METADATA_CACHE_TTL_DAYS)? {
+            return Ok(meta);
+        }
+        let meta = procmeta::resolve(&name);
+        if meta.exe_path.is_some() {
+            db::store_process_metadata(&conn, &meta)?;
+        }
+        Ok(meta)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_destination_history(
+    state: tauri::State<'_, AppState>,
+    ip: Option<String>,
+    org: Option<String>,
+    limit: Option<u32>,
+) -> Result<db::DestinationHistory, AbyssError> {
+    if ip.is_none() && org.is_none() {
+        return Err(AbyssError::InvalidInput(
+            "Either ip or org must be provided".into(),
+        ));
+    }
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_destination_history(&conn, ip.as_deref(), org.as_deref(), limit.unwrap_or(200))
+            .map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_process_history(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    limit: Option<u32>,
+) -> Result<db::ProcessHistory, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_process_history(&conn, &name, limit.unwrap_or(20)).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_session_insights(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<db::SessionInsights, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::compute_session_insights(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Latency, jitter and loss for the calls and games in a session.
+#[tauri::command]
+async fn cmd_get_session_qos(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<db::QosReport, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        if db::get_session(&conn, &session_id)?.is_none() {
+            return Err(AbyssError::NotFound(format!("Session '{session_id}' not found")));
+        }
+        db::compute_qos_report(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Host-measured traffic against the gateway's WAN counters for a session.
+#[tauri::command]
+async fn cmd_get_router_comparison(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<db::RouterComparison, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        if db::get_session(&conn, &session_id)?.is_none() {
+            return Err(AbyssError::NotFound(format!("Session '{session_id}' not found")));
+        }
+        db::get_router_comparison(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Default gateway / route changes recorded during a session.
+#[tauri::command]
+async fn cmd_get_route_events(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<db::RouteEventRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_route_events(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Public address changes (VPN, ISP) recorded during a session.
+#[tauri::command]
+async fn cmd_get_network_changes(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<db::NetworkChangeRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_network_changes(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// The default routes right now, preferred first.
+#[tauri::command]
+async fn cmd_get_default_routes() -> Result<Vec<routes::DefaultRoute>, AbyssError> {
+    Ok(tokio::task::spawn_blocking(routes::default_routes).await?)
+}
+
+/// Read the gateway's WAN counters once with `config` (or the stored
+/// settings), to check a configuration before saving it.
+#[tauri::command]
+async fn cmd_probe_router(
+    state: tauri::State<'_, AppState>,
+    config: Option<router::RouterConfig>,
+) -> Result<router::RouterCounters, AbyssError> {
+    let config = match config {
+        Some(config) => config,
+        None => state.settings.lock_or_recover("settings").router.clone(),
+    };
+    config.validate()?;
+    router::read_counters(&config, &mut None).await
+}
+
+/// Session summary card plus its pre-rendered SVG.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RenderedSessionCard {
+    #[serde(flatten)]
+    card: db::SessionCard,
+    svg: String,
+}
+
+#[tauri::command]
+async fn cmd_render_session_card(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<RenderedSessionCard, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        let card = db::get_session_card(&conn, &session_id)?
+            .ok_or_else(|| AbyssError::NotFound(format!("Session '{session_id}' not found")))?;
+        let svg = card::render_svg(&card);
+        Ok(RenderedSessionCard { card, svg })
+    })
+    .await?
+}
+
+/// Write a printable report of a session to `path`: summary stats,
+/// insights, anomalies, top destinations and processes, with the charts
+/// rendered here as inline SVG.  `format` is "html" (default) or "pdf".
+#[tauri::command]
+async fn cmd_generate_session_report(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    path: String,
+    format: Option<String>,
+) -> Result<String, AbyssError> {
+    let format = match format.as_deref() {
+        None => report::ReportFormat::Html,
+        Some(f) => report::ReportFormat::parse(f)
+            .ok_or_else(|| AbyssError::InvalidInput(format!("Unknown report format '{f}' (expected html or pdf)")))?,
+    };
+    let db_path = state.db_path.clone();
+    let anonymizer = anonymize::Anonymizer::for_exports(&state.settings.lock_or_recover("settings"), &db_path)?;
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        let data = db::get_session_report(&conn, &session_id)?
+            .ok_or_else(|| AbyssError::NotFound(format!("Session '{session_id}' not found")))?;
+        drop(conn);
+        let html = report::render_html(&data, &chrono::Utc::now().to_rfc3339(), anonymizer.as_ref());
+
+        let target = std::path::Path::new(&path);
+        if let Some(parent) = target.parent() {
+            if !parent.exists() {
+                return Err(AbyssError::InvalidInput(format!(
+                    "Export directory does not exist: {}",
+                    parent.display()
+                )));
+            }
+        }
+        match format {
+            report::ReportFormat::Html => {
+                std::fs::write(target, &html).map_err(|e| AbyssError::from(e).context("Failed to write report"))?
+            }
+            report::ReportFormat::Pdf => report::print_pdf(&html, target)?,
+        }
+        Ok(format!("Saved report for '{}' to {}", data.session.name, path))
+    })
+    .await?
+}
+
+// ─── Tier 6: Baseline, Anomaly, Health, Tagging ─────────────────────────────
+
+#[tauri::command]
+async fn cmd_compute_baseline(
+    state: tauri::State<'_, AppState>,
+    range_days: Option<u32>,
+) -> Result<u32, AbyssError> {
+    let db_path = state.db_path.clone();
+    let days = range_days.unwrap_or(90);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::compute_baseline(&conn, days).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_baseline(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<db::BaselineEntry>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_baseline_profile(&conn).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Write the baseline profile to `path` for `cmd_import_baseline` on
+/// another install.
+#[tauri::command]
+async fn cmd_export_baseline(state: tauri::State<'_, AppState>, path: String) -> Result<String, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        let entries = db::get_baseline_profile(&conn)?;
+        if entries.is_empty() {
+            return Err(AbyssError::NotFound("No baseline has been computed yet".into()));
+        }
+        let export = db::BaselineExport {
+            format: db::BASELINE_EXPORT_FORMAT.to_string(),
+            version: db::BASELINE_EXPORT_VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            entries,
+        };
+        let json = serde_json::to_string_pretty(&export)
+            .map_err(|e| AbyssError::from(e).context("JSON serialization failed"))?;
+        if let Some(parent) = std::path::Path::new(&path).parent() {
+            if !parent.exists() {
+                return Err(AbyssError::InvalidInput(format!(
+                    "Export directory does not exist: {}",
+                    parent.display()
+                )));
+            }
+        }
+        std::fs::write(&path, &json).map_err(|e| AbyssError::from(e).context("Failed to write baseline"))?;
+        Ok(format!("Exported {} baseline buckets to {}", export.entries.len(), path))
+    })
+    .await?
+}
+
+/// Load a profile written by `cmd_export_baseline`.  It replaces any earlier
+/// import and is pooled with locally recorded data, so anomaly detection
+/// starts from the learned profile rather than from zero.
+#[tauri::command]
+async fn cmd_import_baseline(state: tauri::State<'_, AppState>, path: String) -> Result<u32, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let json = std::fs::read_to_string(&path).map_err(|e| AbyssError::from(e).context("Failed to read baseline"))?;
+        let export: db::BaselineExport = serde_json::from_str(&json)
+            .map_err(|e| AbyssError::InvalidInput(format!("Not a valid baseline export: {e}")))?;
+        validate_baseline_export(&export)?;
+        let conn = db::open_database(&db_path)?;
+        db::import_baseline(&conn, &export).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+fn validate_baseline_export(export: &db::BaselineExport) -> Result<(), AbyssError> {
+    if export.format != db::BASELINE_EXPORT_FORMAT {
+        return Err(AbyssError::InvalidInput("Not an Abyss baseline export".into()));
+    }
+    if export.version > db::BASELINE_EXPORT_VERSION {
+        return Err(AbyssError::InvalidInput(format!(
+            "Baseline export version {} is newer than this version of Abyss supports",
+            export.version
+        )));
+    }
+    let mut seen = HashSet::new();
+    for e in &export.entries {
+        let values = [
+            e.avg_bps,
+            e.stddev_bps,
+            e.avg_flows,
+            e.stddev_flows,
+            e.avg_latency_ms,
+            e.stddev_latency,
+        ];
+        let valid = (0..24).contains(&e.hour_of_day)
+            && (0..7).contains(&e.day_of_week)
+            && e.sample_count > 0
+            && values.iter().all(|v| v.is_finite() && *v >= 0.0);
+        if !valid || !seen.insert((e.hour_of_day, e.day_of_week)) {
+            return Err(AbyssError::InvalidInput(format!(
+                "Invalid or duplicate baseline bucket (hour {}, day {})",
+                e.hour_of_day, e.day_of_week
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn cmd_detect_anomalies(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<db::Anomaly>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::detect_anomalies(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_get_health_score(
+    state: tauri::State<'_, AppState>,
+    hours: Option<u32>,
+) -> Result<db::HealthScore, AbyssError> {
+    let db_path = state.db_path.clone();
+    let h = hours.unwrap_or(24);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::compute_health_score(&conn, h).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_search_sessions(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<db::SessionInfo>, AbyssError> {
+    let db_path = state.db_path.clone();
+    let lim = limit.unwrap_or(50);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::search_sessions(&conn, &query, lim).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Full-text search across sessions, destinations and processes.
+#[tauri::command]
+async fn cmd_search_everything(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<db::SearchHit>, AbyssError> {
+    let db_path = state.db_path.clone();
+    let lim = limit.unwrap_or(50).min(500);
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::search_everything(&conn, &query, lim).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_update_session_tags(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    tags: Vec<String>,
+) -> Result<(), AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::update_session_tags(&conn, &session_id, &tags).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_export_session_csv(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    path: String,
+) -> Result<String, AbyssError> {
+    let db_path = state.db_path.clone();
+    let anonymizer = anonymize::Anonymizer::for_exports(&state.settings.lock_or_recover("settings"), &db_path)?;
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        let (session, flow_count, csv) = session_csv(&conn, &session_id, anonymizer.as_ref())?;
+
+        // Ensure parent directory exists
+        if let Some(parent) = std::path::Path::new(&path).parent() {
+            if !parent.exists() {
+                return Err(AbyssError::InvalidInput(format!(
+                    "Export directory does not exist: {}",
+                    parent.display()
+                )));
+            }
+        }
+
+        std::fs::write(&path, &csv).map_err(|e| AbyssError::from(e).context("Failed to write CSV"))?;
+        Ok(format!(
+            "Exported {} flows from '{}' to {}",
+            flow_count,
+            session.name,
+            path
+        ))
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_export_session_json(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    path: String,
+) -> Result<String, AbyssError> {
+    let db_path = state.db_path.clone();
+    let anonymizer = anonymize::Anonymizer::for_exports(&state.settings.lock_or_recover("settings"), &db_path)?;
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        let payload = session_bundle(&conn, &session_id, anonymizer.as_ref())?;
+
+        let json = serde_json::to_string_pretty(&payload)
+            .map_err(|e| AbyssError::from(e).context("JSON serialization failed"))?;
+
+        // Ensure parent directory exists
+        if let Some(parent) = std::path::Path::new(&path).parent() {
+            if !parent.exists() {
+                return Err(AbyssError::InvalidInput(format!(
+                    "Export directory does not exist: {}",
+                    parent.display()
+                )));
+            }
+        }
+
+        std::fs::write(&path, &json).map_err(|e| AbyssError::from(e).context("Failed to write JSON"))?;
+        Ok(format!(
+            "Exported session '{}' to {}",
+            payload.session.name, path
+        ))
+    })
+    .await?
+}
+
+/// Ingest a bundle written by `cmd_export_session_json` as a new session.
+/// Returns the new session id.
+#[tauri::command]
+async fn cmd_import_session_json(state: tauri::State<'_, AppState>, path: String) -> Result<String, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let json = std::fs::read_to_string(&path)
+            .map_err(|e| AbyssError::from(e).context(&format!("Failed to read {path}")))?;
+        let bundle: db::SessionBundle = serde_json::from_str(&json)
+            .map_err(|e| AbyssError::InvalidInput(format!("Not a valid session export: {e}")))?;
+        validate_session_bundle(&bundle)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let conn = db::open_database(&db_path)?;
+        db::import_session_bundle(&conn, &bundle, &id)?;
+        println!(
+            "[Abyss] Imported session '{}' as {id} ({} frames, {} flow snapshots)",
+            bundle.session.name,
+            bundle.frames.len(),
+            bundle.flows.len()
+        );
+        Ok(id)
+    })
+    .await?
+}
+
+fn validate_session_bundle(bundle: &db::SessionBundle) -> Result<(), AbyssError> {
+    let s = &bundle.session;
+    let bad_time = |ts: &str| chrono::DateTime::parse_from_rfc3339(ts).is_err();
+    if bad_time(&s.started_at) || s.ended_at.as_deref().is_some_and(bad_time) {
+        return Err(AbyssError::InvalidInput("Session export has an invalid start or end time".into()));
+    }
+    let totals = [
+        s.total_bytes_up,
+        s.total_bytes_down,
+        s.peak_bps,
+        s.avg_latency_ms,
+        s.local_lat,
+        s.local_lng,
+    ];
+    if !totals.iter().all(|v| v.is_finite()) {
+        return Err(AbyssError::InvalidInput("Session export has non-numeric totals".into()));
+    }
+    for f in &bundle.frames {
+        let values = [f.t, f.bps, f.upload_bps, f.download_bps, f.latency_ms];
+        if !values.iter().all(|v| v.is_finite() && *v >= 0.0) {
+            return Err(AbyssError::InvalidInput(format!("Session export has an invalid frame at t={}", f.t)));
+        }
+    }
+    for f in &bundle.flows {
+        let values = [f.bps, f.rtt, f.frame_t.unwrap_or(0.0)];
+        if f.flow_id.is_empty() || f.dst_ip.is_empty() || !values.iter().all(|v| v.is_finite()) {
+            return Err(AbyssError::InvalidInput(format!(
+                "Session export has an invalid flow snapshot '{}'",
+                f.flow_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+// ─── Data budgets ───────────────────────────────────────────────────────────
+
+/// Set the data budget for `period` and `scope` (a zero limit removes it).
+/// Returns the budget with usage already recorded this period.
+#[tauri::command]
+async fn cmd_set_data_budget(
+    state: tauri::State<'_, AppState>,
+    period: alerts::QuotaPeriod,
+    bytes_limit: f64,
+    scope: budgets::BudgetScope,
+) -> Result<Option<budgets::DataBudget>, AbyssError> {
+    if !bytes_limit.is_finite() || bytes_limit < 0.0 {
+        return Err(AbyssError::InvalidInput("bytesLimit must be zero or a positive number of bytes".into()));
+    }
+    let scope = match scope {
+        budgets::BudgetScope::Process { process } if process.trim().is_empty() => {
+            return Err(AbyssError::InvalidInput("A per-process budget needs a process name".into()));
+        }
+        budgets::BudgetScope::Process { process } => budgets::BudgetScope::Process {
+            process: process.trim().to_string(),
+        },
+        global => global,
+    };
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::set_data_budget(&conn, period, &scope, bytes_limit, chrono::Utc::now()).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Every budget with its progress through the current period.
+#[tauri::command]
+async fn cmd_get_budget_status(state: tauri::State<'_, AppState>) -> Result<Vec<budgets::DataBudget>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_data_budgets(&conn, chrono::Utc::now()).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+// ─── Alert rules ────────────────────────────────────────────────────────────
+
+#[tauri::command]
+fn cmd_list_alert_rules(state: tauri::State<'_, AppState>) -> Result<Vec<alerts::AlertRule>, AbyssError> {
+    Ok(state.alert_rules.lock_or_recover("alert_rules").clone())
+}
+
+/// Create a rule; any `id` in the payload is replaced with a new one.
+#[tauri::command]
+async fn cmd_create_alert_rule(
+    state: tauri::State<'_, AppState>,
+    mut rule: alerts::AlertRule,
+) -> Result<alerts::AlertRule, AbyssError> {
+    rule.validate()?;
+    rule.id = uuid::Uuid::new_v4().to_string();
+    save_alert_rule(&state, rule).await
+}
+
+#[tauri::command]
+async fn cmd_update_alert_rule(
+    state: tauri::State<'_, AppState>,
+    rule: alerts::AlertRule,
+) -> Result<alerts::AlertRule, AbyssError> {
+    rule.validate()?;
+    let exists = state
+        .alert_rules
+        .lock_or_recover("alert_rules")
+        .iter()
+        .any(|r| r.id == rule.id);
+    if !exists {
+        return Err(AbyssError::NotFound(format!("Alert rule '{}' not found", rule.id)));
+    }
+    save_alert_rule(&state, rule).await
+}
+
+/// Persist `rule`, then publish it to the monitor loop.
+async fn save_alert_rule(
+    state: &AppState,
+    rule: alerts::AlertRule,
+) -> Result<alerts::AlertRule, AbyssError> {
+    let db_path = state.db_path.clone();
+    let stored = rule.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::save_alert_rule(&conn, &stored).map_err(AbyssError::from)
+    })
+    .await??;
+
+    let mut rules = state.alert_rules.lock_or_recover("alert_rules");
+    match rules.iter_mut().find(|r| r.id == rule.id) {
+        Some(existing) => *existing = rule.clone(),
+        None => rules.push(rule.clone()),
+    }
+    Ok(rule)
+}
+
+#[tauri::command]
+async fn cmd_delete_alert_rule(state: tauri::State<'_, AppState>, id: String) -> Result<bool, AbyssError> {
+    let db_path = state.db_path.clone();
+    let rule_id = id.clone();
+    let deleted = tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::delete_alert_rule(&conn, &rule_id).map_err(AbyssError::from)
+    })
+    .await??;
+    state.alert_rules.lock_or_recover("alert_rules").retain(|r| r.id != id);
+    Ok(deleted)
+}
+
+/// Snooze one rule for `duration_secs` (0 lifts the snooze).  It keeps
+/// being evaluated and recorded, but isn't delivered.  Persisted.
+#[tauri::command]
+async fn cmd_snooze_alert(
+    state: tauri::State<'_, AppState>,
+    rule_id: String,
+    duration_secs: u64,
+) -> Result<notify::MuteState, AbyssError> {
+    let exists = state
+        .alert_rules
+        .lock_or_recover("alert_rules")
+        .iter()
+        .any(|r| r.id == rule_id);
+    if !exists {
+        return Err(AbyssError::NotFound(format!("Alert rule '{rule_id}' not found")));
+    }
+    let until = snooze_deadline(duration_secs)?;
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        let mute = &mut settings.alert_mute;
+        mute.prune(chrono::Utc::now());
+        match until {
+            Some(until) => mute.snoozed.insert(rule_id, until),
+            None => mute.snoozed.remove(&rule_id),
+        };
+        settings.clone()
+    };
+    let mute = snapshot.alert_mute.clone();
+    commit_settings(&state, snapshot).await?;
+    Ok(mute)
+}
+
+/// Mute all alert delivery, for `duration_secs` or until unmuted.  Persisted.
+#[tauri::command]
+async fn cmd_set_alert_mute(
+    state: tauri::State<'_, AppState>,
+    muted: bool,
+    duration_secs: Option<u64>,
+) -> Result<notify::MuteState, AbyssError> {
+    let until = match (muted, duration_secs) {
+        (true, Some(secs)) if secs > 0 => snooze_deadline(secs)?,
+        _ => None,
+    };
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        let mute = &mut settings.alert_mute;
+        mute.prune(chrono::Utc::now());
+        mute.muted = muted;
+        mute.muted_until = until;
+        settings.clone()
+    };
+    let mute = snapshot.alert_mute.clone();
+    commit_settings(&state, snapshot).await?;
+    Ok(mute)
+}
+
+#[tauri::command]
+fn cmd_get_alert_mute(state: tauri::State<'_, AppState>) -> Result<notify::MuteState, AbyssError> {
+    let mut mute = state.settings.lock_or_recover("settings").alert_mute.clone();
+    mute.prune(chrono::Utc::now());
+    Ok(mute)
+}
+
+#[tauri::command]
+fn cmd_get_alert_templates(state: tauri::State<'_, AppState>) -> Result<notify::AlertTemplates, AbyssError> {
+    Ok(state.settings.lock_or_recover("settings").alert_templates.clone())
+}
+
+/// Replace the notification/payload templates.  Persisted.
+#[tauri::command]
+async fn cmd_set_alert_templates(
+    state: tauri::State<'_, AppState>,
+    templates: notify::AlertTemplates,
+) -> Result<(), AbyssError> {
+    templates.validate()?;
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        settings.alert_templates = templates;
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await
+}
+
+/// Render `templates` (default: the saved ones) against a sample alert.
+#[tauri::command]
+fn cmd_preview_alert_templates(
+    state: tauri::State<'_, AppState>,
+    templates: Option<notify::AlertTemplates>,
+) -> Result<notify::RenderedAlert, AbyssError> {
+    let templates =
+        templates.unwrap_or_else(|| state.settings.lock_or_recover("settings").alert_templates.clone());
+    templates.render(&notify::sample_event())
+}
+
+#[tauri::command]
+fn cmd_list_push_targets(state: tauri::State<'_, AppState>) -> Result<Vec<notify::PushTarget>, AbyssError> {
+    Ok(state.settings.lock_or_recover("settings").push_targets.clone())
+}
+
+/// Add a push target (empty `id`) or replace the one with the same id.
+#[tauri::command]
+async fn cmd_list_webhooks(state: tauri::State<'_, AppState>) -> Result<Vec<webhooks::Webhook>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::list_webhooks(&conn).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Register an endpoint for `events`.  Payloads are signed with `secret`, or
+/// with a generated one returned here.
+#[tauri::command]
+async fn cmd_add_webhook(
+    state: tauri::State<'_, AppState>,
+    url: String,
+    events: Vec<webhooks::WebhookEvent>,
+    secret: Option<String>,
+) -> Result<webhooks::Webhook, AbyssError> {
+    let hook = webhooks::Webhook::new(url, events, secret)?;
+    let db_path = state.db_path.clone();
+    let stored = hook.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::insert_webhook(&conn, &stored).map_err(AbyssError::from)
+    })
+    .await??;
+    Ok(hook)
+}
+
+#[tauri::command]
+async fn cmd_remove_webhook(state: tauri::State<'_, AppState>, id: String) -> Result<bool, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::delete_webhook(&conn, &id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+#[tauri::command]
+async fn cmd_save_push_target(
+    state: tauri::State<'_, AppState>,
+    mut target: notify::PushTarget,
+) -> Result<notify::PushTarget, AbyssError> {
+    target.validate()?;
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        if target.id.is_empty() {
+            target.id = uuid::Uuid::new_v4().to_string();
+            settings.push_targets.push(target.clone());
+        } else {
+            let existing = settings
+                .push_targets
+                .iter_mut()
+                .find(|t| t.id == target.id)
+                .ok_or_else(|| AbyssError::NotFound(format!("Push target '{}' not found", target.id)))?;
+            *existing = target.clone();
+        }
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(target)
+}
+
+#[tauri::command]
+async fn cmd_delete_push_target(state: tauri::State<'_, AppState>, id: String) -> Result<bool, AbyssError> {
+    let snapshot = {
+        let mut settings = state.settings.lock_or_recover("settings");
+        let before = settings.push_targets.len();
+        settings.push_targets.retain(|t| t.id != id);
+        if settings.push_targets.len() == before {
+            return Ok(false);
+        }
+        settings.clone()
+    };
+    commit_settings(&state, snapshot).await?;
+    Ok(true)
+}
+
+/// Send a sample alert through `target` (saved or not) using the current templates.
+#[tauri::command]
+async fn cmd_test_push_target(
+    state: tauri::State<'_, AppState>,
+    target: notify::PushTarget,
+) -> Result<(), AbyssError> {
+    target.validate()?;
+    let templates = state.settings.lock_or_recover("settings").alert_templates.clone();
+    let sample = notify::sample_event();
+    let rendered = templates.render(&sample)?;
+    target.send(&rendered, sample.severity).await
+}
+
+/// `now + secs` as RFC 3339, or `None` for 0.
+fn snooze_deadline(secs: u64) -> Result<Option<String>, AbyssError> {
+    if secs == 0 {
+        return Ok(None);
+    }
+    let secs = i64::try_from(secs)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .ok_or_else(|| AbyssError::InvalidInput(format!("Duration {secs}s is out of range")))?;
+    chrono::Utc::now()
+        .checked_add_signed(secs)
+        .map(|t| Some(t.to_rfc3339()))
+        .ok_or_else(|| AbyssError::InvalidInput("Duration is out of range".into()))
+}
+
+/// Alert firings matching `filters`, newest first.
+#[tauri::command]
+async fn cmd_get_alert_history(
+    state: tauri::State<'_, AppState>,
+    filters: Option<db::AlertHistoryFilters>,
+) -> Result<Vec<alerts::AlertEvent>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_alert_history(&conn, &filters.unwrap_or_default()).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Flow open/close events, newest first.
+#[tauri::command]
+async fn cmd_get_flow_events(
+    state: tauri::State<'_, AppState>,
+    filters: Option<db::FlowEventFilters>,
+) -> Result<Vec<lifecycle::FlowEvent>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_flow_events(&conn, &filters.unwrap_or_default()).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Per-rule firing counts and noise flags.
+#[tauri::command]
+async fn cmd_get_alert_stats(state: tauri::State<'_, AppState>) -> Result<Vec<db::AlertRuleStats>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_alert_stats(&conn).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+// ─── Watchlist ──────────────────────────────────────────────────────────────
+
+#[tauri::command]
+fn cmd_get_watchlist(state: tauri::State<'_, AppState>) -> Result<watchlist::Watchlist, AbyssError> {
+    Ok(state.watchlist.lock_or_recover("watchlist").clone())
+}
+
+/// Replace the watched countries (two-letter codes) and ASNs.  New flows to
+/// them are tagged, raise a "Watchlist" alert and are counted per session.
+#[tauri::command]
+async fn cmd_set_watchlist(
+    state: tauri::State<'_, AppState>,
+    countries: Vec<String>,
+    asns: Vec<String>,
+) -> Result<watchlist::Watchlist, AbyssError> {
+    let list = watchlist::Watchlist::new(countries, asns)?;
+    let db_path = state.db_path.clone();
+    let stored = list.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::save_watchlist(&conn, &stored).map_err(AbyssError::from)
+    })
+    .await??;
+    *state.watchlist.lock_or_recover("watchlist") = list.clone();
+    Ok(list)
+}
+
+/// How often flows to each watched entity appeared during a session.
+#[tauri::command]
+async fn cmd_get_watch_hits(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<db::WatchHitCount>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_watch_hits(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+// ─── Threat intel ───────────────────────────────────────────────────────────
+
+/// Blocklisted destinations seen during a session.
+#[tauri::command]
+async fn cmd_get_threat_hits(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<db::ThreatHit>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_threat_hits(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Entry counts, update times and download errors of the loaded threat feeds.
+#[tauri::command]
+fn cmd_get_threat_feeds(state: tauri::State<'_, AppState>) -> Result<Vec<threat::FeedStatus>, AbyssError> {
+    Ok(state.threats.lock_or_recover("threats").feeds.clone())
+}
+
+// ─── WHOIS ──────────────────────────────────────────────────────────────────
+
+/// Network name, CIDR, registration country and abuse contact of a public
+/// IP from RDAP.  Answers are cached in `whois_cache` for a week; while
+/// privacy mode is on only cached answers are returned.
+#[tauri::command]
+async fn cmd_lookup_whois(state: tauri::State<'_, AppState>, ip: String) -> Result<rdap::WhoisInfo, AbyssError> {
+    let addr: std::net::IpAddr = ip
+        .trim()
+        .parse()
+        .map_err(|_| AbyssError::InvalidInput(format!("'{ip}' is not an IP address")))?;
+    let ip = addr.to_string();
+    if is_private_ip(&ip) {
+        return Err(AbyssError::InvalidInput(format!("{ip} is a private address with no public registration")));
+    }
+
+    let db_path = state.db_path.clone();
+    let key = ip.clone();
+    let cached = tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_cached_whois(&conn, &key, rdap::WHOIS_CACHE_TTL_DAYS).map_err(AbyssError::from)
+    })
+    .await??;
+    if let Some(info) = cached {
+        return Ok(info);
+    }
+    if state.settings.lock_or_recover("settings").privacy_mode {
+        return Err(AbyssError::Conflict(
+            "Privacy mode is on; WHOIS lookups would send the address to a remote RDAP server".into(),
+        ));
+    }
+
+    let info = rdap::lookup(addr).await.map_err(|e| e.context(&format!("WHOIS lookup for {ip}")))?;
+    let db_path = state.db_path.clone();
+    let stored = info.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::store_whois(&conn, &stored).map_err(AbyssError::from)
+    })
+    .await??;
+    Ok(info)
+}
+
+// ─── Connectivity ───────────────────────────────────────────────────────────
+
+/// IPv4/IPv6 share per destination org and failing-IPv6 findings for one
+/// session, or across all sessions when `session_id` is omitted.
+#[tauri::command]
+async fn cmd_get_ip_family_report(
+    state: tauri::State<'_, AppState>,
+    session_id: Option<String>,
+) -> Result<db::IpFamilyReport, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_ip_family_report(&conn, session_id.as_deref()).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+/// Paths whose MTU probes found a reduced or black-holed MTU during a session.
+#[tauri::command]
+async fn cmd_get_mtu_findings(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<db::MtuFindingRecord>, AbyssError> {
+    let db_path = state.db_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        db::get_mtu_findings(&conn, &session_id).map_err(AbyssError::from)
+    })
+    .await?
+}
+
+// ─── Application entry point ────────────────────────────────────────────────
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(
+            tauri_plugin_autostart::Builder::new()
+                .arg(tray::BACKGROUND_ARG)
+                .build(),
+        )
+        .invoke_handler(tauri::generate_handler![
+            fetch_cables,
+            cmd_set_live_filters,
+            cmd_get_live_filters,
+            cmd_set_process_filter,
+            cmd_get_process_filter,
+            cmd_set_stream_subscription,
+            cmd_clear_stream_subscription,
+            cmd_list_stream_subscriptions,
+            cmd_get_schema_info,
+            cmd_negotiate_schema,
+            cmd_request_keyframe,
+            cmd_get_monitor_state,
+            cmd_pause_monitoring,
+            cmd_resume_monitoring,
+            cmd_get_diagnostics,
+            cmd_pause_writer,
+            cmd_resume_writer,
+            cmd_restore_database,
+            cmd_get_settings,
+            cmd_set_settings,
+            cmd_set_privacy_mode,
+            cmd_set_redact_at_rest,
+            cmd_anonymize_session,
+            cmd_set_persistence_profile,
+            cmd_get_persistence_exclusions,
+            cmd_set_persistence_exclusions,
+            cmd_set_latency_probes,
+            cmd_set_geoip_db,
+            cmd_set_geo_provider,
+            cmd_set_capture_mode,
+            cmd_get_capture_status,
+            cmd_start_ws_server,
+            cmd_stop_ws_server,
+            cmd_get_ws_server_status,
+            cmd_start_api_server,
+            cmd_stop_api_server,
+            cmd_get_api_server_status,
+            cmd_list_interfaces,
+            cmd_set_monitored_interfaces,
+            cmd_list_sessions,
+            cmd_list_session_thumbnails,
+            cmd_get_session,
+            cmd_delete_session,
+            cmd_merge_sessions,
+            cmd_get_session_frames,
+            cmd_get_session_flows,
+            cmd_get_flow_timeseries,
+            cmd_query_flows,
+            cmd_get_session_destinations,
+            cmd_get_process_usage,
+            cmd_get_global_stats,
+            cmd_update_session_meta,
+            cmd_start_session,
+            cmd_stop_session,
+            cmd_get_startup_prompt,
+            cmd_answer_startup_prompt,
+            cmd_set_monitor_only,
+            cmd_get_monitor_only,
+            cmd_set_autostart,
+            cmd_get_autostart,
+            cmd_shutdown_writer,
+            cmd_get_writer_stats,
+            cmd_get_db_lock_status,
+            cmd_get_db_encryption,
+            cmd_set_db_encryption,
+            cmd_unlock_database,
+            cmd_get_current_session,
+            cmd_export_session_csv,
+            cmd_export_session_json,
+            cmd_import_session_json,
+            cmd_get_playback_data,
+            cmd_get_playback_index,
+            cmd_get_playback_chunk,
+            cmd_get_country_playback,
+            cmd_export_timelapse,
+            cmd_add_session_marker,
+            cmd_get_session_markers,
+            cmd_delete_session_marker,
+            cmd_get_daily_usage,
+            cmd_get_hourly_usage,
+            cmd_get_top_destinations,
+            cmd_set_destination_label,
+            cmd_compare_ranges,
+            cmd_get_country_breakdown,
+            cmd_get_org_breakdown,
+            cmd_get_top_apps,
+            cmd_backfill_os_usage,
+            cmd_get_process_tree,
+            cmd_get_process_metadata,
+            cmd_get_destination_history,
+            cmd_get_process_history,
+            cmd_get_session_insights,
+            cmd_get_session_qos,
+            cmd_get_router_comparison,
+            cmd_probe_router,
+            cmd_get_route_events,
+            cmd_get_network_changes,
+            cmd_get_default_routes,
+            cmd_render_session_card,
+            cmd_generate_session_report,
+            cmd_run_retention,
+            cmd_compact_sessions,
+            cmd_rollup_sessions,
+            cmd_set_frame_retention,
+            cmd_delete_all_sessions,
+            cmd_get_database_path,
+            cmd_open_data_folder,
+            cmd_compute_baseline,
+            cmd_get_baseline,
+            cmd_export_baseline,
+            cmd_import_baseline,
+            cmd_detect_anomalies,
+            cmd_get_health_score,
+            cmd_search_sessions,
+            cmd_search_everything,
+            cmd_update_session_tags,
+            cmd_list_alert_rules,
+            cmd_create_alert_rule,
+            cmd_update_alert_rule,
+            cmd_delete_alert_rule,
+            cmd_set_data_budget,
+            cmd_get_budget_status,
+            cmd_get_alert_history,
+            cmd_get_flow_events,
+            cmd_snooze_alert,
+            cmd_set_alert_mute,
+            cmd_get_alert_mute,
+            cmd_get_alert_templates,
+            cmd_set_alert_templates,
+            cmd_preview_alert_templates,
+            cmd_list_push_targets,
+            cmd_save_push_target,
+            cmd_delete_push_target,
+            cmd_test_push_target,
+            cmd_get_alert_stats,
+            cmd_get_watchlist,
+            cmd_set_watchlist,
+            cmd_get_watch_hits,
+            cmd_get_threat_hits,
+            cmd_get_threat_feeds,
+            cmd_lookup_whois,
+            cmd_get_ip_family_report,
+            cmd_get_mtu_findings,
+            cmd_list_webhooks,
+            cmd_add_webhook,
+            cmd_remove_webhook,
+        ])
+        .on_window_event(|window, event| match event {
+            // In background mode closing the main window only hides it
+            tauri::WindowEvent::CloseRequested { api, .. }
+                if window.label() == "main" && background_mode(window.app_handle()) =>
+            {
+                api.prevent_close();
+                let _ = window.hide();
+                println!("[Abyss] Window hidden; still monitoring in the background");
+            }
+            // Per-window state only; the writer stops on app exit
+            tauri::WindowEvent::Destroyed => {
+                if let Some(state) = window.try_state::<AppState>() {
+                    state
+                        .stream_subscriptions
+                        .lock_or_recover("stream_subscriptions")
+                        .remove(window.label());
+                    state
+                        .schema_versions
+                        .lock_or_recover("schema_versions")
+                        .remove(window.label());
+                }
+            }
+            _ => {}
+        })
+        .setup(|app| {
+            println!("╔════════════════════════════════════════╗");
+            println!("║   ABYSS — Live Network Monitor         ║");
+            println!("╚════════════════════════════════════════╝");
+
+            // Resolve database path in app-local data directory
+            let app_data = app
+                .path()
+                .app_local_data_dir()
+                .expect("Failed to resolve app data directory");
+            std::fs::create_dir_all(&app_data).ok();
+            let db_path = app_data.join("sessions.db");
+            println!("[Abyss] Database: {}", db_path.display());
+
+            let (initial_settings, initial_rules, initial_watchlist) = db::open_database(&db_path)
+                .map(|conn| {
+                    let rules = db::list_alert_rules(&conn).unwrap_or_else(|e| {
+                        eprintln!("[Abyss] Failed to load alert rules: {e}");
+                        Vec::new()
+                    });
+                    let watchlist = db::load_watchlist(&conn).unwrap_or_else(|e| {
+                        eprintln!("[Abyss] Failed to load watchlist: {e}");
+                        watchlist::Watchlist::default()
+                    });
+                    (settings::load(&conn), rules, watchlist)
+                })
+                .unwrap_or_default();
+            units::set_current(initial_settings.units);
+            address::set_strict(initial_settings.strict_address_parsing);
+            if initial_settings.privacy_mode {
+                println!("[Abyss] Privacy mode enabled — remote geo lookups disabled");
+            }
+
+            // Create writer channel
+            let (writer_tx, writer_rx) = writer::create_channel();
+            let launched_at_login = std::env::args().any(|arg| arg == tray::BACKGROUND_ARG);
+            let monitor_only =
+                initial_settings.startup_for(launched_at_login) == settings::StartupSession::MonitorOnly;
+            if monitor_only {
+                let _ = writer_tx.send(writer::WriteCommand::SetMonitorOnly { enabled: true });
+            }
+
+            // Register shared state (session starts inside monitor_loop after geo detection)
+            app.manage(AppState {
+                writer_tx: writer_tx.clone(),
+                writer_thread: Mutex::new(None),
+                writer_stats: Mutex::new(writer::WriterStats::default()),
+                db_path: db_path.clone(),
+                db_lock: Mutex::new(dblock::DbLockStatus::Pending),
+                current_session_id: Mutex::new(None),
+                local_geo: Mutex::new(LocalGeoCache::default()),
+                live_filters: Mutex::new(LiveFilters::default()),
+                process_filter: Mutex::new(ProcessFilter::default()),
+                stream_subscriptions: Mutex::new(HashMap::new()),
+                schema_versions: Mutex::new(HashMap::new()),
+                keyframe_requested: AtomicBool::new(false),
+                startup_prompt: AtomicBool::new(false),
+                launched_at_login,
+                monitor_only: AtomicBool::new(monitor_only),
+                last_frame: Mutex::new(None),
+                geo_status: Mutex::new(GeoPipelineStatus::default()),
+                settings: Mutex::new(initial_settings.clone()),
+                settings_watch: tokio::sync::watch::Sender::new(initial_settings.clone()),
+                capture: capture::Capture::new(),
+                geo_db: Arc::new(Mutex::new(geo::GeoDatabases::default())),
+                interfaces: Mutex::new(Vec::new()),
+                alert_rules: Mutex::new(initial_rules),
+                watchlist: Mutex::new(initial_watchlist),
+                threats: Mutex::new(Arc::new(threat::ThreatIndex::default())),
+                paused_since: Mutex::new(None),
+                monitor_resumed: tokio::sync::Notify::new(),
+                public_ip_recheck: tokio::sync::Notify::new(),
+                ws_server: server::WsServer::new(),
+                api_server: api::ApiServer::new(),
+            });
+            {
+                let state = app.state::<AppState>();
+                let mut geo_db = state.geo_db.lock_or_recover("geo_db");
+                for path in &initial_settings.geoip_db_paths {
+                    if let Err(e) = geo_db.load(std::path::Path::new(path)) {
+                        eprintln!("[Abyss] {e}");
+                    }
+                }
+            }
+            if initial_settings.capture_mode == capture::CaptureMode::Packet {
+                app.state::<AppState>().capture.set_mode(capture::CaptureMode::Packet);
+            }
+            if let (true, Some(token)) = (initial_settings.ws_server_enabled, initial_settings.ws_server_token.clone()) {
+                let config = server::WsConfig {
+                    port: initial_settings.ws_server_port,
+                    allow_lan: initial_settings.ws_server_lan,
+                    token,
+                };
+                if let Err(e) = app.state::<AppState>().ws_server.start(config) {
+                    eprintln!("[Abyss] {e}");
+                }
+            }
+            if let (true, Some(token)) = (initial_settings.api_server_enabled, initial_settings.api_server_token.clone()) {
+                let state = app.state::<AppState>();
+                if let Err(e) = state.api_server.start(initial_settings.api_server_port, token, state.db_path.clone()) {
+                    eprintln!("[Abyss] {e}");
+                }
+            }
+
+            // Spawn writer thread (dedicated OS thread for blocking SQLite I/O)
+            let writer_db_path = db_path.clone();
+            let baseline_db_path = db_path.clone();
+            let error_handle = app.handle().clone();
+            let prune_handle = app.handle().clone();
+            let budget_handle = app.handle().clone();
+            let lock_handle = app.handle().clone();
+            let stats_handle = app.handle().clone();
+            let webhook_db_path = db_path.clone();
+            let writer_thread = std::thread::spawn(move || {
+                writer::writer_thread(
+                    writer_rx,
+                    writer_db_path,
+                    Box::new(move |err| {
+                        let _ = error_handle.emit("writer-error", err);
+                    }),
+                    Box::new(move |session_id| webhooks::session_ended(webhook_db_path.clone(), session_id)),
+                    Box::new(move |report| {
+                        let _ = prune_handle.emit("retention-pruned", report);
+                    }),
+                    Box::new(move |event| {
+                        let _ = budget_handle.emit("budget-threshold", event);
+                    }),
+                    Box::new(move |status| {
+                        if let Some(state) = lock_handle.try_state::<AppState>() {
+                            *state.db_lock.lock_or_recover("db_lock") = status.clone();
+                        }
+                        let _ = lock_handle.emit("db-lock-status", status);
+                    }),
+                    Box::new(move |stats: writer::WriterStats| {
+                        let Some(state) = stats_handle.try_state::<AppState>() else {
+                            return;
+                        };
+                        let previous = std::mem::replace(
+                            &mut *state.writer_stats.lock_or_recover("writer_stats"),
+                            stats.clone(),
+                        );
+                        if stats.dropped_frames > previous.dropped_frames {
+                            let _ = stats_handle.emit(
+                                "writer-backpressure",
+                                WriterBackpressure {
+                                    dropped_frames: stats.dropped_frames - previous.dropped_frames,
+                                    total_dropped_frames: stats.dropped_frames,
+                                    queue_depth: stats.queue_depth,
+                                },
+                            );
+                        }
+                    }),
+                );
+            });
+            *app
+                .state::<AppState>()
+                .writer_thread
+                .lock_or_recover("writer_thread") = Some(writer_thread);
+            tauri::async_runtime::spawn(webhooks::health_watch(db_path.clone()));
+            tauri::async_runtime::spawn(router::watch(app.handle().clone(), writer_tx.clone()));
+            let host = Arc::new(AppHost::new(app.handle().clone()));
+            tauri::async_runtime::spawn(routes::watch(host.clone(), writer_tx.clone()));
+            tauri::async_runtime::spawn(publicip::watch(host.clone(), writer_tx.clone()));
+            tauri::async_runtime::spawn(threat::watch(host, app_data.join("threat-feeds")));
+
+            // Spawn monitor loop (auto-starts a session after geo detection)
+            let handle = app.handle().clone();
+            let monitor_tx = writer_tx.clone();
+            tauri::async_runtime::spawn(async move {
+                monitor_loop(handle, monitor_tx).await;
+            });
+
+            // Spawn auto-baseline recomputation (weekly, first run after 60s)
+            // and frame retention (every 6 hours)
+            tauri::async_runtime::spawn(async move {
+                // Initial delay to let the app settle
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                loop {
+                    // Check if baseline needs recomputing (last update > 7 days ago)
+                    let needs_update = {
+                        let path = baseline_db_path.clone();
+                        tokio::task::spawn_blocking(move || {
+                            if let Ok(conn) = db::open_database(&path) {
+                                let last_update: String = conn
+                                    .query_row(
+                                        "SELECT COALESCE(MAX(updated_at), '2000-01-01') FROM baseline_profile",
+                                        [],
+                                        |row| row.get(0),
+                                    )
+                                    .unwrap_or_else(|_| "2000-01-01".to_string());
+                                // Check if older than 7 days
+                                let days_old: f64 = conn
+                                    .query_row(
+                                        "SELECT julianday('now') - julianday(?1)",
+                                        rusqlite::params![last_update],
+                                        |row| row.get(0),
+                                    )
+                                    .unwrap_or(999.0);
+                                days_old > 7.0
+                            } else {
+                                false
+                            }
+                        })
+                        .await
+                        .unwrap_or(false)
+                    };
+
+                    if needs_update {
+                        let path = baseline_db_path.clone();
+                        let _ = tokio::task::spawn_blocking(move || {
+                            if let Ok(conn) = db::open_database(&path) {
+                                match db::compute_baseline(&conn, 90) {
+                                    Ok(n) => println!("[Abyss] Auto-baseline recomputed: {n} buckets"),
+                                    Err(e) => eprintln!("[Abyss] Auto-baseline failed: {e}"),
+                                }
+                            }
+                        })
+                        .await;
+                    }
+
+                    // Frame retention: compact old sessions to summary-only
+                    let path = baseline_db_path.clone();
+                    let _ = tokio::task::spawn_blocking(move || {
+                        if let Ok(conn) = db::open_database(&path) {
+                            let stored = settings::load(&conn);
+                            let days = stored.frame_retention_days;
+                            if days > 0 {
+                                match db::compact_old_sessions(&conn, days) {
+                                    Ok(0) => {}
+                                    Ok(n) => println!("[Abyss] Compacted {n} session(s) older than {days} days to summary-only"),
+                                    Err(e) => eprintln!("[Abyss] Frame retention failed: {e}"),
+                                }
+                            }
+                            // Rollup: keep older sessions at 1-minute/1-hour resolution
+                            let days = stored.frame_rollup_days;
+                            if days > 0 {
+                                match db::rollup_old_frames(&conn, days) {
+                                    Ok(0) => {}
+                                    Ok(n) => println!("[Abyss] Rolled up frames of {n} session(s) older than {days} days"),
+                                    Err(e) => eprintln!("[Abyss] Frame rollup failed: {e}"),
+                                }
+                            }
+                        }
+                    })
+                    .await;
+
+                    // Sleep for 6 hours before checking again
+                    tokio::time::sleep(std::time::Duration::from_secs(6 * 3600)).await;
+                }
+            });
+
+            // Tray icon for running minimized as a background recorder
+            if let Err(e) = tray::install(app.handle()) {
+                eprintln!("[Abyss] Failed to create tray icon: {e}");
+            }
+            if launched_at_login {
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.hide();
+                }
+                println!("[Abyss] Started at login — running in the background");
+            }
+
+            #[cfg(debug_assertions)]
+            {
+                let window = app
+                    .get_webview_window("main")
+                    .expect("Failed to get main window");
+                window.open_devtools();
+            }
+
+            Ok(())
+        })
+        .build(tauri::generate_context!())
+        .expect("Failed to build Abyss application")
+        .run(|app, event| {
+            if let tauri::RunEvent::ExitRequested { code, api, .. } = event {
+                // The last window closing (`code` None) doesn't end a
+                // background recorder; Quit and `app.exit` do
+                if code.is_none() && background_mode(app) {
+                    api.prevent_exit();
+                    return;
+                }
+                if let Some(state) = app.try_state::<AppState>() {
+                    shutdown_writer(&state);
+                }
+            }
+        });
+}
@@ -0,0 +1,102 @@
+use crate::{FlowRate, ParsedConnection};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Name recorded for sockets without a known owner (matches `process_usage`).
+const UNATTRIBUTED: &str = "System";
+
+// ─── Per-process rates ──────────────────────────────────────────────────────
+
+/// One process's traffic over the last tick, in bits/s.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessRate {
+    pub process: String,
+    pub tx_bps: f64,
+    pub rx_bps: f64,
+    pub sockets: u32,
+    /// Every socket had its own byte counters; otherwise part of the rate is
+    /// a share of the interface remainder.
+    pub measured: bool,
+    /// App the process belongs to (see `proctree`), from its first socket.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_group: Option<String>,
+}
+
+/// Split this tick's traffic by owning process (the socket table's PID).
+///
+/// Sockets with byte counters contribute their measured rate.  What the
+/// interface counters (`iface_bps`, tx/rx) saw beyond that is shared evenly
+/// among the sockets without counters — on Windows without elevation, where
+/// TCP extended stats can't be switched on, that is every socket, so per-process
+/// totals still add up to what the adapters measured instead of a per-port
+/// guess.  Empty without an interface sample; the writer then falls back to
+/// per-flow estimates.
+pub fn attribute(
+    connections: &[ParsedConnection],
+    rates: &HashMap<String, FlowRate>,
+    process_names: &HashMap<u32, String>,
+    app_groups: &HashMap<u32, String>,
+    iface_bps: Option<(f64, f64)>,
+) -> Vec<ProcessRate> {
+    let Some((iface_tx, iface_rx)) = iface_bps else {
+        return Vec::new();
+    };
+
+    // Several local sockets can share one remote endpoint (and its rate)
+    let mut per_key: HashMap<String, u32> = HashMap::new();
+    for conn in connections {
+        *per_key.entry(flow_key(conn)).or_default() += 1;
+    }
+
+    let mut by_process: HashMap<&str, ProcessRate> = HashMap::new();
+    let mut unmeasured: Vec<&str> = Vec::new();
+    let (mut measured_tx, mut measured_rx) = (0.0, 0.0);
+    for conn in connections {
+        let name = process_names
+            .get(&conn.pid)
+            .filter(|_| conn.pid > 0)
+            .map_or(UNATTRIBUTED, String::as_str);
+        let entry = by_process.entry(name).or_insert_with(|| ProcessRate {
+            process: name.to_string(),
+            tx_bps: 0.0,
+            rx_bps: 0.0,
+            sockets: 0,
+            measured: true,
+            app_group: app_groups.get(&conn.pid).cloned(),
+        });
+        entry.sockets += 1;
+        let key = flow_key(conn);
+        match rates.get(&key) {
+            Some(rate) => {
+                let share = per_key.get(&key).copied().unwrap_or(1).max(1) as f64;
+                entry.tx_bps += rate.tx_bps / share;
+                entry.rx_bps += rate.rx_bps / share;
+                measured_tx += rate.tx_bps / share;
+                measured_rx += rate.rx_bps / share;
+            }
+            None => {
+                entry.measured = false;
+                unmeasured.push(name);
+            }
+        }
+    }
+
+    if !unmeasured.is_empty() {
+        let n = unmeasured.len() as f64;
+        let tx_share = (iface_tx - measured_tx).max(0.0) / n;
+        let rx_share = (iface_rx - measured_rx).max(0.0) / n;
+        for name in unmeasured {
+            if let Some(entry) = by_process.get_mut(name) {
+                entry.tx_bps += tx_share;
+                entry.rx_bps += rx_share;
+            }
+        }
+    }
+
+    by_process.into_values().collect()
+}
+
+fn flow_key(conn: &ParsedConnection) -> String {
+    format!("{}:{}:{}", conn.remote_ip, conn.remote_port, conn.proto)
+}
@@ -0,0 +1,150 @@
+//! `abyss-cli` — record and export sessions without the desktop app, e.g.
+//! on a server: `abyss-cli record --out sessions.db`, then
+//! `abyss-cli export --session <id> --format csv`.
+
+use abyss_lib::headless::{self, AbyssError, ExportFormat, RecordOptions};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEFAULT_DB: &str = "sessions.db";
+
+const USAGE: &str = "Record and export Abyss sessions without the desktop app.
+
+Usage:
+  abyss-cli record [--out FILE] [--name NAME] [--duration SECS]
+                   [--geo-db FILE]... [--interface NAME]... [--geo local|remote]
+  abyss-cli export --session ID [--db FILE] [--format csv|json] [--out FILE]
+  abyss-cli sessions [--db FILE] [--limit N]
+
+The database defaults to ./sessions.db.  `record` runs until Ctrl+C (or
+--duration) and ends the session cleanly.  Destinations are placed with
+--geo-db only unless `--geo remote` allows the geo provider saved in the
+app (and tracking the public address).  `export` writes to stdout unless
+--out is given.";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("abyss-cli: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), AbyssError> {
+    let Some((command, rest)) = args.split_first() else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    let flags = Flags::parse(rest)?;
+    match command.as_str() {
+        "record" => {
+            flags.only(&["out", "name", "duration", "geo-db", "interface", "geo"])?;
+            let duration = flags
+                .one("duration")
+                .map(|secs| {
+                    secs.parse::<u64>()
+                        .map(Duration::from_secs)
+                        .map_err(|_| AbyssError::InvalidInput(format!("--duration expects seconds, got '{secs}'")))
+                })
+                .transpose()?;
+            let remote_lookups = match flags.one("geo").unwrap_or("local") {
+                "local" => false,
+                "remote" => true,
+                other => {
+                    return Err(AbyssError::InvalidInput(format!("--geo expects local or remote, got '{other}'")))
+                }
+            };
+            let options = RecordOptions {
+                db_path: PathBuf::from(flags.one("out").unwrap_or(DEFAULT_DB)),
+                name: flags.one("name").map(str::to_string),
+                geo_databases: flags.all("geo-db").map(PathBuf::from).collect(),
+                interfaces: flags.all("interface").map(str::to_string).collect(),
+                duration,
+                remote_lookups,
+            };
+            let stop = headless::stop_on_interrupt();
+            let id = headless::record(options, stop)?;
+            println!("{id}");
+            Ok(())
+        }
+        "export" => {
+            flags.only(&["session", "db", "format", "out"])?;
+            let session = flags
+                .one("session")
+                .ok_or_else(|| AbyssError::InvalidInput("export needs --session ID".into()))?;
+            let format = flags.one("format").unwrap_or("csv");
+            let format = ExportFormat::parse(format)
+                .ok_or_else(|| AbyssError::InvalidInput(format!("Unknown format '{format}' (expected csv or json)")))?;
+            let db = PathBuf::from(flags.one("db").unwrap_or(DEFAULT_DB));
+            let output = headless::export_session(&db, session, format)?;
+            match flags.one("out") {
+                Some(path) => {
+                    std::fs::write(path, output).map_err(|e| AbyssError::from(e).context("Failed to write export"))?;
+                    eprintln!("Exported session {session} to {path}");
+                }
+                None => print!("{output}"),
+            }
+            Ok(())
+        }
+        "sessions" => {
+            flags.only(&["db", "limit"])?;
+            let limit = match flags.one("limit") {
+                Some(n) => n
+                    .parse()
+                    .map_err(|_| AbyssError::InvalidInput(format!("--limit expects a number, got '{n}'")))?,
+                None => 20,
+            };
+            let db = PathBuf::from(flags.one("db").unwrap_or(DEFAULT_DB));
+            for s in headless::list_sessions(&db, limit)? {
+                println!(
+                    "{}  {}  {:>8}  {}",
+                    s.id,
+                    s.started_at.get(..19).unwrap_or(&s.started_at),
+                    s.status,
+                    s.name
+                );
+            }
+            Ok(())
+        }
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        other => Err(AbyssError::InvalidInput(format!("Unknown command '{other}'\n\n{USAGE}"))),
+    }
+}
+
+/// `--name value` pairs, in order; repeatable flags keep every value.
+struct Flags(Vec<(String, String)>);
+
+impl Flags {
+    fn parse(args: &[String]) -> Result<Self, AbyssError> {
+        let mut flags = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                return Err(AbyssError::InvalidInput(format!("Unexpected argument '{arg}'")));
+            };
+            let value = args
+                .next()
+                .ok_or_else(|| AbyssError::InvalidInput(format!("--{name} needs a value")))?;
+            flags.push((name.to_string(), value.clone()));
+        }
+        Ok(Self(flags))
+    }
+
+    fn only(&self, allowed: &[&str]) -> Result<(), AbyssError> {
+        match self.0.iter().find(|(name, _)| !allowed.contains(&name.as_str())) {
+            Some((name, _)) => Err(AbyssError::InvalidInput(format!("Unknown option --{name}"))),
+            None => Ok(()),
+        }
+    }
+
+    fn one<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        self.all(name).last()
+    }
+
+    fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0.iter().filter(move |(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
@@ -0,0 +1,106 @@
+use crate::alerts::{local_midnight, QuotaPeriod};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Percentages of a budget that emit `budget-threshold` when crossed.
+pub const THRESHOLDS: [u8; 2] = [80, 100];
+
+// ─── Budgets ────────────────────────────────────────────────────────────────
+
+/// What a budget counts.  Stored as `process` ('' for global).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum BudgetScope {
+    /// All recorded traffic.
+    Global,
+    /// Traffic attributed to one process (matched case-insensitively).
+    Process { process: String },
+}
+
+impl BudgetScope {
+    pub fn process(&self) -> &str {
+        match self {
+            BudgetScope::Global => "",
+            BudgetScope::Process { process } => process,
+        }
+    }
+
+    pub fn from_process(process: String) -> Self {
+        if process.is_empty() {
+            BudgetScope::Global
+        } else {
+            BudgetScope::Process { process }
+        }
+    }
+}
+
+/// A data-usage budget and its consumption in the current period; the
+/// result of `cmd_get_budget_status`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataBudget {
+    pub id: i64,
+    pub period: QuotaPeriod,
+    pub scope: BudgetScope,
+    pub bytes_limit: f64,
+    /// Local midnight the current period started at (RFC 3339, UTC).
+    pub period_start: String,
+    /// Bytes up and down recorded since `period_start`.
+    pub used_bytes: f64,
+    /// `used_bytes / bytes_limit`; above 1 once exceeded.
+    pub fraction: f64,
+    pub remaining_bytes: f64,
+    /// Highest threshold already reported this period (0 if none).
+    pub notified_pct: u8,
+}
+
+/// A budget crossed one of `THRESHOLDS`; payload of `budget-threshold`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetEvent {
+    pub budget_id: i64,
+    pub period: QuotaPeriod,
+    pub scope: BudgetScope,
+    pub threshold_pct: u8,
+    pub used_bytes: f64,
+    pub bytes_limit: f64,
+    pub period_start: String,
+}
+
+/// Start of the `period` containing `now`, in local time.
+pub fn period_start(period: QuotaPeriod, now: DateTime<Utc>) -> String {
+    local_midnight(period.start(now.with_timezone(&Local).date_naive()))
+}
+
+/// Highest threshold `used_bytes` has reached.
+pub fn level(used_bytes: f64, bytes_limit: f64) -> u8 {
+    THRESHOLDS
+        .iter()
+        .rev()
+        .copied()
+        .find(|&pct| used_bytes >= bytes_limit * pct as f64 / 100.0)
+        .unwrap_or(0)
+}
+
+/// The threshold to report after usage grew to `used_bytes`, if it is past
+/// one not yet reported this period.
+pub fn crossed(used_bytes: f64, bytes_limit: f64, notified_pct: u8) -> Option<u8> {
+    let reached = level(used_bytes, bytes_limit);
+    (reached > notified_pct).then_some(reached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_each_threshold_once_per_period() {
+        let limit = 1_000.0;
+        assert_eq!(crossed(799.0, limit, 0), None);
+        assert_eq!(crossed(800.0, limit, 0), Some(80));
+        assert_eq!(crossed(900.0, limit, 80), None);
+        // A burst straight past the limit skips the 80% event
+        assert_eq!(crossed(1_200.0, limit, 0), Some(100));
+        assert_eq!(crossed(5_000.0, limit, 100), None);
+    }
+}
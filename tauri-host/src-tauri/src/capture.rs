@@ -0,0 +1,566 @@
+use crate::locks::LockExt;
+use crate::ParsedConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// ─── Capture mode ───────────────────────────────────────────────────────────
+
+/// Where the monitor loop gets its connection list from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaptureMode {
+    /// Poll the OS socket table (or netstat) every `NETSTAT_POLL_MS`.
+    #[default]
+    Poller,
+    /// Count every packet on the primary interface (requires the `capture`
+    /// feature and raw-socket privileges).
+    Packet,
+}
+
+/// Reported by `cmd_set_capture_mode` / `cmd_get_capture_status`.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureStatus {
+    /// Mode the user asked for.
+    pub requested: CaptureMode,
+    /// Mode actually in use — `poller` whenever packet capture isn't running.
+    pub active: CaptureMode,
+    /// Whether this build includes the packet capture backend.
+    pub compiled: bool,
+    /// Whether the process can open raw sockets (`None` if it can't be
+    /// determined without trying).
+    pub privileged: Option<bool>,
+    pub interface: Option<String>,
+    pub packets: u64,
+    /// Why packet capture fell back to the poller, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Owns the packet capture thread, if one is running.
+pub struct Capture {
+    requested: Mutex<CaptureMode>,
+    last_error: Mutex<Option<String>>,
+    #[cfg(feature = "capture")]
+    session: Mutex<Option<engine::Session>>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self {
+            requested: Mutex::new(CaptureMode::Poller),
+            last_error: Mutex::new(None),
+            #[cfg(feature = "capture")]
+            session: Mutex::new(None),
+        }
+    }
+
+    /// Switch modes.  A failed packet-capture start is not an error: the
+    /// monitor keeps polling and the reason is returned in `error`.
+    pub fn set_mode(&self, mode: CaptureMode) -> CaptureStatus {
+        *self.requested.lock_or_recover("capture_requested") = mode;
+        *self.last_error.lock_or_recover("capture_error") = None;
+        self.stop();
+
+        if mode == CaptureMode::Packet {
+            if let Err(e) = self.start() {
+                eprintln!("[Abyss] Packet capture unavailable ({e}) — using socket table poller");
+                *self.last_error.lock_or_recover("capture_error") = Some(e);
+            }
+        }
+        self.status()
+    }
+
+    pub fn status(&self) -> CaptureStatus {
+        #[cfg_attr(not(feature = "capture"), allow(unused_mut))]
+        let mut status = CaptureStatus {
+            requested: *self.requested.lock_or_recover("capture_requested"),
+            active: CaptureMode::Poller,
+            compiled: cfg!(feature = "capture"),
+            privileged: has_raw_socket_privilege(),
+            interface: None,
+            packets: 0,
+            error: self.last_error.lock_or_recover("capture_error").clone(),
+        };
+        #[cfg(feature = "capture")]
+        if let Some(session) = self.session.lock_or_recover("capture_session").as_ref() {
+            match session.failure() {
+                Some(e) => status.error = Some(e),
+                None => status.active = CaptureMode::Packet,
+            }
+            status.interface = Some(session.interface.clone());
+            status.packets = session.packets();
+        }
+        status
+    }
+
+    /// DNS answers seen on the wire since the last call (empty when capture
+    /// isn't running).
+    pub fn dns_answers(&self) -> Vec<crate::dns::DnsAnswer> {
+        #[cfg(feature = "capture")]
+        if let Some(session) = self.session.lock_or_recover("capture_session").as_ref() {
+            return session.dns_answers();
+        }
+        Vec::new()
+    }
+
+    /// Live flows from packet capture, or `None` when the poller should be
+    /// used instead (capture off, not compiled in, or its thread died).
+    pub(crate) fn connections(&self) -> Option<Vec<ParsedConnection>> {
+        #[cfg(feature = "capture")]
+        {
+            let mut session = self.session.lock_or_recover("capture_session");
+            if let Some(e) = session.as_ref().and_then(|s| s.failure()) {
+                eprintln!("[Abyss] Packet capture stopped ({e}) — falling back to socket table poller");
+                *self.last_error.lock_or_recover("capture_error") = Some(e);
+                if let Some(dead) = session.take() {
+                    dead.stop();
+                }
+            }
+            session.as_ref().map(|s| s.connections())
+        }
+        #[cfg(not(feature = "capture"))]
+        {
+            None
+        }
+    }
+
+    #[cfg(feature = "capture")]
+    fn start(&self) -> Result<(), String> {
+        let session = engine::Session::start()?;
+        println!("[Abyss] Packet capture started on {}", session.interface);
+        *self.session.lock_or_recover("capture_session") = Some(session);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "capture"))]
+    fn start(&self) -> Result<(), String> {
+        Err("this build does not include packet capture (enable the `capture` feature)".into())
+    }
+
+    fn stop(&self) {
+        #[cfg(feature = "capture")]
+        if let Some(session) = self.session.lock_or_recover("capture_session").take() {
+            session.stop();
+            println!("[Abyss] Packet capture stopped");
+        }
+    }
+}
+
+// ─── Privileges ─────────────────────────────────────────────────────────────
+
+/// CAP_NET_RAW from the effective capability set (root has all bits set).
+#[cfg(target_os = "linux")]
+fn has_raw_socket_privilege() -> Option<bool> {
+    const CAP_NET_RAW: u32 = 13;
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let hex = status.lines().find_map(|l| l.strip_prefix("CapEff:"))?.trim();
+    let caps = u64::from_str_radix(hex, 16).ok()?;
+    Some(caps & (1 << CAP_NET_RAW) != 0)
+}
+
+/// BPF device permissions (macOS) and Npcap's admin-only mode (Windows) can
+/// only be discovered by opening the device.
+#[cfg(not(target_os = "linux"))]
+fn has_raw_socket_privilege() -> Option<bool> {
+    None
+}
+
+// ─── Packet engine ──────────────────────────────────────────────────────────
+
+#[cfg(feature = "capture")]
+mod engine {
+    use crate::dns::{self, DnsAnswer};
+    use crate::locks::LockExt;
+    use crate::ParsedConnection;
+    use pnet_datalink::{Channel, Config, NetworkInterface};
+    use pnet_packet::ethernet::{EtherTypes, EthernetPacket};
+    use pnet_packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+    use pnet_packet::ipv4::Ipv4Packet;
+    use pnet_packet::ipv6::Ipv6Packet;
+    use pnet_packet::tcp::{TcpFlags, TcpPacket};
+    use pnet_packet::udp::UdpPacket;
+    use pnet_packet::Packet;
+    use std::collections::HashMap;
+    use std::net::IpAddr;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+
+    /// Flows with no packets for this long are dropped from the table.
+    const FLOW_IDLE_SECS: u64 = 30;
+    /// Read timeout so the capture thread notices `stop` promptly.
+    const READ_TIMEOUT_MS: u64 = 250;
+    /// DNS answers buffered between monitor ticks; older ones are dropped.
+    const MAX_PENDING_DNS: usize = 4096;
+    /// Outbound bytes buffered per flow while reassembling a ClientHello
+    /// (one maximum-size TLS record).
+    const MAX_HELLO_BYTES: usize = 5 + 16_384;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+    struct FlowKey {
+        proto: &'static str,
+        local_ip: IpAddr,
+        local_port: u16,
+        remote_ip: IpAddr,
+        remote_port: u16,
+    }
+
+    struct FlowStats {
+        bytes_out: u64,
+        bytes_in: u64,
+        packets: u64,
+        closed: bool,
+        last_seen: Instant,
+        /// Server name from the flow's TLS ClientHello.
+        sni: Option<String>,
+        /// Outbound payload so far, while the first segments may still hold a
+        /// ClientHello; `None` once it's been found or ruled out.
+        hello: Option<Vec<u8>>,
+    }
+
+    /// One parsed packet: who sent it to whom and how big it was on the wire.
+    struct PacketInfo<'a> {
+        proto: &'static str,
+        src: IpAddr,
+        dst: IpAddr,
+        src_port: u16,
+        dst_port: u16,
+        /// TCP FIN or RST seen.
+        fin: bool,
+        /// Answers, when this is a DNS response.
+        dns: Vec<DnsAnswer>,
+        /// TCP payload.
+        payload: &'a [u8],
+    }
+
+    type FlowTable = Arc<Mutex<HashMap<FlowKey, FlowStats>>>;
+    type DnsQueue = Arc<Mutex<Vec<DnsAnswer>>>;
+
+    pub struct Session {
+        pub interface: String,
+        table: FlowTable,
+        dns: DnsQueue,
+        stop: Arc<AtomicBool>,
+        packets: Arc<AtomicU64>,
+        failure: Arc<Mutex<Option<String>>>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Session {
+        pub fn start() -> Result<Self, String> {
+            let iface = primary_interface().ok_or("no active network interface found")?;
+            let config = Config {
+                read_timeout: Some(Duration::from_millis(READ_TIMEOUT_MS)),
+                read_buffer_size: 65_536,
+                promiscuous: false,
+                ..Config::default()
+            };
+            let mut rx = match pnet_datalink::channel(&iface, config) {
+                Ok(Channel::Ethernet(_tx, rx)) => rx,
+                Ok(_) => return Err(format!("unsupported channel type on {}", iface.name)),
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    return Err(format!("permission denied opening {} — run elevated or grant CAP_NET_RAW", iface.name))
+                }
+                Err(e) => return Err(format!("failed to open {}: {e}", iface.name)),
+            };
+
+            let local_ips: Vec<IpAddr> = iface.ips.iter().map(|n| n.ip()).collect();
+            // Interfaces without a MAC (tun, wireguard) deliver bare IP packets.
+            let ethernet = iface.mac.is_some_and(|m| !m.is_zero());
+            let table: FlowTable = Arc::new(Mutex::new(HashMap::new()));
+            let dns: DnsQueue = Arc::new(Mutex::new(Vec::new()));
+            let stop = Arc::new(AtomicBool::new(false));
+            let packets = Arc::new(AtomicU64::new(0));
+            let failure = Arc::new(Mutex::new(None));
+
+            let thread = {
+                let (table, dns, stop, packets, failure) =
+                    (table.clone(), dns.clone(), stop.clone(), packets.clone(), failure.clone());
+                std::thread::Builder::new()
+                    .name("abyss-capture".into())
+                    .spawn(move || {
+                        while !stop.load(Ordering::Relaxed) {
+                            let data = match rx.next() {
+                                Ok(data) => data,
+                                Err(e) if matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock) => continue,
+                                Err(e) => {
+                                    *failure.lock_or_recover("capture_failure") = Some(e.to_string());
+                                    return;
+                                }
+                            };
+                            packets.fetch_add(1, Ordering::Relaxed);
+                            let parsed = if ethernet { parse_ethernet(data) } else { parse_ip(data) };
+                            if let Some(mut info) = parsed {
+                                record(&table, &local_ips, &info, data.len() as u64);
+                                if !info.dns.is_empty() {
+                                    let mut pending = dns.lock_or_recover("capture_dns");
+                                    pending.append(&mut info.dns);
+                                    let excess = pending.len().saturating_sub(MAX_PENDING_DNS);
+                                    pending.drain(..excess);
+                                }
+                            }
+                        }
+                    })
+                    .map_err(|e| format!("failed to spawn capture thread: {e}"))?
+            };
+
+            Ok(Self {
+                interface: iface.name,
+                table,
+                dns,
+                stop,
+                packets,
+                failure,
+                thread: Some(thread),
+            })
+        }
+
+        pub fn stop(mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+
+        pub fn failure(&self) -> Option<String> {
+            self.failure.lock_or_recover("capture_failure").clone()
+        }
+
+        pub fn packets(&self) -> u64 {
+            self.packets.load(Ordering::Relaxed)
+        }
+
+        pub fn dns_answers(&self) -> Vec<DnsAnswer> {
+            std::mem::take(&mut *self.dns.lock_or_recover("capture_dns"))
+        }
+
+        /// Snapshot of live public flows with cumulative counters, in the same
+        /// shape the socket table poller produces.  Expires idle flows.
+        pub(crate) fn connections(&self) -> Vec<ParsedConnection> {
+            let mut table = self.table.lock_or_recover("capture_table");
+            table.retain(|_, s| s.last_seen.elapsed() < Duration::from_secs(FLOW_IDLE_SECS));
+            table
+                .iter()
+                .filter(|(k, _)| !crate::is_private_ip(&crate::connections::format_ip(k.remote_ip)))
+                .map(|(k, s)| ParsedConnection {
+                    proto: k.proto.to_string(),
+                    local_ip: crate::connections::format_ip(k.local_ip),
+                    local_port: k.local_port,
+                    remote_ip: crate::connections::format_ip(k.remote_ip),
+                    remote_port: k.remote_port,
+                    state: match (k.proto, s.closed) {
+                        ("tcp", false) => "ESTABLISHED".into(),
+                        ("tcp", true) => "CLOSE_WAIT".into(),
+                        _ => "STATELESS".into(),
+                    },
+                    pid: 0,
+                    bytes_out: Some(s.bytes_out),
+                    bytes_in: Some(s.bytes_in),
+                    packets: Some(s.packets),
+                    sni: s.sni.clone(),
+                })
+                .collect()
+        }
+    }
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// First interface that is up, not loopback, and has an address.
+    fn primary_interface() -> Option<NetworkInterface> {
+        pnet_datalink::interfaces()
+            .into_iter()
+            .filter(|i| i.is_up() && !i.is_loopback() && !i.ips.is_empty())
+            .max_by_key(|i| i.ips.iter().any(|n| n.is_ipv4()))
+    }
+
+    fn record(table: &FlowTable, local_ips: &[IpAddr], info: &PacketInfo, len: u64) {
+        let (key, outbound) = if local_ips.contains(&info.src) {
+            (
+                FlowKey {
+                    proto: info.proto,
+                    local_ip: info.src,
+                    local_port: info.src_port,
+                    remote_ip: info.dst,
+                    remote_port: info.dst_port,
+                },
+                true,
+            )
+        } else if local_ips.contains(&info.dst) {
+            (
+                FlowKey {
+                    proto: info.proto,
+                    local_ip: info.dst,
+                    local_port: info.dst_port,
+                    remote_ip: info.src,
+                    remote_port: info.src_port,
+                },
+                false,
+            )
+        } else {
+            return;
+        };
+
+        let mut table = table.lock_or_recover("capture_table");
+        let stats = table.entry(key).or_insert(FlowStats {
+            bytes_out: 0,
+            bytes_in: 0,
+            packets: 0,
+            closed: false,
+            last_seen: Instant::now(),
+            sni: None,
+            hello: Some(Vec::new()),
+        });
+        if outbound {
+            stats.bytes_out += len;
+        } else {
+            stats.bytes_in += len;
+        }
+        stats.packets += 1;
+        stats.closed |= info.fin;
+        stats.last_seen = Instant::now();
+
+        if outbound && !info.payload.is_empty() {
+            if let Some(hello) = stats.hello.as_mut() {
+                hello.extend_from_slice(info.payload);
+                match client_hello_sni(hello) {
+                    Hello::Incomplete if hello.len() < MAX_HELLO_BYTES => {}
+                    Hello::Sni(name) => {
+                        stats.sni = Some(name);
+                        stats.hello = None;
+                    }
+                    _ => stats.hello = None,
+                }
+            }
+        }
+    }
+
+    // Payloads are sliced from the captured buffer rather than taken from
+    // pnet's views, whose `payload()` only lives as long as the view.
+
+    fn parse_ethernet(data: &[u8]) -> Option<PacketInfo<'_>> {
+        let frame = EthernetPacket::new(data)?;
+        match frame.get_ethertype() {
+            EtherTypes::Ipv4 | EtherTypes::Ipv6 => parse_ip(data.get(EthernetPacket::minimum_packet_size()..)?),
+            _ => None,
+        }
+    }
+
+    fn parse_ip(data: &[u8]) -> Option<PacketInfo<'_>> {
+        match data.first()? >> 4 {
+            4 => {
+                let packet = Ipv4Packet::new(data)?;
+                let (src, dst) = (IpAddr::V4(packet.get_source()), IpAddr::V4(packet.get_destination()));
+                let header = packet.get_header_length() as usize * 4;
+                let end = (packet.get_total_length() as usize).clamp(header, data.len().max(header));
+                parse_transport(packet.get_next_level_protocol(), data.get(header..end)?, src, dst)
+            }
+            6 => {
+                let packet = Ipv6Packet::new(data)?;
+                let (src, dst) = (IpAddr::V6(packet.get_source()), IpAddr::V6(packet.get_destination()));
+                let header = Ipv6Packet::minimum_packet_size();
+                let end = (header + packet.get_payload_length() as usize).min(data.len());
+                parse_transport(packet.get_next_header(), data.get(header..end)?, src, dst)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_transport(next: IpNextHeaderProtocol, payload: &[u8], src: IpAddr, dst: IpAddr) -> Option<PacketInfo<'_>> {
+        let mut dns = Vec::new();
+        let mut segment: &[u8] = &[];
+        let (proto, src_port, dst_port, fin) = match next {
+            IpNextHeaderProtocols::Tcp => {
+                let tcp = TcpPacket::new(payload)?;
+                let fin = tcp.get_flags() & (TcpFlags::FIN | TcpFlags::RST) != 0;
+                let offset = (tcp.get_data_offset() as usize * 4).min(payload.len());
+                segment = &payload[offset..];
+                ("tcp", tcp.get_source(), tcp.get_destination(), fin)
+            }
+            IpNextHeaderProtocols::Udp => {
+                let udp = UdpPacket::new(payload)?;
+                if udp.get_source() == 53 {
+                    dns = dns::parse_response(udp.payload());
+                }
+                ("udp", udp.get_source(), udp.get_destination(), false)
+            }
+            IpNextHeaderProtocols::Icmp | IpNextHeaderProtocols::Icmpv6 => ("icmp", 0, 0, false),
+            _ => return None,
+        };
+        Some(PacketInfo {
+            proto,
+            src,
+            dst,
+            src_port,
+            dst_port,
+            fin,
+            dns,
+            payload: segment,
+        })
+    }
+
+    // ─── TLS ────────────────────────────────────────────────────────────────
+
+    enum Hello {
+        Sni(String),
+        /// Looks like a ClientHello that continues in later segments.
+        Incomplete,
+        /// Not a ClientHello, or one without a server name.
+        NotFound,
+    }
+
+    /// Server name from a TLS ClientHello at the start of `buf`.
+    fn client_hello_sni(buf: &[u8]) -> Hello {
+        // Record header: type 22 (handshake), version 3.x, length; then
+        // handshake type 1 (ClientHello)
+        let header_ok = buf.first().is_none_or(|&b| b == 0x16)
+            && buf.get(1).is_none_or(|&b| b == 0x03)
+            && buf.get(5).is_none_or(|&b| b == 0x01);
+        if !header_ok {
+            return Hello::NotFound;
+        }
+        let Some(record_len) = buf.get(3..5).map(|b| 5 + u16::from_be_bytes([b[0], b[1]]) as usize) else {
+            return Hello::Incomplete;
+        };
+        match scan_sni(&buf[..buf.len().min(record_len)]) {
+            Some(Some(name)) => Hello::Sni(name),
+            None if buf.len() < record_len => Hello::Incomplete,
+            _ => Hello::NotFound,
+        }
+    }
+
+    /// Walk the ClientHello to its server_name extension.  `None` when the
+    /// buffer ends first; `Some(None)` when there is no usable name.
+    fn scan_sni(b: &[u8]) -> Option<Option<String>> {
+        let u16_at = |at: usize| b.get(at..at + 2).map(|v| u16::from_be_bytes([v[0], v[1]]) as usize);
+        // Record header (5), handshake header (4), version (2), random (32)
+        let mut pos = 43;
+        pos += 1 + *b.get(pos)? as usize; // session id
+        pos += 2 + u16_at(pos)?; // cipher suites
+        pos += 1 + *b.get(pos)? as usize; // compression methods
+        let extensions_end = pos + 2 + u16_at(pos)?;
+        pos += 2;
+        while pos + 4 <= extensions_end {
+            let (kind, len) = (u16_at(pos)?, u16_at(pos + 2)?);
+            pos += 4;
+            if kind == 0 {
+                // server_name_list length (2), name_type (1), name length (2)
+                let name_len = u16_at(pos + 3)?;
+                let name = b.get(pos + 5..pos + 5 + name_len)?;
+                return Some(
+                    std::str::from_utf8(name)
+                        .ok()
+                        .filter(|n| !n.is_empty())
+                        .map(|n| n.trim_end_matches('.').to_ascii_lowercase()),
+                );
+            }
+            pos += len;
+        }
+        (b.len() >= extensions_end).then_some(None)
+    }
+}
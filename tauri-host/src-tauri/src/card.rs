@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use crate::alerts::format_bps;
+use crate::db::{format_bytes_human, SessionCard};
+
+const WIDTH: f64 = 480.0;
+const HEIGHT: f64 = 270.0;
+const SPARK_X: f64 = 24.0;
+const SPARK_Y: f64 = 110.0;
+const SPARK_W: f64 = 432.0;
+const SPARK_H: f64 = 60.0;
+
+// ─── SVG share card ─────────────────────────────────────────────────────────
+
+/// Render `card` as a self-contained SVG.  Generated here rather than in the
+/// webview so a shared card looks the same whatever the UI is showing.
+pub fn render_svg(card: &SessionCard) -> String {
+    let mut svg = String::with_capacity(4096);
+    let _ = write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="system-ui, sans-serif">"##
+    );
+    let _ = write!(
+        svg,
+        r##"<rect width="{WIDTH}" height="{HEIGHT}" rx="14" fill="#0b1020"/>"##
+    );
+
+    let _ = write!(
+        svg,
+        r##"<text x="24" y="40" font-size="18" font-weight="600" fill="#e6edf7">{}</text>"##,
+        escape(&card.name)
+    );
+    let _ = write!(
+        svg,
+        r##"<text x="24" y="62" font-size="12" fill="#8592a8">{}{}</text>"##,
+        escape(card.started_at.get(..16).unwrap_or(&card.started_at).replace('T', " ").as_str()),
+        card.duration_secs.map(|d| format!(" · {}", format_duration(d))).unwrap_or_default()
+    );
+
+    let stats = [
+        ("Total", card.total_data_human.clone()),
+        ("Peak", format_bps(card.peak_bps)),
+        ("Flows", card.total_flows.to_string()),
+        ("Countries", card.unique_countries.to_string()),
+    ];
+    for (i, (label, value)) in stats.iter().enumerate() {
+        let x = 24.0 + i as f64 * 108.0;
+        let _ = write!(
+            svg,
+            r##"<text x="{x}" y="86" font-size="10" fill="#8592a8">{label}</text><text x="{x}" y="102" font-size="14" font-weight="600" fill="#e6edf7">{}</text>"##,
+            escape(value)
+        );
+    }
+
+    if let Some(points) = sparkline_points(&card.sparkline) {
+        let _ = write!(
+            svg,
+            r##"<polyline points="{points}" fill="none" stroke="#38bdf8" stroke-width="1.5" stroke-linejoin="round"/>"##
+        );
+    }
+
+    let mut y = 196.0;
+    for share in &card.top_countries {
+        let bar = (share.share * 200.0).max(2.0);
+        let _ = write!(
+            svg,
+            r##"<text x="24" y="{y}" font-size="11" fill="#c4cede">{}</text><rect x="64" y="{}" width="{bar:.1}" height="8" rx="2" fill="#6366f1"/><text x="{:.1}" y="{y}" font-size="10" fill="#8592a8">{}</text>"##,
+            escape(&share.label),
+            y - 8.0,
+            72.0 + bar,
+            format_bytes_human(share.bytes)
+        );
+        y += 15.0;
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Scale throughput samples into the sparkline box.  `None` with fewer than
+/// two points (nothing to draw a line through).
+fn sparkline_points(samples: &[f64]) -> Option<String> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let max = samples.iter().cloned().fold(0.0, f64::max);
+    let step = SPARK_W / (samples.len() - 1) as f64;
+    let mut points = String::new();
+    for (i, v) in samples.iter().enumerate() {
+        let ratio = if max > 0.0 { v.max(0.0) / max } else { 0.0 };
+        let x = SPARK_X + i as f64 * step;
+        let y = SPARK_Y + SPARK_H - ratio * SPARK_H;
+        let _ = write!(points, "{}{x:.1},{y:.1}", if i == 0 { "" } else { " " });
+    }
+    Some(points)
+}
+
+pub(crate) fn format_duration(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    match (secs / 3600, (secs % 3600) / 60) {
+        (0, 0) => format!("{secs}s"),
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m}m"),
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,691 @@
+use crate::{is_private_ip, ParsedConnection};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// IANA dynamic port range: the default ephemeral range on Windows and
+/// macOS, and the fallback when Linux's configured range can't be read.
+const DEFAULT_EPHEMERAL_RANGE: (u16, u16) = (49152, 65535);
+
+// ─── Native socket table ────────────────────────────────────────────────────
+
+/// Reads active TCP/UDP connections straight from the OS socket table
+/// (iphlpapi on Windows, `/proc/net` on Linux, libproc on macOS) without
+/// spawning a process.  Listening sockets, unconnected sockets and private
+/// remotes are dropped, matching what the netstat parser used to keep.
+///
+/// Where the platform exposes them, cumulative per-connection byte counters
+/// are filled into `bytes_out`/`bytes_in` (TCP extended stats on Windows,
+/// `ss -ti` on Linux); otherwise they stay `None` and the caller estimates.
+///
+/// Also returns socket usage counted over the whole table, before filtering.
+///
+/// Returns `Err` on unsupported platforms or when the OS API fails, so the
+/// caller can fall back to parsing `netstat`.
+pub fn read_connections() -> Result<(Vec<ParsedConnection>, SocketUsage), String> {
+    let mut connections = platform::read()?;
+    let usage = SocketUsage::of(&connections, ephemeral_range());
+    connections.retain(|c| {
+        c.remote_port != 0
+            && c.state != "LISTENING"
+            && !c.remote_ip.is_empty()
+            && c.remote_ip != "0.0.0.0"
+            && c.remote_ip != "::"
+            && !is_private_ip(&c.remote_ip)
+    });
+    Ok((connections, usage))
+}
+
+// ─── Socket usage ───────────────────────────────────────────────────────────
+
+/// TIME_WAIT and ephemeral port counts for the whole machine, including
+/// loopback and LAN sockets the flow list leaves out.  Load tests that churn
+/// short connections run out of ephemeral ports long before bandwidth.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SocketUsage {
+    /// TCP sockets in TIME_WAIT (macOS doesn't expose these; always 0 there).
+    pub time_wait: u32,
+    /// Distinct local TCP ports in the ephemeral range held by a non-listening socket.
+    pub ephemeral_in_use: u32,
+    /// Size of the ephemeral port range.
+    pub ephemeral_total: u32,
+}
+
+impl SocketUsage {
+    fn of(connections: &[ParsedConnection], (low, high): (u16, u16)) -> Self {
+        let mut ports = HashSet::new();
+        let mut time_wait = 0;
+        for conn in connections.iter().filter(|c| c.proto == "tcp" && c.state != "LISTENING") {
+            if conn.state == "TIME_WAIT" {
+                time_wait += 1;
+            }
+            if (low..=high).contains(&conn.local_port) {
+                ports.insert(conn.local_port);
+            }
+        }
+        Self {
+            time_wait,
+            ephemeral_in_use: ports.len() as u32,
+            ephemeral_total: u32::from(high.saturating_sub(low)) + 1,
+        }
+    }
+
+    /// Share of the ephemeral range in use, 0–100.
+    pub fn ephemeral_pct(&self) -> f64 {
+        if self.ephemeral_total == 0 {
+            0.0
+        } else {
+            self.ephemeral_in_use as f64 * 100.0 / self.ephemeral_total as f64
+        }
+    }
+}
+
+/// The configured local port range (`net.ipv4.ip_local_port_range`).
+#[cfg(target_os = "linux")]
+fn ephemeral_range() -> (u16, u16) {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_local_port_range")
+        .ok()
+        .and_then(|raw| {
+            let mut parts = raw.split_whitespace().map(|p| p.parse::<u16>().ok());
+            match (parts.next()??, parts.next()??) {
+                (low, high) if low <= high => Some((low, high)),
+                _ => None,
+            }
+        })
+        .unwrap_or(DEFAULT_EPHEMERAL_RANGE)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn ephemeral_range() -> (u16, u16) {
+    DEFAULT_EPHEMERAL_RANGE
+}
+
+/// Formats an address the way the rest of the pipeline expects: IPv4-mapped
+/// IPv6 addresses are collapsed to plain IPv4 so geo lookups work.
+#[cfg_attr(
+    not(any(target_os = "linux", target_os = "windows", target_os = "macos")),
+    allow(dead_code)
+)]
+pub fn format_ip(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => v4.to_string(),
+            None => v6.to_string(),
+        },
+        IpAddr::V4(v4) => v4.to_string(),
+    }
+}
+
+/// TCP state names as printed by Windows `netstat`, which downstream code
+/// (flow direction heuristics, `GeoFlow.state`) already understands.
+#[cfg_attr(
+    not(any(target_os = "linux", target_os = "windows", target_os = "macos")),
+    allow(dead_code)
+)]
+fn tcp_state_name(state: TcpState) -> &'static str {
+    match state {
+        TcpState::Established => "ESTABLISHED",
+        TcpState::SynSent => "SYN_SENT",
+        TcpState::SynReceived => "SYN_RECEIVED",
+        TcpState::FinWait1 => "FIN_WAIT_1",
+        TcpState::FinWait2 => "FIN_WAIT_2",
+        TcpState::TimeWait => "TIME_WAIT",
+        TcpState::Closed => "CLOSED",
+        TcpState::CloseWait => "CLOSE_WAIT",
+        TcpState::LastAck => "LAST_ACK",
+        TcpState::Listen => "LISTENING",
+        TcpState::Closing => "CLOSING",
+        TcpState::Unknown => "UNKNOWN",
+    }
+}
+
+#[allow(dead_code)] // not every platform reports every state
+#[derive(Clone, Copy)]
+enum TcpState {
+    Established,
+    SynSent,
+    SynReceived,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Closed,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+    Unknown,
+}
+
+// ─── Linux: /proc/net/{tcp,tcp6,udp,udp6} ───────────────────────────────────
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use std::collections::HashMap;
+
+    pub fn read() -> Result<Vec<ParsedConnection>, String> {
+        let mut connections = Vec::with_capacity(256);
+        let mut inodes: Vec<u64> = Vec::with_capacity(256);
+        let mut any_table = false;
+
+        for (file, proto) in [
+            ("/proc/net/tcp", "tcp"),
+            ("/proc/net/tcp6", "tcp"),
+            ("/proc/net/udp", "udp"),
+            ("/proc/net/udp6", "udp"),
+        ] {
+            let Ok(raw) = std::fs::read_to_string(file) else {
+                continue;
+            };
+            any_table = true;
+            for line in raw.lines().skip(1) {
+                if let Some((conn, inode)) = parse_line(line, proto) {
+                    connections.push(conn);
+                    inodes.push(inode);
+                }
+            }
+        }
+        if !any_table {
+            return Err("/proc/net socket tables are not readable".into());
+        }
+
+        let owners = socket_owners();
+        let counters = tcp_byte_counters();
+        for (conn, inode) in connections.iter_mut().zip(inodes) {
+            conn.pid = owners.get(&inode).copied().unwrap_or(0);
+            if conn.proto == "tcp" {
+                if let Some(&(out, inb)) =
+                    counters.get(&(conn.local_port, conn.remote_ip.clone(), conn.remote_port))
+                {
+                    conn.bytes_out = Some(out);
+                    conn.bytes_in = Some(inb);
+                }
+            }
+        }
+        Ok(connections)
+    }
+
+    /// Cumulative `bytes_acked`/`bytes_received` per established TCP socket,
+    /// keyed by (local port, remote ip, remote port).  Uses `ss -tinH`, which
+    /// reads tcp_info over netlink and needs no privileges; empty if `ss` is
+    /// missing.
+    fn tcp_byte_counters() -> HashMap<(u16, String, u16), (u64, u64)> {
+        let mut counters = HashMap::new();
+        let Ok(output) = std::process::Command::new("ss")
+            .args(["-tinH", "state", "established"])
+            .output()
+        else {
+            return counters;
+        };
+        let raw = String::from_utf8_lossy(&output.stdout);
+
+        // Each socket is a header line followed by an indented tcp_info line.
+        // With a state filter the state column is omitted:
+        // `Recv-Q Send-Q Local:Port Peer:Port`
+        let mut current: Option<(u16, String, u16)> = None;
+        for line in raw.lines() {
+            if !line.starts_with(char::is_whitespace) {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                current = match (parts.get(2), parts.get(3)) {
+                    (Some(local), Some(peer)) => crate::address::parse_pair("ss", line, local, peer).and_then(
+                        |((_, local_port), (peer_ip, peer_port))| {
+                            let peer_ip = peer_ip
+                                .split('%')
+                                .next()
+                                .and_then(|ip| ip.parse::<IpAddr>().ok())
+                                .map(format_ip)?;
+                            Some((local_port, peer_ip, peer_port))
+                        },
+                    ),
+                    _ => None,
+                };
+                continue;
+            }
+            let Some(key) = current.take() else {
+                continue;
+            };
+            let field = |name: &str| -> Option<u64> {
+                line.split_whitespace()
+                    .find_map(|tok| tok.strip_prefix(name))
+                    .and_then(|v| v.parse().ok())
+            };
+            let out = field("bytes_acked:").or_else(|| field("bytes_sent:")).unwrap_or(0);
+            let inb = field("bytes_received:").unwrap_or(0);
+            counters.insert(key, (out, inb));
+        }
+        counters
+    }
+
+    /// `sl local_address rem_address st tx:rx tr:tm retrnsmt uid timeout inode ...`
+    fn parse_line(line: &str, proto: &str) -> Option<(ParsedConnection, u64)> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            return None;
+        }
+        let (local_ip, local_port) = parse_endpoint(fields[1])?;
+        let (remote_ip, remote_port) = parse_endpoint(fields[2])?;
+        let st = u8::from_str_radix(fields[3], 16).ok()?;
+        let inode: u64 = fields[9].parse().ok()?;
+
+        let state = if proto == "tcp" {
+            tcp_state_name(match st {
+                0x01 => TcpState::Established,
+                0x02 => TcpState::SynSent,
+                0x03 => TcpState::SynReceived,
+                0x04 => TcpState::FinWait1,
+                0x05 => TcpState::FinWait2,
+                0x06 => TcpState::TimeWait,
+                0x07 => TcpState::Closed,
+                0x08 => TcpState::CloseWait,
+                0x09 => TcpState::LastAck,
+                0x0A => TcpState::Listen,
+                0x0B => TcpState::Closing,
+                _ => TcpState::Unknown,
+            })
+            .to_string()
+        } else {
+            "STATELESS".to_string()
+        };
+
+        Some((
+            ParsedConnection {
+                proto: proto.to_string(),
+                local_ip: format_ip(local_ip),
+                local_port,
+                remote_ip: format_ip(remote_ip),
+                remote_port,
+                state,
+                pid: 0,
+                bytes_out: None,
+                bytes_in: None,
+                packets: None,
+                sni: None,
+            },
+            inode,
+        ))
+    }
+
+    /// `0100007F:0035` (IPv4) or 32 hex digits + port (IPv6).  Addresses are
+    /// printed as native-endian 32-bit words of the network-order bytes.
+    fn parse_endpoint(s: &str) -> Option<(IpAddr, u16)> {
+        let (addr, port) = s.split_once(':')?;
+        let port = u16::from_str_radix(port, 16).ok()?;
+        let ip = match addr.len() {
+            8 => {
+                let word = u32::from_str_radix(addr, 16).ok()?;
+                IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes()))
+            }
+            32 => {
+                let mut bytes = [0u8; 16];
+                for i in 0..4 {
+                    let word = u32::from_str_radix(&addr[i * 8..i * 8 + 8], 16).ok()?;
+                    bytes[i * 4..i * 4 + 4].copy_from_slice(&word.to_ne_bytes());
+                }
+                IpAddr::V6(Ipv6Addr::from(bytes))
+            }
+            _ => return None,
+        };
+        Some((ip, port))
+    }
+
+    /// Socket inode → owning PID, from `/proc/<pid>/fd/*` → `socket:[inode]`.
+    /// Sockets owned by other users' processes stay unattributed (pid 0).
+    fn socket_owners() -> HashMap<u64, u32> {
+        let mut owners = HashMap::new();
+        let Ok(procs) = std::fs::read_dir("/proc") else {
+            return owners;
+        };
+        for entry in procs.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+                continue;
+            };
+            let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+                continue;
+            };
+            for fd in fds.flatten() {
+                let Ok(target) = std::fs::read_link(fd.path()) else {
+                    continue;
+                };
+                let target = target.to_string_lossy();
+                if let Some(inode) = target
+                    .strip_prefix("socket:[")
+                    .and_then(|s| s.strip_suffix(']'))
+                    .and_then(|s| s.parse::<u64>().ok())
+                {
+                    owners.entry(inode).or_insert(pid);
+                }
+            }
+        }
+        owners
+    }
+}
+
+// ─── Windows: GetExtendedTcpTable ───────────────────────────────────────────
+
+/// Only TCP is read on Windows: `GetExtendedUdpTable` rows carry no remote
+/// endpoint (UDP is connectionless there), so they can't become flows —
+/// the same reason `netstat -no` never listed them.
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::*;
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetExtendedTcpTable, GetPerTcp6ConnectionEStats, GetPerTcpConnectionEStats,
+        SetPerTcp6ConnectionEStats, SetPerTcpConnectionEStats, TcpConnectionEstatsData, MIB_TCP6ROW,
+        MIB_TCP6ROW_OWNER_PID, MIB_TCP6TABLE_OWNER_PID, MIB_TCPROW_LH, MIB_TCPROW_LH_0,
+        MIB_TCPROW_OWNER_PID, MIB_TCPTABLE_OWNER_PID, TCP_ESTATS_DATA_ROD_v0, TCP_ESTATS_DATA_RW_v0,
+        TCP_TABLE_OWNER_PID_ALL,
+    };
+    use windows_sys::Win32::Networking::WinSock::{IN6_ADDR, IN6_ADDR_0};
+
+    const AF_INET: u32 = 2;
+    const AF_INET6: u32 = 23;
+    const ERROR_INSUFFICIENT_BUFFER: u32 = 122;
+
+    pub fn read() -> Result<Vec<ParsedConnection>, String> {
+        let mut connections = Vec::with_capacity(256);
+
+        let v4 = fetch_table(AF_INET)?;
+        // SAFETY: the buffer was filled by GetExtendedTcpTable with a
+        // MIB_TCPTABLE_OWNER_PID header followed by dwNumEntries rows.
+        unsafe {
+            let table = v4.as_ptr() as *const MIB_TCPTABLE_OWNER_PID;
+            let count = (*table).dwNumEntries as usize;
+            let rows = std::slice::from_raw_parts(
+                (*table).table.as_ptr() as *const MIB_TCPROW_OWNER_PID,
+                count,
+            );
+            for row in rows {
+                let counters = byte_counters(row);
+                connections.push(ParsedConnection {
+                    proto: "tcp".into(),
+                    local_ip: format_ip(IpAddr::V4(Ipv4Addr::from(row.dwLocalAddr.to_ne_bytes()))),
+                    local_port: u16::from_be(row.dwLocalPort as u16),
+                    remote_ip: format_ip(IpAddr::V4(Ipv4Addr::from(row.dwRemoteAddr.to_ne_bytes()))),
+                    remote_port: u16::from_be(row.dwRemotePort as u16),
+                    state: tcp_state_name(state_of(row.dwState)).to_string(),
+                    pid: row.dwOwningPid,
+                    bytes_out: counters.map(|(out, _)| out),
+                    bytes_in: counters.map(|(_, inb)| inb),
+                    packets: None,
+                    sni: None,
+                });
+            }
+        }
+
+        // IPv6 is optional — some systems have the stack disabled
+        if let Ok(v6) = fetch_table(AF_INET6) {
+            // SAFETY: as above, for MIB_TCP6TABLE_OWNER_PID.
+            unsafe {
+                let table = v6.as_ptr() as *const MIB_TCP6TABLE_OWNER_PID;
+                let count = (*table).dwNumEntries as usize;
+                let rows = std::slice::from_raw_parts(
+                    (*table).table.as_ptr() as *const MIB_TCP6ROW_OWNER_PID,
+                    count,
+                );
+                for row in rows {
+                    let counters = byte_counters_v6(row);
+                    connections.push(ParsedConnection {
+                        proto: "tcp".into(),
+                        local_ip: format_ip(IpAddr::V6(Ipv6Addr::from(row.ucLocalAddr))),
+                        local_port: u16::from_be(row.dwLocalPort as u16),
+                        remote_ip: format_ip(IpAddr::V6(Ipv6Addr::from(row.ucRemoteAddr))),
+                        remote_port: u16::from_be(row.dwRemotePort as u16),
+                        state: tcp_state_name(state_of(row.dwState)).to_string(),
+                        pid: row.dwOwningPid,
+                        bytes_out: counters.map(|(out, _)| out),
+                        bytes_in: counters.map(|(_, inb)| inb),
+                        packets: None,
+                        sni: None,
+                    });
+                }
+            }
+        }
+
+        Ok(connections)
+    }
+
+    /// Cumulative (DataBytesOut, DataBytesIn) for an established IPv4
+    /// connection.  Collection has to be switched on per connection, which
+    /// requires elevation; unelevated the Set call fails and so does the Get,
+    /// leaving the flow on estimates.
+    fn byte_counters(row: &MIB_TCPROW_OWNER_PID) -> Option<(u64, u64)> {
+        if row.dwState != 5 {
+            return None;
+        }
+        let tcp_row = MIB_TCPROW_LH {
+            Anonymous: MIB_TCPROW_LH_0 { dwState: row.dwState },
+            dwLocalAddr: row.dwLocalAddr,
+            dwLocalPort: row.dwLocalPort,
+            dwRemoteAddr: row.dwRemoteAddr,
+            dwRemotePort: row.dwRemotePort,
+        };
+        let enable = TCP_ESTATS_DATA_RW_v0 { EnableCollection: 1 };
+        let mut rod: TCP_ESTATS_DATA_ROD_v0 = unsafe { std::mem::zeroed() };
+        // SAFETY: all pointers reference live, correctly sized structs.
+        let ret = unsafe {
+            SetPerTcpConnectionEStats(
+                &tcp_row,
+                TcpConnectionEstatsData,
+                (&enable as *const TCP_ESTATS_DATA_RW_v0).cast(),
+                0,
+                std::mem::size_of::<TCP_ESTATS_DATA_RW_v0>() as u32,
+                0,
+            );
+            GetPerTcpConnectionEStats(
+                &tcp_row,
+                TcpConnectionEstatsData,
+                std::ptr::null_mut(),
+                0,
+                0,
+                std::ptr::null_mut(),
+                0,
+                0,
+                (&mut rod as *mut TCP_ESTATS_DATA_ROD_v0).cast(),
+                0,
+                std::mem::size_of::<TCP_ESTATS_DATA_ROD_v0>() as u32,
+            )
+        };
+        (ret == 0).then_some((rod.DataBytesOut, rod.DataBytesIn))
+    }
+
+    /// `byte_counters` for an established IPv6 connection.
+    fn byte_counters_v6(row: &MIB_TCP6ROW_OWNER_PID) -> Option<(u64, u64)> {
+        if row.dwState != 5 {
+            return None;
+        }
+        let tcp_row = MIB_TCP6ROW {
+            State: row.dwState as i32,
+            LocalAddr: IN6_ADDR { u: IN6_ADDR_0 { Byte: row.ucLocalAddr } },
+            dwLocalScopeId: row.dwLocalScopeId,
+            dwLocalPort: row.dwLocalPort,
+            RemoteAddr: IN6_ADDR { u: IN6_ADDR_0 { Byte: row.ucRemoteAddr } },
+            dwRemoteScopeId: row.dwRemoteScopeId,
+            dwRemotePort: row.dwRemotePort,
+        };
+        let enable = TCP_ESTATS_DATA_RW_v0 { EnableCollection: 1 };
+        let mut rod: TCP_ESTATS_DATA_ROD_v0 = unsafe { std::mem::zeroed() };
+        // SAFETY: all pointers reference live, correctly sized structs.
+        let ret = unsafe {
+            SetPerTcp6ConnectionEStats(
+                &tcp_row,
+                TcpConnectionEstatsData,
+                (&enable as *const TCP_ESTATS_DATA_RW_v0).cast(),
+                0,
+                std::mem::size_of::<TCP_ESTATS_DATA_RW_v0>() as u32,
+                0,
+            );
+            GetPerTcp6ConnectionEStats(
+                &tcp_row,
+                TcpConnectionEstatsData,
+                std::ptr::null_mut(),
+                0,
+                0,
+                std::ptr::null_mut(),
+                0,
+                0,
+                (&mut rod as *mut TCP_ESTATS_DATA_ROD_v0).cast(),
+                0,
+                std::mem::size_of::<TCP_ESTATS_DATA_ROD_v0>() as u32,
+            )
+        };
+        (ret == 0).then_some((rod.DataBytesOut, rod.DataBytesIn))
+    }
+
+    /// Calls GetExtendedTcpTable, growing the buffer until the table fits.
+    /// Backed by `u64`s so the table header is suitably aligned.
+    fn fetch_table(family: u32) -> Result<Vec<u64>, String> {
+        let mut size: u32 = 0;
+        let mut buf: Vec<u64> = Vec::new();
+        for _ in 0..4 {
+            // SAFETY: `buf` holds at least `size` bytes (or is null with size 0).
+            let ret = unsafe {
+                GetExtendedTcpTable(
+                    if buf.is_empty() { std::ptr::null_mut() } else { buf.as_mut_ptr().cast() },
+                    &mut size,
+                    0,
+                    family,
+                    TCP_TABLE_OWNER_PID_ALL,
+                    0,
+                )
+            };
+            match ret {
+                0 if !buf.is_empty() => return Ok(buf),
+                0 | ERROR_INSUFFICIENT_BUFFER => {
+                    // Table can grow between calls; leave some headroom
+                    buf = vec![0u64; (size as usize + 1024) / 8 + 1];
+                    size = (buf.len() * 8) as u32;
+                }
+                code => return Err(format!("GetExtendedTcpTable failed ({code})")),
+            }
+        }
+        Err("GetExtendedTcpTable: table kept growing".into())
+    }
+
+    fn state_of(state: u32) -> TcpState {
+        match state {
+            1 => TcpState::Closed,
+            2 => TcpState::Listen,
+            3 => TcpState::SynSent,
+            4 => TcpState::SynReceived,
+            5 => TcpState::Established,
+            6 => TcpState::FinWait1,
+            7 => TcpState::FinWait2,
+            8 => TcpState::CloseWait,
+            9 => TcpState::Closing,
+            10 => TcpState::LastAck,
+            11 => TcpState::TimeWait,
+            _ => TcpState::Unknown,
+        }
+    }
+}
+
+// ─── macOS: libproc per-process socket fds ──────────────────────────────────
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use libproc::libproc::bsd_info::BSDInfo;
+    use libproc::libproc::file_info::{listpidinfo, pidfdinfo, ListFDs, ProcFDType};
+    use libproc::libproc::net_info::{InSockInfo, SocketFDInfo, SocketInfoKind, TcpSIState};
+    use libproc::libproc::proc_pid::{listpids, pidinfo, ProcType};
+
+    const INI_IPV4: u8 = 0x1;
+
+    pub fn read() -> Result<Vec<ParsedConnection>, String> {
+        let pids = listpids(ProcType::ProcAllPIDS)?;
+        let mut connections = Vec::with_capacity(256);
+
+        for pid in pids {
+            let pid = pid as i32;
+            // Processes we may not inspect (other users, exited) are skipped
+            let Ok(info) = pidinfo::<BSDInfo>(pid, 0) else {
+                continue;
+            };
+            let Ok(fds) = listpidinfo::<ListFDs>(pid, info.pbi_nfiles as usize) else {
+                continue;
+            };
+            for fd in fds {
+                if !matches!(ProcFDType::from(fd.proc_fdtype), ProcFDType::Socket) {
+                    continue;
+                }
+                let Ok(socket) = pidfdinfo::<SocketFDInfo>(pid, fd.proc_fd) else {
+                    continue;
+                };
+                // SAFETY: the union member read matches `soi_kind`.
+                let (proto, ini, state) = match SocketInfoKind::from(socket.psi.soi_kind) {
+                    SocketInfoKind::Tcp => {
+                        let tcp = unsafe { socket.psi.soi_proto.pri_tcp };
+                        ("tcp", tcp.tcpsi_ini, tcp_state_name(state_of(tcp.tcpsi_state)))
+                    }
+                    SocketInfoKind::In if socket.psi.soi_protocol == libc::IPPROTO_UDP => {
+                        ("udp", unsafe { socket.psi.soi_proto.pri_in }, "STATELESS")
+                    }
+                    _ => continue,
+                };
+                let (local_ip, remote_ip) = addresses(&ini);
+                connections.push(ParsedConnection {
+                    proto: proto.into(),
+                    local_ip: format_ip(local_ip),
+                    local_port: u16::from_be(ini.insi_lport as u16),
+                    remote_ip: format_ip(remote_ip),
+                    remote_port: u16::from_be(ini.insi_fport as u16),
+                    state: state.to_string(),
+                    pid: pid as u32,
+                    bytes_out: None,
+                    bytes_in: None,
+                    packets: None,
+                    sni: None,
+                });
+            }
+        }
+        Ok(connections)
+    }
+
+    fn addresses(ini: &InSockInfo) -> (IpAddr, IpAddr) {
+        // SAFETY: `insi_vflag` says which union member is populated.
+        unsafe {
+            if ini.insi_vflag & INI_IPV4 != 0 {
+                let local = ini.insi_laddr.ina_46.i46a_addr4.s_addr;
+                let remote = ini.insi_faddr.ina_46.i46a_addr4.s_addr;
+                (
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(local))),
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(remote))),
+                )
+            } else {
+                (
+                    IpAddr::V6(Ipv6Addr::from(ini.insi_laddr.ina_6.s6_addr)),
+                    IpAddr::V6(Ipv6Addr::from(ini.insi_faddr.ina_6.s6_addr)),
+                )
+            }
+        }
+    }
+
+    fn state_of(state: libc::c_int) -> TcpState {
+        match TcpSIState::from(state) {
+            TcpSIState::Closed => TcpState::Closed,
+            TcpSIState::Listen => TcpState::Listen,
+            TcpSIState::SynSent => TcpState::SynSent,
+            TcpSIState::SynReceived => TcpState::SynReceived,
+            TcpSIState::Established => TcpState::Established,
+            TcpSIState::CloseWait => TcpState::CloseWait,
+            TcpSIState::FinWait1 => TcpState::FinWait1,
+            TcpSIState::Closing => TcpState::Closing,
+            TcpSIState::LastAck => TcpState::LastAck,
+            TcpSIState::FinWait2 => TcpState::FinWait2,
+            TcpSIState::TimeWait => TcpState::TimeWait,
+            _ => TcpState::Unknown,
+        }
+    }
+}
+
+// ─── Other platforms ────────────────────────────────────────────────────────
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+mod platform {
+    use super::*;
+
+    pub fn read() -> Result<Vec<ParsedConnection>, String> {
+        Err("native socket table not supported on this platform".into())
+    }
+}
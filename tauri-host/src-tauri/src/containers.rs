@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// How often running containers and WSL distros are re-listed while a
+/// container runtime is running.
+pub const CONTAINER_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Processes that carry traffic on behalf of containers or WSL, so their
+/// flows are worth attributing further.
+const DOCKER_PROCESSES: &[&str] = &[
+    "com.docker.backend",
+    "com.docker.backend.exe",
+    "com.docker.vpnkit",
+    "vpnkit",
+    "vpnkit.exe",
+    "docker-proxy",
+    "rootlessport",
+];
+const WSL_PROCESSES: &[&str] = &["vmmem", "vmmemwsl", "wslhost.exe", "wslrelay.exe"];
+
+// ─── Container attribution ──────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    Docker,
+    Wsl,
+}
+
+/// The container (or WSL distro) a flow belongs to.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerRef {
+    pub runtime: ContainerRuntime,
+    /// Container name without the leading `/`, or the distro name.
+    pub name: String,
+    /// Image the container runs; `None` for WSL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// Running containers and distros, indexed for per-flow lookups.  Flows
+/// from VM and proxy processes (`vmmem`, `com.docker.backend`) are matched
+/// by local address, then published port, then by being the only thing
+/// running; anything ambiguous is left unattributed.
+#[derive(Clone, Debug, Default)]
+pub struct ContainerIndex {
+    by_ip: HashMap<String, ContainerRef>,
+    by_port: HashMap<u16, ContainerRef>,
+    containers: Vec<ContainerRef>,
+    distros: Vec<ContainerRef>,
+}
+
+impl ContainerIndex {
+    /// List the running containers and WSL distros.  Slow (socket and
+    /// process calls); run it off the async runtime.
+    pub fn snapshot() -> Self {
+        let mut index = Self::default();
+        if let Some(body) = docker_get("/containers/json") {
+            index.add_docker(&body);
+        }
+        for (distro, ips) in wsl_distros() {
+            index.add_distro(distro, ips);
+        }
+        index
+    }
+
+    /// Whether any container runtime is running, judged by process name.
+    pub fn runtime_running(process_names: &HashMap<u32, String>) -> bool {
+        process_names.values().any(|name| runtime_of(name).is_some())
+    }
+
+    /// Add containers from a Docker `/containers/json` response.
+    fn add_docker(&mut self, body: &str) {
+        let Ok(listed) = serde_json::from_str::<Vec<DockerContainer>>(body) else {
+            return;
+        };
+        for container in listed {
+            let name = container
+                .names
+                .first()
+                .map(|n| n.trim_start_matches('/').to_string())
+                .unwrap_or_else(|| container.id.chars().take(12).collect());
+            let tag = ContainerRef {
+                runtime: ContainerRuntime::Docker,
+                name,
+                image: Some(container.image),
+            };
+            for network in container.network_settings.networks.into_values() {
+                if !network.ip_address.is_empty() {
+                    self.by_ip.insert(network.ip_address, tag.clone());
+                }
+            }
+            for port in container.ports.iter().filter_map(|p| p.public_port) {
+                self.by_port.insert(port, tag.clone());
+            }
+            self.containers.push(tag);
+        }
+    }
+
+    fn add_distro(&mut self, distro: String, ips: Vec<String>) {
+        let tag = ContainerRef {
+            runtime: ContainerRuntime::Wsl,
+            name: distro,
+            image: None,
+        };
+        for ip in ips {
+            self.by_ip.insert(ip, tag.clone());
+        }
+        self.distros.push(tag);
+    }
+
+    /// The container a flow from `local_ip:local_port`, owned by `process`,
+    /// belongs to.
+    pub fn attribute(&self, local_ip: &str, local_port: u16, process: Option<&str>) -> Option<ContainerRef> {
+        if let Some(tag) = self.by_ip.get(local_ip) {
+            return Some(tag.clone());
+        }
+        let runtime = runtime_of(process?)?;
+        let candidates = match runtime {
+            ContainerRuntime::Docker => &self.containers,
+            ContainerRuntime::Wsl => &self.distros,
+        };
+        if let Some(tag) = self.by_port.get(&local_port).filter(|t| t.runtime == runtime) {
+            return Some(tag.clone());
+        }
+        match candidates.as_slice() {
+            [only] => Some(only.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Which runtime `process` carries traffic for.
+fn runtime_of(process: &str) -> Option<ContainerRuntime> {
+    let process = process.to_ascii_lowercase();
+    if DOCKER_PROCESSES.contains(&process.as_str()) {
+        Some(ContainerRuntime::Docker)
+    } else if WSL_PROCESSES.contains(&process.as_str()) {
+        Some(ContainerRuntime::Wsl)
+    } else {
+        None
+    }
+}
+
+// ─── Docker engine API ──────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerContainer {
+    id: String,
+    #[serde(default)]
+    names: Vec<String>,
+    image: String,
+    #[serde(default)]
+    ports: Vec<DockerPort>,
+    #[serde(default)]
+    network_settings: DockerNetworkSettings,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerPort {
+    public_port: Option<u16>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerNetworkSettings {
+    #[serde(default)]
+    networks: HashMap<String, DockerNetwork>,
+}
+
+#[derive(Deserialize)]
+struct DockerNetwork {
+    #[serde(rename = "IPAddress", default)]
+    ip_address: String,
+}
+
+/// Body of a `GET` against the engine API, or `None` when Docker isn't
+/// running.  HTTP/1.0 so the response is never chunked.
+fn docker_get(path: &str) -> Option<String> {
+    let mut stream = docker_connect()?;
+    let request = format!("GET {path} HTTP/1.0\r\nHost: docker\r\n\r\n");
+    stream.write_all(request.as_bytes()).ok()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    head.lines()
+        .next()
+        .is_some_and(|status| status.split_whitespace().nth(1) == Some("200"))
+        .then(|| body.to_string())
+}
+
+#[cfg(unix)]
+fn docker_connect() -> Option<std::os::unix::net::UnixStream> {
+    docker_sockets().into_iter().find_map(|path| {
+        let stream = std::os::unix::net::UnixStream::connect(path).ok()?;
+        stream.set_read_timeout(Some(Duration::from_secs(2))).ok()?;
+        Some(stream)
+    })
+}
+
+/// Where the Docker engine API listens, most specific first.
+#[cfg(unix)]
+fn docker_sockets() -> Vec<std::path::PathBuf> {
+    let mut sockets = Vec::new();
+    if let Some(path) = std::env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|host| host.strip_prefix("unix://").map(std::path::PathBuf::from))
+    {
+        sockets.push(path);
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        // Docker Desktop for Mac and Linux
+        sockets.push(std::path::Path::new(&home).join(".docker/run/docker.sock"));
+    }
+    sockets.push("/var/run/docker.sock".into());
+    sockets
+}
+
+#[cfg(windows)]
+fn docker_connect() -> Option<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(r"\\.\pipe\docker_engine")
+        .ok()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn docker_connect() -> Option<std::fs::File> {
+    None
+}
+
+// ─── WSL ────────────────────────────────────────────────────────────────────
+
+/// Running WSL distros and their VM addresses.
+#[cfg(target_os = "windows")]
+fn wsl_distros() -> Vec<(String, Vec<String>)> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let wsl = |args: &[&str]| {
+        std::process::Command::new("wsl.exe")
+            .args(args)
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+    };
+    let Some(listing) = wsl(&["--list", "--running", "--quiet"]) else {
+        return Vec::new();
+    };
+    // wsl.exe writes its own output as UTF-16
+    let units: Vec<u16> = listing
+        .stdout
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .lines()
+        .map(|line| line.trim().trim_matches('\0').to_string())
+        .filter(|name| !name.is_empty())
+        .map(|distro| {
+            let ips = wsl(&["--distribution", &distro, "--exec", "hostname", "-I"])
+                .map(|o| {
+                    String::from_utf8_lossy(&o.stdout)
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            (distro, ips)
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "windows"))]
+fn wsl_distros() -> Vec<(String, Vec<String>)> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTAINERS: &str = r#"[
+        {"Id":"4f1c0a9b2e3d","Names":["/web"],"Image":"nginx:1.27",
+         "Ports":[{"PrivatePort":80,"PublicPort":8080,"Type":"tcp"},{"PrivatePort":80,"Type":"tcp"}],
+         "NetworkSettings":{"Networks":{"bridge":{"IPAddress":"172.17.0.2"}}}},
+        {"Id":"9a8b7c6d5e4f","Names":["/db"],"Image":"postgres:16",
+         "Ports":[],"NetworkSettings":{"Networks":{"bridge":{"IPAddress":"172.17.0.3"}}}}
+    ]"#;
+
+    #[test]
+    fn attributes_by_address_port_and_sole_candidate() {
+        let mut index = ContainerIndex::default();
+        index.add_docker(CONTAINERS);
+
+        let db = index.attribute("172.17.0.3", 51000, None).unwrap();
+        assert_eq!((db.name.as_str(), db.image.as_deref()), ("db", Some("postgres:16")));
+        let web = index.attribute("0.0.0.0", 8080, Some("com.docker.backend.exe")).unwrap();
+        assert_eq!(web.name, "web");
+        // Two containers and no published port: ambiguous
+        assert_eq!(index.attribute("192.168.1.10", 50000, Some("com.docker.backend")), None);
+        // Ordinary processes are never attributed by port
+        assert_eq!(index.attribute("192.168.1.10", 8080, Some("firefox")), None);
+
+        index.add_distro("Ubuntu".into(), vec!["172.22.96.5".into()]);
+        let distro = index.attribute("192.168.1.10", 50000, Some("vmmemWSL")).unwrap();
+        assert_eq!((distro.runtime, distro.name.as_str()), (ContainerRuntime::Wsl, "Ubuntu"));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::AbyssError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Provenance recorded on every imported row.
+pub const SOURCE: &str = "windows-data-usage";
+
+/// Windows keeps roughly 30–60 days of per-app usage.
+pub const MAX_BACKFILL_DAYS: u32 = 60;
+
+// ─── Backfill rows ──────────────────────────────────────────────────────────
+
+/// Approximate usage of one app on one day, from the OS rather than Abyss.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillRow {
+    /// Local calendar day, `YYYY-MM-DD`.
+    pub day: String,
+    /// Executable name where Windows attributes usage to one
+    /// (`msedge.exe`, matching `process_usage`), else its display name.
+    pub app: String,
+    pub app_id: Option<String>,
+    pub bytes_up: f64,
+    pub bytes_down: f64,
+}
+
+/// Result of `cmd_backfill_os_usage`.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillReport {
+    pub source: &'static str,
+    /// Rows stored (one per app per day).
+    pub imported: u32,
+    /// Rows dropped because Abyss was already recording that day.
+    pub skipped: u32,
+    pub first_day: Option<String>,
+    pub last_day: Option<String>,
+}
+
+/// One line of the PowerShell script's output.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageLine {
+    day: String,
+    app: Option<String>,
+    app_id: Option<String>,
+    sent: f64,
+    received: f64,
+}
+
+/// Sum the script's per-profile lines (Wi-Fi and Ethernet are reported
+/// separately) into one row per app per day.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn aggregate(output: &str) -> Vec<BackfillRow> {
+    let mut rows: HashMap<(String, String), BackfillRow> = HashMap::new();
+    for line in output.lines().filter(|l| l.trim_start().starts_with('{')) {
+        let Ok(usage) = serde_json::from_str::<UsageLine>(line) else {
+            continue;
+        };
+        let app_id = usage.app_id.filter(|id| !id.is_empty());
+        let exe = app_id
+            .as_deref()
+            .filter(|id| id.to_ascii_lowercase().ends_with(".exe"))
+            .and_then(|id| id.rsplit(['\\', '/']).next())
+            .map(str::to_string);
+        let Some(app) = exe.or(usage.app.filter(|a| !a.is_empty())) else {
+            continue;
+        };
+        let row = rows.entry((usage.day.clone(), app.clone())).or_insert_with(|| BackfillRow {
+            day: usage.day,
+            app,
+            app_id,
+            bytes_up: 0.0,
+            bytes_down: 0.0,
+        });
+        row.bytes_up += usage.sent;
+        row.bytes_down += usage.received;
+    }
+    let mut rows: Vec<BackfillRow> = rows.into_values().collect();
+    rows.sort_by(|a, b| (&a.day, &a.app).cmp(&(&b.day, &b.app)));
+    rows
+}
+
+// ─── OS data-usage store ────────────────────────────────────────────────────
+
+/// Per-app usage for each of the last `days` full days, over every
+/// connection profile, from `ConnectionProfile.GetAttributedNetworkUsageAsync`.
+/// (Interface counters such as `GetIfTable2` only go back to boot.)
+#[cfg(target_os = "windows")]
+pub fn read_os_usage(days: u32) -> Result<Vec<BackfillRow>, AbyssError> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const SCRIPT: &str = "Add-Type -AssemblyName System.Runtime.WindowsRuntime; \
+        $asTask = [System.WindowsRuntimeSystemExtensions].GetMethods() | Where-Object { $_.Name -eq 'AsTask' -and $_.GetParameters().Count -eq 1 -and $_.GetParameters()[0].ParameterType.Name -eq 'IAsyncOperation`1' } | Select-Object -First 1; \
+        [Windows.Networking.Connectivity.NetworkInformation, Windows.Networking.Connectivity, ContentType = WindowsRuntime] > $null; \
+        $type = [System.Collections.Generic.IReadOnlyList[Windows.Networking.Connectivity.AttributedNetworkUsage]]; \
+        $states = New-Object Windows.Networking.Connectivity.NetworkUsageStates; \
+        $today = [DateTimeOffset]::new([DateTime]::Today); \
+        foreach ($p in [Windows.Networking.Connectivity.NetworkInformation]::GetConnectionProfiles()) { \
+            for ($i = [int]$env:ABYSS_DAYS; $i -ge 1; $i--) { \
+                $from = $today.AddDays(-$i); \
+                $task = $asTask.MakeGenericMethod($type).Invoke($null, @($p.GetAttributedNetworkUsageAsync($from, $from.AddDays(1), $states))); \
+                if (-not $task.Wait(30000)) { continue }; \
+                foreach ($u in $task.Result) { \
+                    [pscustomobject]@{ day = $from.ToString('yyyy-MM-dd'); app = $u.AttributionName; appId = $u.AttributionId; sent = $u.BytesSent; received = $u.BytesReceived } | ConvertTo-Json -Compress \
+                } \
+            } \
+        }";
+
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("ABYSS_DAYS", days.clamp(1, MAX_BACKFILL_DAYS).to_string())
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+    if !output.status.success() {
+        return Err(AbyssError::Internal(format!(
+            "Windows data usage query failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(aggregate(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn read_os_usage(_days: u32) -> Result<Vec<BackfillRow>, AbyssError> {
+    Err(AbyssError::Internal("Data usage backfill is only available on Windows".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_profiles_and_prefers_executable_names() {
+        let output = r#"
+{"day":"2026-01-02","app":"Microsoft Edge","appId":"\\device\\harddiskvolume3\\program files (x86)\\microsoft\\edge\\application\\msedge.exe","sent":100,"received":1000}
+{"day":"2026-01-02","app":"Microsoft Edge","appId":"\\device\\harddiskvolume3\\program files (x86)\\microsoft\\edge\\application\\msedge.exe","sent":50,"received":500}
+{"day":"2026-01-02","app":"Windows Update","appId":"","sent":1,"received":2}
+WARNING: not JSON
+{"day":"2026-01-01","app":"","appId":null,"sent":9,"received":9}
+"#;
+        let rows = aggregate(output);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].app, "Windows Update");
+        assert_eq!(rows[0].app_id, None);
+        assert_eq!(rows[1].app, "msedge.exe");
+        assert_eq!((rows[1].bytes_up, rows[1].bytes_down), (150.0, 1500.0));
+    }
+}
@@ -1,12 +1,32 @@
+use crate::alerts::{format_bps, AlertContext, AlertEvent, AlertRule, QuotaPeriod, Severity};
+use crate::anonymize;
+use crate::budgets::{self, BudgetEvent, BudgetScope, DataBudget};
+use crate::datausage::{BackfillReport, BackfillRow};
+use crate::dbcrypt;
+use crate::ipfamily::FamilyAttempt;
+use crate::journal::JournalEntry;
+use crate::lifecycle::{FlowEvent, FlowEventKind};
+use crate::mtu::MtuFinding;
+use crate::procmeta::ProcessMetadata;
+use crate::publicip::NetworkChange;
+use crate::rdap::WhoisInfo;
+use crate::router::RouterSample;
+use crate::routes::DefaultRoute;
+use crate::units;
+use crate::watchlist::{WatchHit, WatchKind, Watchlist};
+use crate::webhooks::{Webhook, WebhookEvent};
+use crate::wifi::WifiLink;
+use crate::writer::integration_gap_secs;
 use rusqlite::{params, Connection, Result as SqlResult};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 /// Current database schema version. Bump this when altering tables.
-const DB_VERSION: u32 = 4;
+const DB_VERSION: u32 = 39;
 
 /// Opens (or creates) the Abyss sessions database at `path` and runs any
 /// pending migrations.  The connection is returned with WAL journal mode and
-/// foreign-key enforcement enabled.
+/// foreign-key enforcement enabled, keyed if the file is encrypted.
 pub fn open_database(path: &Path) -> SqlResult<Connection> {
     // Ensure parent directory exists
     if let Some(parent) = path.parent() {
@@ -14,7 +34,75 @@ pub fn open_database(path: &Path) -> SqlResult<Connection> {
     }
 
     let conn = Connection::open(path)?;
+    dbcrypt::apply_key(&conn, path)?;
+    prepare(conn)
+}
+
+/// Opens a private in-memory database with the current schema, for tests
+/// that drive the writer without touching the filesystem.
+#[cfg(any(test, feature = "test-utils"))]
+pub fn open_in_memory() -> SqlResult<Connection> {
+    prepare(Connection::open_in_memory()?)
+}
+
+/// Whether `conn` holds an Abyss database this build can migrate: it has
+/// a `sessions` table and wasn't written by a newer schema.
+pub fn is_abyss_database(conn: &Connection) -> SqlResult<bool> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let sessions: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'sessions'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(sessions > 0 && version <= DB_VERSION)
+}
+
+/// Statements the writer runs on every flush, kept compiled by
+/// `PreparedWriter` instead of being re-parsed per row.
+const WRITER_STATEMENTS: [&str; 6] = [
+    INSERT_FRAME_SQL,
+    INSERT_FLOW_SNAPSHOT_SQL,
+    UPDATE_SESSION_TOTALS_SQL,
+    UPSERT_DESTINATION_SQL,
+    INSERT_PROCESS_USAGE_SQL,
+    RECORD_HOSTNAME_SQL,
+];
+
+/// Statement cache size for the writer's connection: the hot statements plus
+/// room for the less frequent ones (DNS, flow events, budgets, ...).
+const WRITER_STATEMENT_CACHE: usize = 64;
+
+/// The writer thread's connection.  The insert helpers it calls go through
+/// `prepare_cached`; this sizes the connection's statement cache so they
+/// aren't evicted, and compiles them up front.  Derefs to the `Connection`.
+pub struct PreparedWriter {
+    conn: Connection,
+}
+
+impl PreparedWriter {
+    pub fn open(path: &Path) -> SqlResult<Self> {
+        Self::new(open_database(path)?)
+    }
+
+    pub fn new(conn: Connection) -> SqlResult<Self> {
+        conn.set_prepared_statement_cache_capacity(WRITER_STATEMENT_CACHE);
+        for sql in WRITER_STATEMENTS {
+            conn.prepare_cached(sql)?;
+        }
+        Ok(Self { conn })
+    }
+}
+
+impl std::ops::Deref for PreparedWriter {
+    type Target = Connection;
 
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+/// Applies the connection pragmas and pending migrations.
+fn prepare(conn: Connection) -> SqlResult<Connection> {
     // Performance pragmas
     conn.execute_batch(
         "PRAGMA journal_mode = WAL;
@@ -46,6 +134,111 @@ fn migrate(conn: &Connection) -> SqlResult<()> {
     if version < 4 {
         conn.execute_batch(SCHEMA_V4)?;
     }
+    if version < 5 {
+        conn.execute_batch(SCHEMA_V5)?;
+    }
+    if version < 6 {
+        conn.execute_batch(SCHEMA_V6)?;
+    }
+    if version < 7 {
+        conn.execute_batch(SCHEMA_V7)?;
+    }
+    if version < 8 {
+        conn.execute_batch(SCHEMA_V8)?;
+    }
+    if version < 9 {
+        conn.execute_batch(SCHEMA_V9)?;
+    }
+    if version < 10 {
+        conn.execute_batch(SCHEMA_V10)?;
+    }
+    if version < 11 {
+        conn.execute_batch(SCHEMA_V11)?;
+    }
+    if version < 12 {
+        conn.execute_batch(SCHEMA_V12)?;
+    }
+    if version < 13 {
+        conn.execute_batch(SCHEMA_V13)?;
+    }
+    if version < 14 {
+        conn.execute_batch(SCHEMA_V14)?;
+    }
+    if version < 15 {
+        conn.execute_batch(SCHEMA_V15)?;
+    }
+    if version < 16 {
+        conn.execute_batch(SCHEMA_V16)?;
+    }
+    if version < 17 {
+        conn.execute_batch(SCHEMA_V17)?;
+    }
+    if version < 18 {
+        conn.execute_batch(SCHEMA_V18)?;
+    }
+    if version < 19 {
+        conn.execute_batch(SCHEMA_V19)?;
+    }
+    if version < 20 {
+        conn.execute_batch(SCHEMA_V20)?;
+    }
+    if version < 21 {
+        conn.execute_batch(SCHEMA_V21)?;
+    }
+    if version < 22 {
+        conn.execute_batch(SCHEMA_V22)?;
+    }
+    if version < 23 {
+        conn.execute_batch(SCHEMA_V23)?;
+    }
+    if version < 24 {
+        conn.execute_batch(SCHEMA_V24)?;
+    }
+    if version < 25 {
+        conn.execute_batch(SCHEMA_V25)?;
+    }
+    if version < 26 {
+        conn.execute_batch(SCHEMA_V26)?;
+    }
+    if version < 27 {
+        conn.execute_batch(SCHEMA_V27)?;
+    }
+    if version < 28 {
+        conn.execute_batch(SCHEMA_V28)?;
+    }
+    if version < 29 {
+        conn.execute_batch(SCHEMA_V29)?;
+    }
+    if version < 30 {
+        conn.execute_batch(SCHEMA_V30)?;
+    }
+    if version < 31 {
+        conn.execute_batch(SCHEMA_V31)?;
+    }
+    if version < 32 {
+        conn.execute_batch(SCHEMA_V32)?;
+    }
+    if version < 33 {
+        conn.execute_batch(SCHEMA_V33)?;
+    }
+    if version < 34 {
+        conn.execute_batch(SCHEMA_V34)?;
+    }
+    if version < 35 {
+        conn.execute_batch(SCHEMA_V35)?;
+    }
+    if version < 36 {
+        conn.execute_batch(SCHEMA_V36)?;
+    }
+    if version < 37 {
+        conn.execute_batch(SCHEMA_V37)?;
+    }
+    if version < 38 {
+        conn.execute_batch(SCHEMA_V38)?;
+    }
+    if version < 39 {
+        conn.execute_batch(SCHEMA_V39)?;
+    }
 
     conn.execute_batch(&format!("PRAGMA user_version = {DB_VERSION};"))?;
     Ok(())
@@ -198,9 +391,545 @@ const SCHEMA_V4: &str = "
 ALTER TABLE sessions ADD COLUMN crash_recovered INTEGER NOT NULL DEFAULT 0;
 ";
 
+/// V5 schema — key/value store for user settings (values are JSON).
+const SCHEMA_V5: &str = "
+CREATE TABLE IF NOT EXISTS settings (
+    key             TEXT    PRIMARY KEY,
+    value           TEXT    NOT NULL,
+    updated_at      TEXT    NOT NULL DEFAULT (datetime('now'))
+);
+";
+
+/// V6 schema — summary-only sessions whose frames and flow snapshots were
+/// pruned by frame retention (totals, destinations and process usage remain).
+const SCHEMA_V6: &str = "
+ALTER TABLE sessions ADD COLUMN summary_only INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE sessions ADD COLUMN compacted_at TEXT;
+";
+
+/// V7 schema — hostnames observed for destination IPs (DNS, SNI, reverse
+/// lookups) and user-assigned labels/categories per IP.
+const SCHEMA_V7: &str = "
+CREATE TABLE IF NOT EXISTS host_observations (
+    ip              TEXT    NOT NULL,
+    hostname        TEXT    NOT NULL,
+    source          TEXT    NOT NULL DEFAULT '',
+    first_seen      TEXT    NOT NULL DEFAULT (datetime('now')),
+    last_seen       TEXT    NOT NULL DEFAULT (datetime('now')),
+    seen_count      INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (ip, hostname)
+);
+
+CREATE INDEX IF NOT EXISTS idx_hostobs_ip ON host_observations(ip, last_seen);
+
+CREATE TABLE IF NOT EXISTS destination_labels (
+    ip              TEXT    PRIMARY KEY,
+    label           TEXT    NOT NULL DEFAULT '',
+    category        TEXT    NOT NULL DEFAULT '',
+    updated_at      TEXT    NOT NULL DEFAULT (datetime('now'))
+);
+";
+
+/// V8 schema — user-defined alert rules (condition stored as JSON) and the
+/// alerts they fired.
+const SCHEMA_V8: &str = "
+CREATE TABLE IF NOT EXISTS alert_rules (
+    id              TEXT    PRIMARY KEY,
+    name            TEXT    NOT NULL,
+    enabled         INTEGER NOT NULL DEFAULT 1,
+    definition      TEXT    NOT NULL,
+    created_at      TEXT    NOT NULL DEFAULT (datetime('now')),
+    updated_at      TEXT    NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS alert_events (
+    id              TEXT    PRIMARY KEY,
+    rule_id         TEXT    NOT NULL,
+    rule_name       TEXT    NOT NULL,
+    session_id      TEXT,
+    severity        TEXT    NOT NULL,
+    subject         TEXT    NOT NULL DEFAULT '',
+    message         TEXT    NOT NULL,
+    value           REAL    NOT NULL DEFAULT 0,
+    threshold       REAL    NOT NULL DEFAULT 0,
+    triggered_at    TEXT    NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_events_time ON alert_events(triggered_at);
+CREATE INDEX IF NOT EXISTS idx_alert_events_rule ON alert_events(rule_id, triggered_at);
+";
+
+/// V9 schema — alert context (frame metrics, matched flow ids) and the count
+/// of breaches suppressed by the rule's cooldown before each firing.
+const SCHEMA_V9: &str = "
+ALTER TABLE alert_events ADD COLUMN suppressed INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE alert_events ADD COLUMN context TEXT NOT NULL DEFAULT '{}';
+";
+
+/// V10 schema — the domain each remote IP was most recently resolved from
+/// (DNS observer), and that domain on flow snapshots and destinations.
+const SCHEMA_V10: &str = "
+CREATE TABLE IF NOT EXISTS dns_map (
+    ip              TEXT    PRIMARY KEY,
+    domain          TEXT    NOT NULL,
+    source          TEXT    NOT NULL DEFAULT '',
+    ttl             INTEGER NOT NULL DEFAULT 0,
+    first_seen      TEXT    NOT NULL DEFAULT (datetime('now')),
+    last_seen       TEXT    NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_dns_map_seen ON dns_map(last_seen);
+
+ALTER TABLE flow_snapshots ADD COLUMN domain TEXT;
+ALTER TABLE destinations ADD COLUMN domain TEXT;
+";
+
+/// V11 schema — TLS server name (SNI) of flows seen by packet capture.
+const SCHEMA_V11: &str = "
+ALTER TABLE flow_snapshots ADD COLUMN sni TEXT;
+";
+
+/// V12 schema — flow open/close events derived from the live flow set.
+const SCHEMA_V12: &str = "
+CREATE TABLE IF NOT EXISTS flow_events (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id      TEXT    REFERENCES sessions(id) ON DELETE CASCADE,
+    kind            TEXT    NOT NULL,
+    flow_id         TEXT    NOT NULL,
+    local_ip        TEXT    NOT NULL,
+    remote_ip       TEXT    NOT NULL,
+    remote_port     INTEGER NOT NULL,
+    proto           TEXT    NOT NULL,
+    process         TEXT,
+    pid             INTEGER,
+    at              TEXT    NOT NULL,
+    opened_at       TEXT    NOT NULL,
+    closed_at       TEXT,
+    duration_secs   REAL,
+    bytes_up        REAL,
+    bytes_down      REAL
+);
+
+CREATE INDEX IF NOT EXISTS idx_flow_events_session ON flow_events(session_id, at);
+CREATE INDEX IF NOT EXISTS idx_flow_events_at ON flow_events(at);
+";
+
+/// V13 schema — TIME_WAIT and ephemeral port counts per sampled frame
+/// (NULL when the socket table wasn't readable).
+const SCHEMA_V13: &str = "
+ALTER TABLE frames ADD COLUMN time_wait INTEGER;
+ALTER TABLE frames ADD COLUMN ephemeral_ports INTEGER;
+";
+
+/// V14 schema — webhook endpoints (subscribed events stored as a JSON array).
+const SCHEMA_V14: &str = "
+CREATE TABLE IF NOT EXISTS webhooks (
+    id              TEXT    PRIMARY KEY,
+    url             TEXT    NOT NULL,
+    secret          TEXT    NOT NULL,
+    events          TEXT    NOT NULL,
+    enabled         INTEGER NOT NULL DEFAULT 1,
+    created_at      TEXT    NOT NULL DEFAULT (datetime('now'))
+);
+";
+
+/// V15 schema — per-session counters kept by the writer, for judging how
+/// complete a recording is.
+const SCHEMA_V15: &str = "
+CREATE TABLE IF NOT EXISTS recording_stats (
+    session_id      TEXT    PRIMARY KEY REFERENCES sessions(id) ON DELETE CASCADE,
+    frames_written  INTEGER NOT NULL DEFAULT 0,
+    flows_written   INTEGER NOT NULL DEFAULT 0,
+    rows_skipped    INTEGER NOT NULL DEFAULT 0,
+    writer_errors   INTEGER NOT NULL DEFAULT 0,
+    dropped_frames  INTEGER NOT NULL DEFAULT 0,
+    updated_at      TEXT    NOT NULL DEFAULT (datetime('now'))
+);
+";
+
+/// V16 schema — baseline buckets imported from another install, pooled into
+/// `baseline_profile` on every recompute (same columns; stddevs as variances).
+const SCHEMA_V16: &str = "
+CREATE TABLE IF NOT EXISTS baseline_import (
+    hour_of_day     INTEGER NOT NULL,
+    day_of_week     INTEGER NOT NULL,
+    avg_bps         REAL    NOT NULL DEFAULT 0,
+    stddev_bps      REAL    NOT NULL DEFAULT 0,
+    avg_flows       REAL    NOT NULL DEFAULT 0,
+    stddev_flows    REAL    NOT NULL DEFAULT 0,
+    avg_latency_ms  REAL    NOT NULL DEFAULT 0,
+    stddev_latency  REAL    NOT NULL DEFAULT 0,
+    common_processes TEXT   NOT NULL DEFAULT '[]',
+    common_countries TEXT   NOT NULL DEFAULT '[]',
+    sample_count    INTEGER NOT NULL DEFAULT 0,
+    imported_at     TEXT    NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (hour_of_day, day_of_week)
+);
+";
+
+/// V17 schema — user annotations on a session's timeline (`t` as in frames).
+const SCHEMA_V17: &str = "
+CREATE TABLE IF NOT EXISTS markers (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id      TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    t               REAL    NOT NULL,
+    label           TEXT    NOT NULL,
+    color           TEXT,
+    created_at      TEXT    NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_markers_session ON markers(session_id, t);
+";
+
+/// V18 schema — behavioural service class inferred per flow snapshot.
+const SCHEMA_V18: &str = "
+ALTER TABLE flow_snapshots ADD COLUMN service_class TEXT;
+";
+
+/// V19 schema — per-flow lookups within a session (flow timeseries).
+const SCHEMA_V19: &str = "
+CREATE INDEX IF NOT EXISTS idx_flowsnap_flow ON flow_snapshots(session_id, flow_id);
+";
+
+/// V20 schema — frames of old sessions averaged into fixed-width buckets
+/// (`resolution` seconds, `t` = bucket start) once the raw rows are dropped.
+const SCHEMA_V20: &str = "
+CREATE TABLE IF NOT EXISTS frames_rollup (
+    session_id      TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    resolution      INTEGER NOT NULL,
+    t               REAL    NOT NULL,
+    timestamp       TEXT    NOT NULL,
+    samples         INTEGER NOT NULL DEFAULT 0,
+    bps             REAL    NOT NULL DEFAULT 0,
+    max_bps         REAL    NOT NULL DEFAULT 0,
+    pps             REAL    NOT NULL DEFAULT 0,
+    upload_bps      REAL    NOT NULL DEFAULT 0,
+    download_bps    REAL    NOT NULL DEFAULT 0,
+    active_flows    REAL    NOT NULL DEFAULT 0,
+    latency_ms      REAL    NOT NULL DEFAULT 0,
+    time_wait       REAL,
+    ephemeral_ports REAL,
+    PRIMARY KEY (session_id, resolution, t)
+);
+";
+
+/// V21 schema — precomputed session list previews (JSON-encoded arrays).
+const SCHEMA_V21: &str = "
+CREATE TABLE IF NOT EXISTS session_thumbnails (
+    session_id   TEXT PRIMARY KEY REFERENCES sessions(id) ON DELETE CASCADE,
+    sparkline    TEXT NOT NULL DEFAULT '[]',
+    destinations TEXT NOT NULL DEFAULT '[]',
+    total_bytes  REAL NOT NULL DEFAULT 0,
+    computed_at  TEXT NOT NULL DEFAULT (datetime('now'))
+);
+";
+
+/// V22 schema — WAN traffic counted by the gateway (UPnP/SNMP) per poll
+/// interval, for comparing against host-level totals.
+const SCHEMA_V22: &str = "
+CREATE TABLE IF NOT EXISTS router_samples (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id      TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    timestamp       TEXT    NOT NULL,
+    source          TEXT    NOT NULL,
+    interval_secs   REAL    NOT NULL,
+    bytes_sent      REAL    NOT NULL,
+    bytes_received  REAL    NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_router_session ON router_samples(session_id);
+";
+
+/// V23 schema — default route at session start and on every change.  The
+/// preferred route is broken out; `routes` holds them all as JSON.
+const SCHEMA_V23: &str = "
+CREATE TABLE IF NOT EXISTS route_events (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id  TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    timestamp   TEXT    NOT NULL,
+    kind        TEXT    NOT NULL,
+    gateway     TEXT,
+    interface   TEXT,
+    metric      INTEGER,
+    routes      TEXT    NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX IF NOT EXISTS idx_route_events_session ON route_events(session_id, timestamp);
+";
+
+/// V24 schema — FTS5 search over sessions, destinations and the processes
+/// seen in each session, kept in sync by triggers.  `sessions` has no
+/// integer key, so its index stores its own copy of the text.
+const SCHEMA_V24: &str = "
+CREATE VIRTUAL TABLE IF NOT EXISTS sessions_fts USING fts5(
+    session_id UNINDEXED, name, notes, tags,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO sessions_fts (session_id, name, notes, tags)
+    SELECT id, name, COALESCE(notes, ''), COALESCE(tags, '') FROM sessions;
+
+CREATE TRIGGER IF NOT EXISTS sessions_fts_ai AFTER INSERT ON sessions BEGIN
+    INSERT INTO sessions_fts (session_id, name, notes, tags)
+    VALUES (new.id, new.name, COALESCE(new.notes, ''), COALESCE(new.tags, ''));
+END;
+CREATE TRIGGER IF NOT EXISTS sessions_fts_au AFTER UPDATE OF name, notes, tags ON sessions BEGIN
+    DELETE FROM sessions_fts WHERE session_id = old.id;
+    INSERT INTO sessions_fts (session_id, name, notes, tags)
+    VALUES (new.id, new.name, COALESCE(new.notes, ''), COALESCE(new.tags, ''));
+END;
+CREATE TRIGGER IF NOT EXISTS sessions_fts_ad AFTER DELETE ON sessions BEGIN
+    DELETE FROM sessions_fts WHERE session_id = old.id;
+END;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS destinations_fts USING fts5(
+    ip, domain, org, city, country,
+    content = 'destinations', content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO destinations_fts (destinations_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS destinations_fts_ai AFTER INSERT ON destinations BEGIN
+    INSERT INTO destinations_fts (rowid, ip, domain, org, city, country)
+    VALUES (new.id, new.ip, new.domain, new.org, new.city, new.country);
+END;
+CREATE TRIGGER IF NOT EXISTS destinations_fts_au AFTER UPDATE OF ip, domain, org, city, country ON destinations
+WHEN old.ip IS NOT new.ip OR old.domain IS NOT new.domain OR old.org IS NOT new.org
+  OR old.city IS NOT new.city OR old.country IS NOT new.country
+BEGIN
+    INSERT INTO destinations_fts (destinations_fts, rowid, ip, domain, org, city, country)
+    VALUES ('delete', old.id, old.ip, old.domain, old.org, old.city, old.country);
+    INSERT INTO destinations_fts (rowid, ip, domain, org, city, country)
+    VALUES (new.id, new.ip, new.domain, new.org, new.city, new.country);
+END;
+CREATE TRIGGER IF NOT EXISTS destinations_fts_ad AFTER DELETE ON destinations BEGIN
+    INSERT INTO destinations_fts (destinations_fts, rowid, ip, domain, org, city, country)
+    VALUES ('delete', old.id, old.ip, old.domain, old.org, old.city, old.country);
+END;
+
+-- One row per process per session; process_usage has one every 30 seconds
+CREATE TABLE IF NOT EXISTS session_processes (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id   TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    process_name TEXT    NOT NULL,
+    UNIQUE(session_id, process_name)
+);
+INSERT OR IGNORE INTO session_processes (session_id, process_name)
+    SELECT DISTINCT session_id, process_name FROM process_usage;
+
+CREATE TRIGGER IF NOT EXISTS session_processes_ai AFTER INSERT ON process_usage BEGIN
+    INSERT OR IGNORE INTO session_processes (session_id, process_name) VALUES (new.session_id, new.process_name);
+END;
+CREATE TRIGGER IF NOT EXISTS session_processes_au AFTER UPDATE OF session_id ON process_usage BEGIN
+    INSERT OR IGNORE INTO session_processes (session_id, process_name) VALUES (new.session_id, new.process_name);
+END;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS processes_fts USING fts5(
+    process_name,
+    content = 'session_processes', content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO processes_fts (processes_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS processes_fts_ai AFTER INSERT ON session_processes BEGIN
+    INSERT INTO processes_fts (rowid, process_name) VALUES (new.id, new.process_name);
+END;
+CREATE TRIGGER IF NOT EXISTS processes_fts_ad AFTER DELETE ON session_processes BEGIN
+    INSERT INTO processes_fts (processes_fts, rowid, process_name) VALUES ('delete', old.id, old.process_name);
+END;
+";
+
+/// V25 schema — Wi-Fi link quality per sampled frame (NULL on wired links
+/// or when the platform tool gave no reading).
+const SCHEMA_V25: &str = "
+ALTER TABLE frames ADD COLUMN wifi_ssid TEXT;
+ALTER TABLE frames ADD COLUMN wifi_rssi INTEGER;
+ALTER TABLE frames ADD COLUMN wifi_link_mbps REAL;
+ALTER TABLE frames ADD COLUMN wifi_channel INTEGER;
+";
+
+/// V26 schema — watched countries/ASNs and how often flows to them showed
+/// up in each session.
+const SCHEMA_V26: &str = "
+CREATE TABLE IF NOT EXISTS watchlist (
+    kind        TEXT    NOT NULL,
+    value       TEXT    NOT NULL,
+    added_at    TEXT    NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (kind, value)
+);
+
+CREATE TABLE IF NOT EXISTS watch_hits (
+    session_id  TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    kind        TEXT    NOT NULL,
+    value       TEXT    NOT NULL,
+    hits        INTEGER NOT NULL DEFAULT 0,
+    first_seen  TEXT    NOT NULL,
+    last_seen   TEXT    NOT NULL,
+    PRIMARY KEY (session_id, kind, value)
+);
+";
+
+/// V27 schema — outbound TCP connections per destination org and address
+/// family, and the IPv6 attempts that failed (per destination).
+const SCHEMA_V27: &str = "
+CREATE TABLE IF NOT EXISTS ip_family_usage (
+    session_id      TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    org             TEXT    NOT NULL,
+    v4_connections  INTEGER NOT NULL DEFAULT 0,
+    v6_connections  INTEGER NOT NULL DEFAULT 0,
+    v6_failures     INTEGER NOT NULL DEFAULT 0,
+    v6_fallbacks    INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (session_id, org)
+);
+
+CREATE TABLE IF NOT EXISTS ipv6_failures (
+    session_id      TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    org             TEXT    NOT NULL,
+    destination     TEXT    NOT NULL,
+    failures        INTEGER NOT NULL DEFAULT 0,
+    fallbacks       INTEGER NOT NULL DEFAULT 0,
+    last_failed_at  TEXT    NOT NULL,
+    PRIMARY KEY (session_id, org, destination)
+);
+";
+
+/// V28 schema — paths whose MTU probes found a reduced or black-holed MTU.
+const SCHEMA_V28: &str = "
+CREATE TABLE IF NOT EXISTS mtu_findings (
+    session_id  TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    ip          TEXT    NOT NULL,
+    domain      TEXT,
+    org         TEXT,
+    status      TEXT    NOT NULL,
+    path_mtu    INTEGER NOT NULL,
+    interface   TEXT,
+    probes      INTEGER NOT NULL DEFAULT 1,
+    first_seen  TEXT    NOT NULL,
+    last_seen   TEXT    NOT NULL,
+    PRIMARY KEY (session_id, ip)
+);
+";
+
+/// V29 schema — threat feed listing a flow's destination.
+const SCHEMA_V29: &str = "
+ALTER TABLE flow_snapshots ADD COLUMN threat TEXT;
+CREATE INDEX IF NOT EXISTS idx_flowsnap_threat ON flow_snapshots(session_id, threat) WHERE threat IS NOT NULL;
+";
+
+/// V30 schema — RDAP ownership records by IP (`WhoisInfo` as JSON).
+const SCHEMA_V30: &str = "
+CREATE TABLE IF NOT EXISTS whois_cache (
+    ip          TEXT    PRIMARY KEY,
+    info        TEXT    NOT NULL,
+    fetched_at  TEXT    NOT NULL
+);
+";
+
+/// V31 schema — public address at session start and on every change, with
+/// where it geolocated and whether the default route was a tunnel.
+const SCHEMA_V31: &str = "
+CREATE TABLE IF NOT EXISTS network_changes (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id  TEXT    NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    timestamp   TEXT    NOT NULL,
+    kind        TEXT    NOT NULL,
+    ip          TEXT    NOT NULL,
+    previous_ip TEXT,
+    city        TEXT,
+    country     TEXT,
+    lat         REAL,
+    lng         REAL,
+    org         TEXT,
+    interface   TEXT,
+    vpn         INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_network_changes_session ON network_changes(session_id, timestamp);
+";
+
+/// V32 schema — app a flow's or process's owner belongs to (helpers grouped
+/// under the process that started them).
+const SCHEMA_V32: &str = "
+ALTER TABLE flow_snapshots ADD COLUMN app_group TEXT;
+ALTER TABLE process_usage ADD COLUMN app_group TEXT;
+";
+
+/// V33 schema — approximate per-app daily usage imported from the OS for
+/// days before Abyss was recording, kept apart from measured data and
+/// marked with where it came from.
+const SCHEMA_V33: &str = "
+CREATE TABLE IF NOT EXISTS usage_backfill (
+    day         TEXT    NOT NULL,
+    app         TEXT    NOT NULL,
+    app_id      TEXT,
+    bytes_up    REAL    NOT NULL DEFAULT 0,
+    bytes_down  REAL    NOT NULL DEFAULT 0,
+    source      TEXT    NOT NULL,
+    imported_at TEXT    NOT NULL,
+    PRIMARY KEY (day, app, source)
+);
+";
+
+/// V34 schema — executable path, version, signer and icon per process name.
+const SCHEMA_V34: &str = "
+CREATE TABLE IF NOT EXISTS process_meta (
+    name         TEXT    PRIMARY KEY COLLATE NOCASE,
+    exe_path     TEXT,
+    version      TEXT,
+    product_name TEXT,
+    description  TEXT,
+    publisher    TEXT,
+    signer       TEXT,
+    signed       INTEGER NOT NULL DEFAULT 0,
+    icon_png     TEXT,
+    resolved_at  TEXT    NOT NULL
+);
+";
+
+/// V35 schema — Docker container or WSL distro behind a flow.
+const SCHEMA_V35: &str = "
+ALTER TABLE flow_snapshots ADD COLUMN container TEXT;
+ALTER TABLE flow_snapshots ADD COLUMN container_image TEXT;
+";
+
+/// V36 schema — data-usage budgets with the writer's running consumption
+/// for the current period.  `process` is '' for the global budget.
+const SCHEMA_V36: &str = "
+CREATE TABLE IF NOT EXISTS data_budgets (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    period        TEXT    NOT NULL,
+    process       TEXT    NOT NULL DEFAULT '' COLLATE NOCASE,
+    bytes_limit   REAL    NOT NULL,
+    period_start  TEXT    NOT NULL,
+    used_bytes    REAL    NOT NULL DEFAULT 0,
+    notified_pct  INTEGER NOT NULL DEFAULT 0,
+    created_at    TEXT    NOT NULL,
+    updated_at    TEXT    NOT NULL,
+    UNIQUE (period, process)
+);
+";
+
+/// V37 schema — destinations by country (and owning process) across
+/// sessions, for the country breakdown.
+const SCHEMA_V37: &str = "
+CREATE INDEX IF NOT EXISTS idx_dest_country_process ON destinations(country, primary_process);
+";
+
+/// V38 schema — destinations by owning organisation, for the org breakdown.
+const SCHEMA_V38: &str = "
+CREATE INDEX IF NOT EXISTS idx_dest_org ON destinations(org, asn);
+";
+
+/// V39 schema — the anonymization key moved out of the database (beside it,
+/// see `anonymize::AnonymizeKey`); a new one is generated when hashing is
+/// next used.
+const SCHEMA_V39: &str = "
+DELETE FROM settings WHERE key = 'anonymizeKey';
+";
+
 // ─── Query helpers ──────────────────────────────────────────────────────────
 
 /// Insert a new session row.
+#[allow(clippy::too_many_arguments)]
 pub fn insert_session(
     conn: &Connection,
     id: &str,
@@ -231,7 +960,16 @@ pub fn finalize_session(conn: &Connection, id: &str, ended_at: &str) -> SqlResul
     Ok(())
 }
 
+const INSERT_FRAME_SQL: &str = "INSERT INTO frames
+    (session_id,t,timestamp,bps,pps,active_flows,latency_ms,
+     upload_bps,download_bps,
+     proto_tcp,proto_udp,proto_icmp,proto_dns,proto_https,proto_http,proto_other,
+     time_wait,ephemeral_ports,
+     wifi_ssid,wifi_rssi,wifi_link_mbps,wifi_channel)
+    VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20,?21,?22)";
+
 /// Insert a telemetry frame row.  Returns the new row id.
+#[allow(clippy::too_many_arguments)]
 pub fn insert_frame(
     conn: &Connection,
     session_id: &str,
@@ -250,36 +988,46 @@ pub fn insert_frame(
     proto_https: u32,
     proto_http: u32,
     proto_other: u32,
+    time_wait: Option<u32>,
+    ephemeral_ports: Option<u32>,
+    wifi: Option<&WifiLink>,
 ) -> SqlResult<i64> {
-    conn.execute(
-        "INSERT INTO frames
-         (session_id,t,timestamp,bps,pps,active_flows,latency_ms,
-          upload_bps,download_bps,
-          proto_tcp,proto_udp,proto_icmp,proto_dns,proto_https,proto_http,proto_other)
-         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16)",
-        params![
-            session_id,
-            t,
-            timestamp,
-            bps,
-            pps,
-            active_flows,
-            latency_ms,
-            upload_bps,
-            download_bps,
-            proto_tcp,
-            proto_udp,
-            proto_icmp,
-            proto_dns,
-            proto_https,
-            proto_http,
-            proto_other,
-        ],
-    )?;
-    Ok(conn.last_insert_rowid())
+    conn.prepare_cached(INSERT_FRAME_SQL)?.insert(params![
+        session_id,
+        t,
+        timestamp,
+        bps,
+        pps,
+        active_flows,
+        latency_ms,
+        upload_bps,
+        download_bps,
+        proto_tcp,
+        proto_udp,
+        proto_icmp,
+        proto_dns,
+        proto_https,
+        proto_http,
+        proto_other,
+        time_wait,
+        ephemeral_ports,
+        wifi.and_then(|w| w.ssid.as_deref()),
+        wifi.and_then(|w| w.rssi_dbm),
+        wifi.and_then(|w| w.link_mbps()),
+        wifi.and_then(|w| w.channel),
+    ])
 }
 
+const INSERT_FLOW_SNAPSHOT_SQL: &str = "INSERT INTO flow_snapshots
+    (session_id,frame_id,flow_id,src_ip,src_city,src_country,
+     dst_ip,dst_lat,dst_lng,dst_city,dst_country,dst_asn,dst_org,
+     bps,pps,rtt,protocol,dir,port,service,started_at,process,pid,domain,sni,
+     service_class,threat,app_group,container,container_image)
+    VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,
+            ?14,?15,?16,?17,?18,?19,?20,?21,?22,?23,?24,?25,?26,?27,?28,?29,?30)";
+
 /// Insert a flow snapshot row.
+#[allow(clippy::too_many_arguments)]
 pub fn insert_flow_snapshot(
     conn: &Connection,
     session_id: &str,
@@ -305,44 +1053,63 @@ pub fn insert_flow_snapshot(
     started_at: f64,
     process: Option<&str>,
     pid: Option<u32>,
+    domain: Option<&str>,
+    sni: Option<&str>,
+    service_class: Option<&str>,
+    threat: Option<&str>,
+    app_group: Option<&str>,
+    container: Option<(&str, Option<&str>)>,
 ) -> SqlResult<()> {
-    conn.execute(
-        "INSERT INTO flow_snapshots
-         (session_id,frame_id,flow_id,src_ip,src_city,src_country,
-          dst_ip,dst_lat,dst_lng,dst_city,dst_country,dst_asn,dst_org,
-          bps,pps,rtt,protocol,dir,port,service,started_at,process,pid)
-         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,
-                 ?14,?15,?16,?17,?18,?19,?20,?21,?22,?23)",
-        params![
-            session_id,
-            frame_id,
-            flow_id,
-            src_ip,
-            src_city,
-            src_country,
-            dst_ip,
-            dst_lat,
-            dst_lng,
-            dst_city,
-            dst_country,
-            dst_asn,
-            dst_org,
-            bps,
-            pps,
-            rtt,
-            protocol,
-            dir,
-            port,
-            service,
-            started_at,
-            process,
-            pid,
-        ],
-    )?;
+    conn.prepare_cached(INSERT_FLOW_SNAPSHOT_SQL)?.execute(params![
+        session_id,
+        frame_id,
+        flow_id,
+        src_ip,
+        src_city,
+        src_country,
+        dst_ip,
+        dst_lat,
+        dst_lng,
+        dst_city,
+        dst_country,
+        dst_asn,
+        dst_org,
+        bps,
+        pps,
+        rtt,
+        protocol,
+        dir,
+        port,
+        service,
+        started_at,
+        process,
+        pid,
+        domain,
+        sni,
+        service_class,
+        threat,
+        app_group,
+        container.map(|(name, _)| name),
+        container.and_then(|(_, image)| image),
+    ])?;
     Ok(())
 }
 
+const UPDATE_SESSION_TOTALS_SQL: &str = "UPDATE sessions SET
+        total_bytes_up   = total_bytes_up   + ?1,
+        total_bytes_down = total_bytes_down + ?2,
+        peak_bps         = MAX(peak_bps, ?3),
+        peak_flows       = MAX(peak_flows, ?4),
+        avg_latency_ms   = CASE
+            WHEN latency_samples = 0 THEN ?5
+            ELSE (avg_latency_ms * latency_samples + ?5) / (latency_samples + 1)
+        END,
+        latency_samples  = latency_samples + 1,
+        total_flows      = total_flows + ?6
+    WHERE id = ?7";
+
 /// Update running totals on the session row.
+#[allow(clippy::too_many_arguments)]
 pub fn update_session_totals(
     conn: &Connection,
     id: &str,
@@ -353,33 +1120,32 @@ pub fn update_session_totals(
     latency_ms: f64,
     new_unique_flows: u32,
 ) -> SqlResult<()> {
-    conn.execute(
-        "UPDATE sessions SET
-            total_bytes_up   = total_bytes_up   + ?1,
-            total_bytes_down = total_bytes_down + ?2,
-            peak_bps         = MAX(peak_bps, ?3),
-            peak_flows       = MAX(peak_flows, ?4),
-            avg_latency_ms   = CASE
-                WHEN latency_samples = 0 THEN ?5
-                ELSE (avg_latency_ms * latency_samples + ?5) / (latency_samples + 1)
-            END,
-            latency_samples  = latency_samples + 1,
-            total_flows      = total_flows + ?6
-         WHERE id = ?7",
-        params![
-            bytes_up_delta,
-            bytes_down_delta,
-            current_bps,
-            current_flows,
-            latency_ms,
-            new_unique_flows,
-            id,
-        ],
-    )?;
+    conn.prepare_cached(UPDATE_SESSION_TOTALS_SQL)?.execute(params![
+        bytes_up_delta,
+        bytes_down_delta,
+        current_bps,
+        current_flows,
+        latency_ms,
+        new_unique_flows,
+        id,
+    ])?;
     Ok(())
 }
 
+const UPSERT_DESTINATION_SQL: &str = "INSERT INTO destinations
+        (session_id, ip, city, country, asn, org, first_seen, last_seen,
+        total_bytes, connection_count, primary_service, primary_process, domain)
+    VALUES (?1,?2,?3,?4,?5,?6,?7,?7,?8,1,?9,?10,?11)
+    ON CONFLICT(session_id, ip) DO UPDATE SET
+        last_seen        = MAX(last_seen, excluded.last_seen),
+        total_bytes      = total_bytes + excluded.total_bytes,
+        connection_count = connection_count + 1,
+        primary_service  = COALESCE(excluded.primary_service, primary_service),
+        primary_process  = COALESCE(excluded.primary_process, primary_process),
+        domain           = COALESCE(excluded.domain, domain)";
+
 /// Upsert a destination row for a session.
+#[allow(clippy::too_many_arguments)]
 pub fn upsert_destination(
     conn: &Connection,
     session_id: &str,
@@ -392,46 +1158,49 @@ pub fn upsert_destination(
     bytes: f64,
     service: Option<&str>,
     process: Option<&str>,
+    domain: Option<&str>,
 ) -> SqlResult<()> {
-    conn.execute(
-        "INSERT INTO destinations
-            (session_id, ip, city, country, asn, org, first_seen, last_seen,
-             total_bytes, connection_count, primary_service, primary_process)
-         VALUES (?1,?2,?3,?4,?5,?6,?7,?7,?8,1,?9,?10)
-         ON CONFLICT(session_id, ip) DO UPDATE SET
-            last_seen        = MAX(last_seen, excluded.last_seen),
-            total_bytes      = total_bytes + excluded.total_bytes,
-            connection_count = connection_count + 1,
-            primary_service  = COALESCE(excluded.primary_service, primary_service),
-            primary_process  = COALESCE(excluded.primary_process, primary_process)",
-        params![session_id, ip, city, country, asn, org, t, bytes, service, process],
-    )?;
+    conn.prepare_cached(UPSERT_DESTINATION_SQL)?.execute(params![session_id, ip, city, country, asn, org, t, bytes, service, process, domain])?;
     Ok(())
 }
 
+const INSERT_PROCESS_USAGE_SQL: &str = "INSERT INTO process_usage
+    (session_id, timestamp, process_name, app_group, bytes_up, bytes_down, flow_count, avg_rtt)
+    VALUES (?1,?2,?3,?4,?5,?6,?7,?8)";
+
 /// Insert per-process usage snapshot.
+#[allow(clippy::too_many_arguments)]
 pub fn insert_process_usage(
     conn: &Connection,
     session_id: &str,
     timestamp: &str,
     process_name: &str,
+    app_group: Option<&str>,
     bytes_up: f64,
     bytes_down: f64,
     flow_count: u32,
     avg_rtt: f64,
 ) -> SqlResult<()> {
-    conn.execute(
-        "INSERT INTO process_usage
-         (session_id, timestamp, process_name, bytes_up, bytes_down, flow_count, avg_rtt)
-         VALUES (?1,?2,?3,?4,?5,?6,?7)",
-        params![session_id, timestamp, process_name, bytes_up, bytes_down, flow_count, avg_rtt],
-    )?;
+    conn.prepare_cached(INSERT_PROCESS_USAGE_SQL)?.execute(params![session_id, timestamp, process_name, app_group, bytes_up, bytes_down, flow_count, avg_rtt])?;
     Ok(())
 }
 
 /// Recover crashed sessions (those with NULL ended_at) by setting ended_at to
 /// the latest frame timestamp, or the session start time if no frames exist.
-pub fn recover_crashed_sessions(conn: &Connection) -> SqlResult<u32> {
+/// Frames the writer journaled but never committed are replayed first.
+pub fn recover_crashed_sessions(conn: &Connection, journal: &[JournalEntry]) -> SqlResult<u32> {
+    let open: Vec<String> = conn
+        .prepare("SELECT id FROM sessions WHERE ended_at IS NULL")?
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    for id in &open {
+        let entries: Vec<&JournalEntry> = journal.iter().filter(|e| &e.session_id == id).collect();
+        if !entries.is_empty() {
+            replay_journal(conn, id, &entries)?;
+        }
+    }
+
     let mut count = 0u32;
     let mut stmt = conn.prepare(
         "SELECT s.id, s.started_at,
@@ -459,18 +1228,193 @@ pub fn recover_crashed_sessions(conn: &Connection) -> SqlResult<u32> {
     Ok(count)
 }
 
-// ─── Read queries used by Tauri commands ────────────────────────────────────
+/// Write the journaled frames newer than the session's last stored frame
+/// and add the bytes integrated across the whole journal (it starts at the
+/// last frame whose totals were committed) to the session totals.
+fn replay_journal(conn: &Connection, session_id: &str, entries: &[&JournalEntry]) -> SqlResult<()> {
+    let last_t: Option<f64> = conn.query_row(
+        "SELECT MAX(t) FROM frames WHERE session_id = ?1",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+    let tx = conn.unchecked_transaction()?;
+    let mut written = 0;
+    for e in entries.iter().filter(|e| last_t.is_none_or(|t| e.t > t)) {
+        let [tcp, udp, icmp, dns, https, http, other] = e.proto;
+        insert_frame(
+            &tx,
+            session_id,
+            e.t,
+            &e.timestamp,
+            e.bps,
+            e.pps,
+            e.active_flows,
+            e.latency_ms,
+            e.upload_bps,
+            e.download_bps,
+            tcp,
+            udp,
+            icmp,
+            dns,
+            https,
+            http,
+            other,
+            e.time_wait,
+            e.ephemeral_ports,
+            None,
+        )?;
+        written += 1;
+    }
 
-use serde::Serialize;
+    let (mut bytes_up, mut bytes_down) = (0.0, 0.0);
+    for pair in entries.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        let dt = next.t - prev.t;
+        if dt > 0.0 && dt <= integration_gap_secs(next.tick_ms) {
+            bytes_up += (prev.upload_bps + next.upload_bps) / 2.0 * dt / 8.0;
+            bytes_down += (prev.download_bps + next.download_bps) / 2.0 * dt / 8.0;
+        }
+    }
+    let peak_bps = entries.iter().map(|e| e.bps).fold(0.0, f64::max);
+    let peak_flows = entries.iter().map(|e| e.active_flows).max().unwrap_or(0);
+    tx.execute(
+        "UPDATE sessions SET
+            total_bytes_up   = total_bytes_up + ?1,
+            total_bytes_down = total_bytes_down + ?2,
+            peak_bps         = MAX(peak_bps, ?3),
+            peak_flows       = MAX(peak_flows, ?4)
+         WHERE id = ?5",
+        params![bytes_up, bytes_down, peak_bps, peak_flows, session_id],
+    )?;
+    add_recording_stats(
+        &tx,
+        session_id,
+        &RecordingStats {
+            frames_written: written,
+            ..Default::default()
+        },
+    )?;
+    tx.commit()
+}
 
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct SessionInfo {
-    pub id: String,
-    pub name: String,
-    pub started_at: String,
-    pub ended_at: Option<String>,
-    pub duration_secs: Option<f64>,
+// ─── Anonymization ──────────────────────────────────────────────────────────
+
+/// Replace every address recorded in `session_id` with `anonymize(ip)` and
+/// drop the hostnames resolved for them (and process names in its alerts),
+/// so the session can be shared.
+/// Rows that collapse onto one anonymized address (two hosts in the same
+/// /24) are merged.  Returns how many distinct addresses were replaced.
+pub fn anonymize_session(
+    conn: &Connection,
+    session_id: &str,
+    anonymize: &dyn Fn(&str) -> String,
+) -> SqlResult<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut replaced = HashSet::new();
+    // (table, column, statement moving rows from ?2 to ?3)
+    let renames: [(&str, &str, &str); 9] = [
+        ("flow_snapshots", "dst_ip",
+            "UPDATE flow_snapshots SET dst_ip = ?3, flow_id = replace(flow_id, ?2, ?3)
+             WHERE session_id = ?1 AND dst_ip = ?2"),
+        ("flow_snapshots", "src_ip",
+            "UPDATE flow_snapshots SET src_ip = ?3 WHERE session_id = ?1 AND src_ip = ?2"),
+        ("flow_events", "remote_ip",
+            "UPDATE flow_events SET remote_ip = ?3, flow_id = replace(flow_id, ?2, ?3)
+             WHERE session_id = ?1 AND remote_ip = ?2"),
+        ("flow_events", "local_ip",
+            "UPDATE flow_events SET local_ip = ?3 WHERE session_id = ?1 AND local_ip = ?2"),
+        ("network_changes", "ip",
+            "UPDATE network_changes SET ip = ?3 WHERE session_id = ?1 AND ip = ?2"),
+        ("network_changes", "previous_ip",
+            "UPDATE network_changes SET previous_ip = ?3 WHERE session_id = ?1 AND previous_ip = ?2"),
+        ("route_events", "gateway",
+            "UPDATE route_events SET gateway = ?3, routes = replace(routes, ?2, ?3)
+             WHERE session_id = ?1 AND gateway = ?2"),
+        ("destinations", "ip",
+            "UPDATE destinations AS d SET
+                total_bytes      = d.total_bytes + o.total_bytes,
+                connection_count = d.connection_count + o.connection_count,
+                first_seen       = MIN(COALESCE(d.first_seen, o.first_seen), COALESCE(o.first_seen, d.first_seen)),
+                last_seen        = MAX(COALESCE(d.last_seen, o.last_seen), COALESCE(o.last_seen, d.last_seen))
+             FROM destinations AS o
+             WHERE d.session_id = ?1 AND d.ip = ?3 AND o.session_id = ?1 AND o.ip = ?2;
+             DELETE FROM destinations WHERE session_id = ?1 AND ip = ?2
+                AND EXISTS (SELECT 1 FROM destinations WHERE session_id = ?1 AND ip = ?3);
+             UPDATE destinations SET ip = ?3 WHERE session_id = ?1 AND ip = ?2"),
+        ("mtu_findings", "ip",
+            "UPDATE mtu_findings AS m SET
+                probes     = m.probes + o.probes,
+                first_seen = MIN(m.first_seen, o.first_seen),
+                last_seen  = MAX(m.last_seen, o.last_seen)
+             FROM mtu_findings AS o
+             WHERE m.session_id = ?1 AND m.ip = ?3 AND o.session_id = ?1 AND o.ip = ?2;
+             DELETE FROM mtu_findings WHERE session_id = ?1 AND ip = ?2
+                AND EXISTS (SELECT 1 FROM mtu_findings WHERE session_id = ?1 AND ip = ?3);
+             UPDATE mtu_findings SET ip = ?3 WHERE session_id = ?1 AND ip = ?2"),
+    ];
+    for (table, column, rename) in renames {
+        let values: Vec<String> = tx
+            .prepare(&format!(
+                "SELECT DISTINCT {column} FROM {table} WHERE session_id = ?1 AND {column} IS NOT NULL"
+            ))?
+            .query_map(params![session_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        for value in values {
+            let anonymized = anonymize(&value);
+            if anonymized == value {
+                continue;
+            }
+            for statement in rename.split(';') {
+                tx.execute(statement, params![session_id, value, anonymized])?;
+            }
+            replaced.insert(value);
+        }
+    }
+    // Alerts keep the matched flow ids (with their addresses) and the process
+    let alerts: Vec<(String, String, String, String)> = tx
+        .prepare("SELECT id, subject, message, context FROM alert_events WHERE session_id = ?1")?
+        .query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    for (id, mut subject, mut message, context) in alerts {
+        let Ok(mut context) = serde_json::from_str::<AlertContext>(&context) else {
+            continue;
+        };
+        replaced.extend(anonymize::scrub_alert(&mut subject, &mut message, &mut context, anonymize));
+        let context = serde_json::to_string(&context)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        tx.execute(
+            "UPDATE alert_events SET subject = ?2, message = ?3, context = ?4 WHERE id = ?1",
+            params![id, subject, message, context],
+        )?;
+    }
+    // Hostnames identify destinations as well as addresses do
+    for statement in [
+        "UPDATE flow_snapshots SET domain = NULL, sni = NULL WHERE session_id = ?1",
+        "UPDATE destinations SET domain = NULL WHERE session_id = ?1",
+        "UPDATE mtu_findings SET domain = NULL WHERE session_id = ?1",
+        // Keyed by address alongside org; the per-org totals stay
+        "DELETE FROM ipv6_failures WHERE session_id = ?1",
+    ] {
+        tx.execute(statement, params![session_id])?;
+    }
+    tx.commit()?;
+    Ok(replaced.len())
+}
+
+// ─── Read queries used by Tauri commands ────────────────────────────────────
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub name: String,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub duration_secs: Option<f64>,
     pub total_bytes_up: f64,
     pub total_bytes_down: f64,
     pub total_flows: i64,
@@ -484,6 +1428,33 @@ pub struct SessionInfo {
     pub notes: String,
     pub tags: String,
     pub status: String,
+    /// Frames and flow snapshots were pruned; only the summary remains.
+    #[serde(default)]
+    pub summary_only: bool,
+    /// Writer counters; only filled in by `get_session`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recording_stats: Option<RecordingStats>,
+}
+
+/// What the writer did with a session's frames (`recording_stats`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStats {
+    /// Rows written to `frames` / `flow_snapshots`.
+    pub frames_written: i64,
+    pub flows_written: i64,
+    /// Frames received but not persisted because of the sampling interval.
+    pub rows_skipped: i64,
+    /// Persistence failures reported while the session was recording.
+    pub writer_errors: i64,
+    /// Frames discarded while the writer was paused (backup, restore, move).
+    pub dropped_frames: i64,
+}
+
+impl RecordingStats {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 pub fn list_sessions(
@@ -496,7 +1467,7 @@ pub fn list_sessions(
                 total_bytes_up, total_bytes_down, total_flows,
                 peak_bps, peak_flows, avg_latency_ms,
                 local_city, local_country, local_lat, local_lng, notes, tags,
-                crash_recovered
+                crash_recovered, summary_only
          FROM sessions
          ORDER BY started_at DESC
          LIMIT ?1 OFFSET ?2",
@@ -531,6 +1502,8 @@ pub fn list_sessions(
                 notes: row.get(15)?,
                 tags: row.get(16)?,
                 status,
+                summary_only: row.get::<_, i32>(18).unwrap_or(0) != 0,
+                recording_stats: None,
             })
         })?
         .filter_map(|r| r.ok())
@@ -544,7 +1517,7 @@ pub fn get_session(conn: &Connection, id: &str) -> SqlResult<Option<SessionInfo>
                 total_bytes_up, total_bytes_down, total_flows,
                 peak_bps, peak_flows, avg_latency_ms,
                 local_city, local_country, local_lat, local_lng, notes, tags,
-                crash_recovered
+                crash_recovered, summary_only
          FROM sessions WHERE id = ?1",
     )?;
     let mut rows = stmt.query_map(params![id], |row| {
@@ -576,6 +1549,55 @@ pub fn get_session(conn: &Connection, id: &str) -> SqlResult<Option<SessionInfo>
             notes: row.get(15)?,
             tags: row.get(16)?,
             status,
+            summary_only: row.get::<_, i32>(18).unwrap_or(0) != 0,
+            recording_stats: None,
+        })
+    })?;
+    let Some(mut session) = rows.next().transpose()? else {
+        return Ok(None);
+    };
+    session.recording_stats = get_recording_stats(conn, id)?;
+    Ok(Some(session))
+}
+
+/// Add `delta` to a session's `recording_stats` row, creating it if needed.
+pub fn add_recording_stats(conn: &Connection, session_id: &str, delta: &RecordingStats) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO recording_stats
+            (session_id, frames_written, flows_written, rows_skipped, writer_errors, dropped_frames)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(session_id) DO UPDATE SET
+            frames_written = frames_written + excluded.frames_written,
+            flows_written  = flows_written  + excluded.flows_written,
+            rows_skipped   = rows_skipped   + excluded.rows_skipped,
+            writer_errors  = writer_errors  + excluded.writer_errors,
+            dropped_frames = dropped_frames + excluded.dropped_frames,
+            updated_at     = datetime('now')",
+        params![
+            session_id,
+            delta.frames_written,
+            delta.flows_written,
+            delta.rows_skipped,
+            delta.writer_errors,
+            delta.dropped_frames
+        ],
+    )?;
+    Ok(())
+}
+
+/// `None` for sessions recorded before the writer kept counters.
+pub fn get_recording_stats(conn: &Connection, session_id: &str) -> SqlResult<Option<RecordingStats>> {
+    let mut stmt = conn.prepare(
+        "SELECT frames_written, flows_written, rows_skipped, writer_errors, dropped_frames
+         FROM recording_stats WHERE session_id = ?1",
+    )?;
+    let mut rows = stmt.query_map(params![session_id], |row| {
+        Ok(RecordingStats {
+            frames_written: row.get(0)?,
+            flows_written: row.get(1)?,
+            rows_skipped: row.get(2)?,
+            writer_errors: row.get(3)?,
+            dropped_frames: row.get(4)?,
         })
     })?;
     rows.next().transpose()
@@ -586,7 +1608,7 @@ pub fn delete_session(conn: &Connection, id: &str) -> SqlResult<bool> {
     Ok(affected > 0)
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct FrameRecord {
     pub t: f64,
@@ -597,6 +1619,18 @@ pub struct FrameRecord {
     pub active_flows: i64,
     pub latency_ms: f64,
     pub pps: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_wait: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ephemeral_ports: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wifi_ssid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wifi_rssi: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wifi_link_mbps: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wifi_channel: Option<i64>,
 }
 
 pub fn get_session_frames(
@@ -607,10 +1641,20 @@ pub fn get_session_frames(
     max_points: Option<u32>,
 ) -> SqlResult<Vec<FrameRecord>> {
     // Build the query dynamically based on optional time range
-    let base = "SELECT t, timestamp, bps, upload_bps, download_bps,
-                       active_flows, latency_ms, pps
-                FROM frames WHERE session_id = ?1";
-    let mut sql = base.to_string();
+    let mut sql = match rollup_resolution(conn, session_id, max_points)? {
+        None => "SELECT t, timestamp, bps, upload_bps, download_bps,
+                        active_flows, latency_ms, pps, time_wait, ephemeral_ports,
+                        wifi_ssid, wifi_rssi, wifi_link_mbps, wifi_channel
+                 FROM frames WHERE session_id = ?1"
+            .to_string(),
+        Some(resolution) => format!(
+            "SELECT t, timestamp, bps, upload_bps, download_bps,
+                    CAST(ROUND(active_flows) AS INTEGER), latency_ms, CAST(ROUND(pps) AS INTEGER),
+                    CAST(ROUND(time_wait) AS INTEGER), CAST(ROUND(ephemeral_ports) AS INTEGER),
+                    NULL, NULL, NULL, NULL
+             FROM frames_rollup WHERE session_id = ?1 AND resolution = {resolution}"
+        ),
+    };
     let mut param_idx = 2u32;
 
     if start_t.is_some() {
@@ -647,6 +1691,12 @@ pub fn get_session_frames(
                 active_flows: row.get(5)?,
                 latency_ms: row.get(6)?,
                 pps: row.get(7)?,
+                time_wait: row.get(8)?,
+                ephemeral_ports: row.get(9)?,
+                wifi_ssid: row.get(10)?,
+                wifi_rssi: row.get(11)?,
+                wifi_link_mbps: row.get(12)?,
+                wifi_channel: row.get(13)?,
             })
         })?
         .filter_map(|r| r.ok())
@@ -678,10 +1728,40 @@ pub fn get_session_frames(
     Ok(all_rows)
 }
 
-#[derive(Serialize, Clone, Debug)]
+/// Which rollup `get_session_frames` reads: `None` while raw frames remain,
+/// else the finest resolution that fits in `max_points` (the coarsest when
+/// none does).
+fn rollup_resolution(conn: &Connection, session_id: &str, max_points: Option<u32>) -> SqlResult<Option<i64>> {
+    let has_raw: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM frames WHERE session_id = ?1)",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+    if has_raw {
+        return Ok(None);
+    }
+    let mut stmt = conn.prepare(
+        "SELECT resolution, COUNT(*) FROM frames_rollup WHERE session_id = ?1
+         GROUP BY resolution ORDER BY resolution ASC",
+    )?;
+    let counts: Vec<(i64, i64)> = stmt
+        .query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    let chosen = match max_points {
+        Some(max) => counts.iter().find(|(_, n)| *n <= max as i64).or(counts.last()),
+        None => counts.first(),
+    };
+    Ok(chosen.map(|(resolution, _)| *resolution))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct FlowSnapshotRecord {
     pub flow_id: String,
+    /// `t` of the frame the snapshot belongs to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_t: Option<f64>,
     pub src_ip: Option<String>,
     pub src_city: Option<String>,
     pub src_country: Option<String>,
@@ -700,6 +1780,16 @@ pub struct FlowSnapshotRecord {
     pub service: Option<String>,
     pub process: Option<String>,
     pub pid: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threat: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_image: Option<String>,
 }
 
 pub fn get_session_flows(
@@ -712,7 +1802,9 @@ pub fn get_session_flows(
     let mut sql = String::from(
         "SELECT flow_id, src_ip, src_city, src_country,
                 dst_ip, dst_lat, dst_lng, dst_city, dst_country, dst_org,
-                bps, pps, rtt, protocol, dir, port, service, process, pid
+                bps, pps, rtt, protocol, dir, port, service, process, pid, service_class,
+                (SELECT t FROM frames WHERE frames.id = flow_snapshots.frame_id), threat, app_group,
+                container, container_image
          FROM flow_snapshots WHERE session_id = ?1",
     );
     let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
@@ -736,6 +1828,7 @@ pub fn get_session_flows(
         .query_map(param_refs.as_slice(), |row| {
             Ok(FlowSnapshotRecord {
                 flow_id: row.get(0)?,
+                frame_t: row.get(20)?,
                 src_ip: row.get(1)?,
                 src_city: row.get(2)?,
                 src_country: row.get(3)?,
@@ -754,6 +1847,11 @@ pub fn get_session_flows(
                 service: row.get(16)?,
                 process: row.get(17)?,
                 pid: row.get(18)?,
+                service_class: row.get(19)?,
+                threat: row.get(21)?,
+                app_group: row.get(22)?,
+                container: row.get(23)?,
+                container_image: row.get(24)?,
             })
         })?
         .filter_map(|r| r.ok())
@@ -761,8 +1859,163 @@ pub fn get_session_flows(
     Ok(rows)
 }
 
+/// One snapshot of a single flow, for its sparkline.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowTimeseriesPoint {
+    pub t: f64,
+    pub bps: f64,
+    pub rtt: f64,
+    pub pps: i64,
+}
+
+/// Every recorded snapshot of `flow_id` in a session, oldest first,
+/// thinned to `max_points` the same way as `get_session_frames`.
+pub fn get_flow_timeseries(
+    conn: &Connection,
+    session_id: &str,
+    flow_id: &str,
+    max_points: Option<u32>,
+) -> SqlResult<Vec<FlowTimeseriesPoint>> {
+    let mut stmt = conn.prepare(
+        "SELECT f.t, fs.bps, fs.rtt, fs.pps
+         FROM flow_snapshots fs
+         JOIN frames f ON f.id = fs.frame_id
+         WHERE fs.session_id = ?1 AND fs.flow_id = ?2
+         ORDER BY f.t ASC",
+    )?;
+    let all_rows: Vec<FlowTimeseriesPoint> = stmt
+        .query_map(params![session_id, flow_id], |row| {
+            Ok(FlowTimeseriesPoint {
+                t: row.get(0)?,
+                bps: row.get(1)?,
+                rtt: row.get(2)?,
+                pps: row.get(3)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let max = max_points.map_or(usize::MAX, |m| m.max(2) as usize);
+    if all_rows.len() <= max {
+        return Ok(all_rows);
+    }
+    let step = all_rows.len() as f64 / max as f64;
+    let mut result: Vec<FlowTimeseriesPoint> = (0..max).map(|i| all_rows[(i as f64 * step) as usize].clone()).collect();
+    // Always include last point
+    if let Some(last) = all_rows.last() {
+        if result.last().map(|r| r.t) != Some(last.t) {
+            result.push(last.clone());
+        }
+    }
+    Ok(result)
+}
+
+/// Optional filters applied before grouping in `query_flows`.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowQueryFilters {
+    pub process: Option<String>,
+    pub country: Option<String>,
+    pub org: Option<String>,
+    pub port: Option<u16>,
+    pub protocol: Option<String>,
+    pub min_bps: Option<f64>,
+}
+
+/// One aggregated row of a `query_flows` pivot.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
+pub struct FlowGroupRecord {
+    pub key: String,
+    pub total_bytes: f64,
+    pub flow_count: i64,
+    pub snapshot_count: i64,
+    pub unique_destinations: i64,
+    pub avg_bps: f64,
+    pub avg_rtt: f64,
+}
+
+/// Aggregate a session's flow snapshots by `group_by` ("process", "country",
+/// "org", "port", "protocol" or "serviceClass").  Bytes use the same 1-second-per-snapshot
+/// estimate as the destinations table.
+pub fn query_flows(
+    conn: &Connection,
+    session_id: &str,
+    group_by: &str,
+    filters: &FlowQueryFilters,
+    limit: u32,
+) -> SqlResult<Vec<FlowGroupRecord>> {
+    let key_expr = match group_by {
+        "country" => "COALESCE(NULLIF(dst_country, ''), '??')",
+        "org" => "COALESCE(NULLIF(dst_org, ''), 'Unknown')",
+        "port" => "CAST(COALESCE(port, 0) AS TEXT)",
+        "protocol" => "COALESCE(NULLIF(protocol, ''), 'other')",
+        "serviceClass" => "COALESCE(service_class, 'unclassified')",
+        _ => "COALESCE(NULLIF(process, ''), 'Unknown')", // default "process"
+    };
+    let mut sql = format!(
+        "SELECT {key_expr} AS grp,
+                COALESCE(SUM(bps), 0) / 8.0,
+                COUNT(DISTINCT flow_id),
+                COUNT(*),
+                COUNT(DISTINCT dst_ip),
+                COALESCE(AVG(bps), 0),
+                COALESCE(AVG(CASE WHEN rtt > 0 THEN rtt ELSE NULL END), 0)
+         FROM flow_snapshots WHERE session_id = ?1"
+    );
+    let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+    params_vec.push(Box::new(session_id.to_string()));
+
+    if let Some(proc) = &filters.process {
+        params_vec.push(Box::new(proc.clone()));
+        sql.push_str(&format!(" AND process = ?{}", params_vec.len()));
+    }
+    if let Some(country) = &filters.country {
+        params_vec.push(Box::new(country.clone()));
+        sql.push_str(&format!(" AND dst_country = ?{}", params_vec.len()));
+    }
+    if let Some(org) = &filters.org {
+        params_vec.push(Box::new(org.clone()));
+        sql.push_str(&format!(" AND dst_org = ?{}", params_vec.len()));
+    }
+    if let Some(port) = filters.port {
+        params_vec.push(Box::new(port));
+        sql.push_str(&format!(" AND port = ?{}", params_vec.len()));
+    }
+    if let Some(protocol) = &filters.protocol {
+        params_vec.push(Box::new(protocol.to_lowercase()));
+        sql.push_str(&format!(" AND protocol = ?{}", params_vec.len()));
+    }
+    if let Some(min_bps) = filters.min_bps {
+        params_vec.push(Box::new(min_bps));
+        sql.push_str(&format!(" AND bps >= ?{}", params_vec.len()));
+    }
+    sql.push_str(" GROUP BY grp ORDER BY SUM(bps) DESC");
+    params_vec.push(Box::new(limit));
+    sql.push_str(&format!(" LIMIT ?{}", params_vec.len()));
+
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(FlowGroupRecord {
+                key: row.get(0)?,
+                total_bytes: row.get::<_, f64>(1).unwrap_or(0.0),
+                flow_count: row.get::<_, i64>(2).unwrap_or(0),
+                snapshot_count: row.get::<_, i64>(3).unwrap_or(0),
+                unique_destinations: row.get::<_, i64>(4).unwrap_or(0),
+                avg_bps: row.get::<_, f64>(5).unwrap_or(0.0),
+                avg_rtt: row.get::<_, f64>(6).unwrap_or(0.0),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct DestinationRecord {
     pub ip: String,
     pub city: Option<String>,
@@ -817,11 +2070,13 @@ pub fn get_session_destinations(
     Ok(rows)
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ProcessUsageRecord {
     pub timestamp: String,
     pub process_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_group: Option<String>,
     pub bytes_up: f64,
     pub bytes_down: f64,
     pub flow_count: i64,
@@ -835,7 +2090,7 @@ pub fn get_process_usage(
     limit: u32,
 ) -> SqlResult<Vec<ProcessUsageRecord>> {
     let mut sql = String::from(
-        "SELECT timestamp, process_name, bytes_up, bytes_down, flow_count, avg_rtt
+        "SELECT timestamp, process_name, bytes_up, bytes_down, flow_count, avg_rtt, app_group
          FROM process_usage WHERE session_id = ?1",
     );
     let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
@@ -856,6 +2111,7 @@ pub fn get_process_usage(
             Ok(ProcessUsageRecord {
                 timestamp: row.get(0)?,
                 process_name: row.get(1)?,
+                app_group: row.get(6)?,
                 bytes_up: row.get(2)?,
                 bytes_down: row.get(3)?,
                 flow_count: row.get(4)?,
@@ -983,6 +2239,72 @@ pub fn cleanup_old_sessions(conn: &Connection, days: u32) -> SqlResult<u32> {
     Ok(affected as u32)
 }
 
+/// Prune frames and flow snapshots of completed sessions older than `days`
+/// days, keeping the session row, destinations and process usage.  Marks
+/// them summary-only.  Returns how many sessions were compacted.
+pub fn compact_old_sessions(conn: &Connection, days: u32) -> SqlResult<u32> {
+    let tx = conn.unchecked_transaction()?;
+    let old = "SELECT id FROM sessions WHERE ended_at IS NOT NULL AND summary_only = 0
+               AND julianday('now') - julianday(started_at) > ?1";
+    tx.execute(&format!("DELETE FROM flow_snapshots WHERE session_id IN ({old})"), params![days])?;
+    tx.execute(&format!("DELETE FROM frames WHERE session_id IN ({old})"), params![days])?;
+    tx.execute(&format!("DELETE FROM frames_rollup WHERE session_id IN ({old})"), params![days])?;
+    let affected = tx.execute(
+        "UPDATE sessions SET summary_only = 1, compacted_at = datetime('now')
+         WHERE ended_at IS NOT NULL AND summary_only = 0
+         AND julianday('now') - julianday(started_at) > ?1",
+        params![days],
+    )?;
+    tx.commit()?;
+    if affected > 0 {
+        conn.execute_batch("PRAGMA incremental_vacuum;")?;
+    }
+    Ok(affected as u32)
+}
+
+/// Bucket widths (seconds) frames are rolled up into.
+pub const ROLLUP_RESOLUTIONS: [i64; 2] = [60, 3600];
+
+/// Average the raw frames of completed sessions older than `days` days into
+/// `frames_rollup` at each of `ROLLUP_RESOLUTIONS`, then delete them.  Flow
+/// snapshots are kept but no longer tied to a frame.  Returns how many
+/// sessions were rolled up.
+pub fn rollup_old_frames(conn: &Connection, days: u32) -> SqlResult<u32> {
+    let tx = conn.unchecked_transaction()?;
+    let ids: Vec<String> = {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM sessions s
+             WHERE ended_at IS NOT NULL AND summary_only = 0
+               AND julianday('now') - julianday(started_at) > ?1
+               AND EXISTS(SELECT 1 FROM frames f WHERE f.session_id = s.id)",
+        )?;
+        let rows = stmt.query_map(params![days], |row| row.get(0))?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+    for id in &ids {
+        for resolution in ROLLUP_RESOLUTIONS {
+            tx.execute(
+                "INSERT OR REPLACE INTO frames_rollup
+                    (session_id, resolution, t, timestamp, samples, bps, max_bps, pps,
+                     upload_bps, download_bps, active_flows, latency_ms, time_wait, ephemeral_ports)
+                 SELECT session_id, ?2, CAST(t / ?2 AS INTEGER) * ?2 AS bucket, MIN(timestamp), COUNT(*),
+                        AVG(bps), MAX(bps), AVG(pps), AVG(upload_bps), AVG(download_bps),
+                        AVG(active_flows), AVG(latency_ms), AVG(time_wait), AVG(ephemeral_ports)
+                 FROM frames WHERE session_id = ?1
+                 GROUP BY bucket",
+                params![id, resolution],
+            )?;
+        }
+        tx.execute("UPDATE flow_snapshots SET frame_id = NULL WHERE session_id = ?1", params![id])?;
+        tx.execute("DELETE FROM frames WHERE session_id = ?1", params![id])?;
+    }
+    tx.commit()?;
+    if !ids.is_empty() {
+        conn.execute_batch("PRAGMA incremental_vacuum;")?;
+    }
+    Ok(ids.len() as u32)
+}
+
 /// Delete oldest sessions to keep at most `max_count` sessions.
 /// Returns how many sessions were deleted.
 pub fn cleanup_excess_sessions(conn: &Connection, max_count: u32) -> SqlResult<u32> {
@@ -1004,6 +2326,82 @@ pub fn cleanup_excess_sessions(conn: &Connection, max_count: u32) -> SqlResult<u
     Ok(affected as u32)
 }
 
+// ─── Retention ──────────────────────────────────────────────────────────────
+
+/// Limits the writer enforces daily (`Settings::retention`).  0 disables a
+/// limit; the recording session is never pruned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionPolicy {
+    /// Delete completed sessions that started more than this many days ago.
+    pub max_age_days: u32,
+    /// Delete the oldest sessions while live data exceeds this size.
+    pub max_db_size_mb: u32,
+    /// Keep at most this many completed sessions.
+    pub max_sessions: u32,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days > 0 || self.max_db_size_mb > 0 || self.max_sessions > 0
+    }
+}
+
+/// What one retention pass removed (emitted as `retention-pruned`).
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    pub deleted_by_age: u32,
+    pub deleted_by_count: u32,
+    pub deleted_by_size: u32,
+    /// Live data after pruning (freed pages are reused, not returned to the OS).
+    pub data_size_mb: f64,
+}
+
+impl RetentionReport {
+    pub fn total(&self) -> u32 {
+        self.deleted_by_age + self.deleted_by_count + self.deleted_by_size
+    }
+}
+
+/// Bytes in use by tables and indexes, excluding free pages.
+fn data_size_bytes(conn: &Connection) -> SqlResult<i64> {
+    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |r| r.get(0))?;
+    let pages: i64 = conn.query_row("PRAGMA page_count", [], |r| r.get(0))?;
+    let free: i64 = conn.query_row("PRAGMA freelist_count", [], |r| r.get(0))?;
+    Ok((pages - free) * page_size)
+}
+
+/// Apply `policy`: age first, then count, then size (oldest sessions go
+/// first until live data fits).
+pub fn enforce_retention(conn: &Connection, policy: &RetentionPolicy) -> SqlResult<RetentionReport> {
+    let mut report = RetentionReport::default();
+    if policy.max_age_days > 0 {
+        report.deleted_by_age = cleanup_old_sessions(conn, policy.max_age_days)?;
+    }
+    if policy.max_sessions > 0 {
+        report.deleted_by_count = cleanup_excess_sessions(conn, policy.max_sessions)?;
+    }
+    if policy.max_db_size_mb > 0 {
+        let limit = policy.max_db_size_mb as i64 * 1024 * 1024;
+        while data_size_bytes(conn)? > limit {
+            let deleted = conn.execute(
+                "DELETE FROM sessions WHERE id = (
+                    SELECT id FROM sessions WHERE ended_at IS NOT NULL
+                    ORDER BY started_at ASC LIMIT 1
+                )",
+                [],
+            )?;
+            if deleted == 0 {
+                break;
+            }
+            report.deleted_by_size += 1;
+        }
+    }
+    report.data_size_mb = data_size_bytes(conn)? as f64 / (1024.0 * 1024.0);
+    Ok(report)
+}
+
 /// Delete ALL completed sessions. Returns count deleted.
 pub fn delete_all_sessions(conn: &Connection) -> SqlResult<u32> {
     let affected = conn.execute(
@@ -1023,6 +2421,385 @@ pub fn get_database_path(db_path: &Path) -> String {
     db_path.to_string_lossy().to_string()
 }
 
+// ─── Session bundles ────────────────────────────────────────────────────────
+
+/// A session and its recorded rows as written by `cmd_export_session_json`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionBundle {
+    pub session: SessionInfo,
+    pub frames: Vec<FrameRecord>,
+    pub flows: Vec<FlowSnapshotRecord>,
+    #[serde(default)]
+    pub destinations: Vec<DestinationRecord>,
+    #[serde(default)]
+    pub processes: Vec<ProcessUsageRecord>,
+}
+
+/// Insert `bundle` (validated by the caller) as a new session `new_id`, in
+/// one transaction.  Frames and snapshots get fresh row ids; snapshots are
+/// re-linked to their frame by `frameT`, and bundles exported before that
+/// field existed keep their snapshots unlinked (counted in queries, absent
+/// from playback).  A session exported mid-recording is closed at its last
+/// frame so it isn't mistaken for a live one.
+pub fn import_session_bundle(conn: &Connection, bundle: &SessionBundle, new_id: &str) -> SqlResult<()> {
+    let s = &bundle.session;
+    let ended_at = s
+        .ended_at
+        .clone()
+        .or_else(|| bundle.frames.last().map(|f| f.timestamp.clone()))
+        .unwrap_or_else(|| s.started_at.clone());
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO sessions
+            (id, name, started_at, ended_at, duration_secs,
+             total_bytes_up, total_bytes_down, total_flows, peak_bps, peak_flows,
+             avg_latency_ms, latency_samples, local_city, local_country, local_lat, local_lng,
+             notes, tags, crash_recovered, summary_only)
+         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20)",
+        params![
+            new_id,
+            s.name,
+            s.started_at,
+            ended_at,
+            s.duration_secs,
+            s.total_bytes_up,
+            s.total_bytes_down,
+            s.total_flows,
+            s.peak_bps,
+            s.peak_flows,
+            s.avg_latency_ms,
+            bundle.frames.len() as i64,
+            s.local_city,
+            s.local_country,
+            s.local_lat,
+            s.local_lng,
+            s.notes,
+            s.tags,
+            s.status == "crashed",
+            s.summary_only,
+        ],
+    )?;
+
+    let mut frame_ids: HashMap<u64, i64> = HashMap::with_capacity(bundle.frames.len());
+    for f in &bundle.frames {
+        tx.execute(
+            "INSERT INTO frames
+             (session_id,t,timestamp,bps,pps,active_flows,latency_ms,upload_bps,download_bps,
+              time_wait,ephemeral_ports,wifi_ssid,wifi_rssi,wifi_link_mbps,wifi_channel)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15)",
+            params![
+                new_id,
+                f.t,
+                f.timestamp,
+                f.bps,
+                f.pps,
+                f.active_flows,
+                f.latency_ms,
+                f.upload_bps,
+                f.download_bps,
+                f.time_wait,
+                f.ephemeral_ports,
+                f.wifi_ssid,
+                f.wifi_rssi,
+                f.wifi_link_mbps,
+                f.wifi_channel,
+            ],
+        )?;
+        frame_ids.insert(f.t.to_bits(), tx.last_insert_rowid());
+    }
+
+    for f in &bundle.flows {
+        let frame_id = f.frame_t.and_then(|t| frame_ids.get(&t.to_bits()).copied());
+        tx.execute(
+            "INSERT INTO flow_snapshots
+             (session_id,frame_id,flow_id,src_ip,src_city,src_country,
+              dst_ip,dst_lat,dst_lng,dst_city,dst_country,dst_org,
+              bps,pps,rtt,protocol,dir,port,service,process,pid,service_class,threat,app_group,
+              container,container_image)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20,?21,?22,?23,?24,?25,?26)",
+            params![
+                new_id,
+                frame_id,
+                f.flow_id,
+                f.src_ip,
+                f.src_city,
+                f.src_country,
+                f.dst_ip,
+                f.dst_lat,
+                f.dst_lng,
+                f.dst_city,
+                f.dst_country,
+                f.dst_org,
+                f.bps,
+                f.pps,
+                f.rtt,
+                f.protocol,
+                f.dir,
+                f.port,
+                f.service,
+                f.process,
+                f.pid,
+                f.service_class,
+                f.threat,
+                f.app_group,
+                f.container,
+                f.container_image,
+            ],
+        )?;
+    }
+
+    for d in &bundle.destinations {
+        tx.execute(
+            "INSERT OR IGNORE INTO destinations
+                (session_id, ip, city, country, asn, org, first_seen, last_seen,
+                 total_bytes, connection_count, primary_service, primary_process)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12)",
+            params![
+                new_id,
+                d.ip,
+                d.city,
+                d.country,
+                d.asn,
+                d.org,
+                d.first_seen,
+                d.last_seen,
+                d.total_bytes,
+                d.connection_count,
+                d.primary_service,
+                d.primary_process,
+            ],
+        )?;
+    }
+
+    for p in &bundle.processes {
+        tx.execute(
+            "INSERT INTO process_usage
+             (session_id, timestamp, process_name, app_group, bytes_up, bytes_down, flow_count, avg_rtt)
+             VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
+            params![new_id, p.timestamp, p.process_name, p.app_group, p.bytes_up, p.bytes_down, p.flow_count, p.avg_rtt],
+        )?;
+    }
+
+    tx.commit()
+}
+
+// ─── Session merging ────────────────────────────────────────────────────────
+
+/// Combine the finished sessions `ids` into a new session `new_id`, in one
+/// transaction, and delete the originals.  Frames (raw and rolled up),
+/// snapshots, process usage, flow and alert events and markers are moved rather than copied; each
+/// fragment's `t` is shifted so its first frame lands at its wall-clock
+/// offset from the earliest fragment's start.  Destinations are merged per
+/// IP and the session totals recomputed.  The caller checks that every id
+/// exists and none is recording.
+pub fn merge_sessions(conn: &Connection, ids: &[String], new_id: &str, new_name: &str) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+
+    // (id, started_at as julian day, earliest frame t, its timestamp as julian day)
+    let mut fragments: Vec<(String, f64, Option<f64>, Option<f64>)> = Vec::with_capacity(ids.len());
+    for id in ids {
+        fragments.push(tx.query_row(
+            "SELECT id, julianday(started_at),
+                    COALESCE((SELECT MIN(t) FROM frames WHERE session_id = ?1),
+                             (SELECT MIN(t) FROM frames_rollup WHERE session_id = ?1 AND resolution = 60)),
+                    COALESCE((SELECT julianday(timestamp) FROM frames WHERE session_id = ?1 ORDER BY t ASC LIMIT 1),
+                             (SELECT julianday(timestamp) FROM frames_rollup
+                              WHERE session_id = ?1 AND resolution = 60 ORDER BY t ASC LIMIT 1))
+             FROM sessions WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?);
+    }
+    fragments.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let Some((first_id, merged_start, _, _)) = fragments.first().cloned() else {
+        return Ok(());
+    };
+
+    let placeholders = (0..ids.len()).map(|i| format!("?{}", i + 1)).collect::<Vec<_>>().join(",");
+    let id_params: Vec<&dyn rusqlite::types::ToSql> = ids.iter().map(|id| id as &dyn rusqlite::types::ToSql).collect();
+
+    let mut tags: Vec<String> = Vec::new();
+    let mut notes: Vec<String> = Vec::new();
+    {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT tags, notes FROM sessions WHERE id IN ({placeholders}) ORDER BY julianday(started_at)"
+        ))?;
+        let rows = stmt.query_map(id_params.as_slice(), |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for (tag_json, note) in rows.filter_map(|r| r.ok()) {
+            for tag in serde_json::from_str::<Vec<String>>(&tag_json).unwrap_or_default() {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            if !note.trim().is_empty() {
+                notes.push(note);
+            }
+        }
+    }
+    tags.truncate(20);
+
+    let n = ids.len();
+    tx.execute(
+        &format!(
+            "INSERT INTO sessions
+                (id, name, started_at, ended_at, duration_secs,
+                 total_bytes_up, total_bytes_down, total_flows, peak_bps, peak_flows,
+                 avg_latency_ms, latency_samples, local_city, local_country, local_lat, local_lng,
+                 notes, tags, summary_only)
+             SELECT ?{a}, ?{b},
+                    (SELECT started_at FROM sessions WHERE id = ?{c}),
+                    (SELECT ended_at FROM sessions WHERE id IN ({placeholders})
+                     ORDER BY julianday(ended_at) DESC LIMIT 1),
+                    NULL,
+                    SUM(total_bytes_up), SUM(total_bytes_down), SUM(total_flows),
+                    MAX(peak_bps), MAX(peak_flows),
+                    CASE WHEN SUM(latency_samples) > 0
+                         THEN SUM(avg_latency_ms * latency_samples) / SUM(latency_samples)
+                         ELSE AVG(avg_latency_ms) END,
+                    SUM(latency_samples),
+                    (SELECT local_city FROM sessions WHERE id = ?{c}),
+                    (SELECT local_country FROM sessions WHERE id = ?{c}),
+                    (SELECT local_lat FROM sessions WHERE id = ?{c}),
+                    (SELECT local_lng FROM sessions WHERE id = ?{c}),
+                    ?{d}, ?{e}, MIN(summary_only)
+             FROM sessions WHERE id IN ({placeholders})",
+            a = n + 1,
+            b = n + 2,
+            c = n + 3,
+            d = n + 4,
+            e = n + 5,
+        ),
+        rusqlite::params_from_iter(
+            ids.iter()
+                .cloned()
+                .chain([
+                    new_id.to_string(),
+                    new_name.to_string(),
+                    first_id,
+                    notes.join("\n\n"),
+                    serde_json::to_string(&tags).unwrap_or_else(|_| "[]".to_string()),
+                ]),
+        ),
+    )?;
+    tx.execute(
+        "UPDATE sessions SET duration_secs = (julianday(ended_at) - julianday(started_at)) * 86400.0 WHERE id = ?1",
+        params![new_id],
+    )?;
+
+    let mut stats = RecordingStats::default();
+    for (id, _, min_t, first_frame_day) in &fragments {
+        let offset = match (min_t, first_frame_day) {
+            // julianday() carries ~10µs of float noise; whole milliseconds are plenty
+            (Some(min_t), Some(day)) => ((day - merged_start) * 86_400_000.0).round() / 1000.0 - min_t,
+            _ => 0.0,
+        };
+        tx.execute(
+            "UPDATE frames SET session_id = ?1, t = t + ?2 WHERE session_id = ?3",
+            params![new_id, offset, id],
+        )?;
+        tx.execute(
+            "UPDATE markers SET session_id = ?1, t = t + ?2 WHERE session_id = ?3",
+            params![new_id, offset, id],
+        )?;
+        tx.execute(
+            "UPDATE frames_rollup SET session_id = ?1, t = t + ?2 WHERE session_id = ?3",
+            params![new_id, offset, id],
+        )?;
+        for table in [
+            "flow_snapshots",
+            "process_usage",
+            "flow_events",
+            "alert_events",
+            "router_samples",
+            "route_events",
+            "network_changes",
+        ] {
+            tx.execute(
+                &format!("UPDATE {table} SET session_id = ?1 WHERE session_id = ?2"),
+                params![new_id, id],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO ip_family_usage
+                (session_id, org, v4_connections, v6_connections, v6_failures, v6_fallbacks)
+             SELECT ?1, org, v4_connections, v6_connections, v6_failures, v6_fallbacks
+             FROM ip_family_usage WHERE session_id = ?2
+             ON CONFLICT(session_id, org) DO UPDATE SET
+                v4_connections = v4_connections + excluded.v4_connections,
+                v6_connections = v6_connections + excluded.v6_connections,
+                v6_failures    = v6_failures + excluded.v6_failures,
+                v6_fallbacks   = v6_fallbacks + excluded.v6_fallbacks",
+            params![new_id, id],
+        )?;
+        tx.execute(
+            "INSERT INTO ipv6_failures (session_id, org, destination, failures, fallbacks, last_failed_at)
+             SELECT ?1, org, destination, failures, fallbacks, last_failed_at
+             FROM ipv6_failures WHERE session_id = ?2
+             ON CONFLICT(session_id, org, destination) DO UPDATE SET
+                failures       = failures + excluded.failures,
+                fallbacks      = fallbacks + excluded.fallbacks,
+                last_failed_at = MAX(last_failed_at, excluded.last_failed_at)",
+            params![new_id, id],
+        )?;
+        tx.execute(
+            "INSERT INTO mtu_findings
+                (session_id, ip, domain, org, status, path_mtu, interface, probes, first_seen, last_seen)
+             SELECT ?1, ip, domain, org, status, path_mtu, interface, probes, first_seen, last_seen
+             FROM mtu_findings WHERE session_id = ?2 ORDER BY last_seen ASC
+             ON CONFLICT(session_id, ip) DO UPDATE SET
+                domain     = COALESCE(excluded.domain, domain),
+                org        = COALESCE(excluded.org, org),
+                status     = excluded.status,
+                path_mtu   = excluded.path_mtu,
+                interface  = excluded.interface,
+                probes     = probes + excluded.probes,
+                first_seen = MIN(first_seen, excluded.first_seen),
+                last_seen  = excluded.last_seen",
+            params![new_id, id],
+        )?;
+        tx.execute(
+            "INSERT INTO watch_hits (session_id, kind, value, hits, first_seen, last_seen)
+             SELECT ?1, kind, value, hits, first_seen, last_seen FROM watch_hits WHERE session_id = ?2
+             ON CONFLICT(session_id, kind, value) DO UPDATE SET
+                hits       = hits + excluded.hits,
+                first_seen = MIN(first_seen, excluded.first_seen),
+                last_seen  = MAX(last_seen, excluded.last_seen)",
+            params![new_id, id],
+        )?;
+        tx.execute(
+            "INSERT INTO destinations
+                (session_id, ip, city, country, asn, org, first_seen, last_seen,
+                 total_bytes, connection_count, primary_service, primary_process, domain)
+             SELECT ?1, ip, city, country, asn, org, first_seen + ?2, last_seen + ?2,
+                    total_bytes, connection_count, primary_service, primary_process, domain
+             FROM destinations WHERE session_id = ?3
+             ON CONFLICT(session_id, ip) DO UPDATE SET
+                first_seen       = MIN(first_seen, excluded.first_seen),
+                last_seen        = MAX(last_seen, excluded.last_seen),
+                total_bytes      = total_bytes + excluded.total_bytes,
+                connection_count = connection_count + excluded.connection_count,
+                primary_service  = COALESCE(primary_service, excluded.primary_service),
+                primary_process  = COALESCE(primary_process, excluded.primary_process),
+                domain           = COALESCE(excluded.domain, domain)",
+            params![new_id, offset, id],
+        )?;
+        if let Some(s) = get_recording_stats(&tx, id)? {
+            stats.frames_written += s.frames_written;
+            stats.flows_written += s.flows_written;
+            stats.rows_skipped += s.rows_skipped;
+            stats.writer_errors += s.writer_errors;
+            stats.dropped_frames += s.dropped_frames;
+        }
+        tx.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
+    }
+    if !stats.is_empty() {
+        add_recording_stats(&tx, new_id, &stats)?;
+    }
+
+    tx.commit()
+}
+
 // ─── Analytics (Tier 4) ─────────────────────────────────────────────────────
 
 /// Daily usage record — aggregated bytes per calendar day.
@@ -1034,66 +2811,139 @@ pub struct DailyUsage {
     pub bytes_down: f64,
     pub session_count: i64,
     pub total_duration_secs: f64,
+    /// Approximate totals from the OS for a day before Abyss was recording.
+    pub backfilled: bool,
 }
 
-/// Query daily data usage, aggregated from session totals.
+/// Query daily data usage, aggregated from session totals, plus any
+/// backfilled days no session covers.
 /// `range_days` limits to last N days (0 = all time).
 pub fn get_daily_usage(conn: &Connection, range_days: u32) -> SqlResult<Vec<DailyUsage>> {
-    let sql = if range_days > 0 {
-        "SELECT DATE(started_at) AS day,
-                COALESCE(SUM(total_bytes_up), 0),
-                COALESCE(SUM(total_bytes_down), 0),
-                COUNT(*),
-                COALESCE(SUM(duration_secs), 0)
-         FROM sessions
-         WHERE julianday('now') - julianday(started_at) <= ?1
-         GROUP BY day
-         ORDER BY day ASC"
-    } else {
+    let mut stmt = conn.prepare(
         "SELECT DATE(started_at) AS day,
                 COALESCE(SUM(total_bytes_up), 0),
                 COALESCE(SUM(total_bytes_down), 0),
                 COUNT(*),
-                COALESCE(SUM(duration_secs), 0)
+                COALESCE(SUM(duration_secs), 0),
+                0
          FROM sessions
+         WHERE ?1 = 0 OR julianday('now') - julianday(started_at) <= ?1
          GROUP BY day
-         ORDER BY day ASC"
-    };
-
-    let mut stmt = conn.prepare(sql)?;
-    let rows: Vec<DailyUsage> = if range_days > 0 {
-        stmt.query_map(params![range_days], |row| {
-            Ok(DailyUsage {
-                date: row.get(0)?,
-                bytes_up: row.get::<_, f64>(1).unwrap_or(0.0),
-                bytes_down: row.get::<_, f64>(2).unwrap_or(0.0),
-                session_count: row.get::<_, i64>(3).unwrap_or(0),
-                total_duration_secs: row.get::<_, f64>(4).unwrap_or(0.0),
-            })
-        })?
-        .filter_map(|r| r.ok())
-        .collect()
-    } else {
-        stmt.query_map([], |row| {
+         UNION ALL
+         SELECT b.day, SUM(b.bytes_up), SUM(b.bytes_down), 0, 0, 1
+         FROM usage_backfill b
+         WHERE b.day NOT IN (SELECT DATE(started_at) FROM sessions)
+           AND (?1 = 0 OR julianday('now') - julianday(b.day) <= ?1)
+         GROUP BY b.day
+         ORDER BY day ASC",
+    )?;
+    let rows = stmt
+        .query_map(params![range_days], |row| {
             Ok(DailyUsage {
                 date: row.get(0)?,
                 bytes_up: row.get::<_, f64>(1).unwrap_or(0.0),
                 bytes_down: row.get::<_, f64>(2).unwrap_or(0.0),
                 session_count: row.get::<_, i64>(3).unwrap_or(0),
                 total_duration_secs: row.get::<_, f64>(4).unwrap_or(0.0),
+                backfilled: row.get::<_, bool>(5).unwrap_or(false),
             })
         })?
         .filter_map(|r| r.ok())
-        .collect()
-    };
-
+        .collect();
     Ok(rows)
 }
 
-/// Top destination record — most contacted IPs across all sessions.
+/// Seconds of traffic each stored frame stands for (the writer keeps one
+/// frame every 5 ticks).
+const FRAME_SAMPLE_SECS: f64 = 5.0;
+
+/// One hour of one day in the usage heatmap.
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HourlyUsage {
+    pub hour: u8,
+    pub bytes: f64,
+    pub avg_flows: f64,
+    /// 0 when no frame in the hour measured latency.
+    pub avg_latency_ms: f64,
+    /// Frames recorded in the hour (0 = not monitoring).
+    pub samples: i64,
+}
+
+/// A day of the usage heatmap: always 24 hours, in local time.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct TopDestination {
+pub struct HourlyUsageDay {
+    pub date: String, // "YYYY-MM-DD"
+    pub hours: Vec<HourlyUsage>,
+}
+
+/// Recorded frames bucketed into local hour-of-day × day, for a heatmap of
+/// when the network is busiest.  Sessions whose frames were rolled up count
+/// through their one-minute rollups.  Only days with frames are returned.
+/// `range_days` limits to last N days (0 = all time).
+pub fn get_hourly_usage(conn: &Connection, range_days: u32) -> SqlResult<Vec<HourlyUsageDay>> {
+    let mut stmt = conn.prepare(
+        "WITH samples AS (
+            SELECT timestamp, upload_bps + download_bps AS bps, active_flows, latency_ms, 1 AS n
+            FROM frames
+            UNION ALL
+            SELECT timestamp, upload_bps + download_bps, active_flows, latency_ms, samples
+            FROM frames_rollup WHERE resolution = 60
+         )
+         SELECT DATE(timestamp, 'localtime') AS day,
+                CAST(strftime('%H', timestamp, 'localtime') AS INTEGER) AS hour,
+                SUM(bps * n), SUM(active_flows * n), SUM(n),
+                SUM(CASE WHEN latency_ms > 0 THEN latency_ms * n END),
+                SUM(CASE WHEN latency_ms > 0 THEN n END)
+         FROM samples
+         WHERE ?1 = 0 OR julianday('now') - julianday(timestamp) <= ?1
+         GROUP BY day, hour
+         ORDER BY day, hour",
+    )?;
+    let rows = stmt.query_map(params![range_days], |row| {
+        let samples: i64 = row.get(4)?;
+        let latency_samples: Option<i64> = row.get(6)?;
+        Ok((
+            row.get::<_, String>(0)?,
+            HourlyUsage {
+                hour: row.get(1)?,
+                bytes: row.get::<_, f64>(2)? / 8.0 * FRAME_SAMPLE_SECS,
+                avg_flows: row.get::<_, f64>(3)? / samples.max(1) as f64,
+                avg_latency_ms: match latency_samples {
+                    Some(n) if n > 0 => row.get::<_, f64>(5)? / n as f64,
+                    _ => 0.0,
+                },
+                samples,
+            },
+        ))
+    })?;
+
+    let mut days: Vec<HourlyUsageDay> = Vec::new();
+    for (date, usage) in rows.filter_map(|r| r.ok()) {
+        if days.last().is_none_or(|d| d.date != date) {
+            days.push(HourlyUsageDay {
+                date,
+                hours: (0..24)
+                    .map(|hour| HourlyUsage {
+                        hour,
+                        ..Default::default()
+                    })
+                    .collect(),
+            });
+        }
+        if let Some(day) = days.last_mut() {
+            let slot = usage.hour.min(23) as usize;
+            day.hours[slot] = usage;
+        }
+    }
+    Ok(days)
+}
+
+/// Top destination record — most contacted IPs across all sessions.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TopDestination {
     pub ip: String,
     pub city: String,
     pub country: String,
@@ -1102,41 +2952,42 @@ pub struct TopDestination {
     pub connection_count: i64,
     pub primary_service: String,
     pub primary_process: String,
+    /// Most recently observed hostname for this IP.
+    pub hostname: Option<String>,
+    /// User-assigned label and category (see `set_destination_label`).
+    pub label: Option<String>,
+    pub category: Option<String>,
 }
 
 /// Get most contacted destinations across all/recent sessions.
 pub fn get_top_destinations(conn: &Connection, range_days: u32, limit: u32) -> SqlResult<Vec<TopDestination>> {
-    let sql = if range_days > 0 {
-        "SELECT d.ip,
-                COALESCE(d.city, ''), COALESCE(d.country, ''),
-                COALESCE(d.org, ''),
-                COALESCE(SUM(d.total_bytes), 0),
-                COALESCE(SUM(d.connection_count), 0),
-                COALESCE(d.primary_service, ''),
-                COALESCE(d.primary_process, '')
-         FROM destinations d
-         JOIN sessions s ON d.session_id = s.id
-         WHERE julianday('now') - julianday(s.started_at) <= ?1
-         GROUP BY d.ip
-         ORDER BY SUM(d.total_bytes) DESC
-         LIMIT ?2"
-    } else {
-        "SELECT d.ip,
-                COALESCE(d.city, ''), COALESCE(d.country, ''),
-                COALESCE(d.org, ''),
-                COALESCE(SUM(d.total_bytes), 0),
-                COALESCE(SUM(d.connection_count), 0),
-                COALESCE(d.primary_service, ''),
-                COALESCE(d.primary_process, '')
-         FROM destinations d
-         GROUP BY d.ip
-         ORDER BY SUM(d.total_bytes) DESC
-         LIMIT ?1"
-    };
-
-    let mut stmt = conn.prepare(sql)?;
-    let rows: Vec<TopDestination> = if range_days > 0 {
-        stmt.query_map(params![range_days, limit], |row| {
+    // range_days = 0 means all time
+    let mut stmt = conn.prepare(
+        "SELECT t.ip, t.city, t.country, t.org, t.total_bytes, t.connection_count,
+                t.primary_service, t.primary_process,
+                (SELECT h.hostname FROM host_observations h
+                 WHERE h.ip = t.ip ORDER BY h.last_seen DESC LIMIT 1),
+                NULLIF(l.label, ''), NULLIF(l.category, '')
+         FROM (
+            SELECT d.ip,
+                   COALESCE(d.city, '') AS city, COALESCE(d.country, '') AS country,
+                   COALESCE(d.org, '') AS org,
+                   COALESCE(SUM(d.total_bytes), 0) AS total_bytes,
+                   COALESCE(SUM(d.connection_count), 0) AS connection_count,
+                   COALESCE(d.primary_service, '') AS primary_service,
+                   COALESCE(d.primary_process, '') AS primary_process
+            FROM destinations d
+            JOIN sessions s ON d.session_id = s.id
+            WHERE ?1 = 0 OR julianday('now') - julianday(s.started_at) <= ?1
+            GROUP BY d.ip
+            ORDER BY SUM(d.total_bytes) DESC
+            LIMIT ?2
+         ) t
+         LEFT JOIN destination_labels l ON l.ip = t.ip
+         ORDER BY t.total_bytes DESC",
+    )?;
+    let rows = stmt
+        .query_map(params![range_days, limit], |row| {
             Ok(TopDestination {
                 ip: row.get(0)?,
                 city: row.get(1)?,
@@ -1146,27 +2997,152 @@ pub fn get_top_destinations(conn: &Connection, range_days: u32, limit: u32) -> S
                 connection_count: row.get::<_, i64>(5).unwrap_or(0),
                 primary_service: row.get::<_, String>(6).unwrap_or_default(),
                 primary_process: row.get::<_, String>(7).unwrap_or_default(),
+                hostname: row.get(8)?,
+                label: row.get(9)?,
+                category: row.get(10)?,
             })
         })?
         .filter_map(|r| r.ok())
-        .collect()
-    } else {
-        stmt.query_map(params![limit], |row| {
-            Ok(TopDestination {
-                ip: row.get(0)?,
-                city: row.get(1)?,
-                country: row.get(2)?,
-                org: row.get(3)?,
-                total_bytes: row.get::<_, f64>(4).unwrap_or(0.0),
-                connection_count: row.get::<_, i64>(5).unwrap_or(0),
-                primary_service: row.get::<_, String>(6).unwrap_or_default(),
-                primary_process: row.get::<_, String>(7).unwrap_or_default(),
+        .collect();
+    Ok(rows)
+}
+
+/// Traffic to one destination country across sessions.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CountryUsage {
+    /// ISO code, or `??` where the destination didn't geolocate.
+    pub country: String,
+    pub total_bytes: f64,
+    pub connection_count: i64,
+    pub unique_destinations: i64,
+    /// Split by each destination's main process (only when requested).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<CountryProcessUsage>,
+}
+
+/// One process's share of a country's traffic.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CountryProcessUsage {
+    /// `None` for destinations with no known process (or redacted).
+    pub process: Option<String>,
+    pub total_bytes: f64,
+    pub connection_count: i64,
+    pub unique_destinations: i64,
+}
+
+/// Bytes, connections and distinct destinations per destination country,
+/// largest first; with `by_process`, each split by owning process.
+/// `range_days` limits to sessions started in the last N days (0 = all time).
+pub fn get_country_breakdown(conn: &Connection, range_days: u32, by_process: bool) -> SqlResult<Vec<CountryUsage>> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(NULLIF(d.country, ''), '??') AS c,
+                COALESCE(SUM(d.total_bytes), 0),
+                COALESCE(SUM(d.connection_count), 0),
+                COUNT(DISTINCT d.ip)
+         FROM destinations d
+         JOIN sessions s ON d.session_id = s.id
+         WHERE ?1 = 0 OR julianday('now') - julianday(s.started_at) <= ?1
+         GROUP BY c
+         ORDER BY SUM(d.total_bytes) DESC",
+    )?;
+    let mut countries: Vec<CountryUsage> = stmt
+        .query_map(params![range_days], |row| {
+            Ok(CountryUsage {
+                country: row.get(0)?,
+                total_bytes: row.get::<_, f64>(1).unwrap_or(0.0),
+                connection_count: row.get::<_, i64>(2).unwrap_or(0),
+                unique_destinations: row.get::<_, i64>(3).unwrap_or(0),
+                processes: Vec::new(),
             })
         })?
         .filter_map(|r| r.ok())
-        .collect()
-    };
+        .collect();
+    if !by_process {
+        return Ok(countries);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(NULLIF(d.country, ''), '??') AS c,
+                NULLIF(d.primary_process, '') AS p,
+                COALESCE(SUM(d.total_bytes), 0),
+                COALESCE(SUM(d.connection_count), 0),
+                COUNT(DISTINCT d.ip)
+         FROM destinations d
+         JOIN sessions s ON d.session_id = s.id
+         WHERE ?1 = 0 OR julianday('now') - julianday(s.started_at) <= ?1
+         GROUP BY c, p
+         ORDER BY SUM(d.total_bytes) DESC",
+    )?;
+    let rows = stmt.query_map(params![range_days], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            CountryProcessUsage {
+                process: row.get(1)?,
+                total_bytes: row.get::<_, f64>(2).unwrap_or(0.0),
+                connection_count: row.get::<_, i64>(3).unwrap_or(0),
+                unique_destinations: row.get::<_, i64>(4).unwrap_or(0),
+            },
+        ))
+    })?;
+    let mut by_country: HashMap<String, Vec<CountryProcessUsage>> = HashMap::new();
+    for (country, usage) in rows.filter_map(|r| r.ok()) {
+        by_country.entry(country).or_default().push(usage);
+    }
+    for country in &mut countries {
+        country.processes = by_country.remove(&country.country).unwrap_or_default();
+    }
+    Ok(countries)
+}
+
+/// Traffic terminating at one organisation (Google, Cloudflare, …).
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OrgUsage {
+    /// Organisation name, else the ASN, else `Unknown`.
+    pub org: String,
+    /// ASNs the organisation's destinations were announced from.
+    pub asns: Vec<String>,
+    pub total_bytes: f64,
+    pub connection_count: i64,
+    pub unique_destinations: i64,
+    pub country_count: i64,
+}
 
+/// Destinations grouped by the organisation that owns them, largest first.
+/// `range_days` limits to sessions started in the last N days (0 = all time).
+pub fn get_org_breakdown(conn: &Connection, range_days: u32, limit: u32) -> SqlResult<Vec<OrgUsage>> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(NULLIF(d.org, ''), NULLIF(d.asn, ''), 'Unknown') AS o,
+                GROUP_CONCAT(DISTINCT NULLIF(d.asn, '')),
+                COALESCE(SUM(d.total_bytes), 0),
+                COALESCE(SUM(d.connection_count), 0),
+                COUNT(DISTINCT d.ip),
+                COUNT(DISTINCT NULLIF(d.country, ''))
+         FROM destinations d
+         JOIN sessions s ON d.session_id = s.id
+         WHERE ?1 = 0 OR julianday('now') - julianday(s.started_at) <= ?1
+         GROUP BY o
+         ORDER BY SUM(d.total_bytes) DESC
+         LIMIT ?2",
+    )?;
+    let rows = stmt
+        .query_map(params![range_days, limit], |row| {
+            let asns: Option<String> = row.get(1)?;
+            Ok(OrgUsage {
+                org: row.get(0)?,
+                asns: asns
+                    .map(|a| a.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+                total_bytes: row.get::<_, f64>(2).unwrap_or(0.0),
+                connection_count: row.get::<_, i64>(3).unwrap_or(0),
+                unique_destinations: row.get::<_, i64>(4).unwrap_or(0),
+                country_count: row.get::<_, i64>(5).unwrap_or(0),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
     Ok(rows)
 }
 
@@ -1174,169 +3150,564 @@ pub fn get_top_destinations(conn: &Connection, range_days: u32, limit: u32) -> S
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct TopApp {
+    /// Process name, or the app group when grouping.
     pub process_name: String,
     pub total_bytes_up: f64,
     pub total_bytes_down: f64,
     pub total_flows: i64,
     pub avg_rtt: f64,
+    /// Processes counted under the group (only when grouping).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    /// Part of the totals that is approximate OS data from before Abyss
+    /// was recording (see `import_usage_backfill`).
+    pub backfilled_bytes: f64,
 }
 
-/// Get most data-hungry processes across all/recent sessions.
-pub fn get_top_apps(conn: &Connection, range_days: u32, limit: u32) -> SqlResult<Vec<TopApp>> {
-    let sql = if range_days > 0 {
-        "SELECT p.process_name,
-                COALESCE(SUM(p.bytes_up), 0),
-                COALESCE(SUM(p.bytes_down), 0),
-                COALESCE(SUM(p.flow_count), 0),
-                AVG(CASE WHEN p.avg_rtt > 0 THEN p.avg_rtt ELSE NULL END)
-         FROM process_usage p
-         JOIN sessions s ON p.session_id = s.id
-         WHERE julianday('now') - julianday(s.started_at) <= ?1
-         GROUP BY p.process_name
-         ORDER BY SUM(p.bytes_up + p.bytes_down) DESC
+/// Get most data-hungry processes across all/recent sessions, including
+/// backfilled days.  With `by_group`, helper processes count towards the
+/// app that started them.
+pub fn get_top_apps(conn: &Connection, range_days: u32, limit: u32, by_group: bool) -> SqlResult<Vec<TopApp>> {
+    let key = if by_group { "COALESCE(app_group, process_name)" } else { "process_name" };
+    let members = if by_group { "GROUP_CONCAT(DISTINCT process_name)" } else { "NULL" };
+    let sql = format!(
+        "WITH usage AS (
+            SELECT p.process_name, p.app_group, p.bytes_up, p.bytes_down,
+                   p.flow_count, p.avg_rtt, 0 AS backfilled
+            FROM process_usage p
+            LEFT JOIN sessions s ON p.session_id = s.id
+            WHERE ?1 = 0 OR julianday('now') - julianday(s.started_at) <= ?1
+            UNION ALL
+            SELECT b.app, NULL, b.bytes_up, b.bytes_down, 0, 0, 1
+            FROM usage_backfill b
+            WHERE ?1 = 0 OR julianday('now') - julianday(b.day) <= ?1
+         )
+         SELECT {key},
+                COALESCE(SUM(bytes_up), 0),
+                COALESCE(SUM(bytes_down), 0),
+                COALESCE(SUM(flow_count), 0),
+                AVG(CASE WHEN avg_rtt > 0 THEN avg_rtt ELSE NULL END),
+                {members},
+                COALESCE(SUM(CASE WHEN backfilled = 1 THEN bytes_up + bytes_down ELSE 0 END), 0)
+         FROM usage
+         GROUP BY {key}
+         ORDER BY SUM(bytes_up + bytes_down) DESC
          LIMIT ?2"
-    } else {
-        "SELECT p.process_name,
-                COALESCE(SUM(p.bytes_up), 0),
-                COALESCE(SUM(p.bytes_down), 0),
-                COALESCE(SUM(p.flow_count), 0),
-                AVG(CASE WHEN p.avg_rtt > 0 THEN p.avg_rtt ELSE NULL END)
-         FROM process_usage p
-         GROUP BY p.process_name
-         ORDER BY SUM(p.bytes_up + p.bytes_down) DESC
-         LIMIT ?1"
-    };
+    );
 
-    let mut stmt = conn.prepare(sql)?;
-    let rows: Vec<TopApp> = if range_days > 0 {
-        stmt.query_map(params![range_days, limit], |row| {
-            Ok(TopApp {
-                process_name: row.get(0)?,
-                total_bytes_up: row.get::<_, f64>(1).unwrap_or(0.0),
-                total_bytes_down: row.get::<_, f64>(2).unwrap_or(0.0),
-                total_flows: row.get::<_, i64>(3).unwrap_or(0),
-                avg_rtt: row.get::<_, f64>(4).unwrap_or(0.0),
-            })
-        })?
-        .filter_map(|r| r.ok())
-        .collect()
-    } else {
-        stmt.query_map(params![limit], |row| {
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params![range_days, limit], |row| {
+            let members: Option<String> = row.get(5)?;
             Ok(TopApp {
                 process_name: row.get(0)?,
                 total_bytes_up: row.get::<_, f64>(1).unwrap_or(0.0),
                 total_bytes_down: row.get::<_, f64>(2).unwrap_or(0.0),
                 total_flows: row.get::<_, i64>(3).unwrap_or(0),
                 avg_rtt: row.get::<_, f64>(4).unwrap_or(0.0),
+                members: members
+                    .map(|m| m.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+                backfilled_bytes: row.get::<_, f64>(6).unwrap_or(0.0),
             })
         })?
         .filter_map(|r| r.ok())
-        .collect()
+        .collect();
+    Ok(rows)
+}
+
+// ─── Usage backfill ─────────────────────────────────────────────────────────
+
+/// Store approximate per-app daily usage read from the OS, tagged with
+/// `source`.  Days on or after the first recorded session are skipped so
+/// backfill never double counts what Abyss measured itself; re-importing a
+/// day replaces its earlier values.
+pub fn import_usage_backfill(conn: &Connection, source: &'static str, rows: &[BackfillRow]) -> SqlResult<BackfillReport> {
+    let first_session_day: Option<String> =
+        conn.query_row("SELECT MIN(DATE(started_at)) FROM sessions", [], |row| row.get(0))?;
+    let imported_at = chrono::Utc::now().to_rfc3339();
+    let mut report = BackfillReport {
+        source,
+        ..Default::default()
     };
 
-    Ok(rows)
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO usage_backfill (day, app, app_id, bytes_up, bytes_down, source, imported_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (day, app, source) DO UPDATE SET
+                app_id = excluded.app_id,
+                bytes_up = excluded.bytes_up,
+                bytes_down = excluded.bytes_down,
+                imported_at = excluded.imported_at",
+        )?;
+        for row in rows {
+            if first_session_day.as_deref().is_some_and(|first| row.day.as_str() >= first) {
+                report.skipped += 1;
+                continue;
+            }
+            stmt.execute(params![
+                row.day,
+                row.app,
+                row.app_id,
+                row.bytes_up,
+                row.bytes_down,
+                source,
+                imported_at
+            ])?;
+            report.imported += 1;
+            if report.first_day.as_ref().is_none_or(|d| &row.day < d) {
+                report.first_day = Some(row.day.clone());
+            }
+            if report.last_day.as_ref().is_none_or(|d| &row.day > d) {
+                report.last_day = Some(row.day.clone());
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(report)
 }
 
-// ─── Post-session insights ──────────────────────────────────────────────────
+// ─── Drill-down views ───────────────────────────────────────────────────────
 
+/// One session in which a destination appeared.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct SessionInsights {
-    pub total_data_human: String,
-    pub busiest_minute: String,
-    pub most_active_process: String,
-    pub unique_countries: i64,
-    pub unique_destinations: i64,
-    pub high_latency_destinations: Vec<String>,
-    pub top_services: Vec<String>,
-    pub unusual_ports: Vec<i64>,
-    pub longest_connection: Option<LongestConnectionInfo>,
+pub struct DestinationSessionEntry {
+    pub session_id: String,
+    pub session_name: String,
+    pub started_at: String,
+    pub total_bytes: f64,
+    pub connection_count: i64,
+    pub first_seen: Option<f64>,
+    pub last_seen: Option<f64>,
 }
 
-/// Info about the single longest-lived flow/connection in a session.
+/// Bytes exchanged with a destination on one calendar day.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct LongestConnectionInfo {
-    pub dst_ip: String,
-    pub service: String,
-    pub duration_secs: f64,
+pub struct DestinationDailyBytes {
+    pub date: String,
+    pub total_bytes: f64,
 }
 
-/// Compute post-session insights from the stored data for a given session.
-pub fn compute_session_insights(conn: &Connection, session_id: &str) -> SqlResult<SessionInsights> {
-    // Total data
-    let (bytes_up, bytes_down): (f64, f64) = conn.query_row(
-        "SELECT COALESCE(total_bytes_up, 0), COALESCE(total_bytes_down, 0) FROM sessions WHERE id = ?1",
-        params![session_id],
-        |row| Ok((row.get(0)?, row.get(1)?)),
-    )?;
-    let total_bytes = bytes_up + bytes_down;
-    let total_data_human = format_bytes_human(total_bytes);
-
-    // Busiest minute — find the frame with highest bps
-    let busiest_minute: String = conn
-        .query_row(
-            "SELECT COALESCE(timestamp, '') FROM frames WHERE session_id = ?1 ORDER BY bps DESC LIMIT 1",
-            params![session_id],
-            |row| row.get(0),
-        )
-        .unwrap_or_default();
+/// A process that contacted a destination.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationProcess {
+    pub process_name: String,
+    pub snapshot_count: i64,
+    pub session_count: i64,
+}
 
-    // Most active process by total bytes
-    let most_active_process: String = conn
-        .query_row(
-            "SELECT COALESCE(process_name, 'Unknown') FROM process_usage WHERE session_id = ?1
-             GROUP BY process_name ORDER BY SUM(bytes_up + bytes_down) DESC LIMIT 1",
-            params![session_id],
-            |row| row.get(0),
-        )
-        .unwrap_or_else(|_| "Unknown".to_string());
+/// A single RTT observation from a stored flow snapshot.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencySample {
+    pub timestamp: String,
+    pub session_id: String,
+    pub rtt: f64,
+}
 
-    // Unique countries
-    let unique_countries: i64 = conn
-        .query_row(
-            "SELECT COUNT(DISTINCT country) FROM destinations WHERE session_id = ?1 AND country IS NOT NULL AND country != ''",
-            params![session_id],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
+/// Everything known about a destination (IP or org) across all sessions.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationHistory {
+    pub ip: Option<String>,
+    pub org: Option<String>,
+    pub sessions: Vec<DestinationSessionEntry>,
+    pub daily_bytes: Vec<DestinationDailyBytes>,
+    pub processes: Vec<DestinationProcess>,
+    pub latency_samples: Vec<LatencySample>,
+}
 
-    // Unique destinations
-    let unique_destinations: i64 = conn
-        .query_row(
-            "SELECT COUNT(DISTINCT ip) FROM destinations WHERE session_id = ?1",
-            params![session_id],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
+/// Build the cross-session history for a destination, matched by exact IP
+/// when given, otherwise by org name.
+pub fn get_destination_history(
+    conn: &Connection,
+    ip: Option<&str>,
+    org: Option<&str>,
+    limit: u32,
+) -> SqlResult<DestinationHistory> {
+    let (dest_col, flow_col, value) = match (ip, org) {
+        (Some(ip), _) => ("d.ip", "fs.dst_ip", ip),
+        (None, Some(org)) => ("d.org", "fs.dst_org", org),
+        (None, None) => {
+            return Ok(DestinationHistory {
+                ip: None,
+                org: None,
+                sessions: Vec::new(),
+                daily_bytes: Vec::new(),
+                processes: Vec::new(),
+                latency_samples: Vec::new(),
+            })
+        }
+    };
 
-    // High latency destinations (avg RTT > 200ms from flow_snapshots)
-    let mut stmt = conn.prepare(
-        "SELECT DISTINCT fs.dst_ip FROM flow_snapshots fs
-         JOIN frames f ON fs.frame_id = f.id
-         WHERE f.session_id = ?1 AND fs.rtt > 200
-         LIMIT 10"
-    )?;
-    let high_latency_destinations: Vec<String> = stmt
-        .query_map(params![session_id], |row| row.get(0))?
+    let mut stmt = conn.prepare(&format!(
+        "SELECT s.id, s.name, s.started_at,
+                COALESCE(SUM(d.total_bytes), 0), COALESCE(SUM(d.connection_count), 0),
+                MIN(d.first_seen), MAX(d.last_seen)
+         FROM destinations d
+         JOIN sessions s ON s.id = d.session_id
+         WHERE {dest_col} = ?1
+         GROUP BY s.id
+         ORDER BY s.started_at DESC
+         LIMIT ?2"
+    ))?;
+    let sessions: Vec<DestinationSessionEntry> = stmt
+        .query_map(params![value, limit], |row| {
+            Ok(DestinationSessionEntry {
+                session_id: row.get(0)?,
+                session_name: row.get(1)?,
+                started_at: row.get(2)?,
+                total_bytes: row.get::<_, f64>(3).unwrap_or(0.0),
+                connection_count: row.get::<_, i64>(4).unwrap_or(0),
+                first_seen: row.get(5)?,
+                last_seen: row.get(6)?,
+            })
+        })?
         .filter_map(|r| r.ok())
         .collect();
 
-    // Top services
-    let mut stmt = conn.prepare(
-        "SELECT COALESCE(fs.service, 'unknown') as svc FROM flow_snapshots fs
-         JOIN frames f ON fs.frame_id = f.id
-         WHERE f.session_id = ?1 AND fs.service IS NOT NULL AND fs.service != ''
-         GROUP BY svc ORDER BY SUM(fs.bps) DESC LIMIT 5"
-    )?;
-    let top_services: Vec<String> = stmt
-        .query_map(params![session_id], |row| row.get(0))?
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DATE(s.started_at) AS day, COALESCE(SUM(d.total_bytes), 0)
+         FROM destinations d
+         JOIN sessions s ON s.id = d.session_id
+         WHERE {dest_col} = ?1
+         GROUP BY day
+         ORDER BY day ASC"
+    ))?;
+    let daily_bytes: Vec<DestinationDailyBytes> = stmt
+        .query_map(params![value], |row| {
+            Ok(DestinationDailyBytes {
+                date: row.get(0)?,
+                total_bytes: row.get::<_, f64>(1).unwrap_or(0.0),
+            })
+        })?
         .filter_map(|r| r.ok())
         .collect();
 
-    // Unusual ports (not in common set: 80, 443, 53, 22, 21, 25, 110, 143, 993, 995, 8080, 8443)
-    let mut stmt = conn.prepare(
-        "SELECT DISTINCT fs.port FROM flow_snapshots fs
+    let mut stmt = conn.prepare(&format!(
+        "SELECT fs.process, COUNT(*), COUNT(DISTINCT fs.session_id)
+         FROM flow_snapshots fs
+         WHERE {flow_col} = ?1 AND fs.process IS NOT NULL AND fs.process != ''
+         GROUP BY fs.process
+         ORDER BY COUNT(*) DESC
+         LIMIT 50"
+    ))?;
+    let processes: Vec<DestinationProcess> = stmt
+        .query_map(params![value], |row| {
+            Ok(DestinationProcess {
+                process_name: row.get(0)?,
+                snapshot_count: row.get::<_, i64>(1).unwrap_or(0),
+                session_count: row.get::<_, i64>(2).unwrap_or(0),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Most recent samples first in SQL, then flipped to chronological order
+    let mut stmt = conn.prepare(&format!(
+        "SELECT f.timestamp, fs.session_id, fs.rtt
+         FROM flow_snapshots fs
+         JOIN frames f ON fs.frame_id = f.id
+         WHERE {flow_col} = ?1 AND fs.rtt > 0
+         ORDER BY f.timestamp DESC
+         LIMIT 500"
+    ))?;
+    let mut latency_samples: Vec<LatencySample> = stmt
+        .query_map(params![value], |row| {
+            Ok(LatencySample {
+                timestamp: row.get(0)?,
+                session_id: row.get(1)?,
+                rtt: row.get(2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    latency_samples.reverse();
+
+    Ok(DestinationHistory {
+        ip: ip.map(str::to_string),
+        org: if ip.is_none() { org.map(str::to_string) } else { None },
+        sessions,
+        daily_bytes,
+        processes,
+        latency_samples,
+    })
+}
+
+/// Bytes a process moved on one calendar day.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessDailyBytes {
+    pub date: String,
+    pub bytes_up: f64,
+    pub bytes_down: f64,
+}
+
+/// A destination contacted by a process.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessDestination {
+    pub ip: String,
+    pub org: String,
+    pub country: String,
+    pub snapshot_count: i64,
+    pub total_bytes: f64,
+}
+
+/// How often a process is active in a given hour of the day (0-23, UTC).
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HourActivity {
+    pub hour: i32,
+    pub sample_count: i64,
+    pub total_bytes: f64,
+}
+
+/// An anomaly from a session in which the process was active.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CorrelatedAnomaly {
+    pub session_id: String,
+    pub session_name: String,
+    pub anomaly: Anomaly,
+}
+
+/// Per-app drill-down across all sessions.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessHistory {
+    pub process_name: String,
+    pub session_count: i64,
+    pub daily_bytes: Vec<ProcessDailyBytes>,
+    pub top_destinations: Vec<ProcessDestination>,
+    pub active_hours: Vec<HourActivity>,
+    pub anomalies: Vec<CorrelatedAnomaly>,
+}
+
+/// Build the cross-session history for a process.  Anomalies are re-detected
+/// for the 10 most recent sessions the process appeared in; only session-wide
+/// spikes and `UNUSUAL_PROCESS` hits for this process are kept.
+pub fn get_process_history(conn: &Connection, name: &str, limit: u32) -> SqlResult<ProcessHistory> {
+    let session_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(DISTINCT session_id) FROM process_usage WHERE process_name = ?1",
+            params![name],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    let mut stmt = conn.prepare(
+        "SELECT DATE(timestamp) AS day,
+                COALESCE(SUM(bytes_up), 0), COALESCE(SUM(bytes_down), 0)
+         FROM process_usage
+         WHERE process_name = ?1
+         GROUP BY day
+         ORDER BY day ASC",
+    )?;
+    let daily_bytes: Vec<ProcessDailyBytes> = stmt
+        .query_map(params![name], |row| {
+            Ok(ProcessDailyBytes {
+                date: row.get(0)?,
+                bytes_up: row.get::<_, f64>(1).unwrap_or(0.0),
+                bytes_down: row.get::<_, f64>(2).unwrap_or(0.0),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT dst_ip, COALESCE(MAX(dst_org), ''), COALESCE(MAX(dst_country), ''),
+                COUNT(*), COALESCE(SUM(bps), 0) / 8.0
+         FROM flow_snapshots
+         WHERE process = ?1
+         GROUP BY dst_ip
+         ORDER BY SUM(bps) DESC
+         LIMIT ?2",
+    )?;
+    let top_destinations: Vec<ProcessDestination> = stmt
+        .query_map(params![name, limit], |row| {
+            Ok(ProcessDestination {
+                ip: row.get(0)?,
+                org: row.get(1)?,
+                country: row.get(2)?,
+                snapshot_count: row.get::<_, i64>(3).unwrap_or(0),
+                total_bytes: row.get::<_, f64>(4).unwrap_or(0.0),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT CAST(strftime('%H', timestamp) AS INTEGER) AS hour,
+                COUNT(*), COALESCE(SUM(bytes_up + bytes_down), 0)
+         FROM process_usage
+         WHERE process_name = ?1
+         GROUP BY hour
+         ORDER BY hour ASC",
+    )?;
+    let active_hours: Vec<HourActivity> = stmt
+        .query_map(params![name], |row| {
+            Ok(HourActivity {
+                hour: row.get(0)?,
+                sample_count: row.get::<_, i64>(1).unwrap_or(0),
+                total_bytes: row.get::<_, f64>(2).unwrap_or(0.0),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let recent_sessions: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT s.id, s.name FROM sessions s
+             WHERE s.ended_at IS NOT NULL
+               AND EXISTS (SELECT 1 FROM process_usage p
+                           WHERE p.session_id = s.id AND p.process_name = ?1)
+             ORDER BY s.started_at DESC
+             LIMIT 10",
+        )?
+        .query_map(params![name], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let own_marker = format!("'{name}'");
+    let mut anomalies = Vec::new();
+    for (sid, sname) in &recent_sessions {
+        let Ok(found) = detect_anomalies(conn, sid) else {
+            continue;
+        };
+        for anomaly in found {
+            let keep = match anomaly.anomaly_type.as_str() {
+                "THROUGHPUT_SPIKE" | "LATENCY_SPIKE" | "EXCESSIVE_FLOWS" => true,
+                "UNUSUAL_PROCESS" => anomaly.message.contains(&own_marker),
+                _ => false,
+            };
+            if keep {
+                anomalies.push(CorrelatedAnomaly {
+                    session_id: sid.clone(),
+                    session_name: sname.clone(),
+                    anomaly,
+                });
+            }
+        }
+    }
+
+    Ok(ProcessHistory {
+        process_name: name.to_string(),
+        session_count,
+        daily_bytes,
+        top_destinations,
+        active_hours,
+        anomalies,
+    })
+}
+
+// ─── Post-session insights ──────────────────────────────────────────────────
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInsights {
+    pub total_data_human: String,
+    pub busiest_minute: String,
+    pub most_active_process: String,
+    pub unique_countries: i64,
+    pub unique_destinations: i64,
+    pub high_latency_destinations: Vec<String>,
+    pub top_services: Vec<String>,
+    pub unusual_ports: Vec<i64>,
+    pub longest_connection: Option<LongestConnectionInfo>,
+    /// Connectivity problems seen during the session (IPv6 fallbacks).
+    pub connectivity: Vec<ConnectivityFinding>,
+}
+
+/// Info about the single longest-lived flow/connection in a session.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LongestConnectionInfo {
+    pub dst_ip: String,
+    pub service: String,
+    pub duration_secs: f64,
+}
+
+/// Compute post-session insights from the stored data for a given session.
+pub fn compute_session_insights(conn: &Connection, session_id: &str) -> SqlResult<SessionInsights> {
+    // Total data
+    let (bytes_up, bytes_down): (f64, f64) = conn.query_row(
+        "SELECT COALESCE(total_bytes_up, 0), COALESCE(total_bytes_down, 0) FROM sessions WHERE id = ?1",
+        params![session_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let total_bytes = bytes_up + bytes_down;
+    let total_data_human = format_bytes_human(total_bytes);
+
+    // Busiest minute — find the frame with highest bps
+    let busiest_minute: String = conn
+        .query_row(
+            "SELECT COALESCE(timestamp, '') FROM frames WHERE session_id = ?1 ORDER BY bps DESC LIMIT 1",
+            params![session_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+
+    // Most active process by total bytes
+    let most_active_process: String = conn
+        .query_row(
+            "SELECT COALESCE(process_name, 'Unknown') FROM process_usage WHERE session_id = ?1
+             GROUP BY process_name ORDER BY SUM(bytes_up + bytes_down) DESC LIMIT 1",
+            params![session_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| "Unknown".to_string());
+
+    // Unique countries
+    let unique_countries: i64 = conn
+        .query_row(
+            "SELECT COUNT(DISTINCT country) FROM destinations WHERE session_id = ?1 AND country IS NOT NULL AND country != ''",
+            params![session_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    // Unique destinations
+    let unique_destinations: i64 = conn
+        .query_row(
+            "SELECT COUNT(DISTINCT ip) FROM destinations WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    // High latency destinations (avg RTT > 200ms from flow_snapshots)
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT fs.dst_ip FROM flow_snapshots fs
+         JOIN frames f ON fs.frame_id = f.id
+         WHERE f.session_id = ?1 AND fs.rtt > 200
+         LIMIT 10"
+    )?;
+    let high_latency_destinations: Vec<String> = stmt
+        .query_map(params![session_id], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Top services
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(fs.service, 'unknown') as svc FROM flow_snapshots fs
+         JOIN frames f ON fs.frame_id = f.id
+         WHERE f.session_id = ?1 AND fs.service IS NOT NULL AND fs.service != ''
+         GROUP BY svc ORDER BY SUM(fs.bps) DESC LIMIT 5"
+    )?;
+    let top_services: Vec<String> = stmt
+        .query_map(params![session_id], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // Unusual ports (not in common set: 80, 443, 53, 22, 21, 25, 110, 143, 993, 995, 8080, 8443)
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT fs.port FROM flow_snapshots fs
          JOIN frames f ON fs.frame_id = f.id
          WHERE f.session_id = ?1 AND fs.port IS NOT NULL
            AND fs.port NOT IN (80, 443, 53, 22, 21, 25, 110, 143, 993, 995, 8080, 8443, 0)
@@ -1380,335 +3751,2237 @@ pub fn compute_session_insights(conn: &Connection, session_id: &str) -> SqlResul
         top_services,
         unusual_ports,
         longest_connection,
+        connectivity: get_connectivity_findings(conn, session_id)?,
     })
 }
 
-fn format_bytes_human(bytes: f64) -> String {
-    if !bytes.is_finite() || bytes < 0.0 {
-        return "0 B".to_string();
-    }
-    if bytes >= 1e12 {
-        format!("{:.1} TB", bytes / 1e12)
-    } else if bytes >= 1e9 {
-        format!("{:.1} GB", bytes / 1e9)
-    } else if bytes >= 1e6 {
-        format!("{:.1} MB", bytes / 1e6)
-    } else if bytes >= 1e3 {
-        format!("{:.1} KB", bytes / 1e3)
-    } else {
-        format!("{bytes:.0} B")
-    }
+/// A byte count in the user's preferred units (see `units`).
+pub fn format_bytes_human(bytes: f64) -> String {
+    units::current().bytes(bytes)
 }
 
-// ─── Playback support ───────────────────────────────────────────────────────
+// ─── QoS report ─────────────────────────────────────────────────────────────
 
-/// A full frame record including proto counters (needed to reconstruct TelemetryFrame).
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct PlaybackFrameRecord {
-    pub frame_id: i64,
-    pub t: f64,
-    pub bps: f64,
-    pub upload_bps: f64,
-    pub download_bps: f64,
-    pub active_flows: i64,
-    pub latency_ms: f64,
-    pub pps: i64,
-    pub proto_tcp: i64,
-    pub proto_udp: i64,
-    pub proto_icmp: i64,
-    pub proto_dns: i64,
-    pub proto_https: i64,
-    pub proto_http: i64,
-    pub proto_other: i64,
-}
+/// Snapshots a stream needs before it is reported (brief UDP exchanges
+/// like DNS or STUN would otherwise qualify).
+const QOS_MIN_SNAPSHOTS: i64 = 10;
+/// Streams listed in a report, longest first.
+const QOS_MAX_STREAMS: usize = 50;
 
-/// A flow snapshot with source lat/lng (for map rendering during playback).
+/// One latency-sensitive stream (a call or game session) within a recording.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct PlaybackFlowRecord {
-    pub frame_id: i64,
+pub struct QosStream {
     pub flow_id: String,
-    pub src_ip: String,
-    pub src_city: String,
-    pub src_country: String,
     pub dst_ip: String,
-    pub dst_lat: f64,
-    pub dst_lng: f64,
-    pub dst_city: String,
-    pub dst_country: String,
     pub dst_org: String,
-    pub bps: f64,
-    pub pps: i64,
-    pub rtt: f64,
-    pub protocol: String,
-    pub dir: String,
-    pub port: i64,
-    pub service: String,
-    pub started_at: f64,
+    pub dst_country: String,
     pub process: String,
-    pub pid: i64,
+    pub port: i64,
+    pub protocol: String,
+    /// Session time (`t`, as in frames) of the first and last snapshot.
+    pub started_t: f64,
+    pub ended_t: f64,
+    pub duration_secs: f64,
+    pub samples: i64,
+    pub avg_bps: f64,
+    pub avg_rtt_ms: f64,
+    pub max_rtt_ms: f64,
+    /// Mean change in RTT between consecutive samples.
+    pub jitter_ms: f64,
+    /// Share of samples where throughput fell below half the stream's median.
+    /// A steady-rate stream only dips like that when packets go missing, so
+    /// this stands in for loss, which isn't observable from socket counters.
+    pub loss_proxy_pct: f64,
+    /// Average network latency (all flows) while this stream was running.
+    pub latency_during_ms: f64,
+    /// "good", "fair" or "poor" from RTT, jitter and the loss proxy.
+    pub quality: String,
 }
 
-/// Complete playback data bundle — one IPC call loads everything.
+/// Network quality during a session's calls and games.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct PlaybackData {
-    pub session: SessionInfo,
-    pub frames: Vec<PlaybackFrameRecord>,
-    pub flows: Vec<PlaybackFlowRecord>,
+pub struct QosReport {
+    pub session_id: String,
+    pub streams: Vec<QosStream>,
+    /// Seconds with at least one latency-sensitive stream running.
+    pub active_secs: f64,
+    /// Average network latency while any stream was running, and otherwise.
+    pub latency_during_ms: f64,
+    pub latency_outside_ms: f64,
 }
 
-/// Load all playback data for a session in a single query batch.
-pub fn get_playback_data(conn: &Connection, session_id: &str) -> SqlResult<Option<PlaybackData>> {
-    let session = match get_session(conn, session_id)? {
-        Some(s) => s,
-        None => return Ok(None),
-    };
-
-    // Load all frames with proto counters
-    let mut frame_stmt = conn.prepare(
-        "SELECT id, t, bps, upload_bps, download_bps, active_flows, latency_ms, pps,
-                proto_tcp, proto_udp, proto_icmp, proto_dns, proto_https, proto_http, proto_other
-         FROM frames
-         WHERE session_id = ?1
-         ORDER BY t ASC",
+/// One snapshot of a candidate stream, joined to its frame's `t`.
+struct QosSample {
+    flow_id: String,
+    t: f64,
+    rtt: f64,
+    bps: f64,
+    dst_ip: String,
+    dst_org: String,
+    dst_country: String,
+    process: String,
+    port: i64,
+    protocol: String,
+}
+
+fn qos_quality(rtt_ms: f64, jitter_ms: f64, loss_pct: f64) -> &'static str {
+    if rtt_ms > 150.0 || jitter_ms > 50.0 || loss_pct > 5.0 {
+        "poor"
+    } else if rtt_ms > 80.0 || jitter_ms > 20.0 || loss_pct > 1.0 {
+        "fair"
+    } else {
+        "good"
+    }
+}
+
+/// Find a session's latency-sensitive streams — flows classified as
+/// real-time, or (for recordings made before classification) bidirectional
+/// UDP with small packets — and summarize their latency, jitter and loss.
+pub fn compute_qos_report(conn: &Connection, session_id: &str) -> SqlResult<QosReport> {
+    let mut stmt = conn.prepare(
+        "SELECT fs.flow_id, f.t, fs.rtt, fs.bps,
+                fs.dst_ip, COALESCE(fs.dst_org, ''), COALESCE(fs.dst_country, ''),
+                COALESCE(fs.process, ''), COALESCE(fs.port, 0), COALESCE(fs.protocol, '')
+         FROM flow_snapshots fs
+         JOIN frames f ON f.id = fs.frame_id
+         WHERE fs.session_id = ?1
+           AND fs.flow_id IN (
+               SELECT flow_id FROM flow_snapshots
+               WHERE session_id = ?1
+                 AND (service_class = 'realTime'
+                      OR (service_class IS NULL AND protocol = 'udp' AND dir = 'bidi'
+                          AND pps > 0 AND bps / 8.0 / pps < 600))
+               GROUP BY flow_id
+               HAVING COUNT(*) >= ?2)
+         ORDER BY fs.flow_id, f.t",
     )?;
-    let frames: Vec<PlaybackFrameRecord> = frame_stmt
-        .query_map(params![session_id], |row| {
-            Ok(PlaybackFrameRecord {
-                frame_id: row.get(0)?,
+    let rows: Vec<QosSample> = stmt
+        .query_map(params![session_id, QOS_MIN_SNAPSHOTS], |row| {
+            Ok(QosSample {
+                flow_id: row.get(0)?,
                 t: row.get(1)?,
-                bps: row.get(2)?,
-                upload_bps: row.get(3)?,
-                download_bps: row.get(4)?,
-                active_flows: row.get(5)?,
-                latency_ms: row.get(6)?,
-                pps: row.get(7)?,
-                proto_tcp: row.get(8)?,
-                proto_udp: row.get(9)?,
-                proto_icmp: row.get(10)?,
-                proto_dns: row.get(11)?,
-                proto_https: row.get(12)?,
-                proto_http: row.get(13)?,
-                proto_other: row.get(14)?,
+                rtt: row.get::<_, f64>(2).unwrap_or(0.0),
+                bps: row.get::<_, f64>(3).unwrap_or(0.0),
+                dst_ip: row.get(4)?,
+                dst_org: row.get(5)?,
+                dst_country: row.get(6)?,
+                process: row.get(7)?,
+                port: row.get(8)?,
+                protocol: row.get(9)?,
             })
         })?
         .filter_map(|r| r.ok())
         .collect();
 
-    // Load all flow snapshots for this session (joined by frame_id)
-    let mut flow_stmt = conn.prepare(
-        "SELECT frame_id, flow_id,
-                COALESCE(src_ip, ''), COALESCE(src_city, ''), COALESCE(src_country, ''),
-                dst_ip, COALESCE(dst_lat, 0), COALESCE(dst_lng, 0),
-                COALESCE(dst_city, ''), COALESCE(dst_country, ''), COALESCE(dst_org, ''),
-                bps, pps, rtt,
-                COALESCE(protocol, ''), COALESCE(dir, ''),
-                COALESCE(port, 0), COALESCE(service, ''),
-                COALESCE(started_at, 0),
-                COALESCE(process, ''), COALESCE(pid, 0)
-         FROM flow_snapshots
-         WHERE session_id = ?1
-         ORDER BY frame_id ASC, bps DESC",
-    )?;
-    let flows: Vec<PlaybackFlowRecord> = flow_stmt
+    let mut frame_stmt = conn.prepare("SELECT t, latency_ms FROM frames WHERE session_id = ?1 ORDER BY t ASC")?;
+    let frames: Vec<(f64, f64)> = frame_stmt
         .query_map(params![session_id], |row| {
-            Ok(PlaybackFlowRecord {
-                frame_id: row.get(0)?,
-                flow_id: row.get(1)?,
-                src_ip: row.get(2)?,
-                src_city: row.get(3)?,
-                src_country: row.get(4)?,
-                dst_ip: row.get(5)?,
-                dst_lat: row.get(6)?,
-                dst_lng: row.get(7)?,
-                dst_city: row.get(8)?,
-                dst_country: row.get(9)?,
-                dst_org: row.get(10)?,
-                bps: row.get(11)?,
-                pps: row.get(12)?,
-                rtt: row.get(13)?,
-                protocol: row.get(14)?,
-                dir: row.get(15)?,
-                port: row.get(16)?,
-                service: row.get(17)?,
-                started_at: row.get(18)?,
-                process: row.get(19)?,
-                pid: row.get(20)?,
-            })
+            Ok((row.get(0)?, row.get::<_, f64>(1).unwrap_or(0.0)))
         })?
         .filter_map(|r| r.ok())
         .collect();
+    let avg_latency = |from: f64, to: f64| {
+        let in_range: Vec<f64> = frames
+            .iter()
+            .filter(|(t, ms)| *t >= from && *t <= to && *ms > 0.0)
+            .map(|(_, ms)| *ms)
+            .collect();
+        if in_range.is_empty() {
+            0.0
+        } else {
+            in_range.iter().sum::<f64>() / in_range.len() as f64
+        }
+    };
 
-    Ok(Some(PlaybackData {
-        session,
-        frames,
-        flows,
-    }))
+    let mut streams = Vec::new();
+    for group in rows.chunk_by(|a, b| a.flow_id == b.flow_id) {
+        let first = &group[0];
+        let (started_t, ended_t) = (first.t, group[group.len() - 1].t);
+        let rtts: Vec<f64> = group.iter().map(|r| r.rtt).filter(|ms| *ms > 0.0).collect();
+        let avg_rtt_ms = if rtts.is_empty() { 0.0 } else { rtts.iter().sum::<f64>() / rtts.len() as f64 };
+        let max_rtt_ms = rtts.iter().copied().fold(0.0, f64::max);
+        let jitter_ms = if rtts.len() > 1 {
+            rtts.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (rtts.len() - 1) as f64
+        } else {
+            0.0
+        };
+
+        let mut rates: Vec<f64> = group.iter().map(|r| r.bps).collect();
+        let avg_bps = rates.iter().sum::<f64>() / rates.len() as f64;
+        rates.sort_by(|a, b| a.total_cmp(b));
+        let median = rates[rates.len() / 2];
+        let dips = rates.iter().filter(|bps| **bps < median / 2.0).count();
+        let loss_proxy_pct = dips as f64 * 100.0 / rates.len() as f64;
+
+        streams.push(QosStream {
+            flow_id: first.flow_id.clone(),
+            dst_ip: first.dst_ip.clone(),
+            dst_org: first.dst_org.clone(),
+            dst_country: first.dst_country.clone(),
+            process: first.process.clone(),
+            port: first.port,
+            protocol: first.protocol.clone(),
+            started_t,
+            ended_t,
+            duration_secs: ended_t - started_t,
+            samples: group.len() as i64,
+            avg_bps: round2(avg_bps),
+            avg_rtt_ms: round2(avg_rtt_ms),
+            max_rtt_ms: round2(max_rtt_ms),
+            jitter_ms: round2(jitter_ms),
+            loss_proxy_pct: round2(loss_proxy_pct),
+            latency_during_ms: round2(avg_latency(started_t, ended_t)),
+            quality: qos_quality(avg_rtt_ms, jitter_ms, loss_proxy_pct).to_string(),
+        });
+    }
+
+    // Frames inside any stream's span, counted once however many overlap
+    let covered = |t: f64| streams.iter().any(|s| t >= s.started_t && t <= s.ended_t);
+    let (mut during, mut outside) = (Vec::new(), Vec::new());
+    let mut active_secs = 0.0;
+    for pair in frames.windows(2) {
+        if covered(pair[0].0) && covered(pair[1].0) {
+            active_secs += pair[1].0 - pair[0].0;
+        }
+    }
+    for (t, ms) in frames.iter().filter(|(_, ms)| *ms > 0.0) {
+        if covered(*t) {
+            during.push(*ms);
+        } else {
+            outside.push(*ms);
+        }
+    }
+    let mean = |v: &[f64]| if v.is_empty() { 0.0 } else { v.iter().sum::<f64>() / v.len() as f64 };
+
+    streams.sort_by(|a, b| b.duration_secs.total_cmp(&a.duration_secs));
+    streams.truncate(QOS_MAX_STREAMS);
+
+    Ok(QosReport {
+        session_id: session_id.to_string(),
+        streams,
+        active_secs: round2(active_secs),
+        latency_during_ms: round2(mean(&during)),
+        latency_outside_ms: round2(mean(&outside)),
+    })
 }
 
-// ─── Tier 6: Baseline, Anomaly Detection, Health Score, Tagging/Search ──────
+// ─── Session card ───────────────────────────────────────────────────────────
 
-/// A single hour-of-day × day-of-week baseline bucket.
+/// Points in a session card's throughput sparkline.
+const CARD_SPARKLINE_POINTS: u32 = 60;
+
+/// Shareable one-glance summary of a session (see `card::render_svg`).
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct BaselineEntry {
-    pub hour_of_day: i32,
-    pub day_of_week: i32,
-    pub avg_bps: f64,
-    pub stddev_bps: f64,
-    pub avg_flows: f64,
-    pub stddev_flows: f64,
+pub struct SessionCard {
+    pub session_id: String,
+    pub name: String,
+    pub started_at: String,
+    pub duration_secs: Option<f64>,
+    pub total_bytes_up: f64,
+    pub total_bytes_down: f64,
+    pub total_data_human: String,
+    pub peak_bps: f64,
     pub avg_latency_ms: f64,
-    pub stddev_latency: f64,
-    pub common_processes: Vec<String>,
-    pub common_countries: Vec<String>,
-    pub sample_count: i64,
+    pub total_flows: i64,
+    pub unique_countries: i64,
+    pub unique_destinations: i64,
+    /// Downsampled total throughput (bits/s); empty for summary-only sessions.
+    pub sparkline: Vec<f64>,
+    pub top_countries: Vec<CardShare>,
+    pub top_apps: Vec<CardShare>,
 }
 
-/// Recompute the baseline_profile table from the last `range_days` of data.
-/// Uses hour-of-day (0-23) × day-of-week (0=Sunday..6=Saturday) buckets.
-/// Each bucket stores the mean & stddev of bps, flows, latency.
-pub fn compute_baseline(conn: &Connection, range_days: u32) -> SqlResult<u32> {
-    let range = if range_days == 0 { 90 } else { range_days };
+/// One row of a card's top-N list with its share of the listed total.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CardShare {
+    pub label: String,
+    pub bytes: f64,
+    pub share: f64,
+}
 
-    // Clear existing baselines
-    conn.execute("DELETE FROM baseline_profile", [])?;
+/// Gather the card for `session_id`, or `None` if it doesn't exist.
+pub fn get_session_card(conn: &Connection, session_id: &str) -> SqlResult<Option<SessionCard>> {
+    let Some(session) = get_session(conn, session_id)? else {
+        return Ok(None);
+    };
 
-    // Aggregate frame-level data into hour×dow buckets
-    let sql = "
-        SELECT
-            CAST(strftime('%H', f.timestamp) AS INTEGER) AS hour_of_day,
-            CAST(strftime('%w', f.timestamp) AS INTEGER) AS day_of_week,
-            AVG(f.bps)       AS avg_bps,
-            -- population variance (stddev² — SQLite lacks sqrt)
-            CASE WHEN COUNT(*) > 1
-                 THEN MAX(0, AVG(f.bps * f.bps) - AVG(f.bps) * AVG(f.bps))
-                 ELSE 0 END AS stddev_bps,
-            AVG(f.active_flows) AS avg_flows,
-            CASE WHEN COUNT(*) > 1
-                 THEN MAX(0, AVG(CAST(f.active_flows AS REAL) * f.active_flows) - AVG(CAST(f.active_flows AS REAL)) * AVG(CAST(f.active_flows AS REAL)))
-                 ELSE 0 END AS stddev_flows,
-            AVG(f.latency_ms)   AS avg_latency,
-            CASE WHEN COUNT(*) > 1
-                 THEN MAX(0, AVG(f.latency_ms * f.latency_ms) - AVG(f.latency_ms) * AVG(f.latency_ms))
-                 ELSE 0 END AS stddev_latency,
-            COUNT(*) AS sample_count
-        FROM frames f
-        JOIN sessions s ON s.id = f.session_id
-        WHERE julianday('now') - julianday(s.started_at) <= ?1
-          AND s.ended_at IS NOT NULL
-        GROUP BY hour_of_day, day_of_week
-    ";
+    let sparkline = get_session_frames(conn, session_id, None, None, Some(CARD_SPARKLINE_POINTS))?
+        .into_iter()
+        .map(|f| f.bps)
+        .collect();
+
+    let top_countries = card_shares(
+        conn,
+        "SELECT country, SUM(total_bytes) AS bytes FROM destinations
+         WHERE session_id = ?1 AND country IS NOT NULL AND country NOT IN ('', '??')
+         GROUP BY country ORDER BY bytes DESC LIMIT 5",
+        session_id,
+    )?;
+    let top_apps = card_shares(
+        conn,
+        "SELECT process_name, SUM(bytes_up + bytes_down) AS bytes FROM process_usage
+         WHERE session_id = ?1
+         GROUP BY process_name ORDER BY bytes DESC LIMIT 3",
+        session_id,
+    )?;
+
+    let (unique_countries, unique_destinations): (i64, i64) = conn.query_row(
+        "SELECT COUNT(DISTINCT NULLIF(NULLIF(country, ''), '??')), COUNT(DISTINCT ip)
+         FROM destinations WHERE session_id = ?1",
+        params![session_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    Ok(Some(SessionCard {
+        session_id: session.id,
+        name: session.name,
+        started_at: session.started_at,
+        duration_secs: session.duration_secs,
+        total_bytes_up: session.total_bytes_up,
+        total_bytes_down: session.total_bytes_down,
+        total_data_human: format_bytes_human(session.total_bytes_up + session.total_bytes_down),
+        peak_bps: session.peak_bps,
+        avg_latency_ms: session.avg_latency_ms,
+        total_flows: session.total_flows,
+        unique_countries,
+        unique_destinations,
+        sparkline,
+        top_countries,
+        top_apps,
+    }))
+}
 
+/// Run a `(label, bytes)` query and attach each row's share of the total.
+fn card_shares(conn: &Connection, sql: &str, session_id: &str) -> SqlResult<Vec<CardShare>> {
     let mut stmt = conn.prepare(sql)?;
-    let buckets: Vec<(i32, i32, f64, f64, f64, f64, f64, f64, i64)> = stmt
-        .query_map(params![range], |row| {
-            Ok((
-                row.get::<_, i32>(0)?,
-                row.get::<_, i32>(1)?,
-                row.get::<_, f64>(2).unwrap_or(0.0),
-                row.get::<_, f64>(3).unwrap_or(0.0),
-                row.get::<_, f64>(4).unwrap_or(0.0),
-                row.get::<_, f64>(5).unwrap_or(0.0),
-                row.get::<_, f64>(6).unwrap_or(0.0),
-                row.get::<_, f64>(7).unwrap_or(0.0),
-                row.get::<_, i64>(8).unwrap_or(0),
-            ))
-        })?
+    let rows: Vec<(String, f64)> = stmt
+        .query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
         .filter_map(|r| r.ok())
         .collect();
+    let total: f64 = rows.iter().map(|(_, bytes)| bytes).sum();
+    Ok(rows
+        .into_iter()
+        .map(|(label, bytes)| CardShare {
+            label,
+            share: if total > 0.0 { bytes / total } else { 0.0 },
+            bytes,
+        })
+        .collect())
+}
 
-    // For each bucket, also find the top processes and countries
-    let proc_sql = "
-        SELECT fs.process, COUNT(*) AS cnt
-        FROM flow_snapshots fs
-        JOIN sessions s ON s.id = fs.session_id
-        WHERE julianday('now') - julianday(s.started_at) <= ?1
-          AND s.ended_at IS NOT NULL
-          AND CAST(strftime('%H', s.started_at) AS INTEGER) = ?2
-          AND CAST(strftime('%w', s.started_at) AS INTEGER) = ?3
-          AND fs.process IS NOT NULL AND fs.process != ''
-        GROUP BY fs.process
-        ORDER BY cnt DESC
-        LIMIT 10
-    ";
-    let country_sql = "
-        SELECT fs.dst_country, COUNT(*) AS cnt
-        FROM flow_snapshots fs
-        JOIN sessions s ON s.id = fs.session_id
-        WHERE julianday('now') - julianday(s.started_at) <= ?1
-          AND s.ended_at IS NOT NULL
-          AND CAST(strftime('%H', s.started_at) AS INTEGER) = ?2
-          AND CAST(strftime('%w', s.started_at) AS INTEGER) = ?3
-          AND fs.dst_country IS NOT NULL AND fs.dst_country != ''
-        GROUP BY fs.dst_country
-        ORDER BY cnt DESC
-        LIMIT 10
-    ";
+// ─── Session reports ────────────────────────────────────────────────────────
 
-    let mut insert_stmt = conn.prepare(
-        "INSERT INTO baseline_profile
-         (hour_of_day, day_of_week, avg_bps, stddev_bps, avg_flows, stddev_flows,
-          avg_latency_ms, stddev_latency, common_processes, common_countries,
-          sample_count, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, datetime('now'))"
+const REPORT_CHART_POINTS: u32 = 240;
+const REPORT_TOP_N: u32 = 10;
+
+/// Everything `report::render_html` puts in a printable session report.
+#[derive(Clone, Debug)]
+pub struct SessionReport {
+    pub session: SessionInfo,
+    pub insights: SessionInsights,
+    pub anomalies: Vec<Anomaly>,
+    /// Downsampled frames for the throughput and latency charts; empty for
+    /// summary-only sessions.
+    pub frames: Vec<FrameRecord>,
+    pub top_destinations: Vec<DestinationRecord>,
+    pub top_processes: Vec<CardShare>,
+    pub top_countries: Vec<CardShare>,
+}
+
+/// Gather the report for `session_id`, or `None` if it doesn't exist.
+pub fn get_session_report(conn: &Connection, session_id: &str) -> SqlResult<Option<SessionReport>> {
+    let Some(session) = get_session(conn, session_id)? else {
+        return Ok(None);
+    };
+    let top_processes = card_shares(
+        conn,
+        &format!(
+            "SELECT process_name, SUM(bytes_up + bytes_down) AS bytes FROM process_usage
+             WHERE session_id = ?1
+             GROUP BY process_name ORDER BY bytes DESC LIMIT {REPORT_TOP_N}"
+        ),
+        session_id,
+    )?;
+    let top_countries = card_shares(
+        conn,
+        &format!(
+            "SELECT country, SUM(total_bytes) AS bytes FROM destinations
+             WHERE session_id = ?1 AND country IS NOT NULL AND country NOT IN ('', '??')
+             GROUP BY country ORDER BY bytes DESC LIMIT {REPORT_TOP_N}"
+        ),
+        session_id,
     )?;
 
-    for &(hour, dow, avg_b, std_b, avg_f, std_f, avg_l, std_l, cnt) in &buckets {
-        let procs: Vec<String> = {
-            let mut ps = conn.prepare(proc_sql)?;
-            let rows = ps.query_map(params![range, hour, dow], |row| row.get::<_, String>(0))?
-                .filter_map(|r| r.ok())
-                .collect();
-            rows
-        };
-        let countries: Vec<String> = {
-            let mut cs = conn.prepare(country_sql)?;
-            let rows = cs.query_map(params![range, hour, dow], |row| row.get::<_, String>(0))?
-                .filter_map(|r| r.ok())
-                .collect();
-            rows
-        };
+    Ok(Some(SessionReport {
+        insights: compute_session_insights(conn, session_id)?,
+        anomalies: detect_anomalies(conn, session_id)?,
+        frames: get_session_frames(conn, session_id, None, None, Some(REPORT_CHART_POINTS))?,
+        top_destinations: get_session_destinations(conn, session_id, "bytes", REPORT_TOP_N)?,
+        top_processes,
+        top_countries,
+        session,
+    }))
+}
 
-        let procs_json = serde_json::to_string(&procs).unwrap_or_else(|_| "[]".to_string());
-        let countries_json = serde_json::to_string(&countries).unwrap_or_else(|_| "[]".to_string());
+// ─── Session thumbnails ─────────────────────────────────────────────────────
 
-        insert_stmt.execute(params![
-            hour, dow, avg_b, std_b, avg_f, std_f, avg_l, std_l,
-            procs_json, countries_json, cnt
-        ])?;
-    }
+const THUMBNAIL_SPARKLINE_POINTS: u32 = 32;
+const THUMBNAIL_DESTINATIONS: u32 = 5;
 
-    Ok(buckets.len() as u32)
+/// Everything a session list row needs for its preview, stored when the
+/// session ends so listing never touches `frames`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionThumbnail {
+    pub session_id: String,
+    /// Downsampled total throughput (bits/s).
+    pub sparkline: Vec<f64>,
+    /// Busiest destinations with known coordinates, most bytes first.
+    pub destinations: Vec<ThumbnailPoint>,
+    pub total_bytes: f64,
 }
 
-/// Retrieve the full baseline profile (all hour×dow buckets).
-pub fn get_baseline_profile(conn: &Connection) -> SqlResult<Vec<BaselineEntry>> {
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailPoint {
+    pub lat: f64,
+    pub lng: f64,
+    pub bytes: f64,
+}
+
+/// Compute and store (or replace) the thumbnail of `session_id`.  A no-op
+/// for unknown sessions.
+pub fn store_session_thumbnail(conn: &Connection, session_id: &str) -> SqlResult<()> {
+    let Some(session) = get_session(conn, session_id)? else {
+        return Ok(());
+    };
+    let sparkline: Vec<f64> = get_session_frames(conn, session_id, None, None, Some(THUMBNAIL_SPARKLINE_POINTS))?
+        .into_iter()
+        .map(|f| f.bps.round())
+        .collect();
+
     let mut stmt = conn.prepare(
-        "SELECT hour_of_day, day_of_week, avg_bps, stddev_bps, avg_flows,
-                stddev_flows, avg_latency_ms, stddev_latency,
-                common_processes, common_countries, sample_count
-         FROM baseline_profile
-         ORDER BY day_of_week, hour_of_day"
+        "SELECT fs.dst_lat, fs.dst_lng, d.total_bytes
+         FROM destinations d
+         JOIN flow_snapshots fs ON fs.id = (
+             SELECT id FROM flow_snapshots
+             WHERE session_id = d.session_id AND dst_ip = d.ip AND dst_lat IS NOT NULL
+             LIMIT 1)
+         WHERE d.session_id = ?1
+         ORDER BY d.total_bytes DESC
+         LIMIT ?2",
+    )?;
+    let destinations: Vec<ThumbnailPoint> = stmt
+        .query_map(params![session_id, THUMBNAIL_DESTINATIONS], |row| {
+            Ok(ThumbnailPoint {
+                lat: row.get(0)?,
+                lng: row.get(1)?,
+                bytes: row.get(2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    conn.execute(
+        "INSERT OR REPLACE INTO session_thumbnails
+            (session_id, sparkline, destinations, total_bytes)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            session_id,
+            serde_json::to_string(&sparkline).unwrap_or_else(|_| "[]".to_string()),
+            serde_json::to_string(&destinations).unwrap_or_else(|_| "[]".to_string()),
+            session.total_bytes_up + session.total_bytes_down,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Thumbnails for the same page of sessions `list_sessions` returns.
+/// Finished sessions without one (recorded before thumbnails existed,
+/// crash-recovered, merged or imported) get it computed and stored here
+/// once; sessions still recording are skipped.
+pub fn list_session_thumbnails(conn: &Connection, limit: u32, offset: u32) -> SqlResult<Vec<SessionThumbnail>> {
+    let page: Vec<(String, bool)> = {
+        let mut stmt = conn.prepare(
+            "SELECT s.id, EXISTS(SELECT 1 FROM session_thumbnails t WHERE t.session_id = s.id)
+             FROM sessions s
+             WHERE s.ended_at IS NOT NULL
+               AND s.id IN (SELECT id FROM sessions ORDER BY started_at DESC LIMIT ?1 OFFSET ?2)
+             ORDER BY s.started_at DESC",
+        )?;
+        let rows = stmt.query_map(params![limit, offset], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+    for (id, _) in page.iter().filter(|(_, stored)| !stored) {
+        store_session_thumbnail(conn, id)?;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT sparkline, destinations, total_bytes FROM session_thumbnails WHERE session_id = ?1",
+    )?;
+    let mut thumbnails = Vec::with_capacity(page.len());
+    for (id, _) in page {
+        let row = stmt.query_row(params![id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?))
+        });
+        let (sparkline, destinations, total_bytes) = match row {
+            Ok(row) => row,
+            Err(rusqlite::Error::QueryReturnedNoRows) => continue,
+            Err(e) => return Err(e),
+        };
+        thumbnails.push(SessionThumbnail {
+            session_id: id,
+            sparkline: serde_json::from_str(&sparkline).unwrap_or_default(),
+            destinations: serde_json::from_str(&destinations).unwrap_or_default(),
+            total_bytes,
+        });
+    }
+    Ok(thumbnails)
+}
+
+// ─── Playback support ───────────────────────────────────────────────────────
+
+/// A full frame record including proto counters (needed to reconstruct TelemetryFrame).
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackFrameRecord {
+    pub frame_id: i64,
+    pub t: f64,
+    pub bps: f64,
+    pub upload_bps: f64,
+    pub download_bps: f64,
+    pub active_flows: i64,
+    pub latency_ms: f64,
+    pub pps: i64,
+    pub proto_tcp: i64,
+    pub proto_udp: i64,
+    pub proto_icmp: i64,
+    pub proto_dns: i64,
+    pub proto_https: i64,
+    pub proto_http: i64,
+    pub proto_other: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wifi_rssi: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wifi_link_mbps: Option<f64>,
+}
+
+/// A flow snapshot with source lat/lng (for map rendering during playback).
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackFlowRecord {
+    pub frame_id: i64,
+    pub flow_id: String,
+    pub src_ip: String,
+    pub src_city: String,
+    pub src_country: String,
+    pub dst_ip: String,
+    pub dst_lat: f64,
+    pub dst_lng: f64,
+    pub dst_city: String,
+    pub dst_country: String,
+    pub dst_org: String,
+    pub bps: f64,
+    pub pps: i64,
+    pub rtt: f64,
+    pub protocol: String,
+    pub dir: String,
+    pub port: i64,
+    pub service: String,
+    pub started_at: f64,
+    pub process: String,
+    pub pid: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threat: Option<String>,
+}
+
+/// Complete playback data bundle — one IPC call loads everything.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackData {
+    pub session: SessionInfo,
+    pub frames: Vec<PlaybackFrameRecord>,
+    pub flows: Vec<PlaybackFlowRecord>,
+    pub markers: Vec<SessionMarker>,
+}
+
+/// Load all playback data for a session in a single query batch.
+pub fn get_playback_data(conn: &Connection, session_id: &str) -> SqlResult<Option<PlaybackData>> {
+    let session = match get_session(conn, session_id)? {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+
+    let frames = playback_frames(conn, session_id, None)?;
+    let flows = playback_flows(conn, session_id, None)?;
+
+    let markers = get_session_markers(conn, session_id)?;
+
+    Ok(Some(PlaybackData {
+        session,
+        frames,
+        flows,
+        markers,
+    }))
+}
+
+/// One entry of a session's playback index: enough to draw the scrub bar
+/// and decide which chunk to fetch.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackIndexEntry {
+    pub frame_id: i64,
+    pub t: f64,
+    pub bps: f64,
+    /// Flow snapshots stored for this frame (0 for frames between samples).
+    pub flows: i64,
+}
+
+/// Everything playback needs up front; frames and flows are then loaded
+/// window by window with `get_playback_chunk`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackIndex {
+    pub session: SessionInfo,
+    pub start_t: Option<f64>,
+    pub end_t: Option<f64>,
+    pub frames: Vec<PlaybackIndexEntry>,
+    pub markers: Vec<SessionMarker>,
+}
+
+pub fn get_playback_index(conn: &Connection, session_id: &str) -> SqlResult<Option<PlaybackIndex>> {
+    let Some(session) = get_session(conn, session_id)? else {
+        return Ok(None);
+    };
+    let mut stmt = conn.prepare(
+        "SELECT f.id, f.t, f.bps, (SELECT COUNT(*) FROM flow_snapshots fs WHERE fs.frame_id = f.id)
+         FROM frames f
+         WHERE f.session_id = ?1
+         ORDER BY f.t ASC",
+    )?;
+    let frames: Vec<PlaybackIndexEntry> = stmt
+        .query_map(params![session_id], |row| {
+            Ok(PlaybackIndexEntry {
+                frame_id: row.get(0)?,
+                t: row.get(1)?,
+                bps: row.get(2)?,
+                flows: row.get(3)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(Some(PlaybackIndex {
+        session,
+        start_t: frames.first().map(|f| f.t),
+        end_t: frames.last().map(|f| f.t),
+        frames,
+        markers: get_session_markers(conn, session_id)?,
+    }))
+}
+
+/// Frames with `from_t <= t < to_t` and their flow snapshots.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackChunk {
+    pub from_t: f64,
+    pub to_t: f64,
+    pub frames: Vec<PlaybackFrameRecord>,
+    pub flows: Vec<PlaybackFlowRecord>,
+}
+
+pub fn get_playback_chunk(conn: &Connection, session_id: &str, from_t: f64, to_t: f64) -> SqlResult<PlaybackChunk> {
+    let range = Some((from_t, to_t));
+    Ok(PlaybackChunk {
+        from_t,
+        to_t,
+        frames: playback_frames(conn, session_id, range)?,
+        flows: playback_flows(conn, session_id, range)?,
+    })
+}
+
+/// A session's frames with proto counters, oldest first; only those with
+/// `from_t <= t < to_t` when `range` is given.
+fn playback_frames(
+    conn: &Connection,
+    session_id: &str,
+    range: Option<(f64, f64)>,
+) -> SqlResult<Vec<PlaybackFrameRecord>> {
+    let (from_t, to_t) = range.unwrap_or((f64::NEG_INFINITY, f64::INFINITY));
+    let mut frame_stmt = conn.prepare(
+        "SELECT id, t, bps, upload_bps, download_bps, active_flows, latency_ms, pps,
+                proto_tcp, proto_udp, proto_icmp, proto_dns, proto_https, proto_http, proto_other,
+                wifi_rssi, wifi_link_mbps
+         FROM frames
+         WHERE session_id = ?1 AND t >= ?2 AND t < ?3
+         ORDER BY t ASC",
+    )?;
+    let frames = frame_stmt
+        .query_map(params![session_id, from_t, to_t], |row| {
+            Ok(PlaybackFrameRecord {
+                frame_id: row.get(0)?,
+                t: row.get(1)?,
+                bps: row.get(2)?,
+                upload_bps: row.get(3)?,
+                download_bps: row.get(4)?,
+                active_flows: row.get(5)?,
+                latency_ms: row.get(6)?,
+                pps: row.get(7)?,
+                proto_tcp: row.get(8)?,
+                proto_udp: row.get(9)?,
+                proto_icmp: row.get(10)?,
+                proto_dns: row.get(11)?,
+                proto_https: row.get(12)?,
+                proto_http: row.get(13)?,
+                proto_other: row.get(14)?,
+                wifi_rssi: row.get(15)?,
+                wifi_link_mbps: row.get(16)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(frames)
+}
+
+/// Flow snapshots of the frames `playback_frames` returns for the same
+/// `range`, busiest first within each frame.
+fn playback_flows(
+    conn: &Connection,
+    session_id: &str,
+    range: Option<(f64, f64)>,
+) -> SqlResult<Vec<PlaybackFlowRecord>> {
+    let (from_t, to_t) = range.unwrap_or((f64::NEG_INFINITY, f64::INFINITY));
+    let mut flow_stmt = conn.prepare(
+        "SELECT frame_id, flow_id,
+                COALESCE(src_ip, ''), COALESCE(src_city, ''), COALESCE(src_country, ''),
+                dst_ip, COALESCE(dst_lat, 0), COALESCE(dst_lng, 0),
+                COALESCE(dst_city, ''), COALESCE(dst_country, ''), COALESCE(dst_org, ''),
+                bps, pps, rtt,
+                COALESCE(protocol, ''), COALESCE(dir, ''),
+                COALESCE(port, 0), COALESCE(service, ''),
+                COALESCE(started_at, 0),
+                COALESCE(process, ''), COALESCE(pid, 0), service_class, threat
+         FROM flow_snapshots
+         WHERE session_id = ?1
+           AND frame_id IN (SELECT id FROM frames WHERE session_id = ?1 AND t >= ?2 AND t < ?3)
+         ORDER BY frame_id ASC, bps DESC",
+    )?;
+    let flows: Vec<PlaybackFlowRecord> = flow_stmt
+        .query_map(params![session_id, from_t, to_t], |row| {
+            Ok(PlaybackFlowRecord {
+                frame_id: row.get(0)?,
+                flow_id: row.get(1)?,
+                src_ip: row.get(2)?,
+                src_city: row.get(3)?,
+                src_country: row.get(4)?,
+                dst_ip: row.get(5)?,
+                dst_lat: row.get(6)?,
+                dst_lng: row.get(7)?,
+                dst_city: row.get(8)?,
+                dst_country: row.get(9)?,
+                dst_org: row.get(10)?,
+                bps: row.get(11)?,
+                pps: row.get(12)?,
+                rtt: row.get(13)?,
+                protocol: row.get(14)?,
+                dir: row.get(15)?,
+                port: row.get(16)?,
+                service: row.get(17)?,
+                started_at: row.get(18)?,
+                process: row.get(19)?,
+                pid: row.get(20)?,
+                service_class: row.get(21)?,
+                threat: row.get(22)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(flows)
+}
+
+/// One destination country's share of a playback frame.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackCountryRecord {
+    pub frame_id: i64,
+    pub country: String,
+    /// Mean destination position, the arc's endpoint.
+    pub lat: f64,
+    pub lng: f64,
+    pub flows: i64,
+    pub bps: f64,
+    /// Bytes over the frame, at the usual one second per snapshot.
+    pub bytes: f64,
+}
+
+/// Playback bundle for the globe's country-arcs mode: flows are summed per
+/// destination country per frame, so long sessions stay small.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CountryPlaybackData {
+    pub session: SessionInfo,
+    pub frames: Vec<PlaybackFrameRecord>,
+    pub countries: Vec<PlaybackCountryRecord>,
+    pub markers: Vec<SessionMarker>,
+}
+
+/// Like `get_playback_data`, with flows aggregated by destination country.
+pub fn get_country_playback_data(conn: &Connection, session_id: &str) -> SqlResult<Option<CountryPlaybackData>> {
+    let session = match get_session(conn, session_id)? {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    let frames = playback_frames(conn, session_id, None)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT frame_id, COALESCE(NULLIF(dst_country, ''), '??') AS country,
+                COALESCE(AVG(dst_lat), 0), COALESCE(AVG(dst_lng), 0),
+                COUNT(*), COALESCE(SUM(bps), 0)
+         FROM flow_snapshots
+         WHERE session_id = ?1 AND frame_id IS NOT NULL
+         GROUP BY frame_id, country
+         ORDER BY frame_id ASC, SUM(bps) DESC",
+    )?;
+    let countries: Vec<PlaybackCountryRecord> = stmt
+        .query_map(params![session_id], |row| {
+            let bps: f64 = row.get(5)?;
+            Ok(PlaybackCountryRecord {
+                frame_id: row.get(0)?,
+                country: row.get(1)?,
+                lat: round2(row.get(2)?),
+                lng: round2(row.get(3)?),
+                flows: row.get(4)?,
+                bps,
+                bytes: bps / 8.0,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let markers = get_session_markers(conn, session_id)?;
+
+    Ok(Some(CountryPlaybackData {
+        session,
+        frames,
+        countries,
+        markers,
+    }))
+}
+
+// ─── Markers ────────────────────────────────────────────────────────────────
+
+/// A labelled moment on a session's timeline ("VPN connected").
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMarker {
+    pub id: i64,
+    pub session_id: String,
+    pub t: f64,
+    pub label: String,
+    pub color: Option<String>,
+    pub created_at: String,
+}
+
+pub fn insert_marker(
+    conn: &Connection,
+    session_id: &str,
+    t: f64,
+    label: &str,
+    color: Option<&str>,
+) -> SqlResult<SessionMarker> {
+    conn.execute(
+        "INSERT INTO markers (session_id, t, label, color) VALUES (?1, ?2, ?3, ?4)",
+        params![session_id, t, label, color],
+    )?;
+    let id = conn.last_insert_rowid();
+    conn.query_row(
+        "SELECT id, session_id, t, label, color, created_at FROM markers WHERE id = ?1",
+        params![id],
+        marker_row,
+    )
+}
+
+/// A session's markers in timeline order.
+pub fn get_session_markers(conn: &Connection, session_id: &str) -> SqlResult<Vec<SessionMarker>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, session_id, t, label, color, created_at
+         FROM markers WHERE session_id = ?1
+         ORDER BY t, id",
+    )?;
+    let rows = stmt
+        .query_map(params![session_id], marker_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+pub fn delete_marker(conn: &Connection, id: i64) -> SqlResult<bool> {
+    let n = conn.execute("DELETE FROM markers WHERE id = ?1", params![id])?;
+    Ok(n > 0)
+}
+
+fn marker_row(row: &rusqlite::Row<'_>) -> SqlResult<SessionMarker> {
+    Ok(SessionMarker {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        t: row.get(2)?,
+        label: row.get(3)?,
+        color: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+// ─── Tier 6: Baseline, Anomaly Detection, Health Score, Tagging/Search ──────
+
+/// A single hour-of-day × day-of-week baseline bucket.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineEntry {
+    pub hour_of_day: i32,
+    pub day_of_week: i32,
+    pub avg_bps: f64,
+    pub stddev_bps: f64,
+    pub avg_flows: f64,
+    pub stddev_flows: f64,
+    pub avg_latency_ms: f64,
+    pub stddev_latency: f64,
+    pub common_processes: Vec<String>,
+    pub common_countries: Vec<String>,
+    pub sample_count: i64,
+}
+
+/// Identifies files written by `export_baseline`.
+pub const BASELINE_EXPORT_FORMAT: &str = "abyss-baseline";
+pub const BASELINE_EXPORT_VERSION: u32 = 1;
+
+/// A baseline profile carried to another install (`cmd_export_baseline`).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineExport {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub entries: Vec<BaselineEntry>,
+}
+
+/// Raw aggregate row: (hour, dow, avg_bps, var_bps, avg_flows, var_flows, avg_lat, var_lat, count).
+type BaselineBucket = (i32, i32, f64, f64, f64, f64, f64, f64, i64);
+
+/// Recompute the baseline_profile table from the last `range_days` of data.
+/// Uses hour-of-day (0-23) × day-of-week (0=Sunday..6=Saturday) buckets.
+/// Each bucket stores the mean & stddev of bps, flows, latency.  Imported
+/// buckets (`baseline_import`) are pooled in, weighted by sample count.
+pub fn compute_baseline(conn: &Connection, range_days: u32) -> SqlResult<u32> {
+    let range = if range_days == 0 { 90 } else { range_days };
+
+    // Clear existing baselines
+    conn.execute("DELETE FROM baseline_profile", [])?;
+
+    // Aggregate frame-level data into hour×dow buckets
+    let sql = "
+        SELECT
+            CAST(strftime('%H', f.timestamp) AS INTEGER) AS hour_of_day,
+            CAST(strftime('%w', f.timestamp) AS INTEGER) AS day_of_week,
+            AVG(f.bps)       AS avg_bps,
+            -- population variance (stddev² — SQLite lacks sqrt)
+            CASE WHEN COUNT(*) > 1
+                 THEN MAX(0, AVG(f.bps * f.bps) - AVG(f.bps) * AVG(f.bps))
+                 ELSE 0 END AS stddev_bps,
+            AVG(f.active_flows) AS avg_flows,
+            CASE WHEN COUNT(*) > 1
+                 THEN MAX(0, AVG(CAST(f.active_flows AS REAL) * f.active_flows) - AVG(CAST(f.active_flows AS REAL)) * AVG(CAST(f.active_flows AS REAL)))
+                 ELSE 0 END AS stddev_flows,
+            AVG(f.latency_ms)   AS avg_latency,
+            CASE WHEN COUNT(*) > 1
+                 THEN MAX(0, AVG(f.latency_ms * f.latency_ms) - AVG(f.latency_ms) * AVG(f.latency_ms))
+                 ELSE 0 END AS stddev_latency,
+            COUNT(*) AS sample_count
+        FROM frames f
+        JOIN sessions s ON s.id = f.session_id
+        WHERE julianday('now') - julianday(s.started_at) <= ?1
+          AND s.ended_at IS NOT NULL
+        GROUP BY hour_of_day, day_of_week
+    ";
+
+    let mut stmt = conn.prepare(sql)?;
+    let buckets: Vec<BaselineBucket> = stmt
+        .query_map(params![range], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, f64>(2).unwrap_or(0.0),
+                row.get::<_, f64>(3).unwrap_or(0.0),
+                row.get::<_, f64>(4).unwrap_or(0.0),
+                row.get::<_, f64>(5).unwrap_or(0.0),
+                row.get::<_, f64>(6).unwrap_or(0.0),
+                row.get::<_, f64>(7).unwrap_or(0.0),
+                row.get::<_, i64>(8).unwrap_or(0),
+            ))
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    // For each bucket, also find the top processes and countries
+    let proc_sql = "
+        SELECT fs.process, COUNT(*) AS cnt
+        FROM flow_snapshots fs
+        JOIN sessions s ON s.id = fs.session_id
+        WHERE julianday('now') - julianday(s.started_at) <= ?1
+          AND s.ended_at IS NOT NULL
+          AND CAST(strftime('%H', s.started_at) AS INTEGER) = ?2
+          AND CAST(strftime('%w', s.started_at) AS INTEGER) = ?3
+          AND fs.process IS NOT NULL AND fs.process != ''
+        GROUP BY fs.process
+        ORDER BY cnt DESC
+        LIMIT 10
+    ";
+    let country_sql = "
+        SELECT fs.dst_country, COUNT(*) AS cnt
+        FROM flow_snapshots fs
+        JOIN sessions s ON s.id = fs.session_id
+        WHERE julianday('now') - julianday(s.started_at) <= ?1
+          AND s.ended_at IS NOT NULL
+          AND CAST(strftime('%H', s.started_at) AS INTEGER) = ?2
+          AND CAST(strftime('%w', s.started_at) AS INTEGER) = ?3
+          AND fs.dst_country IS NOT NULL AND fs.dst_country != ''
+        GROUP BY fs.dst_country
+        ORDER BY cnt DESC
+        LIMIT 10
+    ";
+
+    let mut imported: HashMap<(i32, i32), BaselineEntry> = baseline_rows(conn, "baseline_import")?
+        .into_iter()
+        .map(|e| ((e.hour_of_day, e.day_of_week), e))
+        .collect();
+
+    let mut insert_stmt = conn.prepare(
+        "INSERT INTO baseline_profile
+         (hour_of_day, day_of_week, avg_bps, stddev_bps, avg_flows, stddev_flows,
+          avg_latency_ms, stddev_latency, common_processes, common_countries,
+          sample_count, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, datetime('now'))"
+    )?;
+
+    for &(hour, dow, mut avg_b, mut std_b, mut avg_f, mut std_f, mut avg_l, mut std_l, mut cnt) in &buckets {
+        let mut procs: Vec<String> = {
+            let mut ps = conn.prepare(proc_sql)?;
+            let rows = ps.query_map(params![range, hour, dow], |row| row.get::<_, String>(0))?
+                .filter_map(|r| r.ok())
+                .collect();
+            rows
+        };
+        let mut countries: Vec<String> = {
+            let mut cs = conn.prepare(country_sql)?;
+            let rows = cs.query_map(params![range, hour, dow], |row| row.get::<_, String>(0))?
+                .filter_map(|r| r.ok())
+                .collect();
+            rows
+        };
+
+        if let Some(imp) = imported.remove(&(hour, dow)) {
+            let (n1, n2) = (cnt as f64, imp.sample_count as f64);
+            (avg_b, std_b) = pool(n1, avg_b, std_b, n2, imp.avg_bps, imp.stddev_bps.powi(2));
+            (avg_f, std_f) = pool(n1, avg_f, std_f, n2, imp.avg_flows, imp.stddev_flows.powi(2));
+            (avg_l, std_l) = pool(n1, avg_l, std_l, n2, imp.avg_latency_ms, imp.stddev_latency.powi(2));
+            cnt += imp.sample_count;
+            merge_top(&mut procs, imp.common_processes);
+            merge_top(&mut countries, imp.common_countries);
+        }
+
+        let procs_json = serde_json::to_string(&procs).unwrap_or_else(|_| "[]".to_string());
+        let countries_json = serde_json::to_string(&countries).unwrap_or_else(|_| "[]".to_string());
+
+        insert_stmt.execute(params![
+            hour, dow, avg_b, std_b, avg_f, std_f, avg_l, std_l,
+            procs_json, countries_json, cnt
+        ])?;
+    }
+
+    // Imported buckets with no local data yet
+    let imported_only = imported.len();
+    for imp in imported.into_values() {
+        insert_stmt.execute(params![
+            imp.hour_of_day,
+            imp.day_of_week,
+            imp.avg_bps,
+            imp.stddev_bps.powi(2),
+            imp.avg_flows,
+            imp.stddev_flows.powi(2),
+            imp.avg_latency_ms,
+            imp.stddev_latency.powi(2),
+            serde_json::to_string(&imp.common_processes).unwrap_or_else(|_| "[]".to_string()),
+            serde_json::to_string(&imp.common_countries).unwrap_or_else(|_| "[]".to_string()),
+            imp.sample_count
+        ])?;
+    }
+
+    Ok((buckets.len() + imported_only) as u32)
+}
+
+/// Combined (mean, variance) of two samples of sizes `n1` and `n2`.
+fn pool(n1: f64, mean1: f64, var1: f64, n2: f64, mean2: f64, var2: f64) -> (f64, f64) {
+    let n = n1 + n2;
+    if n <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let mean = (n1 * mean1 + n2 * mean2) / n;
+    let var = (n1 * (var1 + mean1 * mean1) + n2 * (var2 + mean2 * mean2)) / n - mean * mean;
+    (mean, var.max(0.0))
+}
+
+/// Append `more` names not already in `top`, keeping at most 10.
+fn merge_top(top: &mut Vec<String>, more: Vec<String>) {
+    for name in more {
+        if top.len() >= 10 {
+            break;
+        }
+        if !top.contains(&name) {
+            top.push(name);
+        }
+    }
+}
+
+/// Replace the imported baseline with `export` (validated by the caller)
+/// and recompute the profile.  Returns the number of buckets imported.
+pub fn import_baseline(conn: &Connection, export: &BaselineExport) -> SqlResult<u32> {
+    conn.execute_batch("BEGIN IMMEDIATE;")?;
+    let result = (|| -> SqlResult<()> {
+        conn.execute("DELETE FROM baseline_import", [])?;
+        let mut stmt = conn.prepare(
+            "INSERT INTO baseline_import
+             (hour_of_day, day_of_week, avg_bps, stddev_bps, avg_flows, stddev_flows,
+              avg_latency_ms, stddev_latency, common_processes, common_countries, sample_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        for e in &export.entries {
+            // Stored as variances, like baseline_profile
+            stmt.execute(params![
+                e.hour_of_day,
+                e.day_of_week,
+                e.avg_bps,
+                e.stddev_bps * e.stddev_bps,
+                e.avg_flows,
+                e.stddev_flows * e.stddev_flows,
+                e.avg_latency_ms,
+                e.stddev_latency * e.stddev_latency,
+                serde_json::to_string(&e.common_processes).unwrap_or_else(|_| "[]".to_string()),
+                serde_json::to_string(&e.common_countries).unwrap_or_else(|_| "[]".to_string()),
+                e.sample_count
+            ])?;
+        }
+        compute_baseline(conn, 90)?;
+        Ok(())
+    })();
+    match result {
+        Ok(()) => conn.execute_batch("COMMIT;")?,
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK;");
+            return Err(e);
+        }
+    }
+    Ok(export.entries.len() as u32)
+}
+
+/// Retrieve the full baseline profile (all hour×dow buckets).
+pub fn get_baseline_profile(conn: &Connection) -> SqlResult<Vec<BaselineEntry>> {
+    baseline_rows(conn, "baseline_profile")
+}
+
+/// Every bucket of `baseline_profile` or `baseline_import`, with variances
+/// converted to stddevs.
+fn baseline_rows(conn: &Connection, table: &str) -> SqlResult<Vec<BaselineEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT hour_of_day, day_of_week, avg_bps, stddev_bps, avg_flows,
+                stddev_flows, avg_latency_ms, stddev_latency,
+                common_processes, common_countries, sample_count
+         FROM {table}
+         ORDER BY day_of_week, hour_of_day"
+    ))?;
+    let rows = stmt
+        .query_map([], |row| {
+            let proc_str: String = row.get::<_, String>(8).unwrap_or_else(|_| "[]".to_string());
+            let country_str: String = row.get::<_, String>(9).unwrap_or_else(|_| "[]".to_string());
+            Ok(BaselineEntry {
+                hour_of_day: row.get(0)?,
+                day_of_week: row.get(1)?,
+                avg_bps: row.get::<_, f64>(2).unwrap_or(0.0),
+                stddev_bps: row.get::<_, f64>(3).unwrap_or(0.0).sqrt(),
+                avg_flows: row.get::<_, f64>(4).unwrap_or(0.0),
+                stddev_flows: row.get::<_, f64>(5).unwrap_or(0.0).sqrt(),
+                avg_latency_ms: row.get::<_, f64>(6).unwrap_or(0.0),
+                stddev_latency: row.get::<_, f64>(7).unwrap_or(0.0).sqrt(),
+                common_processes: serde_json::from_str(&proc_str).unwrap_or_default(),
+                common_countries: serde_json::from_str(&country_str).unwrap_or_default(),
+                sample_count: row.get::<_, i64>(10).unwrap_or(0),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Get the baseline entry for a specific hour and day-of-week.
+pub fn get_baseline_for_time(conn: &Connection, hour: i32, dow: i32) -> SqlResult<Option<BaselineEntry>> {
+    let result = conn.query_row(
+        "SELECT hour_of_day, day_of_week, avg_bps, stddev_bps, avg_flows,
+                stddev_flows, avg_latency_ms, stddev_latency,
+                common_processes, common_countries, sample_count
+         FROM baseline_profile
+         WHERE hour_of_day = ?1 AND day_of_week = ?2",
+        params![hour, dow],
+        |row| {
+            let proc_str: String = row.get::<_, String>(8).unwrap_or_else(|_| "[]".to_string());
+            let country_str: String = row.get::<_, String>(9).unwrap_or_else(|_| "[]".to_string());
+            Ok(BaselineEntry {
+                hour_of_day: row.get(0)?,
+                day_of_week: row.get(1)?,
+                avg_bps: row.get::<_, f64>(2).unwrap_or(0.0),
+                stddev_bps: row.get::<_, f64>(3).unwrap_or(0.0).sqrt(),
+                avg_flows: row.get::<_, f64>(4).unwrap_or(0.0),
+                stddev_flows: row.get::<_, f64>(5).unwrap_or(0.0).sqrt(),
+                avg_latency_ms: row.get::<_, f64>(6).unwrap_or(0.0),
+                stddev_latency: row.get::<_, f64>(7).unwrap_or(0.0).sqrt(),
+                common_processes: serde_json::from_str(&proc_str).unwrap_or_default(),
+                common_countries: serde_json::from_str(&country_str).unwrap_or_default(),
+                sample_count: row.get(10)?,
+            })
+        },
+    );
+    match result {
+        Ok(entry) => Ok(Some(entry)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Anomaly types detected against the baseline.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Anomaly {
+    pub anomaly_type: String,   // "THROUGHPUT_SPIKE", "LATENCY_SPIKE", etc.
+    pub severity: String,       // "low", "medium", "high"
+    pub message: String,
+    pub current_value: f64,
+    pub baseline_avg: f64,
+    pub baseline_stddev: f64,
+    pub deviation_sigmas: f64,  // how many σ away
+    /// 0-1: how much the baseline behind this finding can be trusted (see
+    /// `Confidence`).  Thin or noisy baselines score low.
+    pub confidence: f64,
+}
+
+/// Baseline samples at which `Confidence::samples` reaches 0.5 (an hour of
+/// frames at the default 5 s sampling interval).
+const CONFIDENCE_HALF_SAMPLES: f64 = 720.0;
+/// Session frames needed for full session coverage (five minutes).
+const CONFIDENCE_FULL_SESSION_FRAMES: f64 = 60.0;
+
+/// Inputs to `Anomaly::confidence`, each scaled 0-1.
+struct Confidence {
+    /// Baseline bucket maturity: n / (n + CONFIDENCE_HALF_SAMPLES).
+    samples: f64,
+    /// Half how much of the session was recorded, half how many of the 168
+    /// hour × weekday buckets the baseline covers.
+    coverage: f64,
+}
+
+impl Confidence {
+    /// For a σ-based finding, discounted by the baseline's coefficient of
+    /// variation — 2σ of a wildly varying metric means little.
+    fn spread(&self, avg: f64, stddev: f64) -> f64 {
+        let cv = if avg > 0.0 { stddev / avg } else { 1.0 };
+        round2(self.categorical() / (1.0 + cv))
+    }
+
+    /// For findings that compare against the common-process/country lists.
+    fn categorical(&self) -> f64 {
+        round2(self.samples * self.coverage)
+    }
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+/// Detect anomalies for a specific session by comparing its metrics to the baseline.
+pub fn detect_anomalies(conn: &Connection, session_id: &str) -> SqlResult<Vec<Anomaly>> {
+    let mut anomalies = Vec::new();
+
+    // Get session's average metrics
+    let session_stats = conn.query_row(
+        "SELECT AVG(f.bps), AVG(f.active_flows), AVG(f.latency_ms),
+                MAX(f.bps), MAX(f.active_flows), MAX(f.latency_ms),
+                CAST(strftime('%H', s.started_at) AS INTEGER),
+                CAST(strftime('%w', s.started_at) AS INTEGER),
+                COUNT(*)
+         FROM frames f
+         JOIN sessions s ON s.id = f.session_id
+         WHERE f.session_id = ?1",
+        params![session_id],
+        |row| {
+            Ok((
+                row.get::<_, f64>(0).unwrap_or(0.0),
+                row.get::<_, f64>(1).unwrap_or(0.0),
+                row.get::<_, f64>(2).unwrap_or(0.0),
+                row.get::<_, f64>(3).unwrap_or(0.0),
+                row.get::<_, f64>(4).unwrap_or(0.0),
+                row.get::<_, f64>(5).unwrap_or(0.0),
+                row.get::<_, i32>(6).unwrap_or(0),
+                row.get::<_, i32>(7).unwrap_or(0),
+                row.get::<_, i64>(8).unwrap_or(0),
+            ))
+        },
+    );
+
+    let (_avg_bps, _avg_flows, _avg_lat, peak_bps, peak_flows, peak_lat, hour, dow, frame_count) =
+        match session_stats {
+            Ok(v) => v,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(anomalies),
+            Err(e) => return Err(e),
+        };
+
+    // Get the baseline for this time slot
+    let baseline = match get_baseline_for_time(conn, hour, dow)? {
+        Some(b) => b,
+        None => return Ok(anomalies), // no baseline data yet
+    };
+
+    if baseline.sample_count < 5 {
+        return Ok(anomalies); // not enough data to compare
+    }
+
+    let buckets: i64 = conn.query_row("SELECT COUNT(*) FROM baseline_profile", [], |row| row.get(0))?;
+    let samples = baseline.sample_count as f64;
+    let confidence = Confidence {
+        samples: samples / (samples + CONFIDENCE_HALF_SAMPLES),
+        coverage: 0.5 * (frame_count as f64 / CONFIDENCE_FULL_SESSION_FRAMES).min(1.0)
+            + 0.5 * (buckets as f64 / 168.0).min(1.0),
+    };
+
+    // Check throughput spike (peak vs baseline)
+    if baseline.stddev_bps > 0.0 {
+        let sigmas = (peak_bps - baseline.avg_bps) / baseline.stddev_bps;
+        if sigmas.is_finite() && sigmas > 2.0 {
+            let severity = if sigmas > 4.0 { "high" } else if sigmas > 3.0 { "medium" } else { "low" };
+            anomalies.push(Anomaly {
+                anomaly_type: "THROUGHPUT_SPIKE".to_string(),
+                severity: severity.to_string(),
+                message: format!(
+                    "Peak throughput {} is {:.1}σ above baseline {}",
+                    format_bps(peak_bps),
+                    sigmas,
+                    format_bps(baseline.avg_bps)
+                ),
+                current_value: peak_bps,
+                baseline_avg: baseline.avg_bps,
+                baseline_stddev: baseline.stddev_bps,
+                deviation_sigmas: sigmas,
+                confidence: confidence.spread(baseline.avg_bps, baseline.stddev_bps),
+            });
+        }
+    }
+
+    // Check latency spike
+    if baseline.stddev_latency > 0.0 {
+        let sigmas = (peak_lat - baseline.avg_latency_ms) / baseline.stddev_latency;
+        if sigmas.is_finite() && sigmas > 2.0 {
+            let severity = if sigmas > 4.0 { "high" } else if sigmas > 3.0 { "medium" } else { "low" };
+            anomalies.push(Anomaly {
+                anomaly_type: "LATENCY_SPIKE".to_string(),
+                severity: severity.to_string(),
+                message: format!(
+                    "Peak latency {:.0}ms is {:.1}σ above baseline {:.0}ms",
+                    peak_lat, sigmas, baseline.avg_latency_ms
+                ),
+                current_value: peak_lat,
+                baseline_avg: baseline.avg_latency_ms,
+                baseline_stddev: baseline.stddev_latency,
+                deviation_sigmas: sigmas,
+                confidence: confidence.spread(baseline.avg_latency_ms, baseline.stddev_latency),
+            });
+        }
+    }
+
+    // Check excessive flows
+    if baseline.stddev_flows > 0.0 {
+        let sigmas = (peak_flows - baseline.avg_flows) / baseline.stddev_flows;
+        if sigmas.is_finite() && sigmas > 3.0 {
+            let severity = if sigmas > 5.0 { "high" } else if sigmas > 4.0 { "medium" } else { "low" };
+            anomalies.push(Anomaly {
+                anomaly_type: "EXCESSIVE_FLOWS".to_string(),
+                severity: severity.to_string(),
+                message: format!(
+                    "Peak flow count {:.0} is {:.1}σ above baseline {:.0}",
+                    peak_flows, sigmas, baseline.avg_flows
+                ),
+                current_value: peak_flows,
+                baseline_avg: baseline.avg_flows,
+                baseline_stddev: baseline.stddev_flows,
+                deviation_sigmas: sigmas,
+                confidence: confidence.spread(baseline.avg_flows, baseline.stddev_flows),
+            });
+        }
+    }
+
+    // Check unusual processes — processes in this session not in the common list
+    // LIMIT to avoid scanning all flow_snapshots for very long sessions
+    let session_procs: Vec<String> = conn
+        .prepare(
+            "SELECT DISTINCT process FROM flow_snapshots
+             WHERE session_id = ?1 AND process IS NOT NULL AND process != ''
+             LIMIT 100",
+        )?
+        .query_map(params![session_id], |row| row.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for proc in &session_procs {
+        if !baseline.common_processes.iter().any(|p| p == proc) {
+            anomalies.push(Anomaly {
+                anomaly_type: "UNUSUAL_PROCESS".to_string(),
+                severity: "low".to_string(),
+                message: format!("Process '{proc}' not seen in baseline"),
+                current_value: 0.0,
+                baseline_avg: 0.0,
+                baseline_stddev: 0.0,
+                deviation_sigmas: 0.0,
+                confidence: confidence.categorical(),
+            });
+        }
+    }
+
+    // Check new countries
+    // LIMIT to avoid scanning all flow_snapshots for very long sessions
+    let session_countries: Vec<String> = conn
+        .prepare(
+            "SELECT DISTINCT dst_country FROM flow_snapshots
+             WHERE session_id = ?1 AND dst_country IS NOT NULL AND dst_country != ''
+             LIMIT 50",
+        )?
+        .query_map(params![session_id], |row| row.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for country in &session_countries {
+        if !baseline.common_countries.iter().any(|c| c == country) {
+            anomalies.push(Anomaly {
+                anomaly_type: "NEW_COUNTRY".to_string(),
+                severity: "low".to_string(),
+                message: format!("Connection to '{country}' — not in baseline"),
+                current_value: 0.0,
+                baseline_avg: 0.0,
+                baseline_stddev: 0.0,
+                deviation_sigmas: 0.0,
+                confidence: confidence.categorical(),
+            });
+        }
+    }
+
+    // Check unusual ports — not in standard services list
+    static STANDARD_PORTS: &[i64] = &[
+        20, 21, 22, 25, 53, 67, 68, 80, 110, 123, 143, 161, 194,
+        389, 443, 445, 465, 514, 587, 636, 853, 993, 995,
+        1080, 1194, 1433, 1521, 1723, 3306, 3389, 5060, 5222,
+        5228, 5353, 5432, 5900, 5938, 6379, 8080, 8443, 8888,
+        9090, 9443, 27017,
+    ];
+
+    let session_ports: Vec<i64> = conn
+        .prepare(
+            "SELECT DISTINCT port FROM flow_snapshots
+             WHERE session_id = ?1 AND port IS NOT NULL AND port > 0",
+        )?
+        .query_map(params![session_id], |row| row.get::<_, i64>(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for &port in &session_ports {
+        // Only flag registered service ports (1-49151) that aren't in the standard set.
+        // Ports >= 49152 are ephemeral/dynamic and expected to vary.
+        // Ports 1024-49151 that aren't standard may indicate unusual services.
+        if !STANDARD_PORTS.contains(&port) && port > 0 && port < 49152 {
+            // Ports 1-1023 are well-known — flag at medium severity if not standard
+            // Ports 1024-49151 are registered — flag at low severity
+            let sev = if port <= 1023 { "medium" } else { "low" };
+            anomalies.push(Anomaly {
+                anomaly_type: "UNUSUAL_PORT".to_string(),
+                severity: sev.to_string(),
+                message: format!("Connection on non-standard port {port}"),
+                current_value: port as f64,
+                baseline_avg: 0.0,
+                baseline_stddev: 0.0,
+                deviation_sigmas: 0.0,
+                confidence: confidence.categorical(),
+            });
+        }
+    }
+
+    // Limit to avoid overwhelming UI
+    anomalies.truncate(20);
+    Ok(anomalies)
+}
+
+/// Network health score (0-100) for the current baseline period.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthScore {
+    pub score: u32,
+    pub latency_score: u32,      // 0-25 (lower latency = higher score)
+    pub stability_score: u32,    // 0-25 (less throughput variance = higher)
+    pub diversity_score: u32,    // 0-25 (healthy protocol mix = higher)
+    pub anomaly_score: u32,      // 0-25 (fewer anomalies = higher)
+    pub details: String,
+}
+
+/// Compute a network health score from the last N hours of data.
+pub fn compute_health_score(conn: &Connection, hours: u32) -> SqlResult<HealthScore> {
+    let hours = if hours == 0 { 24 } else { hours };
+
+    // Check if we have any data in the time range
+    let frame_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*)
+             FROM frames f
+             JOIN sessions s ON s.id = f.session_id
+             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
+            params![hours],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    if frame_count == 0 {
+        return Ok(HealthScore {
+            score: 0,
+            latency_score: 0,
+            stability_score: 0,
+            diversity_score: 0,
+            anomaly_score: 0,
+            details: "No data available — start recording to compute health score".to_string(),
+        });
+    }
+
+    // Latency score: avg latency in last N hours → 0-25
+    let (avg_lat, _lat_var): (f64, f64) = conn
+        .query_row(
+            "SELECT COALESCE(AVG(f.latency_ms), 0),
+                    CASE WHEN COUNT(*) > 1
+                         THEN COALESCE(AVG(f.latency_ms * f.latency_ms) - AVG(f.latency_ms) * AVG(f.latency_ms), 0)
+                         ELSE 0 END
+             FROM frames f
+             JOIN sessions s ON s.id = f.session_id
+             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
+            params![hours],
+            |row| Ok((row.get::<_, f64>(0).unwrap_or(0.0), row.get::<_, f64>(1).unwrap_or(0.0))),
+        )
+        .unwrap_or((0.0, 0.0));
+
+    // Lower latency → higher score: 0ms=25, 100ms=12, 500ms+=0
+    let latency_score = if avg_lat <= 0.0 {
+        25u32
+    } else {
+        (25.0 * (1.0 - (avg_lat / 500.0).min(1.0))).round() as u32
+    };
+
+    // Stability score: low coefficient of variation in bps → higher score
+    let (avg_bps, bps_var): (f64, f64) = conn
+        .query_row(
+            "SELECT COALESCE(AVG(f.bps), 0),
+                    CASE WHEN COUNT(*) > 1
+                         THEN COALESCE(AVG(f.bps * f.bps) - AVG(f.bps) * AVG(f.bps), 0)
+                         ELSE 0 END
+             FROM frames f
+             JOIN sessions s ON s.id = f.session_id
+             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
+            params![hours],
+            |row| Ok((row.get::<_, f64>(0).unwrap_or(0.0), row.get::<_, f64>(1).unwrap_or(0.0))),
+        )
+        .unwrap_or((0.0, 0.0));
+
+    let cv = if avg_bps > 0.0 {
+        let raw_cv = (bps_var.max(0.0).sqrt()) / avg_bps;
+        if raw_cv.is_finite() { raw_cv } else { 0.0 }
+    } else {
+        0.0
+    };
+    // CV 0=stable=25, CV 2+=very unstable=0
+    let stability_score = (25.0 * (1.0 - (cv / 2.0).min(1.0))).round() as u32;
+
+    // Protocol diversity: ratio of unique protocols used
+    let (proto_tcp, proto_udp, proto_dns, proto_https, proto_http, proto_other) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(f.proto_tcp), 0), COALESCE(SUM(f.proto_udp), 0),
+                    COALESCE(SUM(f.proto_dns), 0), COALESCE(SUM(f.proto_https), 0),
+                    COALESCE(SUM(f.proto_http), 0), COALESCE(SUM(f.proto_other), 0)
+             FROM frames f
+             JOIN sessions s ON s.id = f.session_id
+             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
+            params![hours],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0).unwrap_or(0),
+                    row.get::<_, i64>(1).unwrap_or(0),
+                    row.get::<_, i64>(2).unwrap_or(0),
+                    row.get::<_, i64>(3).unwrap_or(0),
+                    row.get::<_, i64>(4).unwrap_or(0),
+                    row.get::<_, i64>(5).unwrap_or(0),
+                ))
+            },
+        )
+        .unwrap_or((0, 0, 0, 0, 0, 0));
+
+    let used_protos = [proto_tcp, proto_udp, proto_dns, proto_https, proto_http, proto_other]
+        .iter()
+        .filter(|&&v| v > 0)
+        .count();
+    // 6 protocols used = 25, 1 = ~4, 0 = 0
+    let diversity_score = if used_protos > 0 {
+        ((used_protos as f64 / 6.0) * 25.0).round() as u32
+    } else {
+        0
+    };
+
+    // Anomaly score: check recent sessions for anomalies
+    // Only check up to 3 most recent sessions to keep computation fast
+    let recent_sessions: Vec<String> = conn
+        .prepare(
+            "SELECT id FROM sessions
+             WHERE ended_at IS NOT NULL
+               AND (julianday('now') - julianday(started_at)) * 24 <= ?1
+             ORDER BY started_at DESC
+             LIMIT 3",
+        )?
+        .query_map(params![hours], |row| row.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut total_anomalies = 0usize;
+    for sid in &recent_sessions {
+        if let Ok(anomalies) = detect_anomalies(conn, sid) {
+            total_anomalies += anomalies.iter().filter(|a| a.severity != "low").count();
+        }
+        // Early exit: if we already have enough anomalies to hit the cap (5+), skip remaining
+        if total_anomalies >= 5 {
+            break;
+        }
+    }
+    // 0 anomalies=25, 5+=0
+    let anomaly_score = (25.0 * (1.0 - (total_anomalies as f64 / 5.0).min(1.0))).round() as u32;
+
+    let total = latency_score + stability_score + diversity_score + anomaly_score;
+
+    let details = if total >= 80 {
+        "Excellent network health".to_string()
+    } else if total >= 60 {
+        "Good network health".to_string()
+    } else if total >= 40 {
+        "Fair network health — some issues detected".to_string()
+    } else {
+        "Poor network health — significant issues".to_string()
+    };
+
+    Ok(HealthScore {
+        score: total,
+        latency_score,
+        stability_score,
+        diversity_score,
+        anomaly_score,
+        details,
+    })
+}
+
+/// Search sessions by name, tags, or notes (every word, as a prefix),
+/// best match first.
+pub fn search_sessions(
+    conn: &Connection,
+    query: &str,
+    limit: u32,
+) -> SqlResult<Vec<SessionInfo>> {
+    let Some(pattern) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn.prepare(
+        "SELECT s.id, s.name, s.started_at, s.ended_at, s.duration_secs,
+                s.total_bytes_up, s.total_bytes_down, s.total_flows,
+                s.peak_bps, s.peak_flows, s.avg_latency_ms,
+                s.local_city, s.local_country, s.local_lat, s.local_lng,
+                s.notes, s.tags, s.crash_recovered, s.summary_only
+         FROM sessions_fts f
+         JOIN sessions s ON s.id = f.session_id
+         WHERE sessions_fts MATCH ?1
+         ORDER BY f.rank
+         LIMIT ?2",
+    )?;
+    let rows = stmt
+        .query_map(params![pattern, limit], |row| {
+            let ended_at: Option<String> = row.get(3)?;
+            let crash_recovered: bool = row.get::<_, i32>(17).unwrap_or(0) != 0;
+            let status = if ended_at.is_none() {
+                "recording".to_string()
+            } else if crash_recovered {
+                "crashed".to_string()
+            } else {
+                "complete".to_string()
+            };
+            Ok(SessionInfo {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                started_at: row.get(2)?,
+                ended_at,
+                duration_secs: row.get(4)?,
+                total_bytes_up: row.get::<_, f64>(5).unwrap_or(0.0),
+                total_bytes_down: row.get::<_, f64>(6).unwrap_or(0.0),
+                total_flows: row.get::<_, i64>(7).unwrap_or(0),
+                peak_bps: row.get::<_, f64>(8).unwrap_or(0.0),
+                peak_flows: row.get::<_, i64>(9).unwrap_or(0),
+                avg_latency_ms: row.get::<_, f64>(10).unwrap_or(0.0),
+                local_city: row.get::<_, String>(11).unwrap_or_default(),
+                local_country: row.get::<_, String>(12).unwrap_or_default(),
+                local_lat: row.get::<_, f64>(13).unwrap_or(0.0),
+                local_lng: row.get::<_, f64>(14).unwrap_or(0.0),
+                notes: row.get::<_, String>(15).unwrap_or_default(),
+                tags: row.get::<_, String>(16).unwrap_or_else(|_| "[]".to_string()),
+                status,
+                summary_only: row.get::<_, i32>(18).unwrap_or(0) != 0,
+                recording_stats: None,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Free text as an FTS5 query: every word must match, as a prefix.  Quotes
+/// are dropped so user input can't form query syntax.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchHitKind {
+    Session,
+    Destination,
+    Process,
+}
+
+/// One `search_everything` result.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub kind: SearchHitKind,
+    pub session_id: String,
+    pub session_name: String,
+    /// Session name, destination IP or process name.
+    pub label: String,
+    /// Matching text with matches wrapped in `[` `]`.
+    pub snippet: String,
+    /// FTS5 bm25 score; lower is a better match.
+    pub rank: f64,
+}
+
+/// Search session names/notes/tags, destination IPs/domains/orgs/places and
+/// process names at once, best matches first.
+pub fn search_everything(conn: &Connection, query: &str, limit: u32) -> SqlResult<Vec<SearchHit>> {
+    let Some(pattern) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let searches = [
+        (
+            SearchHitKind::Session,
+            "SELECT s.id, s.name, s.name, snippet(sessions_fts, -1, '[', ']', '…', 12), sessions_fts.rank
+             FROM sessions_fts JOIN sessions s ON s.id = sessions_fts.session_id
+             WHERE sessions_fts MATCH ?1 ORDER BY sessions_fts.rank LIMIT ?2",
+        ),
+        (
+            SearchHitKind::Destination,
+            "SELECT s.id, s.name, d.ip, snippet(destinations_fts, -1, '[', ']', '…', 12), destinations_fts.rank
+             FROM destinations_fts
+             JOIN destinations d ON d.id = destinations_fts.rowid
+             JOIN sessions s ON s.id = d.session_id
+             WHERE destinations_fts MATCH ?1 ORDER BY destinations_fts.rank LIMIT ?2",
+        ),
+        (
+            SearchHitKind::Process,
+            "SELECT s.id, s.name, p.process_name, snippet(processes_fts, -1, '[', ']', '…', 12), processes_fts.rank
+             FROM processes_fts
+             JOIN session_processes p ON p.id = processes_fts.rowid
+             JOIN sessions s ON s.id = p.session_id
+             WHERE processes_fts MATCH ?1 ORDER BY processes_fts.rank LIMIT ?2",
+        ),
+    ];
+    let mut hits: Vec<SearchHit> = Vec::new();
+    for (kind, sql) in searches {
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params![pattern, limit], |row| {
+            Ok(SearchHit {
+                kind,
+                session_id: row.get(0)?,
+                session_name: row.get(1)?,
+                label: row.get(2)?,
+                snippet: row.get(3)?,
+                rank: row.get(4)?,
+            })
+        })?;
+        hits.extend(rows.filter_map(|r| r.ok()));
+    }
+    hits.sort_by(|a, b| a.rank.total_cmp(&b.rank));
+    hits.truncate(limit as usize);
+    Ok(hits)
+}
+
+/// Update tags for a session.
+pub fn update_session_tags(conn: &Connection, session_id: &str, tags: &[String]) -> SqlResult<()> {
+    // Limit tags: max 20, each max 50 chars
+    let clamped: Vec<String> = tags
+        .iter()
+        .take(20)
+        .map(|t| if t.len() > 50 { t[..50].to_string() } else { t.clone() })
+        .collect();
+    let tags_json = serde_json::to_string(&clamped).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE sessions SET tags = ?1 WHERE id = ?2",
+        params![tags_json, session_id],
+    )?;
+    Ok(())
+}
+
+// ─── Settings ───────────────────────────────────────────────────────────────
+
+/// All stored settings as (key, JSON value) pairs.
+pub fn get_settings(conn: &Connection) -> SqlResult<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Insert or replace a single setting.
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, datetime('now'))
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+// ─── Hostnames & labels ─────────────────────────────────────────────────────
+
+const RECORD_HOSTNAME_SQL: &str = "INSERT INTO host_observations (ip, hostname, source)
+    VALUES (?1, ?2, ?3)
+    ON CONFLICT(ip, hostname) DO UPDATE SET
+        last_seen  = datetime('now'),
+        seen_count = seen_count + 1,
+        source     = excluded.source";
+
+/// Record that `ip` was seen under `hostname` (source: "dns", "sni", ...).
+pub fn record_hostname(conn: &Connection, ip: &str, hostname: &str, source: &str) -> SqlResult<()> {
+    conn.prepare_cached(RECORD_HOSTNAME_SQL)?.execute(params![ip, hostname.trim_end_matches('.').to_ascii_lowercase(), source])?;
+    Ok(())
+}
+
+/// Point `ip` at the domain it was most recently resolved from.
+pub fn upsert_dns_mapping(conn: &Connection, ip: &str, domain: &str, ttl: u32, source: &str) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO dns_map (ip, domain, source, ttl)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(ip) DO UPDATE SET
+            domain     = excluded.domain,
+            source     = excluded.source,
+            ttl        = excluded.ttl,
+            first_seen = CASE WHEN domain = excluded.domain THEN first_seen ELSE datetime('now') END,
+            last_seen  = datetime('now')",
+        params![ip, domain, source, ttl],
+    )?;
+    Ok(())
+}
+
+/// `(ip, domain)` mappings seen within the last `max_age_secs`.
+pub fn load_dns_map(conn: &Connection, max_age_secs: u64) -> SqlResult<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT ip, domain FROM dns_map
+         WHERE last_seen >= datetime('now', '-' || ?1 || ' seconds')",
+    )?;
+    let rows = stmt
+        .query_map(params![max_age_secs as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Set (or with both empty, remove) the user label and category for `ip`.
+pub fn set_destination_label(conn: &Connection, ip: &str, label: &str, category: &str) -> SqlResult<()> {
+    if label.is_empty() && category.is_empty() {
+        conn.execute("DELETE FROM destination_labels WHERE ip = ?1", params![ip])?;
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO destination_labels (ip, label, category, updated_at)
+         VALUES (?1, ?2, ?3, datetime('now'))
+         ON CONFLICT(ip) DO UPDATE SET
+            label      = excluded.label,
+            category   = excluded.category,
+            updated_at = excluded.updated_at",
+        params![ip, label, category],
+    )?;
+    Ok(())
+}
+
+// ─── Range comparison ───────────────────────────────────────────────────────
+
+/// A half-open time range `[start, end)`; either bound may be a date
+/// (`YYYY-MM-DD`) or an RFC 3339 timestamp.  Sessions are assigned to a
+/// range by their start time.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeRange {
+    pub start: String,
+    pub end: String,
+}
+
+/// Bytes attributed to one app or country within a range.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeShare {
+    pub name: String,
+    pub bytes: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeSummary {
+    pub range: TimeRange,
+    pub session_count: i64,
+    pub recorded_secs: f64,
+    pub bytes_up: f64,
+    pub bytes_down: f64,
+    /// Total bytes per recorded hour, so ranges of different length compare.
+    pub bytes_per_hour: f64,
+    /// Session latency averages weighted by sample count.
+    pub avg_latency_ms: f64,
+    pub peak_bps: f64,
+    pub top_apps: Vec<RangeShare>,
+    pub top_countries: Vec<RangeShare>,
+}
+
+/// An app or country present in either range, with B − A.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareDelta {
+    pub name: String,
+    pub bytes_a: f64,
+    pub bytes_b: f64,
+    pub delta: f64,
+}
+
+/// Differences B − A; percentages are `None` when A is zero.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeDeltas {
+    pub bytes_up: f64,
+    pub bytes_down: f64,
+    pub bytes_per_hour: f64,
+    pub bytes_per_hour_pct: Option<f64>,
+    pub avg_latency_ms: f64,
+    pub avg_latency_pct: Option<f64>,
+    pub apps: Vec<ShareDelta>,
+    pub countries: Vec<ShareDelta>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeComparison {
+    pub a: RangeSummary,
+    pub b: RangeSummary,
+    pub deltas: RangeDeltas,
+}
+
+/// Sessions whose start falls within `[?1, ?2)`.
+const RANGE_SESSIONS: &str =
+    "SELECT id FROM sessions WHERE julianday(started_at) >= julianday(?1) AND julianday(started_at) < julianday(?2)";
+
+fn summarize_range(conn: &Connection, range: &TimeRange, limit: u32) -> SqlResult<RangeSummary> {
+    let (session_count, recorded_secs, bytes_up, bytes_down, latency_weighted, latency_samples, peak_bps) =
+        conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(duration_secs), 0),
+                        COALESCE(SUM(total_bytes_up), 0), COALESCE(SUM(total_bytes_down), 0),
+                        COALESCE(SUM(avg_latency_ms * latency_samples), 0),
+                        COALESCE(SUM(latency_samples), 0), COALESCE(MAX(peak_bps), 0)
+                 FROM sessions WHERE id IN ({RANGE_SESSIONS})"
+            ),
+            params![range.start, range.end],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, f64>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, f64>(6)?,
+                ))
+            },
+        )?;
+
+    let shares = |sql: String| -> SqlResult<Vec<RangeShare>> {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(params![range.start, range.end, limit], |row| {
+                Ok(RangeShare {
+                    name: row.get(0)?,
+                    bytes: row.get::<_, f64>(1).unwrap_or(0.0),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    };
+    let top_apps = shares(format!(
+        "SELECT process_name, SUM(bytes_up + bytes_down) AS total
+         FROM process_usage WHERE session_id IN ({RANGE_SESSIONS})
+         GROUP BY process_name ORDER BY total DESC LIMIT ?3"
+    ))?;
+    let top_countries = shares(format!(
+        "SELECT COALESCE(NULLIF(country, ''), '??'), SUM(total_bytes) AS total
+         FROM destinations WHERE session_id IN ({RANGE_SESSIONS})
+         GROUP BY 1 ORDER BY total DESC LIMIT ?3"
+    ))?;
+
+    let hours = recorded_secs / 3600.0;
+    Ok(RangeSummary {
+        range: range.clone(),
+        session_count,
+        recorded_secs,
+        bytes_up,
+        bytes_down,
+        bytes_per_hour: if hours > 0.0 { (bytes_up + bytes_down) / hours } else { 0.0 },
+        avg_latency_ms: if latency_samples > 0 { latency_weighted / latency_samples as f64 } else { 0.0 },
+        peak_bps,
+        top_apps,
+        top_countries,
+    })
+}
+
+/// Union of two top-N lists with per-name deltas, largest change first.
+fn share_deltas(a: &[RangeShare], b: &[RangeShare]) -> Vec<ShareDelta> {
+    let mut names: Vec<&str> = a.iter().chain(b).map(|s| s.name.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+    let bytes_of = |list: &[RangeShare], name: &str| {
+        list.iter().find(|s| s.name == name).map(|s| s.bytes).unwrap_or(0.0)
+    };
+    let mut deltas: Vec<ShareDelta> = names
+        .into_iter()
+        .map(|name| {
+            let (bytes_a, bytes_b) = (bytes_of(a, name), bytes_of(b, name));
+            ShareDelta {
+                name: name.to_string(),
+                bytes_a,
+                bytes_b,
+                delta: bytes_b - bytes_a,
+            }
+        })
+        .collect();
+    deltas.sort_by(|x, y| y.delta.abs().partial_cmp(&x.delta.abs()).unwrap_or(std::cmp::Ordering::Equal));
+    deltas
+}
+
+/// Compare usage between two time ranges (B relative to A).
+pub fn compare_ranges(conn: &Connection, a: &TimeRange, b: &TimeRange, limit: u32) -> SqlResult<RangeComparison> {
+    let a = summarize_range(conn, a, limit)?;
+    let b = summarize_range(conn, b, limit)?;
+    let pct = |from: f64, to: f64| if from > 0.0 { Some((to - from) / from * 100.0) } else { None };
+    let deltas = RangeDeltas {
+        bytes_up: b.bytes_up - a.bytes_up,
+        bytes_down: b.bytes_down - a.bytes_down,
+        bytes_per_hour: b.bytes_per_hour - a.bytes_per_hour,
+        bytes_per_hour_pct: pct(a.bytes_per_hour, b.bytes_per_hour),
+        avg_latency_ms: b.avg_latency_ms - a.avg_latency_ms,
+        avg_latency_pct: pct(a.avg_latency_ms, b.avg_latency_ms),
+        apps: share_deltas(&a.top_apps, &b.top_apps),
+        countries: share_deltas(&a.top_countries, &b.top_countries),
+    };
+    Ok(RangeComparison { a, b, deltas })
+}
+
+// ─── Alerts ─────────────────────────────────────────────────────────────────
+
+/// Bytes (up, down) recorded by sessions started at or after `since`.
+pub fn bytes_since(conn: &Connection, since: &str) -> SqlResult<(f64, f64)> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(total_bytes_up), 0), COALESCE(SUM(total_bytes_down), 0)
+         FROM sessions
+         WHERE julianday(started_at) >= julianday(?1)",
+        params![since],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Every destination country recorded in any session (`NewCountry` rules).
+pub fn known_countries(conn: &Connection) -> SqlResult<HashSet<String>> {
+    let mut stmt =
+        conn.prepare("SELECT DISTINCT country FROM destinations WHERE country IS NOT NULL AND country NOT IN ('', '??')")?;
+    let rows = stmt.query_map([], |row| row.get(0))?.filter_map(|r| r.ok()).collect();
+    Ok(rows)
+}
+
+/// All alert rules, oldest first.  Rows whose definition no longer parses
+/// are skipped with a warning.
+pub fn list_alert_rules(conn: &Connection) -> SqlResult<Vec<AlertRule>> {
+    let mut stmt = conn.prepare("SELECT id, definition FROM alert_rules ORDER BY created_at, id")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .filter_map(|r| r.ok())
+        .filter_map(|(id, definition)| match serde_json::from_str(&definition) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                eprintln!("[Abyss] Skipping alert rule {id}: {e}");
+                None
+            }
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// Insert or replace an alert rule.
+pub fn save_alert_rule(conn: &Connection, rule: &AlertRule) -> SqlResult<()> {
+    let definition = serde_json::to_string(rule)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO alert_rules (id, name, enabled, definition)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET
+            name       = excluded.name,
+            enabled    = excluded.enabled,
+            definition = excluded.definition,
+            updated_at = datetime('now')",
+        params![rule.id, rule.name, rule.enabled as i32, definition],
+    )?;
+    Ok(())
+}
+
+/// Delete an alert rule; its past events are kept.
+pub fn delete_alert_rule(conn: &Connection, id: &str) -> SqlResult<bool> {
+    let n = conn.execute("DELETE FROM alert_rules WHERE id = ?1", params![id])?;
+    Ok(n > 0)
+}
+
+pub fn insert_alert_event(conn: &Connection, event: &AlertEvent) -> SqlResult<()> {
+    let context = serde_json::to_string(&event.context)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT OR IGNORE INTO alert_events
+            (id, rule_id, rule_name, session_id, severity, subject, message, value, threshold,
+             triggered_at, suppressed, context)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            event.id,
+            event.rule_id,
+            event.rule_name,
+            event.session_id,
+            severity_str(event.severity),
+            event.subject,
+            event.message,
+            event.value,
+            event.threshold,
+            event.triggered_at,
+            event.suppressed,
+            context,
+        ],
     )?;
+    Ok(())
+}
+
+/// Filters for `get_alert_history`; bounds accept dates or RFC 3339 timestamps.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertHistoryFilters {
+    pub rule_id: Option<String>,
+    pub session_id: Option<String>,
+    /// Minimum severity.
+    pub severity: Option<Severity>,
+    pub subject: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Alert events matching `filters`, newest first (default 100 per page).
+pub fn get_alert_history(conn: &Connection, filters: &AlertHistoryFilters) -> SqlResult<Vec<AlertEvent>> {
+    let mut sql = String::from(
+        "SELECT id, rule_id, rule_name, session_id, severity, subject, message, value, threshold,
+                triggered_at, suppressed, context
+         FROM alert_events WHERE 1 = 1",
+    );
+    let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+
+    if let Some(rule_id) = &filters.rule_id {
+        params_vec.push(Box::new(rule_id.clone()));
+        sql.push_str(&format!(" AND rule_id = ?{}", params_vec.len()));
+    }
+    if let Some(session_id) = &filters.session_id {
+        params_vec.push(Box::new(session_id.clone()));
+        sql.push_str(&format!(" AND session_id = ?{}", params_vec.len()));
+    }
+    if let Some(min) = filters.severity {
+        let allowed: Vec<String> = [Severity::Info, Severity::Warning, Severity::Critical]
+            .into_iter()
+            .filter(|s| *s >= min)
+            .map(|s| format!("'{}'", severity_str(s)))
+            .collect();
+        sql.push_str(&format!(" AND severity IN ({})", allowed.join(", ")));
+    }
+    if let Some(subject) = &filters.subject {
+        params_vec.push(Box::new(subject.clone()));
+        sql.push_str(&format!(" AND subject = ?{} COLLATE NOCASE", params_vec.len()));
+    }
+    if let Some(since) = &filters.since {
+        params_vec.push(Box::new(since.clone()));
+        sql.push_str(&format!(" AND julianday(triggered_at) >= julianday(?{})", params_vec.len()));
+    }
+    if let Some(until) = &filters.until {
+        params_vec.push(Box::new(until.clone()));
+        sql.push_str(&format!(" AND julianday(triggered_at) < julianday(?{})", params_vec.len()));
+    }
+    sql.push_str(" ORDER BY triggered_at DESC");
+    params_vec.push(Box::new(filters.limit.unwrap_or(100)));
+    sql.push_str(&format!(" LIMIT ?{}", params_vec.len()));
+    params_vec.push(Box::new(filters.offset.unwrap_or(0)));
+    sql.push_str(&format!(" OFFSET ?{}", params_vec.len()));
+
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
     let rows = stmt
-        .query_map([], |row| {
-            let proc_str: String = row.get::<_, String>(8).unwrap_or_else(|_| "[]".to_string());
-            let country_str: String = row.get::<_, String>(9).unwrap_or_else(|_| "[]".to_string());
-            Ok(BaselineEntry {
-                hour_of_day: row.get(0)?,
-                day_of_week: row.get(1)?,
-                avg_bps: row.get::<_, f64>(2).unwrap_or(0.0),
-                stddev_bps: row.get::<_, f64>(3).unwrap_or(0.0).sqrt(),
-                avg_flows: row.get::<_, f64>(4).unwrap_or(0.0),
-                stddev_flows: row.get::<_, f64>(5).unwrap_or(0.0).sqrt(),
-                avg_latency_ms: row.get::<_, f64>(6).unwrap_or(0.0),
-                stddev_latency: row.get::<_, f64>(7).unwrap_or(0.0).sqrt(),
-                common_processes: serde_json::from_str(&proc_str).unwrap_or_default(),
-                common_countries: serde_json::from_str(&country_str).unwrap_or_default(),
-                sample_count: row.get::<_, i64>(10).unwrap_or(0),
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(AlertEvent {
+                id: row.get(0)?,
+                rule_id: row.get(1)?,
+                rule_name: row.get(2)?,
+                session_id: row.get(3)?,
+                severity: parse_severity(&row.get::<_, String>(4)?),
+                subject: row.get(5)?,
+                message: row.get(6)?,
+                value: row.get(7)?,
+                threshold: row.get(8)?,
+                triggered_at: row.get(9)?,
+                suppressed: row.get(10)?,
+                context: serde_json::from_str::<AlertContext>(&row.get::<_, String>(11)?)
+                    .unwrap_or_default(),
             })
         })?
         .filter_map(|r| r.ok())
@@ -1716,475 +5989,959 @@ pub fn get_baseline_profile(conn: &Connection) -> SqlResult<Vec<BaselineEntry>>
     Ok(rows)
 }
 
-/// Get the baseline entry for a specific hour and day-of-week.
-pub fn get_baseline_for_time(conn: &Connection, hour: i32, dow: i32) -> SqlResult<Option<BaselineEntry>> {
-    let result = conn.query_row(
-        "SELECT hour_of_day, day_of_week, avg_bps, stddev_bps, avg_flows,
-                stddev_flows, avg_latency_ms, stddev_latency,
-                common_processes, common_countries, sample_count
-         FROM baseline_profile
-         WHERE hour_of_day = ?1 AND day_of_week = ?2",
-        params![hour, dow],
-        |row| {
-            let proc_str: String = row.get::<_, String>(8).unwrap_or_else(|_| "[]".to_string());
-            let country_str: String = row.get::<_, String>(9).unwrap_or_else(|_| "[]".to_string());
-            Ok(BaselineEntry {
-                hour_of_day: row.get(0)?,
-                day_of_week: row.get(1)?,
-                avg_bps: row.get::<_, f64>(2).unwrap_or(0.0),
-                stddev_bps: row.get::<_, f64>(3).unwrap_or(0.0).sqrt(),
-                avg_flows: row.get::<_, f64>(4).unwrap_or(0.0),
-                stddev_flows: row.get::<_, f64>(5).unwrap_or(0.0).sqrt(),
-                avg_latency_ms: row.get::<_, f64>(6).unwrap_or(0.0),
-                stddev_latency: row.get::<_, f64>(7).unwrap_or(0.0).sqrt(),
-                common_processes: serde_json::from_str(&proc_str).unwrap_or_default(),
-                common_countries: serde_json::from_str(&country_str).unwrap_or_default(),
-                sample_count: row.get(10)?,
+/// Firings per day (7-day average) above which a rule is flagged noisy.
+const NOISY_FIRINGS_PER_DAY: f64 = 12.0;
+
+/// How often a rule fires, for spotting noisy rules.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRuleStats {
+    pub rule_id: String,
+    pub rule_name: String,
+    /// False when the rule has since been deleted.
+    pub exists: bool,
+    pub total: i64,
+    pub last_24h: i64,
+    pub last_7d: i64,
+    pub firings_per_day: f64,
+    /// Breaches swallowed by the cooldown (not counted in `total`).
+    pub suppressed: i64,
+    pub distinct_subjects: i64,
+    pub top_subject: Option<String>,
+    pub first_fired: Option<String>,
+    pub last_fired: Option<String>,
+    /// Median gap between consecutive firings in the last 7 days.
+    pub median_interval_secs: Option<f64>,
+    /// More than `NOISY_FIRINGS_PER_DAY`, or the cooldown suppresses more
+    /// breaches than it lets through.
+    pub noisy: bool,
+}
+
+/// Per-rule firing statistics, noisiest first.  Includes rules that never
+/// fired and deleted rules that still have history.
+pub fn get_alert_stats(conn: &Connection) -> SqlResult<Vec<AlertRuleStats>> {
+    let mut stmt = conn.prepare(
+        "SELECT r.id, COALESCE(r.name, e.rule_name), r.id IS NOT NULL,
+                COALESCE(e.total, 0), COALESCE(e.day, 0), COALESCE(e.week, 0),
+                COALESCE(e.suppressed, 0), COALESCE(e.subjects, 0), e.first_fired, e.last_fired
+         FROM alert_rules r
+         LEFT JOIN (
+            SELECT rule_id, MAX(rule_name) AS rule_name, COUNT(*) AS total,
+                   SUM(julianday('now') - julianday(triggered_at) <= 1) AS day,
+                   SUM(julianday('now') - julianday(triggered_at) <= 7) AS week,
+                   SUM(suppressed) AS suppressed, COUNT(DISTINCT subject) AS subjects,
+                   MIN(triggered_at) AS first_fired, MAX(triggered_at) AS last_fired
+            FROM alert_events GROUP BY rule_id
+         ) e ON e.rule_id = r.id
+         UNION ALL
+         SELECT e.rule_id, MAX(e.rule_name), 0,
+                COUNT(*), SUM(julianday('now') - julianday(e.triggered_at) <= 1),
+                SUM(julianday('now') - julianday(e.triggered_at) <= 7),
+                SUM(e.suppressed), COUNT(DISTINCT e.subject), MIN(e.triggered_at), MAX(e.triggered_at)
+         FROM alert_events e
+         WHERE e.rule_id NOT IN (SELECT id FROM alert_rules)
+         GROUP BY e.rule_id",
+    )?;
+    let mut stats: Vec<AlertRuleStats> = stmt
+        .query_map([], |row| {
+            Ok(AlertRuleStats {
+                rule_id: row.get(0)?,
+                rule_name: row.get(1)?,
+                exists: row.get(2)?,
+                total: row.get(3)?,
+                last_24h: row.get(4)?,
+                last_7d: row.get(5)?,
+                firings_per_day: 0.0,
+                suppressed: row.get(6)?,
+                distinct_subjects: row.get(7)?,
+                top_subject: None,
+                first_fired: row.get(8)?,
+                last_fired: row.get(9)?,
+                median_interval_secs: None,
+                noisy: false,
             })
-        },
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut subject_stmt = conn.prepare(
+        "SELECT subject FROM alert_events WHERE rule_id = ?1
+         GROUP BY subject ORDER BY COUNT(*) DESC, subject LIMIT 1",
+    )?;
+    let mut times_stmt = conn.prepare(
+        "SELECT (julianday(triggered_at) - 2440587.5) * 86400.0 FROM alert_events
+         WHERE rule_id = ?1 AND julianday('now') - julianday(triggered_at) <= 7
+         ORDER BY triggered_at",
+    )?;
+    for s in &mut stats {
+        if s.total == 0 {
+            continue;
+        }
+        s.top_subject = subject_stmt.query_row(params![s.rule_id], |row| row.get(0)).ok();
+        let times: Vec<f64> = times_stmt
+            .query_map(params![s.rule_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        let mut gaps: Vec<f64> = times.windows(2).map(|w| w[1] - w[0]).collect();
+        if !gaps.is_empty() {
+            gaps.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            s.median_interval_secs = Some((gaps[gaps.len() / 2] * 10.0).round() / 10.0);
+        }
+        s.firings_per_day = (s.last_7d as f64 / 7.0 * 100.0).round() / 100.0;
+        s.noisy = s.firings_per_day > NOISY_FIRINGS_PER_DAY || s.suppressed > s.total;
+    }
+    stats.sort_by(|a, b| {
+        b.noisy
+            .cmp(&a.noisy)
+            .then(b.last_7d.cmp(&a.last_7d))
+            .then(b.total.cmp(&a.total))
+    });
+    Ok(stats)
+}
+
+fn parse_severity(s: &str) -> Severity {
+    match s {
+        "critical" => Severity::Critical,
+        "warning" => Severity::Warning,
+        _ => Severity::Info,
+    }
+}
+
+fn severity_str(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+// ─── Flow events ────────────────────────────────────────────────────────────
+
+pub fn insert_flow_event(conn: &Connection, session_id: Option<&str>, event: &FlowEvent) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO flow_events
+            (session_id, kind, flow_id, local_ip, remote_ip, remote_port, proto, process, pid,
+             at, opened_at, closed_at, duration_secs, bytes_up, bytes_down)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        params![
+            session_id,
+            event.kind.as_str(),
+            event.flow_id,
+            event.local_ip,
+            event.remote_ip,
+            event.remote_port,
+            event.proto,
+            event.process,
+            event.pid,
+            event.at,
+            event.opened_at,
+            event.closed_at,
+            event.duration_secs,
+            event.bytes_up,
+            event.bytes_down,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Filters for `get_flow_events`; bounds accept dates or RFC 3339 timestamps.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowEventFilters {
+    pub session_id: Option<String>,
+    pub kind: Option<FlowEventKind>,
+    pub remote_ip: Option<String>,
+    pub process: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Flow events matching `filters`, newest first (default 500 per page).
+pub fn get_flow_events(conn: &Connection, filters: &FlowEventFilters) -> SqlResult<Vec<FlowEvent>> {
+    let mut sql = String::from(
+        "SELECT kind, flow_id, local_ip, remote_ip, remote_port, proto, process, pid,
+                at, opened_at, closed_at, duration_secs, bytes_up, bytes_down
+         FROM flow_events WHERE 1 = 1",
     );
-    match result {
-        Ok(entry) => Ok(Some(entry)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e),
+    let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+
+    if let Some(session_id) = &filters.session_id {
+        params_vec.push(Box::new(session_id.clone()));
+        sql.push_str(&format!(" AND session_id = ?{}", params_vec.len()));
+    }
+    if let Some(kind) = filters.kind {
+        params_vec.push(Box::new(kind.as_str()));
+        sql.push_str(&format!(" AND kind = ?{}", params_vec.len()));
+    }
+    if let Some(remote_ip) = &filters.remote_ip {
+        params_vec.push(Box::new(remote_ip.clone()));
+        sql.push_str(&format!(" AND remote_ip = ?{}", params_vec.len()));
     }
+    if let Some(process) = &filters.process {
+        params_vec.push(Box::new(process.clone()));
+        sql.push_str(&format!(" AND process = ?{} COLLATE NOCASE", params_vec.len()));
+    }
+    if let Some(since) = &filters.since {
+        params_vec.push(Box::new(since.clone()));
+        sql.push_str(&format!(" AND julianday(at) >= julianday(?{})", params_vec.len()));
+    }
+    if let Some(until) = &filters.until {
+        params_vec.push(Box::new(until.clone()));
+        sql.push_str(&format!(" AND julianday(at) < julianday(?{})", params_vec.len()));
+    }
+    sql.push_str(" ORDER BY at DESC, id DESC");
+    params_vec.push(Box::new(filters.limit.unwrap_or(500)));
+    sql.push_str(&format!(" LIMIT ?{}", params_vec.len()));
+    params_vec.push(Box::new(filters.offset.unwrap_or(0)));
+    sql.push_str(&format!(" OFFSET ?{}", params_vec.len()));
+
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(FlowEvent {
+                kind: if row.get::<_, String>(0)? == "closed" {
+                    FlowEventKind::Closed
+                } else {
+                    FlowEventKind::Opened
+                },
+                flow_id: row.get(1)?,
+                local_ip: row.get(2)?,
+                remote_ip: row.get(3)?,
+                remote_port: row.get(4)?,
+                proto: row.get(5)?,
+                process: row.get(6)?,
+                pid: row.get(7)?,
+                at: row.get(8)?,
+                opened_at: row.get(9)?,
+                closed_at: row.get(10)?,
+                duration_secs: row.get(11)?,
+                bytes_up: row.get(12)?,
+                bytes_down: row.get(13)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
 }
 
-/// Anomaly types detected against the baseline.
+// ─── Webhooks ───────────────────────────────────────────────────────────────
+
+/// All webhook endpoints, oldest first.
+pub fn list_webhooks(conn: &Connection) -> SqlResult<Vec<Webhook>> {
+    let mut stmt =
+        conn.prepare("SELECT id, url, secret, events, enabled, created_at FROM webhooks ORDER BY created_at, id")?;
+    let rows = stmt
+        .query_map([], |row| {
+            let events: String = row.get(3)?;
+            Ok(Webhook {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                secret: row.get(2)?,
+                events: serde_json::from_str::<Vec<WebhookEvent>>(&events).unwrap_or_default(),
+                enabled: row.get::<_, i32>(4)? != 0,
+                created_at: row.get(5)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+pub fn insert_webhook(conn: &Connection, hook: &Webhook) -> SqlResult<()> {
+    let events = serde_json::to_string(&hook.events)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO webhooks (id, url, secret, events, enabled, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![hook.id, hook.url, hook.secret, events, hook.enabled as i32, hook.created_at],
+    )?;
+    Ok(())
+}
+
+pub fn delete_webhook(conn: &Connection, id: &str) -> SqlResult<bool> {
+    let n = conn.execute("DELETE FROM webhooks WHERE id = ?1", params![id])?;
+    Ok(n > 0)
+}
+
+// ─── Router counters ────────────────────────────────────────────────────────
+
+pub fn insert_router_sample(conn: &Connection, session_id: &str, sample: &RouterSample) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO router_samples (session_id, timestamp, source, interval_secs, bytes_sent, bytes_received)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            session_id,
+            sample.timestamp,
+            sample.source.as_str(),
+            sample.interval_secs,
+            sample.bytes_sent,
+            sample.bytes_received,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Host-measured traffic next to what the gateway counted, over the router
+/// poll intervals that also have recorded frames.  The router sees every
+/// device behind it, so host shares below 1 are expected; shares above 1
+/// point at host-side over-counting.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct Anomaly {
-    pub anomaly_type: String,   // "THROUGHPUT_SPIKE", "LATENCY_SPIKE", etc.
-    pub severity: String,       // "low", "medium", "high"
-    pub message: String,
-    pub current_value: f64,
-    pub baseline_avg: f64,
-    pub baseline_stddev: f64,
-    pub deviation_sigmas: f64,  // how many σ away
+pub struct RouterComparison {
+    pub session_id: String,
+    /// "upnp" or "snmp"; the most recent when the source changed mid-session.
+    pub source: Option<String>,
+    pub samples: i64,
+    /// Seconds covered by the compared intervals.
+    pub covered_secs: f64,
+    pub router_bytes_up: f64,
+    pub router_bytes_down: f64,
+    /// Integrated from the frames inside the same intervals.
+    pub host_bytes_up: f64,
+    pub host_bytes_down: f64,
+    /// `host / router`, when the router counted anything.
+    pub host_share_up: Option<f64>,
+    pub host_share_down: Option<f64>,
 }
 
-/// Detect anomalies for a specific session by comparing its metrics to the baseline.
-pub fn detect_anomalies(conn: &Connection, session_id: &str) -> SqlResult<Vec<Anomaly>> {
-    let mut anomalies = Vec::new();
-
-    // Get session's average metrics
-    let session_stats = conn.query_row(
-        "SELECT AVG(f.bps), AVG(f.active_flows), AVG(f.latency_ms),
-                MAX(f.bps), MAX(f.active_flows), MAX(f.latency_ms),
-                CAST(strftime('%H', s.started_at) AS INTEGER),
-                CAST(strftime('%w', s.started_at) AS INTEGER)
-         FROM frames f
-         JOIN sessions s ON s.id = f.session_id
-         WHERE f.session_id = ?1",
+pub fn get_router_comparison(conn: &Connection, session_id: &str) -> SqlResult<RouterComparison> {
+    let source: Option<String> = conn.query_row(
+        "SELECT (SELECT source FROM router_samples WHERE session_id = ?1 ORDER BY timestamp DESC LIMIT 1)",
         params![session_id],
-        |row| {
-            Ok((
-                row.get::<_, f64>(0).unwrap_or(0.0),
-                row.get::<_, f64>(1).unwrap_or(0.0),
-                row.get::<_, f64>(2).unwrap_or(0.0),
-                row.get::<_, f64>(3).unwrap_or(0.0),
-                row.get::<_, f64>(4).unwrap_or(0.0),
-                row.get::<_, f64>(5).unwrap_or(0.0),
-                row.get::<_, i32>(6).unwrap_or(0),
-                row.get::<_, i32>(7).unwrap_or(0),
-            ))
-        },
-    );
+        |row| row.get(0),
+    )?;
 
-    let (_avg_bps, _avg_flows, _avg_lat, peak_bps, peak_flows, peak_lat, hour, dow) =
-        match session_stats {
-            Ok(v) => v,
-            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(anomalies),
-            Err(e) => return Err(e),
-        };
+    // Frame rates are averaged per interval, so sparse or rolled-up frames still integrate
+    let mut stmt = conn.prepare(
+        "SELECT r.interval_secs, r.bytes_sent, r.bytes_received, AVG(f.upload_bps), AVG(f.download_bps)
+         FROM router_samples r
+         JOIN frames f ON f.session_id = r.session_id
+          AND julianday(f.timestamp) BETWEEN julianday(r.timestamp) - r.interval_secs / 86400.0
+                                         AND julianday(r.timestamp)
+         WHERE r.session_id = ?1
+         GROUP BY r.id",
+    )?;
+    let rows: Vec<(f64, f64, f64, f64, f64)> = stmt
+        .query_map(params![session_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
 
-    // Get the baseline for this time slot
-    let baseline = match get_baseline_for_time(conn, hour, dow)? {
-        Some(b) => b,
-        None => return Ok(anomalies), // no baseline data yet
+    let mut cmp = RouterComparison {
+        session_id: session_id.to_string(),
+        source,
+        samples: rows.len() as i64,
+        covered_secs: 0.0,
+        router_bytes_up: 0.0,
+        router_bytes_down: 0.0,
+        host_bytes_up: 0.0,
+        host_bytes_down: 0.0,
+        host_share_up: None,
+        host_share_down: None,
     };
-
-    if baseline.sample_count < 5 {
-        return Ok(anomalies); // not enough data to compare
+    for (secs, sent, received, up_bps, down_bps) in rows {
+        cmp.covered_secs += secs;
+        cmp.router_bytes_up += sent;
+        cmp.router_bytes_down += received;
+        cmp.host_bytes_up += up_bps / 8.0 * secs;
+        cmp.host_bytes_down += down_bps / 8.0 * secs;
     }
+    cmp.covered_secs = round2(cmp.covered_secs);
+    cmp.host_bytes_up = cmp.host_bytes_up.round();
+    cmp.host_bytes_down = cmp.host_bytes_down.round();
+    cmp.host_share_up = (cmp.router_bytes_up > 0.0).then(|| round2(cmp.host_bytes_up / cmp.router_bytes_up));
+    cmp.host_share_down = (cmp.router_bytes_down > 0.0).then(|| round2(cmp.host_bytes_down / cmp.router_bytes_down));
+    Ok(cmp)
+}
 
-    // Check throughput spike (peak vs baseline)
-    if baseline.stddev_bps > 0.0 {
-        let sigmas = (peak_bps - baseline.avg_bps) / baseline.stddev_bps;
-        if sigmas.is_finite() && sigmas > 2.0 {
-            let severity = if sigmas > 4.0 { "high" } else if sigmas > 3.0 { "medium" } else { "low" };
-            anomalies.push(Anomaly {
-                anomaly_type: "THROUGHPUT_SPIKE".to_string(),
-                severity: severity.to_string(),
-                message: format!(
-                    "Peak throughput {}/s is {:.1}σ above baseline {}/s",
-                    format_bytes_human(peak_bps),
-                    sigmas,
-                    format_bytes_human(baseline.avg_bps)
-                ),
-                current_value: peak_bps,
-                baseline_avg: baseline.avg_bps,
-                baseline_stddev: baseline.stddev_bps,
-                deviation_sigmas: sigmas,
-            });
-        }
-    }
+// ─── Route events ───────────────────────────────────────────────────────────
+
+/// `kind` is "start" (session start or first reading) or "change".
+pub fn insert_route_event(
+    conn: &Connection,
+    session_id: &str,
+    timestamp: &str,
+    kind: &str,
+    routes: &[DefaultRoute],
+) -> SqlResult<()> {
+    let preferred = routes.first();
+    let json = serde_json::to_string(routes).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO route_events (session_id, timestamp, kind, gateway, interface, metric, routes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            session_id,
+            timestamp,
+            kind,
+            preferred.and_then(|r| r.gateway.as_deref()),
+            preferred.map(|r| r.interface.as_str()),
+            preferred.and_then(|r| r.metric),
+            json,
+        ],
+    )?;
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteEventRecord {
+    pub timestamp: String,
+    pub kind: String,
+    /// The preferred default route's next hop and interface.
+    pub gateway: Option<String>,
+    pub interface: Option<String>,
+    pub metric: Option<i64>,
+    /// Every default route, preferred first.
+    pub routes: Vec<DefaultRoute>,
+}
 
-    // Check latency spike
-    if baseline.stddev_latency > 0.0 {
-        let sigmas = (peak_lat - baseline.avg_latency_ms) / baseline.stddev_latency;
-        if sigmas.is_finite() && sigmas > 2.0 {
-            let severity = if sigmas > 4.0 { "high" } else if sigmas > 3.0 { "medium" } else { "low" };
-            anomalies.push(Anomaly {
-                anomaly_type: "LATENCY_SPIKE".to_string(),
-                severity: severity.to_string(),
-                message: format!(
-                    "Peak latency {:.0}ms is {:.1}σ above baseline {:.0}ms",
-                    peak_lat, sigmas, baseline.avg_latency_ms
-                ),
-                current_value: peak_lat,
-                baseline_avg: baseline.avg_latency_ms,
-                baseline_stddev: baseline.stddev_latency,
-                deviation_sigmas: sigmas,
-            });
-        }
-    }
+pub fn get_route_events(conn: &Connection, session_id: &str) -> SqlResult<Vec<RouteEventRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, kind, gateway, interface, metric, routes FROM route_events
+         WHERE session_id = ?1 ORDER BY timestamp ASC, id ASC",
+    )?;
+    let rows = stmt
+        .query_map(params![session_id], |row| {
+            let routes: String = row.get(5)?;
+            Ok(RouteEventRecord {
+                timestamp: row.get(0)?,
+                kind: row.get(1)?,
+                gateway: row.get(2)?,
+                interface: row.get(3)?,
+                metric: row.get(4)?,
+                routes: serde_json::from_str(&routes).unwrap_or_default(),
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
 
-    // Check excessive flows
-    if baseline.stddev_flows > 0.0 {
-        let sigmas = (peak_flows - baseline.avg_flows) / baseline.stddev_flows;
-        if sigmas.is_finite() && sigmas > 3.0 {
-            let severity = if sigmas > 5.0 { "high" } else if sigmas > 4.0 { "medium" } else { "low" };
-            anomalies.push(Anomaly {
-                anomaly_type: "EXCESSIVE_FLOWS".to_string(),
-                severity: severity.to_string(),
-                message: format!(
-                    "Peak flow count {:.0} is {:.1}σ above baseline {:.0}",
-                    peak_flows, sigmas, baseline.avg_flows
-                ),
-                current_value: peak_flows,
-                baseline_avg: baseline.avg_flows,
-                baseline_stddev: baseline.stddev_flows,
-                deviation_sigmas: sigmas,
-            });
-        }
-    }
+// ─── Network changes ────────────────────────────────────────────────────────
 
-    // Check unusual processes — processes in this session not in the common list
-    // LIMIT to avoid scanning all flow_snapshots for very long sessions
-    let session_procs: Vec<String> = conn
-        .prepare(
-            "SELECT DISTINCT process FROM flow_snapshots
-             WHERE session_id = ?1 AND process IS NOT NULL AND process != ''
-             LIMIT 100",
-        )?
-        .query_map(params![session_id], |row| row.get::<_, String>(0))?
+pub fn insert_network_change(
+    conn: &Connection,
+    session_id: &str,
+    timestamp: &str,
+    kind: &str,
+    change: &NetworkChange,
+) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO network_changes
+            (session_id, timestamp, kind, ip, previous_ip, city, country, lat, lng, org, interface, vpn)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            session_id,
+            timestamp,
+            kind,
+            change.ip,
+            change.previous_ip,
+            change.city,
+            change.country,
+            change.lat,
+            change.lng,
+            change.org,
+            change.interface,
+            change.vpn,
+        ],
+    )?;
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkChangeRecord {
+    pub timestamp: String,
+    /// `start` (session start or first reading) or `change`.
+    pub kind: String,
+    pub ip: String,
+    pub previous_ip: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
+    pub org: Option<String>,
+    pub interface: Option<String>,
+    pub vpn: bool,
+}
+
+pub fn get_network_changes(conn: &Connection, session_id: &str) -> SqlResult<Vec<NetworkChangeRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, kind, ip, previous_ip, city, country, lat, lng, org, interface, vpn
+         FROM network_changes WHERE session_id = ?1 ORDER BY timestamp ASC, id ASC",
+    )?;
+    let rows = stmt
+        .query_map(params![session_id], |row| {
+            Ok(NetworkChangeRecord {
+                timestamp: row.get(0)?,
+                kind: row.get(1)?,
+                ip: row.get(2)?,
+                previous_ip: row.get(3)?,
+                city: row.get(4)?,
+                country: row.get(5)?,
+                lat: row.get(6)?,
+                lng: row.get(7)?,
+                org: row.get(8)?,
+                interface: row.get(9)?,
+                vpn: row.get(10)?,
+            })
+        })?
         .filter_map(|r| r.ok())
         .collect();
+    Ok(rows)
+}
 
-    for proc in &session_procs {
-        if !baseline.common_processes.iter().any(|p| p == proc) {
-            anomalies.push(Anomaly {
-                anomaly_type: "UNUSUAL_PROCESS".to_string(),
-                severity: "low".to_string(),
-                message: format!("Process '{proc}' not seen in baseline"),
-                current_value: 0.0,
-                baseline_avg: 0.0,
-                baseline_stddev: 0.0,
-                deviation_sigmas: 0.0,
-            });
+// ─── Watchlist ──────────────────────────────────────────────────────────────
+
+pub fn load_watchlist(conn: &Connection) -> SqlResult<Watchlist> {
+    let mut stmt = conn.prepare("SELECT kind, value FROM watchlist ORDER BY added_at, value")?;
+    let mut list = Watchlist::default();
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    for (kind, value) in rows.filter_map(|r| r.ok()) {
+        match WatchKind::parse(&kind) {
+            Some(WatchKind::Country) => list.countries.push(value),
+            Some(WatchKind::Asn) => list.asns.push(value),
+            None => {}
         }
     }
+    Ok(list)
+}
 
-    // Check new countries
-    // LIMIT to avoid scanning all flow_snapshots for very long sessions
-    let session_countries: Vec<String> = conn
-        .prepare(
-            "SELECT DISTINCT dst_country FROM flow_snapshots
-             WHERE session_id = ?1 AND dst_country IS NOT NULL AND dst_country != ''
-             LIMIT 50",
-        )?
-        .query_map(params![session_id], |row| row.get::<_, String>(0))?
-        .filter_map(|r| r.ok())
+/// Replace the stored watchlist; entries kept from before keep their `added_at`.
+pub fn save_watchlist(conn: &Connection, list: &Watchlist) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    let entries: Vec<(&str, &str)> = list
+        .countries
+        .iter()
+        .map(|c| (WatchKind::Country.as_str(), c.as_str()))
+        .chain(list.asns.iter().map(|a| (WatchKind::Asn.as_str(), a.as_str())))
         .collect();
+    let stored: Vec<(String, String)> = {
+        let mut stmt = tx.prepare("SELECT kind, value FROM watchlist")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+    for (kind, value) in &stored {
+        if !entries.contains(&(kind.as_str(), value.as_str())) {
+            tx.execute("DELETE FROM watchlist WHERE kind = ?1 AND value = ?2", params![kind, value])?;
+        }
+    }
+    for (kind, value) in entries {
+        tx.execute("INSERT OR IGNORE INTO watchlist (kind, value) VALUES (?1, ?2)", params![kind, value])?;
+    }
+    tx.commit()
+}
 
-    for country in &session_countries {
-        if !baseline.common_countries.iter().any(|c| c == country) {
-            anomalies.push(Anomaly {
-                anomaly_type: "NEW_COUNTRY".to_string(),
-                severity: "low".to_string(),
-                message: format!("Connection to '{country}' — not in baseline"),
-                current_value: 0.0,
-                baseline_avg: 0.0,
-                baseline_stddev: 0.0,
-                deviation_sigmas: 0.0,
-            });
+/// Add `hits` to the session's per-entity counts.
+pub fn record_watch_hits(conn: &Connection, session_id: &str, hits: &[WatchHit]) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO watch_hits (session_id, kind, value, hits, first_seen, last_seen)
+             VALUES (?1, ?2, ?3, 1, ?4, ?4)
+             ON CONFLICT(session_id, kind, value) DO UPDATE SET
+                hits      = hits + 1,
+                last_seen = excluded.last_seen",
+        )?;
+        for hit in hits {
+            stmt.execute(params![session_id, hit.tag.kind.as_str(), hit.tag.value, hit.timestamp])?;
         }
     }
+    tx.commit()
+}
 
-    // Check unusual ports — not in standard services list
-    static STANDARD_PORTS: &[i64] = &[
-        20, 21, 22, 25, 53, 67, 68, 80, 110, 123, 143, 161, 194,
-        389, 443, 445, 465, 514, 587, 636, 853, 993, 995,
-        1080, 1194, 1433, 1521, 1723, 3306, 3389, 5060, 5222,
-        5228, 5353, 5432, 5900, 5938, 6379, 8080, 8443, 8888,
-        9090, 9443, 27017,
-    ];
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchHitCount {
+    pub kind: WatchKind,
+    pub value: String,
+    /// Flows to the entity that appeared during the session.
+    pub hits: i64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
 
-    let session_ports: Vec<i64> = conn
-        .prepare(
-            "SELECT DISTINCT port FROM flow_snapshots
-             WHERE session_id = ?1 AND port IS NOT NULL AND port > 0",
-        )?
-        .query_map(params![session_id], |row| row.get::<_, i64>(0))?
+pub fn get_watch_hits(conn: &Connection, session_id: &str) -> SqlResult<Vec<WatchHitCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT kind, value, hits, first_seen, last_seen FROM watch_hits
+         WHERE session_id = ?1 ORDER BY hits DESC, value ASC",
+    )?;
+    let rows = stmt
+        .query_map(params![session_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
         .filter_map(|r| r.ok())
+        .filter_map(|(kind, value, hits, first_seen, last_seen)| {
+            Some(WatchHitCount {
+                kind: WatchKind::parse(&kind)?,
+                value,
+                hits,
+                first_seen,
+                last_seen,
+            })
+        })
         .collect();
+    Ok(rows)
+}
 
-    for &port in &session_ports {
-        // Only flag registered service ports (1-49151) that aren't in the standard set.
-        // Ports >= 49152 are ephemeral/dynamic and expected to vary.
-        // Ports 1024-49151 that aren't standard may indicate unusual services.
-        if !STANDARD_PORTS.contains(&port) && port > 0 && port < 49152 {
-            // Ports 1-1023 are well-known — flag at medium severity if not standard
-            // Ports 1024-49151 are registered — flag at low severity
-            let sev = if port <= 1023 { "medium" } else { "low" };
-            anomalies.push(Anomaly {
-                anomaly_type: "UNUSUAL_PORT".to_string(),
-                severity: sev.to_string(),
-                message: format!("Connection on non-standard port {port}"),
-                current_value: port as f64,
-                baseline_avg: 0.0,
-                baseline_stddev: 0.0,
-                deviation_sigmas: 0.0,
-            });
+// ─── IPv4 / IPv6 ────────────────────────────────────────────────────────────
+
+/// Failed IPv6 attempts to an org before they count as consistent.
+const IPV6_MIN_FAILURES: i64 = 3;
+/// Failed attempts with no IPv6 connection succeeding at all that mark
+/// IPv6 as broken on the network.
+const IPV6_BROKEN_FAILURES: i64 = 10;
+
+/// Add connection outcomes from the monitor loop to the session's counts.
+/// Orgs the geo cache hadn't resolved are grouped as "Unknown".
+pub fn record_family_attempts(conn: &Connection, session_id: &str, attempts: &[FamilyAttempt]) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    for a in attempts {
+        let org = a.org.as_deref().filter(|o| !o.is_empty()).unwrap_or("Unknown");
+        let (v4, v6, failed) = match (a.ipv6, a.connected) {
+            (false, _) => (1, 0, 0),
+            (true, true) => (0, 1, 0),
+            (true, false) => (0, 0, 1),
+        };
+        let fell_back = (failed == 1 && a.fell_back) as i64;
+        tx.execute(
+            "INSERT INTO ip_family_usage (session_id, org, v4_connections, v6_connections, v6_failures, v6_fallbacks)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(session_id, org) DO UPDATE SET
+                v4_connections = v4_connections + excluded.v4_connections,
+                v6_connections = v6_connections + excluded.v6_connections,
+                v6_failures    = v6_failures + excluded.v6_failures,
+                v6_fallbacks   = v6_fallbacks + excluded.v6_fallbacks",
+            params![session_id, org, v4, v6, failed, fell_back],
+        )?;
+        if failed == 1 {
+            let destination = a.domain.as_deref().unwrap_or(&a.remote_ip);
+            tx.execute(
+                "INSERT INTO ipv6_failures (session_id, org, destination, failures, fallbacks, last_failed_at)
+                 VALUES (?1, ?2, ?3, 1, ?4, ?5)
+                 ON CONFLICT(session_id, org, destination) DO UPDATE SET
+                    failures       = failures + 1,
+                    fallbacks      = fallbacks + excluded.fallbacks,
+                    last_failed_at = excluded.last_failed_at",
+                params![session_id, org, destination, fell_back, a.timestamp],
+            )?;
         }
     }
+    tx.commit()
+}
 
-    // Limit to avoid overwhelming UI
-    anomalies.truncate(20);
-    Ok(anomalies)
+/// Connections to one org by address family.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OrgFamilyUsage {
+    pub org: String,
+    pub v4_connections: i64,
+    pub v6_connections: i64,
+    /// Share of connections made over IPv6, 0–1.
+    pub v6_share: f64,
+    pub v6_failures: i64,
+    pub v6_fallbacks: i64,
 }
 
-/// Network health score (0-100) for the current baseline period.
+/// A destination IPv6 attempts failed for.
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct HealthScore {
-    pub score: u32,
-    pub latency_score: u32,      // 0-25 (lower latency = higher score)
-    pub stability_score: u32,    // 0-25 (less throughput variance = higher)
-    pub diversity_score: u32,    // 0-25 (healthy protocol mix = higher)
-    pub anomaly_score: u32,      // 0-25 (fewer anomalies = higher)
-    pub details: String,
+pub struct AffectedDestination {
+    pub org: String,
+    /// Domain when the DNS observer saw one, otherwise the address.
+    pub destination: String,
+    /// Failed IPv6 attempts, or MTU probes that found the problem.
+    pub failures: i64,
+    pub fallbacks: i64,
+    pub last_failed_at: String,
 }
 
-/// Compute a network health score from the last N hours of data.
-pub fn compute_health_score(conn: &Connection, hours: u32) -> SqlResult<HealthScore> {
-    let hours = if hours == 0 { 24 } else { hours };
+/// A connectivity problem worth surfacing, in the shape of `Anomaly`'s
+/// type/severity/message.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectivityFinding {
+    /// "IPV6_FALLBACK", "IPV6_BROKEN" or "MTU_BLACKHOLE".
+    pub finding_type: String,
+    pub severity: String,
+    pub message: String,
+    pub affected: Vec<AffectedDestination>,
+}
 
-    // Check if we have any data in the time range
-    let frame_count: i64 = conn
-        .query_row(
-            "SELECT COUNT(*)
-             FROM frames f
-             JOIN sessions s ON s.id = f.session_id
-             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
-            params![hours],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IpFamilyReport {
+    pub v4_connections: i64,
+    pub v6_connections: i64,
+    pub v6_share: f64,
+    /// Busiest first.
+    pub orgs: Vec<OrgFamilyUsage>,
+    pub findings: Vec<ConnectivityFinding>,
+}
 
-    if frame_count == 0 {
-        return Ok(HealthScore {
-            score: 0,
-            latency_score: 0,
-            stability_score: 0,
-            diversity_score: 0,
-            anomaly_score: 0,
-            details: "No data available — start recording to compute health score".to_string(),
-        });
-    }
+/// IPv4/IPv6 usage per org for one session, or all sessions when `None`.
+/// An org whose IPv6 attempts fail at least `IPV6_MIN_FAILURES` times and at
+/// least as often as they succeed is reported as an IPv6 fallback.
+pub fn get_ip_family_report(conn: &Connection, session_id: Option<&str>) -> SqlResult<IpFamilyReport> {
+    let v6_share = |v4: i64, v6: i64| if v4 + v6 > 0 { round2(v6 as f64 / (v4 + v6) as f64) } else { 0.0 };
 
-    // Latency score: avg latency in last N hours → 0-25
-    let (avg_lat, _lat_var): (f64, f64) = conn
-        .query_row(
-            "SELECT COALESCE(AVG(f.latency_ms), 0),
-                    CASE WHEN COUNT(*) > 1
-                         THEN COALESCE(AVG(f.latency_ms * f.latency_ms) - AVG(f.latency_ms) * AVG(f.latency_ms), 0)
-                         ELSE 0 END
-             FROM frames f
-             JOIN sessions s ON s.id = f.session_id
-             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
-            params![hours],
-            |row| Ok((row.get::<_, f64>(0).unwrap_or(0.0), row.get::<_, f64>(1).unwrap_or(0.0))),
-        )
-        .unwrap_or((0.0, 0.0));
+    let mut stmt = conn.prepare(
+        "SELECT org, SUM(v4_connections), SUM(v6_connections), SUM(v6_failures), SUM(v6_fallbacks)
+         FROM ip_family_usage WHERE ?1 IS NULL OR session_id = ?1
+         GROUP BY org
+         ORDER BY SUM(v4_connections + v6_connections + v6_failures) DESC, org ASC",
+    )?;
+    let orgs: Vec<OrgFamilyUsage> = stmt
+        .query_map(params![session_id], |row| {
+            let (v4, v6): (i64, i64) = (row.get(1)?, row.get(2)?);
+            Ok(OrgFamilyUsage {
+                org: row.get(0)?,
+                v4_connections: v4,
+                v6_connections: v6,
+                v6_share: v6_share(v4, v6),
+                v6_failures: row.get(3)?,
+                v6_fallbacks: row.get(4)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
 
-    // Lower latency → higher score: 0ms=25, 100ms=12, 500ms+=0
-    let latency_score = if avg_lat <= 0.0 {
-        25u32
+    let mut stmt = conn.prepare(
+        "SELECT org, destination, SUM(failures), SUM(fallbacks), MAX(last_failed_at)
+         FROM ipv6_failures WHERE ?1 IS NULL OR session_id = ?1
+         GROUP BY org, destination
+         ORDER BY SUM(failures) DESC, destination ASC",
+    )?;
+    let failures: Vec<AffectedDestination> = stmt
+        .query_map(params![session_id], |row| {
+            Ok(AffectedDestination {
+                org: row.get(0)?,
+                destination: row.get(1)?,
+                failures: row.get(2)?,
+                fallbacks: row.get(3)?,
+                last_failed_at: row.get(4)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let v4_connections: i64 = orgs.iter().map(|o| o.v4_connections).sum();
+    let v6_connections: i64 = orgs.iter().map(|o| o.v6_connections).sum();
+    let total_failures: i64 = orgs.iter().map(|o| o.v6_failures).sum();
+
+    let mut findings = Vec::new();
+    if v6_connections == 0 && total_failures >= IPV6_BROKEN_FAILURES {
+        findings.push(ConnectivityFinding {
+            finding_type: "IPV6_BROKEN".to_string(),
+            severity: "high".to_string(),
+            message: format!(
+                "All {total_failures} IPv6 connection attempts failed; connections only work after falling back to IPv4"
+            ),
+            affected: failures,
+        });
     } else {
-        (25.0 * (1.0 - (avg_lat / 500.0).min(1.0))).round() as u32
-    };
+        let consistent: Vec<&OrgFamilyUsage> = orgs
+            .iter()
+            .filter(|o| o.v6_failures >= IPV6_MIN_FAILURES && o.v6_failures >= o.v6_connections)
+            .collect();
+        if !consistent.is_empty() {
+            let fallbacks: i64 = consistent.iter().map(|o| o.v6_fallbacks).sum();
+            let names: Vec<&str> = consistent.iter().take(3).map(|o| o.org.as_str()).collect();
+            let more = consistent.len().saturating_sub(names.len());
+            findings.push(ConnectivityFinding {
+                finding_type: "IPV6_FALLBACK".to_string(),
+                severity: if fallbacks > 0 { "medium" } else { "low" }.to_string(),
+                message: format!(
+                    "IPv6 keeps failing for {}{}; {fallbacks} connection(s) fell back to IPv4 after a delay",
+                    names.join(", "),
+                    if more > 0 { format!(" and {more} more") } else { String::new() },
+                ),
+                affected: failures
+                    .into_iter()
+                    .filter(|f| consistent.iter().any(|o| o.org == f.org))
+                    .collect(),
+            });
+        }
+    }
 
-    // Stability score: low coefficient of variation in bps → higher score
-    let (avg_bps, bps_var): (f64, f64) = conn
-        .query_row(
-            "SELECT COALESCE(AVG(f.bps), 0),
-                    CASE WHEN COUNT(*) > 1
-                         THEN COALESCE(AVG(f.bps * f.bps) - AVG(f.bps) * AVG(f.bps), 0)
-                         ELSE 0 END
-             FROM frames f
-             JOIN sessions s ON s.id = f.session_id
-             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
-            params![hours],
-            |row| Ok((row.get::<_, f64>(0).unwrap_or(0.0), row.get::<_, f64>(1).unwrap_or(0.0))),
-        )
-        .unwrap_or((0.0, 0.0));
+    Ok(IpFamilyReport {
+        v4_connections,
+        v6_connections,
+        v6_share: v6_share(v4_connections, v6_connections),
+        orgs,
+        findings,
+    })
+}
 
-    let cv = if avg_bps > 0.0 {
-        let raw_cv = (bps_var.max(0.0).sqrt()) / avg_bps;
-        if raw_cv.is_finite() { raw_cv } else { 0.0 }
-    } else {
-        0.0
-    };
-    // CV 0=stable=25, CV 2+=very unstable=0
-    let stability_score = (25.0 * (1.0 - (cv / 2.0).min(1.0))).round() as u32;
+// ─── Path MTU ───────────────────────────────────────────────────────────────
 
-    // Protocol diversity: ratio of unique protocols used
-    let (proto_tcp, proto_udp, proto_dns, proto_https, proto_http, proto_other) = conn
-        .query_row(
-            "SELECT COALESCE(SUM(f.proto_tcp), 0), COALESCE(SUM(f.proto_udp), 0),
-                    COALESCE(SUM(f.proto_dns), 0), COALESCE(SUM(f.proto_https), 0),
-                    COALESCE(SUM(f.proto_http), 0), COALESCE(SUM(f.proto_other), 0)
-             FROM frames f
-             JOIN sessions s ON s.id = f.session_id
-             WHERE (julianday('now') - julianday(f.timestamp)) * 24 <= ?1",
-            params![hours],
-            |row| {
-                Ok((
-                    row.get::<_, i64>(0).unwrap_or(0),
-                    row.get::<_, i64>(1).unwrap_or(0),
-                    row.get::<_, i64>(2).unwrap_or(0),
-                    row.get::<_, i64>(3).unwrap_or(0),
-                    row.get::<_, i64>(4).unwrap_or(0),
-                    row.get::<_, i64>(5).unwrap_or(0),
-                ))
-            },
-        )
-        .unwrap_or((0, 0, 0, 0, 0, 0));
+/// Record a probed path's MTU problem; a path probed again in the same
+/// session keeps its first sighting and takes the latest result.
+pub fn record_mtu_finding(conn: &Connection, session_id: &str, finding: &MtuFinding) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO mtu_findings
+            (session_id, ip, domain, org, status, path_mtu, interface, probes, first_seen, last_seen)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 1, ?8, ?8)
+         ON CONFLICT(session_id, ip) DO UPDATE SET
+            domain    = COALESCE(excluded.domain, domain),
+            org       = COALESCE(excluded.org, org),
+            status    = excluded.status,
+            path_mtu  = excluded.path_mtu,
+            interface = excluded.interface,
+            probes    = probes + 1,
+            last_seen = excluded.last_seen",
+        params![
+            session_id,
+            finding.ip,
+            finding.domain,
+            finding.org,
+            finding.status.as_str(),
+            finding.path_mtu,
+            finding.interface,
+            finding.timestamp,
+        ],
+    )?;
+    Ok(())
+}
 
-    let used_protos = [proto_tcp, proto_udp, proto_dns, proto_https, proto_http, proto_other]
-        .iter()
-        .filter(|&&v| v > 0)
-        .count();
-    // 6 protocols used = 25, 1 = ~4, 0 = 0
-    let diversity_score = if used_protos > 0 {
-        ((used_protos as f64 / 6.0) * 25.0).round() as u32
-    } else {
-        0
-    };
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MtuFindingRecord {
+    pub ip: String,
+    pub domain: Option<String>,
+    pub org: Option<String>,
+    /// "reduced" or "blackHole".
+    pub status: String,
+    pub path_mtu: i64,
+    pub interface: Option<String>,
+    pub probes: i64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
 
-    // Anomaly score: check recent sessions for anomalies
-    // Only check up to 3 most recent sessions to keep computation fast
-    let recent_sessions: Vec<String> = conn
-        .prepare(
-            "SELECT id FROM sessions
-             WHERE ended_at IS NOT NULL
-               AND (julianday('now') - julianday(started_at)) * 24 <= ?1
-             ORDER BY started_at DESC
-             LIMIT 3",
-        )?
-        .query_map(params![hours], |row| row.get::<_, String>(0))?
+/// MTU findings for a session, black holes first, then smallest MTU.
+pub fn get_mtu_findings(conn: &Connection, session_id: &str) -> SqlResult<Vec<MtuFindingRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT ip, domain, org, status, path_mtu, interface, probes, first_seen, last_seen
+         FROM mtu_findings WHERE session_id = ?1
+         ORDER BY status = 'blackHole' DESC, path_mtu ASC, ip ASC",
+    )?;
+    let rows = stmt
+        .query_map(params![session_id], |row| {
+            Ok(MtuFindingRecord {
+                ip: row.get(0)?,
+                domain: row.get(1)?,
+                org: row.get(2)?,
+                status: row.get(3)?,
+                path_mtu: row.get(4)?,
+                interface: row.get(5)?,
+                probes: row.get(6)?,
+                first_seen: row.get(7)?,
+                last_seen: row.get(8)?,
+            })
+        })?
         .filter_map(|r| r.ok())
         .collect();
+    Ok(rows)
+}
 
-    let mut total_anomalies = 0usize;
-    for sid in &recent_sessions {
-        if let Ok(anomalies) = detect_anomalies(conn, sid) {
-            total_anomalies += anomalies.iter().filter(|a| a.severity != "low").count();
-        }
-        // Early exit: if we already have enough anomalies to hit the cap (5+), skip remaining
-        if total_anomalies >= 5 {
-            break;
-        }
+/// IPv6 and MTU findings for a session's insights.
+fn get_connectivity_findings(conn: &Connection, session_id: &str) -> SqlResult<Vec<ConnectivityFinding>> {
+    let mut findings = get_ip_family_report(conn, Some(session_id))?.findings;
+    let black_holes: Vec<MtuFindingRecord> = get_mtu_findings(conn, session_id)?
+        .into_iter()
+        .filter(|f| f.status == "blackHole")
+        .collect();
+    if let Some(smallest) = black_holes.iter().map(|f| f.path_mtu).min() {
+        findings.push(ConnectivityFinding {
+            finding_type: "MTU_BLACKHOLE".to_string(),
+            severity: "high".to_string(),
+            message: format!(
+                "Packets over {smallest} bytes are silently dropped on the path to {} destination(s); \
+                 connections there can hang once they send full-size packets",
+                black_holes.len()
+            ),
+            affected: black_holes
+                .into_iter()
+                .map(|f| AffectedDestination {
+                    org: f.org.unwrap_or_else(|| "Unknown".to_string()),
+                    destination: f.domain.unwrap_or(f.ip),
+                    failures: f.probes,
+                    fallbacks: 0,
+                    last_failed_at: f.last_seen,
+                })
+                .collect(),
+        });
     }
-    // 0 anomalies=25, 5+=0
-    let anomaly_score = (25.0 * (1.0 - (total_anomalies as f64 / 5.0).min(1.0))).round() as u32;
-
-    let total = latency_score + stability_score + diversity_score + anomaly_score;
+    Ok(findings)
+}
 
-    let details = if total >= 80 {
-        "Excellent network health".to_string()
-    } else if total >= 60 {
-        "Good network health".to_string()
-    } else if total >= 40 {
-        "Fair network health — some issues detected".to_string()
-    } else {
-        "Poor network health — significant issues".to_string()
-    };
+// ─── Threat intel ───────────────────────────────────────────────────────────
 
-    Ok(HealthScore {
-        score: total,
-        latency_score,
-        stability_score,
-        diversity_score,
-        anomaly_score,
-        details,
-    })
+/// A blocklisted destination seen during a session.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreatHit {
+    /// Threat feed that lists the destination.
+    pub feed: String,
+    pub dst_ip: String,
+    pub domain: Option<String>,
+    pub dst_country: Option<String>,
+    pub dst_org: Option<String>,
+    /// Processes that talked to it.
+    pub processes: Vec<String>,
+    pub ports: Vec<i64>,
+    /// Flow snapshots that matched.
+    pub snapshots: i64,
+    pub peak_bps: f64,
+    /// First and last frame `t` the destination was seen at.
+    pub first_t: Option<f64>,
+    pub last_t: Option<f64>,
 }
 
-/// Search sessions by name, tags, or notes.
-pub fn search_sessions(
-    conn: &Connection,
-    query: &str,
-    limit: u32,
-) -> SqlResult<Vec<SessionInfo>> {
-    // Escape LIKE wildcards so user input like "%" or "_" are literal
-    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
-    let pattern = format!("%{escaped}%");
+/// Destinations of a session's flows that matched a threat feed, most seen
+/// first.
+pub fn get_threat_hits(conn: &Connection, session_id: &str) -> SqlResult<Vec<ThreatHit>> {
     let mut stmt = conn.prepare(
-        "SELECT id, name, started_at, ended_at, duration_secs,
-                total_bytes_up, total_bytes_down, total_flows,
-                peak_bps, peak_flows, avg_latency_ms,
-                local_city, local_country, local_lat, local_lng,
-                notes, tags, crash_recovered
-         FROM sessions
-         WHERE name LIKE ?1 ESCAPE '\\'
-            OR tags LIKE ?1 ESCAPE '\\'
-            OR notes LIKE ?1 ESCAPE '\\'
-         ORDER BY started_at DESC
-         LIMIT ?2",
+        "SELECT fs.threat, fs.dst_ip, MAX(fs.domain), MAX(fs.dst_country), MAX(fs.dst_org),
+                json_group_array(DISTINCT fs.process), json_group_array(DISTINCT fs.port),
+                COUNT(*), MAX(fs.bps), MIN(f.t), MAX(f.t)
+         FROM flow_snapshots fs
+         LEFT JOIN frames f ON f.id = fs.frame_id
+         WHERE fs.session_id = ?1 AND fs.threat IS NOT NULL
+         GROUP BY fs.threat, fs.dst_ip
+         ORDER BY COUNT(*) DESC, fs.dst_ip ASC",
     )?;
     let rows = stmt
-        .query_map(params![pattern, limit], |row| {
-            let ended_at: Option<String> = row.get(3)?;
-            let crash_recovered: bool = row.get::<_, i32>(17).unwrap_or(0) != 0;
-            let status = if ended_at.is_none() {
-                "recording".to_string()
-            } else if crash_recovered {
-                "crashed".to_string()
-            } else {
-                "complete".to_string()
-            };
-            Ok(SessionInfo {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                started_at: row.get(2)?,
-                ended_at,
-                duration_secs: row.get(4)?,
-                total_bytes_up: row.get::<_, f64>(5).unwrap_or(0.0),
-                total_bytes_down: row.get::<_, f64>(6).unwrap_or(0.0),
-                total_flows: row.get::<_, i64>(7).unwrap_or(0),
-                peak_bps: row.get::<_, f64>(8).unwrap_or(0.0),
-                peak_flows: row.get::<_, i64>(9).unwrap_or(0),
-                avg_latency_ms: row.get::<_, f64>(10).unwrap_or(0.0),
-                local_city: row.get::<_, String>(11).unwrap_or_default(),
-                local_country: row.get::<_, String>(12).unwrap_or_default(),
-                local_lat: row.get::<_, f64>(13).unwrap_or(0.0),
-                local_lng: row.get::<_, f64>(14).unwrap_or(0.0),
-                notes: row.get::<_, String>(15).unwrap_or_default(),
-                tags: row.get::<_, String>(16).unwrap_or_else(|_| "[]".to_string()),
-                status,
+        .query_map(params![session_id], |row| {
+            let processes: String = row.get(5)?;
+            let ports: String = row.get(6)?;
+            Ok(ThreatHit {
+                feed: row.get(0)?,
+                dst_ip: row.get(1)?,
+                domain: row.get(2)?,
+                dst_country: row.get(3)?,
+                dst_org: row.get(4)?,
+                processes: serde_json::from_str::<Vec<Option<String>>>(&processes)
+                    .map(|p| p.into_iter().flatten().collect())
+                    .unwrap_or_default(),
+                ports: serde_json::from_str::<Vec<Option<i64>>>(&ports)
+                    .map(|p| p.into_iter().flatten().collect())
+                    .unwrap_or_default(),
+                snapshots: row.get(7)?,
+                peak_bps: row.get(8)?,
+                first_t: row.get(9)?,
+                last_t: row.get(10)?,
             })
         })?
         .filter_map(|r| r.ok())
@@ -2192,18 +6949,259 @@ pub fn search_sessions(
     Ok(rows)
 }
 
-/// Update tags for a session.
-pub fn update_session_tags(conn: &Connection, session_id: &str, tags: &[String]) -> SqlResult<()> {
-    // Limit tags: max 20, each max 50 chars
-    let clamped: Vec<String> = tags
-        .iter()
-        .take(20)
-        .map(|t| if t.len() > 50 { t[..50].to_string() } else { t.clone() })
+// ─── WHOIS cache ────────────────────────────────────────────────────────────
+
+/// Cached RDAP record for `ip`, unless older than `max_age_days`.
+pub fn get_cached_whois(conn: &Connection, ip: &str, max_age_days: u32) -> SqlResult<Option<WhoisInfo>> {
+    let mut stmt = conn.prepare(
+        "SELECT info FROM whois_cache
+         WHERE ip = ?1 AND julianday('now') - julianday(fetched_at) <= ?2",
+    )?;
+    let mut rows = stmt.query(params![ip, max_age_days])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    let info: String = row.get(0)?;
+    Ok(serde_json::from_str(&info).ok())
+}
+
+pub fn store_whois(conn: &Connection, info: &WhoisInfo) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO whois_cache (ip, info, fetched_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(ip) DO UPDATE SET info = excluded.info, fetched_at = excluded.fetched_at",
+        params![
+            info.ip,
+            serde_json::to_string(info).unwrap_or_else(|_| "{}".to_string()),
+            info.fetched_at
+        ],
+    )?;
+    Ok(())
+}
+
+// ─── Data budgets ───────────────────────────────────────────────────────────
+
+/// A `data_budgets` row.
+struct BudgetRow {
+    id: i64,
+    period: QuotaPeriod,
+    process: String,
+    bytes_limit: f64,
+    period_start: String,
+    used_bytes: f64,
+    notified_pct: u8,
+}
+
+impl BudgetRow {
+    /// Counters as of `now`: zeroed once the stored period has ended.
+    fn rolled(mut self, now: chrono::DateTime<chrono::Utc>) -> Self {
+        let start = budgets::period_start(self.period, now);
+        if self.period_start != start {
+            self.period_start = start;
+            self.used_bytes = 0.0;
+            self.notified_pct = 0;
+        }
+        self
+    }
+
+    fn into_budget(self) -> DataBudget {
+        DataBudget {
+            id: self.id,
+            period: self.period,
+            scope: BudgetScope::from_process(self.process),
+            bytes_limit: self.bytes_limit,
+            period_start: self.period_start,
+            used_bytes: self.used_bytes,
+            fraction: if self.bytes_limit > 0.0 { self.used_bytes / self.bytes_limit } else { 0.0 },
+            remaining_bytes: (self.bytes_limit - self.used_bytes).max(0.0),
+            notified_pct: self.notified_pct,
+        }
+    }
+}
+
+fn budget_rows(conn: &Connection, process: Option<&str>) -> SqlResult<Vec<BudgetRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, period, process, bytes_limit, period_start, used_bytes, notified_pct
+         FROM data_budgets
+         WHERE ?1 IS NULL OR process = ?1
+         ORDER BY process, id",
+    )?;
+    let rows = stmt
+        .query_map(params![process], |row| {
+            let period: String = row.get(1)?;
+            let (id, process, bytes_limit) = (row.get(0)?, row.get(2)?, row.get(3)?);
+            let (period_start, used_bytes, notified_pct) = (row.get(4)?, row.get(5)?, row.get(6)?);
+            Ok(QuotaPeriod::parse(&period).map(|period| BudgetRow {
+                id,
+                period,
+                process,
+                bytes_limit,
+                period_start,
+                used_bytes,
+                notified_pct,
+            }))
+        })?
+        .filter_map(|r| r.ok().flatten())
         .collect();
-    let tags_json = serde_json::to_string(&clamped).unwrap_or_else(|_| "[]".to_string());
+    Ok(rows)
+}
+
+/// Bytes already recorded in `scope` since `since`, so a new budget starts
+/// from what the period has used so far.
+fn recorded_usage(conn: &Connection, scope: &BudgetScope, since: &str) -> SqlResult<f64> {
+    match scope {
+        BudgetScope::Global => bytes_since(conn, since).map(|(up, down)| up + down),
+        BudgetScope::Process { process } => conn.query_row(
+            "SELECT COALESCE(SUM(bytes_up + bytes_down), 0) FROM process_usage
+             WHERE process_name = ?1 COLLATE NOCASE AND julianday(timestamp) >= julianday(?2)",
+            params![process, since],
+            |row| row.get(0),
+        ),
+    }
+}
+
+/// Create or change the budget for `period` and `scope`; a zero limit
+/// removes it.  A changed limit re-arms thresholds the usage is now below.
+pub fn set_data_budget(
+    conn: &Connection,
+    period: QuotaPeriod,
+    scope: &BudgetScope,
+    bytes_limit: f64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> SqlResult<Option<DataBudget>> {
+    if bytes_limit <= 0.0 {
+        conn.execute(
+            "DELETE FROM data_budgets WHERE period = ?1 AND process = ?2",
+            params![period.as_str(), scope.process()],
+        )?;
+        return Ok(None);
+    }
+    let start = budgets::period_start(period, now);
+    let existing = budget_rows(conn, Some(scope.process()))?
+        .into_iter()
+        .find(|row| row.period == period);
+    let (used_bytes, notified_pct) = match existing {
+        Some(row) if row.period_start == start => {
+            (row.used_bytes, row.notified_pct.min(budgets::level(row.used_bytes, bytes_limit)))
+        }
+        _ => (recorded_usage(conn, scope, &start)?, 0),
+    };
     conn.execute(
-        "UPDATE sessions SET tags = ?1 WHERE id = ?2",
-        params![tags_json, session_id],
+        "INSERT INTO data_budgets
+            (period, process, bytes_limit, period_start, used_bytes, notified_pct, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+         ON CONFLICT(period, process) DO UPDATE SET
+            bytes_limit  = excluded.bytes_limit,
+            period_start = excluded.period_start,
+            used_bytes   = excluded.used_bytes,
+            notified_pct = excluded.notified_pct,
+            updated_at   = excluded.updated_at",
+        params![period.as_str(), scope.process(), bytes_limit, start, used_bytes, notified_pct, now.to_rfc3339()],
+    )?;
+    Ok(get_data_budgets(conn, now)?
+        .into_iter()
+        .find(|b| b.period == period && b.scope.process().eq_ignore_ascii_case(scope.process())))
+}
+
+/// Every budget with its consumption in the period containing `now`.
+pub fn get_data_budgets(conn: &Connection, now: chrono::DateTime<chrono::Utc>) -> SqlResult<Vec<DataBudget>> {
+    Ok(budget_rows(conn, None)?
+        .into_iter()
+        .map(|row| row.rolled(now).into_budget())
+        .collect())
+}
+
+/// Add `bytes` to the global budgets (`process` `None`) or those of one
+/// process, starting a new period where the last one ended.  Returns the
+/// thresholds crossed.
+pub fn add_budget_usage(
+    conn: &Connection,
+    process: Option<&str>,
+    bytes: f64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> SqlResult<Vec<BudgetEvent>> {
+    let mut events = Vec::new();
+    if bytes <= 0.0 {
+        return Ok(events);
+    }
+    for row in budget_rows(conn, Some(process.unwrap_or("")))? {
+        let mut row = row.rolled(now);
+        row.used_bytes += bytes;
+        if let Some(pct) = budgets::crossed(row.used_bytes, row.bytes_limit, row.notified_pct) {
+            row.notified_pct = pct;
+            events.push(BudgetEvent {
+                budget_id: row.id,
+                period: row.period,
+                scope: BudgetScope::from_process(row.process.clone()),
+                threshold_pct: pct,
+                used_bytes: row.used_bytes,
+                bytes_limit: row.bytes_limit,
+                period_start: row.period_start.clone(),
+            });
+        }
+        conn.execute(
+            "UPDATE data_budgets SET period_start = ?2, used_bytes = ?3, notified_pct = ?4 WHERE id = ?1",
+            params![row.id, row.period_start, row.used_bytes, row.notified_pct],
+        )?;
+    }
+    Ok(events)
+}
+
+// ─── Process metadata ───────────────────────────────────────────────────────
+
+/// Cached metadata for process `name` (case-insensitive), unless older
+/// than `max_age_days`.
+pub fn get_cached_process_metadata(conn: &Connection, name: &str, max_age_days: u32) -> SqlResult<Option<ProcessMetadata>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, exe_path, version, product_name, description, publisher,
+                signer, signed, icon_png, resolved_at
+         FROM process_meta
+         WHERE name = ?1 AND julianday('now') - julianday(resolved_at) <= ?2",
+    )?;
+    let mut rows = stmt.query(params![name, max_age_days])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    Ok(Some(ProcessMetadata {
+        name: row.get(0)?,
+        exe_path: row.get(1)?,
+        version: row.get(2)?,
+        product_name: row.get(3)?,
+        description: row.get(4)?,
+        publisher: row.get(5)?,
+        signer: row.get(6)?,
+        signed: row.get(7)?,
+        icon_png: row.get(8)?,
+        resolved_at: row.get(9)?,
+    }))
+}
+
+pub fn store_process_metadata(conn: &Connection, meta: &ProcessMetadata) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO process_meta (name, exe_path, version, product_name, description,
+                                   publisher, signer, signed, icon_png, resolved_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT(name) DO UPDATE SET
+            exe_path     = excluded.exe_path,
+            version      = excluded.version,
+            product_name = excluded.product_name,
+            description  = excluded.description,
+            publisher    = excluded.publisher,
+            signer       = excluded.signer,
+            signed       = excluded.signed,
+            icon_png     = excluded.icon_png,
+            resolved_at  = excluded.resolved_at",
+        params![
+            meta.name,
+            meta.exe_path,
+            meta.version,
+            meta.product_name,
+            meta.description,
+            meta.publisher,
+            meta.signer,
+            meta.signed,
+            meta.icon_png,
+            meta.resolved_at
+        ],
     )?;
     Ok(())
 }
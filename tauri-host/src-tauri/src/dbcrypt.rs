@@ -0,0 +1,269 @@
+use crate::error::AbyssError;
+use crate::locks::LockExt;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::Argon2;
+use rusqlite::{params, Connection, Result as SqlResult};
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Whether this build links SQLCipher (`encryption` feature).
+pub const COMPILED: bool = cfg!(feature = "encryption");
+
+/// Passphrase used when none was entered this run (start-on-login, `abyss-cli`).
+pub const PASSPHRASE_ENV: &str = "ABYSS_DB_PASSPHRASE";
+
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// First bytes of every plaintext SQLite file; SQLCipher files start with
+/// their random salt instead.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Raw SQLCipher key and salt (`x'…'`) for the encrypted database, once
+/// unlocked or set.
+static KEY: Mutex<Option<String>> = Mutex::new(None);
+
+// ─── Status ─────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionStatus {
+    /// Built with SQLCipher; otherwise encryption can't be turned on.
+    pub compiled: bool,
+    pub encrypted: bool,
+    /// A passphrase is available for the encrypted database.
+    pub unlocked: bool,
+}
+
+pub fn status(db_path: &Path) -> EncryptionStatus {
+    EncryptionStatus {
+        compiled: COMPILED,
+        encrypted: is_encrypted(db_path),
+        unlocked: key(db_path).is_some(),
+    }
+}
+
+/// The file exists and isn't a plaintext SQLite database.
+pub fn is_encrypted(db_path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(db_path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        // Missing or shorter than a header: a database yet to be created
+        Err(_) => false,
+    }
+}
+
+/// The database is encrypted and no passphrase has been given.
+pub fn is_locked(db_path: &Path) -> bool {
+    is_encrypted(db_path) && key(db_path).is_none()
+}
+
+/// The key given this run, else the one derived from `PASSPHRASE_ENV`.
+fn key(db_path: &Path) -> Option<String> {
+    let mut key = KEY.lock_or_recover("db_key");
+    if key.is_none() && COMPILED && is_encrypted(db_path) {
+        let passphrase = std::env::var(PASSPHRASE_ENV).ok()?;
+        match derive_key(&passphrase, &read_salt(db_path).ok()?) {
+            Ok(derived) => *key = Some(derived),
+            Err(e) => eprintln!("[Abyss] {PASSPHRASE_ENV} not usable: {e}"),
+        }
+    }
+    key.clone()
+}
+
+/// Give a freshly opened connection the key, before anything reads the
+/// file.  Plaintext databases are left alone.
+pub fn apply_key(conn: &Connection, db_path: &Path) -> SqlResult<()> {
+    if let (true, Some(key)) = (is_encrypted(db_path), key(db_path)) {
+        conn.pragma_update(None, "key", key)?;
+    }
+    Ok(())
+}
+
+// ─── Key derivation ─────────────────────────────────────────────────────────
+
+/// SQLCipher raw key for `passphrase`: Argon2id (default parameters) over
+/// `salt`, so SQLCipher's own PBKDF2 is skipped.  The key is given in the
+/// raw key-and-salt form, which makes SQLCipher write `salt` as the file's
+/// header: the salt travels with the data and nothing beside the database
+/// is needed to open it.
+fn derive_key(passphrase: &str, salt: &[u8; 16]) -> Result<String, AbyssError> {
+    let mut raw = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut raw)
+        .map_err(|e| AbyssError::Internal(format!("Key derivation failed: {e}")))?;
+    let hex: String = raw.iter().chain(salt).map(|b| format!("{b:02x}")).collect();
+    Ok(format!("x'{hex}'"))
+}
+
+/// The salt an encrypted database starts with.
+fn read_salt(db_path: &Path) -> Result<[u8; 16], AbyssError> {
+    let mut salt = [0u8; 16];
+    std::fs::File::open(db_path)
+        .and_then(|mut f| f.read_exact(&mut salt))
+        .map_err(|e| AbyssError::from(e).context("Failed to read the database salt"))?;
+    Ok(salt)
+}
+
+// ─── Unlock and migration ───────────────────────────────────────────────────
+
+fn require_compiled() -> Result<(), AbyssError> {
+    if COMPILED {
+        Ok(())
+    } else {
+        Err(AbyssError::InvalidInput(
+            "This build has no database encryption (built without the `encryption` feature)".into(),
+        ))
+    }
+}
+
+/// Check `passphrase` against the encrypted database and keep it for every
+/// connection opened from now on.
+pub fn unlock(db_path: &Path, passphrase: &str) -> Result<(), AbyssError> {
+    require_compiled()?;
+    if !is_encrypted(db_path) {
+        return Err(AbyssError::Conflict("The database is not encrypted".into()));
+    }
+    let key = derive_key(passphrase, &read_salt(db_path)?)?;
+    let conn = Connection::open(db_path)?;
+    conn.pragma_update(None, "key", &key)?;
+    // SQLCipher only notices a wrong key on the first read
+    if conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)).is_err() {
+        return Err(AbyssError::InvalidInput("Wrong passphrase".into()));
+    }
+    *KEY.lock_or_recover("db_key") = Some(key);
+    Ok(())
+}
+
+/// Rewrite the database encrypted with `passphrase` (or as plaintext for
+/// `None`) via `sqlcipher_export`, which handles turning encryption on for
+/// an existing plaintext database, changing the passphrase and turning it
+/// off alike.  Every passphrase gets a fresh salt.  The writer must be
+/// paused.
+pub fn set_encryption(db_path: &Path, passphrase: Option<&str>) -> Result<(), AbyssError> {
+    require_compiled()?;
+    if passphrase.is_some_and(|p| p.chars().count() < MIN_PASSPHRASE_LEN) {
+        return Err(AbyssError::InvalidInput(format!(
+            "Passphrase must be at least {MIN_PASSPHRASE_LEN} characters"
+        )));
+    }
+    if is_locked(db_path) {
+        return Err(AbyssError::Conflict("Unlock the database before changing its encryption".into()));
+    }
+    let key = match passphrase {
+        Some(passphrase) => {
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            Some(derive_key(passphrase, &salt)?)
+        }
+        None => None,
+    };
+    let target = sibling(db_path, ".rekey");
+    let _ = std::fs::remove_file(&target);
+    {
+        let conn = Connection::open(db_path)?;
+        apply_key(&conn, db_path)?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS target KEY ?2",
+            params![target.to_string_lossy(), key.as_deref().unwrap_or("")],
+        )?;
+        let exported = conn
+            .query_row("SELECT sqlcipher_export('target')", [], |_| Ok(()))
+            .and_then(|_| conn.execute_batch(&format!("PRAGMA target.user_version = {version}")))
+            .and_then(|_| conn.execute_batch("DETACH DATABASE target"));
+        if let Err(e) = exported {
+            let _ = std::fs::remove_file(&target);
+            return Err(AbyssError::from(e).context("Failed to re-encrypt database"));
+        }
+    }
+
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(sibling(db_path, suffix));
+    }
+    std::fs::rename(&target, db_path).map_err(|e| AbyssError::from(e).context("Failed to replace database"))?;
+    *KEY.lock_or_recover("db_key") = key;
+    Ok(())
+}
+
+fn sibling(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("abyss-dbcrypt-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("sessions.db")
+    }
+
+    #[test]
+    fn tells_encrypted_files_by_their_header() {
+        let db = temp_db("header");
+        assert!(!is_encrypted(&db));
+        crate::db::open_database(&db).unwrap();
+        assert!(!is_encrypted(&db));
+        std::fs::write(&db, [0x5a; 64]).unwrap();
+        assert!(is_encrypted(&db));
+        if !COMPILED {
+            assert!(set_encryption(&db, Some("correct horse")).is_err());
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypts_an_existing_database_and_back() {
+        let db = temp_db("migrate");
+        {
+            let conn = crate::db::open_database(&db).unwrap();
+            crate::db::set_setting(&conn, "units", "\"iec\"").unwrap();
+        }
+        set_encryption(&db, Some("correct horse")).unwrap();
+        assert!(is_encrypted(&db));
+        // The salt is the file's own header
+        let salt: String = read_salt(&db).unwrap().iter().map(|b| format!("{b:02x}")).collect();
+        assert!(KEY
+            .lock()
+            .unwrap()
+            .as_deref()
+            .is_some_and(|key| key.starts_with("x'") && key.ends_with(&format!("{salt}'"))));
+        let conn = crate::db::open_database(&db).unwrap();
+        assert_eq!(crate::db::get_settings(&conn).unwrap().len(), 1);
+        drop(conn);
+
+        // The file alone unlocks, as a backup or after a crash would
+        *KEY.lock().unwrap() = None;
+        let copy = temp_db("migrate-copy");
+        std::fs::copy(&db, &copy).unwrap();
+        assert!(unlock(&copy, "wrong horse").is_err());
+        unlock(&copy, "correct horse").unwrap();
+        assert_eq!(crate::db::get_settings(&crate::db::open_database(&copy).unwrap()).unwrap().len(), 1);
+
+        set_encryption(&db, None).unwrap();
+        assert!(!is_encrypted(&db));
+        assert_eq!(crate::db::get_settings(&crate::db::open_database(&db).unwrap()).unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn failed_rewrite_leaves_the_database_as_it_was() {
+        let db = temp_db("failed");
+        {
+            let conn = crate::db::open_database(&db).unwrap();
+            crate::db::set_setting(&conn, "units", "\"iec\"").unwrap();
+        }
+        // The re-encrypted copy can't be written
+        std::fs::create_dir_all(sibling(&db, ".rekey")).unwrap();
+        assert!(set_encryption(&db, Some("correct horse")).is_err());
+        assert!(!is_encrypted(&db));
+        assert_eq!(crate::db::get_settings(&crate::db::open_database(&db).unwrap()).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,346 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often a waiting writer re-checks the lock.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the holder refreshes `heartbeatAt`.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A lock whose holder hasn't refreshed it for this long is taken over even
+/// if the PID is still running (hung writer, or the PID was reused).
+const STALE_AFTER_SECS: i64 = 60;
+
+// ─── Lock file ──────────────────────────────────────────────────────────────
+
+/// Contents of `sessions.db.lock`: which instance writes to the database.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LockInfo {
+    pid: u32,
+    acquired_at: String,
+    heartbeat_at: String,
+}
+
+impl LockInfo {
+    fn is_stale(&self) -> bool {
+        if !process_alive(self.pid) {
+            return true;
+        }
+        DateTime::parse_from_rfc3339(&self.heartbeat_at)
+            .map(|at| (Utc::now() - at.with_timezone(&Utc)).num_seconds() > STALE_AFTER_SECS)
+            .unwrap_or(true)
+    }
+}
+
+fn lock_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+/// The lock file while this instance holds it; removed on drop.
+struct DbLock {
+    path: PathBuf,
+    info: LockInfo,
+    last_heartbeat: Instant,
+}
+
+impl DbLock {
+    fn write(&self) -> std::io::Result<()> {
+        let json = serde_json::to_vec(&self.info).map_err(std::io::Error::other)?;
+        std::fs::write(&self.path, json)
+    }
+
+    fn heartbeat(&mut self) {
+        if self.last_heartbeat.elapsed() < HEARTBEAT_INTERVAL {
+            return;
+        }
+        self.last_heartbeat = Instant::now();
+        self.info.heartbeat_at = Utc::now().to_rfc3339();
+        if let Err(e) = self.write() {
+            eprintln!("[Abyss][writer] Failed to refresh database lock: {e}");
+        }
+    }
+}
+
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        // Only remove the file if it is still ours
+        let ours = std::fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<LockInfo>(&bytes).ok())
+            .is_some_and(|info| info.pid == self.info.pid);
+        if ours {
+            let _ = std::fs::remove_file(&self.path);
+            println!("[Abyss][writer] Released database lock");
+        }
+    }
+}
+
+/// Who holds a lock we couldn't acquire.
+enum Holder {
+    /// A live instance (PID from the lock file).
+    Instance(u32),
+    /// The lock file exists but couldn't be read (being written).
+    Unknown,
+}
+
+/// Create the lock file, taking over a stale one.
+fn acquire(db_path: &Path) -> Result<(DbLock, Option<u32>), Holder> {
+    let path = lock_path(db_path);
+    let mut took_over_from = None;
+    if let Ok(bytes) = std::fs::read(&path) {
+        match serde_json::from_slice::<LockInfo>(&bytes) {
+            Ok(info) if info.pid == std::process::id() => {}
+            Ok(info) if !info.is_stale() => return Err(Holder::Instance(info.pid)),
+            Ok(info) => took_over_from = Some(info.pid),
+            // A truncated file from a crash mid-heartbeat is stale once it stops changing
+            Err(_) => {
+                let fresh = std::fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|at| at.elapsed().ok())
+                    .is_some_and(|age| age.as_secs() as i64 <= STALE_AFTER_SECS);
+                if fresh {
+                    return Err(Holder::Unknown);
+                }
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let lock = DbLock {
+        path: path.clone(),
+        info: LockInfo {
+            pid: std::process::id(),
+            acquired_at: now.clone(),
+            heartbeat_at: now,
+        },
+        last_heartbeat: Instant::now(),
+    };
+    // `create_new` so two instances starting at once can't both win
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|_| Holder::Unknown)?;
+    let json = serde_json::to_vec(&lock.info).unwrap_or_default();
+    if let Err(e) = file.write_all(&json) {
+        eprintln!("[Abyss][writer] Failed to write database lock: {e}");
+    }
+    Ok((lock, took_over_from))
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks the process exists; EPERM means it does but isn't ours
+    unsafe { libc::kill(pid, 0) == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+        let mut code = 0u32;
+        let ok = GetExitCodeProcess(handle, &mut code);
+        CloseHandle(handle);
+        ok == 0 || code == STILL_ACTIVE as u32
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn process_alive(_pid: u32) -> bool {
+    // Fall back on the heartbeat alone
+    true
+}
+
+// ─── Takeover ───────────────────────────────────────────────────────────────
+
+/// Whether the writer may use the database; payload of `db-lock-status`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum DbLockStatus {
+    /// The writer hasn't checked yet.
+    Pending,
+    /// Another instance (usually the one an update is replacing) still has
+    /// the database; session commands are queued and frames dropped until
+    /// it lets go.
+    Waiting {
+        /// `None` when the holder is unknown: the lock file is mid-write, or
+        /// an older build without lock files still has the database busy.
+        holder_pid: Option<u32>,
+        since: String,
+        attempts: u32,
+        reason: String,
+    },
+    /// This instance owns the database.
+    Held {
+        since: String,
+        /// PID of a crashed or hung instance whose stale lock was replaced.
+        took_over_from: Option<u32>,
+    },
+}
+
+/// Callback invoked with every lock status change.
+pub type LockSink = Box<dyn Fn(DbLockStatus) + Send>;
+
+/// Coordinates which instance writes to a database: the writer waits and
+/// retries until the lock file is free (or stale) before opening it, so a
+/// freshly updated instance never writes alongside the one it replaces.
+pub struct Takeover {
+    db_path: PathBuf,
+    lock: Option<DbLock>,
+    waiting_since: Option<String>,
+    attempts: u32,
+    on_status: LockSink,
+}
+
+impl Takeover {
+    pub fn new(db_path: &Path, on_status: LockSink) -> Self {
+        Self {
+            db_path: db_path.to_path_buf(),
+            lock: None,
+            waiting_since: None,
+            attempts: 0,
+            on_status,
+        }
+    }
+
+    /// Acquire the lock if we don't have it yet, or refresh its heartbeat
+    /// if we do.  Returns whether it is held.
+    pub fn try_acquire(&mut self) -> bool {
+        if let Some(lock) = &mut self.lock {
+            lock.heartbeat();
+            return true;
+        }
+        match acquire(&self.db_path) {
+            Ok((lock, took_over_from)) => {
+                match took_over_from {
+                    Some(pid) => println!("[Abyss][writer] Took over stale database lock from PID {pid}"),
+                    None => println!("[Abyss][writer] Acquired database lock"),
+                }
+                self.lock = Some(lock);
+                self.waiting_since = None;
+                self.attempts = 0;
+                (self.on_status)(DbLockStatus::Held {
+                    since: Utc::now().to_rfc3339(),
+                    took_over_from,
+                });
+                true
+            }
+            Err(Holder::Instance(pid)) => {
+                self.wait(Some(pid), format!("Another Abyss instance (PID {pid}) is still using the database"));
+                false
+            }
+            Err(Holder::Unknown) => {
+                self.wait(None, "The database lock is being updated by another instance".into());
+                false
+            }
+        }
+    }
+
+    /// The lock is ours but the database itself is busy (an older build
+    /// still writing); keep retrying rather than failing.
+    pub fn database_busy(&mut self, reason: String) {
+        self.wait(None, reason);
+    }
+
+    /// Forget `path`'s lock and move to another database file.
+    pub fn switch(&mut self, db_path: &Path) {
+        if self.db_path != db_path {
+            self.lock = None;
+            self.db_path = db_path.to_path_buf();
+        }
+    }
+
+    /// How long the writer may block for its next command.
+    pub fn wake_interval(&self, idle: Duration) -> Duration {
+        match self.lock {
+            Some(_) => idle.min(HEARTBEAT_INTERVAL),
+            None => RETRY_INTERVAL,
+        }
+    }
+
+    fn wait(&mut self, holder_pid: Option<u32>, reason: String) {
+        self.attempts += 1;
+        let since = self.waiting_since.get_or_insert_with(|| Utc::now().to_rfc3339()).clone();
+        if self.attempts == 1 {
+            println!("[Abyss][writer] Waiting for the database: {reason}");
+        }
+        (self.on_status)(DbLockStatus::Waiting {
+            holder_pid,
+            since,
+            attempts: self.attempts,
+            reason,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn takeover(db_path: &Path) -> (Takeover, Arc<Mutex<Vec<DbLockStatus>>>) {
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let sink = statuses.clone();
+        let takeover = Takeover::new(db_path, Box::new(move |s| sink.lock().unwrap().push(s)));
+        (takeover, statuses)
+    }
+
+    fn temp_db(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("abyss-dblock-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("sessions.db");
+        let _ = std::fs::remove_file(lock_path(&db));
+        db
+    }
+
+    #[test]
+    fn waits_for_a_live_holder_and_takes_over_stale_locks() {
+        let db = temp_db("holder");
+        // PID 1 always exists on unix; on other platforms the heartbeat decides
+        let info = LockInfo {
+            pid: 1,
+            acquired_at: Utc::now().to_rfc3339(),
+            heartbeat_at: Utc::now().to_rfc3339(),
+        };
+        std::fs::write(lock_path(&db), serde_json::to_vec(&info).unwrap()).unwrap();
+
+        let (mut takeover, statuses) = takeover(&db);
+        assert!(!takeover.try_acquire());
+        assert!(matches!(
+            statuses.lock().unwrap().last(),
+            Some(DbLockStatus::Waiting { holder_pid: Some(1), attempts: 1, .. })
+        ));
+
+        let stale = LockInfo {
+            heartbeat_at: (Utc::now() - chrono::Duration::seconds(STALE_AFTER_SECS + 5)).to_rfc3339(),
+            ..info
+        };
+        std::fs::write(lock_path(&db), serde_json::to_vec(&stale).unwrap()).unwrap();
+        assert!(takeover.try_acquire());
+        assert!(matches!(
+            statuses.lock().unwrap().last(),
+            Some(DbLockStatus::Held { took_over_from: Some(1), .. })
+        ));
+
+        drop(takeover);
+        assert!(!lock_path(&db).exists());
+    }
+}
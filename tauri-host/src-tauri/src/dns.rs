@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, Instant};
+
+/// Seconds between reads of the OS resolver cache.
+const CACHE_READ_INTERVAL_SECS: u64 = 30;
+/// Resolutions are kept this long after last being seen.  Much longer than
+/// typical record TTLs: connections outlive the answer that opened them.
+pub const DNS_RETAIN_SECS: u64 = 6 * 3600;
+/// An unchanged mapping is re-persisted at most this often (refreshes `last_seen`).
+const PERSIST_REFRESH_SECS: u64 = 600;
+/// Compression pointers followed per name before it's treated as malformed.
+const MAX_NAME_JUMPS: usize = 16;
+
+// ─── Answers ────────────────────────────────────────────────────────────────
+
+/// One address the system resolved a domain to.
+#[derive(Clone, Debug)]
+pub struct DnsAnswer {
+    pub ip: String,
+    pub domain: String,
+    pub ttl: u32,
+    /// Where it was observed: `capture` (DNS responses on the wire) or
+    /// `cache` (the OS resolver cache).
+    pub source: &'static str,
+}
+
+/// A/AAAA answers in a DNS response, attributed to the queried name (what the
+/// application asked for, rather than the CDN name at the end of a CNAME
+/// chain).  Malformed or failed responses yield nothing.
+#[cfg_attr(not(feature = "capture"), allow(dead_code))]
+pub fn parse_response(msg: &[u8]) -> Vec<DnsAnswer> {
+    parse_message(msg).unwrap_or_default()
+}
+
+fn parse_message(msg: &[u8]) -> Option<Vec<DnsAnswer>> {
+    let flags = read_u16(msg, 2)?;
+    // Responses only (QR set), and only successful ones (RCODE 0)
+    if flags & 0x8000 == 0 || flags & 0x000f != 0 {
+        return None;
+    }
+    let questions = read_u16(msg, 4)?;
+    let answer_count = read_u16(msg, 6)?;
+
+    let mut pos = 12;
+    let mut queried: Option<String> = None;
+    for _ in 0..questions {
+        let (name, next) = read_name(msg, pos)?;
+        pos = next + 4; // QTYPE, QCLASS
+        queried.get_or_insert(name);
+    }
+
+    let mut answers = Vec::new();
+    for _ in 0..answer_count {
+        let (owner, next) = read_name(msg, pos)?;
+        let rtype = read_u16(msg, next)?;
+        let ttl = u32::from_be_bytes(msg.get(next + 4..next + 8)?.try_into().ok()?);
+        let len = read_u16(msg, next + 8)? as usize;
+        let data = msg.get(next + 10..next + 10 + len)?;
+        pos = next + 10 + len;
+
+        let ip = match (rtype, data.len()) {
+            (1, 4) => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(data).ok()?)),
+            (28, 16) => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?)),
+            _ => continue,
+        };
+        let domain = queried.clone().unwrap_or(owner);
+        if domain.is_empty() {
+            continue;
+        }
+        answers.push(DnsAnswer {
+            ip: crate::connections::format_ip(ip),
+            domain,
+            ttl,
+            source: "capture",
+        });
+    }
+    Some(answers)
+}
+
+fn read_u16(msg: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(msg.get(at..at + 2)?.try_into().ok()?))
+}
+
+/// Decode a (possibly compressed) name at `pos`.  Returns the lowercased name
+/// and the offset just past it in the original record.
+fn read_name(msg: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+    loop {
+        let len = *msg.get(pos)? as usize;
+        match len {
+            0 => break,
+            l if l & 0xc0 == 0xc0 => {
+                jumps += 1;
+                if jumps > MAX_NAME_JUMPS {
+                    return None;
+                }
+                end.get_or_insert(pos + 2);
+                pos = (read_u16(msg, pos)? & 0x3fff) as usize;
+            }
+            l if l & 0xc0 == 0 => {
+                let label = msg.get(pos + 1..pos + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                pos += 1 + l;
+            }
+            _ => return None,
+        }
+    }
+    Some((labels.join("."), end.unwrap_or(pos + 1)))
+}
+
+// ─── Observer ───────────────────────────────────────────────────────────────
+
+struct Resolution {
+    domain: String,
+    seen: Instant,
+    persisted: Option<Instant>,
+}
+
+/// Remote IP → the domain the system most recently resolved to it.  Fed by
+/// packet capture (when running) and periodic reads of the OS resolver
+/// cache (Windows); other platforms need packet capture to see lookups.
+#[derive(Default)]
+pub struct DnsObserver {
+    resolved: HashMap<String, Resolution>,
+    cache_task: Option<tokio::task::JoinHandle<Vec<DnsAnswer>>>,
+    last_cache_read: Option<Instant>,
+}
+
+impl DnsObserver {
+    /// Pre-populate from persisted mappings (already in the database).
+    pub fn seed(&mut self, mappings: Vec<(String, String)>) {
+        let now = Instant::now();
+        for (ip, domain) in mappings {
+            self.resolved.insert(
+                ip,
+                Resolution {
+                    domain,
+                    seen: now,
+                    persisted: Some(now),
+                },
+            );
+        }
+    }
+
+    /// Fold in `captured` answers and any finished cache read, start the next
+    /// cache read when due, and return the mappings that need persisting.
+    pub async fn tick(&mut self, captured: Vec<DnsAnswer>) -> Vec<DnsAnswer> {
+        let mut answers = captured;
+        if self.cache_task.as_ref().is_some_and(|t| t.is_finished()) {
+            if let Some(task) = self.cache_task.take() {
+                answers.extend(task.await.unwrap_or_default());
+            }
+        }
+        let due = self
+            .last_cache_read
+            .is_none_or(|at| at.elapsed() >= Duration::from_secs(CACHE_READ_INTERVAL_SECS));
+        if cfg!(windows) && self.cache_task.is_none() && due {
+            self.last_cache_read = Some(Instant::now());
+            self.cache_task = Some(tokio::task::spawn_blocking(read_os_cache));
+        }
+
+        let now = Instant::now();
+        let refresh = Duration::from_secs(PERSIST_REFRESH_SECS);
+        let mut changed = Vec::new();
+        for answer in answers {
+            let entry = self.resolved.entry(answer.ip.clone()).or_insert(Resolution {
+                domain: String::new(),
+                seen: now,
+                persisted: None,
+            });
+            entry.seen = now;
+            let stale = entry.persisted.is_none_or(|at| at.elapsed() >= refresh);
+            if entry.domain != answer.domain || stale {
+                entry.domain.clone_from(&answer.domain);
+                entry.persisted = Some(now);
+                changed.push(answer);
+            }
+        }
+        let retain = Duration::from_secs(DNS_RETAIN_SECS);
+        self.resolved.retain(|_, r| r.seen.elapsed() < retain);
+        changed
+    }
+
+    pub fn domain(&self, ip: &str) -> Option<&str> {
+        self.resolved.get(ip).map(|r| r.domain.as_str())
+    }
+}
+
+// ─── OS resolver cache ──────────────────────────────────────────────────────
+
+/// Parse `ipconfig /displaydns`.  Each entry is headed by the queried name
+/// over a dashed rule; any field whose value is an address is an answer.
+/// Field labels are localized, so only that structure is relied on.
+#[cfg(windows)]
+fn read_os_cache() -> Vec<DnsAnswer> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let Ok(output) = std::process::Command::new("ipconfig")
+        .arg("/displaydns")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+    else {
+        return Vec::new();
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    let mut answers = Vec::new();
+    let mut current: Option<String> = None;
+    let mut previous = "";
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("----") {
+            current = Some(previous.trim_end_matches('.').to_ascii_lowercase()).filter(|n| !n.is_empty());
+        } else if let (Some(domain), Some((_, value))) = (&current, line.split_once(": ")) {
+            if let Ok(ip) = value.trim().parse::<IpAddr>() {
+                answers.push(DnsAnswer {
+                    ip: crate::connections::format_ip(ip),
+                    domain: domain.clone(),
+                    ttl: 0,
+                    source: "cache",
+                });
+            }
+        }
+        previous = line;
+    }
+    answers
+}
+
+/// No unprivileged way to read the resolver cache here (systemd-resolved
+/// and mDNSResponder don't expose theirs); lookups are only seen through
+/// packet capture.
+#[cfg(not(windows))]
+fn read_os_cache() -> Vec<DnsAnswer> {
+    Vec::new()
+}
@@ -0,0 +1,380 @@
+use crate::containers::ContainerIndex;
+use crate::dns::DnsObserver;
+use crate::threat::ThreatIndex;
+use crate::watchlist::{WatchedFlow, Watchlist};
+use crate::{
+    get_geo_cached, probe, protocol_code, service_code, FlowRate, GeoCacheEntry, GeoEndpoint, GeoFlow, LocalGeo,
+    ParsedConnection, PerfStats,
+};
+use std::collections::HashMap;
+use std::time::Instant;
+
+// ─── Pipeline ───────────────────────────────────────────────────────────────
+
+/// One step in turning a connection into a `GeoFlow`.  Stages run in order
+/// for every flow in a frame; any stage can drop the flow.
+pub trait Enricher: Send {
+    /// Short label for per-stage timings in `PerfStats`.
+    fn name(&self) -> &'static str;
+    /// Fill in this stage's fields.  Returns false to leave the flow out of
+    /// the frame entirely (it isn't counted in the frame totals either).
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool;
+}
+
+/// A flow on its way through the pipeline.  Stages fill in `flow`; raw
+/// (unrounded) measurements are kept alongside for the frame totals.
+pub struct FlowDraft<'c> {
+    /// `remote_ip:port:proto`, as keyed in `FlowRate` maps.
+    pub key: &'c str,
+    pub key_hash: u32,
+    pub conn: &'c ParsedConnection,
+    /// Present in the previous frame.
+    pub existed: bool,
+    pub measured: Option<FlowRate>,
+    pub raw_bps: f64,
+    pub measured_rtt_ms: Option<f64>,
+    pub flow: GeoFlow,
+}
+
+impl<'c> FlowDraft<'c> {
+    /// A flow with only its identity filled in.
+    pub fn new(key: &'c str, conn: &'c ParsedConnection, local: &LocalGeo, existed: bool) -> Self {
+        let mut key_hash: u32 = 2_166_136_261;
+        for b in key.bytes() {
+            key_hash ^= b as u32;
+            key_hash = key_hash.wrapping_mul(16_777_619);
+        }
+        Self {
+            key,
+            key_hash,
+            conn,
+            existed,
+            measured: None,
+            raw_bps: 0.0,
+            measured_rtt_ms: None,
+            flow: GeoFlow {
+                id: format!("live-{key}"),
+                src: GeoEndpoint {
+                    ip: conn.local_ip.clone(),
+                    lat: local.lat,
+                    lng: local.lng,
+                    city: local.city.clone(),
+                    country: local.country.clone(),
+                    asn: None,
+                    org: None,
+                },
+                dst: GeoEndpoint {
+                    ip: conn.remote_ip.clone(),
+                    lat: 0.0,
+                    lng: 0.0,
+                    city: String::new(),
+                    country: String::new(),
+                    asn: None,
+                    org: None,
+                },
+                bps: 0.0,
+                pps: 0,
+                rtt: 0.0,
+                protocol: protocol_code(&conn.proto),
+                dir: "bidi".to_string(),
+                port: conn.remote_port,
+                service: None,
+                started_at: 0.0,
+                process: None,
+                pid: if conn.pid > 0 { Some(conn.pid) } else { None },
+                state: if !conn.state.is_empty() && conn.state != "STATELESS" {
+                    Some(conn.state.clone())
+                } else {
+                    None
+                },
+                tx_bps: None,
+                rx_bps: None,
+                rtt_method: None,
+                domain: None,
+                sni: conn.sni.clone(),
+                service_class: None,
+                watch: None,
+                threat: None,
+                app_group: None,
+                container: None,
+            },
+        }
+    }
+}
+
+/// Per-tick inputs the stages read (and the geo cache they touch).
+pub struct TickContext<'a> {
+    pub geo_cache: &'a mut HashMap<String, GeoCacheEntry>,
+    pub process_names: &'a HashMap<u32, String>,
+    pub app_groups: &'a HashMap<u32, String>,
+    pub containers: &'a ContainerIndex,
+    pub rates: &'a HashMap<String, FlowRate>,
+    pub rtts: &'a HashMap<String, probe::RttSample>,
+    pub dns: &'a DnsObserver,
+    pub watchlist: &'a Watchlist,
+    /// Flows the `Watch` stage tagged this tick.
+    pub watched: Vec<WatchedFlow>,
+    pub threats: &'a ThreatIndex,
+    pub perf: &'a mut PerfStats,
+}
+
+/// The ordered stages `build_frame` runs each flow through.
+pub struct Pipeline {
+    stages: Vec<Box<dyn Enricher>>,
+}
+
+impl Default for Pipeline {
+    /// Geo first (flows without a location are dropped before anything else
+    /// runs), then measurements, then labels.
+    fn default() -> Self {
+        Self {
+            stages: vec![
+                Box::new(Geo),
+                Box::new(Throughput),
+                Box::new(Latency),
+                Box::new(Process),
+                Box::new(Container),
+                Box::new(Service),
+                Box::new(Domain),
+                Box::new(Threat),
+                Box::new(Watch),
+            ],
+        }
+    }
+}
+
+impl Pipeline {
+    /// Run every stage over `draft`, timing each.  False if a stage dropped it.
+    pub fn run(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        for stage in &self.stages {
+            let started = Instant::now();
+            let keep = stage.enrich(draft, ctx);
+            ctx.perf.record_stage(stage.name(), started.elapsed().as_secs_f64() * 1000.0);
+            if !keep {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+// ─── Stages ─────────────────────────────────────────────────────────────────
+
+/// Destination location from the geo cache; unresolved flows are dropped
+/// until a lookup lands.
+struct Geo;
+
+impl Enricher for Geo {
+    fn name(&self) -> &'static str {
+        "geo"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        let Some(geo) = get_geo_cached(ctx.geo_cache, &draft.conn.remote_ip, ctx.perf) else {
+            return false;
+        };
+        let dst = &mut draft.flow.dst;
+        dst.lat = round2(geo.lat);
+        dst.lng = round2(geo.lng);
+        dst.city = geo.city.clone();
+        dst.country = geo.country.clone();
+        dst.asn = if !geo.asn.is_empty() { Some(geo.asn.clone()) } else { None };
+        dst.org = if !geo.org.is_empty() { Some(geo.org.clone()) } else { None };
+        true
+    }
+}
+
+/// Rate, direction and packet rate: from byte counters when the OS has them,
+/// otherwise a stable per-flow estimate from the port.
+struct Throughput;
+
+impl Enricher for Throughput {
+    fn name(&self) -> &'static str {
+        "throughput"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        let conn = draft.conn;
+        // Real counters when available; a socket that was measurable but has
+        // no rate (kept visible during the grace period) is idle, not unknown.
+        let measured = ctx
+            .rates
+            .get(draft.key)
+            .copied()
+            .or_else(|| conn.bytes_out.map(|_| FlowRate::default()));
+
+        let (flow_bps, dir) = match measured {
+            Some(rate) => {
+                let dir = if rate.tx_bps > rate.rx_bps * 2.0 {
+                    "up"
+                } else if rate.rx_bps > rate.tx_bps * 2.0 {
+                    "down"
+                } else {
+                    "bidi"
+                };
+                (rate.tx_bps + rate.rx_bps, dir)
+            }
+            None => {
+                let base_bps: f64 = match conn.remote_port {
+                    443 => 50_000.0,
+                    80 => 30_000.0,
+                    53 => 500.0,
+                    22 => 5_000.0,
+                    _ => 10_000.0,
+                };
+
+                let bps_factor = if draft.existed {
+                    0.7 + (draft.key_hash % 60) as f64 / 100.0
+                } else {
+                    2.0
+                };
+
+                let dir = if conn.state == "ESTABLISHED" || conn.state == "STATELESS" {
+                    if draft.key_hash.is_multiple_of(2) {
+                        "up"
+                    } else {
+                        "down"
+                    }
+                } else {
+                    "bidi"
+                };
+                (base_bps * bps_factor, dir)
+            }
+        };
+
+        draft.measured = measured;
+        draft.raw_bps = flow_bps;
+        let flow = &mut draft.flow;
+        flow.bps = (flow_bps / 10.0).round() * 10.0;
+        flow.dir = dir.to_string();
+        flow.pps = match measured.and_then(|r| r.pps) {
+            Some(pps) => pps.round() as u32,
+            None => (flow_bps / 1000.0).max(1.0) as u32,
+        };
+        flow.tx_bps = measured.map(|r| r.tx_bps.round());
+        flow.rx_bps = measured.map(|r| r.rx_bps.round());
+        true
+    }
+}
+
+/// Probed RTT when the prober has one for the destination, else an estimate.
+struct Latency;
+
+impl Enricher for Latency {
+    fn name(&self) -> &'static str {
+        "latency"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        let sample = ctx.rtts.get(&draft.conn.remote_ip);
+        draft.measured_rtt_ms = sample.map(|s| s.ms);
+        draft.flow.rtt = match sample {
+            Some(sample) => round2(sample.ms),
+            None => round2(10.0 + (draft.key_hash % 600) as f64 / 10.0),
+        };
+        draft.flow.rtt_method = sample.map(|s| s.method);
+        true
+    }
+}
+
+/// Owning process name and app group from the process tree snapshot.
+struct Process;
+
+impl Enricher for Process {
+    fn name(&self) -> &'static str {
+        "process"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        if draft.conn.pid > 0 {
+            draft.flow.process = ctx.process_names.get(&draft.conn.pid).cloned();
+            draft.flow.app_group = ctx.app_groups.get(&draft.conn.pid).cloned();
+        }
+        true
+    }
+}
+
+/// Container or WSL distro behind flows of Docker and WSL VM processes.
+struct Container;
+
+impl Enricher for Container {
+    fn name(&self) -> &'static str {
+        "container"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        let conn = draft.conn;
+        draft.flow.container = ctx
+            .containers
+            .attribute(&conn.local_ip, conn.local_port, draft.flow.process.as_deref());
+        true
+    }
+}
+
+/// Well-known service code from the remote port.
+struct Service;
+
+impl Enricher for Service {
+    fn name(&self) -> &'static str {
+        "service"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, _ctx: &mut TickContext) -> bool {
+        draft.flow.service = service_code(draft.conn.remote_port);
+        true
+    }
+}
+
+/// Domain the destination was recently resolved from.
+struct Domain;
+
+impl Enricher for Domain {
+    fn name(&self) -> &'static str {
+        "domain"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        draft.flow.domain = ctx.dns.domain(&draft.conn.remote_ip).map(str::to_string);
+        true
+    }
+}
+
+/// Blocklist the destination appears on, when threat intel is enabled.
+struct Threat;
+
+impl Enricher for Threat {
+    fn name(&self) -> &'static str {
+        "threat"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        draft.flow.threat = ctx.threats.lookup(&draft.conn.remote_ip).map(str::to_string);
+        true
+    }
+}
+
+/// Watched country or ASN of the destination.
+struct Watch;
+
+impl Enricher for Watch {
+    fn name(&self) -> &'static str {
+        "watch"
+    }
+
+    fn enrich(&self, draft: &mut FlowDraft, ctx: &mut TickContext) -> bool {
+        draft.flow.watch = ctx.watchlist.matches(&draft.flow.dst);
+        if let Some(tag) = &draft.flow.watch {
+            ctx.watched.push(WatchedFlow {
+                key: draft.key.to_string(),
+                flow_id: draft.flow.id.clone(),
+                process: draft.flow.process.clone(),
+                tag: tag.clone(),
+            });
+        }
+        true
+    }
+}
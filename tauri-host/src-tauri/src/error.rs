@@ -0,0 +1,169 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+// ─── Error type ─────────────────────────────────────────────────────────────
+
+/// Error returned by every Tauri command and emitted on `writer-error`.
+/// Serializes as `{ kind, message, retryable }` so the frontend can branch
+/// on `kind` instead of matching message text.
+#[derive(Debug, Clone)]
+pub enum AbyssError {
+    /// The requested session/destination/process does not exist.
+    NotFound(String),
+    /// SQLite is busy or locked by another connection; try again shortly.
+    DatabaseLocked(String),
+    /// Any other SQLite failure.
+    Database(String),
+    /// The arguments were rejected before touching any data.
+    InvalidInput(String),
+    /// The request conflicts with current state (e.g. deleting the active session).
+    Conflict(String),
+    /// Filesystem error (exports, data folder).
+    Io(String),
+    /// Remote request failed (cable map, geo lookups, push notifications).
+    Network(String),
+    /// The writer thread is gone and cannot accept commands.
+    WriterUnavailable(String),
+    /// Unexpected failure (task panics, serialization).
+    Internal(String),
+}
+
+impl AbyssError {
+    /// Stable machine-readable identifier for the variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AbyssError::NotFound(_) => "notFound",
+            AbyssError::DatabaseLocked(_) => "databaseLocked",
+            AbyssError::Database(_) => "database",
+            AbyssError::InvalidInput(_) => "invalidInput",
+            AbyssError::Conflict(_) => "conflict",
+            AbyssError::Io(_) => "io",
+            AbyssError::Network(_) => "network",
+            AbyssError::WriterUnavailable(_) => "writerUnavailable",
+            AbyssError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AbyssError::NotFound(m)
+            | AbyssError::DatabaseLocked(m)
+            | AbyssError::Database(m)
+            | AbyssError::InvalidInput(m)
+            | AbyssError::Conflict(m)
+            | AbyssError::Io(m)
+            | AbyssError::Network(m)
+            | AbyssError::WriterUnavailable(m)
+            | AbyssError::Internal(m) => m,
+        }
+    }
+
+    /// Whether the same call may succeed if retried unchanged.
+    pub fn retryable(&self) -> bool {
+        matches!(self, AbyssError::DatabaseLocked(_) | AbyssError::Network(_))
+    }
+
+    /// Prefix the message with what was being attempted.
+    pub fn context(self, what: &str) -> Self {
+        let wrap = |m: String| format!("{what}: {m}");
+        match self {
+            AbyssError::NotFound(m) => AbyssError::NotFound(wrap(m)),
+            AbyssError::DatabaseLocked(m) => AbyssError::DatabaseLocked(wrap(m)),
+            AbyssError::Database(m) => AbyssError::Database(wrap(m)),
+            AbyssError::InvalidInput(m) => AbyssError::InvalidInput(wrap(m)),
+            AbyssError::Conflict(m) => AbyssError::Conflict(wrap(m)),
+            AbyssError::Io(m) => AbyssError::Io(wrap(m)),
+            AbyssError::Network(m) => AbyssError::Network(wrap(m)),
+            AbyssError::WriterUnavailable(m) => AbyssError::WriterUnavailable(wrap(m)),
+            AbyssError::Internal(m) => AbyssError::Internal(wrap(m)),
+        }
+    }
+}
+
+impl fmt::Display for AbyssError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AbyssError {}
+
+impl Serialize for AbyssError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("AbyssError", 3)?;
+        s.serialize_field("kind", self.kind())?;
+        s.serialize_field("message", self.message())?;
+        s.serialize_field("retryable", &self.retryable())?;
+        s.end()
+    }
+}
+
+// ─── Conversions ────────────────────────────────────────────────────────────
+
+impl From<rusqlite::Error> for AbyssError {
+    fn from(e: rusqlite::Error) -> Self {
+        use rusqlite::ErrorCode;
+        match &e {
+            rusqlite::Error::QueryReturnedNoRows => AbyssError::NotFound(e.to_string()),
+            rusqlite::Error::SqliteFailure(err, _)
+                if matches!(err.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) =>
+            {
+                AbyssError::DatabaseLocked(e.to_string())
+            }
+            _ => AbyssError::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for AbyssError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AbyssError::NotFound(e.to_string()),
+            _ => AbyssError::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AbyssError {
+    fn from(e: reqwest::Error) -> Self {
+        AbyssError::Network(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AbyssError {
+    fn from(e: serde_json::Error) -> Self {
+        AbyssError::Internal(e.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for AbyssError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        AbyssError::Internal(e.to_string())
+    }
+}
+
+impl<T> From<std::sync::mpsc::SendError<T>> for AbyssError {
+    fn from(_: std::sync::mpsc::SendError<T>) -> Self {
+        AbyssError::WriterUnavailable("writer thread is not running".into())
+    }
+}
+
+impl From<maxminddb::MaxMindDBError> for AbyssError {
+    fn from(e: maxminddb::MaxMindDBError) -> Self {
+        use maxminddb::MaxMindDBError as E;
+        match e {
+            E::AddressNotFoundError(m) => AbyssError::NotFound(m),
+            E::IoError(m) => AbyssError::Io(m),
+            E::InvalidDatabaseError(m) => AbyssError::InvalidInput(m),
+            other => AbyssError::Internal(other.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl From<tauri::Error> for AbyssError {
+    fn from(e: tauri::Error) -> Self {
+        AbyssError::Internal(e.to_string())
+    }
+}
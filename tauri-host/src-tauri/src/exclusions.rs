@@ -0,0 +1,255 @@
+use crate::error::AbyssError;
+use crate::settings::Settings;
+use crate::threat::{mask_v4, mask_v6, parse_network};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// Version prefix of `Settings::persistence_exclusions`.
+const SEALED_PREFIX: &str = "v1:";
+
+/// AES-GCM nonce length in bytes.
+const NONCE_LEN: usize = 12;
+
+const MAX_ENTRIES: usize = 500;
+
+// ─── Exclusion list ─────────────────────────────────────────────────────────
+
+/// Processes and address ranges whose traffic is never written to disk.
+/// The live view still shows them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersistenceExclusions {
+    /// Process names, matched case-insensitively with or without `.exe`.
+    pub processes: Vec<String>,
+    /// IPs or CIDR ranges (`10.0.0.0/8`, `2001:db8::/32`) of the remote end.
+    pub ip_ranges: Vec<String>,
+}
+
+impl PersistenceExclusions {
+    /// Trimmed, without blanks or duplicates.
+    pub fn normalized(self) -> Self {
+        fn clean(entries: Vec<String>, key: fn(&str) -> String) -> Vec<String> {
+            let mut seen = HashSet::new();
+            entries
+                .into_iter()
+                .map(|e| e.trim().to_string())
+                .filter(|e| !e.is_empty() && seen.insert(key(e)))
+                .collect()
+        }
+        Self {
+            processes: clean(self.processes, process_key),
+            ip_ranges: clean(self.ip_ranges, str::to_lowercase),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        if self.processes.len() + self.ip_ranges.len() > MAX_ENTRIES {
+            return Err(AbyssError::InvalidInput(format!(
+                "At most {MAX_ENTRIES} exclusions are supported"
+            )));
+        }
+        if let Some(range) = self.ip_ranges.iter().find(|r| parse_network(r).is_none()) {
+            return Err(AbyssError::InvalidInput(format!("'{range}' is not an IP or CIDR range")));
+        }
+        Ok(())
+    }
+
+    /// Encrypt for storing in settings, so the list itself (which says
+    /// what's being hidden) isn't readable from the database.
+    pub fn seal(&self, key: &ExclusionKey) -> Result<String, AbyssError> {
+        let plaintext = serde_json::to_vec(self)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&key.0)
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| AbyssError::Internal("Failed to encrypt persistence exclusions".into()))?;
+        Ok(format!("{SEALED_PREFIX}{}{}", hex(&nonce), hex(&ciphertext)))
+    }
+
+    pub fn open(sealed: &str, key: &ExclusionKey) -> Result<Self, AbyssError> {
+        let invalid = || AbyssError::InvalidInput("Persistence exclusions can't be decrypted with this key".into());
+        let bytes = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .and_then(unhex)
+            .filter(|b| b.len() > NONCE_LEN)
+            .ok_or_else(invalid)?;
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = Aes256Gcm::new(&key.0)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid())?;
+        serde_json::from_slice(&plaintext).map_err(|_| invalid())
+    }
+
+    /// The list stored in `settings`, or an empty one when there is none.
+    pub fn load(settings: &Settings, key: Option<&ExclusionKey>) -> Result<Self, AbyssError> {
+        match (&settings.persistence_exclusions, key) {
+            (None, _) => Ok(Self::default()),
+            (Some(sealed), Some(key)) => Self::open(sealed, key),
+            (Some(_), None) => Err(AbyssError::Internal("Persistence exclusion key unavailable".into())),
+        }
+    }
+}
+
+// ─── Key ────────────────────────────────────────────────────────────────────
+
+/// AES-256 key sealing the exclusion list, kept in `sessions.db.key` next
+/// to the database (created when exclusions are first saved, readable by
+/// the owner only).
+#[derive(Clone)]
+pub struct ExclusionKey(Key<Aes256Gcm>);
+
+impl ExclusionKey {
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::generate_key(&mut OsRng))
+    }
+
+    /// The key beside `db_path`, if exclusions were ever saved for it.
+    pub fn load(db_path: &Path) -> Result<Option<Self>, AbyssError> {
+        let path = key_path(db_path);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => unhex(text.trim())
+                .filter(|bytes| bytes.len() == 32)
+                .map(|bytes| Some(Self(*Key::<Aes256Gcm>::from_slice(&bytes))))
+                .ok_or_else(|| AbyssError::Internal(format!("Corrupt key file {}", path.display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AbyssError::from(e).context("Failed to read exclusion key")),
+        }
+    }
+
+    pub fn load_or_create(db_path: &Path) -> Result<Self, AbyssError> {
+        if let Some(key) = Self::load(db_path)? {
+            return Ok(key);
+        }
+        let key = Self::generate();
+        write_private(&key_path(db_path), hex(&key.0).as_bytes())
+            .map_err(|e| AbyssError::from(e).context("Failed to create exclusion key"))?;
+        Ok(key)
+    }
+}
+
+fn key_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".key");
+    PathBuf::from(name)
+}
+
+#[cfg(unix)]
+pub(crate) fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(bytes)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, bytes)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// ─── Filter ─────────────────────────────────────────────────────────────────
+
+/// `PersistenceExclusions` compiled for the writer's per-flow checks.
+#[derive(Clone, Debug, Default)]
+pub struct ExclusionFilter {
+    processes: HashSet<String>,
+    ranges: Vec<(IpAddr, u8)>,
+}
+
+impl ExclusionFilter {
+    pub fn new(exclusions: &PersistenceExclusions) -> Self {
+        Self {
+            processes: exclusions.processes.iter().map(|p| process_key(p)).collect(),
+            ranges: exclusions.ip_ranges.iter().filter_map(|r| parse_network(r)).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty() && self.ranges.is_empty()
+    }
+
+    pub fn excludes_process(&self, process: &str) -> bool {
+        !self.processes.is_empty() && self.processes.contains(&process_key(process))
+    }
+
+    pub fn excludes_ip(&self, ip: &str) -> bool {
+        if self.ranges.is_empty() {
+            return false;
+        }
+        let Ok(ip) = ip.parse::<IpAddr>() else {
+            return false;
+        };
+        self.ranges.iter().any(|&(network, len)| match (network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => mask_v4(u32::from(ip), len) == mask_v4(u32::from(net), len),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => mask_v6(u128::from(ip), len) == mask_v6(u128::from(net), len),
+            _ => false,
+        })
+    }
+
+    /// A flow to or from an excluded process or remote address.
+    pub fn excludes(&self, process: Option<&str>, remote_ip: &str) -> bool {
+        process.is_some_and(|p| self.excludes_process(p)) || self.excludes_ip(remote_ip)
+    }
+}
+
+fn process_key(process: &str) -> String {
+    let process = process.trim().to_lowercase();
+    match process.strip_suffix(".exe") {
+        Some(stem) => stem.to_string(),
+        None => process,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_list_round_trips_and_filters_flows() {
+        let exclusions = PersistenceExclusions {
+            processes: vec![" 1Password.exe ".into(), "".into(), "1password".into()],
+            ip_ranges: vec!["10.0.0.0/8".into(), "2001:db8::/32".into()],
+        }
+        .normalized();
+        exclusions.validate().unwrap();
+        assert_eq!(exclusions.processes, ["1Password.exe"]);
+
+        let key = ExclusionKey::generate();
+        let sealed = exclusions.seal(&key).unwrap();
+        assert!(!sealed.contains("1Password"));
+        assert_eq!(PersistenceExclusions::open(&sealed, &key).unwrap(), exclusions);
+        assert!(PersistenceExclusions::open(&sealed, &ExclusionKey::generate()).is_err());
+
+        let filter = ExclusionFilter::new(&exclusions);
+        assert!(filter.excludes(Some("1password"), "93.184.216.34"));
+        assert!(filter.excludes(None, "10.1.2.3"));
+        assert!(filter.excludes(None, "2001:db8::1"));
+        assert!(!filter.excludes(Some("firefox"), "11.0.0.1"));
+
+        let bad = PersistenceExclusions {
+            ip_ranges: vec!["10.0.0.0/33".into()],
+            ..Default::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{FlowRate, GeoFlow, ParsedConnection};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// Per-second samples kept for each flow (one minute at the normal rate).
+const WINDOW: usize = 60;
+/// Samples needed before a flow is classified at all.
+const MIN_SAMPLES: usize = 10;
+/// Below this a sample counts as idle regardless of the flow's peak.
+const IDLE_BPS: f64 = 2_000.0;
+/// A sample under this fraction of the window's peak counts as idle.
+const IDLE_PEAK_FRACTION: f64 = 0.1;
+
+// ─── Classes ────────────────────────────────────────────────────────────────
+
+/// What a flow looks like it is doing, judged by its traffic shape rather
+/// than its port — a video player and a large download both sit on 443.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServiceClass {
+    /// Mostly inbound, in regular bursts with idle gaps (segmented video/audio).
+    VideoStreaming,
+    /// Sustained, steady inbound transfer with full-size packets.
+    FileDownload,
+    /// Sustained, steady outbound transfer.
+    FileUpload,
+    /// Continuous, roughly symmetric, small packets (calls, games).
+    RealTime,
+    /// Short request/response bursts at modest rates (browsing, APIs).
+    Interactive,
+    /// Long-lived and nearly silent (keepalives, push channels, sync).
+    Background,
+}
+
+impl ServiceClass {
+    /// Value stored in `flow_snapshots.service_class` (same as the serde name).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ServiceClass::VideoStreaming => "videoStreaming",
+            ServiceClass::FileDownload => "fileDownload",
+            ServiceClass::FileUpload => "fileUpload",
+            ServiceClass::RealTime => "realTime",
+            ServiceClass::Interactive => "interactive",
+            ServiceClass::Background => "background",
+        }
+    }
+}
+
+// ─── Signature ──────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy)]
+struct Sample {
+    tx_bps: f64,
+    rx_bps: f64,
+    pps: Option<f64>,
+}
+
+/// Behavioural features of one flow's recent samples.
+struct Signature {
+    mean_bps: f64,
+    peak_bps: f64,
+    /// Share of samples above the idle threshold.
+    active_fraction: f64,
+    /// Coefficient of variation of the active samples.
+    active_cv: f64,
+    /// Idle → active transitions in the window.
+    bursts: u32,
+    /// Received share of all bytes (1.0 = purely inbound).
+    down_share: f64,
+    /// Mean bytes per packet, when the source counts packets.
+    packet_bytes: Option<f64>,
+    duration_secs: f64,
+}
+
+impl Signature {
+    fn of(samples: &VecDeque<Sample>, duration_secs: f64) -> Self {
+        let totals: Vec<f64> = samples.iter().map(|s| s.tx_bps + s.rx_bps).collect();
+        let n = totals.len().max(1) as f64;
+        let peak_bps = totals.iter().copied().fold(0.0, f64::max);
+        let idle_below = IDLE_BPS.max(peak_bps * IDLE_PEAK_FRACTION);
+
+        let active: Vec<f64> = totals.iter().copied().filter(|bps| *bps >= idle_below).collect();
+        let active_mean = active.iter().sum::<f64>() / active.len().max(1) as f64;
+        let active_cv = if active.len() > 1 && active_mean > 0.0 {
+            let var = active.iter().map(|v| (v - active_mean).powi(2)).sum::<f64>() / active.len() as f64;
+            var.sqrt() / active_mean
+        } else {
+            0.0
+        };
+
+        let mut bursts = 0;
+        for pair in totals.windows(2) {
+            if pair[0] < idle_below && pair[1] >= idle_below {
+                bursts += 1;
+            }
+        }
+
+        let (tx, rx) = samples.iter().fold((0.0, 0.0), |(tx, rx), s| (tx + s.tx_bps, rx + s.rx_bps));
+        let down_share = if tx + rx > 0.0 { rx / (tx + rx) } else { 0.5 };
+
+        let (bytes, packets) = samples
+            .iter()
+            .filter_map(|s| s.pps.filter(|p| *p > 0.0).map(|p| ((s.tx_bps + s.rx_bps) / 8.0, p)))
+            .fold((0.0, 0.0), |(b, p), (sb, sp)| (b + sb, p + sp));
+        let packet_bytes = (packets > 0.0).then(|| bytes / packets);
+
+        Self {
+            mean_bps: totals.iter().sum::<f64>() / n,
+            peak_bps,
+            active_fraction: active.len() as f64 / n,
+            active_cv,
+            bursts,
+            down_share,
+            packet_bytes,
+            duration_secs,
+        }
+    }
+
+    /// First matching rule wins; `None` when nothing fits well enough.
+    fn classify(&self) -> Option<ServiceClass> {
+        let steady = self.active_fraction >= 0.8 && self.active_cv < 0.5;
+        let small_packets = self.packet_bytes.is_none_or(|b| b < 600.0);
+        let full_packets = self.packet_bytes.is_none_or(|b| b >= 1000.0);
+
+        if self.mean_bps < 16_000.0 && self.active_fraction < 0.5 && self.duration_secs >= 30.0 {
+            return Some(ServiceClass::Background);
+        }
+        if steady && small_packets && self.mean_bps < 4_000_000.0 && (0.2..=0.8).contains(&self.down_share) {
+            return Some(ServiceClass::RealTime);
+        }
+        if steady && full_packets && self.mean_bps >= 1_000_000.0 {
+            if self.down_share >= 0.9 {
+                return Some(ServiceClass::FileDownload);
+            }
+            if self.down_share <= 0.1 {
+                return Some(ServiceClass::FileUpload);
+            }
+        }
+        if self.down_share >= 0.85
+            && self.bursts >= 2
+            && (0.1..0.8).contains(&self.active_fraction)
+            && self.peak_bps >= 2_000_000.0
+            && self.duration_secs >= 20.0
+        {
+            return Some(ServiceClass::VideoStreaming);
+        }
+        if self.bursts >= 1 && self.mean_bps < 1_000_000.0 {
+            return Some(ServiceClass::Interactive);
+        }
+        None
+    }
+}
+
+// ─── Classifier ─────────────────────────────────────────────────────────────
+
+struct FlowHistory {
+    first_seen: Instant,
+    samples: VecDeque<Sample>,
+    class: Option<ServiceClass>,
+}
+
+/// Keeps a rolling window of each live flow's measured rates and labels
+/// flows with a `ServiceClass` once the window says enough.  Flows without
+/// OS byte counters are never classified: their rates are port estimates.
+#[derive(Default)]
+pub struct ServiceClassifier {
+    flows: HashMap<String, FlowHistory>,
+}
+
+impl ServiceClassifier {
+    /// Add this tick's sample for every flow in `presence` and forget flows
+    /// that left it.  A measured flow missing from `rates` was idle this tick.
+    pub fn observe(
+        &mut self,
+        presence: &HashMap<String, (ParsedConnection, Instant)>,
+        rates: &HashMap<String, FlowRate>,
+    ) {
+        let now = Instant::now();
+        self.flows.retain(|key, _| presence.contains_key(key));
+        for key in presence.keys() {
+            let rate = rates.get(key);
+            let history = match self.flows.get_mut(key) {
+                Some(history) => history,
+                None if rate.is_some() => self.flows.entry(key.clone()).or_insert_with(|| FlowHistory {
+                    first_seen: now,
+                    samples: VecDeque::with_capacity(WINDOW),
+                    class: None,
+                }),
+                None => continue,
+            };
+            let sample = rate.map_or(
+                Sample {
+                    tx_bps: 0.0,
+                    rx_bps: 0.0,
+                    pps: None,
+                },
+                |r| Sample {
+                    tx_bps: r.tx_bps,
+                    rx_bps: r.rx_bps,
+                    pps: r.pps,
+                },
+            );
+            if history.samples.len() == WINDOW {
+                history.samples.pop_front();
+            }
+            history.samples.push_back(sample);
+            if history.samples.len() >= MIN_SAMPLES {
+                let duration = now.duration_since(history.first_seen).as_secs_f64();
+                // An inconclusive window keeps the last label rather than flickering off
+                if let Some(class) = Signature::of(&history.samples, duration).classify() {
+                    history.class = Some(class);
+                }
+            }
+        }
+    }
+
+    /// Set `service_class` on each frame flow the classifier has a label for.
+    pub fn label(&self, flows: &mut [GeoFlow]) {
+        for flow in flows {
+            let key = flow.id.strip_prefix("live-").unwrap_or(&flow.id);
+            flow.service_class = self.flows.get(key).and_then(|h| h.class);
+        }
+    }
+}
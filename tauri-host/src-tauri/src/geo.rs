@@ -0,0 +1,514 @@
+use crate::error::AbyssError;
+use crate::locks::LockExt;
+use crate::GeoInfo;
+use maxminddb::{geoip2, Reader};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::net::IpAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+// ─── Providers ──────────────────────────────────────────────────────────────
+
+/// Selectable geolocation backend, persisted in settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GeoProviderKind {
+    /// ip-api.com batch endpoint (free, no key, HTTP only).
+    #[default]
+    IpApi,
+    /// ipinfo.io (optional token raises the rate limit).
+    IpInfo,
+    /// ipgeolocation.io (API key required).
+    IpGeolocation,
+    /// Loaded MaxMind databases only — nothing leaves the machine.
+    LocalMmdb,
+}
+
+pub type GeoFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Per-IP results (`None` = the provider has no data for that IP), or why
+/// the whole batch failed (rate limits included) so the caller backs off.
+pub type GeoBatch = Result<Vec<(String, Option<GeoInfo>)>, String>;
+
+/// A source of IP geolocation.  Futures are `'static` so lookups can run on
+/// a spawned task while the monitor loop keeps ticking.
+pub trait GeoProvider: Send + Sync {
+    fn kind(&self) -> GeoProviderKind;
+    /// Most IPs to pass to a single `lookup`.
+    fn max_batch(&self) -> usize;
+    /// Whether lookups send IPs off the machine (blocked by privacy mode).
+    fn is_remote(&self) -> bool {
+        true
+    }
+    fn lookup(&self, client: reqwest::Client, ips: Vec<String>) -> GeoFuture<GeoBatch>;
+    /// Geolocate this machine's public address, if the provider can.
+    fn locate_self(&self, client: reqwest::Client) -> GeoFuture<Option<GeoInfo>>;
+}
+
+/// Build the provider selected in settings.
+pub fn provider(
+    kind: GeoProviderKind,
+    api_key: Option<String>,
+    local: Arc<Mutex<GeoDatabases>>,
+) -> Arc<dyn GeoProvider> {
+    match kind {
+        GeoProviderKind::IpApi => Arc::new(IpApi),
+        GeoProviderKind::IpInfo => Arc::new(IpInfo { token: api_key }),
+        GeoProviderKind::IpGeolocation => Arc::new(IpGeolocation { api_key }),
+        GeoProviderKind::LocalMmdb => Arc::new(LocalMmdb { databases: local }),
+    }
+}
+
+/// Split ip-api/ipinfo style "AS15169 Google LLC" into ("AS15169", "Google LLC").
+fn split_as_field(raw: &str) -> (String, String) {
+    let raw = raw.trim();
+    match raw.split_once(' ') {
+        Some((asn, org)) if asn.starts_with("AS") => (asn.to_string(), org.trim().to_string()),
+        _ if raw.starts_with("AS") => (raw.to_string(), String::new()),
+        _ => (String::new(), raw.to_string()),
+    }
+}
+
+fn check_status(resp: &reqwest::Response, name: &str) -> Result<(), String> {
+    if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(format!("{name} rate limited (429)"));
+    }
+    if !resp.status().is_success() {
+        return Err(format!("{name} HTTP {}", resp.status()));
+    }
+    Ok(())
+}
+
+// ─── ip-api.com ─────────────────────────────────────────────────────────────
+
+const IP_API_BATCH: &str = "http://ip-api.com/batch";
+
+struct IpApi;
+
+#[derive(Deserialize)]
+struct IpApiItem {
+    status: String,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    city: Option<String>,
+    #[serde(rename = "countryCode")]
+    country_code: Option<String>,
+    #[serde(rename = "as")]
+    as_field: Option<String>,
+    org: Option<String>,
+    isp: Option<String>,
+}
+
+impl GeoProvider for IpApi {
+    fn kind(&self) -> GeoProviderKind {
+        GeoProviderKind::IpApi
+    }
+
+    fn max_batch(&self) -> usize {
+        100
+    }
+
+    fn lookup(&self, client: reqwest::Client, ips: Vec<String>) -> GeoFuture<GeoBatch> {
+        Box::pin(async move {
+            let body: Vec<serde_json::Value> = ips
+                .iter()
+                .map(|ip| {
+                    serde_json::json!({
+                        "query": ip,
+                        "fields": "status,lat,lon,city,countryCode,as,org,isp"
+                    })
+                })
+                .collect();
+            let resp = client
+                .post(IP_API_BATCH)
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| format!("ip-api batch failed: {e}"))?;
+            check_status(&resp, "ip-api")?;
+            let results: Vec<IpApiItem> = resp
+                .json()
+                .await
+                .map_err(|e| format!("ip-api response unreadable: {e}"))?;
+            Ok(ips
+                .into_iter()
+                .zip(results)
+                .map(|(ip, r)| {
+                    if r.status != "success" {
+                        return (ip, None);
+                    }
+                    // "as" looks like "AS15169 Google LLC" — keep just the AS number
+                    let (asn, _) = split_as_field(r.as_field.as_deref().unwrap_or(""));
+                    // Prefer org over isp
+                    let org = r
+                        .org
+                        .filter(|o| !o.trim().is_empty())
+                        .or(r.isp)
+                        .map(|s| s.trim().to_string())
+                        .unwrap_or_default();
+                    let info = GeoInfo {
+                        lat: r.lat.unwrap_or(0.0),
+                        lng: r.lon.unwrap_or(0.0),
+                        city: r.city.unwrap_or_else(|| "Unknown".into()),
+                        country: r.country_code.unwrap_or_else(|| "??".into()),
+                        asn,
+                        org,
+                    };
+                    (ip, Some(info))
+                })
+                .collect())
+        })
+    }
+
+    fn locate_self(&self, client: reqwest::Client) -> GeoFuture<Option<GeoInfo>> {
+        Box::pin(async move {
+            let data: serde_json::Value = client
+                .get("http://ip-api.com/json/?fields=lat,lon,city,countryCode")
+                .send()
+                .await
+                .ok()?
+                .json()
+                .await
+                .ok()?;
+            Some(GeoInfo {
+                lat: data["lat"].as_f64()?,
+                lng: data["lon"].as_f64()?,
+                city: data["city"].as_str().unwrap_or("Unknown").to_string(),
+                country: data["countryCode"].as_str().unwrap_or("US").to_string(),
+                asn: String::new(),
+                org: String::new(),
+            })
+        })
+    }
+}
+
+// ─── ipinfo.io ──────────────────────────────────────────────────────────────
+
+/// Per-IP requests: the batch endpoint needs a paid plan.
+struct IpInfo {
+    token: Option<String>,
+}
+
+impl IpInfo {
+    fn url(&self, ip: Option<&str>) -> String {
+        let mut url = match ip {
+            Some(ip) => format!("https://ipinfo.io/{ip}/json"),
+            None => "https://ipinfo.io/json".to_string(),
+        };
+        if let Some(token) = &self.token {
+            url.push_str("?token=");
+            url.push_str(token);
+        }
+        url
+    }
+
+    /// `loc` is "lat,lng"; bogon (private/reserved) answers have none.
+    fn parse(data: &serde_json::Value) -> Option<GeoInfo> {
+        let (lat, lng) = data["loc"].as_str()?.split_once(',')?;
+        let (asn, org) = split_as_field(data["org"].as_str().unwrap_or(""));
+        Some(GeoInfo {
+            lat: lat.trim().parse().ok()?,
+            lng: lng.trim().parse().ok()?,
+            city: data["city"].as_str().filter(|c| !c.is_empty()).unwrap_or("Unknown").to_string(),
+            country: data["country"].as_str().unwrap_or("??").to_string(),
+            asn,
+            org,
+        })
+    }
+}
+
+impl GeoProvider for IpInfo {
+    fn kind(&self) -> GeoProviderKind {
+        GeoProviderKind::IpInfo
+    }
+
+    fn max_batch(&self) -> usize {
+        10
+    }
+
+    fn lookup(&self, client: reqwest::Client, ips: Vec<String>) -> GeoFuture<GeoBatch> {
+        let urls: Vec<String> = ips.iter().map(|ip| self.url(Some(ip))).collect();
+        Box::pin(async move {
+            let mut results = Vec::with_capacity(ips.len());
+            for (ip, url) in ips.into_iter().zip(urls) {
+                let resp = client
+                    .get(&url)
+                    .send()
+                    .await
+                    .map_err(|e| format!("ipinfo lookup failed: {e}"))?;
+                check_status(&resp, "ipinfo")?;
+                let data: serde_json::Value = resp
+                    .json()
+                    .await
+                    .map_err(|e| format!("ipinfo response unreadable: {e}"))?;
+                results.push((ip, Self::parse(&data)));
+            }
+            Ok(results)
+        })
+    }
+
+    fn locate_self(&self, client: reqwest::Client) -> GeoFuture<Option<GeoInfo>> {
+        let url = self.url(None);
+        Box::pin(async move {
+            let data: serde_json::Value = client.get(&url).send().await.ok()?.json().await.ok()?;
+            Self::parse(&data)
+        })
+    }
+}
+
+// ─── ipgeolocation.io ───────────────────────────────────────────────────────
+
+struct IpGeolocation {
+    api_key: Option<String>,
+}
+
+impl IpGeolocation {
+    fn parse(data: &serde_json::Value) -> Option<GeoInfo> {
+        // Coordinates come back as strings
+        let coord = |key: &str| -> Option<f64> {
+            data[key].as_str().and_then(|v| v.parse().ok()).or_else(|| data[key].as_f64())
+        };
+        let org = data["organization"]
+            .as_str()
+            .filter(|o| !o.is_empty())
+            .or(data["isp"].as_str())
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        Some(GeoInfo {
+            lat: coord("latitude")?,
+            lng: coord("longitude")?,
+            city: data["city"].as_str().filter(|c| !c.is_empty()).unwrap_or("Unknown").to_string(),
+            country: data["country_code2"].as_str().unwrap_or("??").to_string(),
+            asn: data["asn"].as_str().unwrap_or("").to_string(),
+            org,
+        })
+    }
+}
+
+impl GeoProvider for IpGeolocation {
+    fn kind(&self) -> GeoProviderKind {
+        GeoProviderKind::IpGeolocation
+    }
+
+    fn max_batch(&self) -> usize {
+        10
+    }
+
+    fn lookup(&self, client: reqwest::Client, ips: Vec<String>) -> GeoFuture<GeoBatch> {
+        let key = self.api_key.clone();
+        Box::pin(async move {
+            let key = key.ok_or("ipgeolocation.io requires an API key")?;
+            let mut results = Vec::with_capacity(ips.len());
+            for ip in ips {
+                let resp = client
+                    .get("https://api.ipgeolocation.io/ipgeo")
+                    .query(&[("apiKey", key.as_str()), ("ip", ip.as_str())])
+                    .send()
+                    .await
+                    .map_err(|e| format!("ipgeolocation lookup failed: {e}"))?;
+                check_status(&resp, "ipgeolocation")?;
+                let data: serde_json::Value = resp
+                    .json()
+                    .await
+                    .map_err(|e| format!("ipgeolocation response unreadable: {e}"))?;
+                results.push((ip, Self::parse(&data)));
+            }
+            Ok(results)
+        })
+    }
+
+    fn locate_self(&self, client: reqwest::Client) -> GeoFuture<Option<GeoInfo>> {
+        let key = self.api_key.clone();
+        Box::pin(async move {
+            let data: serde_json::Value = client
+                .get("https://api.ipgeolocation.io/ipgeo")
+                .query(&[("apiKey", key?.as_str())])
+                .send()
+                .await
+                .ok()?
+                .json()
+                .await
+                .ok()?;
+            Self::parse(&data)
+        })
+    }
+}
+
+// ─── Local MMDB provider ────────────────────────────────────────────────────
+
+struct LocalMmdb {
+    databases: Arc<Mutex<GeoDatabases>>,
+}
+
+impl GeoProvider for LocalMmdb {
+    fn kind(&self) -> GeoProviderKind {
+        GeoProviderKind::LocalMmdb
+    }
+
+    fn max_batch(&self) -> usize {
+        100
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    fn lookup(&self, _client: reqwest::Client, ips: Vec<String>) -> GeoFuture<GeoBatch> {
+        let results: GeoBatch = {
+            let databases = self.databases.lock_or_recover("geo_db");
+            if databases.is_loaded() {
+                Ok(ips.into_iter().map(|ip| {
+                    let info = databases.lookup(&ip);
+                    (ip, info)
+                }).collect())
+            } else {
+                Err("no local GeoIP database loaded".into())
+            }
+        };
+        Box::pin(std::future::ready(results))
+    }
+
+    /// A database can't tell which public address this machine has.
+    fn locate_self(&self, _client: reqwest::Client) -> GeoFuture<Option<GeoInfo>> {
+        Box::pin(std::future::ready(None))
+    }
+}
+
+// ─── Local MaxMind databases ────────────────────────────────────────────────
+
+/// Metadata for a loaded `.mmdb` file.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoDbInfo {
+    pub path: String,
+    /// e.g. "GeoLite2-City", "GeoLite2-ASN".
+    pub database_type: String,
+    /// Unix timestamp the database was built at.
+    pub build_epoch: u64,
+}
+
+/// Which local databases are loaded.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoDbStatus {
+    pub city: Option<GeoDbInfo>,
+    pub asn: Option<GeoDbInfo>,
+}
+
+struct LoadedDb {
+    reader: Reader<Vec<u8>>,
+    info: GeoDbInfo,
+}
+
+/// GeoLite2/GeoIP2 City (or Country) and ASN databases, consulted before the
+/// HTTP batch API.  A City database is required for a hit — without
+/// coordinates a flow can't be placed — while ASN only enriches it.
+#[derive(Default)]
+pub struct GeoDatabases {
+    city: Option<LoadedDb>,
+    asn: Option<LoadedDb>,
+}
+
+impl GeoDatabases {
+    /// Load `path`, placing it in the City or ASN slot according to its
+    /// metadata.  Replaces any database already in that slot.
+    pub fn load(&mut self, path: &Path) -> Result<GeoDbInfo, AbyssError> {
+        let reader = Reader::open_readfile(path)
+            .map_err(|e| AbyssError::from(e).context(&format!("Failed to open {}", path.display())))?;
+        let info = GeoDbInfo {
+            path: path.to_string_lossy().to_string(),
+            database_type: reader.metadata.database_type.clone(),
+            build_epoch: reader.metadata.build_epoch,
+        };
+        let kind = info.database_type.to_ascii_lowercase();
+        let slot = if kind.contains("asn") {
+            &mut self.asn
+        } else if kind.contains("city") || kind.contains("country") {
+            &mut self.city
+        } else {
+            return Err(AbyssError::InvalidInput(format!(
+                "Unsupported database type '{}' (expected a City, Country or ASN database)",
+                info.database_type
+            )));
+        };
+        *slot = Some(LoadedDb {
+            reader,
+            info: info.clone(),
+        });
+        println!("[Abyss] GeoIP database loaded: {} ({})", info.database_type, info.path);
+        Ok(info)
+    }
+
+    pub fn clear(&mut self) {
+        self.city = None;
+        self.asn = None;
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.city.is_some()
+    }
+
+    pub fn status(&self) -> GeoDbStatus {
+        GeoDbStatus {
+            city: self.city.as_ref().map(|db| db.info.clone()),
+            asn: self.asn.as_ref().map(|db| db.info.clone()),
+        }
+    }
+
+    /// Paths of every loaded database, for persisting in settings.
+    pub fn paths(&self) -> Vec<String> {
+        [&self.city, &self.asn]
+            .into_iter()
+            .flatten()
+            .map(|db| db.info.path.clone())
+            .collect()
+    }
+
+    /// Resolve `ip` locally.  `None` means "ask the remote API".
+    pub(crate) fn lookup(&self, ip: &str) -> Option<GeoInfo> {
+        let addr: IpAddr = ip.parse().ok()?;
+        let city_db = self.city.as_ref()?;
+        let record: geoip2::City = city_db.reader.lookup(addr).ok()?;
+        let location = record.location.as_ref()?;
+        let (lat, lng) = (location.latitude?, location.longitude?);
+
+        let city = record
+            .city
+            .as_ref()
+            .and_then(|c| c.names.as_ref())
+            .and_then(|names| names.get("en").copied())
+            .unwrap_or("Unknown")
+            .to_string();
+        let country = record
+            .country
+            .as_ref()
+            .or(record.registered_country.as_ref())
+            .and_then(|c| c.iso_code)
+            .unwrap_or("??")
+            .to_string();
+
+        let (asn, org) = self
+            .asn
+            .as_ref()
+            .and_then(|db| db.reader.lookup::<geoip2::Asn>(addr).ok())
+            .map(|a| {
+                (
+                    a.autonomous_system_number.map(|n| format!("AS{n}")).unwrap_or_default(),
+                    a.autonomous_system_organization.unwrap_or("").trim().to_string(),
+                )
+            })
+            .unwrap_or_default();
+
+        Some(GeoInfo {
+            lat,
+            lng,
+            city,
+            country,
+            asn,
+            org,
+        })
+    }
+}
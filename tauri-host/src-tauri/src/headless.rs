@@ -0,0 +1,330 @@
+//! Recording and export without the desktop shell, for `abyss-cli`: the
+//! shared monitor loop (`monitor::Monitor`) feeding the same writer
+//! thread, with alerts and progress printed instead of shown in a webview.
+
+use crate::locks::LockExt;
+use crate::{
+    alerts, anonymize::Anonymizer, db, geo, monitor, publicip, routes, settings, threat, watchlist, writer, LocalGeo,
+    TelemetryFrame,
+};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub use crate::db::SessionInfo;
+pub use crate::error::AbyssError;
+
+/// How often `record` prints a progress line.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(60);
+
+// ─── Recording ──────────────────────────────────────────────────────────────
+
+/// What `record` captures and where it stores it.
+#[derive(Clone, Debug, Default)]
+pub struct RecordOptions {
+    /// Database to record into; created with the current schema if missing.
+    pub db_path: PathBuf,
+    /// Session name; defaults to the start time, as in the app.
+    pub name: Option<String>,
+    /// GeoLite2/GeoIP2 City and ASN databases.  Falls back to the ones saved
+    /// in the database's settings; without a City database (or
+    /// `remote_lookups`) destinations are stored with a placeholder
+    /// location ("??").
+    pub geo_databases: Vec<PathBuf>,
+    /// Interfaces to record; empty uses the saved setting (all interfaces
+    /// unless narrowed in the app).
+    pub interfaces: Vec<String>,
+    /// Stop after this long; `None` records until `stop` is set.
+    pub duration: Option<Duration>,
+    /// Geolocate with the saved provider and follow the public address, as
+    /// the app does.  Off, recording runs in privacy mode: nothing is looked
+    /// up off the machine and unplaced destinations are stored as "??".
+    pub remote_lookups: bool,
+}
+
+/// `record`'s side of the monitor loop: settings, alert rules and the
+/// watchlist as saved in the database, alerts and progress on stdout.
+struct CliHost {
+    db_path: PathBuf,
+    settings: tokio::sync::watch::Sender<settings::Settings>,
+    geo_db: Arc<Mutex<geo::GeoDatabases>>,
+    session_id: Mutex<Option<String>>,
+    alert_rules: Vec<alerts::AlertRule>,
+    watchlist: watchlist::Watchlist,
+    threats: Mutex<Arc<threat::ThreatIndex>>,
+    local_geo: Mutex<Option<LocalGeo>>,
+    public_ip_recheck: tokio::sync::Notify,
+    started: Instant,
+    last_progress: Mutex<Instant>,
+}
+
+impl monitor::MonitorHost for CliHost {
+    fn settings(&self) -> settings::Settings {
+        self.settings.borrow().clone()
+    }
+
+    fn subscribe_settings(&self) -> tokio::sync::watch::Receiver<settings::Settings> {
+        self.settings.subscribe()
+    }
+
+    fn db_path(&self) -> PathBuf {
+        self.db_path.clone()
+    }
+
+    fn geo_db(&self) -> Arc<Mutex<geo::GeoDatabases>> {
+        self.geo_db.clone()
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.session_id.lock_or_recover("session_id").clone()
+    }
+
+    fn alert_rules(&self) -> Vec<alerts::AlertRule> {
+        self.alert_rules.clone()
+    }
+
+    fn watchlist(&self) -> watchlist::Watchlist {
+        self.watchlist.clone()
+    }
+
+    fn threats(&self) -> Arc<threat::ThreatIndex> {
+        self.threats.lock_or_recover("threats").clone()
+    }
+
+    fn local_geo(&self) -> LocalGeo {
+        self.local_geo
+            .lock_or_recover("local_geo")
+            .clone()
+            .unwrap_or_else(crate::fallback_local_geo)
+    }
+
+    fn set_local_geo(&self, geo: &LocalGeo) {
+        *self.local_geo.lock_or_recover("local_geo") = Some(geo.clone());
+    }
+
+    fn set_threats(&self, index: Arc<threat::ThreatIndex>) {
+        *self.threats.lock_or_recover("threats") = index;
+    }
+
+    fn public_ip_recheck(&self) -> &tokio::sync::Notify {
+        &self.public_ip_recheck
+    }
+
+    fn publish_frame(&self, frame: &TelemetryFrame, _material: bool) {
+        let mut last_progress = self.last_progress.lock_or_recover("last_progress");
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            println!(
+                "[Abyss] {} recorded · {} flows · {}",
+                crate::card::format_duration(self.started.elapsed().as_secs_f64()),
+                frame.net.active_flows,
+                alerts::format_bps(frame.net.bps)
+            );
+            *last_progress = Instant::now();
+        }
+    }
+
+    fn alert(&self, event: &alerts::AlertEvent, _actions: &[alerts::AlertAction]) {
+        if self.settings().alert_mute.suppresses(event, chrono::Utc::now()) {
+            println!("[Abyss] Alert muted: {} ({})", event.rule_name, event.subject);
+        } else {
+            println!("[Abyss] Alert [{:?}] {}: {}", event.severity, event.rule_name, event.message);
+        }
+    }
+}
+
+/// Record one session until `stop` is set (or `options.duration` passes)
+/// and return its id.  Blocks the calling thread.
+pub fn record(options: RecordOptions, stop: &AtomicBool) -> Result<String, AbyssError> {
+    let (mut tuning, alert_rules, watchlist) = {
+        let conn = db::open_database(&options.db_path)?;
+        (settings::load(&conn), db::list_alert_rules(&conn)?, db::load_watchlist(&conn)?)
+    };
+    let mut geo_db = geo::GeoDatabases::default();
+    if options.geo_databases.is_empty() {
+        for path in &tuning.geoip_db_paths {
+            if let Err(e) = geo_db.load(Path::new(path)) {
+                eprintln!("[Abyss] Skipping saved GeoIP database: {e}");
+            }
+        }
+    } else {
+        for path in &options.geo_databases {
+            geo_db.load(path)?;
+        }
+    }
+    if !options.interfaces.is_empty() {
+        tuning.monitored_interfaces = options.interfaces.clone();
+    }
+    if !options.remote_lookups {
+        tuning.privacy_mode = true;
+    }
+    if !geo_db.is_loaded() && tuning.privacy_mode {
+        println!("[Abyss] No GeoIP City database — destinations are recorded without a location");
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| AbyssError::from(e).context("Failed to start the async runtime"))?;
+
+    let (writer_tx, writer_rx) = writer::create_channel();
+    let writer_db_path = options.db_path.clone();
+    let writer_handle = std::thread::spawn(move || {
+        writer::writer_thread(
+            writer_rx,
+            writer_db_path,
+            Box::new(|err| eprintln!("[Abyss][writer] {err}")),
+            Box::new(|_| {}),
+            Box::new(|report| println!("[Abyss] Retention pruned {} sessions", report.total())),
+            Box::new(|event| {
+                println!(
+                    "[Abyss] Data budget {}% used ({} of {})",
+                    event.threshold_pct,
+                    db::format_bytes_human(event.used_bytes),
+                    db::format_bytes_human(event.bytes_limit)
+                )
+            }),
+            Box::new(|status| {
+                if let crate::dblock::DbLockStatus::Waiting { reason, .. } = status {
+                    eprintln!("[Abyss] Waiting for the database: {reason}");
+                }
+            }),
+            Box::new(|_| {}),
+        )
+    });
+
+    let feeds_dir = options
+        .db_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("threat-feeds");
+    let host = Arc::new(CliHost {
+        db_path: options.db_path.clone(),
+        settings: tokio::sync::watch::Sender::new(tuning),
+        geo_db: Arc::new(Mutex::new(geo_db)),
+        session_id: Mutex::new(None),
+        alert_rules,
+        watchlist,
+        threats: Mutex::new(Arc::default()),
+        local_geo: Mutex::new(None),
+        public_ip_recheck: tokio::sync::Notify::new(),
+        started: Instant::now(),
+        last_progress: Mutex::new(Instant::now()),
+    });
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let recorded = runtime.block_on(async {
+        let mut monitor = monitor::Monitor::new(&*host, writer_tx.clone()).await;
+        let local = monitor.local_geo().clone();
+        let name = options
+            .name
+            .unwrap_or_else(|| chrono::Local::now().format("Session \u{2014} %b %d, %Y %I:%M %p").to_string());
+        writer_tx.send(writer::WriteCommand::StartSession {
+            id: session_id.clone(),
+            name: name.clone(),
+            local_city: local.city,
+            local_country: local.country,
+            local_lat: local.lat,
+            local_lng: local.lng,
+        })?;
+        *host.session_id.lock_or_recover("session_id") = Some(session_id.clone());
+        println!(
+            "[Abyss] Recording '{name}' ({session_id}) to {}",
+            options.db_path.display()
+        );
+        tokio::spawn(routes::watch(host.clone(), writer_tx.clone()));
+        tokio::spawn(publicip::watch(host.clone(), writer_tx.clone()));
+        tokio::spawn(threat::watch(host.clone(), feeds_dir));
+
+        let start = Instant::now();
+        while !stop.load(Ordering::Relaxed) && options.duration.is_none_or(|limit| start.elapsed() < limit) {
+            let tick = monitor.tick(&*host).await;
+            if writer_handle.is_finished() {
+                return Err(AbyssError::Internal("The writer stopped unexpectedly".into()));
+            }
+            tokio::time::sleep(tick).await;
+        }
+        monitor.close_flows(&*host);
+        Ok(())
+    });
+    drop(runtime);
+
+    let _ = writer_tx.send(writer::WriteCommand::EndSession { id: session_id.clone() });
+    let _ = writer_tx.send(writer::WriteCommand::Shutdown);
+    let _ = writer_handle.join();
+    recorded?;
+    println!("[Abyss] Session {session_id} saved");
+    Ok(session_id)
+}
+
+/// Set when Ctrl+C (or SIGTERM) arrives after `stop_on_interrupt`.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Route Ctrl+C to the returned flag instead of killing the process, so
+/// `record` can end the session cleanly.
+pub fn stop_on_interrupt() -> &'static AtomicBool {
+    #[cfg(unix)]
+    {
+        extern "C" fn on_signal(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::Relaxed);
+        }
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only stores to an atomic, which is signal-safe
+        unsafe {
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::BOOL;
+        use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+        unsafe extern "system" fn on_ctrl(_: u32) -> BOOL {
+            INTERRUPTED.store(true, Ordering::Relaxed);
+            1
+        }
+        // SAFETY: registers a handler that only stores to an atomic
+        unsafe {
+            SetConsoleCtrlHandler(Some(on_ctrl), 1);
+        }
+    }
+    &INTERRUPTED
+}
+
+// ─── Export ─────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// One row per flow snapshot (`cmd_export_session_csv`).
+    Csv,
+    /// The session bundle `cmd_import_session_json` reads back.
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Render `session_id` from the database at `db_path` in `format`.
+pub fn export_session(db_path: &Path, session_id: &str, format: ExportFormat) -> Result<String, AbyssError> {
+    let conn = db::open_database(db_path)?;
+    let anonymizer = Anonymizer::for_exports(&settings::load(&conn), db_path)?;
+    match format {
+        ExportFormat::Csv => crate::session_csv(&conn, session_id, anonymizer.as_ref()).map(|(_, _, csv)| csv),
+        ExportFormat::Json => {
+            let bundle = crate::session_bundle(&conn, session_id, anonymizer.as_ref())?;
+            serde_json::to_string_pretty(&bundle).map_err(|e| AbyssError::from(e).context("JSON serialization failed"))
+        }
+    }
+}
+
+/// The most recent sessions in the database at `db_path`, newest first.
+pub fn list_sessions(db_path: &Path, limit: u32) -> Result<Vec<SessionInfo>, AbyssError> {
+    let conn = db::open_database(db_path)?;
+    db::list_sessions(&conn, limit, 0).map_err(AbyssError::from)
+}
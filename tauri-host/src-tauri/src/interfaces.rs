@@ -0,0 +1,484 @@
+use crate::ParsedConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+// ─── Interface inventory ────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InterfaceKind {
+    Ethernet,
+    Wifi,
+    Cellular,
+    /// VPN and other point-to-point tunnels (WireGuard, tun/utun, PPP).
+    Tunnel,
+    /// Software-only links: bridges, veth pairs, hypervisor adapters.
+    Virtual,
+    Loopback,
+    Other,
+}
+
+/// One network interface with cumulative counters and the rate measured
+/// between the last two samples.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceInfo {
+    pub name: String,
+    /// Human-readable name where the OS has one (Windows adapter description).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub kind: InterfaceKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_speed_mbps: Option<u64>,
+    pub up: bool,
+    /// Assigned IPv4/IPv6 addresses, formatted like `ParsedConnection::local_ip`.
+    pub addresses: Vec<String>,
+    /// False when excluded by `Settings::monitored_interfaces`.
+    pub monitored: bool,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Bits/s over the last sampling interval.
+    pub rx_bps: f64,
+    pub tx_bps: f64,
+}
+
+/// The interface carrying the most traffic in a frame.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveInterface {
+    pub name: String,
+    pub kind: InterfaceKind,
+    pub rx_bps: f64,
+    pub tx_bps: f64,
+}
+
+/// Samples interface counters and turns deltas into rates.
+#[derive(Default)]
+pub struct InterfaceTracker {
+    previous: HashMap<String, (u64, u64, Instant)>,
+    latest: Vec<InterfaceInfo>,
+}
+
+impl InterfaceTracker {
+    /// Re-read every interface and update rates.
+    pub fn sample(&mut self) -> &[InterfaceInfo] {
+        let now = Instant::now();
+        let mut current = list();
+        for iface in &mut current {
+            if let Some(&(rx, tx, at)) = self.previous.get(&iface.name) {
+                let dt = now.duration_since(at).as_secs_f64();
+                // A counter going backwards means a reset or 32-bit wrap: skip a beat
+                if dt > 0.0 && iface.rx_bytes >= rx && iface.tx_bytes >= tx {
+                    iface.rx_bps = ((iface.rx_bytes - rx) as f64 * 8.0 / dt).round();
+                    iface.tx_bps = ((iface.tx_bytes - tx) as f64 * 8.0 / dt).round();
+                }
+            }
+        }
+        self.previous = current
+            .iter()
+            .map(|i| (i.name.clone(), (i.rx_bytes, i.tx_bytes, now)))
+            .collect();
+        self.latest = current;
+        &self.latest
+    }
+
+    /// Busiest non-loopback interface in the latest sample, if any moved data.
+    pub fn active(&self) -> Option<ActiveInterface> {
+        self.latest
+            .iter()
+            .filter(|i| i.kind != InterfaceKind::Loopback && i.rx_bps + i.tx_bps > 0.0)
+            .max_by(|a, b| {
+                (a.rx_bps + a.tx_bps)
+                    .partial_cmp(&(b.rx_bps + b.tx_bps))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|i| ActiveInterface {
+                name: i.name.clone(),
+                kind: i.kind,
+                rx_bps: i.rx_bps,
+                tx_bps: i.tx_bps,
+            })
+    }
+}
+
+// ─── Monitored interfaces ───────────────────────────────────────────────────
+
+/// Combined (tx, rx) bits/s of the monitored, non-loopback interfaces, or
+/// `None` if there are none to measure.
+pub fn monitored_totals(ifaces: &[InterfaceInfo]) -> Option<(f64, f64)> {
+    let measured: Vec<&InterfaceInfo> = ifaces
+        .iter()
+        .filter(|i| i.monitored && i.kind != InterfaceKind::Loopback)
+        .collect();
+    if measured.is_empty() {
+        return None;
+    }
+    Some(measured.iter().fold((0.0, 0.0), |(tx, rx), i| (tx + i.tx_bps, rx + i.rx_bps)))
+}
+
+/// Whether `iface` is monitored under `monitored` (empty means all).
+pub fn is_monitored(iface: &InterfaceInfo, monitored: &[String]) -> bool {
+    monitored.is_empty() || monitored.iter().any(|m| m.eq_ignore_ascii_case(&iface.name))
+}
+
+/// Drop connections whose local address belongs to an interface outside
+/// `monitored`.  Sockets bound to a wildcard or unknown address are kept.
+pub fn retain_monitored(connections: &mut Vec<ParsedConnection>, monitored: &[String]) {
+    if monitored.is_empty() {
+        return;
+    }
+    let excluded: HashSet<String> = list()
+        .into_iter()
+        .filter(|iface| !is_monitored(iface, monitored))
+        .flat_map(|iface| iface.addresses)
+        .collect();
+    if !excluded.is_empty() {
+        connections.retain(|c| !excluded.contains(&c.local_ip));
+    }
+}
+
+fn format_mac(bytes: &[u8]) -> Option<String> {
+    if bytes.is_empty() || bytes.iter().all(|&b| b == 0) {
+        return None;
+    }
+    Some(
+        bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+// ─── Platform enumeration ───────────────────────────────────────────────────
+
+/// Enumerate interfaces from sysfs.
+#[cfg(target_os = "linux")]
+pub fn list() -> Vec<InterfaceInfo> {
+    use std::path::Path;
+
+    let read = |path: &Path| std::fs::read_to_string(path).ok().map(|s| s.trim().to_string());
+    let read_u64 = |path: &Path| read(path).and_then(|s| s.parse::<u64>().ok());
+
+    let Ok(entries) = std::fs::read_dir("/sys/class/net") else {
+        return Vec::new();
+    };
+    let mut addresses = unix_addresses();
+    let mut interfaces: Vec<InterfaceInfo> = entries
+        .filter_map(|e| e.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let dir = entry.path();
+            let arp_type = read_u64(&dir.join("type")).unwrap_or(0);
+            let kind = if arp_type == 772 {
+                InterfaceKind::Loopback
+            } else if dir.join("wireless").exists() || dir.join("phy80211").exists() {
+                InterfaceKind::Wifi
+            } else if dir.join("tun_flags").exists()
+                || matches!(arp_type, 512 | 768 | 769 | 776 | 778 | 823 | 65534)
+            {
+                // ppp, ipip, ip6tnl, sit, gre, ip6gre, none (wireguard)
+                InterfaceKind::Tunnel
+            } else if arp_type == 519 || name.starts_with("wwan") {
+                InterfaceKind::Cellular
+            } else if !dir.join("device").exists() {
+                InterfaceKind::Virtual
+            } else if arp_type == 1 {
+                InterfaceKind::Ethernet
+            } else {
+                InterfaceKind::Other
+            };
+            // "unknown" is normal for loopback and tunnels; fall back to IFF_UP
+            let up = match read(&dir.join("operstate")).as_deref() {
+                Some("up") => true,
+                Some("unknown") => read(&dir.join("flags"))
+                    .and_then(|f| u64::from_str_radix(f.trim_start_matches("0x"), 16).ok())
+                    .is_some_and(|flags| flags & 1 != 0),
+                _ => false,
+            };
+            let mac = read(&dir.join("address")).and_then(|addr| {
+                let bytes: Vec<u8> = addr
+                    .split(':')
+                    .filter_map(|b| u8::from_str_radix(b, 16).ok())
+                    .collect();
+                format_mac(&bytes)
+            });
+            // Reads fail (EINVAL) or return -1 when the link is down or virtual
+            let link_speed_mbps = read(&dir.join("speed"))
+                .and_then(|s| s.parse::<i64>().ok())
+                .filter(|&s| s > 0)
+                .map(|s| s as u64);
+            InterfaceInfo {
+                addresses: addresses.remove(&name).unwrap_or_default(),
+                name,
+                description: None,
+                kind,
+                mac,
+                link_speed_mbps,
+                up,
+                monitored: true,
+                rx_bytes: read_u64(&dir.join("statistics/rx_bytes")).unwrap_or(0),
+                tx_bytes: read_u64(&dir.join("statistics/tx_bytes")).unwrap_or(0),
+                rx_bps: 0.0,
+                tx_bps: 0.0,
+            }
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// Enumerate interfaces with `GetIfTable2`, skipping NDIS filter layers.
+#[cfg(target_os = "windows")]
+pub fn list() -> Vec<InterfaceInfo> {
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        FreeMibTable, GetIfTable2, IF_TYPE_ETHERNET_CSMACD, IF_TYPE_IEEE80211, IF_TYPE_PPP,
+        IF_TYPE_PROP_VIRTUAL, IF_TYPE_SOFTWARE_LOOPBACK, IF_TYPE_TUNNEL, IF_TYPE_WWANPP,
+        IF_TYPE_WWANPP2, MIB_IF_ROW2, MIB_IF_TABLE2,
+    };
+    use windows_sys::Win32::NetworkManagement::Ndis::IfOperStatusUp;
+
+    let mut addresses = windows_addresses();
+    let wide = |chars: &[u16]| {
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+        String::from_utf16_lossy(&chars[..len])
+    };
+
+    let mut table: *mut MIB_IF_TABLE2 = std::ptr::null_mut();
+    // SAFETY: on success GetIfTable2 allocates `NumEntries` rows, which are
+    // only read before the table is released with FreeMibTable.
+    unsafe {
+        if GetIfTable2(&mut table) != 0 || table.is_null() {
+            return Vec::new();
+        }
+        let rows: &[MIB_IF_ROW2] = std::slice::from_raw_parts(
+            (*table).Table.as_ptr(),
+            (*table).NumEntries as usize,
+        );
+        let interfaces = rows
+            .iter()
+            // Bit 1 of the flags marks filter drivers stacked on a real adapter
+            .filter(|row| row.InterfaceAndOperStatusFlags._bitfield & 0b10 == 0)
+            .map(|row| {
+                let hardware = row.InterfaceAndOperStatusFlags._bitfield & 0b1 != 0;
+                let kind = match row.Type {
+                    IF_TYPE_SOFTWARE_LOOPBACK => InterfaceKind::Loopback,
+                    IF_TYPE_IEEE80211 => InterfaceKind::Wifi,
+                    IF_TYPE_WWANPP | IF_TYPE_WWANPP2 => InterfaceKind::Cellular,
+                    IF_TYPE_TUNNEL | IF_TYPE_PPP => InterfaceKind::Tunnel,
+                    IF_TYPE_PROP_VIRTUAL => InterfaceKind::Virtual,
+                    IF_TYPE_ETHERNET_CSMACD if hardware => InterfaceKind::Ethernet,
+                    IF_TYPE_ETHERNET_CSMACD => InterfaceKind::Virtual,
+                    _ => InterfaceKind::Other,
+                };
+                let mac_len = (row.PhysicalAddressLength as usize).min(row.PhysicalAddress.len());
+                let speed = row.ReceiveLinkSpeed.max(row.TransmitLinkSpeed);
+                InterfaceInfo {
+                    name: wide(&row.Alias),
+                    description: Some(wide(&row.Description)).filter(|d| !d.is_empty()),
+                    kind,
+                    mac: format_mac(&row.PhysicalAddress[..mac_len]),
+                    // u64::MAX means unknown
+                    link_speed_mbps: (speed > 0 && speed != u64::MAX).then_some(speed / 1_000_000),
+                    up: row.OperStatus == IfOperStatusUp,
+                    addresses: addresses.remove(&row.InterfaceIndex).unwrap_or_default(),
+                    monitored: true,
+                    rx_bytes: row.InOctets,
+                    tx_bytes: row.OutOctets,
+                    rx_bps: 0.0,
+                    tx_bps: 0.0,
+                }
+            })
+            .collect();
+        FreeMibTable(table as *const _);
+        interfaces
+    }
+}
+
+/// Unicast addresses per interface index.
+#[cfg(target_os = "windows")]
+fn windows_addresses() -> HashMap<u32, Vec<String>> {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        FreeMibTable, GetUnicastIpAddressTable, MIB_UNICASTIPADDRESS_ROW, MIB_UNICASTIPADDRESS_TABLE,
+    };
+    use windows_sys::Win32::Networking::WinSock::{AF_INET, AF_INET6, AF_UNSPEC};
+
+    let mut map: HashMap<u32, Vec<String>> = HashMap::new();
+    let mut table: *mut MIB_UNICASTIPADDRESS_TABLE = std::ptr::null_mut();
+    // SAFETY: rows are read before FreeMibTable; the SOCKADDR_INET union is
+    // read according to its family tag.
+    unsafe {
+        if GetUnicastIpAddressTable(AF_UNSPEC, &mut table) != 0 || table.is_null() {
+            return map;
+        }
+        let rows: &[MIB_UNICASTIPADDRESS_ROW] = std::slice::from_raw_parts(
+            (*table).Table.as_ptr(),
+            (*table).NumEntries as usize,
+        );
+        for row in rows {
+            let ip = match row.Address.si_family {
+                AF_INET => IpAddr::V4(Ipv4Addr::from(u32::from_be(row.Address.Ipv4.sin_addr.S_un.S_addr))),
+                AF_INET6 => IpAddr::V6(Ipv6Addr::from(row.Address.Ipv6.sin6_addr.u.Byte)),
+                _ => continue,
+            };
+            map.entry(row.InterfaceIndex)
+                .or_default()
+                .push(crate::connections::format_ip(ip));
+        }
+        FreeMibTable(table as *const _);
+    }
+    map
+}
+
+/// Enumerate interfaces with `getifaddrs` (AF_LINK entries carry counters).
+/// Wi-Fi vs. Ethernet comes from `networksetup`, both report IFT_ETHER.
+#[cfg(target_os = "macos")]
+pub fn list() -> Vec<InterfaceInfo> {
+    use std::ffi::CStr;
+
+    const IFT_ETHER: u8 = 0x06;
+    const IFT_PPP: u8 = 0x17;
+    const IFT_LOOP: u8 = 0x18;
+    const IFT_GIF: u8 = 0x37;
+    const IFT_STF: u8 = 0x39;
+    const IFT_BRIDGE: u8 = 0xd1;
+    const IFT_CELLULAR: u8 = 0xff;
+
+    let wifi_devices = macos_wifi_devices();
+    let mut addresses = unix_addresses();
+    let mut interfaces = Vec::new();
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: the list returned by getifaddrs is only walked before freeifaddrs;
+    // ifa_data of an AF_LINK entry points at an if_data, and sockaddr_dl is
+    // variable-length (sdl_data holds name then address, sized by sdl_len).
+    unsafe {
+        if libc::getifaddrs(&mut addrs) != 0 {
+            return interfaces;
+        }
+        let mut cursor = addrs;
+        while !cursor.is_null() {
+            let ifa = &*cursor;
+            cursor = ifa.ifa_next;
+            if ifa.ifa_addr.is_null()
+                || ifa.ifa_data.is_null()
+                || (*ifa.ifa_addr).sa_family as i32 != libc::AF_LINK
+            {
+                continue;
+            }
+            let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+            let data = &*(ifa.ifa_data as *const libc::if_data);
+            let dl = &*(ifa.ifa_addr as *const libc::sockaddr_dl);
+            let mac_bytes = std::slice::from_raw_parts(
+                (dl.sdl_data.as_ptr() as *const u8).add(dl.sdl_nlen as usize),
+                dl.sdl_alen as usize,
+            );
+            let kind = match data.ifi_type {
+                IFT_LOOP => InterfaceKind::Loopback,
+                IFT_CELLULAR => InterfaceKind::Cellular,
+                IFT_GIF | IFT_STF | IFT_PPP => InterfaceKind::Tunnel,
+                IFT_BRIDGE => InterfaceKind::Virtual,
+                _ if name.starts_with("utun") || name.starts_with("ipsec") => InterfaceKind::Tunnel,
+                _ if name.starts_with("awdl") || name.starts_with("llw") || name.starts_with("anpi") => {
+                    InterfaceKind::Virtual
+                }
+                _ if wifi_devices.contains(&name) => InterfaceKind::Wifi,
+                IFT_ETHER => InterfaceKind::Ethernet,
+                _ => InterfaceKind::Other,
+            };
+            interfaces.push(InterfaceInfo {
+                addresses: addresses.remove(&name).unwrap_or_default(),
+                name,
+                description: None,
+                kind,
+                mac: format_mac(mac_bytes),
+                link_speed_mbps: (data.ifi_baudrate > 0).then_some(data.ifi_baudrate as u64 / 1_000_000),
+                up: ifa.ifa_flags & libc::IFF_UP as u32 != 0,
+                monitored: true,
+                rx_bytes: data.ifi_ibytes as u64,
+                tx_bytes: data.ifi_obytes as u64,
+                rx_bps: 0.0,
+                tx_bps: 0.0,
+            });
+        }
+        libc::freeifaddrs(addrs);
+    }
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// IPv4/IPv6 addresses per interface name.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn unix_addresses() -> HashMap<String, Vec<String>> {
+    use std::ffi::CStr;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: the list is only walked before freeifaddrs, and each ifa_addr
+    // is cast according to its sa_family.
+    unsafe {
+        if libc::getifaddrs(&mut addrs) != 0 {
+            return map;
+        }
+        let mut cursor = addrs;
+        while !cursor.is_null() {
+            let ifa = &*cursor;
+            cursor = ifa.ifa_next;
+            if ifa.ifa_addr.is_null() {
+                continue;
+            }
+            let ip = match (*ifa.ifa_addr).sa_family as i32 {
+                libc::AF_INET => {
+                    let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)))
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr))
+                }
+                _ => continue,
+            };
+            let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+            map.entry(name).or_default().push(crate::connections::format_ip(ip));
+        }
+        libc::freeifaddrs(addrs);
+    }
+    map
+}
+
+/// Device names listed under a "Wi-Fi"/"AirPort" hardware port (cached).
+#[cfg(target_os = "macos")]
+fn macos_wifi_devices() -> &'static [String] {
+    use std::sync::OnceLock;
+    static DEVICES: OnceLock<Vec<String>> = OnceLock::new();
+    DEVICES.get_or_init(|| {
+        let Ok(output) = std::process::Command::new("networksetup")
+            .arg("-listallhardwareports")
+            .output()
+        else {
+            return Vec::new();
+        };
+        let text = String::from_utf8_lossy(&output.stdout);
+        let mut devices = Vec::new();
+        let mut wifi_port = false;
+        for line in text.lines() {
+            if let Some(port) = line.strip_prefix("Hardware Port: ") {
+                wifi_port = port.contains("Wi-Fi") || port.contains("AirPort");
+            } else if let Some(device) = line.strip_prefix("Device: ") {
+                if wifi_port {
+                    devices.push(device.trim().to_string());
+                }
+            }
+        }
+        devices
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+pub fn list() -> Vec<InterfaceInfo> {
+    Vec::new()
+}
@@ -0,0 +1,141 @@
+use crate::{is_private_ip, ParsedConnection};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// An IPv4 connection from the same process and to the same port this close
+/// to a failed IPv6 attempt counts as the fallback for it.
+const FALLBACK_WINDOW: Duration = Duration::from_secs(10);
+
+// ─── Attempts ───────────────────────────────────────────────────────────────
+
+/// Outcome of one outbound TCP connection, for the writer's per-org
+/// IPv4/IPv6 counts.  `org` and `domain` are filled in by the monitor loop
+/// from its geo cache and DNS observer.
+#[derive(Clone, Debug)]
+pub struct FamilyAttempt {
+    pub remote_ip: String,
+    pub ipv6: bool,
+    /// Reached ESTABLISHED.  Only IPv6 attempts are reported when they fail.
+    pub connected: bool,
+    /// A failed IPv6 attempt the process got past over IPv4 (happy eyeballs
+    /// or a retry).
+    pub fell_back: bool,
+    pub org: Option<String>,
+    pub domain: Option<String>,
+    pub timestamp: String,
+}
+
+impl FamilyAttempt {
+    fn new(remote_ip: &str, ipv6: bool, connected: bool, fell_back: bool) -> Self {
+        Self {
+            remote_ip: remote_ip.to_string(),
+            ipv6,
+            connected,
+            fell_back,
+            org: None,
+            domain: None,
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// ─── Tracker ────────────────────────────────────────────────────────────────
+
+struct Socket {
+    remote_ip: String,
+    remote_port: u16,
+    pid: u32,
+    ipv6: bool,
+    connected: bool,
+}
+
+/// A failed IPv6 attempt waiting to see whether IPv4 takes over.
+struct PendingFailure {
+    pid: u32,
+    remote_port: u16,
+    remote_ip: String,
+    failed_at: Instant,
+}
+
+/// Follows outbound TCP sockets across polls.  A socket that leaves the
+/// table without getting past SYN_SENT is a failed attempt; attempts that
+/// fail faster than the poll interval are never seen.
+#[derive(Default)]
+pub struct FamilyTracker {
+    sockets: HashMap<String, Socket>,
+    /// (pid, remote port) → last IPv4 connection.
+    v4_connected: HashMap<(u32, u16), Instant>,
+    pending: Vec<PendingFailure>,
+}
+
+impl FamilyTracker {
+    /// Diff `connections` against the sockets seen so far.  Returns newly
+    /// connected sockets and settled IPv6 failures.
+    pub fn observe(&mut self, connections: &[ParsedConnection]) -> Vec<FamilyAttempt> {
+        let now = Instant::now();
+        let mut attempts = Vec::new();
+        let mut present: Vec<String> = Vec::with_capacity(connections.len());
+
+        for conn in connections {
+            if conn.proto != "tcp" || conn.state == "LISTENING" || conn.remote_port == 0 || is_private_ip(&conn.remote_ip)
+            {
+                continue;
+            }
+            let key = format!("{}:{}>{}:{}", conn.local_ip, conn.local_port, conn.remote_ip, conn.remote_port);
+            let socket = self.sockets.entry(key.clone()).or_insert_with(|| Socket {
+                remote_ip: conn.remote_ip.clone(),
+                remote_port: conn.remote_port,
+                pid: conn.pid,
+                ipv6: conn.remote_ip.contains(':'),
+                connected: false,
+            });
+            present.push(key);
+            if socket.connected || conn.state == "SYN_SENT" {
+                continue;
+            }
+            socket.connected = true;
+            attempts.push(FamilyAttempt::new(&socket.remote_ip, socket.ipv6, true, false));
+            if !socket.ipv6 {
+                self.v4_connected.insert((socket.pid, socket.remote_port), now);
+            }
+        }
+
+        let present: HashSet<String> = present.into_iter().collect();
+        let gone: Vec<String> = self.sockets.keys().filter(|k| !present.contains(*k)).cloned().collect();
+        for key in gone {
+            let Some(socket) = self.sockets.remove(&key) else {
+                continue;
+            };
+            if socket.connected || !socket.ipv6 {
+                continue;
+            }
+            self.pending.push(PendingFailure {
+                pid: socket.pid,
+                remote_port: socket.remote_port,
+                remote_ip: socket.remote_ip,
+                failed_at: now,
+            });
+        }
+
+        // IPv4 usually connects before the IPv6 attempt is abandoned (happy
+        // eyeballs), but a plain retry only starts after it times out
+        let v4_connected = &self.v4_connected;
+        self.pending.retain(|failure| {
+            let fell_back = v4_connected.get(&(failure.pid, failure.remote_port)).is_some_and(|at| {
+                let gap = at
+                    .saturating_duration_since(failure.failed_at)
+                    .max(failure.failed_at.saturating_duration_since(*at));
+                gap < FALLBACK_WINDOW
+            });
+            let expired = failure.failed_at.elapsed() >= FALLBACK_WINDOW;
+            if fell_back || expired {
+                attempts.push(FamilyAttempt::new(&failure.remote_ip, true, false, fell_back));
+                return false;
+            }
+            true
+        });
+        self.v4_connected.retain(|_, at| at.elapsed() < FALLBACK_WINDOW * 2);
+        attempts
+    }
+}
@@ -0,0 +1,202 @@
+use crate::TelemetryFrame;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+// ─── Frame journal ──────────────────────────────────────────────────────────
+
+/// One line of `sessions.db.frames`: the `frames` row a telemetry frame
+/// would produce, written as soon as the writer receives it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub session_id: String,
+    pub t: f64,
+    pub timestamp: String,
+    pub bps: f64,
+    pub pps: u32,
+    pub active_flows: u32,
+    pub latency_ms: f64,
+    pub upload_bps: f64,
+    pub download_bps: f64,
+    /// TCP, UDP, ICMP, DNS, HTTPS, HTTP, other.
+    pub proto: [u32; 7],
+    pub time_wait: Option<u32>,
+    pub ephemeral_ports: Option<u32>,
+    /// Monitor tick the frame was taken at (0 in older journals).
+    #[serde(default)]
+    pub tick_ms: u64,
+}
+
+impl JournalEntry {
+    pub fn new(session_id: &str, timestamp: &str, frame: &TelemetryFrame) -> Self {
+        let p = &frame.proto;
+        Self {
+            session_id: session_id.to_string(),
+            t: frame.t,
+            timestamp: timestamp.to_string(),
+            bps: frame.net.bps,
+            pps: frame.net.pps,
+            active_flows: frame.net.active_flows,
+            latency_ms: frame.net.latency_ms,
+            upload_bps: frame.net.upload_bps,
+            download_bps: frame.net.download_bps,
+            proto: [p.tcp, p.udp, p.icmp, p.dns, p.https, p.http, p.other],
+            time_wait: frame.sockets.map(|s| s.time_wait),
+            ephemeral_ports: frame.sockets.map(|s| s.ephemeral_in_use),
+            tick_ms: frame.rate.map_or(0, |rate| rate.tick_ms),
+        }
+    }
+}
+
+fn journal_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".frames");
+    PathBuf::from(name)
+}
+
+/// Append-only record of the frames the writer has received but not yet
+/// committed.  The writer buffers up to `BATCH_MAX_AGE` of sampled rows and
+/// only keeps every `PersistenceProfile::frame_interval`th frame, so without it a crash
+/// loses the tail of the session; crash recovery replays it instead.
+///
+/// After each committed batch the journal is cut back to the last sampled
+/// frame (already stored, kept as the baseline for integrating bytes) and
+/// the frames received since.
+pub struct FrameJournal {
+    path: PathBuf,
+    /// `None` once a write failed; journaling stays off until reopened.
+    file: Option<File>,
+    /// Lines from the last sampled frame onwards.
+    tail: Vec<String>,
+}
+
+impl FrameJournal {
+    /// Open the journal next to `db_path`, keeping whatever a previous run
+    /// left behind until `read`/`clear`.
+    pub fn open(db_path: &Path) -> Self {
+        let path = journal_path(db_path);
+        let file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("[Abyss][writer] Frame journal unavailable ({}): {e}", path.display());
+                None
+            }
+        };
+        Self {
+            path,
+            file,
+            tail: Vec::new(),
+        }
+    }
+
+    /// Entries left by a previous run.  A line torn by the crash is skipped.
+    pub fn read(&self) -> Vec<JournalEntry> {
+        let Ok(file) = File::open(&self.path) else {
+            return Vec::new();
+        };
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect()
+    }
+
+    /// Journal a frame.  `sampled` frames update the session totals, so the
+    /// ones before them are no longer needed once the batch commits.
+    pub fn append(&mut self, entry: &JournalEntry, sampled: bool) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        if sampled {
+            self.tail.clear();
+        }
+        self.write(format!("{line}\n").as_bytes());
+        self.tail.push(line);
+    }
+
+    /// The pending batch was committed: drop everything before the tail.
+    pub fn checkpoint(&mut self) {
+        let lines: String = self.tail.iter().map(|line| format!("{line}\n")).collect();
+        self.truncate();
+        self.write(lines.as_bytes());
+    }
+
+    /// The session ended (or another started): nothing is left to recover.
+    pub fn clear(&mut self) {
+        self.tail.clear();
+        self.truncate();
+    }
+
+    /// Stop journaling for this database and remove the file.
+    pub fn remove(self) {
+        if self.file.is_some() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn truncate(&mut self) {
+        if let Some(Err(e)) = self.file.as_ref().map(|f| f.set_len(0)) {
+            self.disable(e);
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        if let Err(e) = file.write_all(bytes) {
+            self.disable(e);
+        }
+    }
+
+    fn disable(&mut self, e: std::io::Error) {
+        eprintln!("[Abyss][writer] Frame journal disabled ({}): {e}", self.path.display());
+        self.file = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FrameBuilder;
+
+    fn temp_journal(name: &str) -> FrameJournal {
+        let dir = std::env::temp_dir().join(format!("abyss-journal-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("sessions.db");
+        let _ = std::fs::remove_file(journal_path(&db));
+        FrameJournal::open(&db)
+    }
+
+    fn entry(t: f64) -> JournalEntry {
+        JournalEntry::new("s1", "2026-01-01T00:00:00+00:00", &FrameBuilder::at(t).build())
+    }
+
+    #[test]
+    fn checkpoint_keeps_the_last_sampled_frame_onwards() {
+        let mut journal = temp_journal("checkpoint");
+        for t in 1..=7 {
+            journal.append(&entry(t as f64), t % 5 == 0);
+        }
+        assert_eq!(journal.read().len(), 7);
+
+        journal.checkpoint();
+        let ts: Vec<f64> = journal.read().iter().map(|e| e.t).collect();
+        assert_eq!(ts, [5.0, 6.0, 7.0]);
+
+        // A torn last line (crash mid-write) doesn't hide the rest
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal.path)
+            .unwrap()
+            .write_all(b"{\"sessionId\":\"s1\",\"t\":8")
+            .unwrap();
+        assert_eq!(journal.read().len(), 3);
+
+        journal.clear();
+        assert!(journal.read().is_empty());
+        journal.remove();
+    }
+}
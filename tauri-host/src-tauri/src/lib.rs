@@ -1,24 +1,87 @@
+// Much of the monitor and storage code is only reached from the desktop app;
+// `abyss-cli` builds without it.
+#![cfg_attr(not(feature = "gui"), allow(dead_code, unused_imports))]
+
+pub mod address;
+mod alerts;
+mod anonymize;
+#[cfg(feature = "gui")]
+mod api;
+#[cfg(feature = "gui")]
+mod app;
+mod attribution;
+mod budgets;
+mod capture;
+mod card;
+mod connections;
+mod containers;
+mod datausage;
 mod db;
+mod dbcrypt;
+mod dblock;
+mod dns;
+mod enrich;
+mod error;
+mod exclusions;
+mod fingerprint;
+mod geo;
+pub mod headless;
+mod interfaces;
+mod ipfamily;
+mod journal;
+mod lifecycle;
+mod locks;
+mod monitor;
+mod mtu;
+mod notify;
+mod pacing;
+mod probe;
+mod procmeta;
+mod proctree;
+mod publicip;
+mod rdap;
+mod report;
+mod router;
+mod routes;
+mod schema;
+#[cfg(feature = "gui")]
+mod server;
+mod settings;
+mod threat;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod timelapse;
+#[cfg(feature = "gui")]
+mod tray;
+mod units;
+mod watchlist;
+mod webhooks;
+mod wifi;
 mod writer;
 
+#[cfg(feature = "gui")]
+pub use app::run;
+
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::process::Command as StdCommand;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tauri::Emitter;
-use tauri::Manager;
+use address::is_private_ip;
+use error::AbyssError;
+use locks::LockExt;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x0800_0000;
 
-const SCHEMA_VERSION: u32 = 2;
 const TICK_MS: u64 = 1000;
 const NETSTAT_POLL_MS: u64 = 2000;
-const GEO_API: &str = "http://ip-api.com/batch";
+/// Tick and poll interval while adaptive polling has slowed down.
+const IDLE_POLL_MS: u64 = 5000;
 const MAX_FLOWS_PER_FRAME: usize = 25;
 const GEO_CACHE_MAX_SIZE: usize = 2_000;
 const GEO_CACHE_TTL_SECS: u64 = 10 * 60;
@@ -27,10 +90,18 @@ const GEO_BACKOFF_MAX_SECS: u64 = 30;
 #[cfg(debug_assertions)]
 const PERF_LOG_INTERVAL_SECS: u64 = 10;
 const FLOW_GRACE_SECS: u64 = 8;
-const MATERIAL_FLOW_DELTA: i32 = 2;
+const MATERIAL_FLOW_DELTA: u32 = 2;
 const MATERIAL_THROUGHPUT_DELTA_PCT: f64 = 7.0;
 const MATERIAL_MIN_BPS_DELTA: f64 = 900_000.0;
 const MATERIAL_LATENCY_DELTA_MS: f64 = 10.0;
+/// Maximum time between full frames, even when nothing material changed.
+const KEYFRAME_INTERVAL_SECS: u64 = 15;
+/// How often quota and anomaly alert rules re-read recorded usage.
+const ALERT_USAGE_INTERVAL_SECS: u64 = 60;
+/// Longest window `cmd_get_playback_chunk` returns in one call.
+const MAX_PLAYBACK_CHUNK_SECS: f64 = 3600.0;
+/// How long exit waits for the writer to finalize the open session.
+const WRITER_SHUTDOWN_TIMEOUT_SECS: u64 = 5;
 
 #[derive(Clone, Serialize, Debug)]
 pub struct GeoEndpoint {
@@ -65,6 +136,36 @@ pub struct GeoFlow {
     pub pid: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub state: Option<String>,
+    /// Measured send/receive rates (bits/s); absent when `bps` is estimated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_bps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_bps: Option<f64>,
+    /// How `rtt` was measured; absent when it is an estimate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtt_method: Option<probe::ProbeMethod>,
+    /// Domain the destination was resolved from, when the DNS observer saw it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// TLS server name the client asked for (packet capture only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    /// Behavioural class inferred from the flow's recent rates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_class: Option<fingerprint::ServiceClass>,
+    /// Watched country or ASN the destination belongs to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch: Option<watchlist::WatchTag>,
+    /// Threat feed listing the destination.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threat: Option<String>,
+    /// Top-level app the owning process belongs to (its own name unless it
+    /// is a helper another app started).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_group: Option<String>,
+    /// Docker container or WSL distro behind a VM or proxy process's flow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<containers::ContainerRef>,
 }
 
 #[derive(Clone, Copy, Serialize, Debug, Default)]
@@ -87,6 +188,10 @@ pub struct NetMetrics {
     pub latency_ms: f64,
     pub upload_bps: f64,
     pub download_bps: f64,
+    /// Flows whose throughput came from OS byte counters rather than estimates.
+    pub measured_flows: u32,
+    /// Flows with a probed RTT; when non-zero `latency_ms` averages only those.
+    pub measured_rtt_flows: u32,
 }
 
 #[derive(Clone, Serialize, Debug)]
@@ -98,22 +203,48 @@ pub struct TelemetryFrame {
     pub net: NetMetrics,
     pub proto: ProtoCounters,
     pub flows: Vec<GeoFlow>,
+    /// Busiest interface this tick (Wi-Fi, Ethernet, VPN tunnel, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<interfaces::ActiveInterface>,
+    /// Tick/poll intervals in effect (slower while idle on battery).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<pacing::PollRate>,
+    /// TIME_WAIT and ephemeral port usage (absent on the netstat fallback).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sockets: Option<connections::SocketUsage>,
+    /// Signal and link rate of the Wi-Fi association while traffic goes over it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wifi: Option<wifi::WifiLink>,
+    /// Per-process rates for the writer's `process_usage`; not sent to the UI.
+    #[serde(skip)]
+    pub processes: Vec<attribution::ProcessRate>,
 }
 
-/// Shared application state accessible by Tauri commands and the monitor loop.
-pub struct AppState {
-    /// Channel sender for dispatching write commands to the persistence thread.
-    pub writer_tx: std::sync::mpsc::Sender<writer::WriteCommand>,
-    /// Path to the SQLite database file.
-    pub db_path: PathBuf,
-    /// Currently recording session ID (None if no active session).
-    pub current_session_id: Mutex<Option<String>>,
-    /// Last-known local geo position (set by monitor loop, read by manual starts).
-    pub local_geo: Mutex<LocalGeoCache>,
+/// Compact per-tick metrics for the always-on-top mini widget (`metrics-mini`).
+#[derive(Clone, Copy, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MiniMetrics {
+    pub t: f64,
+    pub upload_bps: f64,
+    pub download_bps: f64,
+    pub latency_ms: f64,
+    pub active_flows: u32,
+}
+
+/// The writer fell behind and dropped queued frames; payload of
+/// `writer-backpressure`.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriterBackpressure {
+    /// Frames dropped since the previous event.
+    pub dropped_frames: u64,
+    pub total_dropped_frames: u64,
+    /// Commands still queued when the writer caught up.
+    pub queue_depth: usize,
 }
 
 /// Cached local geo data for reuse when manually starting sessions.
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize)]
 pub struct LocalGeoCache {
     pub city: String,
     pub country: String,
@@ -121,6 +252,167 @@ pub struct LocalGeoCache {
     pub lng: f64,
 }
 
+/// Snapshot of the geo lookup pipeline.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoPipelineStatus {
+    pub cache_size: usize,
+    pub lookup_in_flight: bool,
+    pub consecutive_failures: u32,
+    /// False when privacy mode has disabled remote lookups (or the provider
+    /// is local-only).
+    pub remote_lookups_enabled: bool,
+    pub provider: geo::GeoProviderKind,
+    /// Seconds until lookups resume (0 when not backing off).
+    pub backoff_remaining_secs: f64,
+    /// Seconds since the last successful batch lookup (None if none yet).
+    pub last_success_secs_ago: Option<f64>,
+    /// A local MaxMind City database is answering lookups before the API.
+    pub local_db_loaded: bool,
+}
+
+/// Everything a freshly loaded window needs to render without waiting for
+/// the next material frame.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorState {
+    pub monitoring: MonitoringStatus,
+    pub frame: Option<TelemetryFrame>,
+    pub local_geo: LocalGeoCache,
+    pub session_id: Option<String>,
+    pub geo: GeoPipelineStatus,
+    pub live_filters: LiveFilters,
+    pub process_filter: ProcessFilter,
+    pub settings: settings::Settings,
+}
+
+/// Whether the monitor loop is running; payload of `monitoring-state`.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitoringStatus {
+    pub paused: bool,
+    pub paused_since: Option<String>,
+}
+
+/// Runtime health information for troubleshooting.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub poison_recoveries: Vec<locks::PoisonRecovery>,
+    pub unparsed_lines: Vec<address::UnparsedLines>,
+}
+
+/// Live-stream focus filters.  Flows that don't match are dropped from the
+/// flow list of the emitted frames only, before it's cut to
+/// `maxFlowsPerFrame`; `net` and `proto` totals still cover all traffic, and
+/// recording, alerts and the watchlist see every flow.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveFilters {
+    pub process: Option<String>,
+    pub country: Option<String>,
+    pub protocol: Option<String>,
+    pub min_bps: Option<f64>,
+}
+
+impl LiveFilters {
+    fn matches(&self, process: Option<&str>, country: &str, protocol: &str, bps: f64) -> bool {
+        if let Some(want) = &self.process {
+            if !process.is_some_and(|p| p.eq_ignore_ascii_case(want)) {
+                return false;
+            }
+        }
+        if let Some(want) = &self.country {
+            if !country.eq_ignore_ascii_case(want) {
+                return false;
+            }
+        }
+        if let Some(want) = &self.protocol {
+            if !protocol.eq_ignore_ascii_case(want) {
+                return false;
+            }
+        }
+        self.min_bps.is_none_or(|min| bps >= min)
+    }
+
+    fn matches_flow(&self, flow: &GeoFlow) -> bool {
+        let protocol = match flow.protocol {
+            1 => "tcp",
+            2 => "udp",
+            3 => "icmp",
+            _ => "other",
+        };
+        self.matches(flow.process.as_deref(), &flow.dst.country, protocol, flow.bps)
+    }
+}
+
+/// Process selection for the live stream.  Flows of other processes are
+/// left out of the emitted flow list (before it's cut to
+/// `maxFlowsPerFrame`) and queued behind the selected ones for remote
+/// geolocation; recording sees every flow.  Names match case-insensitively,
+/// with or without a trailing `.exe`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessFilter {
+    /// Only these processes, when non-empty; flows with no known process are dropped.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl ProcessFilter {
+    fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        let clean = |names: Vec<String>| -> Vec<String> {
+            let mut out: Vec<String> = Vec::new();
+            for name in names.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+                if !out.iter().any(|o| same_process(o, name)) {
+                    out.push(name.to_string());
+                }
+            }
+            out
+        };
+        Self {
+            include: clean(include),
+            exclude: clean(exclude),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    fn matches(&self, process: Option<&str>) -> bool {
+        if !self.include.is_empty() && !process.is_some_and(|p| self.include.iter().any(|i| same_process(i, p))) {
+            return false;
+        }
+        !process.is_some_and(|p| self.exclude.iter().any(|e| same_process(e, p)))
+    }
+}
+
+/// `chrome` matches `Chrome.exe`.
+fn same_process(a: &str, b: &str) -> bool {
+    fn base(name: &str) -> &str {
+        let split = name.len().saturating_sub(4);
+        match name.get(split..) {
+            Some(ext) if ext.eq_ignore_ascii_case(".exe") => &name[..split],
+            _ => name,
+        }
+    }
+    base(a).eq_ignore_ascii_case(base(b))
+}
+
+/// What a single webview window wants to receive on `telemetry-frame`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
+pub enum StreamSubscription {
+    /// Full frames on material change, heartbeats otherwise (the default).
+    #[default]
+    Full,
+    /// Heartbeats only — metrics without the flow list.
+    HeartbeatOnly,
+    /// Full frames with the flow list narrowed by `filters`.
+    Filtered { filters: LiveFilters },
+}
+
 #[derive(Clone, Copy)]
 struct FrameSnapshot {
     active_flows: u32,
@@ -132,10 +424,35 @@ struct FrameSnapshot {
 struct ParsedConnection {
     proto: String,
     local_ip: String,
+    local_port: u16,
     remote_ip: String,
     remote_port: u16,
     state: String,
     pid: u32,
+    /// Cumulative bytes sent/received on this socket, when the OS exposes them.
+    bytes_out: Option<u64>,
+    bytes_in: Option<u64>,
+    /// Cumulative packets in both directions (packet capture only).
+    packets: Option<u64>,
+    /// TLS server name from the connection's ClientHello (packet capture only).
+    sni: Option<String>,
+}
+
+/// Measured throughput for one flow key, summed over its sockets (bits/s).
+#[derive(Clone, Copy, Default)]
+struct FlowRate {
+    tx_bps: f64,
+    rx_bps: f64,
+    /// Packets/s, when the source counts packets.
+    pps: Option<f64>,
+}
+
+/// A socket's cumulative counters as of one poll.
+struct CounterSample {
+    bytes_out: u64,
+    bytes_in: u64,
+    packets: Option<u64>,
+    at: Instant,
 }
 
 #[derive(Clone)]
@@ -166,10 +483,22 @@ struct PerfStats {
     ticks: u32,
     geo_cache_hits: u32,
     geo_cache_misses: u32,
+    /// Time spent in each enrichment stage, in pipeline order.
+    enrich_ms: Vec<(&'static str, f64)>,
+}
+
+impl PerfStats {
+    fn record_stage(&mut self, name: &'static str, ms: f64) {
+        match self.enrich_ms.iter_mut().find(|(stage, _)| *stage == name) {
+            Some((_, total)) => *total += ms,
+            None => self.enrich_ms.push((name, ms)),
+        }
+    }
 }
 
 type GeoTaskResult = (Vec<(String, GeoCacheEntry)>, f64, bool);
 
+#[derive(Clone)]
 struct LocalGeo {
     lat: f64,
     lng: f64,
@@ -177,82 +506,6 @@ struct LocalGeo {
     country: String,
 }
 
-#[derive(Deserialize)]
-struct GeoApiItem {
-    status: String,
-    lat: Option<f64>,
-    lon: Option<f64>,
-    city: Option<String>,
-    #[serde(rename = "countryCode")]
-    country_code: Option<String>,
-    #[serde(rename = "as")]
-    as_field: Option<String>,
-    org: Option<String>,
-    isp: Option<String>,
-}
-
-fn is_private_ip(ip: &str) -> bool {
-    ip.starts_with("10.")
-        || ip.starts_with("192.168.")
-        || (ip.starts_with("172.") && {
-            let second: u8 = ip
-                .split('.')
-                .nth(1)
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(0);
-            (16..=31).contains(&second)
-        })
-        || ip.starts_with("127.")
-        || ip.starts_with("0.")
-        || ip == "::1"
-        || ip == "::"
-        || ip.starts_with("fe80:")
-        || ip.starts_with("fc00:")
-        || ip.starts_with("fd")
-        || ip == "*"
-        // IPv4-mapped IPv6: ::ffff:10.x, ::ffff:192.168.x, etc.
-        || (ip.starts_with("::ffff:") && {
-            let v4 = &ip[7..];
-            is_private_ip(v4)
-        })
-}
-
-fn split_address(addr: &str) -> (String, u16) {
-    // Handle IPv6 in brackets: [::1]:443
-    if let Some(rest) = addr.strip_prefix('[') {
-        if let Some(close) = rest.find(']') {
-            let ip = rest[..close].to_string();
-            let port = rest
-                .get(close + 2..) // skip "]:" 
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(0);
-            return (ip, port);
-        }
-        // Malformed bracket — return as-is
-        return (addr.to_string(), 0);
-    }
-    // Count colons to distinguish IPv6 (bare, no brackets) from IPv4
-    let colon_count = addr.chars().filter(|&c| c == ':').count();
-    if colon_count > 1 {
-        // Bare IPv6 without brackets — last colon separates port
-        if let Some(pos) = addr.rfind(':') {
-            // Only treat as port if what follows is a valid u16
-            if let Ok(port) = addr[pos + 1..].parse::<u16>() {
-                return (addr[..pos].to_string(), port);
-            }
-        }
-        // No valid port found — entire string is the IP
-        return (addr.to_string(), 0);
-    }
-    // IPv4: last colon separates port
-    if let Some(pos) = addr.rfind(':') {
-        let ip = addr[..pos].to_string();
-        let port = addr[pos + 1..].parse().unwrap_or(0);
-        return (ip, port);
-    }
-    (addr.to_string(), 0)
-}
-
 fn protocol_code(proto: &str) -> u8 {
     match proto {
         "tcp" => 1,
@@ -290,6 +543,25 @@ fn service_code(port: u16) -> Option<u8> {
     }
 }
 
+/// Standard padded base64 (WebSocket handshakes, process icons).
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
 fn parse_netstat() -> Vec<ParsedConnection> {
     let mut cmd = StdCommand::new("netstat");
     cmd.args(["-no"]);
@@ -326,8 +598,11 @@ fn parse_netstat() -> Vec<ParsedConnection> {
             continue;
         }
 
-        let (local_ip, _local_port) = split_address(parts[1]);
-        let (remote_ip, remote_port) = split_address(parts[2]);
+        let Some(((local_ip, local_port), (remote_ip, remote_port))) =
+            address::parse_pair("netstat", trimmed, parts[1], parts[2])
+        else {
+            continue;
+        };
 
         // TCP has state field, UDP does not (PID may shift position)
         let (state, pid) = if proto_upper == "TCP" {
@@ -350,89 +625,107 @@ fn parse_netstat() -> Vec<ParsedConnection> {
         connections.push(ParsedConnection {
             proto: proto_upper.to_lowercase(),
             local_ip,
+            local_port,
             remote_ip,
             remote_port,
             state,
             pid,
+            bytes_out: None,
+            bytes_in: None,
+            packets: None,
+            sni: None,
         });
     }
 
     connections
 }
 
-const PROCESS_CACHE_TTL_SECS: u64 = 10;
+/// Set once the netstat fallback has been logged, to avoid repeating it every poll.
+static NETSTAT_FALLBACK_LOGGED: AtomicBool = AtomicBool::new(false);
 
-fn resolve_process_names() -> HashMap<u32, String> {
-    let mut cmd = StdCommand::new("tasklist");
-    cmd.args(["/FO", "CSV", "/NH"]);
-    #[cfg(target_os = "windows")]
-    cmd.creation_flags(CREATE_NO_WINDOW);
-    let output = match cmd.output() {
-        Ok(o) => o,
-        Err(_) => return HashMap::new(),
+/// Reads the native socket table, falling back to `netstat` where that
+/// isn't available.
+fn poll_connections(monitored_interfaces: &[String]) -> (Vec<ParsedConnection>, Option<connections::SocketUsage>) {
+    let (mut conns, usage) = match connections::read_connections() {
+        Ok((conns, usage)) => (conns, Some(usage)),
+        Err(e) => {
+            if !NETSTAT_FALLBACK_LOGGED.swap(true, Ordering::Relaxed) {
+                eprintln!("[Abyss] Native socket table unavailable ({e}) — falling back to netstat");
+            }
+            (parse_netstat(), None)
+        }
     };
+    interfaces::retain_monitored(&mut conns, monitored_interfaces);
+    (conns, usage)
+}
 
-    let raw = String::from_utf8_lossy(&output.stdout);
-    let mut map = HashMap::new();
+/// Packet capture sees traffic but not which process owns it; copy pids
+/// over from the socket table for the same local port and remote endpoint.
+fn attribute_owners(captured: &mut [ParsedConnection], polled: &[ParsedConnection]) {
+    let owners: HashMap<(u16, &str, u16, &str), u32> = polled
+        .iter()
+        .filter(|c| c.pid > 0)
+        .map(|c| ((c.local_port, c.remote_ip.as_str(), c.remote_port, c.proto.as_str()), c.pid))
+        .collect();
+    for conn in captured.iter_mut() {
+        let key = (conn.local_port, conn.remote_ip.as_str(), conn.remote_port, conn.proto.as_str());
+        if let Some(&pid) = owners.get(&key) {
+            conn.pid = pid;
+        }
+    }
+}
 
-    for line in raw.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
+/// Turns cumulative socket byte counters into per-flow rates.  `counters`
+/// holds the previous poll's sample per socket and is replaced with
+/// this poll's values.  Sockets seen for the first time get a zero rate —
+/// known to be measurable, but with no interval to measure yet.
+fn measure_flow_rates(
+    connections: &[ParsedConnection],
+    counters: &mut HashMap<String, CounterSample>,
+) -> HashMap<String, FlowRate> {
+    let now = Instant::now();
+    let mut rates: HashMap<String, FlowRate> = HashMap::new();
+    let mut next: HashMap<String, CounterSample> = HashMap::with_capacity(connections.len());
+
+    for conn in connections {
+        let (Some(out), Some(inb)) = (conn.bytes_out, conn.bytes_in) else {
             continue;
-        }
-        // Format: "name.exe","1234","Console","1","12,345 K"
-        let mut fields = Vec::new();
-        let mut in_quote = false;
-        let mut field = String::new();
-        for ch in trimmed.chars() {
-            match ch {
-                '"' => in_quote = !in_quote,
-                ',' if !in_quote => {
-                    fields.push(std::mem::take(&mut field));
-                    if fields.len() >= 2 {
-                        break;
-                    }
-                }
-                _ => field.push(ch),
-            }
-        }
-        if !field.is_empty() {
-            fields.push(field);
-        }
-        if fields.len() >= 2 {
-            if let Ok(pid) = fields[1].trim().parse::<u32>() {
-                let name = fields[0].trim().to_string();
-                if !name.is_empty() && pid > 0 {
-                    map.insert(pid, name);
+        };
+        let socket_key = format!(
+            "{}:{}:{}:{}",
+            conn.local_port, conn.remote_ip, conn.remote_port, conn.proto
+        );
+        let rate = rates
+            .entry(format!("{}:{}:{}", conn.remote_ip, conn.remote_port, conn.proto))
+            .or_default();
+        if let Some(prev) = counters.get(&socket_key) {
+            let dt = now.duration_since(prev.at).as_secs_f64();
+            if dt > 0.0 {
+                rate.tx_bps += out.saturating_sub(prev.bytes_out) as f64 * 8.0 / dt;
+                rate.rx_bps += inb.saturating_sub(prev.bytes_in) as f64 * 8.0 / dt;
+                if let (Some(pkts), Some(prev_pkts)) = (conn.packets, prev.packets) {
+                    *rate.pps.get_or_insert(0.0) += pkts.saturating_sub(prev_pkts) as f64 / dt;
                 }
             }
         }
+        next.insert(
+            socket_key,
+            CounterSample {
+                bytes_out: out,
+                bytes_in: inb,
+                packets: conn.packets,
+                at: now,
+            },
+        );
     }
 
-    map
+    *counters = next;
+    rates
 }
 
-async fn detect_local_geo(client: &reqwest::Client) -> LocalGeo {
-    if let Ok(resp) = client
-        .get("http://ip-api.com/json/?fields=lat,lon,city,countryCode")
-        .send()
-        .await
-    {
-        if let Ok(data) = resp.json::<serde_json::Value>().await {
-            return LocalGeo {
-                lat: data["lat"].as_f64().unwrap_or(40.71),
-                lng: data["lon"].as_f64().unwrap_or(-74.01),
-                city: data["city"]
-                    .as_str()
-                    .unwrap_or("Unknown")
-                    .to_string(),
-                country: data["countryCode"]
-                    .as_str()
-                    .unwrap_or("US")
-                    .to_string(),
-            };
-        }
-    }
+const PROCESS_CACHE_TTL_SECS: u64 = 10;
+
+fn fallback_local_geo() -> LocalGeo {
     LocalGeo {
         lat: 40.71,
         lng: -74.01,
@@ -441,97 +734,54 @@ async fn detect_local_geo(client: &reqwest::Client) -> LocalGeo {
     }
 }
 
+async fn detect_local_geo(provider: &dyn geo::GeoProvider, client: &reqwest::Client) -> LocalGeo {
+    match provider.locate_self(client.clone()).await {
+        Some(info) => LocalGeo {
+            lat: info.lat,
+            lng: info.lng,
+            city: info.city,
+            country: info.country,
+        },
+        None => fallback_local_geo(),
+    }
+}
+
+/// Resolve up to `provider.max_batch()` IPs into cache entries.  Returns
+/// false when the batch failed (rate limit, network) so the caller backs off.
 async fn geolocate_batch(
+    provider: Arc<dyn geo::GeoProvider>,
     client: reqwest::Client,
     ips: Vec<String>,
+    ttl: Duration,
 ) -> (Vec<(String, GeoCacheEntry)>, bool) {
     if ips.is_empty() {
         return (Vec::new(), true);
     }
 
-    let batch: Vec<String> = ips.into_iter().take(100).collect();
-    let body: Vec<serde_json::Value> = batch
-        .iter()
-        .map(|ip| {
-            serde_json::json!({
-                "query": ip,
-                "fields": "status,lat,lon,city,countryCode,as,org,isp"
-            })
-        })
-        .collect();
-
-    let mut updates = Vec::with_capacity(batch.len());
-    let mut success = false;
-
-    match client.post(GEO_API).json(&body).send().await {
-        Ok(resp) => {
-            // Handle rate limiting (HTTP 429)
-            if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-                eprintln!("[Abyss] GeoIP rate limited (429) — will retry with backoff");
-                return (Vec::new(), false);
-            }
-            if !resp.status().is_success() {
-                eprintln!("[Abyss] GeoIP batch HTTP {}", resp.status());
-                return (Vec::new(), false);
-            }
-            if let Ok(results) = resp.json::<Vec<GeoApiItem>>().await {
-                success = true;
-                for (i, r) in results.iter().enumerate() {
-                    if i >= batch.len() {
-                        break;
-                    }
-                    if r.status == "success" {
-                        // ip-api "as" field looks like "AS15169 Google LLC" — extract just the AS number
-                        let asn_raw = r.as_field.clone().unwrap_or_default();
-                        let asn = asn_raw
-                            .split_whitespace()
-                            .next()
-                            .unwrap_or("")
-                            .to_string();
-                        // Prefer org over isp, trim whitespace
-                        let org = r
-                            .org
-                            .clone()
-                            .or_else(|| r.isp.clone())
-                            .map(|s| s.trim().to_string())
-                            .unwrap_or_default();
-                        updates.push((
-                            batch[i].clone(),
-                            GeoCacheEntry {
-                                value: Some(GeoInfo {
-                                    lat: r.lat.unwrap_or(0.0),
-                                    lng: r.lon.unwrap_or(0.0),
-                                    city: r.city.clone().unwrap_or_else(|| "Unknown".into()),
-                                    country: r
-                                        .country_code
-                                        .clone()
-                                        .unwrap_or_else(|| "??".into()),
-                                    asn,
-                                    org,
-                                }),
-                                expires_at: Instant::now() + Duration::from_secs(GEO_CACHE_TTL_SECS),
-                                last_access: Instant::now(),
-                            },
-                        ));
-                    } else {
-                        updates.push((
-                            batch[i].clone(),
-                            GeoCacheEntry {
-                                value: None,
-                                expires_at: Instant::now() + Duration::from_secs(GEO_CACHE_TTL_SECS),
-                                last_access: Instant::now(),
-                            },
-                        ));
-                    }
-                }
-            }
+    let batch: Vec<String> = ips.into_iter().take(provider.max_batch()).collect();
+    match provider.lookup(client, batch).await {
+        Ok(results) => {
+            let now = Instant::now();
+            let updates = results
+                .into_iter()
+                .map(|(ip, value)| {
+                    (
+                        ip,
+                        GeoCacheEntry {
+                            value,
+                            expires_at: now + ttl,
+                            last_access: now,
+                        },
+                    )
+                })
+                .collect();
+            (updates, true)
         }
         Err(e) => {
-            eprintln!("[Abyss] GeoIP batch failed: {e}");
+            eprintln!("[Abyss] GeoIP lookup failed ({:?}): {e} — will retry with backoff", provider.kind());
+            (Vec::new(), false)
         }
     }
-
-    (updates, success)
 }
 
 fn prune_geo_cache(cache: &mut HashMap<String, GeoCacheEntry>) {
@@ -563,6 +813,79 @@ fn prune_geo_cache(cache: &mut HashMap<String, GeoCacheEntry>) {
     });
 }
 
+/// Resolve destinations not already cached from the local MMDB databases,
+/// if any are loaded.
+fn lookup_local_geo(
+    geo_db: &geo::GeoDatabases,
+    connections: &[ParsedConnection],
+    cache: &mut HashMap<String, GeoCacheEntry>,
+    ttl: Duration,
+) {
+    if !geo_db.is_loaded() {
+        return;
+    }
+    let now = Instant::now();
+    for conn in connections {
+        if is_private_ip(&conn.remote_ip) || cache.get(&conn.remote_ip).is_some_and(|entry| entry.expires_at > now) {
+            continue;
+        }
+        if let Some(info) = geo_db.lookup(&conn.remote_ip) {
+            cache.insert(
+                conn.remote_ip.clone(),
+                GeoCacheEntry {
+                    value: Some(info),
+                    expires_at: now + ttl,
+                    last_access: now,
+                },
+            );
+        }
+    }
+}
+
+/// Cache a placeholder location ("??", at `local`) for public destinations
+/// nothing resolved, so their flows are kept rather than dropped by the
+/// `Geo` stage.
+fn placeholder_geo(
+    connections: &[ParsedConnection],
+    local: &LocalGeo,
+    cache: &mut HashMap<String, GeoCacheEntry>,
+    ttl: Duration,
+) {
+    let now = Instant::now();
+    for conn in connections {
+        if is_private_ip(&conn.remote_ip) {
+            continue;
+        }
+        cache.entry(conn.remote_ip.clone()).or_insert_with(|| GeoCacheEntry {
+            value: Some(GeoInfo {
+                lat: local.lat,
+                lng: local.lng,
+                city: "Unknown".into(),
+                country: "??".into(),
+                asn: String::new(),
+                org: String::new(),
+            }),
+            expires_at: now + ttl,
+            last_access: now,
+        });
+    }
+}
+
+/// Record this poll's connections in `presence` and return every one seen
+/// within `FLOW_GRACE_SECS`, so flows don't flicker between polls.
+fn smooth_presence(
+    presence: &mut HashMap<String, (ParsedConnection, Instant)>,
+    connections: &[ParsedConnection],
+) -> Vec<ParsedConnection> {
+    let now = Instant::now();
+    for conn in connections {
+        let key = format!("{}:{}:{}", conn.remote_ip, conn.remote_port, conn.proto);
+        presence.insert(key, (conn.clone(), now));
+    }
+    presence.retain(|_, (_, last_seen)| now.duration_since(*last_seen) < Duration::from_secs(FLOW_GRACE_SECS));
+    presence.values().map(|(conn, _)| conn.clone()).collect()
+}
+
 fn get_geo_cached<'a>(
     cache: &'a mut HashMap<String, GeoCacheEntry>,
     ip: &str,
@@ -589,27 +912,18 @@ fn get_geo_cached<'a>(
     None
 }
 
-#[allow(clippy::too_many_arguments)]
+/// This tick's frame with every resolved flow, busiest first.  Each
+/// consumer cuts the list to `max_flows_per_frame` itself (`focus_frame`
+/// for the UI, `truncate_flows` for everything else).
 fn build_frame(
     connections: &[ParsedConnection],
-    geo_cache: &mut HashMap<String, GeoCacheEntry>,
     prev_keys: &mut HashSet<String>,
     local: &LocalGeo,
     elapsed: f64,
-    perf: &mut PerfStats,
-    process_names: &HashMap<u32, String>,
     flow_first_seen: &mut HashMap<String, f64>,
+    pipeline: &enrich::Pipeline,
+    ctx: &mut enrich::TickContext,
 ) -> TelemetryFrame {
-    let round2 = |v: f64| (v * 100.0).round() / 100.0;
-    let fnv1a = |s: &str| -> u32 {
-        let mut h: u32 = 2_166_136_261;
-        for b in s.bytes() {
-            h ^= b as u32;
-            h = h.wrapping_mul(16_777_619);
-        }
-        h
-    };
-
     let mut flow_map: HashMap<String, &ParsedConnection> = HashMap::with_capacity(connections.len());
     for conn in connections {
         // Build key without format! — avoids extra allocation from formatting machinery
@@ -634,84 +948,31 @@ fn build_frame(
         flow_map.entry(key).or_insert(conn);
     }
 
-    let mut flows = Vec::with_capacity(flow_map.len().min(MAX_FLOWS_PER_FRAME));
+    let mut flows = Vec::with_capacity(flow_map.len());
     let mut proto = ProtoCounters::default();
     let mut total_up: f64 = 0.0;
     let mut total_down: f64 = 0.0;
+    let mut total_pps: u32 = 0;
+    let mut rtt_sum: f64 = 0.0;
+    let mut resolved_flows: u32 = 0;
+    let mut measured_flows: u32 = 0;
+    let mut measured_rtt_sum: f64 = 0.0;
+    let mut measured_rtt_flows: u32 = 0;
 
     for (key, conn) in &flow_map {
-        let geo = match get_geo_cached(geo_cache, &conn.remote_ip, perf) {
-            Some(g) => g,
-            _ => continue,
-        };
-
-        let base_bps: f64 = match conn.remote_port {
-            443 => 50_000.0,
-            80 => 30_000.0,
-            53 => 500.0,
-            22 => 5_000.0,
-            _ => 10_000.0,
-        };
-
-        let existed = prev_keys.contains(key);
-        let key_hash = fnv1a(key);
-        let bps_factor = if existed {
-            0.7 + (key_hash % 60) as f64 / 100.0
-        } else {
-            2.0
-        };
-        let estimated_bps = base_bps * bps_factor;
-
-        let dir = if conn.state == "ESTABLISHED" || conn.state == "STATELESS" {
-            if key_hash % 2 == 0 {
-                "up"
-            } else {
-                "down"
-            }
-        } else {
-            "bidi"
-        };
-
-        let process_name = if conn.pid > 0 {
-            process_names.get(&conn.pid).cloned()
-        } else {
-            None
-        };
-
-        let first_seen = *flow_first_seen.entry(key.clone()).or_insert(elapsed);
+        let mut draft = enrich::FlowDraft::new(key, conn, local, prev_keys.contains(key));
+        if !pipeline.run(&mut draft, ctx) {
+            continue;
+        }
+        draft.flow.started_at = *flow_first_seen.entry(key.clone()).or_insert(elapsed);
 
-        flows.push(GeoFlow {
-            id: format!("live-{key}"),
-            src: GeoEndpoint {
-                ip: conn.local_ip.clone(),
-                lat: local.lat,
-                lng: local.lng,
-                city: local.city.clone(),
-                country: local.country.clone(),
-                asn: None,
-                org: None,
-            },
-            dst: GeoEndpoint {
-                ip: conn.remote_ip.clone(),
-                lat: round2(geo.lat),
-                lng: round2(geo.lng),
-                city: geo.city.clone(),
-                country: geo.country.clone(),
-                asn: if !geo.asn.is_empty() { Some(geo.asn.clone()) } else { None },
-                org: if !geo.org.is_empty() { Some(geo.org.clone()) } else { None },
-            },
-            bps: (estimated_bps / 10.0).round() * 10.0,
-            pps: (estimated_bps / 1000.0).max(1.0) as u32,
-            rtt: round2(10.0 + (key_hash % 600) as f64 / 10.0),
-            protocol: protocol_code(&conn.proto),
-            dir: dir.to_string(),
-            port: conn.remote_port,
-            service: service_code(conn.remote_port),
-            started_at: first_seen,
-            process: process_name,
-            pid: if conn.pid > 0 { Some(conn.pid) } else { None },
-            state: if !conn.state.is_empty() && conn.state != "STATELESS" { Some(conn.state.clone()) } else { None },
-        });
+        if let Some(ms) = draft.measured_rtt_ms {
+            measured_rtt_sum += ms;
+            measured_rtt_flows += 1;
+        }
+        resolved_flows += 1;
+        total_pps += draft.flow.pps;
+        rtt_sum += draft.flow.rtt;
 
         match conn.remote_port {
             443 => proto.https += 1,
@@ -722,14 +983,21 @@ fn build_frame(
         match conn.proto.as_str() {
             "tcp" => proto.tcp += 1,
             "udp" => proto.udp += 1,
+            "icmp" => proto.icmp += 1,
             _ => proto.other += 1,
         }
 
-        if dir == "up" {
-            total_up += estimated_bps;
+        if let Some(rate) = draft.measured {
+            measured_flows += 1;
+            total_up += rate.tx_bps;
+            total_down += rate.rx_bps;
+        } else if draft.flow.dir == "up" {
+            total_up += draft.raw_bps;
         } else {
-            total_down += estimated_bps;
+            total_down += draft.raw_bps;
         }
+
+        flows.push(draft.flow);
     }
 
     prev_keys.clear();
@@ -740,22 +1008,20 @@ fn build_frame(
     flow_first_seen.retain(|k, _| prev_keys.contains(k));
 
     let total_bps = total_up + total_down;
-    let total_pps: u32 = flows.iter().map(|f| f.pps).sum();
-    let avg_rtt = if flows.is_empty() {
+    let avg_rtt = if measured_rtt_flows > 0 {
+        measured_rtt_sum / measured_rtt_flows as f64
+    } else if resolved_flows == 0 {
         0.0
     } else {
-        flows.iter().map(|f| f.rtt).sum::<f64>() / flows.len() as f64
+        rtt_sum / resolved_flows as f64
     };
 
-    let active_flow_count = flows.len() as u32;
+    let active_flow_count = resolved_flows;
     // Sort by throughput descending so the most active flows survive truncation
-    if flows.len() > MAX_FLOWS_PER_FRAME {
-        flows.sort_unstable_by(|a, b| b.bps.partial_cmp(&a.bps).unwrap_or(std::cmp::Ordering::Equal));
-    }
-    flows.truncate(MAX_FLOWS_PER_FRAME);
+    flows.sort_unstable_by(|a, b| b.bps.partial_cmp(&a.bps).unwrap_or(std::cmp::Ordering::Equal));
 
     TelemetryFrame {
-        schema: SCHEMA_VERSION,
+        schema: schema::SCHEMA_VERSION,
         t: elapsed,
         light: None,
         net: NetMetrics {
@@ -765,974 +1031,157 @@ fn build_frame(
             latency_ms: avg_rtt,
             upload_bps: total_up,
             download_bps: total_down,
+            measured_flows,
+            measured_rtt_flows,
         },
         proto,
         flows,
+        interface: None,
+        rate: None,
+        sockets: None,
+        wifi: None,
+        processes: Vec::new(),
     }
 }
 
-fn is_material_change(prev: Option<FrameSnapshot>, next: &TelemetryFrame) -> bool {
+fn is_material_change(prev: Option<FrameSnapshot>, next: &TelemetryFrame, tuning: &settings::Settings) -> bool {
     let Some(previous) = prev else {
         return true;
     };
 
-    let flow_delta = previous.active_flows as i32 - next.net.active_flows as i32;
-    if flow_delta.abs() >= MATERIAL_FLOW_DELTA {
+    if previous.active_flows.abs_diff(next.net.active_flows) >= tuning.material_flow_delta {
         return true;
     }
 
     let baseline_bps = previous.bps.max(1.0);
     let throughput_abs_delta = (next.net.bps - previous.bps).abs();
     let throughput_delta_pct = (throughput_abs_delta / baseline_bps) * 100.0;
-    if throughput_abs_delta >= MATERIAL_MIN_BPS_DELTA
-        && throughput_delta_pct >= MATERIAL_THROUGHPUT_DELTA_PCT
+    if throughput_abs_delta >= tuning.material_min_bps_delta
+        && throughput_delta_pct >= tuning.material_throughput_delta_pct
     {
         return true;
     }
 
-    (next.net.latency_ms - previous.latency_ms).abs() >= MATERIAL_LATENCY_DELTA_MS
+    (next.net.latency_ms - previous.latency_ms).abs() >= tuning.material_latency_delta_ms
 }
 
-async fn monitor_loop(app: tauri::AppHandle, writer_tx: std::sync::mpsc::Sender<writer::WriteCommand>) {
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .unwrap_or_default();
-
-    println!("[Abyss] Detecting local geo position...");
-    let local_geo = detect_local_geo(&client).await;
-    println!(
-        "[Abyss] Local: {}, {} ({:.2}, {:.2})",
-        local_geo.city, local_geo.country, local_geo.lat, local_geo.lng
-    );
-
-    // Cache the detected geo in AppState for manual session starts
-    if let Some(state) = app.try_state::<AppState>() {
-        if let Ok(mut geo_cache) = state.local_geo.lock() {
-            geo_cache.city = local_geo.city.clone();
-            geo_cache.country = local_geo.country.clone();
-            geo_cache.lat = local_geo.lat;
-            geo_cache.lng = local_geo.lng;
-        }
-    }
-
-    // Auto-start a recording session with detected local geo
-    {
-        let session_id = uuid::Uuid::new_v4().to_string();
-        let now = chrono::Local::now();
-        let session_name = now.format("Session \u{2014} %b %d, %Y %I:%M %p").to_string();
-        let _ = writer_tx.send(writer::WriteCommand::StartSession {
-            id: session_id.clone(),
-            name: session_name,
-            local_city: local_geo.city.clone(),
-            local_country: local_geo.country.clone(),
-            local_lat: local_geo.lat,
-            local_lng: local_geo.lng,
-        });
-        if let Some(state) = app.try_state::<AppState>() {
-            *state.current_session_id.lock().unwrap_or_else(|e| e.into_inner()) =
-                Some(session_id.clone());
-        }
-        println!("[Abyss] Session started: {session_id}");
-    }
-
-    let mut geo_cache: HashMap<String, GeoCacheEntry> = HashMap::with_capacity(256);
-    let mut prev_keys: HashSet<String> = HashSet::with_capacity(64);
-    let start = Instant::now();
-    let mut last_geo_lookup = Instant::now() - Duration::from_secs(10);
-    let mut geo_task: Option<tokio::task::JoinHandle<GeoTaskResult>> = None;
-    let mut geo_failures: u32 = 0;
-    let mut geo_backoff_until: Option<Instant> = None;
-    let mut last_netstat_poll = Instant::now() - Duration::from_millis(NETSTAT_POLL_MS);
-    let mut cached_connections: Vec<ParsedConnection> = Vec::new();
-    #[cfg(debug_assertions)]
-    let mut last_perf_log = Instant::now();
-    let mut last_snapshot: Option<FrameSnapshot> = None;
-    let mut perf = PerfStats::default();
-    let mut flow_presence: HashMap<String, (ParsedConnection, Instant)> = HashMap::new();
-    let mut process_names: HashMap<u32, String> = HashMap::new();
-    let mut last_process_refresh = Instant::now() - Duration::from_secs(PROCESS_CACHE_TTL_SECS + 1);
-    let mut last_forced_process_refresh = Instant::now();
-    let mut flow_first_seen: HashMap<String, f64> = HashMap::new();
-
-    println!("[Abyss] Monitor started — emitting telemetry-frame events @ 1 Hz");
-
-    loop {
-        perf.cycles += 1;
-        let connections: Vec<ParsedConnection> =
-            if last_netstat_poll.elapsed() >= Duration::from_millis(NETSTAT_POLL_MS) {
-                let parse_started = Instant::now();
-                let parsed: Vec<ParsedConnection> = tokio::task::spawn_blocking(parse_netstat)
-                    .await
-                    .unwrap_or_default();
-                perf.parse_netstat_ms += parse_started.elapsed().as_secs_f64() * 1000.0;
-                cached_connections = parsed;
-                last_netstat_poll = Instant::now();
-                cached_connections.clone()
-            } else {
-                cached_connections.clone()
-            };
-
-        prune_geo_cache(&mut geo_cache);
-
-        if let Some(task) = geo_task.take() {
-            if task.is_finished() {
-                match task.await {
-                    Ok((updates, elapsed_ms, success)) => {
-                        for (ip, entry) in updates {
-                            geo_cache.insert(ip, entry);
-                        }
-                        if success {
-                            geo_failures = 0;
-                            geo_backoff_until = None;
-                        } else {
-                            geo_failures = geo_failures.saturating_add(1);
-                            let backoff_secs = (GEO_BACKOFF_MIN_SECS
-                                * 2_u64.pow(geo_failures.saturating_sub(1).min(4)))
-                            .min(GEO_BACKOFF_MAX_SECS);
-                            geo_backoff_until = Some(
-                                Instant::now() + Duration::from_secs(backoff_secs),
-                            );
-                        }
-                        perf.geolocate_batch_ms += elapsed_ms;
-                    }
-                    Err(e) => {
-                        eprintln!("[Abyss] Geo task join failed: {e}");
-                        geo_failures = geo_failures.saturating_add(1);
-                        let backoff_secs = (GEO_BACKOFF_MIN_SECS
-                            * 2_u64.pow(geo_failures.saturating_sub(1).min(4)))
-                        .min(GEO_BACKOFF_MAX_SECS);
-                        geo_backoff_until =
-                            Some(Instant::now() + Duration::from_secs(backoff_secs));
-                    }
-                }
-            } else {
-                geo_task = Some(task);
-            }
-        }
-
-        let geo_backoff_active = geo_backoff_until
-            .map(|until| until > Instant::now())
-            .unwrap_or(false);
-
-        if geo_task.is_none()
-            && !geo_backoff_active
-            && last_geo_lookup.elapsed() > Duration::from_secs(3)
-        {
-            let now = Instant::now();
-            let remote_ips: Vec<String> = connections
-                .iter()
-                .map(|c| c.remote_ip.clone())
-                .collect::<HashSet<_>>()
-                .into_iter()
-                .filter(|ip| {
-                    !is_private_ip(ip)
-                        && !geo_cache
-                            .get(ip)
-                            .map(|entry| entry.expires_at > now)
-                            .unwrap_or(false)
-                })
-                .take(100)
-                .collect();
-
-            if !remote_ips.is_empty() {
-                let client_clone = client.clone();
-                geo_task = Some(tokio::spawn(async move {
-                    let started = Instant::now();
-                    let (updates, success) = geolocate_batch(client_clone, remote_ips).await;
-                    (updates, started.elapsed().as_secs_f64() * 1000.0, success)
-                }));
-            }
-            last_geo_lookup = Instant::now();
-        }
-
-        // Flow presence smoothing: keep recently-seen connections visible
-        let presence_now = Instant::now();
-        for conn in &connections {
-            let key = format!("{}:{}:{}", conn.remote_ip, conn.remote_port, conn.proto);
-            flow_presence.insert(key, (conn.clone(), presence_now));
-        }
-        flow_presence.retain(|_, (_, last_seen)| {
-            presence_now.duration_since(*last_seen) < Duration::from_secs(FLOW_GRACE_SECS)
-        });
-        let stable_connections: Vec<ParsedConnection> =
-            flow_presence.values().map(|(conn, _)| conn.clone()).collect();
-
-        // Only spawn tasklist when new PIDs appear or every 60s as fallback
-        if last_process_refresh.elapsed() >= Duration::from_secs(PROCESS_CACHE_TTL_SECS) {
-            let has_new_pids = stable_connections
-                .iter()
-                .any(|c| c.pid > 0 && !process_names.contains_key(&c.pid));
-            let force_refresh = last_forced_process_refresh.elapsed() >= Duration::from_secs(60);
-            if has_new_pids || force_refresh {
-                process_names = tokio::task::spawn_blocking(resolve_process_names)
-                    .await
-                    .unwrap_or_default();
-                last_forced_process_refresh = Instant::now();
-            }
-            // Always reset check timer to avoid rescanning every tick
-            last_process_refresh = Instant::now();
-        }
-
-        let build_started = Instant::now();
-        let frame = build_frame(
-            &stable_connections,
-            &mut geo_cache,
-            &mut prev_keys,
-            &local_geo,
-            start.elapsed().as_secs_f64(),
-            &mut perf,
-            &process_names,
-            &mut flow_first_seen,
-        );
-        perf.build_frame_ms += build_started.elapsed().as_secs_f64() * 1000.0;
-
-        let material = is_material_change(last_snapshot, &frame);
-        let should_emit_heartbeat = !material;
-
-        if material {
-            let emit_started = Instant::now();
-            // Compute payload size BEFORE emit to avoid double serialization
-            if cfg!(debug_assertions) {
-                perf.ws_payload_bytes += serde_json::to_vec(&frame).map_or(0, |v| v.len());
-            }
-            let _ = app.emit("telemetry-frame", &frame);
-            perf.emit_frame_ms += emit_started.elapsed().as_secs_f64() * 1000.0;
-            last_snapshot = Some(FrameSnapshot {
-                active_flows: frame.net.active_flows,
-                bps: frame.net.bps,
-                latency_ms: frame.net.latency_ms,
-            });
-            perf.ticks += 1;
-        } else if should_emit_heartbeat {
-            // Build heartbeat directly without cloning flows vec
-            let heartbeat = TelemetryFrame {
-                schema: frame.schema,
-                t: frame.t,
-                light: Some(true),
-                net: frame.net,
-                proto: frame.proto,
-                flows: Vec::new(),
-            };
-
-            let emit_started = Instant::now();
-            if cfg!(debug_assertions) {
-                perf.ws_payload_bytes += serde_json::to_vec(&heartbeat).map_or(0, |v| v.len());
-            }
-            let _ = app.emit("telemetry-frame", &heartbeat);
-            perf.emit_frame_ms += emit_started.elapsed().as_secs_f64() * 1000.0;
-            perf.ticks += 1;
-        }
-
-        #[cfg(debug_assertions)]
-        {
-            let flow_count = frame.flows.len();
-            if flow_count > 0 {
-                let mbps = (frame.net.bps * 8.0) / 1_000_000.0;
-                println!(
-                    "[Abyss] {} flows | {:.1} Mbps | {} geo cached",
-                    flow_count, mbps, geo_cache.len()
-                );
-            }
-
-            if last_perf_log.elapsed() >= Duration::from_secs(PERF_LOG_INTERVAL_SECS)
-                && perf.cycles > 0
-            {
-                let cycles = perf.cycles as f64;
-                let ticks = perf.ticks.max(1) as f64;
-                let hit_total = perf.geo_cache_hits + perf.geo_cache_misses;
-                let hit_rate = if hit_total > 0 {
-                    (perf.geo_cache_hits as f64 * 100.0) / hit_total as f64
-                } else {
-                    0.0
-                };
-                println!(
-                    "[Abyss][perf] parse={:.1}ms geo={:.1}ms build={:.1}ms emit={:.1}ms payload={:.1}KB hit={:.1}% cache={}",
-                    perf.parse_netstat_ms / cycles,
-                    perf.geolocate_batch_ms / cycles,
-                    perf.build_frame_ms / cycles,
-                    perf.emit_frame_ms / ticks,
-                    perf.ws_payload_bytes as f64 / ticks / 1024.0,
-                    hit_rate,
-                    geo_cache.len()
-                );
-
-                perf = PerfStats::default();
-                last_perf_log = Instant::now();
-            }
-        }
-
-        // Send frame to writer for session persistence (writer handles sampling)
-        let _ = writer_tx.send(writer::WriteCommand::Frame(Box::new(frame)));
-
-        tokio::time::sleep(Duration::from_millis(TICK_MS)).await;
-    }
-}
-
-#[tauri::command]
-async fn fetch_cables() -> Result<String, String> {
-    let url = "https://www.submarinecablemap.com/api/v3/cable/cable-geo.json";
-    let resp = reqwest::get(url).await.map_err(|e| e.to_string())?;
-    if !resp.status().is_success() {
-        return Err(format!("Cable fetch failed with status {}", resp.status()));
-    }
-    let text = resp.text().await.map_err(|e| e.to_string())?;
-
-    // Simplify cable coordinates — keep every 3rd point to reduce JS heap by ~60%.
-    // Preserves first and last points of each line for correct endpoints.
-    let mut parsed: serde_json::Value = serde_json::from_str(&text)
-        .map_err(|e| format!("Failed to parse cable JSON: {e}"))?;
-
-    if let Some(features) = parsed.get_mut("features").and_then(|v| v.as_array_mut()) {
-        for feature in features.iter_mut() {
-            if let Some(coords) = feature
-                .get_mut("geometry")
-                .and_then(|g| g.get_mut("coordinates"))
-                .and_then(|c| c.as_array_mut())
-            {
-                for line in coords.iter_mut() {
-                    if let Some(points) = line.as_array() {
-                        let len = points.len();
-                        if len > 6 {
-                            let simplified: Vec<serde_json::Value> = points
-                                .iter()
-                                .enumerate()
-                                .filter(|(i, _)| *i == 0 || *i == len - 1 || *i % 3 == 0)
-                                .map(|(_, v)| v.clone())
-                                .collect();
-                            *line = serde_json::Value::Array(simplified);
-                        }
-                    }
-                }
-            }
-        }
+/// Lightweight copy of a frame without the flow list.
+fn heartbeat_of(frame: &TelemetryFrame) -> TelemetryFrame {
+    TelemetryFrame {
+        schema: frame.schema,
+        t: frame.t,
+        light: Some(true),
+        net: frame.net,
+        proto: frame.proto,
+        flows: Vec::new(),
+        interface: frame.interface.clone(),
+        rate: frame.rate,
+        sockets: frame.sockets,
+        wifi: frame.wifi.clone(),
+        processes: Vec::new(),
     }
-
-    let simplified = serde_json::to_string(&parsed)
-        .map_err(|e| format!("Failed to serialize simplified cables: {e}"))?;
-    #[cfg(debug_assertions)]
-    println!(
-        "[Abyss] Fetched submarine cable data ({} bytes raw, {} bytes simplified)",
-        text.len(),
-        simplified.len()
-    );
-    Ok(simplified)
 }
 
-// ─── Session management Tauri commands ──────────────────────────────────────
-
-#[tauri::command]
-async fn cmd_list_sessions(
-    state: tauri::State<'_, AppState>,
-    limit: Option<u32>,
-    offset: Option<u32>,
-) -> Result<Vec<db::SessionInfo>, String> {
-    let db_path = state.db_path.clone();
-    let limit = limit.unwrap_or(50);
-    let offset = offset.unwrap_or(0);
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::list_sessions(&conn, limit, offset).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
+/// Cut the flow list of a `build_frame` frame to its `max_flows` busiest.
+fn truncate_flows(frame: &mut TelemetryFrame, max_flows: usize) {
+    frame.flows.truncate(max_flows);
 }
 
-#[tauri::command]
-async fn cmd_get_session(
-    state: tauri::State<'_, AppState>,
-    id: String,
-) -> Result<Option<db::SessionInfo>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_session(&conn, &id).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_delete_session(
-    state: tauri::State<'_, AppState>,
-    id: String,
-) -> Result<bool, String> {
-    // Prevent deleting the currently recording session
-    {
-        let guard = state
-            .current_session_id
-            .lock()
-            .map_err(|e| e.to_string())?;
-        if guard.as_deref() == Some(id.as_str()) {
-            return Err("Cannot delete the active recording session".into());
-        }
+/// The frame the UI gets: flows matching every filter in `filters` and
+/// `processes`, then the `max_flows` busiest of those, so a focused view
+/// gets the whole budget.  `frame` must still hold every flow.
+fn focus_frame(
+    frame: &TelemetryFrame,
+    filters: &[&LiveFilters],
+    processes: &ProcessFilter,
+    max_flows: usize,
+) -> TelemetryFrame {
+    let flows = frame
+        .flows
+        .iter()
+        .filter(|flow| processes.matches(flow.process.as_deref()) && filters.iter().all(|f| f.matches_flow(flow)))
+        .take(max_flows)
+        .cloned()
+        .collect();
+    TelemetryFrame {
+        schema: frame.schema,
+        t: frame.t,
+        light: frame.light,
+        net: frame.net,
+        proto: frame.proto,
+        flows,
+        interface: frame.interface.clone(),
+        rate: frame.rate,
+        sockets: frame.sockets,
+        wifi: frame.wifi.clone(),
+        processes: Vec::new(),
     }
-
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::delete_session(&conn, &id).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_session_frames(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    start_t: Option<f64>,
-    end_t: Option<f64>,
-    max_points: Option<u32>,
-) -> Result<Vec<db::FrameRecord>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_session_frames(&conn, &session_id, start_t, end_t, max_points)
-            .map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_session_flows(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    process_filter: Option<String>,
-    country_filter: Option<String>,
-    limit: Option<u32>,
-) -> Result<Vec<db::FlowSnapshotRecord>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_session_flows(
-            &conn,
-            &session_id,
-            process_filter.as_deref(),
-            country_filter.as_deref(),
-            limit.unwrap_or(100),
-        )
-        .map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
 }
 
-#[tauri::command]
-async fn cmd_get_session_destinations(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    sort_by: Option<String>,
-    limit: Option<u32>,
-) -> Result<Vec<db::DestinationRecord>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_session_destinations(
-            &conn,
-            &session_id,
-            sort_by.as_deref().unwrap_or("bytes"),
-            limit.unwrap_or(50),
-        )
-        .map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_process_usage(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    process_name: Option<String>,
-    limit: Option<u32>,
-) -> Result<Vec<db::ProcessUsageRecord>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_process_usage(
-            &conn,
-            &session_id,
-            process_name.as_deref(),
-            limit.unwrap_or(500),
-        )
-        .map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_global_stats(
-    state: tauri::State<'_, AppState>,
-) -> Result<db::GlobalStats, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_global_stats(&conn, &db_path).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-fn cmd_update_session_meta(
-    state: tauri::State<'_, AppState>,
-    id: String,
-    name: Option<String>,
-    notes: Option<String>,
-    tags: Option<String>,
-) -> Result<(), String> {
-    state
-        .writer_tx
-        .send(writer::WriteCommand::UpdateMeta {
-            id,
-            name,
-            notes,
-            tags,
-        })
-        .map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn cmd_start_session(
-    state: tauri::State<'_, AppState>,
-    name: Option<String>,
-) -> Result<String, String> {
-    // Stop any existing session first
-    {
-        let mut guard = state
-            .current_session_id
-            .lock()
-            .map_err(|e| e.to_string())?;
-        if let Some(old_id) = guard.take() {
-            let _ = state
-                .writer_tx
-                .send(writer::WriteCommand::EndSession { id: old_id });
-        }
+// ─── Session exports ────────────────────────────────────────────────────────
+
+/// A session's flow snapshots as CSV, with the number of flows written.
+fn session_csv(
+    conn: &rusqlite::Connection,
+    session_id: &str,
+    anonymizer: Option<&anonymize::Anonymizer>,
+) -> Result<(db::SessionInfo, usize, String), AbyssError> {
+    let session = db::get_session(conn, session_id)?
+        .ok_or_else(|| AbyssError::NotFound("Session not found".into()))?;
+    let mut flows = db::get_session_flows(conn, session_id, None, None, 50000)?;
+    if let Some(anonymizer) = anonymizer {
+        flows.iter_mut().for_each(|f| anonymizer.flow(f));
     }
 
-    let session_id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Local::now();
-    let session_name =
-        name.unwrap_or_else(|| now.format("Session \u{2014} %b %d, %Y %I:%M %p").to_string());
-
-    // Use cached geo data so manually-started sessions have correct map coordinates
-    let geo = state
-        .local_geo
-        .lock()
-        .map(|g| g.clone())
-        .unwrap_or_default();
-
-    state
-        .writer_tx
-        .send(writer::WriteCommand::StartSession {
-            id: session_id.clone(),
-            name: session_name,
-            local_city: geo.city,
-            local_country: geo.country,
-            local_lat: geo.lat,
-            local_lng: geo.lng,
-        })
-        .map_err(|e| e.to_string())?;
-
-    *state
-        .current_session_id
-        .lock()
-        .map_err(|e| e.to_string())? = Some(session_id.clone());
-
-    Ok(session_id)
-}
-
-#[tauri::command]
-fn cmd_stop_session(state: tauri::State<'_, AppState>) -> Result<Option<String>, String> {
-    let mut guard = state
-        .current_session_id
-        .lock()
-        .map_err(|e| e.to_string())?;
-    if let Some(id) = guard.take() {
-        let _ = state
-            .writer_tx
-            .send(writer::WriteCommand::EndSession { id: id.clone() });
-        Ok(Some(id))
-    } else {
-        Ok(None)
+    let mut csv = String::with_capacity(flows.len() * 200);
+    csv.push_str("flow_id,src_ip,src_city,src_country,dst_ip,dst_city,dst_country,dst_org,bps,pps,rtt_ms,protocol,direction,port,service,process,pid\n");
+
+    for f in &flows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            escape_csv(&f.flow_id),
+            escape_csv(f.src_ip.as_deref().unwrap_or("")),
+            escape_csv(f.src_city.as_deref().unwrap_or("")),
+            escape_csv(f.src_country.as_deref().unwrap_or("")),
+            escape_csv(&f.dst_ip),
+            escape_csv(f.dst_city.as_deref().unwrap_or("")),
+            escape_csv(f.dst_country.as_deref().unwrap_or("")),
+            escape_csv(f.dst_org.as_deref().unwrap_or("")),
+            f.bps,
+            f.pps,
+            f.rtt,
+            escape_csv(f.protocol.as_deref().unwrap_or("")),
+            escape_csv(f.dir.as_deref().unwrap_or("")),
+            f.port.unwrap_or(0),
+            escape_csv(f.service.as_deref().unwrap_or("")),
+            escape_csv(f.process.as_deref().unwrap_or("")),
+            f.pid.unwrap_or(0),
+        ));
     }
+    Ok((session, flows.len(), csv))
 }
 
-#[tauri::command]
-fn cmd_get_current_session(state: tauri::State<'_, AppState>) -> Result<Option<String>, String> {
-    let guard = state
-        .current_session_id
-        .lock()
-        .map_err(|e| e.to_string())?;
-    Ok(guard.clone())
-}
-
-#[tauri::command]
-async fn cmd_cleanup_sessions(
-    state: tauri::State<'_, AppState>,
-    days: Option<u32>,
-) -> Result<u32, String> {
-    let db_path = state.db_path.clone();
-    let days = days.unwrap_or(90);
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::cleanup_old_sessions(&conn, days).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_cleanup_excess_sessions(
-    state: tauri::State<'_, AppState>,
-    max_count: u32,
-) -> Result<u32, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::cleanup_excess_sessions(&conn, max_count).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_delete_all_sessions(
-    state: tauri::State<'_, AppState>,
-) -> Result<u32, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::delete_all_sessions(&conn).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_database_path(
-    state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
-    Ok(db::get_database_path(&state.db_path))
-}
-
-#[tauri::command]
-async fn cmd_open_data_folder(
-    state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let db_path = state.db_path.clone();
-    let folder = db_path
-        .parent()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|| db_path.to_string_lossy().to_string());
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("explorer")
-            .arg(&folder)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(&folder)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&folder)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+/// A session and everything recorded in it, as `cmd_export_session_json`
+/// writes it.
+fn session_bundle(
+    conn: &rusqlite::Connection,
+    session_id: &str,
+    anonymizer: Option<&anonymize::Anonymizer>,
+) -> Result<db::SessionBundle, AbyssError> {
+    let session = db::get_session(conn, session_id)?
+        .ok_or_else(|| AbyssError::NotFound("Session not found".into()))?;
+    let mut bundle = db::SessionBundle {
+        session,
+        frames: db::get_session_frames(conn, session_id, None, None, None)?,
+        flows: db::get_session_flows(conn, session_id, None, None, 50000)?,
+        destinations: db::get_session_destinations(conn, session_id, "bytes", 1000)?,
+        processes: db::get_process_usage(conn, session_id, None, 5000)?,
+    };
+    if let Some(anonymizer) = anonymizer {
+        bundle.flows.iter_mut().for_each(|f| anonymizer.flow(f));
+        bundle.destinations.iter_mut().for_each(|d| anonymizer.destination(d));
     }
-    Ok(())
-}
-
-#[tauri::command]
-async fn cmd_get_playback_data(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-) -> Result<db::PlaybackData, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_playback_data(&conn, &session_id)
-            .map_err(|e| e.to_string())?
-            .ok_or_else(|| "Session not found".to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_daily_usage(
-    state: tauri::State<'_, AppState>,
-    range_days: u32,
-) -> Result<Vec<db::DailyUsage>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_daily_usage(&conn, range_days).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_top_destinations(
-    state: tauri::State<'_, AppState>,
-    range_days: u32,
-    limit: u32,
-) -> Result<Vec<db::TopDestination>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_top_destinations(&conn, range_days, limit).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_top_apps(
-    state: tauri::State<'_, AppState>,
-    range_days: u32,
-    limit: u32,
-) -> Result<Vec<db::TopApp>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_top_apps(&conn, range_days, limit).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_session_insights(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-) -> Result<db::SessionInsights, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::compute_session_insights(&conn, &session_id).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-// ─── Tier 6: Baseline, Anomaly, Health, Tagging ─────────────────────────────
-
-#[tauri::command]
-async fn cmd_compute_baseline(
-    state: tauri::State<'_, AppState>,
-    range_days: Option<u32>,
-) -> Result<u32, String> {
-    let db_path = state.db_path.clone();
-    let days = range_days.unwrap_or(90);
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::compute_baseline(&conn, days).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_baseline(
-    state: tauri::State<'_, AppState>,
-) -> Result<Vec<db::BaselineEntry>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::get_baseline_profile(&conn).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_detect_anomalies(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-) -> Result<Vec<db::Anomaly>, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::detect_anomalies(&conn, &session_id).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_get_health_score(
-    state: tauri::State<'_, AppState>,
-    hours: Option<u32>,
-) -> Result<db::HealthScore, String> {
-    let db_path = state.db_path.clone();
-    let h = hours.unwrap_or(24);
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::compute_health_score(&conn, h).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_search_sessions(
-    state: tauri::State<'_, AppState>,
-    query: String,
-    limit: Option<u32>,
-) -> Result<Vec<db::SessionInfo>, String> {
-    let db_path = state.db_path.clone();
-    let lim = limit.unwrap_or(50);
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::search_sessions(&conn, &query, lim).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_update_session_tags(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    tags: Vec<String>,
-) -> Result<(), String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        db::update_session_tags(&conn, &session_id, &tags).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_export_session_csv(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    path: String,
-) -> Result<String, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        let session = db::get_session(&conn, &session_id)
-            .map_err(|e| e.to_string())?
-            .ok_or_else(|| "Session not found".to_string())?;
-        let flows = db::get_session_flows(&conn, &session_id, None, None, 50000)
-            .map_err(|e| e.to_string())?;
-
-        let mut csv = String::with_capacity(flows.len() * 200);
-        csv.push_str("flow_id,src_ip,src_city,src_country,dst_ip,dst_city,dst_country,dst_org,bps,pps,rtt_ms,protocol,direction,port,service,process,pid\n");
-
-        for f in &flows {
-            csv.push_str(&format!(
-                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
-                escape_csv(&f.flow_id),
-                escape_csv(f.src_ip.as_deref().unwrap_or("")),
-                escape_csv(f.src_city.as_deref().unwrap_or("")),
-                escape_csv(f.src_country.as_deref().unwrap_or("")),
-                escape_csv(&f.dst_ip),
-                escape_csv(f.dst_city.as_deref().unwrap_or("")),
-                escape_csv(f.dst_country.as_deref().unwrap_or("")),
-                escape_csv(f.dst_org.as_deref().unwrap_or("")),
-                f.bps,
-                f.pps,
-                f.rtt,
-                escape_csv(f.protocol.as_deref().unwrap_or("")),
-                escape_csv(f.dir.as_deref().unwrap_or("")),
-                f.port.unwrap_or(0),
-                escape_csv(f.service.as_deref().unwrap_or("")),
-                escape_csv(f.process.as_deref().unwrap_or("")),
-                f.pid.unwrap_or(0),
-            ));
-        }
-
-        // Ensure parent directory exists
-        if let Some(parent) = std::path::Path::new(&path).parent() {
-            if !parent.exists() {
-                return Err(format!("Export directory does not exist: {}", parent.display()));
-            }
-        }
-
-        std::fs::write(&path, &csv).map_err(|e| format!("Failed to write CSV: {e}"))?;
-        Ok(format!(
-            "Exported {} flows from '{}' to {}",
-            flows.len(),
-            session.name,
-            path
-        ))
-    })
-    .await
-    .map_err(|e| e.to_string())?
-}
-
-#[tauri::command]
-async fn cmd_export_session_json(
-    state: tauri::State<'_, AppState>,
-    session_id: String,
-    path: String,
-) -> Result<String, String> {
-    let db_path = state.db_path.clone();
-    tokio::task::spawn_blocking(move || {
-        let conn = db::open_database(&db_path).map_err(|e| e.to_string())?;
-        let session = db::get_session(&conn, &session_id)
-            .map_err(|e| e.to_string())?
-            .ok_or_else(|| "Session not found".to_string())?;
-        let frames = db::get_session_frames(&conn, &session_id, None, None, None)
-            .map_err(|e| e.to_string())?;
-        let flows = db::get_session_flows(&conn, &session_id, None, None, 50000)
-            .map_err(|e| e.to_string())?;
-        let destinations = db::get_session_destinations(&conn, &session_id, "bytes", 1000)
-            .map_err(|e| e.to_string())?;
-        let processes = db::get_process_usage(&conn, &session_id, None, 5000)
-            .map_err(|e| e.to_string())?;
-
-        #[derive(serde::Serialize)]
-        #[serde(rename_all = "camelCase")]
-        struct ExportPayload {
-            session: db::SessionInfo,
-            frames: Vec<db::FrameRecord>,
-            flows: Vec<db::FlowSnapshotRecord>,
-            destinations: Vec<db::DestinationRecord>,
-            processes: Vec<db::ProcessUsageRecord>,
-        }
-
-        let payload = ExportPayload {
-            session,
-            frames,
-            flows,
-            destinations,
-            processes,
-        };
-
-        let json = serde_json::to_string_pretty(&payload)
-            .map_err(|e| format!("JSON serialization failed: {e}"))?;
-
-        // Ensure parent directory exists
-        if let Some(parent) = std::path::Path::new(&path).parent() {
-            if !parent.exists() {
-                return Err(format!("Export directory does not exist: {}", parent.display()));
-            }
-        }
-
-        std::fs::write(&path, &json).map_err(|e| format!("Failed to write JSON: {e}"))?;
-        Ok(format!(
-            "Exported session '{}' to {}",
-            payload.session.name, path
-        ))
-    })
-    .await
-    .map_err(|e| e.to_string())?
+    Ok(bundle)
 }
 
 /// Escape a string for CSV (wrap in quotes if it contains commas, quotes, newlines, or carriage returns).
@@ -1744,153 +1193,47 @@ fn escape_csv(s: &str) -> String {
     }
 }
 
-// ─── Application entry point ────────────────────────────────────────────────
-
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![
-            fetch_cables,
-            cmd_list_sessions,
-            cmd_get_session,
-            cmd_delete_session,
-            cmd_get_session_frames,
-            cmd_get_session_flows,
-            cmd_get_session_destinations,
-            cmd_get_process_usage,
-            cmd_get_global_stats,
-            cmd_update_session_meta,
-            cmd_start_session,
-            cmd_stop_session,
-            cmd_get_current_session,
-            cmd_cleanup_sessions,
-            cmd_export_session_csv,
-            cmd_export_session_json,
-            cmd_get_playback_data,
-            cmd_get_daily_usage,
-            cmd_get_top_destinations,
-            cmd_get_top_apps,
-            cmd_get_session_insights,
-            cmd_cleanup_excess_sessions,
-            cmd_delete_all_sessions,
-            cmd_get_database_path,
-            cmd_open_data_folder,
-            cmd_compute_baseline,
-            cmd_get_baseline,
-            cmd_detect_anomalies,
-            cmd_get_health_score,
-            cmd_search_sessions,
-            cmd_update_session_tags,
-        ])
-        .on_window_event(|window, event| {
-            if let tauri::WindowEvent::Destroyed = event {
-                if let Some(state) = window.try_state::<AppState>() {
-                    let _ = state.writer_tx.send(writer::WriteCommand::Shutdown);
-                    println!("[Abyss] Shutdown signal sent to writer");
-                }
-            }
-        })
-        .setup(|app| {
-            println!("╔════════════════════════════════════════╗");
-            println!("║   ABYSS — Live Network Monitor         ║");
-            println!("╚════════════════════════════════════════╝");
-
-            // Resolve database path in app-local data directory
-            let app_data = app
-                .path()
-                .app_local_data_dir()
-                .expect("Failed to resolve app data directory");
-            std::fs::create_dir_all(&app_data).ok();
-            let db_path = app_data.join("sessions.db");
-            println!("[Abyss] Database: {}", db_path.display());
-
-            // Create writer channel
-            let (writer_tx, writer_rx) = writer::create_channel();
-
-            // Register shared state (session starts inside monitor_loop after geo detection)
-            app.manage(AppState {
-                writer_tx: writer_tx.clone(),
-                db_path: db_path.clone(),
-                current_session_id: Mutex::new(None),
-                local_geo: Mutex::new(LocalGeoCache::default()),
-            });
-
-            // Spawn writer thread (dedicated OS thread for blocking SQLite I/O)
-            let writer_db_path = db_path.clone();
-            let baseline_db_path = db_path.clone();
-            std::thread::spawn(move || {
-                writer::writer_thread(writer_rx, writer_db_path);
-            });
-
-            // Spawn monitor loop (auto-starts a session after geo detection)
-            let handle = app.handle().clone();
-            let monitor_tx = writer_tx.clone();
-            tauri::async_runtime::spawn(async move {
-                monitor_loop(handle, monitor_tx).await;
-            });
-
-            // Spawn auto-baseline recomputation (weekly, first run after 60s)
-            tauri::async_runtime::spawn(async move {
-                // Initial delay to let the app settle
-                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
-                loop {
-                    // Check if baseline needs recomputing (last update > 7 days ago)
-                    let needs_update = {
-                        let path = baseline_db_path.clone();
-                        tokio::task::spawn_blocking(move || {
-                            if let Ok(conn) = db::open_database(&path) {
-                                let last_update: String = conn
-                                    .query_row(
-                                        "SELECT COALESCE(MAX(updated_at), '2000-01-01') FROM baseline_profile",
-                                        [],
-                                        |row| row.get(0),
-                                    )
-                                    .unwrap_or_else(|_| "2000-01-01".to_string());
-                                // Check if older than 7 days
-                                let days_old: f64 = conn
-                                    .query_row(
-                                        "SELECT julianday('now') - julianday(?1)",
-                                        rusqlite::params![last_update],
-                                        |row| row.get(0),
-                                    )
-                                    .unwrap_or(999.0);
-                                days_old > 7.0
-                            } else {
-                                false
-                            }
-                        })
-                        .await
-                        .unwrap_or(false)
-                    };
-
-                    if needs_update {
-                        let path = baseline_db_path.clone();
-                        let _ = tokio::task::spawn_blocking(move || {
-                            if let Ok(conn) = db::open_database(&path) {
-                                match db::compute_baseline(&conn, 90) {
-                                    Ok(n) => println!("[Abyss] Auto-baseline recomputed: {n} buckets"),
-                                    Err(e) => eprintln!("[Abyss] Auto-baseline failed: {e}"),
-                                }
-                            }
-                        })
-                        .await;
-                    }
-
-                    // Sleep for 6 hours before checking again
-                    tokio::time::sleep(std::time::Duration::from_secs(6 * 3600)).await;
-                }
-            });
-
-            #[cfg(debug_assertions)]
-            {
-                let window = app
-                    .get_webview_window("main")
-                    .expect("Failed to get main window");
-                window.open_devtools();
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{FlowBuilder, FrameBuilder};
+
+    /// 40 flows, busiest first as `build_frame` leaves them, with slack's
+    /// the quietest.
+    fn crowded_frame() -> TelemetryFrame {
+        let mut flows: Vec<GeoFlow> = (0..39)
+            .map(|i| FlowBuilder::new(&format!("f{i}")).process("chrome.exe", 10).bps(100_000.0 - i as f64).build())
+            .collect();
+        flows.push(FlowBuilder::new("quiet").process("slack.exe", 20).dst("1.1.1.1", "AU").bps(10.0).build());
+        FrameBuilder::at(1.0).flows(flows).build()
+    }
 
-            Ok(())
-        })
-        .run(tauri::generate_context!())
-        .expect("Failed to run Abyss application");
+    #[test]
+    fn filters_apply_before_the_flow_budget() {
+        let frame = crowded_frame();
+        let slack = LiveFilters {
+            process: Some("slack.exe".into()),
+            ..Default::default()
+        };
+        let focused = focus_frame(&frame, &[&slack], &ProcessFilter::default(), 35);
+        assert_eq!(focused.flows.len(), 1);
+        assert_eq!(focused.flows[0].id, "quiet");
+
+        let selected = ProcessFilter::new(vec!["Slack".into()], Vec::new());
+        let focused = focus_frame(&frame, &[], &selected, 35);
+        assert_eq!(focused.flows.len(), 1);
+        let australia = LiveFilters {
+            country: Some("au".into()),
+            ..Default::default()
+        };
+        assert_eq!(focus_frame(&frame, &[&slack, &australia], &selected, 35).flows.len(), 1);
+
+        // Unfocused, the budget goes to the busiest
+        let unfocused = focus_frame(&frame, &[], &ProcessFilter::default(), 35);
+        assert_eq!(unfocused.flows.len(), 35);
+        assert_eq!(unfocused.flows[0].id, "f0");
+        let mut recorded = frame;
+        truncate_flows(&mut recorded, 35);
+        assert!(recorded.flows.iter().all(|f| f.id != "quiet"));
+    }
 }
@@ -0,0 +1,177 @@
+use crate::{FlowRate, ParsedConnection};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Rate samples further apart than this aren't integrated into byte totals
+/// (the monitor was paused or stalled).
+const MAX_BYTE_GAP_SECS: f64 = 10.0;
+
+// ─── Flow events ────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlowEventKind {
+    Opened,
+    Closed,
+}
+
+impl FlowEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FlowEventKind::Opened => "opened",
+            FlowEventKind::Closed => "closed",
+        }
+    }
+}
+
+/// A connection appearing in or dropping out of the live flow set, emitted
+/// as `flow-opened` / `flow-closed` and persisted to `flow_events`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowEvent {
+    pub kind: FlowEventKind,
+    /// Same id as the flow in telemetry frames (`live-<ip>:<port>:<proto>`).
+    pub flow_id: String,
+    pub local_ip: String,
+    pub remote_ip: String,
+    pub remote_port: u16,
+    pub proto: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// When this event happened (`opened_at` or `closed_at`).
+    pub at: String,
+    pub opened_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    /// Bytes counted from OS byte counters while open; absent when the flow
+    /// was never measured (estimated rates aren't totalled).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_up: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_down: Option<f64>,
+}
+
+/// The remote address in a flow id (`live-<ip>:<port>:<proto>`).
+pub fn flow_id_ip(flow_id: &str) -> Option<&str> {
+    let mut parts = flow_id.strip_prefix("live-")?.rsplitn(3, ':');
+    let (_proto, _port) = (parts.next()?, parts.next()?);
+    parts.next().filter(|ip| !ip.is_empty())
+}
+
+// ─── Tracker ────────────────────────────────────────────────────────────────
+
+struct OpenFlow {
+    opened: FlowEvent,
+    first_seen: Instant,
+    last_seen: Instant,
+    bytes: Option<(f64, f64)>,
+}
+
+/// Turns the monitor loop's flow presence map into open/close events.  A flow
+/// closes when it leaves the map (after its grace period), dated to when it
+/// was last seen rather than when the grace period ran out.
+#[derive(Default)]
+pub struct FlowTracker {
+    open: HashMap<String, OpenFlow>,
+    last_update: Option<Instant>,
+}
+
+impl FlowTracker {
+    /// Diff `presence` (flow key → connection, last seen) against the flows
+    /// already open and add this interval's measured bytes.  Returns opens
+    /// followed by closes.
+    pub fn update(
+        &mut self,
+        presence: &HashMap<String, (ParsedConnection, Instant)>,
+        rates: &HashMap<String, FlowRate>,
+        process_names: &HashMap<u32, String>,
+    ) -> Vec<FlowEvent> {
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map(|at| now.duration_since(at).as_secs_f64())
+            .filter(|dt| *dt <= MAX_BYTE_GAP_SECS)
+            .unwrap_or(0.0);
+        self.last_update = Some(now);
+
+        let mut events = Vec::new();
+        for (key, (conn, last_seen)) in presence {
+            let process = (conn.pid > 0).then(|| process_names.get(&conn.pid).cloned()).flatten();
+            let flow = self.open.entry(key.clone()).or_insert_with(|| {
+                let opened_at = at_instant(*last_seen);
+                let opened = FlowEvent {
+                    kind: FlowEventKind::Opened,
+                    flow_id: format!("live-{key}"),
+                    local_ip: conn.local_ip.clone(),
+                    remote_ip: conn.remote_ip.clone(),
+                    remote_port: conn.remote_port,
+                    proto: conn.proto.clone(),
+                    process: process.clone(),
+                    pid: (conn.pid > 0).then_some(conn.pid),
+                    at: opened_at.clone(),
+                    opened_at,
+                    closed_at: None,
+                    duration_secs: None,
+                    bytes_up: None,
+                    bytes_down: None,
+                };
+                events.push(opened.clone());
+                OpenFlow {
+                    opened,
+                    first_seen: *last_seen,
+                    last_seen: *last_seen,
+                    bytes: None,
+                }
+            });
+            flow.last_seen = *last_seen;
+            // Names resolve a tick or two after the socket appears
+            if flow.opened.process.is_none() {
+                flow.opened.process = process;
+            }
+            if let Some(rate) = rates.get(key) {
+                let (up, down) = flow.bytes.get_or_insert((0.0, 0.0));
+                *up += rate.tx_bps / 8.0 * dt;
+                *down += rate.rx_bps / 8.0 * dt;
+            }
+        }
+
+        let closed: Vec<String> = self.open.keys().filter(|k| !presence.contains_key(*k)).cloned().collect();
+        for key in closed {
+            if let Some(flow) = self.open.remove(&key) {
+                events.push(close(flow));
+            }
+        }
+        events
+    }
+
+    /// Close every open flow (monitoring paused or stopped).
+    pub fn close_all(&mut self) -> Vec<FlowEvent> {
+        self.last_update = None;
+        self.open.drain().map(|(_, flow)| close(flow)).collect()
+    }
+}
+
+fn close(flow: OpenFlow) -> FlowEvent {
+    let closed_at = at_instant(flow.last_seen);
+    FlowEvent {
+        kind: FlowEventKind::Closed,
+        at: closed_at.clone(),
+        closed_at: Some(closed_at),
+        duration_secs: Some(flow.last_seen.duration_since(flow.first_seen).as_secs_f64()),
+        bytes_up: flow.bytes.map(|(up, _)| up.round()),
+        bytes_down: flow.bytes.map(|(_, down)| down.round()),
+        ..flow.opened
+    }
+}
+
+/// Wall-clock time of a monotonic `instant` in the past.
+fn at_instant(instant: Instant) -> String {
+    let ago = chrono::Duration::from_std(instant.elapsed()).unwrap_or_default();
+    (Utc::now() - ago).to_rfc3339()
+}
@@ -0,0 +1,60 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+// ─── Poison-tolerant locking ────────────────────────────────────────────────
+
+/// Per-lock poison recovery counters, reported through `cmd_get_diagnostics`.
+static RECOVERIES: Mutex<BTreeMap<&'static str, PoisonRecovery>> = Mutex::new(BTreeMap::new());
+
+/// How often a named lock was found poisoned and recovered.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoisonRecovery {
+    pub lock: &'static str,
+    pub count: u64,
+    pub last_at: String,
+}
+
+/// Locking that never fails: a poisoned mutex (a thread panicked while
+/// holding it) is cleared, recorded, and its data handed back as-is.  All
+/// `AppState` values stay internally consistent across a panic, so there is
+/// nothing to repair — failing every later command would be strictly worse.
+pub trait LockExt<T> {
+    fn lock_or_recover(&self, name: &'static str) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self, name: &'static str) -> MutexGuard<'_, T> {
+        match self.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                self.clear_poison();
+                record_recovery(name);
+                poisoned.into_inner()
+            }
+        }
+    }
+}
+
+fn record_recovery(name: &'static str) {
+    eprintln!("[Abyss] Recovered poisoned lock '{name}'");
+    let mut map = RECOVERIES.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = map.entry(name).or_insert(PoisonRecovery {
+        lock: name,
+        count: 0,
+        last_at: String::new(),
+    });
+    entry.count += 1;
+    entry.last_at = chrono::Utc::now().to_rfc3339();
+}
+
+/// All poison recoveries since startup, ordered by lock name.
+pub fn poison_recoveries() -> Vec<PoisonRecovery> {
+    RECOVERIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,695 @@
+//! The monitor loop shared by the desktop app and `abyss-cli`: each tick
+//! polls connections, geolocates and enriches them, runs the probes and
+//! alert rules and hands the frame to the writer.  Whatever the program
+//! running it shows or keeps outside the database (events, tray, shared
+//! state) goes through `MonitorHost`.
+
+use crate::alerts::{self, AlertAction, AlertEvent, AlertRule};
+use crate::routes::RouteChange;
+use crate::publicip::NetworkChange;
+use crate::*;
+
+/// What the monitor loop and its watchers (`routes::watch`,
+/// `publicip::watch`, `threat::watch`) read from and report to the program
+/// running them.  Reporting methods default to doing nothing.
+pub trait MonitorHost: Send + Sync {
+    /// Current settings, read every tick.
+    fn settings(&self) -> settings::Settings;
+    /// Wakes on every settings change.
+    fn subscribe_settings(&self) -> tokio::sync::watch::Receiver<settings::Settings>;
+    fn db_path(&self) -> PathBuf;
+    /// Local MaxMind databases consulted before the remote geo API.
+    fn geo_db(&self) -> Arc<Mutex<geo::GeoDatabases>>;
+    /// The session being recorded, if any.
+    fn session_id(&self) -> Option<String>;
+    fn alert_rules(&self) -> Vec<AlertRule>;
+    fn watchlist(&self) -> watchlist::Watchlist;
+    fn threats(&self) -> Arc<threat::ThreatIndex>;
+    /// The map origin; follows public address changes.
+    fn local_geo(&self) -> LocalGeo;
+    fn set_local_geo(&self, geo: &LocalGeo);
+    fn set_threats(&self, index: Arc<threat::ThreatIndex>);
+    /// Wakes `publicip::watch` early when the default route changes.
+    fn public_ip_recheck(&self) -> &tokio::sync::Notify;
+
+    /// Packet capture, when it can replace socket polling.
+    fn capture(&self) -> Option<&capture::Capture> {
+        None
+    }
+    /// Processes whose destinations are geolocated first.
+    fn process_filter(&self) -> ProcessFilter {
+        ProcessFilter::default()
+    }
+    /// Whether a full frame was asked for since the last call.
+    fn take_keyframe_request(&self) -> bool {
+        false
+    }
+
+    fn geo_status(&self, _status: GeoPipelineStatus) {}
+    fn interfaces(&self, _sampled: Vec<interfaces::InterfaceInfo>) {}
+    fn flow_events(&self, _events: &[lifecycle::FlowEvent]) {}
+    fn mtu_finding(&self, _finding: &mtu::MtuFinding) {}
+    fn route_changed(&self, _change: &RouteChange) {}
+    fn network_changed(&self, _change: &NetworkChange) {}
+    /// This tick's frame with every flow, before it is cut to `max_flows`.
+    fn emit_frame(&self, _frame: &TelemetryFrame, _max_flows: usize, _material: bool, _perf: &mut PerfStats) {}
+    /// This tick's frame as the writer gets it.
+    fn publish_frame(&self, _frame: &TelemetryFrame, _material: bool) {}
+    /// An alert fired; it is recorded by the writer either way.
+    fn alert(&self, event: &AlertEvent, actions: &[AlertAction]);
+    /// Baseline anomalies and alert findings for desktop notifications.
+    fn findings(&self, _session_id: Option<&str>, _findings: Vec<notify::Finding>) {}
+}
+
+/// The monitor loop's state between ticks.
+pub struct Monitor {
+    writer_tx: writer::WriterSender,
+    client: reqwest::Client,
+    provider_key: (geo::GeoProviderKind, Option<String>),
+    provider: Arc<dyn geo::GeoProvider>,
+    geo_db: Arc<Mutex<geo::GeoDatabases>>,
+    local_geo: LocalGeo,
+    geo_cache: HashMap<String, GeoCacheEntry>,
+    prev_keys: HashSet<String>,
+    start: Instant,
+    last_geo_lookup: Instant,
+    geo_task: Option<tokio::task::JoinHandle<GeoTaskResult>>,
+    geo_failures: u32,
+    geo_backoff_until: Option<Instant>,
+    last_geo_success: Option<Instant>,
+    privacy_active: bool,
+    tuning: settings::Settings,
+    last_netstat_poll: Instant,
+    cached_connections: Vec<ParsedConnection>,
+    socket_usage: Option<connections::SocketUsage>,
+    #[cfg(debug_assertions)]
+    last_perf_log: Instant,
+    last_snapshot: Option<FrameSnapshot>,
+    last_keyframe: Instant,
+    perf: PerfStats,
+    flow_presence: HashMap<String, (ParsedConnection, Instant)>,
+    process_names: HashMap<u32, String>,
+    app_groups: HashMap<u32, String>,
+    last_process_refresh: Instant,
+    last_forced_process_refresh: Instant,
+    container_index: containers::ContainerIndex,
+    last_container_refresh: Instant,
+    flow_first_seen: HashMap<String, f64>,
+    byte_counters: HashMap<String, CounterSample>,
+    flow_rates: HashMap<String, FlowRate>,
+    alert_engine: alerts::RuleEngine,
+    watch_tracker: watchlist::WatchTracker,
+    last_usage_check: Instant,
+    prober: probe::LatencyProber,
+    mtu_prober: mtu::MtuProber,
+    interface_tracker: interfaces::InterfaceTracker,
+    wifi_sampler: wifi::WifiSampler,
+    dns_observer: dns::DnsObserver,
+    family_tracker: ipfamily::FamilyTracker,
+    pacer: pacing::AdaptivePacer,
+    pipeline: enrich::Pipeline,
+    flow_tracker: lifecycle::FlowTracker,
+    classifier: fingerprint::ServiceClassifier,
+}
+
+impl Monitor {
+    /// Detect the local position (with the configured provider, unless
+    /// privacy mode rules a remote one out), publish it to `host` and seed
+    /// the DNS observer from recent recordings.
+    pub async fn new(host: &impl MonitorHost, writer_tx: writer::WriterSender) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap_or_default();
+        let tuning = host.settings();
+        let geo_db = host.geo_db();
+        let provider_key = (tuning.geo_provider, tuning.geo_api_key.clone());
+        let provider = geo::provider(provider_key.0, provider_key.1.clone(), geo_db.clone());
+        let local_geo = if tuning.privacy_mode && provider.is_remote() {
+            println!("[Abyss] Privacy mode — skipping remote local geo detection");
+            fallback_local_geo()
+        } else {
+            println!("[Abyss] Detecting local geo position...");
+            detect_local_geo(&*provider, &client).await
+        };
+        println!(
+            "[Abyss] Local: {}, {} ({:.2}, {:.2})",
+            local_geo.city, local_geo.country, local_geo.lat, local_geo.lng
+        );
+        host.set_local_geo(&local_geo);
+
+        let mut dns_observer = dns::DnsObserver::default();
+        let db_path = host.db_path();
+        let known = tokio::task::spawn_blocking(move || {
+            db::open_database(&db_path).and_then(|conn| db::load_dns_map(&conn, dns::DNS_RETAIN_SECS))
+        })
+        .await;
+        if let Ok(Ok(mappings)) = known {
+            dns_observer.seed(mappings);
+        }
+
+        Self {
+            writer_tx,
+            client,
+            provider_key,
+            provider,
+            geo_db,
+            local_geo,
+            geo_cache: HashMap::with_capacity(256),
+            prev_keys: HashSet::with_capacity(64),
+            start: Instant::now(),
+            last_geo_lookup: Instant::now() - Duration::from_secs(10),
+            geo_task: None,
+            geo_failures: 0,
+            geo_backoff_until: None,
+            last_geo_success: None,
+            privacy_active: tuning.privacy_mode,
+            last_netstat_poll: Instant::now() - Duration::from_millis(tuning.netstat_poll_ms),
+            tuning,
+            cached_connections: Vec::new(),
+            socket_usage: None,
+            #[cfg(debug_assertions)]
+            last_perf_log: Instant::now(),
+            last_snapshot: None,
+            last_keyframe: Instant::now(),
+            perf: PerfStats::default(),
+            flow_presence: HashMap::new(),
+            process_names: HashMap::new(),
+            app_groups: HashMap::new(),
+            last_process_refresh: Instant::now() - Duration::from_secs(PROCESS_CACHE_TTL_SECS + 1),
+            last_forced_process_refresh: Instant::now(),
+            container_index: containers::ContainerIndex::default(),
+            last_container_refresh: Instant::now() - containers::CONTAINER_REFRESH_INTERVAL,
+            flow_first_seen: HashMap::new(),
+            byte_counters: HashMap::new(),
+            flow_rates: HashMap::new(),
+            alert_engine: alerts::RuleEngine::default(),
+            watch_tracker: watchlist::WatchTracker::default(),
+            last_usage_check: Instant::now(),
+            prober: probe::LatencyProber::default(),
+            mtu_prober: mtu::MtuProber::default(),
+            interface_tracker: interfaces::InterfaceTracker::default(),
+            wifi_sampler: wifi::WifiSampler::default(),
+            dns_observer,
+            family_tracker: ipfamily::FamilyTracker::default(),
+            pacer: pacing::AdaptivePacer::default(),
+            pipeline: enrich::Pipeline::default(),
+            flow_tracker: lifecycle::FlowTracker::default(),
+            classifier: fingerprint::ServiceClassifier::default(),
+        }
+    }
+
+    /// The local position sessions are recorded from.
+    pub fn local_geo(&self) -> &LocalGeo {
+        &self.local_geo
+    }
+
+    /// Close every open flow, ahead of a pause or shutdown.
+    pub fn close_flows(&mut self, host: &impl MonitorHost) {
+        let events = self.flow_tracker.close_all();
+        self.record_flow_events(host, events);
+    }
+
+    /// Forget counters and presence from before a pause; they would read
+    /// as one long interval.
+    pub fn resume(&mut self) {
+        self.byte_counters.clear();
+        self.flow_rates.clear();
+        self.flow_presence.clear();
+        self.prober.reset();
+        self.pacer.reset();
+        self.last_snapshot = None;
+        self.last_netstat_poll = Instant::now() - Duration::from_millis(self.tuning.netstat_poll_ms);
+    }
+
+    /// Run one tick and return how long to wait before the next.
+    pub async fn tick(&mut self, host: &impl MonitorHost) -> Duration {
+        self.perf.cycles += 1;
+        let next = host.settings();
+        if (next.tick_ms, next.netstat_poll_ms, next.max_flows_per_frame)
+            != (self.tuning.tick_ms, self.tuning.netstat_poll_ms, self.tuning.max_flows_per_frame)
+        {
+            println!(
+                "[Abyss] Settings reloaded — tick {} ms, poll {} ms, {} flows/frame",
+                next.tick_ms, next.netstat_poll_ms, next.max_flows_per_frame
+            );
+        }
+        self.tuning = next;
+        let connections: Vec<ParsedConnection> =
+            if self.last_netstat_poll.elapsed() >= Duration::from_millis(self.pacer.rate(&self.tuning).netstat_poll_ms) {
+                let parse_started = Instant::now();
+                let captured = host.capture().and_then(capture::Capture::connections);
+                let monitored = self.tuning.monitored_interfaces.clone();
+                let (parsed, usage) = tokio::task::spawn_blocking(move || match captured {
+                    Some(mut captured) => {
+                        interfaces::retain_monitored(&mut captured, &monitored);
+                        let (polled, usage) = poll_connections(&monitored);
+                        attribute_owners(&mut captured, &polled);
+                        (captured, usage)
+                    }
+                    None => poll_connections(&monitored),
+                })
+                .await
+                .unwrap_or_default();
+                self.perf.parse_netstat_ms += parse_started.elapsed().as_secs_f64() * 1000.0;
+                self.socket_usage = usage;
+                self.flow_rates = measure_flow_rates(&parsed, &mut self.byte_counters);
+                self.cached_connections = parsed;
+                self.last_netstat_poll = Instant::now();
+                self.cached_connections.clone()
+            } else {
+                self.cached_connections.clone()
+            };
+
+        let privacy_mode = self.tuning.privacy_mode;
+        let latency_probes = self.tuning.latency_probes;
+        let next_provider_key = (self.tuning.geo_provider, self.tuning.geo_api_key.clone());
+        let process_filter = host.process_filter();
+        if next_provider_key != self.provider_key {
+            // Results from different providers aren't comparable; start over.
+            if let Some(task) = self.geo_task.take() {
+                task.abort();
+            }
+            self.geo_cache.clear();
+            self.geo_failures = 0;
+            self.geo_backoff_until = None;
+            self.provider_key = next_provider_key;
+            self.provider = geo::provider(self.provider_key.0, self.provider_key.1.clone(), self.geo_db.clone());
+            println!("[Abyss] Geo provider switched to {:?}", self.provider.kind());
+        }
+        if privacy_mode != self.privacy_active {
+            // Drop in-flight lookups and cached results (real or placeholder)
+            // so the new mode takes effect immediately.
+            if let Some(task) = self.geo_task.take() {
+                task.abort();
+            }
+            self.geo_cache.clear();
+            self.privacy_active = privacy_mode;
+            println!(
+                "[Abyss] Privacy mode {}",
+                if privacy_mode { "enabled — remote geo lookups off" } else { "disabled" }
+            );
+        }
+
+        prune_geo_cache(&mut self.geo_cache);
+
+        if let Some(task) = self.geo_task.take() {
+            if task.is_finished() {
+                match task.await {
+                    Ok((updates, elapsed_ms, success)) => {
+                        for (ip, entry) in updates {
+                            self.geo_cache.insert(ip, entry);
+                        }
+                        if success {
+                            self.geo_failures = 0;
+                            self.geo_backoff_until = None;
+                            self.last_geo_success = Some(Instant::now());
+                        } else {
+                            self.back_off_geo();
+                        }
+                        self.perf.geolocate_batch_ms += elapsed_ms;
+                    }
+                    Err(e) => {
+                        eprintln!("[Abyss] Geo task join failed: {e}");
+                        self.back_off_geo();
+                    }
+                }
+            } else {
+                self.geo_task = Some(task);
+            }
+        }
+
+        // Local MMDB lookups first.  They never leave the machine, so they
+        // also run in privacy mode; misses fall through to the HTTP batch.
+        let geo_ttl = Duration::from_secs(self.tuning.geo_cache_ttl_secs);
+        let local_db_loaded = {
+            let geo_db = self.geo_db.lock_or_recover("geo_db");
+            lookup_local_geo(&geo_db, &connections, &mut self.geo_cache, geo_ttl);
+            geo_db.is_loaded()
+        };
+
+        let geo_backoff_active = self
+            .geo_backoff_until
+            .map(|until| until > Instant::now())
+            .unwrap_or(false);
+
+        if (!privacy_mode || !self.provider.is_remote())
+            && self.geo_task.is_none()
+            && !geo_backoff_active
+            && self.last_geo_lookup.elapsed() > Duration::from_secs(3)
+        {
+            let now = Instant::now();
+            // Selected processes' destinations first; the rest fill the batch
+            // (they're recorded like any other flow)
+            let (selected, others): (Vec<&ParsedConnection>, Vec<&ParsedConnection>) = connections
+                .iter()
+                .partition(|c| process_filter.matches(self.process_names.get(&c.pid).map(String::as_str)));
+            let mut queued = HashSet::new();
+            let remote_ips: Vec<String> = selected
+                .into_iter()
+                .chain(others)
+                .map(|c| c.remote_ip.as_str())
+                .filter(|ip| {
+                    queued.insert(*ip)
+                        && !is_private_ip(ip)
+                        && !self
+                            .geo_cache
+                            .get(*ip)
+                            .map(|entry| entry.expires_at > now)
+                            .unwrap_or(false)
+                })
+                .take(self.provider.max_batch())
+                .map(str::to_string)
+                .collect();
+
+            if !remote_ips.is_empty() {
+                let client = self.client.clone();
+                let provider = self.provider.clone();
+                self.geo_task = Some(tokio::spawn(async move {
+                    let started = Instant::now();
+                    let (updates, success) = geolocate_batch(provider, client, remote_ips, geo_ttl).await;
+                    (updates, started.elapsed().as_secs_f64() * 1000.0, success)
+                }));
+            }
+            self.last_geo_lookup = Instant::now();
+        }
+
+        host.geo_status(GeoPipelineStatus {
+            cache_size: self.geo_cache.len(),
+            lookup_in_flight: self.geo_task.is_some(),
+            consecutive_failures: self.geo_failures,
+            remote_lookups_enabled: !privacy_mode && self.provider.is_remote(),
+            provider: self.provider.kind(),
+            backoff_remaining_secs: self
+                .geo_backoff_until
+                .map(|until| until.saturating_duration_since(Instant::now()).as_secs_f64())
+                .unwrap_or(0.0),
+            last_success_secs_ago: self.last_geo_success.map(|t| t.elapsed().as_secs_f64()),
+            local_db_loaded,
+        });
+
+        // Flow presence smoothing: keep recently-seen connections visible
+        let stable_connections = smooth_presence(&mut self.flow_presence, &connections);
+
+        // Only walk the process table when new PIDs appear or every 60s as fallback
+        if self.last_process_refresh.elapsed() >= Duration::from_secs(PROCESS_CACHE_TTL_SECS) {
+            let has_new_pids = stable_connections
+                .iter()
+                .any(|c| c.pid > 0 && !self.process_names.contains_key(&c.pid));
+            let force_refresh = self.last_forced_process_refresh.elapsed() >= Duration::from_secs(60);
+            if has_new_pids || force_refresh {
+                let tree = tokio::task::spawn_blocking(proctree::ProcessTree::snapshot)
+                    .await
+                    .unwrap_or_default();
+                self.process_names = tree.names();
+                self.app_groups = tree.app_groups();
+                self.last_forced_process_refresh = Instant::now();
+            }
+            // Always reset check timer to avoid rescanning every tick
+            self.last_process_refresh = Instant::now();
+        }
+
+        // Containers are only listed while Docker Desktop or WSL is running
+        if self.last_container_refresh.elapsed() >= containers::CONTAINER_REFRESH_INTERVAL {
+            self.container_index = if containers::ContainerIndex::runtime_running(&self.process_names) {
+                tokio::task::spawn_blocking(containers::ContainerIndex::snapshot)
+                    .await
+                    .unwrap_or_default()
+            } else {
+                containers::ContainerIndex::default()
+            };
+            self.last_container_refresh = Instant::now();
+        }
+
+        let flow_events = self.flow_tracker.update(&self.flow_presence, &self.flow_rates, &self.process_names);
+        self.record_flow_events(host, flow_events);
+        self.classifier.observe(&self.flow_presence, &self.flow_rates);
+
+        // The map origin follows public address changes (`publicip::watch`)
+        let origin = host.local_geo();
+        if origin.lat != self.local_geo.lat || origin.lng != self.local_geo.lng {
+            self.local_geo = origin;
+        }
+
+        if privacy_mode {
+            // No remote lookups: give public destinations a placeholder geo
+            // pinned to the local position so flows stay visible with "??".
+            placeholder_geo(&stable_connections, &self.local_geo, &mut self.geo_cache, geo_ttl);
+        }
+
+        // Domains recently resolved for remote IPs
+        let captured_dns = host.capture().map(capture::Capture::dns_answers).unwrap_or_default();
+        let resolved = self.dns_observer.tick(captured_dns).await;
+        if !resolved.is_empty() {
+            let _ = self.writer_tx.send(writer::WriteCommand::RecordDns(resolved));
+        }
+
+        // IPv4/IPv6 connection outcomes per destination org
+        let mut attempts = self.family_tracker.observe(&connections);
+        if !attempts.is_empty() {
+            for attempt in &mut attempts {
+                attempt.org = self
+                    .geo_cache
+                    .get(&attempt.remote_ip)
+                    .and_then(|e| e.value.as_ref())
+                    .map(|g| g.org.clone())
+                    .filter(|org| !org.is_empty());
+                attempt.domain = self.dns_observer.domain(&attempt.remote_ip).map(str::to_string);
+            }
+            let _ = self.writer_tx.send(writer::WriteCommand::RecordFamilyAttempts(attempts));
+        }
+
+        // Measured RTTs for a rotating sample of public destinations
+        if latency_probes {
+            self.prober
+                .tick(
+                    stable_connections
+                        .iter()
+                        .filter(|c| !is_private_ip(&c.remote_ip))
+                        .map(|c| (c.remote_ip.as_str(), c.remote_port, c.proto.as_str())),
+                )
+                .await;
+        } else {
+            self.prober.reset();
+        }
+
+        let watchlist = host.watchlist();
+        let threats = host.threats();
+
+        let build_started = Instant::now();
+        let mut enrich_ctx = enrich::TickContext {
+            geo_cache: &mut self.geo_cache,
+            process_names: &self.process_names,
+            app_groups: &self.app_groups,
+            containers: &self.container_index,
+            rates: &self.flow_rates,
+            rtts: self.prober.samples(),
+            dns: &self.dns_observer,
+            watchlist: &watchlist,
+            watched: Vec::new(),
+            threats: &threats,
+            perf: &mut self.perf,
+        };
+        let mut frame = build_frame(
+            &stable_connections,
+            &mut self.prev_keys,
+            &self.local_geo,
+            self.start.elapsed().as_secs_f64(),
+            &mut self.flow_first_seen,
+            &self.pipeline,
+            &mut enrich_ctx,
+        );
+        let watched = std::mem::take(&mut enrich_ctx.watched);
+        self.classifier.label(&mut frame.flows);
+        let mut sampled_interfaces = self.interface_tracker.sample().to_vec();
+        frame.interface = self.interface_tracker.active();
+        self.wifi_sampler.tick(frame.interface.as_ref()).await;
+        frame.wifi = self.wifi_sampler.current();
+        // Path MTU of the busiest TCP destinations, alongside latency probing
+        if latency_probes {
+            for finding in self.mtu_prober.tick(&frame.flows, frame.interface.as_ref()).await {
+                println!(
+                    "[Abyss] Path MTU to {} is {} ({})",
+                    finding.ip,
+                    finding.path_mtu,
+                    finding.status.as_str()
+                );
+                host.mtu_finding(&finding);
+                let _ = self.writer_tx.send(writer::WriteCommand::RecordMtuFinding(finding));
+            }
+        } else {
+            self.mtu_prober.reset();
+        }
+        frame.sockets = self.socket_usage;
+        let rate = self.pacer.observe(&frame, &self.tuning).await;
+        frame.rate = Some(rate);
+        for iface in &mut sampled_interfaces {
+            iface.monitored = interfaces::is_monitored(iface, &self.tuning.monitored_interfaces);
+        }
+        frame.processes = attribution::attribute(
+            &stable_connections,
+            &self.flow_rates,
+            &self.process_names,
+            &self.app_groups,
+            interfaces::monitored_totals(&sampled_interfaces),
+        );
+        host.interfaces(sampled_interfaces);
+        self.perf.build_frame_ms += build_started.elapsed().as_secs_f64() * 1000.0;
+
+        let keyframe_due = host.take_keyframe_request()
+            || self.last_keyframe.elapsed() >= Duration::from_secs(self.tuning.keyframe_interval_secs);
+        let material = keyframe_due || is_material_change(self.last_snapshot, &frame, &self.tuning);
+
+        let emit_started = Instant::now();
+        host.emit_frame(&frame, self.tuning.max_flows_per_frame, material, &mut self.perf);
+        truncate_flows(&mut frame, self.tuning.max_flows_per_frame);
+        host.publish_frame(&frame, material);
+        self.perf.emit_frame_ms += emit_started.elapsed().as_secs_f64() * 1000.0;
+        self.perf.ticks += 1;
+        if material {
+            self.last_keyframe = Instant::now();
+            self.last_snapshot = Some(FrameSnapshot {
+                active_flows: frame.net.active_flows,
+                bps: frame.net.bps,
+                latency_ms: frame.net.latency_ms,
+            });
+        }
+
+        #[cfg(debug_assertions)]
+        self.log_perf(&frame);
+
+        self.evaluate_alerts(host, &frame, watched).await;
+
+        // Send frame to writer for session persistence (writer handles sampling)
+        let _ = self.writer_tx.send(writer::WriteCommand::Frame(Box::new(frame)));
+
+        Duration::from_millis(rate.tick_ms)
+    }
+
+    /// Alert rules: live conditions every tick, history ones (and baseline
+    /// anomalies for desktop notifications) once a minute.
+    async fn evaluate_alerts(
+        &mut self,
+        host: &impl MonitorHost,
+        frame: &TelemetryFrame,
+        watched: Vec<watchlist::WatchedFlow>,
+    ) {
+        let rules = host.alert_rules();
+        if self.alert_engine.needs_known_countries(&rules) {
+            let db_path = host.db_path();
+            let known = tokio::task::spawn_blocking(move || {
+                db::open_database(&db_path).and_then(|conn| db::known_countries(&conn))
+            })
+            .await;
+            match known {
+                Ok(Ok(known)) => self.alert_engine.set_known_countries(known),
+                Ok(Err(e)) => eprintln!("[Abyss] Loading known countries failed: {e}"),
+                Err(e) => eprintln!("[Abyss] Loading known countries panicked: {e}"),
+            }
+        }
+        let notify_anomalies = self.tuning.anomaly_notifications.enabled;
+        let session_id = host.session_id();
+        let mut fired = if rules.is_empty() {
+            Vec::new()
+        } else {
+            self.alert_engine.evaluate_frame(&rules, frame, session_id.as_deref())
+        };
+        let mut findings = Vec::new();
+        if self.last_usage_check.elapsed() >= Duration::from_secs(ALERT_USAGE_INTERVAL_SECS)
+            && ((notify_anomalies && session_id.is_some())
+                || rules.iter().any(|r| r.enabled && r.condition.uses_history()))
+        {
+            self.last_usage_check = Instant::now();
+            let db_path = host.db_path();
+            let sid = session_id.clone();
+            let usage = tokio::task::spawn_blocking(move || {
+                db::open_database(&db_path).and_then(|conn| alerts::load_usage(&conn, sid.as_deref()))
+            })
+            .await;
+            match usage {
+                Ok(Ok(usage)) => {
+                    findings.extend(usage.anomalies.iter().filter_map(notify::Finding::from_anomaly));
+                    fired.extend(self.alert_engine.evaluate_usage(&rules, &usage, session_id.as_deref()))
+                }
+                Ok(Err(e)) => eprintln!("[Abyss] Alert usage check failed: {e}"),
+                Err(e) => eprintln!("[Abyss] Alert usage check panicked: {e}"),
+            }
+        }
+        for event in fired {
+            let rule = rules.iter().find(|r| r.id == event.rule_id);
+            findings.extend(rule.and_then(|r| notify::Finding::from_alert(&event, r)));
+            let actions = rule.map(|r| r.actions.as_slice()).unwrap_or_default();
+            host.alert(&event, actions);
+            let _ = self.writer_tx.send(writer::WriteCommand::RecordAlert(Box::new(event)));
+        }
+        let (hits, watch_alerts) = self.watch_tracker.observe(watched, frame, session_id.as_deref());
+        if !hits.is_empty() {
+            let _ = self.writer_tx.send(writer::WriteCommand::RecordWatchHits(hits));
+        }
+        for event in watch_alerts {
+            host.alert(&event, &[AlertAction::Event, AlertAction::Notification]);
+            let _ = self.writer_tx.send(writer::WriteCommand::RecordAlert(Box::new(event)));
+        }
+        host.findings(session_id.as_deref(), findings);
+    }
+
+    /// Report flow events to `host` and queue them for the writer.
+    fn record_flow_events(&self, host: &impl MonitorHost, events: Vec<lifecycle::FlowEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        host.flow_events(&events);
+        let _ = self.writer_tx.send(writer::WriteCommand::RecordFlowEvents(events));
+    }
+
+    fn back_off_geo(&mut self) {
+        self.geo_failures = self.geo_failures.saturating_add(1);
+        let backoff_secs =
+            (GEO_BACKOFF_MIN_SECS * 2_u64.pow(self.geo_failures.saturating_sub(1).min(4))).min(GEO_BACKOFF_MAX_SECS);
+        self.geo_backoff_until = Some(Instant::now() + Duration::from_secs(backoff_secs));
+    }
+
+    #[cfg(debug_assertions)]
+    fn log_perf(&mut self, frame: &TelemetryFrame) {
+        let flow_count = frame.flows.len();
+        if flow_count > 0 {
+            println!(
+                "[Abyss] {} flows | {} | {} geo cached",
+                flow_count,
+                units::current().rate(frame.net.bps),
+                self.geo_cache.len()
+            );
+        }
+
+        let perf = &self.perf;
+        if self.last_perf_log.elapsed() >= Duration::from_secs(PERF_LOG_INTERVAL_SECS) && perf.cycles > 0 {
+            let cycles = perf.cycles as f64;
+            let ticks = perf.ticks.max(1) as f64;
+            let hit_total = perf.geo_cache_hits + perf.geo_cache_misses;
+            let hit_rate = if hit_total > 0 {
+                (perf.geo_cache_hits as f64 * 100.0) / hit_total as f64
+            } else {
+                0.0
+            };
+            println!(
+                "[Abyss][perf] parse={:.1}ms geo={:.1}ms build={:.1}ms emit={:.1}ms payload={:.1}KB hit={:.1}% cache={}",
+                perf.parse_netstat_ms / cycles,
+                perf.geolocate_batch_ms / cycles,
+                perf.build_frame_ms / cycles,
+                perf.emit_frame_ms / ticks,
+                perf.ws_payload_bytes as f64 / ticks / 1024.0,
+                hit_rate,
+                self.geo_cache.len()
+            );
+            let stages: Vec<String> = perf
+                .enrich_ms
+                .iter()
+                .map(|(stage, ms)| format!("{stage}={:.2}ms", ms / cycles))
+                .collect();
+            println!("[Abyss][perf] enrich {}", stages.join(" "));
+
+            self.perf = PerfStats::default();
+            self.last_perf_log = Instant::now();
+        }
+    }
+}
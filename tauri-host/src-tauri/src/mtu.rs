@@ -0,0 +1,381 @@
+use crate::interfaces::ActiveInterface;
+use crate::{is_private_ip, protocol_code, GeoFlow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Per-echo timeout.
+const ECHO_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Time between probe rounds.
+const MTU_PROBE_INTERVAL: Duration = Duration::from_secs(120);
+/// A destination is re-probed at most this often.
+const MTU_RECHECK: Duration = Duration::from_secs(1800);
+/// Destinations probed per round (concurrently), busiest first.
+const MTU_SAMPLE_SIZE: usize = 3;
+/// Ethernet MTU; the largest packet probed.
+const FULL_MTU: u16 = 1500;
+/// Lost echoes are retried this many times before a size counts as dropped.
+const ECHO_ATTEMPTS: usize = 2;
+
+// ─── Findings ───────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MtuStatus {
+    /// Full-size packets get through.
+    Ok,
+    /// The path MTU is below 1500 but the path says so ("fragmentation
+    /// needed"/"packet too big"), so path MTU discovery copes.
+    Reduced,
+    /// Packets above the path MTU vanish without an error: TCP connections
+    /// stall as soon as a full-size segment is sent.
+    BlackHole,
+}
+
+impl MtuStatus {
+    /// Value stored in `mtu_findings.status`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MtuStatus::Ok => "ok",
+            MtuStatus::Reduced => "reduced",
+            MtuStatus::BlackHole => "blackHole",
+        }
+    }
+}
+
+/// A path with a reduced or black-holed MTU, emitted as `mtu-finding` and
+/// persisted by the writer against the current session.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MtuFinding {
+    pub ip: String,
+    pub domain: Option<String>,
+    pub org: Option<String>,
+    pub status: MtuStatus,
+    /// Largest packet (IP header included) that got through.
+    pub path_mtu: u16,
+    /// Interface the probe went out on.
+    pub interface: Option<String>,
+    pub timestamp: String,
+}
+
+struct Target {
+    ip: String,
+    domain: Option<String>,
+    org: Option<String>,
+}
+
+type ProbeResults = Vec<(Target, Option<(MtuStatus, u16)>)>;
+
+// ─── Prober ─────────────────────────────────────────────────────────────────
+
+/// Periodically measures the path MTU to the busiest TCP destinations.
+/// Probes run on a blocking task, as a search can take several seconds.
+#[derive(Default)]
+pub struct MtuProber {
+    /// Last probe per IP, for `MTU_RECHECK`.
+    probed: HashMap<String, Instant>,
+    task: Option<tokio::task::JoinHandle<ProbeResults>>,
+    interface: Option<String>,
+    last_round: Option<Instant>,
+}
+
+impl MtuProber {
+    /// Collect a finished round and start the next one when due.  Returns
+    /// the reduced and black-holed paths found by the finished round.
+    pub async fn tick(&mut self, flows: &[GeoFlow], interface: Option<&ActiveInterface>) -> Vec<MtuFinding> {
+        let mut findings = Vec::new();
+        if let Some(task) = self.task.take_if(|t| t.is_finished()) {
+            let timestamp = Utc::now().to_rfc3339();
+            for (target, result) in task.await.unwrap_or_default() {
+                let Some((status, path_mtu)) = result.filter(|(status, _)| *status != MtuStatus::Ok) else {
+                    continue;
+                };
+                findings.push(MtuFinding {
+                    ip: target.ip,
+                    domain: target.domain,
+                    org: target.org,
+                    status,
+                    path_mtu,
+                    interface: self.interface.clone(),
+                    timestamp: timestamp.clone(),
+                });
+            }
+        }
+        self.probed.retain(|_, at| at.elapsed() < MTU_RECHECK);
+
+        let due = self.last_round.is_none_or(|at| at.elapsed() >= MTU_PROBE_INTERVAL);
+        if self.task.is_some() || !due {
+            return findings;
+        }
+
+        // TCP is where black holes hurt (full-size segments stall)
+        let mut busiest: Vec<&GeoFlow> = flows
+            .iter()
+            .filter(|f| f.protocol == protocol_code("tcp") && !is_private_ip(&f.dst.ip) && !self.probed.contains_key(&f.dst.ip))
+            .collect();
+        busiest.sort_by(|a, b| b.bps.total_cmp(&a.bps));
+        let mut targets: Vec<Target> = Vec::new();
+        for flow in busiest {
+            if targets.len() == MTU_SAMPLE_SIZE {
+                break;
+            }
+            if targets.iter().any(|t| t.ip == flow.dst.ip) {
+                continue;
+            }
+            targets.push(Target {
+                ip: flow.dst.ip.clone(),
+                domain: flow.domain.clone(),
+                org: flow.dst.org.clone(),
+            });
+        }
+
+        self.last_round = Some(Instant::now());
+        if targets.is_empty() {
+            return findings;
+        }
+        for target in &targets {
+            self.probed.insert(target.ip.clone(), Instant::now());
+        }
+        self.interface = interface.map(|i| i.name.clone());
+        self.task = Some(tokio::task::spawn_blocking(move || probe_all(targets)));
+        findings
+    }
+
+    /// Forget probed paths (probing disabled).
+    pub fn reset(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.probed.clear();
+        self.last_round = None;
+    }
+}
+
+// ─── Probes ─────────────────────────────────────────────────────────────────
+
+/// Outcome of one don't-fragment echo.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Echo {
+    Reply,
+    /// Refused locally or by a router as larger than the path allows.
+    TooBig,
+    Lost,
+}
+
+fn probe_all(targets: Vec<Target>) -> ProbeResults {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .into_iter()
+            .map(|target| {
+                scope.spawn(move || {
+                    let result = target.ip.parse().ok().and_then(search);
+                    (target, result)
+                })
+            })
+            .collect();
+        handles.into_iter().filter_map(|h| h.join().ok()).collect()
+    })
+}
+
+/// Binary-search the largest packet that gets an echo reply.  `None` when
+/// the host doesn't answer echoes at all.
+fn search(ip: IpAddr) -> Option<(MtuStatus, u16)> {
+    // IPv6 guarantees 1280; 576 is the IPv4 minimum every host accepts
+    let header = if ip.is_ipv4() { 28 } else { 48 };
+    let mut low = if ip.is_ipv4() { 576 } else { 1280 };
+    if echo_retried(ip, low - header)? != Echo::Reply {
+        return None;
+    }
+    let mut high = FULL_MTU;
+    let mut signalled = match echo_retried(ip, high - header)? {
+        Echo::Reply => return Some((MtuStatus::Ok, FULL_MTU)),
+        Echo::TooBig => true,
+        Echo::Lost => false,
+    };
+    // `low` got through, `high` didn't
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        match echo_retried(ip, mid - header)? {
+            Echo::Reply => low = mid,
+            Echo::TooBig => {
+                signalled = true;
+                high = mid;
+            }
+            Echo::Lost => high = mid,
+        }
+    }
+    let status = if signalled { MtuStatus::Reduced } else { MtuStatus::BlackHole };
+    Some((status, low))
+}
+
+/// Repeat lost echoes, as one lost reply says nothing about packet size.
+fn echo_retried(ip: IpAddr, payload: u16) -> Option<Echo> {
+    let mut outcome = Echo::Lost;
+    for _ in 0..ECHO_ATTEMPTS {
+        outcome = df_echo(ip, payload as usize)?;
+        if outcome != Echo::Lost {
+            break;
+        }
+    }
+    Some(outcome)
+}
+
+/// macOS headers define it, the libc crate doesn't.
+#[cfg(target_os = "macos")]
+const IPV6_DONTFRAG: libc::c_int = 62;
+
+/// Echo with `payload` bytes and fragmentation disallowed, over a connected
+/// ICMP datagram socket so "fragmentation needed" comes back as `EMSGSIZE`.
+/// `None` when the socket can't be set up.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn df_echo(ip: IpAddr, payload: usize) -> Option<Echo> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    use std::net::SocketAddr;
+    use std::os::fd::AsRawFd;
+
+    let (domain, protocol, echo_request, echo_reply) = match ip {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, 8u8, 0u8),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, 128u8, 129u8),
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(protocol)).ok()?;
+    #[cfg(target_os = "linux")]
+    let (level, option, value) = match ip {
+        IpAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DO),
+        IpAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_DO),
+    };
+    #[cfg(target_os = "macos")]
+    let (level, option, value) = match ip {
+        IpAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_DONTFRAG, 1),
+        IpAddr::V6(_) => (libc::IPPROTO_IPV6, IPV6_DONTFRAG, 1),
+    };
+    // SAFETY: the option value is a c_int that outlives the call.
+    let set = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            option,
+            (&value as *const libc::c_int).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if set != 0 {
+        return None;
+    }
+    socket.connect(&SocketAddr::new(ip, 0).into()).ok()?;
+    let socket: std::net::UdpSocket = socket.into();
+
+    let mut packet = vec![0u8; 8 + payload];
+    packet[0] = echo_request;
+    // Sequence number is the payload size, so a late reply to a previous
+    // size isn't taken for this one
+    packet[6..8].copy_from_slice(&(payload as u16).to_be_bytes());
+    if ip.is_ipv4() {
+        let sum = icmp_checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    let too_big = |e: &std::io::Error| e.raw_os_error() == Some(libc::EMSGSIZE);
+    if let Err(e) = socket.send(&packet) {
+        return Some(if too_big(&e) { Echo::TooBig } else { Echo::Lost });
+    }
+
+    let started = Instant::now();
+    let mut buf = vec![0u8; packet.len() + 64];
+    loop {
+        let Some(remaining) = ECHO_TIMEOUT.checked_sub(started.elapsed()) else {
+            return Some(Echo::Lost);
+        };
+        socket.set_read_timeout(Some(remaining.max(Duration::from_millis(1)))).ok()?;
+        let mut reply = match socket.recv(&mut buf) {
+            Ok(len) => &buf[..len],
+            Err(e) if too_big(&e) => return Some(Echo::TooBig),
+            Err(_) => return Some(Echo::Lost),
+        };
+        // macOS delivers IPv4 replies with the IP header attached
+        if ip.is_ipv4() && reply.first().is_some_and(|b| b >> 4 == 4) {
+            let header_len = ((reply[0] & 0x0f) as usize * 4).min(reply.len());
+            reply = &reply[header_len..];
+        }
+        if reply.len() == packet.len() && reply[0] == echo_reply && reply[6..8] == packet[6..8] {
+            return Some(Echo::Reply);
+        }
+    }
+}
+
+/// Echo through the ICMP helper API with the DF flag set.  IPv6 isn't
+/// probed on Windows.
+#[cfg(windows)]
+fn df_echo(ip: IpAddr, payload: usize) -> Option<Echo> {
+    use windows_sys::Win32::Foundation::{GetLastError, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        IcmpCloseHandle, IcmpCreateFile, IcmpSendEcho, ICMP_ECHO_REPLY, IP_OPTION_INFORMATION,
+    };
+
+    // IP_PACKET_TOO_BIG
+    const PACKET_TOO_BIG: u32 = 11009;
+
+    let IpAddr::V4(v4) = ip else {
+        return None;
+    };
+    let request = vec![0u8; payload];
+    let mut reply = vec![0u8; std::mem::size_of::<ICMP_ECHO_REPLY>() + payload + 8];
+    let options = IP_OPTION_INFORMATION {
+        Ttl: 128,
+        Tos: 0,
+        // IP_FLAG_DF
+        Flags: 0x2,
+        OptionsSize: 0,
+        OptionsData: std::ptr::null_mut(),
+    };
+    // SAFETY: the handle is checked before use and closed before returning;
+    // the reply buffer is sized for one ICMP_ECHO_REPLY plus the payload.
+    let status = unsafe {
+        let handle = IcmpCreateFile();
+        if handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let replies = IcmpSendEcho(
+            handle,
+            u32::from_ne_bytes(v4.octets()),
+            request.as_ptr().cast(),
+            payload as u16,
+            &options,
+            reply.as_mut_ptr().cast(),
+            reply.len() as u32,
+            ECHO_TIMEOUT.as_millis() as u32,
+        );
+        let status = if replies == 0 {
+            GetLastError()
+        } else {
+            std::ptr::read_unaligned(reply.as_ptr() as *const ICMP_ECHO_REPLY).Status
+        };
+        IcmpCloseHandle(handle);
+        status
+    };
+    Some(match status {
+        // IP_SUCCESS
+        0 => Echo::Reply,
+        PACKET_TOO_BIG => Echo::TooBig,
+        _ => Echo::Lost,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn df_echo(_ip: IpAddr, _payload: usize) -> Option<Echo> {
+    None
+}
+
+/// RFC 1071 ones' complement checksum.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn icmp_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -0,0 +1,646 @@
+#[cfg(feature = "gui")]
+use crate::app::AppState;
+use crate::alerts::{self, AlertAction, AlertContext, AlertEvent, AlertRule, Condition, Severity};
+use crate::db;
+use crate::error::AbyssError;
+use crate::locks::LockExt;
+use crate::webhooks::{self, WebhookEvent};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+#[cfg(feature = "gui")]
+use tauri::{Emitter, Manager};
+
+/// Timeout for a single push delivery.
+const PUSH_TIMEOUT_SECS: u64 = 10;
+
+// ─── Mute & snooze ──────────────────────────────────────────────────────────
+
+/// Global mute and per-rule snoozes, persisted in settings so a restart
+/// doesn't resume a silenced alert storm.  Timestamps are RFC 3339.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MuteState {
+    /// Mute every alert.  With `muted_until` set the mute lapses then.
+    pub muted: bool,
+    pub muted_until: Option<String>,
+    /// Rule id → snoozed until.
+    pub snoozed: HashMap<String, String>,
+}
+
+impl MuteState {
+    /// Whether delivery of `event` is currently suppressed.
+    pub fn suppresses(&self, event: &AlertEvent, now: DateTime<Utc>) -> bool {
+        self.muted_at(now)
+            || self
+                .snoozed
+                .get(&event.rule_id)
+                .is_some_and(|until| in_future(until, now))
+    }
+
+    pub fn muted_at(&self, now: DateTime<Utc>) -> bool {
+        self.muted && self.muted_until.as_deref().is_none_or(|until| in_future(until, now))
+    }
+
+    /// Drop lapsed snoozes and an expired timed mute.
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        self.snoozed.retain(|_, until| in_future(until, now));
+        if self.muted && !self.muted_at(now) {
+            self.muted = false;
+            self.muted_until = None;
+        }
+    }
+}
+
+fn in_future(timestamp: &str, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(timestamp).is_ok_and(|t| t > now)
+}
+
+// ─── Templates ──────────────────────────────────────────────────────────────
+
+/// User-editable text for alert notifications and the JSON body sent to
+/// webhook-style channels.  `{{name}}` placeholders are substituted from
+/// `template_vars`; unknown names are left as written.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlertTemplates {
+    pub title: String,
+    pub body: String,
+    /// JSON payload template (values are JSON-escaped on substitution, so
+    /// placeholders go inside string literals).  `None` sends the event as-is.
+    pub payload: Option<String>,
+}
+
+impl Default for AlertTemplates {
+    fn default() -> Self {
+        Self {
+            title: "{{severity}}: {{rule}}".into(),
+            body: "{{message}}".into(),
+            payload: None,
+        }
+    }
+}
+
+impl AlertTemplates {
+    /// Reject templates that can't produce a notification: an empty title
+    /// or a payload that doesn't render to valid JSON.
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        if self.title.trim().is_empty() {
+            return Err(AbyssError::InvalidInput("Notification title template is empty".into()));
+        }
+        self.render(&sample_event()).map(|_| ())
+    }
+
+    pub fn render(&self, event: &AlertEvent) -> Result<RenderedAlert, AbyssError> {
+        let vars = template_vars(event);
+        let payload = match &self.payload {
+            Some(template) => {
+                let json = render(template, &vars, json_escape);
+                Some(serde_json::from_str(&json).map_err(|e| {
+                    AbyssError::InvalidInput(format!("Payload template is not valid JSON: {e}"))
+                })?)
+            }
+            None => None,
+        };
+        Ok(RenderedAlert {
+            title: render(&self.title, &vars, str::to_string),
+            body: render(&self.body, &vars, str::to_string),
+            payload,
+        })
+    }
+}
+
+/// A notification rendered from `AlertTemplates`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedAlert {
+    pub title: String,
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<serde_json::Value>,
+}
+
+/// Placeholder values for `event`.  Ones that don't apply to the rule
+/// (e.g. `process` for a quota rule) render empty.
+pub fn template_vars(event: &AlertEvent) -> HashMap<&'static str, String> {
+    let context = &event.context;
+    let bps = context.bps.or(context.frame.map(|f| f.bps));
+    HashMap::from([
+        ("rule", event.rule_name.clone()),
+        ("rule_id", event.rule_id.clone()),
+        ("severity", format!("{:?}", event.severity)),
+        ("subject", event.subject.clone()),
+        ("message", event.message.clone()),
+        ("value", format_number(event.value)),
+        ("threshold", format_number(event.threshold)),
+        ("process", context.process.clone().unwrap_or_default()),
+        ("country", context.country.clone().unwrap_or_default()),
+        ("bps", bps.map(format_number).unwrap_or_default()),
+        ("bps_human", bps.map(alerts::format_bps).unwrap_or_default()),
+        ("latency_ms", context.frame.map(|f| format!("{:.0}", f.latency_ms)).unwrap_or_default()),
+        ("flows", context.frame.map(|f| f.active_flows.to_string()).unwrap_or_default()),
+        ("suppressed", event.suppressed.to_string()),
+        ("session_id", event.session_id.clone().unwrap_or_default()),
+        ("time", event.triggered_at.clone()),
+    ])
+}
+
+/// Substitute `{{ name }}` placeholders, passing values through `escape`.
+fn render(template: &str, vars: &HashMap<&'static str, String>, escape: impl Fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        match vars.get(after[..end].trim()) {
+            Some(value) => out.push_str(&escape(value)),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Escape for inclusion inside a JSON string literal.
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{value:.0}")
+    } else {
+        format!("{value:.2}")
+    }
+}
+
+/// Stand-in event for validating and previewing templates.
+pub fn sample_event() -> AlertEvent {
+    AlertEvent {
+        id: "preview".into(),
+        rule_id: "preview".into(),
+        rule_name: "Chrome bandwidth".into(),
+        severity: Severity::Warning,
+        subject: "chrome.exe".into(),
+        message: format!("chrome.exe is using {}", alerts::format_bps(12_500_000.0)),
+        value: 12_500_000.0,
+        threshold: 10_000_000.0,
+        session_id: None,
+        triggered_at: Utc::now().to_rfc3339(),
+        suppressed: 0,
+        context: AlertContext {
+            frame: None,
+            flow_ids: Vec::new(),
+            process: Some("chrome.exe".into()),
+            country: Some("US".into()),
+            bps: Some(12_500_000.0),
+        },
+    }
+}
+
+// ─── Push providers ─────────────────────────────────────────────────────────
+
+/// A phone push service alerts can be forwarded to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum PushProvider {
+    /// An ntfy topic (ntfy.sh or self-hosted); `token` for protected topics.
+    Ntfy {
+        #[serde(default = "default_ntfy_server")]
+        server: String,
+        topic: String,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// A Gotify server, authenticated with an application token.
+    Gotify { server: String, token: String },
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".into()
+}
+
+/// A configured push destination, persisted in settings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushTarget {
+    /// Assigned on save when empty.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Alerts below this severity aren't pushed.
+    #[serde(default = "default_push_severity")]
+    pub min_severity: Severity,
+    pub provider: PushProvider,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_push_severity() -> Severity {
+    Severity::Critical
+}
+
+impl PushTarget {
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        if self.name.trim().is_empty() {
+            return Err(AbyssError::InvalidInput("Push target name is required".into()));
+        }
+        let (server, secret_ok) = match &self.provider {
+            PushProvider::Ntfy { server, topic, .. } => {
+                let topic = topic.trim();
+                if topic.is_empty() || topic.contains('/') {
+                    return Err(AbyssError::InvalidInput(format!("Invalid ntfy topic '{topic}'")));
+                }
+                (server, true)
+            }
+            PushProvider::Gotify { server, token } => (server, !token.trim().is_empty()),
+        };
+        if !(server.starts_with("https://") || server.starts_with("http://")) {
+            return Err(AbyssError::InvalidInput(format!("Push server '{server}' must be an http(s) URL")));
+        }
+        if !secret_ok {
+            return Err(AbyssError::InvalidInput("Gotify needs an application token".into()));
+        }
+        Ok(())
+    }
+
+    /// Deliver one rendered alert.
+    pub async fn send(&self, alert: &RenderedAlert, severity: Severity) -> Result<(), AbyssError> {
+        let client = push_client();
+        let request = match &self.provider {
+            PushProvider::Ntfy { server, topic, token } => {
+                let (priority, tags) = match severity {
+                    Severity::Critical => ("5", "rotating_light"),
+                    Severity::Warning => ("4", "warning"),
+                    Severity::Info => ("3", "information_source"),
+                };
+                let mut request = client
+                    .post(format!("{}/{}", server.trim_end_matches('/'), topic.trim()))
+                    .header("Title", header_safe(&alert.title))
+                    .header("Priority", priority)
+                    .header("Tags", tags)
+                    .body(alert.body.clone());
+                if let Some(token) = token.as_deref().filter(|t| !t.is_empty()) {
+                    request = request.bearer_auth(token);
+                }
+                request
+            }
+            PushProvider::Gotify { server, token } => {
+                let priority = match severity {
+                    Severity::Critical => 8,
+                    Severity::Warning => 5,
+                    Severity::Info => 2,
+                };
+                client
+                    .post(format!("{}/message", server.trim_end_matches('/')))
+                    .header("X-Gotify-Key", token.as_str())
+                    .json(&serde_json::json!({
+                        "title": alert.title,
+                        "message": alert.body,
+                        "priority": priority,
+                    }))
+            }
+        };
+        let resp = request.send().await?;
+        if !resp.status().is_success() {
+            return Err(AbyssError::Network(format!("{} returned HTTP {}", self.name, resp.status())));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn push_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(PUSH_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// HTTP header values can't carry newlines (or, for ntfy, non-ASCII).
+fn header_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { ' ' })
+        .collect()
+}
+
+// ─── Routing ────────────────────────────────────────────────────────────────
+
+/// `alert-fired` payload: the event plus its rendered text.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification<'a> {
+    #[serde(flatten)]
+    event: &'a AlertEvent,
+    title: String,
+    body: String,
+}
+
+/// Deliver a fired alert through its rule's `actions` (frontend event,
+/// desktop notification, webhooks), to push targets at or below its
+/// severity and to `AlertFired` webhook endpoints.  Persistence happens through the writer, and muted or snoozed
+/// alerts are still recorded.
+#[cfg(feature = "gui")]
+pub fn dispatch(app: &tauri::AppHandle, event: &AlertEvent, actions: &[AlertAction]) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let (suppressed, templates, targets) = {
+        let settings = state.settings.lock_or_recover("settings");
+        let targets: Vec<PushTarget> = settings
+            .push_targets
+            .iter()
+            .filter(|t| t.enabled && event.severity >= t.min_severity)
+            .cloned()
+            .collect();
+        (
+            settings.alert_mute.suppresses(event, Utc::now()),
+            settings.alert_templates.clone(),
+            targets,
+        )
+    };
+    if suppressed {
+        println!("[Abyss] Alert muted: {} ({})", event.rule_name, event.subject);
+        return;
+    }
+    let vars = template_vars(event);
+    let notification = Notification {
+        event,
+        title: render(&templates.title, &vars, str::to_string),
+        body: render(&templates.body, &vars, str::to_string),
+    };
+    println!("[Abyss] Alert [{:?}] {}", event.severity, notification.title);
+    if actions.contains(&AlertAction::Event) {
+        let _ = app.emit("alert-fired", &notification);
+    }
+    if let Ok(data) = serde_json::to_value(event) {
+        webhooks::deliver(state.db_path.clone(), WebhookEvent::AlertFired, data);
+    }
+    if actions.contains(&AlertAction::Notification) {
+        let (title, body) = (notification.title.clone(), notification.body.clone());
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = system_notification(&title, &body) {
+                eprintln!("[Abyss] Desktop notification failed: {e}");
+            }
+        });
+    }
+    let webhooks: Vec<String> = actions
+        .iter()
+        .filter_map(|a| match a {
+            AlertAction::Webhook { url } => Some(url.clone()),
+            _ => None,
+        })
+        .collect();
+
+    if targets.is_empty() && webhooks.is_empty() {
+        return;
+    }
+    // The event itself unless a payload template renders
+    let payload = templates
+        .render(event)
+        .ok()
+        .and_then(|r| r.payload)
+        .or_else(|| serde_json::to_value(event).ok())
+        .unwrap_or_default();
+    let rendered = RenderedAlert {
+        title: notification.title,
+        body: notification.body,
+        payload: None,
+    };
+    let severity = event.severity;
+    tauri::async_runtime::spawn(async move {
+        for target in targets {
+            if let Err(e) = target.send(&rendered, severity).await {
+                eprintln!("[Abyss] Push to '{}' failed: {e}", target.name);
+            }
+        }
+        for url in webhooks {
+            if let Err(e) = post_webhook(&url, &payload).await {
+                eprintln!("[Abyss] Webhook {url} failed: {e}");
+            }
+        }
+    });
+}
+
+async fn post_webhook(url: &str, payload: &serde_json::Value) -> Result<(), AbyssError> {
+    let resp = push_client().post(url).json(payload).send().await?;
+    if !resp.status().is_success() {
+        return Err(AbyssError::Network(format!("HTTP {}", resp.status())));
+    }
+    Ok(())
+}
+
+// ─── Anomaly notifications ──────────────────────────────────────────────────
+
+/// Desktop notifications for high-severity findings: baseline anomalies
+/// and critical alert rules.  Persisted in settings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AnomalyNotifications {
+    pub enabled: bool,
+    /// Minimum seconds between two notifications; findings in between wait
+    /// for the next check.
+    pub min_interval_secs: u64,
+    /// Finding types never notified, e.g. "NEW_COUNTRY" or "LATENCY_SPIKE".
+    pub muted_types: Vec<String>,
+}
+
+impl Default for AnomalyNotifications {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_interval_secs: 300,
+            muted_types: Vec::new(),
+        }
+    }
+}
+
+impl AnomalyNotifications {
+    fn mutes(&self, finding_type: &str) -> bool {
+        self.muted_types.iter().any(|t| t.trim().eq_ignore_ascii_case(finding_type))
+    }
+}
+
+/// A high-severity finding offered to `AnomalyNotifier`.
+pub struct Finding {
+    pub finding_type: String,
+    pub title: String,
+    pub body: String,
+}
+
+impl Finding {
+    /// High-severity baseline anomalies only.
+    pub fn from_anomaly(anomaly: &db::Anomaly) -> Option<Self> {
+        (anomaly.severity == "high").then(|| Self {
+            finding_type: anomaly.anomaly_type.clone(),
+            title: format!("Anomaly: {}", humanize_type(&anomaly.anomaly_type)),
+            body: anomaly.message.clone(),
+        })
+    }
+
+    /// Critical alerts whose rule doesn't already notify through its actions.
+    pub fn from_alert(event: &AlertEvent, rule: &AlertRule) -> Option<Self> {
+        if event.severity != Severity::Critical || rule.actions.contains(&AlertAction::Notification) {
+            return None;
+        }
+        let finding_type = match rule.condition {
+            Condition::Anomaly { .. } => event.subject.clone(),
+            ref other => other.finding_type().to_string(),
+        };
+        Some(Self {
+            finding_type,
+            title: event.rule_name.clone(),
+            body: event.message.clone(),
+        })
+    }
+}
+
+/// "THROUGHPUT_SPIKE" → "Throughput spike".
+fn humanize_type(finding_type: &str) -> String {
+    let lower = finding_type.replace('_', " ").to_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => lower,
+    }
+}
+
+/// Throttle state for `AnomalyNotifications`, owned by the monitor loop.
+#[derive(Default)]
+pub struct AnomalyNotifier {
+    last_sent: Option<Instant>,
+    session_id: Option<String>,
+    /// Types already notified this session.  Baseline anomalies are
+    /// re-detected on every check until the session ends, so each type is
+    /// shown once per session.
+    notified: HashSet<String>,
+}
+
+impl AnomalyNotifier {
+    /// Show `findings` that aren't muted or already shown this session,
+    /// at most once per `min_interval_secs`; several collapse into one
+    /// notification.
+    #[cfg(feature = "gui")]
+    pub fn offer(&mut self, app: &tauri::AppHandle, session_id: Option<&str>, findings: Vec<Finding>) {
+        if self.session_id.as_deref() != session_id {
+            self.session_id = session_id.map(str::to_string);
+            self.notified.clear();
+        }
+        if findings.is_empty() {
+            return;
+        }
+        let Some(state) = app.try_state::<AppState>() else {
+            return;
+        };
+        let (prefs, muted) = {
+            let settings = state.settings.lock_or_recover("settings");
+            (settings.anomaly_notifications.clone(), settings.alert_mute.muted_at(Utc::now()))
+        };
+        if !prefs.enabled || muted {
+            return;
+        }
+        let mut fresh: Vec<Finding> = Vec::new();
+        for finding in findings {
+            let seen = fresh.iter().any(|f| f.finding_type == finding.finding_type);
+            if !seen && !prefs.mutes(&finding.finding_type) && !self.notified.contains(&finding.finding_type) {
+                fresh.push(finding);
+            }
+        }
+        if fresh.is_empty()
+            || self
+                .last_sent
+                .is_some_and(|t| t.elapsed() < Duration::from_secs(prefs.min_interval_secs))
+        {
+            return;
+        }
+        self.last_sent = Some(Instant::now());
+        self.notified.extend(fresh.iter().map(|f| f.finding_type.clone()));
+
+        let (title, body) = match fresh.as_slice() {
+            [only] => (only.title.clone(), only.body.clone()),
+            many => (
+                format!("{} network anomalies", many.len()),
+                many.iter().map(|f| f.body.as_str()).collect::<Vec<_>>().join("\n"),
+            ),
+        };
+        println!("[Abyss] Anomaly notification: {title}");
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = system_notification(&title, &body) {
+                eprintln!("[Abyss] Desktop notification failed: {e}");
+            }
+        });
+    }
+}
+
+// ─── Desktop notifications ──────────────────────────────────────────────────
+
+/// Show an OS notification through the platform's own notifier.  Title and
+/// body reach the scripts through environment variables, never the script
+/// text, so alert text can't inject commands.
+#[cfg(target_os = "linux")]
+fn system_notification(title: &str, body: &str) -> Result<(), AbyssError> {
+    run_notifier(std::process::Command::new("notify-send").args(["--app-name=Abyss", title, body]))
+}
+
+#[cfg(target_os = "macos")]
+fn system_notification(title: &str, body: &str) -> Result<(), AbyssError> {
+    run_notifier(
+        std::process::Command::new("osascript")
+            .args([
+                "-e",
+                r#"display notification (system attribute "ABYSS_BODY") with title (system attribute "ABYSS_TITLE")"#,
+            ])
+            .env("ABYSS_TITLE", title)
+            .env("ABYSS_BODY", body),
+    )
+}
+
+#[cfg(target_os = "windows")]
+fn system_notification(title: &str, body: &str) -> Result<(), AbyssError> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const TOAST: &str = "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+        $t = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+        $x = $t.GetElementsByTagName('text'); \
+        $x.Item(0).AppendChild($t.CreateTextNode($env:ABYSS_TITLE)) > $null; \
+        $x.Item(1).AppendChild($t.CreateTextNode($env:ABYSS_BODY)) > $null; \
+        [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('Abyss').Show([Windows.UI.Notifications.ToastNotification]::new($t))";
+    run_notifier(
+        std::process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", TOAST])
+            .env("ABYSS_TITLE", title)
+            .env("ABYSS_BODY", body)
+            .creation_flags(CREATE_NO_WINDOW),
+    )
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn system_notification(_title: &str, _body: &str) -> Result<(), AbyssError> {
+    Err(AbyssError::Internal("Desktop notifications aren't supported on this platform".into()))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn run_notifier(command: &mut std::process::Command) -> Result<(), AbyssError> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(AbyssError::Internal(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
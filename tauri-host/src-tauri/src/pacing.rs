@@ -0,0 +1,190 @@
+use crate::settings::Settings;
+use crate::TelemetryFrame;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Seconds between power source reads.
+const POWER_READ_INTERVAL_SECS: u64 = 60;
+/// Consecutive quiet ticks before polling slows down.
+const STABLE_TICKS_BEFORE_IDLE: u32 = 10;
+
+// ─── Effective rate ─────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// No battery found or the platform doesn't report it (treated as AC).
+    Unknown,
+}
+
+/// The tick and poll intervals the monitor loop is currently running at.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PollRate {
+    pub tick_ms: u64,
+    pub netstat_poll_ms: u64,
+    /// Slowed to `Settings::idle_poll_ms` (on battery, activity stable).
+    pub idle: bool,
+    pub power: PowerSource,
+}
+
+// ─── Scheduler ──────────────────────────────────────────────────────────────
+
+/// Slows the monitor loop while the machine is on battery and the flow count
+/// and throughput stay put, and returns to the configured rate on the first
+/// tick that moves by a material amount (see `Settings::material_*`).
+pub struct AdaptivePacer {
+    power: PowerSource,
+    power_task: Option<tokio::task::JoinHandle<PowerSource>>,
+    last_power_read: Option<Instant>,
+    previous: Option<(u32, f64)>,
+    stable_ticks: u32,
+    idle: bool,
+}
+
+impl Default for AdaptivePacer {
+    fn default() -> Self {
+        Self {
+            power: PowerSource::Unknown,
+            power_task: None,
+            last_power_read: None,
+            previous: None,
+            stable_ticks: 0,
+            idle: false,
+        }
+    }
+}
+
+impl AdaptivePacer {
+    /// Fold in this tick's frame and return the rate for the next one.
+    pub async fn observe(&mut self, frame: &TelemetryFrame, tuning: &Settings) -> PollRate {
+        if self.power_task.as_ref().is_some_and(|t| t.is_finished()) {
+            if let Some(task) = self.power_task.take() {
+                self.power = task.await.unwrap_or(PowerSource::Unknown);
+            }
+        }
+        let due = self
+            .last_power_read
+            .is_none_or(|at| at.elapsed() >= Duration::from_secs(POWER_READ_INTERVAL_SECS));
+        if tuning.adaptive_polling && self.power_task.is_none() && due {
+            self.last_power_read = Some(Instant::now());
+            self.power_task = Some(tokio::task::spawn_blocking(power_source));
+        }
+
+        let flows = frame.net.active_flows;
+        let bps = frame.net.bps;
+        let spike = self.previous.is_some_and(|(prev_flows, prev_bps)| {
+            let delta = (bps - prev_bps).abs();
+            prev_flows.abs_diff(flows) >= tuning.material_flow_delta
+                || (delta >= tuning.material_min_bps_delta
+                    && delta / prev_bps.max(1.0) * 100.0 >= tuning.material_throughput_delta_pct)
+        });
+        self.previous = Some((flows, bps));
+        self.stable_ticks = if spike { 0 } else { self.stable_ticks.saturating_add(1) };
+
+        let idle = tuning.adaptive_polling
+            && self.power == PowerSource::Battery
+            && self.stable_ticks >= STABLE_TICKS_BEFORE_IDLE;
+        if idle != self.idle {
+            self.idle = idle;
+            if idle {
+                println!("[Abyss] On battery with stable activity — polling every {} ms", tuning.idle_poll_ms);
+            } else {
+                println!("[Abyss] Polling restored to {} ms", tuning.tick_ms);
+            }
+        }
+        self.rate(tuning)
+    }
+
+    /// The rate currently in effect.
+    pub fn rate(&self, tuning: &Settings) -> PollRate {
+        let (tick_ms, netstat_poll_ms) = if self.idle {
+            (tuning.tick_ms.max(tuning.idle_poll_ms), tuning.netstat_poll_ms.max(tuning.idle_poll_ms))
+        } else {
+            (tuning.tick_ms, tuning.netstat_poll_ms)
+        };
+        PollRate {
+            tick_ms,
+            netstat_poll_ms,
+            idle: self.idle,
+            power: self.power,
+        }
+    }
+
+    /// Back to the configured rate until activity settles again.
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.stable_ticks = 0;
+        self.idle = false;
+    }
+}
+
+// ─── Power source ───────────────────────────────────────────────────────────
+
+/// Battery status from sysfs: discharging means on battery; any battery or
+/// online mains adapter otherwise means AC.
+#[cfg(target_os = "linux")]
+fn power_source() -> PowerSource {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).ok().map(|s| s.trim().to_string());
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return PowerSource::Unknown;
+    };
+    let mut source = PowerSource::Unknown;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let dir = entry.path();
+        match read(dir.join("type")).as_deref() {
+            Some("Battery") => match read(dir.join("status")).as_deref() {
+                Some("Discharging") => return PowerSource::Battery,
+                Some(_) => source = PowerSource::Ac,
+                None => {}
+            },
+            Some("Mains" | "USB") if read(dir.join("online")).as_deref() == Some("1") => {
+                source = PowerSource::Ac;
+            }
+            _ => {}
+        }
+    }
+    source
+}
+
+#[cfg(target_os = "windows")]
+fn power_source() -> PowerSource {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // SAFETY: GetSystemPowerStatus only writes the struct passed to it.
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return PowerSource::Unknown;
+    }
+    // BatteryFlag 128: no system battery
+    match (status.ACLineStatus, status.BatteryFlag) {
+        (_, 128) => PowerSource::Unknown,
+        (0, _) => PowerSource::Battery,
+        (1, _) => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
+
+/// First line of `pmset -g batt`: "Now drawing from 'Battery Power'".
+#[cfg(target_os = "macos")]
+fn power_source() -> PowerSource {
+    let Ok(output) = std::process::Command::new("pmset").args(["-g", "batt"]).output() else {
+        return PowerSource::Unknown;
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let first = text.lines().next().unwrap_or_default();
+    if first.contains("Battery Power") {
+        PowerSource::Battery
+    } else if first.contains("AC Power") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn power_source() -> PowerSource {
+    PowerSource::Unknown
+}
@@ -0,0 +1,289 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+/// Per-probe timeout for both ICMP echo and TCP connect.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Seconds between probe rounds.
+const PROBE_INTERVAL_SECS: u64 = 5;
+/// Destinations probed per round (concurrently).
+const PROBE_SAMPLE_SIZE: usize = 8;
+/// Measured RTTs older than this are dropped and the flow falls back to an estimate.
+const RTT_SAMPLE_TTL_SECS: u64 = 60;
+/// Echo payload, also used to recognise our replies.
+const ECHO_PAYLOAD: &[u8; 8] = b"abyssrtt";
+
+/// Set once opening an ICMP socket fails (no ping_group_range membership,
+/// sandbox, ...); later probes go straight to TCP.
+static ICMP_UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+static ECHO_SEQ: AtomicU16 = AtomicU16::new(0);
+
+// ─── Samples ────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProbeMethod {
+    Icmp,
+    Tcp,
+}
+
+/// One measured round trip to a destination.
+#[derive(Clone, Copy, Debug)]
+pub struct RttSample {
+    pub ms: f64,
+    pub method: ProbeMethod,
+    pub at: Instant,
+}
+
+type ProbeResults = Vec<(String, Option<RttSample>)>;
+
+/// Periodically measures RTT to a rotating sample of active destinations.
+/// Probes run on a blocking task so a slow round never stalls the monitor tick.
+#[derive(Default)]
+pub struct LatencyProber {
+    samples: HashMap<String, RttSample>,
+    /// Last probe attempt per IP, successful or not, for rotation.
+    attempted: HashMap<String, Instant>,
+    task: Option<tokio::task::JoinHandle<ProbeResults>>,
+    last_round: Option<Instant>,
+}
+
+impl LatencyProber {
+    /// Latest RTT per remote IP.
+    pub fn samples(&self) -> &HashMap<String, RttSample> {
+        &self.samples
+    }
+
+    /// Collect a finished round and start the next one when due.
+    /// `candidates` are (remote IP, remote port, protocol) of current flows.
+    pub async fn tick<'a>(&mut self, candidates: impl Iterator<Item = (&'a str, u16, &'a str)>) {
+        if self.task.as_ref().is_some_and(|t| t.is_finished()) {
+            if let Some(task) = self.task.take() {
+                if let Ok(results) = task.await {
+                    for (ip, sample) in results {
+                        if let Some(sample) = sample {
+                            self.samples.insert(ip, sample);
+                        }
+                    }
+                }
+            }
+        }
+
+        let ttl = Duration::from_secs(RTT_SAMPLE_TTL_SECS);
+        self.samples.retain(|_, s| s.at.elapsed() < ttl);
+        self.attempted.retain(|_, at| at.elapsed() < ttl);
+
+        let due = self
+            .last_round
+            .is_none_or(|at| at.elapsed() >= Duration::from_secs(PROBE_INTERVAL_SECS));
+        if self.task.is_some() || !due {
+            return;
+        }
+
+        // One target per IP; TCP flows keep their port, others try 443.
+        let mut targets: HashMap<&str, u16> = HashMap::new();
+        for (ip, port, proto) in candidates {
+            let port = if proto == "tcp" && port > 0 { port } else { 443 };
+            targets.entry(ip).or_insert(port);
+        }
+        let mut targets: Vec<(String, u16)> = targets
+            .into_iter()
+            .map(|(ip, port)| (ip.to_string(), port))
+            .collect();
+        // Never-probed first, then least recently probed
+        targets.sort_by_key(|(ip, _)| self.attempted.get(ip).copied());
+        targets.truncate(PROBE_SAMPLE_SIZE);
+
+        self.last_round = Some(Instant::now());
+        if targets.is_empty() {
+            return;
+        }
+        for (ip, _) in &targets {
+            self.attempted.insert(ip.clone(), Instant::now());
+        }
+        self.task = Some(tokio::task::spawn_blocking(move || probe_all(targets)));
+    }
+
+    /// Drop all samples (probing disabled).
+    pub fn reset(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.samples.clear();
+        self.attempted.clear();
+        self.last_round = None;
+    }
+}
+
+// ─── Probes ─────────────────────────────────────────────────────────────────
+
+/// Probe every target concurrently.
+fn probe_all(targets: Vec<(String, u16)>) -> ProbeResults {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .into_iter()
+            .map(|(ip, port)| {
+                scope.spawn(move || {
+                    let sample = ip.parse().ok().and_then(|addr| probe(addr, port));
+                    (ip, sample)
+                })
+            })
+            .collect();
+        handles.into_iter().filter_map(|h| h.join().ok()).collect()
+    })
+}
+
+/// ICMP echo, falling back to timing a TCP connect to `port`.
+fn probe(ip: IpAddr, port: u16) -> Option<RttSample> {
+    if !ICMP_UNAVAILABLE.load(Ordering::Relaxed) {
+        match icmp_echo(ip) {
+            Ok(Some(ms)) => return Some(sample(ms, ProbeMethod::Icmp)),
+            // Many hosts drop ICMP; TCP usually still answers.
+            Ok(None) => {}
+            Err(e) => {
+                if !ICMP_UNAVAILABLE.swap(true, Ordering::Relaxed) {
+                    println!("[Abyss] ICMP probes unavailable ({e}) — using TCP connect timing");
+                }
+            }
+        }
+    }
+    tcp_connect(ip, port).map(|ms| sample(ms, ProbeMethod::Tcp))
+}
+
+fn sample(ms: f64, method: ProbeMethod) -> RttSample {
+    RttSample {
+        ms,
+        method,
+        at: Instant::now(),
+    }
+}
+
+/// Time a TCP handshake.  A refused connection still took one round trip.
+fn tcp_connect(ip: IpAddr, port: u16) -> Option<f64> {
+    let started = Instant::now();
+    match TcpStream::connect_timeout(&SocketAddr::new(ip, port), PROBE_TIMEOUT) {
+        Ok(_) => Some(elapsed_ms(started)),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => Some(elapsed_ms(started)),
+        Err(_) => None,
+    }
+}
+
+fn elapsed_ms(started: Instant) -> f64 {
+    started.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Unprivileged echo over an ICMP datagram socket.  `Err` only when the
+/// socket can't be opened at all; a lost or filtered echo is `Ok(None)`.
+#[cfg(unix)]
+fn icmp_echo(ip: IpAddr) -> io::Result<Option<f64>> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let (domain, protocol, echo_request, echo_reply) = match ip {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, 8u8, 0u8),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, 128u8, 129u8),
+    };
+    let socket: std::net::UdpSocket = Socket::new(domain, Type::DGRAM, Some(protocol))?.into();
+
+    let seq = ECHO_SEQ.fetch_add(1, Ordering::Relaxed).to_be_bytes();
+    let mut packet = [0u8; 16];
+    packet[0] = echo_request;
+    // Identifier (bytes 4..6) is assigned by the kernel for datagram sockets
+    packet[6..8].copy_from_slice(&seq);
+    packet[8..].copy_from_slice(ECHO_PAYLOAD);
+    if ip.is_ipv4() {
+        // The kernel fills in the ICMPv6 checksum, but not always ICMPv4's
+        let sum = icmp_checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+
+    let started = Instant::now();
+    if socket.send_to(&packet, SocketAddr::new(ip, 0)).is_err() {
+        return Ok(None);
+    }
+    let mut buf = [0u8; 1500];
+    loop {
+        let Some(remaining) = PROBE_TIMEOUT.checked_sub(started.elapsed()) else {
+            return Ok(None);
+        };
+        socket.set_read_timeout(Some(remaining.max(Duration::from_millis(1))))?;
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(_) => return Ok(None),
+        };
+        if from.ip() != ip {
+            continue;
+        }
+        let mut reply = &buf[..len];
+        // macOS delivers IPv4 replies with the IP header attached
+        if ip.is_ipv4() && reply.first().is_some_and(|b| b >> 4 == 4) {
+            let header_len = ((reply[0] & 0x0f) as usize * 4).min(reply.len());
+            reply = &reply[header_len..];
+        }
+        if reply.len() >= 8 && reply[0] == echo_reply && reply[6..8] == seq {
+            return Ok(Some(elapsed_ms(started)));
+        }
+    }
+}
+
+/// Echo through the ICMP helper API (no admin rights needed).  IPv6 goes
+/// straight to the TCP fallback.
+#[cfg(windows)]
+fn icmp_echo(ip: IpAddr) -> io::Result<Option<f64>> {
+    use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        IcmpCloseHandle, IcmpCreateFile, IcmpSendEcho, ICMP_ECHO_REPLY,
+    };
+
+    let IpAddr::V4(v4) = ip else {
+        return Ok(None);
+    };
+    let mut reply = vec![0u8; std::mem::size_of::<ICMP_ECHO_REPLY>() + ECHO_PAYLOAD.len() + 8];
+    let started = Instant::now();
+    // SAFETY: the handle is checked before use and closed before returning;
+    // the reply buffer is sized for one ICMP_ECHO_REPLY plus the payload.
+    let (replies, status) = unsafe {
+        let handle = IcmpCreateFile();
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        let replies = IcmpSendEcho(
+            handle,
+            u32::from_ne_bytes(v4.octets()),
+            ECHO_PAYLOAD.as_ptr().cast(),
+            ECHO_PAYLOAD.len() as u16,
+            std::ptr::null(),
+            reply.as_mut_ptr().cast(),
+            reply.len() as u32,
+            PROBE_TIMEOUT.as_millis() as u32,
+        );
+        IcmpCloseHandle(handle);
+        let echo = std::ptr::read_unaligned(reply.as_ptr() as *const ICMP_ECHO_REPLY);
+        (replies, echo.Status)
+    };
+    // Status 0 is IP_SUCCESS
+    if replies == 0 || status != 0 {
+        return Ok(None);
+    }
+    Ok(Some(elapsed_ms(started)))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn icmp_echo(_ip: IpAddr) -> io::Result<Option<f64>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no ICMP support on this platform"))
+}
+
+/// RFC 1071 ones' complement checksum.
+#[cfg(unix)]
+fn icmp_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -0,0 +1,219 @@
+use crate::proctree::ProcessTree;
+use chrono::Utc;
+use serde::Serialize;
+
+/// Re-resolve cached metadata after this long, to pick up app updates.
+pub const METADATA_CACHE_TTL_DAYS: u32 = 30;
+
+// ─── Metadata ───────────────────────────────────────────────────────────────
+
+/// What Top Apps shows for a process beyond its name; the result of
+/// `cmd_get_process_metadata`.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessMetadata {
+    pub name: String,
+    /// `None` when no process of that name is running (or its image is
+    /// unreadable), in which case nothing else is resolved either.
+    pub exe_path: Option<String>,
+    pub version: Option<String>,
+    pub product_name: Option<String>,
+    pub description: Option<String>,
+    /// Company from the version resource, else the signer's name.
+    pub publisher: Option<String>,
+    /// Subject of the code-signing certificate.
+    pub signer: Option<String>,
+    /// The signature was present and verified.
+    pub signed: bool,
+    /// PNG, base64 encoded.
+    pub icon_png: Option<String>,
+    pub resolved_at: String,
+}
+
+/// Resolve `name` against the running processes and read its executable's
+/// version, signature and icon.
+pub fn resolve(name: &str) -> ProcessMetadata {
+    let mut meta = ProcessMetadata {
+        name: name.to_string(),
+        exe_path: ProcessTree::snapshot().executable(name),
+        resolved_at: Utc::now().to_rfc3339(),
+        ..Default::default()
+    };
+    if let Some(exe) = meta.exe_path.clone() {
+        read_details(&exe, &mut meta);
+        if meta.publisher.is_none() {
+            meta.publisher = meta.signer.as_deref().and_then(signer_name);
+        }
+    }
+    meta
+}
+
+/// The organisation in a signer: `CN=` of an Authenticode subject, or the
+/// name in a macOS `Developer ID Application: Name (TEAMID)` authority.
+fn signer_name(signer: &str) -> Option<String> {
+    let name = if let Some(cn) = signer.split(", ").find_map(|part| part.strip_prefix("CN=")) {
+        cn.trim_matches('"')
+    } else {
+        let name = signer.split_once(": ").map_or(signer, |(_, name)| name);
+        match name.rsplit_once(" (") {
+            Some((name, team)) if team.ends_with(')') => name,
+            _ => name,
+        }
+    };
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// ─── Platform readers ───────────────────────────────────────────────────────
+
+/// Version resource, Authenticode signature and associated icon, in one
+/// PowerShell call.
+#[cfg(target_os = "windows")]
+fn read_details(exe: &str, meta: &mut ProcessMetadata) {
+    use serde::Deserialize;
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const SCRIPT: &str = "$path = $env:ABYSS_EXE; \
+        $v = (Get-Item -LiteralPath $path).VersionInfo; \
+        $sig = Get-AuthenticodeSignature -LiteralPath $path; \
+        $icon = $null; \
+        try { \
+            Add-Type -AssemblyName System.Drawing; \
+            $bmp = [System.Drawing.Icon]::ExtractAssociatedIcon($path).ToBitmap(); \
+            $ms = New-Object System.IO.MemoryStream; \
+            $bmp.Save($ms, [System.Drawing.Imaging.ImageFormat]::Png); \
+            $icon = [Convert]::ToBase64String($ms.ToArray()) \
+        } catch {}; \
+        [pscustomobject]@{ version = $v.ProductVersion; productName = $v.ProductName; description = $v.FileDescription; publisher = $v.CompanyName; signer = $sig.SignerCertificate.Subject; signed = ($sig.Status -eq 'Valid'); icon = $icon } | ConvertTo-Json -Compress";
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Details {
+        version: Option<String>,
+        product_name: Option<String>,
+        description: Option<String>,
+        publisher: Option<String>,
+        signer: Option<String>,
+        signed: bool,
+        icon: Option<String>,
+    }
+
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("ABYSS_EXE", exe)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output();
+    let Ok(output) = output else {
+        return;
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let Some(details) = stdout
+        .lines()
+        .find_map(|line| serde_json::from_str::<Details>(line.trim()).ok())
+    else {
+        eprintln!("[Abyss] Could not read metadata for {exe}");
+        return;
+    };
+    let non_empty = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    meta.version = non_empty(details.version);
+    meta.product_name = non_empty(details.product_name);
+    meta.description = non_empty(details.description);
+    meta.publisher = non_empty(details.publisher);
+    meta.signer = non_empty(details.signer);
+    meta.signed = details.signed;
+    meta.icon_png = non_empty(details.icon);
+}
+
+/// `Info.plist`, `codesign` and the bundle icon of the `.app` the
+/// executable lives in.  Bare binaries only get their signature checked.
+#[cfg(target_os = "macos")]
+fn read_details(exe: &str, meta: &mut ProcessMetadata) {
+    use std::path::Path;
+    use std::process::Command;
+
+    let exe = Path::new(exe);
+    let bundle = exe
+        .ancestors()
+        .find(|p| p.extension().is_some_and(|ext| ext == "app"))
+        .unwrap_or(exe);
+
+    // codesign prints its description on stderr
+    if let Ok(output) = Command::new("codesign").args(["-dv", "--verbose=2"]).arg(bundle).output() {
+        let info = String::from_utf8_lossy(&output.stderr);
+        meta.signer = info
+            .lines()
+            .find_map(|line| line.strip_prefix("Authority="))
+            .map(str::to_string);
+        meta.signed = output.status.success()
+            && meta.signer.is_some()
+            && Command::new("codesign")
+                .args(["--verify", "--deep"])
+                .arg(bundle)
+                .status()
+                .is_ok_and(|s| s.success());
+    }
+
+    let plist = bundle.join("Contents/Info.plist");
+    let Ok(output) = Command::new("plutil")
+        .args(["-convert", "json", "-o", "-"])
+        .arg(&plist)
+        .output()
+    else {
+        return;
+    };
+    let Ok(info) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+        return;
+    };
+    let field = |key: &str| info.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    meta.version = field("CFBundleShortVersionString").or_else(|| field("CFBundleVersion"));
+    meta.product_name = field("CFBundleDisplayName").or_else(|| field("CFBundleName"));
+    meta.description = field("CFBundleGetInfoString");
+
+    let Some(icon) = field("CFBundleIconFile") else {
+        return;
+    };
+    let mut icns = bundle.join("Contents/Resources").join(icon);
+    if icns.extension().is_none() {
+        icns.set_extension("icns");
+    }
+    let png = std::env::temp_dir().join(format!("abyss-icon-{}.png", std::process::id()));
+    let converted = Command::new("sips")
+        .args(["-s", "format", "png", "-Z", "64"])
+        .arg(&icns)
+        .arg("--out")
+        .arg(&png)
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if converted {
+        meta.icon_png = std::fs::read(&png).ok().map(|bytes| crate::base64(&bytes));
+    }
+    let _ = std::fs::remove_file(&png);
+}
+
+/// Linux executables carry no version resource or signature; icons live
+/// with desktop entries rather than the binary, so only the path is known.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn read_details(_exe: &str, _meta: &mut ProcessMetadata) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signer_names_from_authenticode_and_codesign() {
+        assert_eq!(
+            signer_name("CN=Microsoft Corporation, O=Microsoft Corporation, L=Redmond, S=Washington, C=US").as_deref(),
+            Some("Microsoft Corporation")
+        );
+        assert_eq!(
+            signer_name(r#"CN="Mozilla Corporation", O="Mozilla Corporation", C=US"#).as_deref(),
+            Some("Mozilla Corporation")
+        );
+        assert_eq!(
+            signer_name("Developer ID Application: Google LLC (EQHXZ8M8AV)").as_deref(),
+            Some("Google LLC")
+        );
+        assert_eq!(signer_name("Apple Root CA").as_deref(), Some("Apple Root CA"));
+        assert_eq!(signer_name(""), None);
+    }
+}
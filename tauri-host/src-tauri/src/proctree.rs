@@ -0,0 +1,376 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Longest parent chain followed; guards against PID cycles from reuse.
+const MAX_DEPTH: usize = 32;
+
+/// Processes that start apps rather than being one (init systems, shells,
+/// session managers, terminals).  A helper's ancestry is followed up to, but
+/// not including, the first of these.  Compared case-insensitively.
+const LAUNCHERS: &[&str] = &[
+    // Linux
+    "init",
+    "systemd",
+    "gnome-shell",
+    "gnome-session-binary",
+    "plasmashell",
+    "kwin_wayland",
+    "kwin_x11",
+    "gnome-terminal-server",
+    "konsole",
+    "xterm",
+    "tmux: server",
+    "screen",
+    "sshd",
+    "sudo",
+    "login",
+    "sh",
+    "bash",
+    "dash",
+    "zsh",
+    "fish",
+    // macOS
+    "launchd",
+    "kernel_task",
+    "Terminal",
+    "iTerm2",
+    // Windows
+    "System",
+    "wininit.exe",
+    "winlogon.exe",
+    "services.exe",
+    "svchost.exe",
+    "userinit.exe",
+    "explorer.exe",
+    "sihost.exe",
+    "RuntimeBroker.exe",
+    "cmd.exe",
+    "powershell.exe",
+    "pwsh.exe",
+    "conhost.exe",
+    "OpenConsole.exe",
+    "WindowsTerminal.exe",
+];
+
+// ─── Process tree ───────────────────────────────────────────────────────────
+
+/// One running process.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// `None` for roots (PID 0/1) or when the parent is unknown.
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    pub exe: Option<String>,
+    /// Windows only exposes another process's command line by reading its
+    /// memory, so it is left unset there.
+    pub cmdline: Option<String>,
+}
+
+/// A process and the ancestors its traffic is grouped under; the result of
+/// `cmd_get_process_tree`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessLineage {
+    pub process: ProcessInfo,
+    /// Parent first, up to the group's top-level process.
+    pub ancestors: Vec<ProcessInfo>,
+    pub app_group: String,
+}
+
+/// Snapshot of the running processes, for PID → name and PID → app group.
+#[derive(Default)]
+pub struct ProcessTree {
+    processes: HashMap<u32, ProcessInfo>,
+}
+
+impl ProcessTree {
+    pub fn snapshot() -> Self {
+        Self::from_processes(platform_processes())
+    }
+
+    pub fn from_processes(processes: impl IntoIterator<Item = ProcessInfo>) -> Self {
+        Self {
+            processes: processes.into_iter().map(|p| (p.pid, p)).collect(),
+        }
+    }
+
+    pub fn names(&self) -> HashMap<u32, String> {
+        self.processes
+            .iter()
+            .map(|(&pid, p)| (pid, p.name.clone()))
+            .collect()
+    }
+
+    /// The app each process belongs to: helpers (`msedgewebview2.exe`,
+    /// renderer and GPU processes) are grouped under the top-level process
+    /// that started them.
+    pub fn app_groups(&self) -> HashMap<u32, String> {
+        self.processes
+            .keys()
+            .filter_map(|&pid| Some((pid, self.group_root(pid)?.name.clone())))
+            .collect()
+    }
+
+    /// Image path of a running process called `name` (case-insensitive).
+    pub fn executable(&self, name: &str) -> Option<String> {
+        self.processes
+            .values()
+            .filter(|p| p.name.eq_ignore_ascii_case(name))
+            .find_map(|p| p.exe.clone())
+    }
+
+    pub fn lineage(&self, pid: u32) -> Option<ProcessLineage> {
+        let process = self.processes.get(&pid)?.clone();
+        let ancestors: Vec<ProcessInfo> = self.ancestry(pid).into_iter().skip(1).cloned().collect();
+        let app_group = ancestors.last().unwrap_or(&process).name.clone();
+        Some(ProcessLineage {
+            process,
+            ancestors,
+            app_group,
+        })
+    }
+
+    fn group_root(&self, pid: u32) -> Option<&ProcessInfo> {
+        self.ancestry(pid).pop()
+    }
+
+    /// `pid` followed by its ancestors up to the first launcher.
+    fn ancestry(&self, pid: u32) -> Vec<&ProcessInfo> {
+        let Some(mut current) = self.processes.get(&pid) else {
+            return Vec::new();
+        };
+        let mut chain = vec![current];
+        while chain.len() < MAX_DEPTH {
+            let parent = current
+                .parent_pid
+                .filter(|&ppid| ppid != current.pid && chain.iter().all(|p| p.pid != ppid))
+                .and_then(|ppid| self.processes.get(&ppid));
+            match parent {
+                Some(parent) if !is_launcher(&parent.name) => {
+                    chain.push(parent);
+                    current = parent;
+                }
+                _ => break,
+            }
+        }
+        chain
+    }
+}
+
+fn is_launcher(name: &str) -> bool {
+    LAUNCHERS.iter().any(|l| l.eq_ignore_ascii_case(name))
+}
+
+/// Roots have no meaningful parent.
+fn parent_of(ppid: u32) -> Option<u32> {
+    (ppid > 1).then_some(ppid)
+}
+
+// ─── Platform process tables ────────────────────────────────────────────────
+
+/// Every process in `/proc`: name and parent from `stat`, executable from
+/// the `exe` link, arguments from `cmdline`.  Other users' executables are
+/// unreadable without privileges and stay unset.
+#[cfg(target_os = "linux")]
+fn platform_processes() -> Vec<ProcessInfo> {
+    let Ok(dir) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    dir.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+            // pid (comm) state ppid ... — comm may itself contain spaces and parens
+            let (open, close) = (stat.find('(')?, stat.rfind(')')?);
+            let ppid: u32 = stat.get(close + 1..)?.split_whitespace().nth(1)?.parse().ok()?;
+            let exe = std::fs::read_link(format!("/proc/{pid}/exe"))
+                .ok()
+                .map(|p| p.to_string_lossy().into_owned());
+            let cmdline = std::fs::read(format!("/proc/{pid}/cmdline"))
+                .ok()
+                .map(|raw| {
+                    String::from_utf8_lossy(&raw)
+                        .split('\0')
+                        .filter(|arg| !arg.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .filter(|cmdline| !cmdline.is_empty());
+            Some(ProcessInfo {
+                pid,
+                parent_pid: parent_of(ppid),
+                name: stat.get(open + 1..close)?.to_string(),
+                exe,
+                cmdline,
+            })
+        })
+        .collect()
+}
+
+/// Every process libproc lists; arguments from `KERN_PROCARGS2`.
+#[cfg(target_os = "macos")]
+fn platform_processes() -> Vec<ProcessInfo> {
+    use libproc::libproc::bsd_info::BSDInfo;
+    use libproc::libproc::proc_pid::{listpids, name, pidinfo, pidpath, ProcType};
+
+    let Ok(pids) = listpids(ProcType::ProcAllPIDS) else {
+        return Vec::new();
+    };
+    pids.into_iter()
+        .filter(|&pid| pid > 0)
+        .filter_map(|pid| {
+            let pid = pid as i32;
+            let info = pidinfo::<BSDInfo>(pid, 0).ok()?;
+            let exe = pidpath(pid).ok();
+            let name = name(pid).ok().filter(|n| !n.is_empty()).or_else(|| {
+                exe.as_deref()
+                    .and_then(|p| p.rsplit('/').next())
+                    .map(str::to_string)
+            })?;
+            Some(ProcessInfo {
+                pid: pid as u32,
+                parent_pid: parent_of(info.pbi_ppid),
+                name,
+                exe,
+                cmdline: process_args(pid),
+            })
+        })
+        .collect()
+}
+
+/// `argc`, the executable path, NUL padding, then `argc` NUL-terminated
+/// arguments (and the environment, which is skipped).
+#[cfg(target_os = "macos")]
+fn process_args(pid: i32) -> Option<String> {
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
+    let mut size: libc::size_t = 0;
+    // SAFETY: a null buffer asks for the size; the second call fills `buf`.
+    unsafe {
+        if libc::sysctl(mib.as_mut_ptr(), 3, std::ptr::null_mut(), &mut size, std::ptr::null_mut(), 0) != 0 {
+            return None;
+        }
+    }
+    let mut buf = vec![0u8; size];
+    unsafe {
+        if libc::sysctl(mib.as_mut_ptr(), 3, buf.as_mut_ptr().cast(), &mut size, std::ptr::null_mut(), 0) != 0 {
+            return None;
+        }
+    }
+    buf.truncate(size);
+    let argc = i32::from_ne_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+    let args: Vec<String> = buf[4..]
+        .split(|&b| b == 0)
+        .skip(1) // executable path
+        .skip_while(|s| s.is_empty())
+        .take(argc)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect();
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+/// Every process in a Toolhelp snapshot, with its image path.
+#[cfg(target_os = "windows")]
+fn platform_processes() -> Vec<ProcessInfo> {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+
+    let mut processes = Vec::new();
+    // SAFETY: `entry` is sized as the API requires and the snapshot is closed below.
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return processes;
+        }
+        let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+        entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+        let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+        while more {
+            let len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+            let pid = entry.th32ProcessID;
+            if pid > 0 {
+                processes.push(ProcessInfo {
+                    pid,
+                    parent_pid: parent_of(entry.th32ParentProcessID),
+                    name: String::from_utf16_lossy(&entry.szExeFile[..len]),
+                    exe: image_path(pid),
+                    cmdline: None,
+                });
+            }
+            more = Process32NextW(snapshot, &mut entry) != 0;
+        }
+        CloseHandle(snapshot);
+    }
+    processes
+}
+
+#[cfg(target_os = "windows")]
+fn image_path(pid: u32) -> Option<String> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: `len` holds the buffer size in characters, updated to the path length.
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return None;
+        }
+        let mut buf = [0u16; 1024];
+        let mut len = buf.len() as u32;
+        let ok = QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, buf.as_mut_ptr(), &mut len) != 0;
+        CloseHandle(handle);
+        ok.then(|| String::from_utf16_lossy(&buf[..len as usize]))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn platform_processes() -> Vec<ProcessInfo> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, parent_pid: u32, name: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            parent_pid: parent_of(parent_pid),
+            name: name.to_string(),
+            exe: None,
+            cmdline: None,
+        }
+    }
+
+    #[test]
+    fn helpers_are_grouped_under_the_app_that_started_them() {
+        let tree = ProcessTree::from_processes([
+            process(4, 0, "System"),
+            process(500, 4, "explorer.exe"),
+            process(600, 500, "Teams.exe"),
+            process(610, 600, "msedgewebview2.exe"),
+            process(611, 610, "msedgewebview2.exe"),
+            process(700, 500, "cmd.exe"),
+            process(701, 700, "curl.exe"),
+        ]);
+        let groups = tree.app_groups();
+        assert_eq!(groups[&611], "Teams.exe");
+        assert_eq!(groups[&600], "Teams.exe");
+        assert_eq!(groups[&701], "curl.exe");
+        assert_eq!(groups[&500], "explorer.exe");
+
+        let lineage = tree.lineage(611).unwrap();
+        let ancestors: Vec<u32> = lineage.ancestors.iter().map(|p| p.pid).collect();
+        assert_eq!(ancestors, vec![610, 600]);
+    }
+
+    #[test]
+    fn parent_cycles_terminate() {
+        let tree = ProcessTree::from_processes([process(10, 11, "a"), process(11, 10, "b")]);
+        assert_eq!(tree.lineage(10).unwrap().ancestors.len(), 1);
+    }
+}
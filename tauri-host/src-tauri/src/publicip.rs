@@ -0,0 +1,122 @@
+use crate::interfaces::InterfaceKind;
+use crate::monitor::MonitorHost;
+use crate::writer::{WriteCommand, WriterSender};
+use crate::{geo, routes, GeoInfo, LocalGeo};
+use chrono::Utc;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the public address is re-queried when the routes stay put.
+const PUBLIC_IP_POLL_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Wait after a route change before re-querying, so a VPN that just came up
+/// has finished its handshake.
+const ROUTE_SETTLE_DELAY: Duration = Duration::from_secs(3);
+
+/// Plain-text echo of the caller's address.
+const PUBLIC_IP_URL: &str = "https://api.ipify.org";
+
+// ─── Public address ─────────────────────────────────────────────────────────
+
+/// The machine's public address changed (or was read for the first time):
+/// a VPN connected or disconnected, or the ISP handed out a new one.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkChange {
+    pub timestamp: String,
+    pub ip: String,
+    pub previous_ip: Option<String>,
+    /// Where `ip` geolocates; unset when the lookup failed.
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
+    pub org: Option<String>,
+    /// Interface of the preferred default route at the time.
+    pub interface: Option<String>,
+    /// The preferred default route goes through a tunnel.
+    pub vpn: bool,
+    pub initial: bool,
+}
+
+/// This machine's public address, or `None` when offline.
+async fn query(client: &reqwest::Client) -> Option<String> {
+    let body = client.get(PUBLIC_IP_URL).send().await.ok()?.text().await.ok()?;
+    let ip: IpAddr = body.trim().parse().ok()?;
+    Some(ip.to_string())
+}
+
+/// Geolocate `ip` with the provider selected in settings.
+async fn locate(provider: &dyn geo::GeoProvider, client: &reqwest::Client, ip: &str) -> Option<GeoInfo> {
+    let batch = provider.lookup(client.clone(), vec![ip.to_string()]).await.ok()?;
+    batch.into_iter().find(|(addr, _)| addr == ip).and_then(|(_, info)| info)
+}
+
+// ─── Watcher ────────────────────────────────────────────────────────────────
+
+/// Re-query the public address every `PUBLIC_IP_POLL_INTERVAL`, and shortly
+/// after each default route change.  A new address is geolocated, becomes
+/// the map origin (`MonitorHost::set_local_geo`), is persisted by the
+/// writer against the current session and is reported to `host`.
+/// Nothing is queried while privacy mode is on.
+pub async fn watch(host: Arc<impl MonitorHost>, writer_tx: WriterSender) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap_or_default();
+    let mut current: Option<String> = None;
+    loop {
+        let settings = host.settings();
+        let (privacy_mode, provider_kind, api_key) = (settings.privacy_mode, settings.geo_provider, settings.geo_api_key);
+        let ip = if privacy_mode { None } else { query(&client).await };
+        if let Some(ip) = ip.filter(|ip| current.as_ref() != Some(ip)) {
+            let provider = geo::provider(provider_kind, api_key, host.geo_db());
+            let info = locate(&*provider, &client, &ip).await;
+            let route = tokio::task::spawn_blocking(routes::default_routes)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .next();
+
+            let initial = current.is_none();
+            let previous_ip = current.replace(ip.clone());
+            if !initial {
+                println!(
+                    "[Abyss] Public IP changed: {} → {ip}",
+                    previous_ip.as_deref().unwrap_or("none")
+                );
+            }
+            if let Some(info) = &info {
+                host.set_local_geo(&LocalGeo {
+                    lat: info.lat,
+                    lng: info.lng,
+                    city: info.city.clone(),
+                    country: info.country.clone(),
+                });
+            }
+            let change = NetworkChange {
+                timestamp: Utc::now().to_rfc3339(),
+                ip,
+                previous_ip,
+                city: info.as_ref().map(|i| i.city.clone()),
+                country: info.as_ref().map(|i| i.country.clone()),
+                lat: info.as_ref().map(|i| i.lat),
+                lng: info.as_ref().map(|i| i.lng),
+                org: info.as_ref().map(|i| i.org.clone()).filter(|org| !org.is_empty()),
+                vpn: route
+                    .as_ref()
+                    .is_some_and(|r| r.interface_kind == Some(InterfaceKind::Tunnel)),
+                interface: route.map(|r| r.interface),
+                initial,
+            };
+            host.network_changed(&change);
+            let _ = writer_tx.send(WriteCommand::RecordNetworkChange(change));
+        }
+        let woken = tokio::time::timeout(PUBLIC_IP_POLL_INTERVAL, host.public_ip_recheck().notified()).await;
+        if woken.is_ok() {
+            tokio::time::sleep(ROUTE_SETTLE_DELAY).await;
+        }
+    }
+}
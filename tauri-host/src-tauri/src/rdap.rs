@@ -0,0 +1,160 @@
+use crate::error::AbyssError;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Redirects to the RDAP server of the registry responsible for an address.
+const RDAP_BOOTSTRAP: &str = "https://rdap.org/ip/";
+const RDAP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Cached answers older than this are looked up again.
+pub const WHOIS_CACHE_TTL_DAYS: u32 = 7;
+
+// ─── Ownership ──────────────────────────────────────────────────────────────
+
+/// Who holds the network an address belongs to, from its RDAP record.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WhoisInfo {
+    pub ip: String,
+    /// Registry handle of the network (`NET-8-8-8-0-2`).
+    pub handle: Option<String>,
+    /// Network name (`GOGL`).
+    pub name: Option<String>,
+    /// CIDR blocks covering the network, or `start - end` when the registry
+    /// only gives a range.
+    pub cidr: Vec<String>,
+    /// Registration country (ISO 3166 alpha-2).
+    pub country: Option<String>,
+    /// Registrant organization.
+    pub org: Option<String>,
+    pub abuse_name: Option<String>,
+    pub abuse_email: Option<String>,
+    pub abuse_phone: Option<String>,
+    pub registered_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// RDAP server that answered.
+    pub source: Option<String>,
+    /// When the record was fetched (RFC 3339).
+    pub fetched_at: String,
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(RDAP_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// Look `ip` up over RDAP (through the rdap.org bootstrap redirect).
+pub async fn lookup(ip: IpAddr) -> Result<WhoisInfo, AbyssError> {
+    let resp = client()
+        .get(format!("{RDAP_BOOTSTRAP}{ip}"))
+        .header("Accept", "application/rdap+json")
+        .send()
+        .await?;
+    match resp.status() {
+        s if s.is_success() => {}
+        reqwest::StatusCode::NOT_FOUND => {
+            return Err(AbyssError::NotFound(format!("No RDAP record for {ip}")));
+        }
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            return Err(AbyssError::Network("RDAP server rate limit reached, try again later".into()));
+        }
+        s => return Err(AbyssError::Network(format!("RDAP lookup failed with status {s}"))),
+    }
+    let source = resp.url().host_str().map(str::to_string);
+    let record: Value = resp.json().await?;
+    let mut info = parse(&ip.to_string(), &record);
+    info.source = source;
+    Ok(info)
+}
+
+/// Pull the ownership fields out of an RDAP IP network object (RFC 9083).
+fn parse(ip: &str, record: &Value) -> WhoisInfo {
+    let text = |v: &Value, key: &str| v.get(key).and_then(Value::as_str).map(str::to_string);
+
+    let mut cidr: Vec<String> = record
+        .get("cidr0_cidrs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|c| {
+            let prefix = c.get("v4prefix").or_else(|| c.get("v6prefix"))?.as_str()?;
+            Some(format!("{prefix}/{}", c.get("length")?.as_u64()?))
+        })
+        .collect();
+    if cidr.is_empty() {
+        if let (Some(start), Some(end)) = (text(record, "startAddress"), text(record, "endAddress")) {
+            cidr.push(format!("{start} - {end}"));
+        }
+    }
+
+    let event = |action: &str| {
+        record
+            .get("events")
+            .and_then(Value::as_array)?
+            .iter()
+            .find(|e| e.get("eventAction").and_then(Value::as_str) == Some(action))
+            .and_then(|e| text(e, "eventDate"))
+    };
+
+    let entities = entities(record);
+    let with_role = |role: &str| {
+        entities.iter().copied().find(|e| {
+            e.get("roles")
+                .and_then(Value::as_array)
+                .is_some_and(|roles| roles.iter().any(|r| r.as_str() == Some(role)))
+        })
+    };
+    let abuse = with_role("abuse");
+
+    WhoisInfo {
+        ip: ip.to_string(),
+        handle: text(record, "handle"),
+        name: text(record, "name"),
+        cidr,
+        country: text(record, "country"),
+        org: with_role("registrant").and_then(|e| vcard(e, "fn")),
+        abuse_name: abuse.and_then(|e| vcard(e, "fn")),
+        abuse_email: abuse.and_then(|e| vcard(e, "email")),
+        abuse_phone: abuse.and_then(|e| vcard(e, "tel")),
+        registered_at: event("registration"),
+        updated_at: event("last changed"),
+        source: None,
+        fetched_at: Utc::now().to_rfc3339(),
+    }
+}
+
+/// Entities of a record, including those nested in other entities (ARIN
+/// and RIPE list the abuse contact under the registrant).
+fn entities(record: &Value) -> Vec<&Value> {
+    let mut found = Vec::new();
+    let mut pending: Vec<&Value> = vec![record];
+    while let Some(parent) = pending.pop() {
+        for entity in parent.get("entities").and_then(Value::as_array).into_iter().flatten() {
+            found.push(entity);
+            pending.push(entity);
+        }
+    }
+    found
+}
+
+/// First value of a jCard property (`["vcard", [[name, params, type, value], ...]]`).
+fn vcard(entity: &Value, property: &str) -> Option<String> {
+    entity
+        .get("vcardArray")?
+        .get(1)?
+        .as_array()?
+        .iter()
+        .find(|p| p.get(0).and_then(Value::as_str) == Some(property))?
+        .get(3)?
+        .as_str()
+        .map(|v| v.trim_start_matches("tel:").trim().to_string())
+        .filter(|v| !v.is_empty())
+}
@@ -0,0 +1,417 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::alerts::format_bps;
+use crate::anonymize::Anonymizer;
+use crate::card::{escape, format_duration};
+use crate::db::{format_bytes_human, CardShare, SessionReport};
+use crate::error::AbyssError;
+
+const CHART_W: f64 = 720.0;
+const CHART_H: f64 = 180.0;
+const CHART_PAD_X: f64 = 56.0;
+const CHART_PAD_Y: f64 = 16.0;
+const BAR_ROW_H: f64 = 22.0;
+
+const STYLE: &str = "body{font-family:system-ui,-apple-system,'Segoe UI',sans-serif;color:#1f2937;margin:32px auto;max-width:800px;padding:0 16px}\
+h1{font-size:24px;margin:0 0 4px}h2{font-size:16px;margin:28px 0 8px;border-bottom:1px solid #e5e7eb;padding-bottom:4px}\
+.meta{color:#6b7280;font-size:13px}.stats{display:grid;grid-template-columns:repeat(3,1fr);gap:8px;margin-top:16px}\
+.stat{border:1px solid #e5e7eb;border-radius:8px;padding:8px 12px}.stat b{display:block;font-size:18px}.stat span{color:#6b7280;font-size:12px}\
+table{width:100%;border-collapse:collapse;font-size:13px}th,td{text-align:left;padding:4px 6px;border-bottom:1px solid #f1f5f9}\
+th{color:#6b7280;font-weight:600}td.num{text-align:right;font-variant-numeric:tabular-nums}\
+ul{padding-left:20px;font-size:14px}.sev-high{color:#b91c1c}.sev-medium{color:#b45309}.sev-low{color:#4b5563}\
+footer{margin-top:32px;color:#9ca3af;font-size:11px}section{break-inside:avoid}";
+
+// ─── Format ─────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    Html,
+    /// The HTML printed by a headless Chromium-based browser.
+    Pdf,
+}
+
+impl ReportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "html" => Some(ReportFormat::Html),
+            "pdf" => Some(ReportFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+// ─── HTML report ────────────────────────────────────────────────────────────
+
+/// Render `report` as a single HTML file with inline CSS and SVG charts, so
+/// it can be attached to a ticket and opened anywhere.  Addresses go
+/// through `anonymizer` when given, as in session exports.
+pub fn render_html(report: &SessionReport, generated_at: &str, anonymizer: Option<&Anonymizer>) -> String {
+    let session = &report.session;
+    let insights = &report.insights;
+    let ip = |ip: &str| anonymizer.map_or_else(|| ip.to_string(), |a| a.ip(ip));
+    let mut html = String::with_capacity(32 * 1024);
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html><html lang="en"><head><meta charset="utf-8"><title>{} — Abyss session report</title><style>{STYLE}</style></head><body>"#,
+        escape(&session.name)
+    );
+
+    let _ = write!(html, "<h1>{}</h1><div class=\"meta\">{}", escape(&session.name), escape(&timestamp(&session.started_at)));
+    if let Some(ended) = &session.ended_at {
+        let _ = write!(html, " → {}", escape(&timestamp(ended)));
+    }
+    if let Some(duration) = session.duration_secs {
+        let _ = write!(html, " · {}", format_duration(duration));
+    }
+    if !session.local_city.is_empty() || !session.local_country.is_empty() {
+        let place = [session.local_city.as_str(), session.local_country.as_str()]
+            .iter()
+            .filter(|s| !s.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = write!(html, " · {}", escape(&place));
+    }
+    html.push_str("</div>");
+
+    let stats = [
+        ("Total data", insights.total_data_human.clone()),
+        ("Uploaded", format_bytes_human(session.total_bytes_up)),
+        ("Downloaded", format_bytes_human(session.total_bytes_down)),
+        ("Peak throughput", format_bps(session.peak_bps)),
+        ("Average latency", format!("{:.0} ms", session.avg_latency_ms)),
+        ("Flows", session.total_flows.to_string()),
+        ("Destinations", insights.unique_destinations.to_string()),
+        ("Countries", insights.unique_countries.to_string()),
+        ("Peak concurrent flows", session.peak_flows.to_string()),
+    ];
+    html.push_str("<div class=\"stats\">");
+    for (label, value) in stats {
+        let _ = write!(html, "<div class=\"stat\"><b>{}</b><span>{label}</span></div>", escape(&value));
+    }
+    html.push_str("</div>");
+
+    if !report.frames.is_empty() {
+        let t: Vec<f64> = report.frames.iter().map(|f| f.t).collect();
+        let down: Vec<f64> = report.frames.iter().map(|f| f.download_bps).collect();
+        let up: Vec<f64> = report.frames.iter().map(|f| f.upload_bps).collect();
+        let latency: Vec<f64> = report.frames.iter().map(|f| f.latency_ms).collect();
+        html.push_str("<section><h2>Throughput</h2>");
+        html.push_str(&line_chart(&t, &[("Download", "#2563eb", &down), ("Upload", "#16a34a", &up)], format_bps));
+        html.push_str("</section><section><h2>Latency</h2>");
+        html.push_str(&line_chart(&t, &[("Latency", "#d97706", &latency)], |ms| format!("{ms:.0} ms")));
+        html.push_str("</section>");
+    }
+
+    html.push_str("<section><h2>Insights</h2><ul>");
+    if !insights.busiest_minute.is_empty() {
+        let _ = write!(html, "<li>Busiest moment: {}</li>", escape(&timestamp(&insights.busiest_minute)));
+    }
+    if !insights.most_active_process.is_empty() {
+        let _ = write!(html, "<li>Most active process: {}</li>", escape(&insights.most_active_process));
+    }
+    if !insights.top_services.is_empty() {
+        let _ = write!(html, "<li>Top services: {}</li>", escape(&insights.top_services.join(", ")));
+    }
+    if !insights.high_latency_destinations.is_empty() {
+        let _ = write!(
+            html,
+            "<li>High-latency destinations: {}</li>",
+            escape(&insights.high_latency_destinations.iter().map(|d| ip(d)).collect::<Vec<_>>().join(", "))
+        );
+    }
+    if !insights.unusual_ports.is_empty() {
+        let ports: Vec<String> = insights.unusual_ports.iter().map(|p| p.to_string()).collect();
+        let _ = write!(html, "<li>Unusual ports: {}</li>", ports.join(", "));
+    }
+    if let Some(longest) = &insights.longest_connection {
+        let _ = write!(
+            html,
+            "<li>Longest connection: {} ({}) for {}</li>",
+            escape(&ip(&longest.dst_ip)),
+            escape(&longest.service),
+            format_duration(longest.duration_secs)
+        );
+    }
+    for finding in &insights.connectivity {
+        let _ = write!(
+            html,
+            "<li class=\"sev-{}\">{}</li>",
+            escape(&finding.severity),
+            escape(&finding.message)
+        );
+    }
+    html.push_str("</ul></section>");
+
+    html.push_str("<section><h2>Anomalies</h2>");
+    if report.anomalies.is_empty() {
+        html.push_str("<p class=\"meta\">Nothing unusual compared with the baseline.</p>");
+    } else {
+        html.push_str("<table><tr><th>Severity</th><th>Finding</th><th>Deviation</th><th>Confidence</th></tr>");
+        for anomaly in &report.anomalies {
+            let _ = write!(
+                html,
+                "<tr><td class=\"sev-{0}\">{0}</td><td>{1}</td><td class=\"num\">{2:.1}σ</td><td class=\"num\">{3:.0}%</td></tr>",
+                escape(&anomaly.severity),
+                escape(&anomaly.message),
+                anomaly.deviation_sigmas,
+                anomaly.confidence * 100.0
+            );
+        }
+        html.push_str("</table>");
+    }
+    html.push_str("</section>");
+
+    if !report.top_processes.is_empty() {
+        html.push_str("<section><h2>Top processes</h2>");
+        html.push_str(&bar_chart(&report.top_processes, "#6366f1"));
+        html.push_str("</section>");
+    }
+    if !report.top_countries.is_empty() {
+        html.push_str("<section><h2>Top countries</h2>");
+        html.push_str(&bar_chart(&report.top_countries, "#0891b2"));
+        html.push_str("</section>");
+    }
+
+    if !report.top_destinations.is_empty() {
+        html.push_str("<section><h2>Top destinations</h2><table><tr><th>Address</th><th>Organisation</th><th>Country</th><th>Service</th><th>Process</th><th>Data</th><th>Connections</th></tr>");
+        for dest in &report.top_destinations {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+                escape(&ip(&dest.ip)),
+                escape(dest.org.as_deref().unwrap_or("")),
+                escape(dest.country.as_deref().unwrap_or("")),
+                escape(dest.primary_service.as_deref().unwrap_or("")),
+                escape(dest.primary_process.as_deref().unwrap_or("")),
+                format_bytes_human(dest.total_bytes),
+                dest.connection_count
+            );
+        }
+        html.push_str("</table></section>");
+    }
+
+    if !session.notes.trim().is_empty() {
+        let _ = write!(html, "<section><h2>Notes</h2><p>{}</p></section>", escape(&session.notes).replace('\n', "<br>"));
+    }
+
+    let _ = write!(
+        html,
+        "<footer>Generated by Abyss on {} · session {}</footer></body></html>",
+        escape(&timestamp(generated_at)),
+        escape(&session.id)
+    );
+    html
+}
+
+/// `2024-05-01T12:34:56.789Z` → `2024-05-01 12:34:56`.
+fn timestamp(rfc3339: &str) -> String {
+    rfc3339.get(..19).unwrap_or(rfc3339).replace('T', " ")
+}
+
+/// One SVG line per series over the shared `t` axis (seconds into the
+/// session), with the maximum and the time range labelled.
+fn line_chart(t: &[f64], series: &[(&str, &str, &[f64])], label: impl Fn(f64) -> String) -> String {
+    let (t0, t1) = (t.first().copied().unwrap_or(0.0), t.last().copied().unwrap_or(0.0));
+    let span = (t1 - t0).max(1.0);
+    let max = series
+        .iter()
+        .flat_map(|(_, _, values)| values.iter())
+        .cloned()
+        .fold(0.0, f64::max);
+    let plot_w = CHART_W - CHART_PAD_X;
+    let plot_h = CHART_H - 2.0 * CHART_PAD_Y;
+    let bottom = CHART_PAD_Y + plot_h;
+
+    let mut svg = String::with_capacity(8192);
+    let _ = write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="100%" viewBox="0 0 {CHART_W} {CHART_H}" font-size="11" fill="#6b7280">"##
+    );
+    let _ = write!(
+        svg,
+        r##"<line x1="{CHART_PAD_X}" y1="{CHART_PAD_Y}" x2="{CHART_W}" y2="{CHART_PAD_Y}" stroke="#f1f5f9"/><line x1="{CHART_PAD_X}" y1="{bottom}" x2="{CHART_W}" y2="{bottom}" stroke="#e5e7eb"/>"##
+    );
+    let _ = write!(
+        svg,
+        r##"<text x="{}" y="{}" text-anchor="end">{}</text><text x="{}" y="{}" text-anchor="end">0</text>"##,
+        CHART_PAD_X - 6.0,
+        CHART_PAD_Y + 4.0,
+        escape(&label(max)),
+        CHART_PAD_X - 6.0,
+        bottom
+    );
+    let _ = write!(
+        svg,
+        r##"<text x="{CHART_PAD_X}" y="{CHART_H}">{}</text><text x="{CHART_W}" y="{CHART_H}" text-anchor="end">{}</text>"##,
+        format_duration(t0),
+        format_duration(t1)
+    );
+
+    for (i, (name, colour, values)) in series.iter().enumerate() {
+        let mut points = String::new();
+        for (t, v) in t.iter().zip(values.iter()) {
+            let ratio = if max > 0.0 { v.max(0.0) / max } else { 0.0 };
+            let x = CHART_PAD_X + (t - t0) / span * plot_w;
+            let y = bottom - ratio * plot_h;
+            let _ = write!(points, "{}{x:.1},{y:.1}", if points.is_empty() { "" } else { " " });
+        }
+        let _ = write!(
+            svg,
+            r##"<polyline points="{points}" fill="none" stroke="{colour}" stroke-width="1.5" stroke-linejoin="round"/>"##
+        );
+        if series.len() > 1 {
+            let x = CHART_W - 90.0 * (series.len() - i) as f64;
+            let _ = write!(
+                svg,
+                r##"<rect x="{x}" y="2" width="10" height="3" fill="{colour}"/><text x="{}" y="8">{}</text>"##,
+                x + 14.0,
+                escape(name)
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Horizontal bars sized by each row's share of the listed total.
+fn bar_chart(rows: &[CardShare], colour: &str) -> String {
+    let height = rows.len() as f64 * BAR_ROW_H;
+    let bar_x = 180.0;
+    let bar_w = CHART_W - bar_x - 90.0;
+    let mut svg = String::with_capacity(rows.len() * 256);
+    let _ = write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="100%" viewBox="0 0 {CHART_W} {height}" font-size="12" fill="#374151">"##
+    );
+    for (i, row) in rows.iter().enumerate() {
+        let y = i as f64 * BAR_ROW_H;
+        let w = (row.share * bar_w).max(2.0);
+        let _ = write!(
+            svg,
+            r##"<text x="0" y="{:.1}">{}</text><rect x="{bar_x}" y="{:.1}" width="{w:.1}" height="12" rx="2" fill="{colour}"/><text x="{:.1}" y="{:.1}" fill="#6b7280">{} · {:.0}%</text>"##,
+            y + 14.0,
+            escape(&row.label),
+            y + 4.0,
+            bar_x + w + 6.0,
+            y + 14.0,
+            format_bytes_human(row.bytes),
+            row.share * 100.0
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+// ─── PDF ────────────────────────────────────────────────────────────────────
+
+/// Print `html` to `pdf_path` with a headless Edge, Chrome or Chromium —
+/// the same engine the webview uses, so the charts come out as rendered.
+pub fn print_pdf(html: &str, pdf_path: &Path) -> Result<(), AbyssError> {
+    let browser = browser_candidates().into_iter().find(|p| p.is_file()).ok_or_else(|| {
+        AbyssError::Internal(
+            "PDF reports need Microsoft Edge, Google Chrome or Chromium installed; save as HTML instead".into(),
+        )
+    })?;
+
+    let scratch = std::env::temp_dir().join(format!("abyss-report-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch)?;
+    let html_path = scratch.join("report.html");
+    std::fs::write(&html_path, html)?;
+
+    let mut command = std::process::Command::new(&browser);
+    command
+        .args(["--headless", "--disable-gpu", "--no-first-run", "--no-pdf-header-footer"])
+        // A throwaway profile so a browser the user already has open isn't reused
+        .arg(format!("--user-data-dir={}", scratch.join("profile").display()))
+        .arg(format!("--print-to-pdf={}", pdf_path.display()))
+        .arg(&html_path);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output();
+    let _ = std::fs::remove_dir_all(&scratch);
+    let output = output?;
+
+    if !output.status.success() || !pdf_path.is_file() {
+        return Err(AbyssError::Internal(format!(
+            "{} could not print the report: {}",
+            browser.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Where a Chromium-based browser is usually installed; Edge first on
+/// Windows since it ships with the OS.
+#[cfg(target_os = "windows")]
+fn browser_candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    for root in ["ProgramFiles(x86)", "ProgramFiles", "LOCALAPPDATA"] {
+        if let Some(root) = std::env::var_os(root) {
+            let root = Path::new(&root);
+            candidates.push(root.join(r"Microsoft\Edge\Application\msedge.exe"));
+            candidates.push(root.join(r"Google\Chrome\Application\chrome.exe"));
+        }
+    }
+    candidates
+}
+
+#[cfg(target_os = "macos")]
+fn browser_candidates() -> Vec<PathBuf> {
+    [
+        "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+        "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+        "/Applications/Chromium.app/Contents/MacOS/Chromium",
+        "/Applications/Brave Browser.app/Contents/MacOS/Brave Browser",
+    ]
+    .into_iter()
+    .map(PathBuf::from)
+    .collect()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn browser_candidates() -> Vec<PathBuf> {
+    const NAMES: &[&str] = &[
+        "chromium",
+        "chromium-browser",
+        "google-chrome",
+        "google-chrome-stable",
+        "microsoft-edge",
+        "brave-browser",
+    ];
+    let Some(path) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+    std::env::split_paths(&path)
+        .flat_map(|dir| NAMES.iter().map(move |name| dir.join(name)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_chart_scales_to_the_series_maximum() {
+        let t = [0.0, 30.0, 60.0];
+        let svg = line_chart(&t, &[("Down", "#000", &[0.0, 50.0, 100.0])], |v| format!("{v} <max>"));
+        // First point sits on the baseline, the last at the top-right corner
+        assert!(svg.contains(&format!("points=\"{CHART_PAD_X:.1},{:.1} ", CHART_H - CHART_PAD_Y)));
+        assert!(svg.contains(&format!(" {CHART_W:.1},{CHART_PAD_Y:.1}\"")));
+        assert!(svg.contains("100 &lt;max&gt;"));
+        assert!(svg.contains(">1m<"));
+    }
+
+    #[test]
+    fn parses_formats_case_insensitively() {
+        assert_eq!(ReportFormat::parse("PDF"), Some(ReportFormat::Pdf));
+        assert_eq!(ReportFormat::parse("html"), Some(ReportFormat::Html));
+        assert_eq!(ReportFormat::parse("docx"), None);
+    }
+}
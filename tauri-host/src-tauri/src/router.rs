@@ -0,0 +1,513 @@
+#[cfg(feature = "gui")]
+use crate::app::AppState;
+use crate::error::AbyssError;
+use crate::locks::LockExt;
+use crate::writer::{WriteCommand, WriterSender};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+#[cfg(feature = "gui")]
+use tauri::Manager;
+use tokio::net::UdpSocket;
+
+/// SSDP multicast group gateways answer discovery on.
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+const IGD_DEVICE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+const WAN_COMMON_SERVICE: &str = "urn:schemas-upnp-org:service:WANCommonInterfaceConfig:1";
+/// How long to wait for SSDP replies, SOAP calls and SNMP responses.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// How often the watcher re-checks settings while router polling is off.
+const DISABLED_RECHECK: Duration = Duration::from_secs(30);
+/// Deltas implying more than this (bits/s) are counter resets, not traffic.
+const MAX_PLAUSIBLE_BPS: f64 = 100e9;
+
+// ─── Configuration ──────────────────────────────────────────────────────────
+
+/// Where WAN byte counters are read from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RouterSource {
+    #[default]
+    Off,
+    /// UPnP IGD `WANCommonInterfaceConfig` (found by SSDP discovery).
+    Upnp,
+    /// SNMPv2c interface counters of `snmp_if_index` on `snmp_host`.
+    Snmp,
+}
+
+impl RouterSource {
+    /// Value stored in `router_samples.source`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RouterSource::Off => "off",
+            RouterSource::Upnp => "upnp",
+            RouterSource::Snmp => "snmp",
+        }
+    }
+}
+
+/// Gateway counter polling.  Persisted in settings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RouterConfig {
+    pub source: RouterSource,
+    /// SNMP agent, `host` or `host:port` (161 when omitted).
+    pub snmp_host: Option<String>,
+    pub snmp_community: String,
+    /// `ifIndex` of the router's WAN interface.
+    pub snmp_if_index: u32,
+    pub poll_secs: u64,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            source: RouterSource::Off,
+            snmp_host: None,
+            snmp_community: "public".to_string(),
+            snmp_if_index: 1,
+            poll_secs: 60,
+        }
+    }
+}
+
+impl RouterConfig {
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        if !(10..=3600).contains(&self.poll_secs) {
+            return Err(AbyssError::InvalidInput("router.pollSecs must be between 10 and 3600".into()));
+        }
+        if self.source == RouterSource::Snmp {
+            if self.snmp_host.as_deref().is_none_or(|h| h.trim().is_empty()) {
+                return Err(AbyssError::InvalidInput("router.snmpHost is required for SNMP".into()));
+            }
+            if self.snmp_community.is_empty() {
+                return Err(AbyssError::InvalidInput("router.snmpCommunity must not be empty".into()));
+            }
+            if self.snmp_if_index == 0 {
+                return Err(AbyssError::InvalidInput("router.snmpIfIndex must be at least 1".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+// ─── Counters ───────────────────────────────────────────────────────────────
+
+/// One reading of the gateway's cumulative WAN byte counters.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouterCounters {
+    pub source: RouterSource,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Counter width: IGD and `ifInOctets` wrap at 2^32, `ifHCInOctets` at 2^64.
+    pub counter_bits: u32,
+}
+
+/// Traffic the gateway counted between two readings, recorded against the
+/// current session.
+#[derive(Clone, Debug)]
+pub struct RouterSample {
+    pub timestamp: String,
+    pub source: RouterSource,
+    pub interval_secs: f64,
+    pub bytes_sent: f64,
+    pub bytes_received: f64,
+}
+
+impl RouterCounters {
+    /// Bytes (sent, received) since `prev`, allowing for one 32-bit wrap.
+    /// `None` when the counters were reset (router reboot) or the source
+    /// changed in between.
+    fn delta_since(&self, prev: &RouterCounters, interval_secs: f64) -> Option<(u64, u64)> {
+        if prev.source != self.source || prev.counter_bits != self.counter_bits {
+            return None;
+        }
+        let diff = |prev: u64, cur: u64| -> Option<u64> {
+            if cur >= prev {
+                Some(cur - prev)
+            } else if self.counter_bits == 32 {
+                Some((1u64 << 32) - prev + cur)
+            } else {
+                None
+            }
+        };
+        let sent = diff(prev.bytes_sent, self.bytes_sent)?;
+        let received = diff(prev.bytes_received, self.bytes_received)?;
+        // A reboot of a 32-bit counter looks like a wrap; no link carries this much
+        let limit = MAX_PLAUSIBLE_BPS / 8.0 * interval_secs.max(1.0);
+        (sent as f64 <= limit && received as f64 <= limit).then_some((sent, received))
+    }
+}
+
+/// Read the WAN counters once with `config`.  `upnp_control` caches the
+/// discovered IGD control URL between calls.
+pub async fn read_counters(
+    config: &RouterConfig,
+    upnp_control: &mut Option<String>,
+) -> Result<RouterCounters, AbyssError> {
+    match config.source {
+        RouterSource::Off => Err(AbyssError::InvalidInput("Router polling is off".into())),
+        RouterSource::Upnp => {
+            let control = match upnp_control.clone() {
+                Some(url) => url,
+                None => {
+                    let url = discover_igd().await?;
+                    *upnp_control = Some(url.clone());
+                    url
+                }
+            };
+            let result = upnp_counters(&control).await;
+            if result.is_err() {
+                // The gateway may have moved or restarted; rediscover next time
+                *upnp_control = None;
+            }
+            result
+        }
+        RouterSource::Snmp => snmp_counters(config).await,
+    }
+}
+
+// ─── Watcher ────────────────────────────────────────────────────────────────
+
+/// Poll the gateway every `RouterConfig::poll_secs` while enabled and send
+/// the traffic it counted in each interval to the writer.  Failures are
+/// logged once until the next success.
+#[cfg(feature = "gui")]
+pub async fn watch(app: tauri::AppHandle, writer_tx: WriterSender) {
+    let mut upnp_control: Option<String> = None;
+    let mut previous: Option<(RouterCounters, Instant)> = None;
+    let mut failing = false;
+    loop {
+        let config = match app.try_state::<AppState>() {
+            Some(state) => state.settings.lock_or_recover("settings").router.clone(),
+            None => RouterConfig::default(),
+        };
+        if config.source == RouterSource::Off {
+            previous = None;
+            upnp_control = None;
+            tokio::time::sleep(DISABLED_RECHECK).await;
+            continue;
+        }
+
+        match read_counters(&config, &mut upnp_control).await {
+            Ok(counters) => {
+                if failing {
+                    println!("[Abyss] Router counters available again ({})", counters.source.as_str());
+                    failing = false;
+                }
+                let now = Instant::now();
+                if let Some((prev, at)) = &previous {
+                    let interval_secs = now.duration_since(*at).as_secs_f64();
+                    if let Some((sent, received)) = counters.delta_since(prev, interval_secs) {
+                        let _ = writer_tx.send(WriteCommand::RecordRouterSample(RouterSample {
+                            timestamp: Utc::now().to_rfc3339(),
+                            source: counters.source,
+                            interval_secs,
+                            bytes_sent: sent as f64,
+                            bytes_received: received as f64,
+                        }));
+                    }
+                }
+                previous = Some((counters, now));
+            }
+            Err(e) => {
+                if !failing {
+                    eprintln!("[Abyss] Router counters unavailable: {e}");
+                    failing = true;
+                }
+                previous = None;
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.poll_secs)).await;
+    }
+}
+
+// ─── UPnP IGD ───────────────────────────────────────────────────────────────
+
+/// Find the gateway by SSDP and return its `WANCommonInterfaceConfig`
+/// control URL.
+async fn discover_igd() -> Result<String, AbyssError> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_ADDR}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {IGD_DEVICE}\r\n\r\n"
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDR).await?;
+
+    let mut buf = [0u8; 2048];
+    let location = tokio::time::timeout(QUERY_TIMEOUT, async {
+        loop {
+            let (n, _) = socket.recv_from(&mut buf).await?;
+            let reply = String::from_utf8_lossy(&buf[..n]);
+            let location = reply.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim().eq_ignore_ascii_case("location").then(|| value.trim().to_string())
+            });
+            if let Some(location) = location {
+                return Ok::<_, std::io::Error>(location);
+            }
+        }
+    })
+    .await
+    .map_err(|_| AbyssError::NotFound("No UPnP gateway answered discovery".into()))??;
+
+    let description = http_client()
+        .get(&location)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let control = service_control_url(&description, WAN_COMMON_SERVICE).ok_or_else(|| {
+        AbyssError::NotFound("The gateway does not expose WAN byte counters over UPnP".into())
+    })?;
+    let base = xml_text(&description, "URLBase").unwrap_or(&location);
+    let url = reqwest::Url::parse(base)
+        .and_then(|base| base.join(control))
+        .map_err(|e| AbyssError::Network(format!("Bad UPnP control URL '{control}': {e}")))?;
+    Ok(url.to_string())
+}
+
+async fn upnp_counters(control_url: &str) -> Result<RouterCounters, AbyssError> {
+    let sent = soap_counter(control_url, "GetTotalBytesSent", "NewTotalBytesSent").await?;
+    let received = soap_counter(control_url, "GetTotalBytesReceived", "NewTotalBytesReceived").await?;
+    Ok(RouterCounters {
+        source: RouterSource::Upnp,
+        bytes_sent: sent,
+        bytes_received: received,
+        // The IGD spec types these as ui4; some gateways report 64-bit values anyway
+        counter_bits: if sent > u32::MAX as u64 || received > u32::MAX as u64 { 64 } else { 32 },
+    })
+}
+
+async fn soap_counter(control_url: &str, action: &str, field: &str) -> Result<u64, AbyssError> {
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{action} xmlns:u=\"{WAN_COMMON_SERVICE}\"/></s:Body></s:Envelope>"
+    );
+    let resp = http_client()
+        .post(control_url)
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header("SOAPAction", format!("\"{WAN_COMMON_SERVICE}#{action}\""))
+        .body(body)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(AbyssError::Network(format!("UPnP {action} returned HTTP {}", resp.status())));
+    }
+    let text = resp.text().await?;
+    xml_text(&text, field)
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or_else(|| AbyssError::Network(format!("UPnP {action} reply has no {field}")))
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+    CLIENT.get_or_init(|| reqwest::Client::builder().timeout(QUERY_TIMEOUT).build().unwrap_or_default())
+}
+
+/// Text of the first `<tag>` element (any namespace prefix).
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let mut from = 0;
+    while let Some(pos) = xml[from..].find(tag) {
+        let start = from + pos;
+        from = start + tag.len();
+        let prefix = xml[..start].rfind('<').map(|lt| &xml[lt + 1..start]);
+        let opening = prefix.is_some_and(|p| {
+            p.is_empty() || p.strip_suffix(':').is_some_and(|ns| ns.chars().all(|c| c.is_ascii_alphanumeric()))
+        });
+        if opening && xml[from..].starts_with('>') {
+            let content = &xml[from + 1..];
+            return content.find("</").map(|end| &content[..end]);
+        }
+    }
+    None
+}
+
+/// `controlURL` of the `<service>` whose `serviceType` is `service_type`.
+fn service_control_url<'a>(description: &'a str, service_type: &str) -> Option<&'a str> {
+    description
+        .split("<service>")
+        .skip(1)
+        .find(|block| xml_text(block, "serviceType").is_some_and(|t| t.trim() == service_type))
+        .and_then(|block| xml_text(block, "controlURL"))
+        .map(str::trim)
+}
+
+// ─── SNMP ───────────────────────────────────────────────────────────────────
+
+const IF_HC_IN_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 6];
+const IF_HC_OUT_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 10];
+const IF_IN_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 10];
+const IF_OUT_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 16];
+
+/// WAN counters over SNMPv2c: the 64-bit `ifHC*Octets` when the agent has
+/// them, else the 32-bit `ifInOctets`/`ifOutOctets`.
+async fn snmp_counters(config: &RouterConfig) -> Result<RouterCounters, AbyssError> {
+    let target = snmp_target(config.snmp_host.as_deref().unwrap_or_default());
+    let index = config.snmp_if_index;
+    let oid = |base: &[u32]| -> Vec<u32> { base.iter().copied().chain([index]).collect() };
+
+    let community = config.snmp_community.as_bytes();
+    match snmp_get(&target, community, &[oid(IF_HC_OUT_OCTETS), oid(IF_HC_IN_OCTETS)]).await {
+        Ok(values) => Ok(RouterCounters {
+            source: RouterSource::Snmp,
+            bytes_sent: values[0],
+            bytes_received: values[1],
+            counter_bits: 64,
+        }),
+        Err(AbyssError::NotFound(_)) => {
+            let values = snmp_get(&target, community, &[oid(IF_OUT_OCTETS), oid(IF_IN_OCTETS)]).await?;
+            Ok(RouterCounters {
+                source: RouterSource::Snmp,
+                bytes_sent: values[0],
+                bytes_received: values[1],
+                counter_bits: 32,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// `host`, `host:port`, an IP or `[v6]:port`, with port 161 by default.
+fn snmp_target(host: &str) -> String {
+    let host = host.trim();
+    if host.parse::<std::net::SocketAddr>().is_ok() {
+        return host.to_string();
+    }
+    if host.parse::<std::net::Ipv6Addr>().is_ok() {
+        return format!("[{host}]:161");
+    }
+    if host.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+        return host.to_string();
+    }
+    format!("{host}:161")
+}
+
+/// One GetRequest for `oids`; returns their counter values in order.
+/// `NotFound` when the agent has no such object.
+async fn snmp_get(target: &str, community: &[u8], oids: &[Vec<u32>]) -> Result<Vec<u64>, AbyssError> {
+    let request_id = (uuid::Uuid::new_v4().as_u128() & 0x7fff_ffff) as i64;
+    let varbinds: Vec<u8> = oids
+        .iter()
+        .flat_map(|oid| ber_tlv(0x30, &[ber_tlv(0x06, &ber_oid(oid)), vec![0x05, 0x00]].concat()))
+        .collect();
+    let pdu = [ber_int(request_id), ber_int(0), ber_int(0), ber_tlv(0x30, &varbinds)].concat();
+    // version 1 = SNMPv2c; 0xA0 = GetRequest-PDU
+    let message = ber_tlv(0x30, &[ber_int(1), ber_tlv(0x04, community), ber_tlv(0xa0, &pdu)].concat());
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(target).await?;
+    socket.send(&message).await?;
+    let mut buf = [0u8; 1500];
+    let n = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| AbyssError::Network(format!("No SNMP response from {target}")))??;
+    parse_snmp_response(&buf[..n], request_id, oids.len())
+}
+
+fn parse_snmp_response(buf: &[u8], request_id: i64, expected: usize) -> Result<Vec<u64>, AbyssError> {
+    let malformed = || AbyssError::Network("Malformed SNMP response".into());
+    let (_, message, _) = ber_read(buf).ok_or_else(malformed)?;
+    let (_, _version, rest) = ber_read(message).ok_or_else(malformed)?;
+    let (_, _community, rest) = ber_read(rest).ok_or_else(malformed)?;
+    let (tag, pdu, _) = ber_read(rest).ok_or_else(malformed)?;
+    if tag != 0xa2 {
+        return Err(malformed());
+    }
+    let (_, id, rest) = ber_read(pdu).ok_or_else(malformed)?;
+    let (_, status, rest) = ber_read(rest).ok_or_else(malformed)?;
+    let (_, _index, rest) = ber_read(rest).ok_or_else(malformed)?;
+    let (_, mut varbinds, _) = ber_read(rest).ok_or_else(malformed)?;
+    if ber_uint(id) != request_id as u64 {
+        return Err(AbyssError::Network("SNMP response for another request".into()));
+    }
+    match ber_uint(status) {
+        0 => {}
+        // noSuchName (SNMPv1-style agents)
+        2 => return Err(AbyssError::NotFound("SNMP agent has no such object".into())),
+        code => return Err(AbyssError::Network(format!("SNMP error status {code}"))),
+    }
+
+    let mut values = Vec::with_capacity(expected);
+    while !varbinds.is_empty() {
+        let (_, varbind, rest) = ber_read(varbinds).ok_or_else(malformed)?;
+        varbinds = rest;
+        let (_, _oid, value) = ber_read(varbind).ok_or_else(malformed)?;
+        let (tag, content, _) = ber_read(value).ok_or_else(malformed)?;
+        match tag {
+            // Counter32, Gauge32, Counter64
+            0x41 | 0x42 | 0x46 => values.push(ber_uint(content)),
+            // noSuchObject, noSuchInstance, endOfMibView
+            0x80..=0x82 => return Err(AbyssError::NotFound("SNMP agent has no such object".into())),
+            other => return Err(AbyssError::Network(format!("Unexpected SNMP value type 0x{other:02x}"))),
+        }
+    }
+    if values.len() != expected {
+        return Err(malformed());
+    }
+    Ok(values)
+}
+
+fn ber_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn ber_int(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    // Drop leading bytes that only repeat the sign
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0) || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    ber_tlv(0x02, &bytes[start..])
+}
+
+fn ber_oid(arcs: &[u32]) -> Vec<u8> {
+    let mut out = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for &arc in &arcs[2..] {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        out.extend(chunk.into_iter().rev());
+    }
+    out
+}
+
+/// Split one TLV off the front of `buf`: (tag, content, remainder).
+fn ber_read(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = buf.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first & 0x80 == 0 {
+        (first as usize, rest)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, &rest[n..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+fn ber_uint(content: &[u8]) -> u64 {
+    content.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
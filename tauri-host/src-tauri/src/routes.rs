@@ -0,0 +1,256 @@
+use crate::interfaces::{self, InterfaceKind};
+use crate::monitor::MonitorHost;
+use crate::writer::{WriteCommand, WriterSender};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the routing table is re-read.
+const ROUTE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// ─── Default routes ─────────────────────────────────────────────────────────
+
+/// One default (0.0.0.0/0 or ::/0) route.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefaultRoute {
+    pub family: IpFamily,
+    /// Next hop; `None` for point-to-point links (most VPN tunnels).
+    pub gateway: Option<String>,
+    pub interface: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface_kind: Option<InterfaceKind>,
+    /// Effective metric, lower wins.  macOS doesn't report one; there the
+    /// routing table order is kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+/// A change of the default routes, sent to the writer.  The first reading
+/// after startup has `initial` set.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteChange {
+    pub timestamp: String,
+    /// Current default routes, preferred first.
+    pub routes: Vec<DefaultRoute>,
+    pub previous: Vec<DefaultRoute>,
+    pub initial: bool,
+}
+
+/// Current default routes, IPv4 before IPv6 and by metric within a family,
+/// so the first entry is the one new connections take.
+pub fn default_routes() -> Vec<DefaultRoute> {
+    let mut routes = platform_routes();
+    let kinds: Vec<(String, InterfaceKind)> = interfaces::list().into_iter().map(|i| (i.name, i.kind)).collect();
+    for route in &mut routes {
+        route.interface_kind = kinds.iter().find(|(name, _)| *name == route.interface).map(|(_, kind)| *kind);
+    }
+    routes.sort_by_key(|r| (r.family, r.metric.unwrap_or(u32::MAX)));
+    routes
+}
+
+/// "192.168.1.1 via wlan0", for logs.
+pub fn describe(routes: &[DefaultRoute]) -> String {
+    match routes.first() {
+        Some(r) => format!("{} via {}", r.gateway.as_deref().unwrap_or("link"), r.interface),
+        None => "none".to_string(),
+    }
+}
+
+// ─── Watcher ────────────────────────────────────────────────────────────────
+
+/// Re-read the default routes every `ROUTE_POLL_INTERVAL`; each change is
+/// persisted by the writer against the current session and reported to
+/// `host`, and prompts `publicip::watch` to re-query.
+pub async fn watch(host: Arc<impl MonitorHost>, writer_tx: WriterSender) {
+    let mut current: Option<Vec<DefaultRoute>> = None;
+    loop {
+        let routes = tokio::task::spawn_blocking(default_routes).await.unwrap_or_default();
+        if current.as_ref() != Some(&routes) {
+            let previous = current.replace(routes.clone());
+            let initial = previous.is_none();
+            let previous = previous.unwrap_or_default();
+            if !initial {
+                println!("[Abyss] Default route changed: {} → {}", describe(&previous), describe(&routes));
+                host.public_ip_recheck().notify_one();
+            }
+            let change = RouteChange {
+                timestamp: Utc::now().to_rfc3339(),
+                routes,
+                previous,
+                initial,
+            };
+            host.route_changed(&change);
+            let _ = writer_tx.send(WriteCommand::RecordRouteChange(change));
+        }
+        tokio::time::sleep(ROUTE_POLL_INTERVAL).await;
+    }
+}
+
+// ─── Platform tables ────────────────────────────────────────────────────────
+
+/// Default routes from `/proc/net/route` and `/proc/net/ipv6_route`.
+#[cfg(target_os = "linux")]
+fn platform_routes() -> Vec<DefaultRoute> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const RTF_UP: u32 = 0x1;
+    const RTF_REJECT: u32 = 0x200;
+
+    let mut routes = Vec::new();
+    if let Ok(table) = std::fs::read_to_string("/proc/net/route") {
+        // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+        for line in table.lines().skip(1) {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 8 || cols[1] != "00000000" || cols[7] != "00000000" {
+                continue;
+            }
+            let flags = u32::from_str_radix(cols[3], 16).unwrap_or(0);
+            if flags & RTF_UP == 0 {
+                continue;
+            }
+            // Addresses are the raw network-order bytes printed as a host-endian word
+            let gateway = u32::from_str_radix(cols[2], 16)
+                .ok()
+                .map(|g| Ipv4Addr::from(g.to_le_bytes()))
+                .filter(|ip| !ip.is_unspecified());
+            routes.push(DefaultRoute {
+                family: IpFamily::Ipv4,
+                gateway: gateway.map(|ip| ip.to_string()),
+                interface: cols[0].to_string(),
+                interface_kind: None,
+                metric: cols[6].parse().ok(),
+            });
+        }
+    }
+    if let Ok(table) = std::fs::read_to_string("/proc/net/ipv6_route") {
+        // dest plen src plen next_hop metric refcnt use flags iface
+        for line in table.lines() {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 10 || cols[1] != "00" || cols[0].bytes().any(|b| b != b'0') || cols[9] == "lo" {
+                continue;
+            }
+            let flags = u32::from_str_radix(cols[8], 16).unwrap_or(0);
+            if flags & RTF_UP == 0 || flags & RTF_REJECT != 0 {
+                continue;
+            }
+            let gateway = u128::from_str_radix(cols[4], 16)
+                .ok()
+                .map(Ipv6Addr::from)
+                .filter(|ip| !ip.is_unspecified());
+            routes.push(DefaultRoute {
+                family: IpFamily::Ipv6,
+                gateway: gateway.map(|ip| ip.to_string()),
+                interface: cols[9].to_string(),
+                interface_kind: None,
+                metric: u32::from_str_radix(cols[5], 16).ok(),
+            });
+        }
+    }
+    routes
+}
+
+/// Default routes from `GetIpForwardTable2`.  The effective metric is the
+/// route metric plus the interface metric, as Windows ranks them.
+#[cfg(target_os = "windows")]
+fn platform_routes() -> Vec<DefaultRoute> {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        ConvertInterfaceLuidToAlias, FreeMibTable, GetIpForwardTable2, GetIpInterfaceEntry,
+        InitializeIpInterfaceEntry, MIB_IPFORWARD_ROW2, MIB_IPFORWARD_TABLE2, MIB_IPINTERFACE_ROW,
+    };
+    use windows_sys::Win32::Networking::WinSock::{AF_INET, AF_INET6, AF_UNSPEC};
+
+    let mut routes = Vec::new();
+    let mut table: *mut MIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+    // SAFETY: rows are read before FreeMibTable; SOCKADDR_INET unions are
+    // read according to their family tag, and the interface row is
+    // initialized before GetIpInterfaceEntry fills it.
+    unsafe {
+        if GetIpForwardTable2(AF_UNSPEC, &mut table) != 0 || table.is_null() {
+            return routes;
+        }
+        let rows: &[MIB_IPFORWARD_ROW2] = std::slice::from_raw_parts(
+            (*table).Table.as_ptr(),
+            (*table).NumEntries as usize,
+        );
+        for row in rows.iter().filter(|r| r.DestinationPrefix.PrefixLength == 0 && r.Loopback == 0) {
+            let (family, gateway) = match row.NextHop.si_family {
+                AF_INET => (
+                    IpFamily::Ipv4,
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(row.NextHop.Ipv4.sin_addr.S_un.S_addr))),
+                ),
+                AF_INET6 => (IpFamily::Ipv6, IpAddr::V6(Ipv6Addr::from(row.NextHop.Ipv6.sin6_addr.u.Byte))),
+                _ => continue,
+            };
+            let mut iface: MIB_IPINTERFACE_ROW = std::mem::zeroed();
+            InitializeIpInterfaceEntry(&mut iface);
+            iface.Family = row.NextHop.si_family;
+            iface.InterfaceLuid = row.InterfaceLuid;
+            let iface_metric = if GetIpInterfaceEntry(&mut iface) == 0 { iface.Metric } else { 0 };
+
+            let mut alias = [0u16; 257];
+            let interface = if ConvertInterfaceLuidToAlias(&row.InterfaceLuid, alias.as_mut_ptr(), alias.len()) == 0 {
+                let len = alias.iter().position(|&c| c == 0).unwrap_or(alias.len());
+                String::from_utf16_lossy(&alias[..len])
+            } else {
+                row.InterfaceIndex.to_string()
+            };
+            routes.push(DefaultRoute {
+                family,
+                gateway: (!gateway.is_unspecified()).then(|| gateway.to_string()),
+                interface,
+                interface_kind: None,
+                metric: Some(row.Metric.saturating_add(iface_metric)),
+            });
+        }
+        FreeMibTable(table as *const _);
+    }
+    routes
+}
+
+/// Default routes from `netstat -rn`, in table (priority) order.
+#[cfg(target_os = "macos")]
+fn platform_routes() -> Vec<DefaultRoute> {
+    let Ok(output) = std::process::Command::new("netstat").arg("-rn").output() else {
+        return Vec::new();
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut family = IpFamily::Ipv4;
+    let mut routes = Vec::new();
+    for line in text.lines() {
+        match line.trim() {
+            "Internet:" => family = IpFamily::Ipv4,
+            "Internet6:" => family = IpFamily::Ipv6,
+            _ => {}
+        }
+        // Destination Gateway Flags Netif Expire
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 4 || cols[0] != "default" {
+            continue;
+        }
+        routes.push(DefaultRoute {
+            family,
+            // Tunnels route via "link#N" rather than a next hop
+            gateway: (!cols[1].starts_with("link#")).then(|| cols[1].to_string()),
+            interface: cols[3].to_string(),
+            interface_kind: None,
+            metric: None,
+        });
+    }
+    routes
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn platform_routes() -> Vec<DefaultRoute> {
+    Vec::new()
+}
@@ -0,0 +1,197 @@
+use crate::{GeoEndpoint, GeoFlow, ProtoCounters, TelemetryFrame};
+use serde::Serialize;
+
+/// Version of the `TelemetryFrame` the monitor builds.  Bump it when a field
+/// changes meaning or goes away, and keep a shim below for the previous one.
+///
+/// - 2: flows, net and protocol counters.
+/// - 3: adds `interface`, `rate`, `sockets`, `wifi`, measured-flow counts and
+///   per-flow rates, RTT method, domain/SNI, service class, watch and threat tags.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Versions frames can still be emitted in, oldest first.
+pub const SUPPORTED_VERSIONS: &[u32] = &[2, 3];
+
+/// Unversioned event carrying the current schema, for windows that never
+/// negotiated one.
+pub const LEGACY_EVENT: &str = "telemetry-frame";
+
+// ─── Negotiation ────────────────────────────────────────────────────────────
+
+/// Event that frames of `version` are emitted on (`telemetry-frame-v3`).
+pub fn event_name(version: u32) -> String {
+    format!("{LEGACY_EVENT}-v{version}")
+}
+
+/// Highest version both the backend and a UI bundle accepting `accepted` support.
+pub fn negotiate(accepted: &[u32]) -> Option<u32> {
+    SUPPORTED_VERSIONS.iter().rev().copied().find(|v| accepted.contains(v))
+}
+
+/// A schema version and the event its frames arrive on.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChannel {
+    pub version: u32,
+    pub event: String,
+}
+
+impl SchemaChannel {
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            event: event_name(version),
+        }
+    }
+}
+
+/// What `cmd_get_schema_info` reports to a UI bundle deciding what to listen on.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaInfo {
+    pub current: u32,
+    pub supported: Vec<SchemaChannel>,
+    pub legacy_event: &'static str,
+}
+
+pub fn info() -> SchemaInfo {
+    SchemaInfo {
+        current: SCHEMA_VERSION,
+        supported: SUPPORTED_VERSIONS.iter().map(|&v| SchemaChannel::new(v)).collect(),
+        legacy_event: LEGACY_EVENT,
+    }
+}
+
+// ─── Compatibility shims ────────────────────────────────────────────────────
+
+/// A frame in the shape of a negotiated schema version.
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum VersionedFrame<'a> {
+    Current(&'a TelemetryFrame),
+    V2(FrameV2<'a>),
+}
+
+/// `frame` as a UI bundle on `version` expects it.  Unknown versions get the
+/// current shape.
+pub fn shape(frame: &TelemetryFrame, version: u32) -> VersionedFrame<'_> {
+    match version {
+        2 => VersionedFrame::V2(FrameV2::from(frame)),
+        _ => VersionedFrame::Current(frame),
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameV2<'a> {
+    schema: u32,
+    t: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    light: Option<bool>,
+    net: NetV2,
+    proto: ProtoCounters,
+    flows: Vec<FlowV2<'a>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NetV2 {
+    bps: f64,
+    pps: u32,
+    active_flows: u32,
+    latency_ms: f64,
+    upload_bps: f64,
+    download_bps: f64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FlowV2<'a> {
+    id: &'a str,
+    src: &'a GeoEndpoint,
+    dst: &'a GeoEndpoint,
+    bps: f64,
+    pps: u32,
+    rtt: f64,
+    protocol: u8,
+    dir: &'a str,
+    port: u16,
+    service: Option<u8>,
+    started_at: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<&'a str>,
+}
+
+impl<'a> From<&'a TelemetryFrame> for FrameV2<'a> {
+    fn from(frame: &'a TelemetryFrame) -> Self {
+        Self {
+            schema: 2,
+            t: frame.t,
+            light: frame.light,
+            net: NetV2 {
+                bps: frame.net.bps,
+                pps: frame.net.pps,
+                active_flows: frame.net.active_flows,
+                latency_ms: frame.net.latency_ms,
+                upload_bps: frame.net.upload_bps,
+                download_bps: frame.net.download_bps,
+            },
+            proto: frame.proto,
+            flows: frame.flows.iter().map(FlowV2::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a GeoFlow> for FlowV2<'a> {
+    fn from(flow: &'a GeoFlow) -> Self {
+        Self {
+            id: &flow.id,
+            src: &flow.src,
+            dst: &flow.dst,
+            bps: flow.bps,
+            pps: flow.pps,
+            rtt: flow.rtt,
+            protocol: flow.protocol,
+            dir: &flow.dir,
+            port: flow.port,
+            service: flow.service,
+            started_at: flow.started_at,
+            process: flow.process.as_deref(),
+            pid: flow.pid,
+            state: flow.state.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{FlowBuilder, FrameBuilder};
+
+    #[test]
+    fn negotiates_newest_common_version() {
+        assert_eq!(negotiate(&[2, 3, 4]), Some(3));
+        assert_eq!(negotiate(&[2]), Some(2));
+        assert_eq!(negotiate(&[1, 4]), None);
+    }
+
+    #[test]
+    fn v2_frames_keep_only_v2_fields() {
+        let flow = FlowBuilder::new("a").process("curl", 7).domain("example.com").build();
+        let frame = FrameBuilder::at(12.0).flow(flow).build();
+
+        let v2 = serde_json::to_value(shape(&frame, 2)).unwrap();
+        assert_eq!(v2["schema"], 2);
+        assert!(v2["net"].get("measuredFlows").is_none());
+        assert_eq!(v2["flows"][0]["process"], "curl");
+        assert!(v2["flows"][0].get("domain").is_none());
+
+        let current = serde_json::to_value(shape(&frame, SCHEMA_VERSION)).unwrap();
+        assert_eq!(current, serde_json::to_value(&frame).unwrap());
+        assert_eq!(current["flows"][0]["domain"], "example.com");
+    }
+}
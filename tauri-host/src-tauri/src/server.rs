@@ -0,0 +1,536 @@
+use crate::error::AbyssError;
+use crate::locks::LockExt;
+use crate::{base64, TelemetryFrame};
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+/// Frames buffered per client before a slow client starts skipping frames.
+const FRAME_BUFFER: usize = 16;
+/// Largest HTTP upgrade request accepted.
+const MAX_HANDSHAKE_BYTES: usize = 8 * 1024;
+/// Clients must finish the upgrade within this long.
+const HANDSHAKE_TIMEOUT_SECS: u64 = 5;
+/// Largest client frame accepted (clients only send control frames).
+const MAX_CLIENT_FRAME_BYTES: u64 = 64 * 1024;
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+// ─── Server ─────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WsServerStatus {
+    pub running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Connect URL including the token (`ws://host:port/?token=...`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub clients: usize,
+}
+
+/// Where and how to listen.
+pub struct WsConfig {
+    pub port: u16,
+    /// Listen on all interfaces instead of loopback only.
+    pub allow_lan: bool,
+    pub token: String,
+}
+
+struct Running {
+    addr: SocketAddr,
+    token: String,
+    /// Dropped on stop, which ends every client's send loop.
+    frames: broadcast::Sender<Arc<str>>,
+    accept: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// Opt-in WebSocket server streaming each `TelemetryFrame` as JSON text to
+/// external clients (OBS overlays, dashboards).  Clients authenticate with
+/// `?token=` on the upgrade URL or an `Authorization: Bearer` header.
+pub struct WsServer {
+    running: Mutex<Option<Running>>,
+    clients: Arc<AtomicUsize>,
+}
+
+impl WsServer {
+    pub fn new() -> Self {
+        Self {
+            running: Mutex::new(None),
+            clients: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// (Re)start listening with `config`.  Connected clients of a previous
+    /// instance are disconnected.
+    pub fn start(&self, config: WsConfig) -> Result<WsServerStatus, AbyssError> {
+        self.stop();
+        let ip = if config.allow_lan { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+        let listener = std::net::TcpListener::bind((ip, config.port))
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+            .map_err(|e| AbyssError::Io(format!("Could not listen on port {}: {e}", config.port)))?;
+        let addr = listener.local_addr()?;
+
+        let (frames, _) = broadcast::channel(FRAME_BUFFER);
+        let token: Arc<str> = config.token.as_str().into();
+        let accept = tauri::async_runtime::spawn(accept_loop(
+            listener,
+            token,
+            frames.clone(),
+            self.clients.clone(),
+        ));
+        println!("[Abyss] WebSocket server listening on {addr}");
+        *self.running.lock_or_recover("ws_server") = Some(Running {
+            addr,
+            token: config.token,
+            frames,
+            accept,
+        });
+        Ok(self.status())
+    }
+
+    pub fn stop(&self) {
+        if let Some(running) = self.running.lock_or_recover("ws_server").take() {
+            running.accept.abort();
+            println!("[Abyss] WebSocket server on {} stopped", running.addr);
+        }
+    }
+
+    pub fn status(&self) -> WsServerStatus {
+        let running = self.running.lock_or_recover("ws_server");
+        WsServerStatus {
+            running: running.is_some(),
+            address: running.as_ref().map(|r| r.addr.to_string()),
+            url: running.as_ref().map(|r| {
+                let host = if r.addr.ip().is_unspecified() { Ipv4Addr::LOCALHOST.into() } else { r.addr.ip() };
+                format!("ws://{}/?token={}", SocketAddr::new(host, r.addr.port()), r.token)
+            }),
+            clients: if running.is_some() { self.clients.load(Ordering::Relaxed) } else { 0 },
+        }
+    }
+
+    /// Send `frame` to connected clients (serialized only if there are any).
+    pub fn publish(&self, frame: &TelemetryFrame) {
+        let running = self.running.lock_or_recover("ws_server");
+        let Some(running) = running.as_ref().filter(|r| r.frames.receiver_count() > 0) else {
+            return;
+        };
+        if let Ok(json) = serde_json::to_string(frame) {
+            let _ = running.frames.send(json.into());
+        }
+    }
+}
+
+async fn accept_loop(
+    listener: std::net::TcpListener,
+    token: Arc<str>,
+    frames: broadcast::Sender<Arc<str>>,
+    clients: Arc<AtomicUsize>,
+) {
+    let listener = match tokio::net::TcpListener::from_std(listener) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("[Abyss] WebSocket server failed to start: {e}");
+            return;
+        }
+    };
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("[Abyss] WebSocket accept failed: {e}");
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                continue;
+            }
+        };
+        let token = token.clone();
+        let rx = frames.subscribe();
+        let clients = clients.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_client(stream, &token, rx, &clients).await {
+                eprintln!("[Abyss] WebSocket client {peer}: {e}");
+            }
+        });
+    }
+}
+
+async fn serve_client(
+    mut stream: TcpStream,
+    token: &str,
+    mut frames: broadcast::Receiver<Arc<str>>,
+    clients: &AtomicUsize,
+) -> std::io::Result<()> {
+    let handshake = tokio::time::timeout(
+        Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+        handshake(&mut stream, token),
+    )
+    .await
+    .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "handshake timed out"))?;
+    if !handshake? {
+        return Ok(());
+    }
+
+    clients.fetch_add(1, Ordering::Relaxed);
+    let (reader, writer) = stream.into_split();
+    let writer = Arc::new(tokio::sync::Mutex::new(writer));
+    let read_task = tokio::spawn(read_loop(reader, writer.clone()));
+
+    // Ends when the server stops (sender dropped) or a write fails (client gone)
+    let server_stopped = loop {
+        match frames.recv().await {
+            Ok(json) => {
+                if write_frame(&mut *writer.lock().await, OP_TEXT, json.as_bytes()).await.is_err() {
+                    break false;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break true,
+        }
+    };
+    if server_stopped {
+        // 1001 Going Away
+        let mut writer = writer.lock().await;
+        let _ = write_frame(&mut writer, OP_CLOSE, &1001_u16.to_be_bytes()).await;
+        let _ = writer.shutdown().await;
+    }
+    read_task.abort();
+    clients.fetch_sub(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Answer pings and closes; everything else clients send is ignored.
+async fn read_loop(mut reader: OwnedReadHalf, writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>) {
+    while let Ok((opcode, payload)) = read_frame(&mut reader).await {
+        let mut writer = writer.lock().await;
+        match opcode {
+            OP_PING if write_frame(&mut writer, OP_PONG, &payload).await.is_err() => break,
+            OP_CLOSE => {
+                let _ = write_frame(&mut writer, OP_CLOSE, payload.get(..2).unwrap_or_default()).await;
+                let _ = writer.shutdown().await;
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+// ─── Protocol ───────────────────────────────────────────────────────────────
+
+/// Read the HTTP upgrade request and answer it.  Ok(false) when the request
+/// was rejected (the response has been sent).
+async fn handshake(stream: &mut TcpStream, token: &str) -> std::io::Result<bool> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(false);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > MAX_HANDSHAKE_BYTES {
+            stream.write_all(b"HTTP/1.1 431 Request Header Fields Too Large\r\n\r\n").await?;
+            return Ok(false);
+        }
+    }
+    let request = String::from_utf8_lossy(&buf);
+    let mut lines = request.split("\r\n");
+    let target = lines
+        .next()
+        .and_then(|line| line.strip_prefix("GET "))
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap_or_default();
+    let header = |name: &str| {
+        request.split("\r\n").skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    };
+
+    let key = header("Sec-WebSocket-Key");
+    let upgrade = header("Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let (Some(key), true) = (key, upgrade) else {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").await?;
+        return Ok(false);
+    };
+
+    let query_token = target
+        .split_once('?')
+        .and_then(|(_, query)| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+    let bearer = header("Authorization").and_then(|v| v.strip_prefix("Bearer "));
+    if !query_token.or(bearer).is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes())) {
+        stream.write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n").await?;
+        return Ok(false);
+    }
+
+    let accept = base64(&sha1(format!("{key}258EAFA5-E914-47DA-95CA-C5AB0DC85B11").as_bytes()));
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(true)
+}
+
+/// Write one unmasked, unfragmented frame.
+async fn write_frame(writer: &mut OwnedWriteHalf, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut header = Vec::with_capacity(10);
+    header.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => header.push(len as u8),
+        len @ 126..=0xFFFF => {
+            header.push(126);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            header.push(127);
+            header.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    writer.write_all(&header).await?;
+    writer.write_all(payload).await
+}
+
+/// Read one client frame, unmasked.  Fragmented messages are returned frame
+/// by frame (only control frames are acted on, and those can't fragment).
+async fn read_frame(reader: &mut OwnedReadHalf) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await?;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    if len > MAX_CLIENT_FRAME_BYTES {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "frame too large"));
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    Ok((opcode, payload))
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// SHA-1, only for `Sec-WebSocket-Accept` (RFC 6455 §4.2.2).
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Both ends of a loopback connection: (client, server).
+    async fn connected() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (client, server)
+    }
+
+    /// Run `handshake` against `request` and return the response status line
+    /// and headers.
+    fn respond(request: &str) -> (bool, String) {
+        block_on(async {
+            let (mut client, mut server) = connected().await;
+            client.write_all(request.as_bytes()).await.unwrap();
+            let accepted = handshake(&mut server, "s3cret").await.unwrap();
+            drop(server);
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+            (accepted, response)
+        })
+    }
+
+    fn upgrade_request(target: &str, extra: &str) -> String {
+        format!(
+            "GET {target} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n{extra}\r\n"
+        )
+    }
+
+    #[test]
+    fn sha1_matches_the_fips_vectors() {
+        let hex = |digest: [u8; 20]| digest.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn accepts_with_the_rfc_6455_key_vector() {
+        for request in [
+            upgrade_request("/?token=s3cret", ""),
+            upgrade_request("/", "Authorization: Bearer s3cret\r\n"),
+        ] {
+            let (accepted, response) = respond(&request);
+            assert!(accepted);
+            assert!(response.starts_with("HTTP/1.1 101 "), "{response}");
+            // RFC 6455 §1.3
+            assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"), "{response}");
+        }
+    }
+
+    #[test]
+    fn rejects_a_missing_or_wrong_token() {
+        for request in [
+            upgrade_request("/", ""),
+            upgrade_request("/?token=wrong", ""),
+            upgrade_request("/?token=s3cre", ""),
+            upgrade_request("/", "Authorization: Bearer wrong\r\n"),
+            upgrade_request("/", "Authorization: Basic s3cret\r\n"),
+        ] {
+            let (accepted, response) = respond(&request);
+            assert!(!accepted);
+            assert!(response.starts_with("HTTP/1.1 401 "), "{request}: {response}");
+        }
+    }
+
+    #[test]
+    fn rejects_requests_that_are_not_upgrades() {
+        let (accepted, response) = respond("GET /?token=s3cret HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(!accepted);
+        assert!(response.starts_with("HTTP/1.1 400 "));
+
+        let padding = format!("X-Padding: {}\r\n", "a".repeat(MAX_HANDSHAKE_BYTES));
+        let oversized = upgrade_request("/?token=s3cret", &padding);
+        let (accepted, response) = respond(&oversized);
+        assert!(!accepted);
+        assert!(response.starts_with("HTTP/1.1 431 "));
+    }
+
+    #[test]
+    fn reads_masked_client_frames() {
+        block_on(async {
+            let (mut client, server) = connected().await;
+            let (mut reader, _writer) = server.into_split();
+            // RFC 6455 §5.7: a single-frame masked text message "Hello"
+            client
+                .write_all(&[0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58])
+                .await
+                .unwrap();
+            assert_eq!(read_frame(&mut reader).await.unwrap(), (OP_TEXT, b"Hello".to_vec()));
+
+            // 16-bit extended length, masked with zeros
+            let payload = vec![b'x'; 300];
+            let mut frame = vec![0x80 | OP_PING, 0x80 | 126];
+            frame.extend_from_slice(&300u16.to_be_bytes());
+            frame.extend_from_slice(&[0; 4]);
+            frame.extend_from_slice(&payload);
+            client.write_all(&frame).await.unwrap();
+            assert_eq!(read_frame(&mut reader).await.unwrap(), (OP_PING, payload));
+        });
+    }
+
+    #[test]
+    fn refuses_oversized_client_frames() {
+        block_on(async {
+            let (mut client, server) = connected().await;
+            let (mut reader, _writer) = server.into_split();
+            let mut frame = vec![0x80 | OP_TEXT, 0x80 | 127];
+            frame.extend_from_slice(&(MAX_CLIENT_FRAME_BYTES + 1).to_be_bytes());
+            client.write_all(&frame).await.unwrap();
+            let err = read_frame(&mut reader).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        });
+    }
+
+    #[test]
+    fn writes_unmasked_frames_with_extended_lengths() {
+        block_on(async {
+            let (mut client, server) = connected().await;
+            let (_reader, mut writer) = server.into_split();
+            write_frame(&mut writer, OP_TEXT, b"hi").await.unwrap();
+            write_frame(&mut writer, OP_TEXT, &[b'y'; 200]).await.unwrap();
+            write_frame(&mut writer, OP_CLOSE, &[]).await.unwrap();
+            drop(writer);
+
+            let mut bytes = Vec::new();
+            client.read_to_end(&mut bytes).await.unwrap();
+            assert_eq!(&bytes[..4], &[0x81, 2, b'h', b'i']);
+            assert_eq!(&bytes[4..8], &[0x81, 126, 0, 200]);
+            assert_eq!(&bytes[208..], &[0x88, 0]);
+        });
+    }
+
+    #[test]
+    fn compares_tokens_in_full() {
+        assert!(constant_time_eq(b"s3cret", b"s3cret"));
+        assert!(!constant_time_eq(b"s3cret", b"s3creT"));
+        assert!(!constant_time_eq(b"s3cret", b"s3cret2"));
+        assert!(!constant_time_eq(b"", b"s3cret"));
+    }
+}
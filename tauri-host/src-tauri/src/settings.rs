@@ -0,0 +1,319 @@
+use crate::anonymize::AnonymizeMode;
+use crate::capture::CaptureMode;
+use crate::db;
+use crate::geo::GeoProviderKind;
+use crate::notify::{AlertTemplates, AnomalyNotifications, MuteState, PushTarget};
+use crate::router::RouterConfig;
+use crate::threat::ThreatIntelConfig;
+use crate::units::UnitPrefs;
+use crate::writer::PersistenceProfile;
+use crate::error::AbyssError;
+use crate::{
+    GEO_CACHE_TTL_SECS, IDLE_POLL_MS, KEYFRAME_INTERVAL_SECS, MATERIAL_FLOW_DELTA, MATERIAL_LATENCY_DELTA_MS,
+    MATERIAL_MIN_BPS_DELTA, MATERIAL_THROUGHPUT_DELTA_PCT, MAX_FLOWS_PER_FRAME, NETSTAT_POLL_MS, TICK_MS,
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+// ─── Settings ───────────────────────────────────────────────────────────────
+
+/// Default port for the telemetry WebSocket server.
+pub const WS_DEFAULT_PORT: u16 = 8765;
+/// Default port for the session query API.
+pub const API_DEFAULT_PORT: u16 = 8766;
+
+/// What happens about recording when the app starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StartupSession {
+    /// Start recording a session right away.
+    #[default]
+    Record,
+    /// Show live traffic and emit `startup-session-prompt`; recording starts
+    /// once the user answers with `cmd_answer_startup_prompt`.
+    Ask,
+    /// Show live traffic without starting a session.
+    MonitorOnly,
+}
+
+/// Runtime settings shared by the monitor loop, the writer and commands.
+/// Defaults mirror the compile-time constants in `lib.rs`; the monitor loop
+/// picks up changes to the tuning knobs on its next tick.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub tick_ms: u64,
+    pub netstat_poll_ms: u64,
+    pub max_flows_per_frame: usize,
+    pub keyframe_interval_secs: u64,
+    pub geo_cache_ttl_secs: u64,
+    /// A frame is emitted in full (rather than as a heartbeat) when active
+    /// flows, throughput or latency move by at least these amounts.
+    pub material_flow_delta: u32,
+    pub material_throughput_delta_pct: f64,
+    pub material_min_bps_delta: f64,
+    pub material_latency_delta_ms: f64,
+    /// Slow ticks and polls to `idle_poll_ms` while on battery with stable
+    /// activity (see `pacing`).
+    pub adaptive_polling: bool,
+    pub idle_poll_ms: u64,
+    /// Never send IPs to the remote geo API; flows fall back to "??".
+    pub privacy_mode: bool,
+    /// Persist only anonymized IPs (see `anonymize_mode`) and no process
+    /// names or hostnames; session exports are anonymized the same way.
+    pub redact_at_rest: bool,
+    /// Its hashing key is kept beside the database (`anonymize::AnonymizeKey`).
+    pub anonymize_mode: AnonymizeMode,
+    /// Connection source requested at startup (see `capture`).
+    pub capture_mode: CaptureMode,
+    /// Whether a session is recorded from launch (see `StartupSession`).
+    pub startup_session: StartupSession,
+    /// Closing the window hides it to the tray; monitoring and recording
+    /// carry on until Quit.
+    pub background_mode: bool,
+    /// Record a session when launched at login (start-on-login passes
+    /// `--background`), whatever `startup_session` says.
+    pub record_on_login: bool,
+    /// Prune frames/flow snapshots of sessions older than this many days,
+    /// keeping their summary.  0 keeps everything.
+    pub frame_retention_days: u32,
+    /// Replace raw frames of sessions older than this many days with
+    /// 1-minute and 1-hour averages.  0 keeps full resolution.
+    pub frame_rollup_days: u32,
+    /// Age, size and count limits the writer prunes sessions to daily.
+    pub retention: db::RetentionPolicy,
+    /// How often frames, flows, destinations and process usage are
+    /// written; applies from the next session.
+    pub persistence: PersistenceProfile,
+    /// `PersistenceExclusions` the writer drops before insert, encrypted
+    /// with the key beside the database (see `exclusions`).
+    pub persistence_exclusions: Option<String>,
+    /// Local MaxMind `.mmdb` files loaded at startup (see `geo`).
+    pub geoip_db_paths: Vec<String>,
+    /// Which `GeoProvider` resolves flow destinations.
+    pub geo_provider: GeoProviderKind,
+    /// Token/key for providers that take one (ipinfo.io, ipgeolocation.io).
+    pub geo_api_key: Option<String>,
+    /// Measure RTT to active destinations (ICMP echo / TCP connect) instead
+    /// of estimating it.
+    pub latency_probes: bool,
+    /// Interfaces whose connections are monitored; empty means all.
+    pub monitored_interfaces: Vec<String>,
+    /// Skip netstat/ss lines whose addresses don't parse instead of
+    /// guessing (port 0); either way they are counted in diagnostics.
+    pub strict_address_parsing: bool,
+    /// Global alert mute and per-rule snoozes.
+    pub alert_mute: MuteState,
+    /// Notification text and webhook payload templates for alerts.
+    pub alert_templates: AlertTemplates,
+    /// Desktop notifications for high-severity anomalies.
+    pub anomaly_notifications: AnomalyNotifications,
+    /// ntfy / Gotify destinations alerts are pushed to.
+    pub push_targets: Vec<PushTarget>,
+    /// Stream telemetry frames over a local WebSocket (see `server`).
+    pub ws_server_enabled: bool,
+    pub ws_server_port: u16,
+    /// Listen on all interfaces rather than loopback only.
+    pub ws_server_lan: bool,
+    /// Clients must present this token; generated on first start.
+    pub ws_server_token: Option<String>,
+    /// Serve recorded history over a loopback HTTP API (see `api`).
+    pub api_server_enabled: bool,
+    pub api_server_port: u16,
+    /// Bearer token for API requests; generated on first start.
+    pub api_server_token: Option<String>,
+    /// SI vs IEC and bits vs bytes for sizes and rates in generated text.
+    pub units: UnitPrefs,
+    /// Gateway WAN counters recorded next to sessions (see `router`).
+    pub router: RouterConfig,
+    /// Blocklists flows are checked against (see `threat`).
+    pub threat_intel: ThreatIntelConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tick_ms: TICK_MS,
+            netstat_poll_ms: NETSTAT_POLL_MS,
+            max_flows_per_frame: MAX_FLOWS_PER_FRAME,
+            keyframe_interval_secs: KEYFRAME_INTERVAL_SECS,
+            geo_cache_ttl_secs: GEO_CACHE_TTL_SECS,
+            material_flow_delta: MATERIAL_FLOW_DELTA,
+            material_throughput_delta_pct: MATERIAL_THROUGHPUT_DELTA_PCT,
+            material_min_bps_delta: MATERIAL_MIN_BPS_DELTA,
+            material_latency_delta_ms: MATERIAL_LATENCY_DELTA_MS,
+            adaptive_polling: true,
+            idle_poll_ms: IDLE_POLL_MS,
+            privacy_mode: false,
+            redact_at_rest: false,
+            anonymize_mode: AnonymizeMode::Truncate,
+            capture_mode: CaptureMode::Poller,
+            startup_session: StartupSession::Record,
+            background_mode: false,
+            record_on_login: true,
+            frame_retention_days: 0,
+            frame_rollup_days: 0,
+            retention: db::RetentionPolicy::default(),
+            persistence: PersistenceProfile::default(),
+            persistence_exclusions: None,
+            geoip_db_paths: Vec::new(),
+            geo_provider: GeoProviderKind::IpApi,
+            geo_api_key: None,
+            latency_probes: true,
+            monitored_interfaces: Vec::new(),
+            strict_address_parsing: false,
+            alert_mute: MuteState::default(),
+            alert_templates: AlertTemplates::default(),
+            anomaly_notifications: AnomalyNotifications::default(),
+            push_targets: Vec::new(),
+            ws_server_enabled: false,
+            ws_server_port: WS_DEFAULT_PORT,
+            ws_server_lan: false,
+            ws_server_token: None,
+            api_server_enabled: false,
+            api_server_port: API_DEFAULT_PORT,
+            api_server_token: None,
+            units: UnitPrefs::default(),
+            router: RouterConfig::default(),
+            threat_intel: ThreatIntelConfig::default(),
+        }
+    }
+}
+
+// ─── Updates ────────────────────────────────────────────────────────────────
+
+/// Fields changed through their own command, which validates them or
+/// applies side effects (writer, capture thread, loaded databases).
+const DEDICATED_KEYS: &[(&str, &str)] = &[
+    ("redactAtRest", "cmd_set_redact_at_rest"),
+    ("anonymizeMode", "cmd_set_redact_at_rest"),
+    ("captureMode", "cmd_set_capture_mode"),
+    ("geoipDbPaths", "cmd_set_geoip_db"),
+    ("geoProvider", "cmd_set_geo_provider"),
+    ("geoApiKey", "cmd_set_geo_provider"),
+    ("monitoredInterfaces", "cmd_set_monitored_interfaces"),
+    ("persistence", "cmd_set_persistence_profile"),
+    ("persistenceExclusions", "cmd_set_persistence_exclusions"),
+    ("alertMute", "cmd_set_alert_mute"),
+    ("alertTemplates", "cmd_set_alert_templates"),
+    ("pushTargets", "cmd_save_push_target"),
+    ("wsServerEnabled", "cmd_start_ws_server"),
+    ("wsServerPort", "cmd_start_ws_server"),
+    ("wsServerLan", "cmd_start_ws_server"),
+    ("wsServerToken", "cmd_start_ws_server"),
+    ("apiServerEnabled", "cmd_start_api_server"),
+    ("apiServerPort", "cmd_start_api_server"),
+    ("apiServerToken", "cmd_start_api_server"),
+];
+
+impl Settings {
+    /// Reject values the monitor loop can't run with.
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        let in_range = |name: &str, value: f64, min: f64, max: f64| {
+            if (min..=max).contains(&value) {
+                Ok(())
+            } else {
+                Err(AbyssError::InvalidInput(format!("{name} must be between {min} and {max}")))
+            }
+        };
+        in_range("tickMs", self.tick_ms as f64, 100.0, 10_000.0)?;
+        in_range("netstatPollMs", self.netstat_poll_ms as f64, 250.0, 60_000.0)?;
+        in_range("maxFlowsPerFrame", self.max_flows_per_frame as f64, 1.0, 500.0)?;
+        in_range("keyframeIntervalSecs", self.keyframe_interval_secs as f64, 1.0, 3600.0)?;
+        in_range("geoCacheTtlSecs", self.geo_cache_ttl_secs as f64, 60.0, 7.0 * 86_400.0)?;
+        in_range("materialFlowDelta", self.material_flow_delta as f64, 1.0, 1000.0)?;
+        in_range("materialThroughputDeltaPct", self.material_throughput_delta_pct, 0.0, 1000.0)?;
+        in_range("materialMinBpsDelta", self.material_min_bps_delta, 0.0, 1e12)?;
+        in_range("materialLatencyDeltaMs", self.material_latency_delta_ms, 0.0, 60_000.0)?;
+        in_range("idlePollMs", self.idle_poll_ms as f64, 1000.0, 60_000.0)?;
+        in_range("retention.maxAgeDays", self.retention.max_age_days as f64, 0.0, 36_500.0)?;
+        in_range("retention.maxDbSizeMb", self.retention.max_db_size_mb as f64, 0.0, 1_048_576.0)?;
+        in_range("retention.maxSessions", self.retention.max_sessions as f64, 0.0, 1_000_000.0)?;
+        in_range(
+            "anomalyNotifications.minIntervalSecs",
+            self.anomaly_notifications.min_interval_secs as f64,
+            0.0,
+            86_400.0,
+        )?;
+        self.persistence.validate()?;
+        self.router.validate()?;
+        self.threat_intel.validate()
+    }
+
+    /// What to do about recording at launch; `at_login` when started by
+    /// start-on-login, where nobody is there to answer a prompt.
+    pub fn startup_for(&self, at_login: bool) -> StartupSession {
+        if at_login && self.record_on_login {
+            StartupSession::Record
+        } else {
+            self.startup_session
+        }
+    }
+}
+
+/// `current` with the camelCase fields in `patch` replaced.  Unknown keys
+/// and keys owned by a dedicated command are rejected.
+pub fn apply_patch(
+    current: &Settings,
+    patch: serde_json::Map<String, serde_json::Value>,
+) -> Result<Settings, AbyssError> {
+    let mut merged = match serde_json::to_value(current)? {
+        serde_json::Value::Object(map) => map,
+        _ => return Err(AbyssError::Internal("settings did not serialize to an object".into())),
+    };
+    for (key, value) in patch {
+        if let Some((_, command)) = DEDICATED_KEYS.iter().find(|(k, _)| *k == key) {
+            return Err(AbyssError::InvalidInput(format!("'{key}' is changed with {command}")));
+        }
+        if !merged.contains_key(&key) {
+            return Err(AbyssError::InvalidInput(format!("Unknown setting '{key}'")));
+        }
+        merged.insert(key, value);
+    }
+    let updated: Settings = serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| AbyssError::InvalidInput(format!("Invalid setting value: {e}")))?;
+    updated.validate()?;
+    Ok(updated)
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+/// Load settings from the `settings` table.  Each field is stored as its own
+/// row (camelCase key, JSON value); missing or unparsable keys keep defaults.
+pub fn load(conn: &Connection) -> Settings {
+    let defaults = match serde_json::to_value(Settings::default()) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return Settings::default(),
+    };
+    let mut merged = defaults.clone();
+    for (key, raw) in db::get_settings(conn).unwrap_or_default() {
+        if !defaults.contains_key(&key) {
+            continue;
+        }
+        // Check each key on its own so one bad value can't reset the rest.
+        let value = serde_json::from_str::<serde_json::Value>(&raw).ok().filter(|value| {
+            let mut alone = defaults.clone();
+            alone.insert(key.clone(), value.clone());
+            serde_json::from_value::<Settings>(serde_json::Value::Object(alone)).is_ok()
+        });
+        match value {
+            Some(value) => {
+                merged.insert(key, value);
+            }
+            None => eprintln!("[Abyss] Ignoring unparsable setting '{key}'; using its default"),
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(merged)).unwrap_or_default()
+}
+
+/// Persist every field of `settings`.
+pub fn save(conn: &Connection, settings: &Settings) -> Result<(), AbyssError> {
+    let map = match serde_json::to_value(settings)? {
+        serde_json::Value::Object(map) => map,
+        _ => return Err(AbyssError::Internal("settings did not serialize to an object".into())),
+    };
+    for (key, value) in map {
+        db::set_setting(conn, &key, &value.to_string())?;
+    }
+    Ok(())
+}
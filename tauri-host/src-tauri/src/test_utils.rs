@@ -0,0 +1,239 @@
+//! Fixtures for driving the writer and database without a running monitor:
+//! frame/flow builders, an in-memory database and a clock tests can step.
+//! Built for `cargo test` and with the `test-utils` feature.
+
+use crate::attribution::ProcessRate;
+use crate::schema::SCHEMA_VERSION;
+use crate::{db, GeoEndpoint, GeoFlow, NetMetrics, ProtoCounters, TelemetryFrame};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+
+/// A fresh in-memory database with the current schema.
+pub fn memory_db() -> Connection {
+    db::open_in_memory().expect("in-memory database")
+}
+
+// ─── Clock ──────────────────────────────────────────────────────────────────
+
+/// Deterministic wall clock: starts at 2026-01-01T00:00:00Z and only moves
+/// when `advance` is called.  Clones share the same time.
+#[derive(Clone)]
+pub struct TestClock(Arc<Mutex<DateTime<Utc>>>);
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::at(Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap())
+    }
+}
+
+impl TestClock {
+    pub fn at(start: DateTime<Utc>) -> Self {
+        Self(Arc::new(Mutex::new(start)))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+
+    pub fn advance(&self, secs: i64) {
+        *self.0.lock().unwrap() += Duration::seconds(secs);
+    }
+
+    /// The clock as a writer time source.
+    pub fn source(&self) -> Box<dyn Fn() -> DateTime<Utc> + Send> {
+        let clock = self.clone();
+        Box::new(move || clock.now())
+    }
+}
+
+// ─── Flows ──────────────────────────────────────────────────────────────────
+
+/// Builds a `GeoFlow` from this host to a public destination.  Defaults to
+/// an outbound HTTPS flow to 93.184.216.34 at 8 kbit/s.
+pub struct FlowBuilder {
+    flow: GeoFlow,
+}
+
+impl FlowBuilder {
+    pub fn new(id: &str) -> Self {
+        Self {
+            flow: GeoFlow {
+                id: id.to_string(),
+                src: endpoint("192.168.1.10", 40.71, -74.01, "New York", "US"),
+                dst: endpoint("93.184.216.34", 42.15, -70.82, "Norwell", "US"),
+                bps: 8_000.0,
+                pps: 4,
+                rtt: 20.0,
+                protocol: 1,
+                dir: "out".to_string(),
+                port: 443,
+                service: None,
+                started_at: 0.0,
+                process: None,
+                pid: None,
+                state: Some("ESTABLISHED".to_string()),
+                tx_bps: None,
+                rx_bps: None,
+                rtt_method: None,
+                domain: None,
+                sni: None,
+                service_class: None,
+                watch: None,
+                threat: None,
+                app_group: None,
+                container: None,
+            },
+        }
+    }
+
+    pub fn dst(mut self, ip: &str, country: &str) -> Self {
+        self.flow.dst.ip = ip.to_string();
+        self.flow.dst.country = country.to_string();
+        self
+    }
+
+    pub fn org(mut self, asn: &str, org: &str) -> Self {
+        self.flow.dst.asn = Some(asn.to_string());
+        self.flow.dst.org = Some(org.to_string());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.flow.port = port;
+        self
+    }
+
+    /// UDP instead of TCP.
+    pub fn udp(mut self) -> Self {
+        self.flow.protocol = 2;
+        self.flow.state = None;
+        self
+    }
+
+    pub fn bps(mut self, bps: f64) -> Self {
+        self.flow.bps = bps;
+        self
+    }
+
+    pub fn process(mut self, name: &str, pid: u32) -> Self {
+        self.flow.process = Some(name.to_string());
+        self.flow.pid = Some(pid);
+        self
+    }
+
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.flow.domain = Some(domain.to_string());
+        self
+    }
+
+    pub fn build(self) -> GeoFlow {
+        self.flow
+    }
+}
+
+fn endpoint(ip: &str, lat: f64, lng: f64, city: &str, country: &str) -> GeoEndpoint {
+    GeoEndpoint {
+        ip: ip.to_string(),
+        lat,
+        lng,
+        city: city.to_string(),
+        country: country.to_string(),
+        asn: None,
+        org: None,
+    }
+}
+
+// ─── Frames ─────────────────────────────────────────────────────────────────
+
+/// Builds a `TelemetryFrame` at monitor time `t`.  Totals (`bps`,
+/// `active_flows`) follow the flows added unless set explicitly.
+pub struct FrameBuilder {
+    frame: TelemetryFrame,
+    bps: Option<f64>,
+}
+
+impl FrameBuilder {
+    pub fn at(t: f64) -> Self {
+        Self {
+            frame: TelemetryFrame {
+                schema: SCHEMA_VERSION,
+                t,
+                light: None,
+                net: NetMetrics {
+                    bps: 0.0,
+                    pps: 0,
+                    active_flows: 0,
+                    latency_ms: 0.0,
+                    upload_bps: 0.0,
+                    download_bps: 0.0,
+                    measured_flows: 0,
+                    measured_rtt_flows: 0,
+                },
+                proto: ProtoCounters::default(),
+                flows: Vec::new(),
+                interface: None,
+                rate: None,
+                sockets: None,
+                wifi: None,
+                processes: Vec::new(),
+            },
+            bps: None,
+        }
+    }
+
+    /// Interface upload/download rates (bits/s), integrated into session byte totals.
+    pub fn rates(mut self, upload_bps: f64, download_bps: f64) -> Self {
+        self.frame.net.upload_bps = upload_bps;
+        self.frame.net.download_bps = download_bps;
+        self
+    }
+
+    pub fn bps(mut self, bps: f64) -> Self {
+        self.bps = Some(bps);
+        self
+    }
+
+    pub fn latency(mut self, ms: f64) -> Self {
+        self.frame.net.latency_ms = ms;
+        self
+    }
+
+    pub fn flow(mut self, flow: GeoFlow) -> Self {
+        self.frame.flows.push(flow);
+        self
+    }
+
+    pub fn flows(mut self, flows: impl IntoIterator<Item = GeoFlow>) -> Self {
+        self.frame.flows.extend(flows);
+        self
+    }
+
+    /// Attributed per-process rate (bits/s), for `process_usage`.
+    pub fn process_rate(mut self, process: &str, tx_bps: f64, rx_bps: f64) -> Self {
+        self.frame.processes.push(ProcessRate {
+            process: process.to_string(),
+            tx_bps,
+            rx_bps,
+            sockets: 1,
+            measured: true,
+            app_group: None,
+        });
+        self
+    }
+
+    pub fn build(mut self) -> TelemetryFrame {
+        let flows = &self.frame.flows;
+        self.frame.net.active_flows = flows.len() as u32;
+        self.frame.net.pps = flows.iter().map(|f| f.pps).sum();
+        self.frame.net.bps = self.bps.unwrap_or_else(|| flows.iter().map(|f| f.bps).sum());
+        for flow in flows {
+            match flow.protocol {
+                1 => self.frame.proto.tcp += 1,
+                2 => self.frame.proto.udp += 1,
+                _ => self.frame.proto.other += 1,
+            }
+        }
+        self.frame
+    }
+}
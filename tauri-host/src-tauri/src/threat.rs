@@ -0,0 +1,336 @@
+use crate::error::AbyssError;
+use crate::monitor::MonitorHost;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// How often the watcher re-checks feed ages and local files when settings
+/// haven't changed.
+const FEED_RECHECK: Duration = Duration::from_secs(300);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+// ─── Configuration ──────────────────────────────────────────────────────────
+
+/// One blocklist: an `http(s)://` URL downloaded every `refresh_hours`, or
+/// a local file re-read when it changes.  One IP or CIDR per line; `#` and
+/// `;` start comments (FireHOL `.netset`/`.ipset`, abuse.ch, Spamhaus DROP).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreatFeed {
+    /// Shown on matching flows (`GeoFlow::threat`).
+    pub name: String,
+    pub source: String,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+impl ThreatFeed {
+    fn is_remote(&self) -> bool {
+        self.source.starts_with("https://") || self.source.starts_with("http://")
+    }
+}
+
+/// IP reputation lookups against local blocklists.  Off by default; feeds
+/// are only downloaded while enabled.  Persisted in settings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ThreatIntelConfig {
+    pub enabled: bool,
+    pub feeds: Vec<ThreatFeed>,
+    pub refresh_hours: u32,
+}
+
+impl Default for ThreatIntelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            feeds: vec![
+                ThreatFeed {
+                    name: "firehol_level1".to_string(),
+                    source: "https://iplists.firehol.org/files/firehol_level1.netset".to_string(),
+                    enabled: true,
+                },
+                ThreatFeed {
+                    name: "feodo".to_string(),
+                    source: "https://feodotracker.abuse.ch/downloads/ipblocklist.txt".to_string(),
+                    enabled: true,
+                },
+            ],
+            refresh_hours: 24,
+        }
+    }
+}
+
+impl ThreatIntelConfig {
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        if !(1..=168).contains(&self.refresh_hours) {
+            return Err(AbyssError::InvalidInput(
+                "threatIntel.refreshHours must be between 1 and 168".into(),
+            ));
+        }
+        let mut names = BTreeSet::new();
+        for feed in &self.feeds {
+            if feed.name.trim().is_empty() || feed.source.trim().is_empty() {
+                return Err(AbyssError::InvalidInput("threatIntel feeds need a name and a source".into()));
+            }
+            if !names.insert(feed.name.as_str()) {
+                return Err(AbyssError::InvalidInput(format!(
+                    "threatIntel feed '{}' is listed twice",
+                    feed.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// ─── Index ──────────────────────────────────────────────────────────────────
+
+/// Load state of one feed, for `cmd_get_threat_feeds`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedStatus {
+    pub name: String,
+    pub source: String,
+    /// Addresses and networks loaded from the feed.
+    pub entries: usize,
+    /// When the list was downloaded (or the local file last changed).
+    pub updated_at: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Blocklisted networks of all enabled feeds, keyed by prefix length and
+/// masked address.  A lookup tries each prefix length present, longest first.
+#[derive(Default)]
+pub struct ThreatIndex {
+    v4: HashMap<(u8, u32), usize>,
+    v6: HashMap<(u8, u128), usize>,
+    v4_prefixes: Vec<u8>,
+    v6_prefixes: Vec<u8>,
+    /// Feed names, indexed by the map values.
+    names: Vec<String>,
+    pub feeds: Vec<FeedStatus>,
+}
+
+impl ThreatIndex {
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    /// Feed with the most specific entry covering `ip`; for identical
+    /// entries, the first feed in configured order.
+    pub fn lookup(&self, ip: &str) -> Option<&str> {
+        if self.is_empty() {
+            return None;
+        }
+        let feed = match ip.parse::<IpAddr>().ok()? {
+            IpAddr::V4(v4) => {
+                let addr = u32::from(v4);
+                self.v4_prefixes
+                    .iter()
+                    .find_map(|&len| self.v4.get(&(len, mask_v4(addr, len))))
+            }
+            IpAddr::V6(v6) => {
+                let addr = u128::from(v6);
+                self.v6_prefixes
+                    .iter()
+                    .find_map(|&len| self.v6.get(&(len, mask_v6(addr, len))))
+            }
+        };
+        feed.map(|&i| self.names[i].as_str())
+    }
+
+    /// Add a feed's list; returns how many entries it had.  Entries already
+    /// listed by an earlier feed keep that feed's name.
+    fn add(&mut self, name: &str, text: &str) -> usize {
+        let feed = self.names.len();
+        self.names.push(name.to_string());
+        let mut entries = 0;
+        for (ip, len) in parse_list(text) {
+            entries += 1;
+            match ip {
+                IpAddr::V4(v4) => {
+                    self.v4.entry((len, mask_v4(u32::from(v4), len))).or_insert(feed);
+                }
+                IpAddr::V6(v6) => {
+                    self.v6.entry((len, mask_v6(u128::from(v6), len))).or_insert(feed);
+                }
+            }
+        }
+        let v4: BTreeSet<u8> = self.v4.keys().map(|(len, _)| *len).collect();
+        let v6: BTreeSet<u8> = self.v6.keys().map(|(len, _)| *len).collect();
+        self.v4_prefixes = v4.into_iter().rev().collect();
+        self.v6_prefixes = v6.into_iter().rev().collect();
+        entries
+    }
+}
+
+pub fn mask_v4(addr: u32, len: u8) -> u32 {
+    if len == 0 {
+        0
+    } else {
+        addr & (u32::MAX << (32 - len as u32))
+    }
+}
+
+pub fn mask_v6(addr: u128, len: u8) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        addr & (u128::MAX << (128 - len as u32))
+    }
+}
+
+/// An IP (as a /32 or /128) or CIDR network and its prefix length.
+pub fn parse_network(entry: &str) -> Option<(IpAddr, u8)> {
+    let (ip, len) = match entry.split_once('/') {
+        Some((ip, len)) => (ip.parse::<IpAddr>().ok()?, len.parse::<u8>().ok()?),
+        None => {
+            let ip = entry.parse::<IpAddr>().ok()?;
+            (ip, if ip.is_ipv4() { 32 } else { 128 })
+        }
+    };
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    (len <= max).then_some((ip, len))
+}
+
+/// Addresses and networks of a blocklist.  Lines that don't start with an
+/// IP or CIDR (headers, CSV columns after the first) are skipped.
+fn parse_list(text: &str) -> impl Iterator<Item = (IpAddr, u8)> + '_ {
+    text.lines().filter_map(|line| {
+        let entry = line.split(['#', ';']).next()?.split([' ', '\t', ',']).find(|s| !s.is_empty())?;
+        // Default routes in a blocklist would flag everything
+        parse_network(entry).filter(|&(_, len)| len > 0)
+    })
+}
+
+// ─── Feeds ──────────────────────────────────────────────────────────────────
+
+/// Keep `host`'s threat index in step with the configured feeds: download
+/// remote lists older than `refresh_hours` into `feeds_dir` and rebuild the
+/// index when settings, downloads or local files change.
+pub async fn watch(host: Arc<impl MonitorHost>, feeds_dir: PathBuf) {
+    let mut settings_rx = host.subscribe_settings();
+    let client = reqwest::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let mut loaded: Option<(ThreatIntelConfig, Vec<Option<SystemTime>>)> = None;
+    let mut errors: HashMap<String, String> = HashMap::new();
+    loop {
+        let config = settings_rx.borrow_and_update().threat_intel.clone();
+        let feeds: Vec<&ThreatFeed> = config.feeds.iter().filter(|f| config.enabled && f.enabled).collect();
+
+        let max_age = Duration::from_secs(config.refresh_hours as u64 * 3600);
+        for feed in feeds.iter().filter(|f| f.is_remote()) {
+            let path = feed_path(&feeds_dir, feed);
+            let stale = modified(&path).is_none_or(|at| at.elapsed().unwrap_or_default() >= max_age);
+            if !stale {
+                continue;
+            }
+            match download(&client, feed, &path).await {
+                Ok(()) => {
+                    errors.remove(&feed.name);
+                }
+                Err(e) => {
+                    eprintln!("[Abyss] Threat feed '{}' download failed: {e}", feed.name);
+                    errors.insert(feed.name.clone(), e.to_string());
+                }
+            }
+        }
+
+        let stamps: Vec<Option<SystemTime>> = feeds.iter().map(|f| modified(&source_path(&feeds_dir, f))).collect();
+        if loaded.as_ref() != Some(&(config.clone(), stamps.clone())) {
+            let sources: Vec<(ThreatFeed, PathBuf)> = feeds
+                .iter()
+                .map(|f| ((*f).clone(), source_path(&feeds_dir, f)))
+                .collect();
+            let feed_errors = errors.clone();
+            let index = tokio::task::spawn_blocking(move || build_index(&sources, &feed_errors))
+                .await
+                .unwrap_or_default();
+            if !index.is_empty() {
+                let entries: usize = index.feeds.iter().map(|f| f.entries).sum();
+                println!("[Abyss] Threat intel: {entries} entries from {} feed(s)", index.feeds.len());
+            }
+            host.set_threats(Arc::new(index));
+            loaded = Some((config, stamps));
+        }
+
+        // Settings changes take effect right away; otherwise re-check ages
+        if let Ok(Err(_)) = tokio::time::timeout(FEED_RECHECK, settings_rx.changed()).await {
+            return;
+        }
+    }
+}
+
+fn build_index(sources: &[(ThreatFeed, PathBuf)], errors: &HashMap<String, String>) -> ThreatIndex {
+    let mut index = ThreatIndex::default();
+    for (feed, path) in sources {
+        let mut status = FeedStatus {
+            name: feed.name.clone(),
+            source: feed.source.clone(),
+            entries: 0,
+            updated_at: modified(path).map(|at| DateTime::<Utc>::from(at).to_rfc3339()),
+            error: errors.get(&feed.name).cloned(),
+        };
+        match std::fs::read_to_string(path) {
+            Ok(text) => status.entries = index.add(&feed.name, &text),
+            Err(e) => {
+                status.error.get_or_insert_with(|| format!("Cannot read {}: {e}", path.display()));
+            }
+        }
+        index.feeds.push(status);
+    }
+    index
+}
+
+async fn download(client: &reqwest::Client, feed: &ThreatFeed, path: &Path) -> Result<(), AbyssError> {
+    let resp = client.get(&feed.source).send().await?;
+    if !resp.status().is_success() {
+        return Err(AbyssError::Network(format!("HTTP {}", resp.status())));
+    }
+    let text = resp.text().await?;
+    if parse_list(&text).next().is_none() {
+        return Err(AbyssError::Network("response contains no IP addresses".into()));
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Write then rename so a failed write never leaves half a list behind
+    let partial = path.with_extension("part");
+    std::fs::write(&partial, text)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Where a remote feed is cached.
+fn feed_path(feeds_dir: &Path, feed: &ThreatFeed) -> PathBuf {
+    let file: String = feed
+        .name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    feeds_dir.join(format!("{file}.txt"))
+}
+
+/// The file a feed is read from.
+fn source_path(feeds_dir: &Path, feed: &ThreatFeed) -> PathBuf {
+    if feed.is_remote() {
+        feed_path(feeds_dir, feed)
+    } else {
+        PathBuf::from(&feed.source)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
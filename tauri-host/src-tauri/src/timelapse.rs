@@ -0,0 +1,189 @@
+use crate::db::{PlaybackData, PlaybackFlowRecord};
+use crate::error::AbyssError;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Output frames a single export may produce (~11 minutes at 30 fps).
+const MAX_FRAMES: usize = 20_000;
+/// Arcs kept per output frame, strongest first.
+const MAX_ARCS: usize = 100;
+/// Arcs fainter than this are left out of a frame.
+const MIN_OPACITY: f64 = 0.01;
+
+// ─── Output ─────────────────────────────────────────────────────────────────
+
+/// A session resampled at a fixed step, ready to be drawn frame by frame
+/// into a video.  Every arc starts at `origin`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Timelapse {
+    pub session_id: String,
+    pub fps: u32,
+    /// Session seconds advanced per output frame.
+    pub step: f64,
+    /// Length of the rendered video, `frames.len() / fps`.
+    pub duration_secs: f64,
+    /// Local position `[lat, lng]`.
+    pub origin: [f64; 2],
+    pub frames: Vec<TimelapseFrame>,
+}
+
+/// Interpolated network state at session time `t`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelapseFrame {
+    pub t: f64,
+    pub bps: f64,
+    pub upload_bps: f64,
+    pub download_bps: f64,
+    pub latency_ms: f64,
+    pub active_flows: f64,
+    pub arcs: Vec<ArcState>,
+}
+
+/// One flow's arc in a frame.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ArcState {
+    pub flow_id: String,
+    /// Destination `[lat, lng]`.
+    pub to: [f64; 2],
+    pub bps: f64,
+    /// 0–1, log-scaled against the session's busiest flow snapshot.
+    pub intensity: f64,
+    /// 0–1; arcs fade in and out between the snapshots they appear in.
+    pub opacity: f64,
+}
+
+// ─── Interpolation ──────────────────────────────────────────────────────────
+
+/// Flows recorded at one sampled frame.
+struct Keyframe<'a> {
+    t: f64,
+    flows: HashMap<&'a str, &'a PlaybackFlowRecord>,
+}
+
+/// Resample `data` every `step` session seconds.  Frame metrics are
+/// interpolated linearly; flows only exist at the (sparser) flow snapshot
+/// frames, so arcs are eased between those, fading in from and out to zero
+/// where a flow is missing on one side.
+pub fn build(data: &PlaybackData, fps: u32, step: f64) -> Result<Timelapse, AbyssError> {
+    if !(1..=120).contains(&fps) {
+        return Err(AbyssError::InvalidInput("fps must be between 1 and 120".into()));
+    }
+    if !step.is_finite() || step <= 0.0 {
+        return Err(AbyssError::InvalidInput("step must be a positive number of seconds".into()));
+    }
+    let (Some(first), Some(last)) = (data.frames.first(), data.frames.last()) else {
+        return Err(AbyssError::NotFound("Session has no recorded frames".into()));
+    };
+    let count = ((last.t - first.t) / step).floor() as usize + 1;
+    if count > MAX_FRAMES {
+        return Err(AbyssError::InvalidInput(format!(
+            "A step of {step}s gives {count} frames; the limit is {MAX_FRAMES}"
+        )));
+    }
+
+    let frame_t: HashMap<i64, f64> = data.frames.iter().map(|f| (f.frame_id, f.t)).collect();
+    let mut keyframes: Vec<Keyframe> = Vec::new();
+    for flow in &data.flows {
+        let Some(&t) = frame_t.get(&flow.frame_id) else {
+            continue;
+        };
+        if keyframes.last().is_none_or(|k| k.t != t) {
+            keyframes.push(Keyframe { t, flows: HashMap::new() });
+        }
+        if let Some(k) = keyframes.last_mut() {
+            k.flows.insert(flow.flow_id.as_str(), flow);
+        }
+    }
+    keyframes.sort_by(|a, b| a.t.total_cmp(&b.t));
+    let peak_flow_bps = data.flows.iter().map(|f| f.bps).fold(0.0, f64::max);
+
+    let frames = (0..count)
+        .map(|i| {
+            let t = first.t + i as f64 * step;
+            let (a, b, alpha) = bracket(&data.frames, |f| f.t, t);
+            let lerp = |x: f64, y: f64| x + (y - x) * alpha;
+            TimelapseFrame {
+                t: round3(t),
+                bps: lerp(a.bps, b.bps).round(),
+                upload_bps: lerp(a.upload_bps, b.upload_bps).round(),
+                download_bps: lerp(a.download_bps, b.download_bps).round(),
+                latency_ms: round3(lerp(a.latency_ms, b.latency_ms)),
+                active_flows: round3(lerp(a.active_flows as f64, b.active_flows as f64)),
+                arcs: arcs_at(&keyframes, t, peak_flow_bps),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Timelapse {
+        session_id: data.session.id.clone(),
+        fps,
+        step,
+        duration_secs: round3(frames.len() as f64 / fps as f64),
+        origin: [data.session.local_lat, data.session.local_lng],
+        frames,
+    })
+}
+
+/// The items either side of `t` (by `key`, ascending) and how far `t` is
+/// between them; clamped to the ends.
+fn bracket<T>(items: &[T], key: impl Fn(&T) -> f64, t: f64) -> (&T, &T, f64) {
+    let next = items.partition_point(|item| key(item) <= t);
+    if next == 0 {
+        return (&items[0], &items[0], 0.0);
+    }
+    if next == items.len() {
+        let last = &items[items.len() - 1];
+        return (last, last, 0.0);
+    }
+    let (a, b) = (&items[next - 1], &items[next]);
+    let span = key(b) - key(a);
+    let alpha = if span > 0.0 { (t - key(a)) / span } else { 0.0 };
+    (a, b, alpha)
+}
+
+fn arcs_at(keyframes: &[Keyframe], t: f64, peak_flow_bps: f64) -> Vec<ArcState> {
+    if keyframes.is_empty() {
+        return Vec::new();
+    }
+    let (a, b, alpha) = bracket(keyframes, |k| k.t, t);
+    // Ease so arcs linger near each snapshot rather than sliding constantly
+    let eased = alpha * alpha * (3.0 - 2.0 * alpha);
+
+    let mut arcs: Vec<ArcState> = Vec::with_capacity(a.flows.len().max(b.flows.len()));
+    let ids = a.flows.keys().chain(b.flows.keys().filter(|id| !a.flows.contains_key(*id)));
+    for id in ids {
+        let (from, to) = (a.flows.get(id), b.flows.get(id));
+        let Some(flow) = from.or(to) else {
+            continue;
+        };
+        let bps0 = from.map_or(0.0, |f| f.bps);
+        let bps1 = to.map_or(0.0, |f| f.bps);
+        let opacity = (from.is_some() as u8 as f64) * (1.0 - eased) + (to.is_some() as u8 as f64) * eased;
+        if opacity < MIN_OPACITY {
+            continue;
+        }
+        let bps = bps0 + (bps1 - bps0) * eased;
+        let intensity = if peak_flow_bps > 0.0 {
+            ((1.0 + bps).ln() / (1.0 + peak_flow_bps).ln()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        arcs.push(ArcState {
+            flow_id: flow.flow_id.clone(),
+            to: [flow.dst_lat, flow.dst_lng],
+            bps: bps.round(),
+            intensity: round3(intensity),
+            opacity: round3(opacity),
+        });
+    }
+    arcs.sort_unstable_by(|x, y| y.bps.total_cmp(&x.bps));
+    arcs.truncate(MAX_ARCS);
+    arcs
+}
+
+fn round3(v: f64) -> f64 {
+    (v * 1000.0).round() / 1000.0
+}
@@ -0,0 +1,219 @@
+use crate::app::AppState;
+use crate::locks::LockExt;
+use crate::units::UnitPrefs;
+use crate::{units, TelemetryFrame};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager};
+
+pub const TRAY_ID: &str = "abyss";
+
+/// Passed by start-on-login; the app then starts hidden in the tray.
+pub const BACKGROUND_ARG: &str = "--background";
+
+const ITEM_SESSION: &str = "tray-session";
+const ITEM_PAUSE: &str = "tray-pause";
+const ITEM_SHOW: &str = "tray-show";
+const ITEM_DATA_FOLDER: &str = "tray-data-folder";
+const ITEM_QUIT: &str = "tray-quit";
+
+// ─── Tray ───────────────────────────────────────────────────────────────────
+
+/// Menu items whose labels follow the recording state, plus the last
+/// throughput seen, so the tooltip can be rebuilt without a new frame
+/// (e.g. right after pausing).
+struct TrayHandles {
+    session: MenuItem<tauri::Wry>,
+    pause: MenuItem<tauri::Wry>,
+    throughput: Mutex<Throughput>,
+    /// Labels and tooltip last applied, to skip redundant platform calls.
+    applied: Mutex<Option<TrayView>>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Throughput {
+    download_bps: f64,
+    upload_bps: f64,
+    active_flows: u32,
+}
+
+#[derive(Clone, PartialEq)]
+struct TrayView {
+    session_label: &'static str,
+    session_enabled: bool,
+    pause_label: &'static str,
+    tooltip: String,
+}
+
+/// Add the tray icon.  Left click brings the main window back; the menu
+/// drives the same session and pause paths as the commands.
+pub fn install(app: &AppHandle) -> tauri::Result<()> {
+    let session = MenuItem::with_id(app, ITEM_SESSION, "Start session", true, None::<&str>)?;
+    let pause = MenuItem::with_id(app, ITEM_PAUSE, "Pause monitoring", true, None::<&str>)?;
+    let show = MenuItem::with_id(app, ITEM_SHOW, "Show Abyss", true, None::<&str>)?;
+    let data_folder = MenuItem::with_id(app, ITEM_DATA_FOLDER, "Open data folder", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, ITEM_QUIT, "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &session,
+            &pause,
+            &PredefinedMenuItem::separator(app)?,
+            &show,
+            &data_folder,
+            &PredefinedMenuItem::separator(app)?,
+            &quit,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Abyss")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(on_tray_icon_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(TrayHandles {
+        session,
+        pause,
+        throughput: Mutex::new(Throughput::default()),
+        applied: Mutex::new(None),
+    });
+    refresh(app);
+    Ok(())
+}
+
+/// Record the latest throughput (called by the monitor loop every tick).
+pub fn update(app: &AppHandle, frame: &TelemetryFrame) {
+    let Some(handles) = app.try_state::<TrayHandles>() else {
+        return;
+    };
+    *handles.throughput.lock_or_recover("tray_throughput") = Throughput {
+        download_bps: frame.net.download_bps,
+        upload_bps: frame.net.upload_bps,
+        active_flows: frame.net.active_flows,
+    };
+    refresh(app);
+}
+
+/// Bring menu labels and the tooltip in line with the current state.
+pub fn refresh(app: &AppHandle) {
+    let (Some(handles), Some(state)) = (app.try_state::<TrayHandles>(), app.try_state::<AppState>()) else {
+        return;
+    };
+    let recording = state
+        .current_session_id
+        .lock_or_recover("current_session_id")
+        .is_some();
+    let paused = state.paused_since.lock_or_recover("paused_since").is_some();
+    let monitor_only = state.monitor_only.load(Ordering::Relaxed);
+    let throughput = *handles.throughput.lock_or_recover("tray_throughput");
+    let view = TrayView {
+        session_label: if recording { "Stop session" } else { "Start session" },
+        session_enabled: recording || !monitor_only,
+        pause_label: if paused { "Resume monitoring" } else { "Pause monitoring" },
+        tooltip: tooltip(units::current(), throughput, recording, paused),
+    };
+
+    let mut applied = handles.applied.lock_or_recover("tray_applied");
+    if applied.as_ref() == Some(&view) {
+        return;
+    }
+    let _ = handles.session.set_text(view.session_label);
+    let _ = handles.session.set_enabled(view.session_enabled);
+    let _ = handles.pause.set_text(view.pause_label);
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some(&view.tooltip));
+    }
+    *applied = Some(view);
+}
+
+fn tooltip(units: UnitPrefs, throughput: Throughput, recording: bool, paused: bool) -> String {
+    if paused {
+        return "Abyss \u{2014} monitoring paused".to_string();
+    }
+    format!(
+        "Abyss \u{2014} \u{2193} {}  \u{2191} {}\n{} flows{}",
+        units.rate(throughput.download_bps),
+        units.rate(throughput.upload_bps),
+        throughput.active_flows,
+        if recording { " \u{00b7} recording" } else { "" }
+    )
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    match event.id().as_ref() {
+        ITEM_SESSION => {
+            if crate::app::end_session(&state).is_none() {
+                state.startup_prompt.store(false, Ordering::Relaxed);
+                if let Err(e) = crate::app::begin_session(&state, None) {
+                    eprintln!("[Abyss] Tray could not start a session: {e}");
+                }
+            }
+            refresh(app);
+        }
+        ITEM_PAUSE => {
+            let paused = state.paused_since.lock_or_recover("paused_since").is_some();
+            crate::app::set_paused(app, &state, !paused);
+        }
+        ITEM_SHOW => show_main_window(app),
+        ITEM_DATA_FOLDER => {
+            if let Err(e) = crate::app::open_data_folder(&state.db_path) {
+                eprintln!("[Abyss] Could not open data folder: {e}");
+            }
+        }
+        ITEM_QUIT => app.exit(0),
+        _ => {}
+    }
+}
+
+fn on_tray_icon_event(tray: &TrayIcon, event: TrayIconEvent) {
+    if let TrayIconEvent::Click {
+        button: MouseButton::Left,
+        button_state: MouseButtonState::Up,
+        ..
+    } = event
+    {
+        show_main_window(tray.app_handle());
+    }
+}
+
+fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tooltip_shows_throughput_or_pause() {
+        let throughput = Throughput {
+            download_bps: 12_000_000.0,
+            upload_bps: 800_000.0,
+            active_flows: 42,
+        };
+        let text = tooltip(UnitPrefs::default(), throughput, true, false);
+        assert!(text.contains("12.0 Mbps"), "{text}");
+        assert!(text.contains("800"), "{text}");
+        assert!(text.contains("42 flows \u{00b7} recording"), "{text}");
+        assert!(!tooltip(UnitPrefs::default(), throughput, false, false).contains("recording"));
+        assert_eq!(
+            tooltip(UnitPrefs::default(), throughput, true, true),
+            "Abyss \u{2014} monitoring paused"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// ─── Preference ─────────────────────────────────────────────────────────────
+
+/// Multiplier between unit steps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnitSystem {
+    /// Powers of 1000: kB, MB, Mbps.
+    #[default]
+    Si,
+    /// Powers of 1024: KiB, MiB, Mibit/s.
+    Iec,
+}
+
+/// Whether throughput is shown in bits or bytes per second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RateUnit {
+    #[default]
+    Bits,
+    Bytes,
+}
+
+/// How sizes and rates are written in server-generated text (insights,
+/// anomaly and alert messages, session cards, notifications).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UnitPrefs {
+    pub system: UnitSystem,
+    pub rate_unit: RateUnit,
+}
+
+/// Process-wide copy of `Settings::units`, so formatting deep in `db` and
+/// `alerts` doesn't need the settings threaded through.  Updated whenever
+/// settings are committed.
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+impl UnitPrefs {
+    fn to_bits(self) -> u8 {
+        (self.system == UnitSystem::Iec) as u8 | ((self.rate_unit == RateUnit::Bytes) as u8) << 1
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        Self {
+            system: if bits & 1 != 0 { UnitSystem::Iec } else { UnitSystem::Si },
+            rate_unit: if bits & 2 != 0 { RateUnit::Bytes } else { RateUnit::Bits },
+        }
+    }
+}
+
+pub fn set_current(prefs: UnitPrefs) {
+    CURRENT.store(prefs.to_bits(), Ordering::Relaxed);
+}
+
+pub fn current() -> UnitPrefs {
+    UnitPrefs::from_bits(CURRENT.load(Ordering::Relaxed))
+}
+
+// ─── Formatting ─────────────────────────────────────────────────────────────
+
+const SI_BYTES: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+const IEC_BYTES: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+const SI_BITS: [&str; 5] = ["bps", "kbps", "Mbps", "Gbps", "Tbps"];
+const IEC_BITS: [&str; 5] = ["bit/s", "Kibit/s", "Mibit/s", "Gibit/s", "Tibit/s"];
+const SI_BYTE_RATE: [&str; 5] = ["B/s", "kB/s", "MB/s", "GB/s", "TB/s"];
+const IEC_BYTE_RATE: [&str; 5] = ["B/s", "KiB/s", "MiB/s", "GiB/s", "TiB/s"];
+
+impl UnitPrefs {
+    /// A byte count, e.g. "1.5 GB" / "1.4 GiB".
+    pub fn bytes(self, bytes: f64) -> String {
+        let labels = match self.system {
+            UnitSystem::Si => &SI_BYTES,
+            UnitSystem::Iec => &IEC_BYTES,
+        };
+        scaled(bytes, self.step(), labels)
+    }
+
+    /// A rate given in bits/s (as every `*_bps` field is), e.g. "12.0 Mbps"
+    /// or, with `RateUnit::Bytes`, "1.5 MB/s".
+    pub fn rate(self, bps: f64) -> String {
+        let (value, labels) = match (self.rate_unit, self.system) {
+            (RateUnit::Bits, UnitSystem::Si) => (bps, &SI_BITS),
+            (RateUnit::Bits, UnitSystem::Iec) => (bps, &IEC_BITS),
+            (RateUnit::Bytes, UnitSystem::Si) => (bps / 8.0, &SI_BYTE_RATE),
+            (RateUnit::Bytes, UnitSystem::Iec) => (bps / 8.0, &IEC_BYTE_RATE),
+        };
+        scaled(value, self.step(), labels)
+    }
+
+    fn step(self) -> f64 {
+        match self.system {
+            UnitSystem::Si => 1000.0,
+            UnitSystem::Iec => 1024.0,
+        }
+    }
+}
+
+fn scaled(value: f64, step: f64, labels: &[&str; 5]) -> String {
+    if !value.is_finite() || value < 0.0 {
+        return format!("0 {}", labels[0]);
+    }
+    let mut value = value;
+    let mut idx = 0;
+    while value >= step && idx < labels.len() - 1 {
+        value /= step;
+        idx += 1;
+    }
+    if idx == 0 {
+        format!("{value:.0} {}", labels[0])
+    } else {
+        format!("{value:.1} {}", labels[idx])
+    }
+}
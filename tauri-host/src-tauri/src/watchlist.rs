@@ -0,0 +1,205 @@
+use crate::alerts::{AlertContext, AlertEvent, AlertFrame, Severity};
+use crate::error::AbyssError;
+use crate::{GeoEndpoint, TelemetryFrame};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// `AlertEvent::rule_id` of watchlist alerts (there is no stored rule).
+pub const WATCHLIST_RULE_ID: &str = "watchlist";
+/// Minimum time between two alerts for the same watched country or ASN.
+const ALERT_COOLDOWN: Duration = Duration::from_secs(300);
+
+// ─── Watchlist ──────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WatchKind {
+    Country,
+    Asn,
+}
+
+impl WatchKind {
+    /// Value stored in `watchlist.kind` and `watch_hits.kind`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WatchKind::Country => "country",
+            WatchKind::Asn => "asn",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "country" => Some(WatchKind::Country),
+            "asn" => Some(WatchKind::Asn),
+            _ => None,
+        }
+    }
+}
+
+/// Countries (ISO 3166 alpha-2, as the geo providers report them) and
+/// autonomous systems (`AS15169`) to watch for, stored in `watchlist`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watchlist {
+    pub countries: Vec<String>,
+    pub asns: Vec<String>,
+}
+
+impl Watchlist {
+    /// Normalize entries: countries upper-cased, ASNs as `AS<number>` (a
+    /// bare number is accepted), duplicates dropped.
+    pub fn new(countries: Vec<String>, asns: Vec<String>) -> Result<Self, AbyssError> {
+        let mut list = Watchlist::default();
+        for country in countries.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(AbyssError::InvalidInput(format!(
+                    "'{country}' is not a two-letter country code"
+                )));
+            }
+            let country = country.to_ascii_uppercase();
+            if !list.countries.contains(&country) {
+                list.countries.push(country);
+            }
+        }
+        for asn in asns.iter().map(|a| a.trim()).filter(|a| !a.is_empty()) {
+            let digits = asn
+                .get(..2)
+                .filter(|prefix| prefix.eq_ignore_ascii_case("AS"))
+                .map_or(asn, |_| &asn[2..]);
+            let Ok(number) = digits.parse::<u32>() else {
+                return Err(AbyssError::InvalidInput(format!("'{asn}' is not an AS number")));
+            };
+            let asn = format!("AS{number}");
+            if !list.asns.contains(&asn) {
+                list.asns.push(asn);
+            }
+        }
+        Ok(list)
+    }
+
+    /// The watched entity `dst` belongs to; the country wins when both are listed.
+    pub fn matches(&self, dst: &GeoEndpoint) -> Option<WatchTag> {
+        if self.countries.iter().any(|c| c.eq_ignore_ascii_case(&dst.country)) {
+            return Some(WatchTag {
+                kind: WatchKind::Country,
+                value: dst.country.to_ascii_uppercase(),
+            });
+        }
+        let asn = dst.asn.as_deref()?;
+        self.asns.iter().any(|a| a.eq_ignore_ascii_case(asn)).then(|| WatchTag {
+            kind: WatchKind::Asn,
+            value: asn.to_ascii_uppercase(),
+        })
+    }
+}
+
+/// Set on `GeoFlow::watch` for flows to a watched country or ASN.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchTag {
+    pub kind: WatchKind,
+    pub value: String,
+}
+
+/// A tagged flow in this tick's frame (before live filters and truncation).
+pub struct WatchedFlow {
+    pub key: String,
+    pub flow_id: String,
+    pub process: Option<String>,
+    pub tag: WatchTag,
+}
+
+/// A flow to a watched entity that wasn't there the tick before, for the
+/// writer's per-session `watch_hits` counts.
+#[derive(Clone, Debug)]
+pub struct WatchHit {
+    pub tag: WatchTag,
+    pub timestamp: String,
+}
+
+// ─── Tracker ────────────────────────────────────────────────────────────────
+
+/// Turns each tick's tagged flows into hits and alerts.  A flow counts once
+/// when it first shows up tagged (usually the tick its geo lookup lands), and
+/// each watched entity alerts at most once per `ALERT_COOLDOWN`.
+#[derive(Default)]
+pub struct WatchTracker {
+    tagged: HashSet<String>,
+    last_alert: HashMap<WatchTag, Instant>,
+}
+
+impl WatchTracker {
+    pub fn observe(
+        &mut self,
+        watched: Vec<WatchedFlow>,
+        frame: &TelemetryFrame,
+        session_id: Option<&str>,
+    ) -> (Vec<WatchHit>, Vec<AlertEvent>) {
+        let timestamp = Utc::now().to_rfc3339();
+        let mut fresh: HashMap<WatchTag, Vec<WatchedFlow>> = HashMap::new();
+        let mut tagged = HashSet::with_capacity(watched.len());
+        for flow in watched {
+            tagged.insert(flow.key.clone());
+            if !self.tagged.contains(&flow.key) {
+                fresh.entry(flow.tag.clone()).or_default().push(flow);
+            }
+        }
+        self.tagged = tagged;
+        self.last_alert.retain(|_, at| at.elapsed() < ALERT_COOLDOWN);
+
+        let mut hits = Vec::new();
+        let mut alerts = Vec::new();
+        for (tag, flows) in fresh {
+            hits.extend(flows.iter().map(|_| WatchHit {
+                tag: tag.clone(),
+                timestamp: timestamp.clone(),
+            }));
+            if self.last_alert.contains_key(&tag) {
+                continue;
+            }
+            self.last_alert.insert(tag.clone(), Instant::now());
+            alerts.push(alert(&tag, &flows, frame, session_id, &timestamp));
+        }
+        (hits, alerts)
+    }
+}
+
+fn alert(
+    tag: &WatchTag,
+    flows: &[WatchedFlow],
+    frame: &TelemetryFrame,
+    session_id: Option<&str>,
+    timestamp: &str,
+) -> AlertEvent {
+    let what = match tag.kind {
+        WatchKind::Country => "watched country",
+        WatchKind::Asn => "watched network",
+    };
+    let process = flows.iter().find_map(|f| f.process.clone());
+    let message = match &process {
+        Some(process) => format!("{process} connected to {what} {}", tag.value),
+        None => format!("{} new flow(s) to {what} {}", flows.len(), tag.value),
+    };
+    AlertEvent {
+        id: uuid::Uuid::new_v4().to_string(),
+        rule_id: WATCHLIST_RULE_ID.to_string(),
+        rule_name: "Watchlist".to_string(),
+        severity: Severity::Warning,
+        subject: tag.value.clone(),
+        message,
+        value: flows.len() as f64,
+        threshold: 0.0,
+        session_id: session_id.map(str::to_string),
+        triggered_at: timestamp.to_string(),
+        suppressed: 0,
+        context: AlertContext {
+            frame: Some(AlertFrame::of(frame)),
+            flow_ids: flows.iter().map(|f| f.flow_id.clone()).collect(),
+            process,
+            country: (tag.kind == WatchKind::Country).then(|| tag.value.clone()),
+            bps: None,
+        },
+    }
+}
@@ -0,0 +1,272 @@
+use crate::db;
+use crate::error::AbyssError;
+use crate::notify;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Delivery attempts per endpoint before a payload is dropped.
+const MAX_ATTEMPTS: u32 = 4;
+/// Delay before the first retry; doubles with each further attempt.
+const RETRY_BASE_SECS: u64 = 2;
+/// How often the health score is recomputed to detect drops.
+const HEALTH_CHECK_INTERVAL_SECS: u64 = 3600;
+/// History behind each health score check.
+const HEALTH_WINDOW_HOURS: u32 = 24;
+/// Points lost since the previous check that fire `HealthScoreDropped`.
+const HEALTH_DROP_POINTS: u32 = 10;
+
+// ─── Endpoints ──────────────────────────────────────────────────────────────
+
+/// What an endpoint can subscribe to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookEvent {
+    /// An alert rule fired (and wasn't muted); `data` is the alert event.
+    AlertFired,
+    /// A session was finalized; `data` holds the session and its insights.
+    SessionEnded,
+    /// The 24-hour health score fell by `HEALTH_DROP_POINTS` or more.
+    HealthScoreDropped,
+}
+
+impl WebhookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::AlertFired => "alertFired",
+            WebhookEvent::SessionEnded => "sessionEnded",
+            WebhookEvent::HealthScoreDropped => "healthScoreDropped",
+        }
+    }
+}
+
+/// A user-configured endpoint, stored in the `webhooks` table.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// HMAC-SHA256 key for the `X-Abyss-Signature` header.
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+impl Webhook {
+    /// A new enabled endpoint.  A random secret is generated unless one is given.
+    pub fn new(url: String, events: Vec<WebhookEvent>, secret: Option<String>) -> Result<Self, AbyssError> {
+        let url = url.trim().to_string();
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(AbyssError::InvalidInput(format!("Webhook URL '{url}' must be an http(s) URL")));
+        }
+        let mut unique: Vec<WebhookEvent> = Vec::new();
+        for event in events {
+            if !unique.contains(&event) {
+                unique.push(event);
+            }
+        }
+        if unique.is_empty() {
+            return Err(AbyssError::InvalidInput("A webhook must subscribe to at least one event".into()));
+        }
+        let secret = match secret.map(|s| s.trim().to_string()) {
+            Some(s) if s.is_empty() => {
+                return Err(AbyssError::InvalidInput("Webhook secret must not be empty".into()));
+            }
+            Some(s) => s,
+            None => format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple()),
+        };
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            url,
+            secret,
+            events: unique,
+            enabled: true,
+            created_at: Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+// ─── Delivery ───────────────────────────────────────────────────────────────
+
+/// Body posted to each endpoint.  `X-Abyss-Signature: sha256=<hex>` is the
+/// HMAC-SHA256 of these exact bytes keyed by the endpoint's secret; `id`
+/// (also in `X-Abyss-Delivery`) stays the same across retries.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope<'a> {
+    id: String,
+    event: WebhookEvent,
+    sent_at: String,
+    data: &'a serde_json::Value,
+}
+
+/// POST `data` to every enabled endpoint subscribed to `event`, in the
+/// background.  Network errors, 429 and 5xx responses are retried with
+/// exponential backoff; other failures are logged and dropped.
+#[cfg(feature = "gui")]
+pub fn deliver(db_path: PathBuf, event: WebhookEvent, data: serde_json::Value) {
+    tauri::async_runtime::spawn(async move {
+        let hooks = match subscribers(db_path, event).await {
+            Ok(hooks) => hooks,
+            Err(e) => {
+                eprintln!("[Abyss] Loading webhooks failed: {e}");
+                return;
+            }
+        };
+        if hooks.is_empty() {
+            return;
+        }
+        let envelope = Envelope {
+            id: uuid::Uuid::new_v4().to_string(),
+            event,
+            sent_at: Utc::now().to_rfc3339(),
+            data: &data,
+        };
+        let body = match serde_json::to_vec(&envelope) {
+            Ok(body) => body,
+            Err(e) => {
+                eprintln!("[Abyss] Webhook payload failed to serialize: {e}");
+                return;
+            }
+        };
+        for hook in hooks {
+            let (body, delivery_id) = (body.clone(), envelope.id.clone());
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = send_with_retry(&hook, event, &delivery_id, body).await {
+                    eprintln!("[Abyss] Webhook {} failed: {e}", hook.url);
+                }
+            });
+        }
+    });
+}
+
+async fn subscribers(db_path: PathBuf, event: WebhookEvent) -> Result<Vec<Webhook>, AbyssError> {
+    tokio::task::spawn_blocking(move || {
+        let conn = db::open_database(&db_path)?;
+        let hooks = db::list_webhooks(&conn)?;
+        Ok(hooks.into_iter().filter(|h| h.enabled && h.events.contains(&event)).collect())
+    })
+    .await?
+}
+
+async fn send_with_retry(
+    hook: &Webhook,
+    event: WebhookEvent,
+    delivery_id: &str,
+    body: Vec<u8>,
+) -> Result<(), AbyssError> {
+    let signature = format!("sha256={}", sign(&hook.secret, &body));
+    let mut attempt = 1;
+    loop {
+        let result = notify::push_client()
+            .post(&hook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Abyss-Event", event.name())
+            .header("X-Abyss-Delivery", delivery_id)
+            .header("X-Abyss-Signature", &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let err = match result {
+            Ok(resp) if resp.status().is_success() => return Ok(()),
+            Ok(resp) if resp.status().is_server_error() || resp.status().as_u16() == 429 => {
+                AbyssError::Network(format!("HTTP {}", resp.status()))
+            }
+            // The endpoint rejected the payload; resending won't change that
+            Ok(resp) => AbyssError::InvalidInput(format!("HTTP {}", resp.status())),
+            Err(e) => AbyssError::from(e),
+        };
+        if !err.retryable() || attempt >= MAX_ATTEMPTS {
+            return Err(err.context(&format!("attempt {attempt}")));
+        }
+        tokio::time::sleep(Duration::from_secs(RETRY_BASE_SECS << (attempt - 1))).await;
+        attempt += 1;
+    }
+}
+
+/// Lowercase hex HMAC-SHA256 of `body`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map(|mut mac| {
+            mac.update(body);
+            mac.finalize().into_bytes().iter().map(|b| format!("{b:02x}")).collect()
+        })
+        .unwrap_or_default()
+}
+
+// ─── Sources ────────────────────────────────────────────────────────────────
+
+/// Deliver `SessionEnded` with the finalized session and its insights.
+#[cfg(feature = "gui")]
+pub fn session_ended(db_path: PathBuf, session_id: String) {
+    tauri::async_runtime::spawn(async move {
+        let lookup_path = db_path.clone();
+        let summary = tokio::task::spawn_blocking(move || -> Result<serde_json::Value, AbyssError> {
+            let conn = db::open_database(&lookup_path)?;
+            let session = db::get_session(&conn, &session_id)?
+                .ok_or_else(|| AbyssError::NotFound(format!("Session '{session_id}' not found")))?;
+            let insights = db::compute_session_insights(&conn, &session_id).ok();
+            Ok(serde_json::json!({ "session": session, "insights": insights }))
+        })
+        .await;
+        match summary {
+            Ok(Ok(data)) => deliver(db_path, WebhookEvent::SessionEnded, data),
+            Ok(Err(e)) => eprintln!("[Abyss] Session summary for webhooks failed: {e}"),
+            Err(e) => eprintln!("[Abyss] Session summary for webhooks panicked: {e}"),
+        }
+    });
+}
+
+/// Recompute the health score every `HEALTH_CHECK_INTERVAL_SECS` and
+/// deliver `HealthScoreDropped` when it falls far enough below the last one.
+#[cfg(feature = "gui")]
+pub async fn health_watch(db_path: PathBuf) {
+    let mut previous: Option<u32> = None;
+    loop {
+        tokio::time::sleep(Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS)).await;
+        let path = db_path.clone();
+        let checked = tokio::task::spawn_blocking(move || health_check(&path)).await;
+        let health = match checked {
+            Ok(Ok(Some(health))) => health,
+            Ok(Ok(None)) => {
+                previous = None;
+                continue;
+            }
+            Ok(Err(e)) => {
+                eprintln!("[Abyss] Health score check failed: {e}");
+                continue;
+            }
+            Err(e) => {
+                eprintln!("[Abyss] Health score check panicked: {e}");
+                continue;
+            }
+        };
+        if let Some(prev) = previous.filter(|prev| health.score + HEALTH_DROP_POINTS <= *prev) {
+            println!("[Abyss] Health score dropped {prev} → {}", health.score);
+            deliver(
+                db_path.clone(),
+                WebhookEvent::HealthScoreDropped,
+                serde_json::json!({ "previousScore": prev, "health": health }),
+            );
+        }
+        previous = Some(health.score);
+    }
+}
+
+/// The current health score, or `None` when nothing subscribes to drops or
+/// nothing was recorded in the window (which scores 0).
+fn health_check(db_path: &Path) -> Result<Option<db::HealthScore>, AbyssError> {
+    let conn = db::open_database(db_path)?;
+    let subscribed = db::list_webhooks(&conn)?
+        .iter()
+        .any(|h| h.enabled && h.events.contains(&WebhookEvent::HealthScoreDropped));
+    if !subscribed {
+        return Ok(None);
+    }
+    let health = db::compute_health_score(&conn, HEALTH_WINDOW_HOURS)?;
+    Ok((health.score > 0).then_some(health))
+}
@@ -0,0 +1,347 @@
+use crate::interfaces::{ActiveInterface, InterfaceKind};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// How often the link is re-read while traffic goes over Wi-Fi.
+const WIFI_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// A reading older than this is dropped rather than repeated in frames.
+const WIFI_SAMPLE_TTL: Duration = Duration::from_secs(30);
+
+// ─── Link ───────────────────────────────────────────────────────────────────
+
+/// Quality of the current Wi-Fi association.  Every field but `interface`
+/// is optional: each platform tool reports a different subset.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WifiLink {
+    pub interface: String,
+    /// `None` for hidden networks and where the OS withholds it (macOS
+    /// without location permission).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bssid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rssi_dbm: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise_dbm: Option<i32>,
+    /// Negotiated PHY rate, not throughput.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_rate_mbps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_rate_mbps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_mhz: Option<u32>,
+}
+
+impl WifiLink {
+    /// Best single link rate for storage: transmit, else receive.
+    pub fn link_mbps(&self) -> Option<f64> {
+        self.tx_rate_mbps.or(self.rx_rate_mbps)
+    }
+}
+
+/// Channel number for a centre frequency (2.4, 5 and 6 GHz bands).
+#[cfg(target_os = "linux")]
+fn channel_of(mhz: u32) -> Option<u32> {
+    match mhz {
+        2484 => Some(14),
+        2412..=2472 => Some((mhz - 2407) / 5),
+        5955..=7115 => Some((mhz - 5950) / 5),
+        5000..=5900 => Some((mhz - 5000) / 5),
+        _ => None,
+    }
+}
+
+/// Leading number of a value like "-52 dBm", "866.7 MBit/s" or "-40.".
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+fn leading_number(value: &str) -> Option<f64> {
+    let end = value
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+        .map_or(value.len(), |(i, _)| i);
+    value[..end].trim_end_matches('.').parse().ok()
+}
+
+// ─── Sampler ────────────────────────────────────────────────────────────────
+
+/// Re-reads the Wi-Fi link every `WIFI_POLL_INTERVAL` on a blocking task (the
+/// platform tools take up to a second or two), so the monitor tick only ever
+/// copies the latest reading into its frame.
+#[derive(Default)]
+pub struct WifiSampler {
+    latest: Option<(WifiLink, Instant)>,
+    task: Option<tokio::task::JoinHandle<Option<WifiLink>>>,
+    last_read: Option<Instant>,
+}
+
+impl WifiSampler {
+    /// Collect a finished reading and start the next one when due.  Nothing
+    /// is read while the busiest interface is known not to be Wi-Fi.
+    pub async fn tick(&mut self, active: Option<&ActiveInterface>) {
+        if self.task.as_ref().is_some_and(|t| t.is_finished()) {
+            if let Some(task) = self.task.take() {
+                if let Ok(link) = task.await {
+                    self.latest = link.map(|link| (link, Instant::now()));
+                }
+            }
+        }
+        if active.is_some_and(|i| i.kind != InterfaceKind::Wifi) {
+            self.reset();
+            return;
+        }
+        self.latest = self.latest.take().filter(|(_, at)| at.elapsed() < WIFI_SAMPLE_TTL);
+
+        let due = self.last_read.is_none_or(|at| at.elapsed() >= WIFI_POLL_INTERVAL);
+        if self.task.is_some() || !due {
+            return;
+        }
+        self.last_read = Some(Instant::now());
+        let hint = active.map(|i| i.name.clone());
+        self.task = Some(tokio::task::spawn_blocking(move || read_link(hint.as_deref())));
+    }
+
+    /// Latest reading, if there is a fresh one.
+    pub fn current(&self) -> Option<WifiLink> {
+        self.latest.as_ref().map(|(link, _)| link.clone())
+    }
+
+    fn reset(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.latest = None;
+        self.last_read = None;
+    }
+}
+
+// ─── Platform readers ───────────────────────────────────────────────────────
+
+/// The associated link from `iw dev <iface> link` (nl80211), falling back to
+/// the signal level in `/proc/net/wireless` when `iw` isn't installed.
+/// `hint` is the busiest interface, preferred when it is wireless.
+#[cfg(target_os = "linux")]
+fn read_link(hint: Option<&str>) -> Option<WifiLink> {
+    // Inter-| sta-|   Quality        |  ...
+    //  face | tus | link level noise |  ...
+    //  wlan0: 0000   70.  -40.  -256  ...
+    let table = std::fs::read_to_string("/proc/net/wireless").ok()?;
+    let wireless: Vec<(String, Option<i32>, Option<i32>)> = table
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(':')?;
+            let cols: Vec<&str> = rest.split_whitespace().collect();
+            let dbm = |col: Option<&&str>| {
+                col.and_then(|v| leading_number(v))
+                    .map(|v| v as i32)
+                    .filter(|v| (-110..0).contains(v))
+            };
+            Some((name.trim().to_string(), dbm(cols.get(2)), dbm(cols.get(3))))
+        })
+        .collect();
+    let (interface, rssi_dbm, noise_dbm) = wireless
+        .iter()
+        .find(|(name, _, _)| Some(name.as_str()) == hint)
+        .or_else(|| wireless.iter().find(|(_, rssi, _)| rssi.is_some()))?
+        .clone();
+
+    let mut link = WifiLink {
+        interface,
+        rssi_dbm,
+        noise_dbm,
+        ..WifiLink::default()
+    };
+    let output = std::process::Command::new("iw")
+        .args(["dev", &link.interface, "link"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success());
+    if let Some(output) = output {
+        let text = String::from_utf8_lossy(&output.stdout);
+        if text.trim_start().starts_with("Not connected") {
+            return None;
+        }
+        parse_iw_link(&text, &mut link);
+    }
+    link.channel = link.frequency_mhz.and_then(channel_of);
+    (link.rssi_dbm.is_some() || link.ssid.is_some()).then_some(link)
+}
+
+/// Fields of `iw dev <iface> link`:
+///
+/// ```text
+/// Connected to aa:bb:cc:dd:ee:ff (on wlan0)
+///     SSID: home
+///     freq: 5180.0
+///     signal: -45 dBm
+///     rx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2
+///     tx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2
+/// ```
+#[cfg(target_os = "linux")]
+fn parse_iw_link(text: &str, link: &mut WifiLink) {
+    for line in text.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("Connected to ") {
+            link.bssid = rest.split_whitespace().next().map(str::to_string);
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "SSID" if !value.is_empty() => link.ssid = Some(value.to_string()),
+            "freq" => link.frequency_mhz = leading_number(value).map(|f| f as u32),
+            "signal" => link.rssi_dbm = leading_number(value).map(|v| v as i32).or(link.rssi_dbm),
+            "rx bitrate" => link.rx_rate_mbps = leading_number(value),
+            "tx bitrate" => link.tx_rate_mbps = leading_number(value),
+            _ => {}
+        }
+    }
+}
+
+/// The first connected interface in `netsh wlan show interfaces`.  Labels
+/// are matched in English, so other display languages give no reading.
+#[cfg(target_os = "windows")]
+fn read_link(_hint: Option<&str>) -> Option<WifiLink> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let output = std::process::Command::new("netsh")
+        .args(["wlan", "show", "interfaces"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    let mut links: Vec<(WifiLink, bool)> = Vec::new();
+    let mut signal_pct: Option<f64> = None;
+    for line in text.lines() {
+        // "    Key     : value"; BSSIDs contain colons, labels don't
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if key == "Name" {
+            links.push((
+                WifiLink {
+                    interface: value.to_string(),
+                    ..WifiLink::default()
+                },
+                false,
+            ));
+            signal_pct = None;
+            continue;
+        }
+        let Some((link, connected)) = links.last_mut() else {
+            continue;
+        };
+        match key {
+            "State" => *connected = value == "connected",
+            "SSID" if !value.is_empty() => link.ssid = Some(value.to_string()),
+            "BSSID" => link.bssid = Some(value.to_string()),
+            "Channel" => link.channel = value.parse().ok(),
+            "Receive rate (Mbps)" => link.rx_rate_mbps = leading_number(value),
+            "Transmit rate (Mbps)" => link.tx_rate_mbps = leading_number(value),
+            "Rssi" => link.rssi_dbm = leading_number(value).map(|v| v as i32),
+            "Signal" => {
+                signal_pct = leading_number(value);
+                // Older builds only report quality; Windows maps 0–100% onto -100…-50 dBm
+                if link.rssi_dbm.is_none() {
+                    link.rssi_dbm = signal_pct.map(|pct| (pct / 2.0 - 100.0).round() as i32);
+                }
+            }
+            _ => {}
+        }
+    }
+    links
+        .into_iter()
+        .find(|(link, connected)| *connected && (link.ssid.is_some() || link.rssi_dbm.is_some()))
+        .map(|(link, _)| link)
+}
+
+/// The current network from `system_profiler SPAirPortDataType`:
+///
+/// ```text
+///         en0:
+///           Status: Connected
+///           Current Network Information:
+///             home:
+///               Channel: 36 (5GHz, 80MHz)
+///               Signal / Noise: -52 dBm / -94 dBm
+///               Transmit Rate: 1200
+/// ```
+///
+/// CoreWLAN itself needs an Objective-C bridge this crate doesn't carry.
+#[cfg(target_os = "macos")]
+fn read_link(_hint: Option<&str>) -> Option<WifiLink> {
+    let output = std::process::Command::new("system_profiler")
+        .arg("SPAirPortDataType")
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+    // Indent of "Interfaces:", then of the interface names under it
+    let mut interfaces_indent: Option<usize> = None;
+    let mut name_indent: Option<usize> = None;
+    let mut interface: Option<String> = None;
+    let mut current: Option<(WifiLink, usize)> = None;
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let indent = indent_of(line);
+        let trimmed = line.trim();
+        if let Some((link, header_indent)) = current.as_mut() {
+            if indent <= *header_indent {
+                break;
+            }
+            if trimmed.ends_with(':') && link.ssid.is_none() {
+                let ssid = trimmed.trim_end_matches(':');
+                // Withheld without location permission
+                if ssid != "<redacted>" {
+                    link.ssid = Some(ssid.to_string());
+                }
+                continue;
+            }
+            let Some((key, value)) = trimmed.split_once(": ") else {
+                continue;
+            };
+            match key {
+                "Channel" => link.channel = leading_number(value).map(|c| c as u32),
+                "Transmit Rate" => link.tx_rate_mbps = leading_number(value),
+                "Signal / Noise" => {
+                    let mut parts = value.split('/').map(str::trim);
+                    link.rssi_dbm = parts.next().and_then(leading_number).map(|v| v as i32);
+                    link.noise_dbm = parts.next().and_then(leading_number).map(|v| v as i32);
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if trimmed == "Interfaces:" {
+            interfaces_indent = Some(indent);
+        } else if trimmed == "Current Network Information:" {
+            if let Some(name) = &interface {
+                current = Some((
+                    WifiLink {
+                        interface: name.clone(),
+                        ..WifiLink::default()
+                    },
+                    indent,
+                ));
+            }
+        } else if trimmed.ends_with(':') && interfaces_indent.is_some_and(|i| indent > i) {
+            if name_indent.is_none_or(|n| n == indent) {
+                name_indent = Some(indent);
+                interface = Some(trimmed.trim_end_matches(':').to_string());
+            }
+        }
+    }
+    current.map(|(link, _)| link).filter(|l| l.rssi_dbm.is_some() || l.ssid.is_some())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn read_link(_hint: Option<&str>) -> Option<WifiLink> {
+    None
+}
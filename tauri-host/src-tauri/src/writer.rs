@@ -1,23 +1,169 @@
+use crate::alerts::AlertEvent;
+use crate::anonymize::{AnonymizeKey, Anonymizer};
+use crate::budgets::BudgetEvent;
 use crate::db;
+use crate::dbcrypt;
+use crate::dblock::{LockSink, Takeover};
+use crate::dns::DnsAnswer;
+use crate::error::AbyssError;
+use crate::exclusions::{ExclusionFilter, ExclusionKey, PersistenceExclusions};
+use crate::ipfamily::FamilyAttempt;
+use crate::journal::{FrameJournal, JournalEntry};
+use crate::lifecycle::{flow_id_ip, FlowEvent};
+use crate::locks::LockExt;
+use crate::mtu::MtuFinding;
+use crate::publicip::NetworkChange;
+use crate::router::RouterSample;
+use crate::routes::{DefaultRoute, RouteChange};
+use crate::settings;
+use crate::watchlist::WatchHit;
+use crate::wifi::WifiLink;
 use crate::{GeoFlow, TelemetryFrame};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rusqlite::Connection;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, SendError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
 
 // ─── Configuration ──────────────────────────────────────────────────────────
 
-/// How often (in ticks) to persist a full frame snapshot.
+/// How often (in ticks) to persist a full frame snapshot ("balanced").
 const FRAME_SAMPLE_INTERVAL: u32 = 5; // every 5 seconds
-/// How often (in ticks) to persist flow snapshots.
+/// How often (in ticks) to persist flow snapshots ("balanced").
 const FLOW_SAMPLE_INTERVAL: u32 = 10; // every 10 seconds
-/// How often (in ticks) to aggregate per-process usage.
+/// How often (in ticks) to aggregate per-process usage ("balanced").
 const PROCESS_AGG_INTERVAL: u32 = 30; // every 30 seconds
 /// How often (in ticks) to update session running totals.
 const TOTALS_UPDATE_INTERVAL: u32 = 5; // every 5 seconds
-/// How often (in ticks) to upsert destinations.
+/// How often (in ticks) to upsert destinations ("balanced").
 const DEST_UPDATE_INTERVAL: u32 = 10; // every 10 seconds
+/// Longest sampling interval a profile may use (ticks).
+const MAX_SAMPLE_INTERVAL: u32 = 3600;
+/// Frames more than this many ticks apart (pause, suspend, stalled monitor)
+/// are not integrated across — the gap starts a new baseline instead.
+const INTEGRATION_GAP_TICKS: f64 = 3.0;
+/// Floor of that gap, so a briefly stalled 1 s monitor still integrates.
+const MIN_INTEGRATION_GAP_SECS: f64 = 10.0;
+/// Size of the per-session unique-flow filter: 2^20 bits (128 KiB) keeps
+/// false positives under ~0.1% up to ~50k distinct flows per session.
+const FLOW_FILTER_BITS: usize = 1 << 20;
+/// Hash probes per flow key in the unique-flow filter.
+const FLOW_FILTER_HASHES: u64 = 4;
+/// How often `Settings::retention` is enforced.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+/// First retention pass after startup, once recording has settled.
+const RETENTION_FIRST_RUN: Duration = Duration::from_secs(5 * 60);
+/// Longest the writer blocks on its channel, so timers fire while idle.
+const IDLE_WAKE: Duration = Duration::from_secs(60);
+/// Buffered frame, flow, destination and process rows are committed in one
+/// transaction once the oldest is this old...
+const BATCH_MAX_AGE: Duration = Duration::from_secs(5);
+/// ...or once this many rows are waiting.
+const BATCH_MAX_ROWS: usize = 500;
+/// Window `WriterStats::rows_per_sec` is averaged over.
+const STATS_RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Commands the channel holds before the oldest queued frame is dropped
+/// for each new one (about four minutes of frames at the default tick).
+const CHANNEL_CAPACITY: usize = 256;
+
+// ─── Persistence profiles ───────────────────────────────────────────────────
+
+/// Named sampling interval sets, trading database size for fidelity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PersistencePreset {
+    /// Every frame, flows every 5 s.
+    Detailed,
+    /// A frame every 5 s, flows every 10 s.
+    #[default]
+    Balanced,
+    /// A frame every 30 s, flows every minute, process usage every 5 minutes.
+    Minimal,
+    /// The intervals as given.
+    Custom,
+}
+
+/// How often (in ticks) each data class is persisted.  Persisted in
+/// settings; the writer reloads it whenever a session starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PersistenceProfile {
+    pub preset: PersistencePreset,
+    pub frame_interval: u32,
+    /// Flow snapshots hang off frame rows, so this is a multiple of
+    /// `frame_interval`.
+    pub flow_interval: u32,
+    pub process_interval: u32,
+    pub destination_interval: u32,
+}
+
+impl Default for PersistenceProfile {
+    fn default() -> Self {
+        Self::preset(PersistencePreset::Balanced)
+    }
+}
+
+impl PersistenceProfile {
+    /// The intervals of `preset`; `Custom` starts from "balanced".
+    pub fn preset(preset: PersistencePreset) -> Self {
+        let (frame_interval, flow_interval, process_interval, destination_interval) = match preset {
+            PersistencePreset::Detailed => (1, 5, 10, 5),
+            PersistencePreset::Balanced | PersistencePreset::Custom => (
+                FRAME_SAMPLE_INTERVAL,
+                FLOW_SAMPLE_INTERVAL,
+                PROCESS_AGG_INTERVAL,
+                DEST_UPDATE_INTERVAL,
+            ),
+            PersistencePreset::Minimal => (30, 60, 300, 60),
+        };
+        Self {
+            preset,
+            frame_interval,
+            flow_interval,
+            process_interval,
+            destination_interval,
+        }
+    }
+
+    /// `self` with a named preset's intervals filled in; custom profiles
+    /// are kept as given.
+    pub fn resolved(self) -> Self {
+        match self.preset {
+            PersistencePreset::Custom => self,
+            preset => Self::preset(preset),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AbyssError> {
+        for (name, value) in [
+            ("frameInterval", self.frame_interval),
+            ("flowInterval", self.flow_interval),
+            ("processInterval", self.process_interval),
+            ("destinationInterval", self.destination_interval),
+        ] {
+            if !(1..=MAX_SAMPLE_INTERVAL).contains(&value) {
+                return Err(AbyssError::InvalidInput(format!(
+                    "persistence.{name} must be between 1 and {MAX_SAMPLE_INTERVAL}"
+                )));
+            }
+        }
+        if !self.flow_interval.is_multiple_of(self.frame_interval) {
+            return Err(AbyssError::InvalidInput(
+                "persistence.flowInterval must be a multiple of frameInterval".into(),
+            ));
+        }
+        Ok(())
+    }
+}
 
 // ─── Write commands ─────────────────────────────────────────────────────────
 
@@ -43,41 +189,689 @@ pub enum WriteCommand {
         notes: Option<String>,
         tags: Option<String>,
     },
+    /// Persist an alert fired by the rules engine.
+    RecordAlert(Box<AlertEvent>),
+    /// Persist new or refreshed IP → domain resolutions from the DNS observer.
+    RecordDns(Vec<DnsAnswer>),
+    /// Count IPv4/IPv6 connection outcomes against the current session.
+    RecordFamilyAttempts(Vec<FamilyAttempt>),
+    /// Persist flow open/close events against the current session.
+    RecordFlowEvents(Vec<FlowEvent>),
+    /// Persist a reduced or black-holed path MTU against the current session.
+    RecordMtuFinding(MtuFinding),
+    /// Persist traffic the gateway counted against the current session.
+    RecordRouterSample(RouterSample),
+    /// The default routes changed (or were read for the first time).
+    RecordRouteChange(RouteChange),
+    /// The public address changed (or was read for the first time).
+    RecordNetworkChange(NetworkChange),
+    /// Count flows to watched countries/ASNs against the current session.
+    RecordWatchHits(Vec<WatchHit>),
+    /// Toggle at-rest redaction of IPs and process names, and how IPs are
+    /// anonymized.
+    SetRedaction { enabled: bool, anonymizer: Anonymizer },
+    /// Replace the processes and address ranges whose flows are never
+    /// persisted.
+    SetExclusions(ExclusionFilter),
+    /// Toggle monitor-only mode: traffic data (frames, flows, DNS, alerts,
+    /// findings) is dropped instead of persisted.
+    SetMonitorOnly { enabled: bool },
+    /// Close the database connection and stop writing until `Resume`/`Reopen`.
+    /// Traffic arriving meanwhile is dropped; session commands are deferred.
+    Pause { ack: ControlAck },
+    /// Reopen the database and replay deferred commands.
+    Resume { ack: ControlAck },
+    /// Close and reopen the database at `path` (e.g. after a restore),
+    /// reloading its settings and keys before replaying deferred commands.
+    Reopen { path: PathBuf, ack: ControlAck },
     /// Shut down the writer thread.
     Shutdown,
 }
 
-/// Creates the mpsc channel pair for sending write commands.
-pub fn create_channel() -> (mpsc::Sender<WriteCommand>, mpsc::Receiver<WriteCommand>) {
-    mpsc::channel()
+impl WriteCommand {
+    /// Whether the command stores observed traffic (dropped in monitor-only
+    /// mode) rather than managing sessions or the writer.  Route and public
+    /// address changes are kept: they only persist inside a session, and the
+    /// writer needs the current ones for the next.
+    fn records_traffic(&self) -> bool {
+        matches!(
+            self,
+            WriteCommand::Frame(_)
+                | WriteCommand::RecordAlert(_)
+                | WriteCommand::RecordDns(_)
+                | WriteCommand::RecordFamilyAttempts(_)
+                | WriteCommand::RecordFlowEvents(_)
+                | WriteCommand::RecordMtuFinding(_)
+                | WriteCommand::RecordRouterSample(_)
+                | WriteCommand::RecordWatchHits(_)
+        )
+    }
+}
+
+/// Acknowledgement for writer control commands, sent once the writer has
+/// actually closed or reopened its connection.
+pub type ControlAck = oneshot::Sender<Result<(), AbyssError>>;
+
+/// Callback invoked with every persistence error (the app emits these as
+/// `writer-error` events).
+pub type ErrorSink = Box<dyn Fn(AbyssError) + Send>;
+
+/// Callback invoked with the id of each session the writer finalizes.
+pub type SessionSink = Box<dyn Fn(String) + Send>;
+
+/// Callback invoked after a retention pass deleted sessions (the app emits
+/// these as `retention-pruned` events).
+pub type RetentionSink = Box<dyn Fn(db::RetentionReport) + Send>;
+
+/// Callback invoked when recorded usage crosses a data budget threshold
+/// (the app emits these as `budget-threshold` events).
+pub type BudgetSink = Box<dyn Fn(BudgetEvent) + Send>;
+
+/// Callback invoked with the writer's counters after every batch flush.
+pub type StatsSink = Box<dyn Fn(WriterStats) + Send>;
+
+/// Source of the wall-clock timestamps stamped on persisted rows (frames,
+/// session start/end).  Tests substitute a clock they can step.
+pub type Clock = Box<dyn Fn() -> DateTime<Utc> + Send>;
+
+/// Writer throughput and backlog; the result of `cmd_get_writer_stats`.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriterStats {
+    /// Commands waiting in the channel when the writer last drained it.
+    pub queue_depth: usize,
+    /// Rows buffered for the next flush.
+    pub pending_rows: usize,
+    pub flushes: u64,
+    pub rows_flushed: u64,
+    /// Time spent in the last flush's transaction.
+    pub last_flush_ms: f64,
+    pub avg_flush_ms: f64,
+    pub max_flush_ms: f64,
+    /// Rows flushed per second over the last minute.
+    pub rows_per_sec: f64,
+    /// Frames dropped because the channel was full (since startup).
+    pub dropped_frames: u64,
+    /// Other traffic commands (flow events, DNS answers, alerts, ...)
+    /// dropped because the channel was full (since startup).
+    pub dropped_records: u64,
+}
+
+// ─── Channel ────────────────────────────────────────────────────────────────
+
+/// Creates the bounded channel pair for sending write commands.
+pub fn create_channel() -> (WriterSender, WriterReceiver) {
+    channel_with_capacity(CHANNEL_CAPACITY)
+}
+
+fn channel_with_capacity(capacity: usize) -> (WriterSender, WriterReceiver) {
+    let shared = Arc::new(Channel {
+        queue: Mutex::new(Queue {
+            items: VecDeque::new(),
+            senders: 1,
+            receiver_alive: true,
+        }),
+        ready: Condvar::new(),
+        capacity,
+        dropped_frames: AtomicU64::new(0),
+        dropped_records: AtomicU64::new(0),
+    });
+    (WriterSender(shared.clone()), WriterReceiver(shared))
+}
+
+/// A queue that never blocks the sender: while the writer is stuck (e.g. in
+/// a long VACUUM) a full channel drops its oldest queued traffic command
+/// (`WriteCommand::records_traffic`: a frame or a batch of records) for each
+/// new command.  Session and control commands are always kept, so only they
+/// can take the queue past its capacity.
+struct Channel {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    capacity: usize,
+    dropped_frames: AtomicU64,
+    dropped_records: AtomicU64,
+}
+
+struct Queue {
+    items: VecDeque<WriteCommand>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+impl Channel {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock_or_recover("writer_channel")
+    }
+}
+
+/// Sending half of the writer channel; cloned into every producer.
+pub struct WriterSender(Arc<Channel>);
+
+impl WriterSender {
+    /// Queue `cmd`.  Fails only once the writer thread has exited.
+    // Same signature as `mpsc::Sender::send`, so callers hand the command back
+    // unchanged.
+    #[allow(clippy::result_large_err)]
+    pub fn send(&self, cmd: WriteCommand) -> Result<(), SendError<WriteCommand>> {
+        let mut queue = self.0.lock();
+        if !queue.receiver_alive {
+            return Err(SendError(cmd));
+        }
+        if queue.items.len() >= self.0.capacity {
+            if let Some(oldest) = queue.items.iter().position(WriteCommand::records_traffic) {
+                let counter = match queue.items.remove(oldest) {
+                    Some(WriteCommand::Frame(_)) => &self.0.dropped_frames,
+                    _ => &self.0.dropped_records,
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        queue.items.push_back(cmd);
+        drop(queue);
+        self.0.ready.notify_one();
+        Ok(())
+    }
+}
+
+impl Clone for WriterSender {
+    fn clone(&self) -> Self {
+        self.0.lock().senders += 1;
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for WriterSender {
+    fn drop(&mut self) {
+        self.0.lock().senders -= 1;
+        self.0.ready.notify_one();
+    }
+}
+
+/// Receiving half of the writer channel, owned by the writer thread.
+pub struct WriterReceiver(Arc<Channel>);
+
+impl WriterReceiver {
+    /// Wait up to `timeout` for a command; `Disconnected` once every sender
+    /// is gone and the queue is empty.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<WriteCommand, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.0.lock();
+        loop {
+            if let Some(cmd) = queue.items.pop_front() {
+                return Ok(cmd);
+            }
+            if queue.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            queue = self
+                .0
+                .ready
+                .wait_timeout(queue, remaining)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+
+    /// Take every command queued right now.
+    pub fn try_iter(&self) -> impl Iterator<Item = WriteCommand> {
+        std::mem::take(&mut self.0.lock().items).into_iter()
+    }
+
+    /// Frames dropped for lack of room since the last call.
+    pub fn take_dropped_frames(&self) -> u64 {
+        self.0.dropped_frames.swap(0, Ordering::Relaxed)
+    }
+
+    /// Other traffic commands dropped for lack of room since the last call.
+    pub fn take_dropped_records(&self) -> u64 {
+        self.0.dropped_records.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl Drop for WriterReceiver {
+    fn drop(&mut self) {
+        let mut queue = self.0.lock();
+        queue.receiver_alive = false;
+        queue.items.clear();
+    }
 }
 
 // ─── Writer thread ──────────────────────────────────────────────────────────
 
 /// Runs the blocking writer loop on a dedicated thread.
 /// Receives `WriteCommand`s and batches writes to SQLite.
-pub fn writer_thread(rx: mpsc::Receiver<WriteCommand>, db_path: PathBuf) {
-    let conn = match db::open_database(&db_path) {
-        Ok(c) => c,
+#[allow(clippy::too_many_arguments)]
+pub fn writer_thread(
+    rx: WriterReceiver,
+    db_path: PathBuf,
+    on_error: ErrorSink,
+    on_session_ended: SessionSink,
+    on_pruned: RetentionSink,
+    on_budget: BudgetSink,
+    on_lock: LockSink,
+    on_stats: StatsSink,
+) {
+    let mut state = WriterState::new(on_error, on_session_ended, on_pruned, on_budget, on_stats, Box::new(Utc::now));
+    state.journal = Some(FrameJournal::open(&db_path));
+    state.exclusion_key = load_exclusion_key(&db_path);
+    state.anonymize_key = load_anonymize_key(&db_path);
+    // Until another instance (e.g. the one an update replaced) lets go of
+    // the database the writer behaves as if paused
+    let mut takeover = Takeover::new(&db_path, on_lock);
+    let mut conn = None;
+    let mut started = false;
+    let mut db_path = db_path;
+    // Session commands received while paused, replayed once the database reopens
+    let mut deferred = Deferred::default();
+    let mut next_retention = Instant::now() + RETENTION_FIRST_RUN;
+    // Commands drained from the channel ahead of processing, so the
+    // backlog can be measured (`WriterStats::queue_depth`)
+    let mut backlog: VecDeque<WriteCommand> = VecDeque::new();
+
+    loop {
+        if !started && takeover.try_acquire() {
+            match db::PreparedWriter::open(&db_path) {
+                Ok(c) => {
+                    state.start(&c);
+                    started = true;
+                    if !deferred.commands.is_empty() {
+                        println!(
+                            "[Abyss][writer] Database free — replaying {} queued command(s), {} frame(s) and {} record batch(es) dropped while waiting",
+                            deferred.commands.len(),
+                            deferred.dropped_frames,
+                            deferred.dropped_records
+                        );
+                    }
+                    state.replay(&c, &mut deferred);
+                    conn = Some(c);
+                }
+                Err(_) if dbcrypt::is_locked(&db_path) => {
+                    takeover.database_busy("Database is encrypted — waiting for its passphrase".into())
+                }
+                Err(e) => takeover.database_busy(AbyssError::from(e).context("Failed to open database").to_string()),
+            }
+        } else if started {
+            takeover.try_acquire();
+        }
+        let cmd = match backlog.pop_front() {
+            Some(cmd) => Some(cmd),
+            None => match rx.recv_timeout(state.wake_interval(takeover.wake_interval(IDLE_WAKE))) {
+                Ok(cmd) => {
+                    backlog.extend(rx.try_iter());
+                    state.stats.queue_depth = backlog.len();
+                    match rx.take_dropped_frames() {
+                        0 => {}
+                        n => state.frames_dropped(n),
+                    }
+                    match rx.take_dropped_records() {
+                        0 => {}
+                        n => state.records_dropped(n),
+                    }
+                    Some(cmd)
+                }
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Some(c) = &conn {
+                        state.flush(c);
+                    }
+                    break;
+                }
+            },
+        };
+        if let Some(c) = &conn {
+            state.flush_if_due(c);
+        }
+        // Skipped while paused; runs on the first wake after resuming
+        if let (Some(c), true) = (&conn, Instant::now() >= next_retention) {
+            state.enforce_retention(c);
+            next_retention = Instant::now() + RETENTION_INTERVAL;
+        }
+        let Some(cmd) = cmd else {
+            continue;
+        };
+        match cmd {
+            WriteCommand::Pause { ack } => {
+                if let Some(c) = &conn {
+                    state.flush(c);
+                }
+                if conn.take().is_some() {
+                    println!("[Abyss][writer] Paused — database closed");
+                }
+                let _ = ack.send(Ok(()));
+            }
+            WriteCommand::Resume { ack } => {
+                let result = match conn {
+                    Some(_) => Ok(()),
+                    None if !started => Err(waiting_for_lock()),
+                    None => state.reopen(&mut conn, &db_path, &mut deferred),
+                };
+                let _ = ack.send(result);
+            }
+            WriteCommand::Reopen { path, ack } => {
+                if let Some(c) = &conn {
+                    state.flush(c);
+                }
+                conn = None;
+                if let Some(journal) = state.journal.take() {
+                    journal.remove();
+                }
+                state.journal = Some(FrameJournal::open(&path));
+                state.exclusion_key = load_exclusion_key(&path);
+                state.anonymize_key = load_anonymize_key(&path);
+                takeover.switch(&path);
+                db_path = path;
+                let result = if takeover.try_acquire() {
+                    started = true;
+                    state.reopen(&mut conn, &db_path, &mut deferred)
+                } else {
+                    started = false;
+                    Err(waiting_for_lock())
+                };
+                let _ = ack.send(result);
+            }
+            WriteCommand::SetRedaction { enabled, anonymizer } => {
+                state.redact = enabled;
+                state.anonymizer = anonymizer;
+                println!(
+                    "[Abyss][writer] At-rest redaction {}",
+                    if enabled { "enabled" } else { "disabled" }
+                );
+            }
+            WriteCommand::SetExclusions(filter) => {
+                state.exclusions = filter;
+                // Saving the first list creates the key
+                if state.exclusion_key.is_none() {
+                    state.exclusion_key = load_exclusion_key(&db_path);
+                }
+            }
+            WriteCommand::SetMonitorOnly { enabled } => {
+                state.monitor_only = enabled;
+                println!(
+                    "[Abyss][writer] Monitor-only mode {}",
+                    if enabled { "enabled — nothing is persisted" } else { "disabled" }
+                );
+            }
+            WriteCommand::Shutdown => {
+                // Finalize any open session before exiting
+                if let Some(c) = &conn {
+                    state.flush(c);
+                }
+                match (&conn, state.current_session_id.clone()) {
+                    (Some(c), Some(sid)) => {
+                        state.flush_recording_stats(c, &sid);
+                        if let Err(e) = db::finalize_session(c, &sid, &state.now()) {
+                            state.report("Failed to finalize session on shutdown", e);
+                        } else {
+                            state.clear_journal();
+                            println!("[Abyss][writer] Finalized session {sid} on shutdown");
+                        }
+                    }
+                    (None, Some(sid)) => {
+                        println!("[Abyss][writer] Paused at shutdown — session {sid} left for crash recovery");
+                    }
+                    _ => {}
+                }
+                println!("[Abyss][writer] Shut down cleanly");
+                break;
+            }
+            cmd if state.monitor_only && cmd.records_traffic() => {}
+            other => match &conn {
+                Some(c) => state.apply(c, other),
+                None => deferred.hold(other),
+            },
+        }
+    }
+}
+
+/// Longest gap between frames integrated across when ticking every
+/// `tick_ms` (0 when unknown).
+pub fn integration_gap_secs(tick_ms: u64) -> f64 {
+    (tick_ms as f64 / 1000.0 * INTEGRATION_GAP_TICKS).max(MIN_INTEGRATION_GAP_SECS)
+}
+
+/// `integration_gap_secs` at the rate `frame` was polled at.
+fn frame_gap_secs(frame: &TelemetryFrame) -> f64 {
+    integration_gap_secs(frame.rate.map_or(0, |rate| rate.tick_ms))
+}
+
+/// What arrived while the database was closed.  Only session commands are
+/// held for replay; traffic is counted and dropped, and of the route and
+/// public address changes only the latest of each is kept, so the backlog
+/// stays small however long the database stays unavailable.
+#[derive(Default)]
+struct Deferred {
+    commands: Vec<WriteCommand>,
+    dropped_frames: u64,
+    dropped_records: u64,
+}
+
+impl Deferred {
+    fn hold(&mut self, cmd: WriteCommand) {
+        match cmd {
+            WriteCommand::Frame(_) => self.dropped_frames += 1,
+            cmd if cmd.records_traffic() => self.dropped_records += 1,
+            cmd @ (WriteCommand::RecordRouteChange(_) | WriteCommand::RecordNetworkChange(_)) => {
+                let kind = std::mem::discriminant(&cmd);
+                self.commands.retain(|c| std::mem::discriminant(c) != kind);
+                self.commands.push(cmd);
+            }
+            session_cmd => self.commands.push(session_cmd),
+        }
+    }
+}
+
+fn waiting_for_lock() -> AbyssError {
+    AbyssError::DatabaseLocked("Waiting for another Abyss instance to release the database".into())
+}
+
+fn load_exclusion_key(db_path: &Path) -> Option<ExclusionKey> {
+    match ExclusionKey::load(db_path) {
+        Ok(key) => key,
         Err(e) => {
-            eprintln!("[Abyss][writer] Failed to open database: {e}");
-            return;
+            eprintln!("[Abyss][writer] Persistence exclusions unavailable: {e}");
+            None
+        }
+    }
+}
+
+fn load_anonymize_key(db_path: &Path) -> Option<AnonymizeKey> {
+    match AnonymizeKey::load(db_path) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("[Abyss][writer] Anonymization key unavailable: {e}");
+            None
+        }
+    }
+}
+
+// ─── Internal state ─────────────────────────────────────────────────────────
+
+struct WriterState {
+    current_session_id: Option<String>,
+    tick_counter: u32,
+    /// Track which destination IPs we've already seen in this session
+    /// to decide when to upsert (dedup within the destination-update interval).
+    seen_dest_ips: HashMap<String, bool>,
+    /// Previous frame's `(t, upload_bps, download_bps)` for integrating totals.
+    last_rate_sample: Option<(f64, f64, f64)>,
+    /// Bytes integrated since the last session totals update.
+    pending_bytes_up: f64,
+    pending_bytes_down: f64,
+    /// Flow keys already seen this session, for `sessions.total_flows`.
+    seen_flows: FlowFilter,
+    /// Flows first seen since the last session totals update.
+    pending_new_flows: u32,
+    /// Previous frame's `t` for integrating per-process rates.
+    last_process_t: Option<f64>,
+    /// Per-process (up, down) bytes integrated since the last `process_usage` row.
+    pending_process_bytes: HashMap<String, (f64, f64)>,
+    /// App group each process name was last seen under, for `process_usage`.
+    process_groups: HashMap<String, String>,
+    /// Anonymize IPs and drop process names before persisting flows/destinations.
+    redact: bool,
+    anonymizer: Anonymizer,
+    /// Hashing key for the stored `anonymize_mode` until `SetRedaction`
+    /// brings one.  `None` in tests.
+    anonymize_key: Option<AnonymizeKey>,
+    /// Flows (and process rows) dropped before insert.
+    exclusions: ExclusionFilter,
+    /// Decrypts `Settings::persistence_exclusions`.  `None` in tests.
+    exclusion_key: Option<ExclusionKey>,
+    /// Drop traffic data instead of persisting it.
+    monitor_only: bool,
+    /// Sampling intervals, reloaded from settings when a session starts.
+    profile: PersistenceProfile,
+    /// Latest default routes, recorded again whenever a session starts.
+    default_routes: Vec<DefaultRoute>,
+    /// Latest public address, recorded again whenever a session starts.
+    public_ip: Option<NetworkChange>,
+    /// `recording_stats` deltas since the last flush.
+    pending_stats: db::RecordingStats,
+    /// Errors reported since the last flush (`report` only has `&self`).
+    pending_errors: Cell<i64>,
+    /// Sampled ticks waiting to be written in the next batch.
+    pending: Vec<PendingTick>,
+    pending_rows: usize,
+    /// When the oldest pending tick was buffered.
+    batch_started: Option<Instant>,
+    /// Frames received since the last committed batch, replayed by crash
+    /// recovery.  `None` in tests.
+    journal: Option<FrameJournal>,
+    stats: WriterStats,
+    /// `(flushed at, rows)` within `STATS_RATE_WINDOW`, for `rows_per_sec`.
+    recent_flushes: VecDeque<(Instant, usize)>,
+    created: Instant,
+    on_error: ErrorSink,
+    on_session_ended: SessionSink,
+    on_pruned: RetentionSink,
+    on_budget: BudgetSink,
+    on_stats: StatsSink,
+    clock: Clock,
+}
+
+/// The writes one frame is due for, buffered until the batch is flushed.
+struct PendingTick {
+    session_id: String,
+    frame: Box<TelemetryFrame>,
+    /// Writer clock when the frame arrived.
+    timestamp: String,
+    write_frame: bool,
+    /// Flow snapshots, attached to this tick's frame row.
+    write_flows: bool,
+    /// Bytes and unique flows since the previous totals update.
+    totals: Option<(f64, f64, u32)>,
+    destinations: bool,
+    /// Per-process bytes integrated since the previous `process_usage` rows.
+    process_usage: Option<HashMap<String, (f64, f64)>>,
+}
+
+impl PendingTick {
+    /// Rows the tick will write (frame, flows, destinations, process usage).
+    fn rows(&self) -> usize {
+        let flows = self.frame.flows.len();
+        self.write_frame as usize
+            + if self.write_flows { flows } else { 0 }
+            + if self.destinations { flows } else { 0 }
+            + self.process_usage.as_ref().map_or(0, |bytes| bytes.len().max(1))
+            + self.totals.is_some() as usize
+    }
+}
+
+impl WriterState {
+    fn new(
+        on_error: ErrorSink,
+        on_session_ended: SessionSink,
+        on_pruned: RetentionSink,
+        on_budget: BudgetSink,
+        on_stats: StatsSink,
+        clock: Clock,
+    ) -> Self {
+        Self {
+            current_session_id: None,
+            tick_counter: 0,
+            seen_dest_ips: HashMap::new(),
+            last_rate_sample: None,
+            pending_bytes_up: 0.0,
+            pending_bytes_down: 0.0,
+            seen_flows: FlowFilter::new(),
+            pending_new_flows: 0,
+            last_process_t: None,
+            pending_process_bytes: HashMap::new(),
+            process_groups: HashMap::new(),
+            redact: false,
+            anonymizer: Anonymizer::default(),
+            anonymize_key: None,
+            exclusions: ExclusionFilter::default(),
+            exclusion_key: None,
+            monitor_only: false,
+            profile: PersistenceProfile::default(),
+            default_routes: Vec::new(),
+            public_ip: None,
+            pending_stats: db::RecordingStats::default(),
+            pending_errors: Cell::new(0),
+            pending: Vec::new(),
+            pending_rows: 0,
+            batch_started: None,
+            journal: None,
+            stats: WriterStats::default(),
+            recent_flushes: VecDeque::new(),
+            created: Instant::now(),
+            on_error,
+            on_session_ended,
+            on_pruned,
+            on_budget,
+            on_stats,
+            clock,
         }
-    };
+    }
+
+    /// Finalize sessions a previous run left open, replaying the frames it
+    /// journaled, and pick up the stored redaction settings and exclusions.
+    fn start(&mut self, conn: &Connection) {
+        let journal = self.journal.as_ref().map(FrameJournal::read).unwrap_or_default();
+        match db::recover_crashed_sessions(conn, &journal) {
+            Ok(0) => {}
+            Ok(n) => println!(
+                "[Abyss][writer] Recovered {n} crashed session(s), {} journaled frame(s) replayed",
+                journal.len()
+            ),
+            Err(e) => self.report("Crash recovery failed", e),
+        }
+        self.clear_journal();
+        self.load_settings(conn);
+    }
 
-    // Recover any crashed sessions from previous runs
-    match db::recover_crashed_sessions(&conn) {
-        Ok(0) => {}
-        Ok(n) => println!("[Abyss][writer] Recovered {n} crashed session(s)"),
-        Err(e) => eprintln!("[Abyss][writer] Crash recovery failed: {e}"),
+    /// Pick up the database's redaction, anonymization and exclusion settings.
+    fn load_settings(&mut self, conn: &Connection) {
+        let stored = settings::load(conn);
+        self.redact = stored.redact_at_rest;
+        self.anonymizer = Anonymizer::new(stored.anonymize_mode, self.anonymize_key.clone());
+        match PersistenceExclusions::load(&stored, self.exclusion_key.as_ref()) {
+            Ok(exclusions) => self.exclusions = ExclusionFilter::new(&exclusions),
+            Err(e) => self.report("Failed to load persistence exclusions", e),
+        }
+    }
+
+    fn clear_journal(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
     }
 
-    let mut state = WriterState::new();
+    /// Current time from the writer's clock, as stored (RFC 3339).
+    fn now(&self) -> String {
+        (self.clock)().to_rfc3339()
+    }
 
-    for cmd in rx.iter() {
+    /// Handle a data command against an open connection.
+    fn apply(&mut self, conn: &Connection, cmd: WriteCommand) {
         match cmd {
             WriteCommand::Frame(frame) => {
-                state.handle_frame(&conn, &frame);
+                self.handle_frame(frame);
             }
             WriteCommand::StartSession {
                 id,
@@ -87,10 +881,12 @@ pub fn writer_thread(rx: mpsc::Receiver<WriteCommand>, db_path: PathBuf) {
                 local_lat,
                 local_lng,
             } => {
-                state.handle_start_session(&conn, &id, &name, &local_city, &local_country, local_lat, local_lng);
+                self.flush(conn);
+                self.handle_start_session(conn, &id, &name, &local_city, &local_country, local_lat, local_lng);
             }
             WriteCommand::EndSession { id } => {
-                state.handle_end_session(&conn, &id);
+                self.flush(conn);
+                self.handle_end_session(conn, &id);
             }
             WriteCommand::UpdateMeta {
                 id,
@@ -99,51 +895,246 @@ pub fn writer_thread(rx: mpsc::Receiver<WriteCommand>, db_path: PathBuf) {
                 tags,
             } => {
                 if let Err(e) = db::update_session_meta(
-                    &conn,
+                    conn,
                     &id,
                     name.as_deref(),
                     notes.as_deref(),
                     tags.as_deref(),
                 ) {
-                    eprintln!("[Abyss][writer] Failed to update session meta: {e}");
+                    self.report("Failed to update session meta", e);
                 }
             }
-            WriteCommand::Shutdown => {
-                // Finalize any open session before exiting
-                if let Some(sid) = &state.current_session_id {
-                    let now = Utc::now().to_rfc3339();
-                    if let Err(e) = db::finalize_session(&conn, sid, &now) {
-                        eprintln!("[Abyss][writer] Failed to finalize session on shutdown: {e}");
-                    } else {
-                        println!("[Abyss][writer] Finalized session {sid} on shutdown");
+            WriteCommand::RecordAlert(event) => {
+                self.record_alert(conn, *event);
+            }
+            WriteCommand::RecordDns(mut answers) => {
+                answers.retain(|a| !self.exclusions.excludes_ip(&a.ip));
+                self.record_dns(conn, &answers);
+            }
+            WriteCommand::RecordFamilyAttempts(mut attempts) => {
+                attempts.retain(|a| !self.exclusions.excludes_ip(&a.remote_ip));
+                if let Some(session_id) = &self.current_session_id {
+                    if self.redact {
+                        for attempt in &mut attempts {
+                            attempt.remote_ip = self.anonymizer.ip(&attempt.remote_ip);
+                            attempt.domain = None;
+                        }
+                    }
+                    if let Err(e) = db::record_family_attempts(conn, session_id, &attempts) {
+                        self.report("Failed to record IP family attempts", e);
                     }
                 }
-                println!("[Abyss][writer] Shut down cleanly");
-                break;
             }
+            WriteCommand::RecordFlowEvents(events) => {
+                self.record_flow_events(conn, events);
+            }
+            WriteCommand::RecordMtuFinding(mut finding) => {
+                if self.exclusions.excludes_ip(&finding.ip) {
+                    return;
+                }
+                if let Some(session_id) = &self.current_session_id {
+                    if self.redact {
+                        finding.ip = self.anonymizer.ip(&finding.ip);
+                        finding.domain = None;
+                    }
+                    if let Err(e) = db::record_mtu_finding(conn, session_id, &finding) {
+                        self.report("Failed to record MTU finding", e);
+                    }
+                }
+            }
+            WriteCommand::RecordRouteChange(change) => {
+                self.record_route_change(conn, change);
+            }
+            WriteCommand::RecordNetworkChange(change) => {
+                let kind = if change.initial { "start" } else { "change" };
+                self.insert_network_change(conn, &change.timestamp, kind, &change);
+                self.public_ip = Some(change);
+            }
+            WriteCommand::RecordRouterSample(sample) => {
+                if let Some(session_id) = &self.current_session_id {
+                    if let Err(e) = db::insert_router_sample(conn, session_id, &sample) {
+                        self.report("Failed to record router sample", e);
+                    }
+                }
+            }
+            WriteCommand::RecordWatchHits(hits) => {
+                if let Some(session_id) = &self.current_session_id {
+                    if let Err(e) = db::record_watch_hits(conn, session_id, &hits) {
+                        self.report("Failed to record watch hits", e);
+                    }
+                }
+            }
+            // Control commands are handled by the writer loop itself
+            _ => {}
         }
     }
-}
 
-// ─── Internal state ─────────────────────────────────────────────────────────
+    /// Open `path` into `conn`, reload its settings and replay commands
+    /// deferred while paused.  If the active session doesn't exist in the reopened database (e.g.
+    /// after a restore) it is dropped rather than failing every subsequent
+    /// insert.
+    fn reopen(
+        &mut self,
+        conn: &mut Option<db::PreparedWriter>,
+        path: &Path,
+        deferred: &mut Deferred,
+    ) -> Result<(), AbyssError> {
+        let c = db::PreparedWriter::open(path).map_err(|e| AbyssError::from(e).context("Failed to reopen database"))?;
+        let (dropped_frames, dropped_records) = (deferred.dropped_frames, deferred.dropped_records);
+        self.load_settings(&c);
+        self.replay(&c, deferred);
+        if let Some(sid) = self.current_session_id.clone() {
+            if db::get_session(&c, &sid)?.is_none() {
+                println!("[Abyss][writer] Session {sid} not in reopened database — no longer recording");
+                self.current_session_id = None;
+                self.reset_session_tracking();
+            }
+        }
+        println!(
+            "[Abyss][writer] Resumed on {} ({dropped_frames} frame(s) and {dropped_records} record batch(es) dropped while paused)",
+            path.display()
+        );
+        *conn = Some(c);
+        Ok(())
+    }
 
-struct WriterState {
-    current_session_id: Option<String>,
-    tick_counter: u32,
-    /// Track which destination IPs we've already seen in this session
-    /// to decide when to upsert (dedup within the destination-update interval).
-    seen_dest_ips: HashMap<String, bool>,
-}
+    /// Apply the commands deferred while the database was closed and count
+    /// the frames dropped meanwhile against the session they belonged to.
+    fn replay(&mut self, conn: &Connection, deferred: &mut Deferred) {
+        for cmd in deferred.commands.drain(..) {
+            self.apply(conn, cmd);
+        }
+        if self.current_session_id.is_some() {
+            self.pending_stats.dropped_frames += deferred.dropped_frames as i64;
+        }
+        deferred.dropped_frames = 0;
+        deferred.dropped_records = 0;
+    }
 
-impl WriterState {
-    fn new() -> Self {
-        Self {
-            current_session_id: None,
-            tick_counter: 0,
-            seen_dest_ips: HashMap::new(),
+    /// Clear per-session sampling state (tick phase, seen IPs, integration).
+    fn reset_session_tracking(&mut self) {
+        self.tick_counter = 0;
+        self.seen_dest_ips.clear();
+        self.last_rate_sample = None;
+        self.pending_bytes_up = 0.0;
+        self.pending_bytes_down = 0.0;
+        self.seen_flows.clear();
+        self.pending_new_flows = 0;
+        self.last_process_t = None;
+        self.pending_process_bytes.clear();
+        self.pending_stats = db::RecordingStats::default();
+        self.pending_errors.set(0);
+    }
+
+    /// Add the counters gathered since the last flush to `recording_stats`.
+    fn flush_recording_stats(&mut self, conn: &Connection, session_id: &str) {
+        let mut delta = std::mem::take(&mut self.pending_stats);
+        delta.writer_errors += self.pending_errors.replace(0);
+        if delta.is_empty() {
+            return;
+        }
+        if let Err(e) = db::add_recording_stats(conn, session_id, &delta) {
+            self.report("add_recording_stats failed", e);
+        }
+    }
+
+    /// Integrate upload/download bytes between the previous frame and this
+    /// one with the trapezoidal rule over `t`, so bursts between samples are
+    /// counted by their actual duration rather than a fixed interval.
+    fn integrate_bytes(&mut self, frame: &TelemetryFrame) {
+        let (t, up, down) = (frame.t, frame.net.upload_bps, frame.net.download_bps);
+        if let Some((prev_t, prev_up, prev_down)) = self.last_rate_sample {
+            let dt = t - prev_t;
+            if dt > 0.0 && dt <= frame_gap_secs(frame) {
+                self.pending_bytes_up += (prev_up + up) / 2.0 * dt / 8.0;
+                self.pending_bytes_down += (prev_down + down) / 2.0 * dt / 8.0;
+            }
+        }
+        self.last_rate_sample = Some((t, up, down));
+    }
+
+    /// Remember which app each process name was last seen under.
+    fn track_app_groups(&mut self, frame: &TelemetryFrame) {
+        let rates = frame.processes.iter().map(|r| (&r.process, &r.app_group));
+        let flows = frame.flows.iter().filter_map(|f| Some((f.process.as_ref()?, &f.app_group)));
+        for (process, group) in rates.chain(flows) {
+            if let Some(group) = group {
+                if self.process_groups.get(process) != Some(group) {
+                    self.process_groups.insert(process.clone(), group.clone());
+                }
+            }
+        }
+    }
+
+    /// Integrate each process's attributed rate (`TelemetryFrame::processes`)
+    /// over the time since the previous frame.
+    fn integrate_process_bytes(&mut self, frame: &TelemetryFrame) {
+        if let Some(prev_t) = self.last_process_t {
+            let dt = frame.t - prev_t;
+            if dt > 0.0 && dt <= frame_gap_secs(frame) {
+                for rate in &frame.processes {
+                    let (up, down) = self.pending_process_bytes.entry(rate.process.clone()).or_default();
+                    *up += rate.tx_bps / 8.0 * dt;
+                    *down += rate.rx_bps / 8.0 * dt;
+                }
+            }
+        }
+        self.last_process_t = Some(frame.t);
+    }
+
+    /// Add recorded bytes to the matching data budgets and report any
+    /// threshold they cross.
+    fn count_budget_usage(&self, conn: &Connection, process: Option<&str>, bytes: f64) {
+        match db::add_budget_usage(conn, process, bytes, (self.clock)()) {
+            Ok(events) => {
+                for event in events {
+                    println!(
+                        "[Abyss][writer] Data budget {} reached {}% ({:.1} of {:.1} MB)",
+                        event.budget_id,
+                        event.threshold_pct,
+                        event.used_bytes / 1_048_576.0,
+                        event.bytes_limit / 1_048_576.0
+                    );
+                    (self.on_budget)(event);
+                }
+            }
+            Err(e) => self.report("Failed to update data budgets", e),
+        }
+    }
+
+    /// Log a persistence failure and forward it to the error sink.
+    fn report(&self, what: &str, e: impl Into<AbyssError>) {
+        if self.current_session_id.is_some() {
+            self.pending_errors.set(self.pending_errors.get() + 1);
+        }
+        let err = e.into().context(what);
+        eprintln!("[Abyss][writer] {err}");
+        (self.on_error)(err);
+    }
+
+    /// Prune sessions per the stored retention policy, if one is set.
+    fn enforce_retention(&self, conn: &Connection) {
+        let policy = settings::load(conn).retention;
+        if !policy.is_enabled() {
+            return;
+        }
+        match db::enforce_retention(conn, &policy) {
+            Ok(report) if report.total() > 0 => {
+                println!(
+                    "[Abyss][writer] Retention pruned {} session(s) ({} by age, {} by count, {} by size)",
+                    report.total(),
+                    report.deleted_by_age,
+                    report.deleted_by_count,
+                    report.deleted_by_size
+                );
+                (self.on_pruned)(report);
+            }
+            Ok(_) => {}
+            Err(e) => self.report("Retention failed", e),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_start_session(
         &mut self,
         conn: &Connection,
@@ -154,52 +1145,217 @@ impl WriterState {
         local_lat: f64,
         local_lng: f64,
     ) {
-        let now = Utc::now().to_rfc3339();
+        let now = self.now();
         match db::insert_session(conn, id, name, &now, local_city, local_country, local_lat, local_lng) {
             Ok(_) => {
                 println!("[Abyss][writer] Started session '{name}' ({id})");
                 self.current_session_id = Some(id.to_string());
-                self.tick_counter = 0;
-                self.seen_dest_ips.clear();
+                self.reset_session_tracking();
+                self.clear_journal();
+                self.profile = settings::load(conn).persistence.resolved();
+                if !self.default_routes.is_empty() {
+                    self.insert_route_event(conn, &now, "start", &self.default_routes);
+                }
+                if let Some(change) = &self.public_ip {
+                    self.insert_network_change(conn, &now, "start", change);
+                }
             }
             Err(e) => {
-                eprintln!("[Abyss][writer] Failed to start session: {e}");
+                self.report("Failed to start session", e);
             }
         }
     }
 
     fn handle_end_session(&mut self, conn: &Connection, id: &str) {
-        let now = Utc::now().to_rfc3339();
+        if self.current_session_id.as_deref() == Some(id) {
+            self.flush_recording_stats(conn, id);
+        }
+        let now = self.now();
         match db::finalize_session(conn, id, &now) {
             Ok(_) => {
                 println!("[Abyss][writer] Ended session {id}");
+                if let Err(e) = db::store_session_thumbnail(conn, id) {
+                    self.report("Failed to store session thumbnail", e);
+                }
                 self.current_session_id = None;
-                self.tick_counter = 0;
-                self.seen_dest_ips.clear();
+                self.reset_session_tracking();
+                self.clear_journal();
+                (self.on_session_ended)(id.to_string());
             }
             Err(e) => {
-                eprintln!("[Abyss][writer] Failed to finalize session: {e}");
+                self.report("Failed to finalize session", e);
             }
         }
     }
 
-    fn handle_frame(&mut self, conn: &Connection, frame: &TelemetryFrame) {
+    /// Sample the frame into the pending batch; nothing is written until
+    /// `flush`.
+    fn handle_frame(&mut self, mut frame: Box<TelemetryFrame>) {
         let session_id = match &self.current_session_id {
             Some(id) => id.clone(),
             None => return, // No active session, skip
         };
+        if !self.exclusions.is_empty() {
+            let exclusions = &self.exclusions;
+            frame.flows.retain(|f| !exclusions.excludes(f.process.as_deref(), &f.dst.ip));
+            frame.processes.retain(|p| !exclusions.excludes_process(&p.process));
+        }
 
         self.tick_counter += 1;
         let tick = self.tick_counter;
-        let now = Utc::now().to_rfc3339();
+        self.integrate_bytes(&frame);
+        self.integrate_process_bytes(&frame);
+        self.track_app_groups(&frame);
+        for flow in &frame.flows {
+            if self.seen_flows.insert(&flow.id) {
+                self.pending_new_flows += 1;
+            }
+        }
+
+        let timestamp = self.now();
+        let totals_due = tick.is_multiple_of(TOTALS_UPDATE_INTERVAL);
+        if let Some(journal) = &mut self.journal {
+            journal.append(&JournalEntry::new(&session_id, &timestamp, &frame), totals_due);
+        }
+
+        let profile = self.profile;
+        let write_frame = tick.is_multiple_of(profile.frame_interval);
+        if !write_frame {
+            self.pending_stats.rows_skipped += 1;
+        }
+        let totals = totals_due.then(|| {
+            (
+                std::mem::take(&mut self.pending_bytes_up),
+                std::mem::take(&mut self.pending_bytes_down),
+                std::mem::take(&mut self.pending_new_flows),
+            )
+        });
+        let process_usage = tick
+            .is_multiple_of(profile.process_interval)
+            .then(|| std::mem::take(&mut self.pending_process_bytes));
+        let pending = PendingTick {
+            session_id,
+            timestamp,
+            write_frame,
+            // Flows hang off a frame row (FK integrity)
+            write_flows: write_frame && tick.is_multiple_of(profile.flow_interval),
+            totals,
+            destinations: tick.is_multiple_of(profile.destination_interval),
+            process_usage,
+            frame,
+        };
+        if !pending.write_frame && pending.totals.is_none() && !pending.destinations && pending.process_usage.is_none() {
+            return;
+        }
+        self.pending_rows += pending.rows();
+        self.batch_started.get_or_insert_with(Instant::now);
+        self.pending.push(pending);
+    }
+
+    /// The channel overflowed while the writer was busy: count the lost
+    /// frames against the session and publish them (`writer-backpressure`).
+    fn frames_dropped(&mut self, n: u64) {
+        eprintln!("[Abyss][writer] Falling behind — dropped {n} queued frame(s)");
+        self.stats.dropped_frames += n;
+        if self.current_session_id.is_some() {
+            self.pending_stats.dropped_frames += n as i64;
+        }
+        (self.on_stats)(self.stats.clone());
+    }
+
+    fn records_dropped(&mut self, n: u64) {
+        eprintln!("[Abyss][writer] Falling behind — dropped {n} queued traffic record batch(es)");
+        self.stats.dropped_records += n;
+        (self.on_stats)(self.stats.clone());
+    }
+
+    /// How long the writer may block before the pending batch is due.
+    fn wake_interval(&self, max: Duration) -> Duration {
+        match self.batch_started {
+            _ if self.pending_rows >= BATCH_MAX_ROWS => Duration::ZERO,
+            Some(started) => max.min(BATCH_MAX_AGE.saturating_sub(started.elapsed())),
+            None => max,
+        }
+    }
+
+    fn flush_if_due(&mut self, conn: &Connection) {
+        if self.wake_interval(BATCH_MAX_AGE).is_zero() {
+            self.flush(conn);
+        }
+    }
+
+    /// Write every pending tick in one transaction.
+    fn flush(&mut self, conn: &Connection) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let started = Instant::now();
+        let rows = std::mem::take(&mut self.pending_rows);
+        let ticks = std::mem::take(&mut self.pending);
+        self.batch_started = None;
+        if let Err(e) = conn.execute_batch("BEGIN TRANSACTION;") {
+            self.report("begin batch tx failed", e);
+            return;
+        }
+        for tick in ticks {
+            self.write_tick(conn, tick);
+        }
+        if let Err(e) = conn.execute_batch("COMMIT;") {
+            self.report("commit batch failed", e);
+            let _ = conn.execute_batch("ROLLBACK;");
+            return;
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.checkpoint();
+        }
+        self.record_flush(started.elapsed(), rows);
+    }
+
+    fn record_flush(&mut self, took: Duration, rows: usize) {
+        let now = Instant::now();
+        let ms = took.as_secs_f64() * 1000.0;
+        let stats = &mut self.stats;
+        stats.flushes += 1;
+        stats.rows_flushed += rows as u64;
+        stats.last_flush_ms = ms;
+        stats.avg_flush_ms += (ms - stats.avg_flush_ms) / stats.flushes as f64;
+        stats.max_flush_ms = stats.max_flush_ms.max(ms);
+        stats.pending_rows = self.pending_rows;
+
+        self.recent_flushes.push_back((now, rows));
+        while self
+            .recent_flushes
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > STATS_RATE_WINDOW)
+        {
+            self.recent_flushes.pop_front();
+        }
+        let window = now.duration_since(self.created).min(STATS_RATE_WINDOW).as_secs_f64().max(1.0);
+        let recent: usize = self.recent_flushes.iter().map(|(_, rows)| rows).sum();
+        stats.rows_per_sec = recent as f64 / window;
+        (self.on_stats)(stats.clone());
+    }
 
-        // 1) Persist frame snapshot at FRAME_SAMPLE_INTERVAL
-        let frame_row_id = if tick % FRAME_SAMPLE_INTERVAL == 0 {
+    fn write_tick(&mut self, conn: &Connection, tick: PendingTick) {
+        let PendingTick {
+            session_id,
+            frame,
+            timestamp,
+            write_frame,
+            write_flows,
+            totals,
+            destinations,
+            process_usage,
+        } = tick;
+        let session_id = session_id.as_str();
+
+        // 1) Frame snapshot
+        let frame_row_id = if write_frame {
             match db::insert_frame(
                 conn,
-                &session_id,
+                session_id,
                 frame.t,
-                &now,
+                &timestamp,
                 frame.net.bps,
                 frame.net.pps,
                 frame.net.active_flows,
@@ -213,10 +1369,16 @@ impl WriterState {
                 frame.proto.https,
                 frame.proto.http,
                 frame.proto.other,
+                frame.sockets.map(|s| s.time_wait),
+                frame.sockets.map(|s| s.ephemeral_in_use),
+                self.stored_wifi(&frame).as_ref(),
             ) {
-                Ok(id) => Some(id),
+                Ok(id) => {
+                    self.pending_stats.frames_written += 1;
+                    Some(id)
+                }
                 Err(e) => {
-                    eprintln!("[Abyss][writer] insert_frame failed: {e}");
+                    self.report("insert_frame failed", e);
                     None
                 }
             }
@@ -224,59 +1386,49 @@ impl WriterState {
             None
         };
 
-        // 2) Persist flow snapshots at FLOW_SAMPLE_INTERVAL
-        // Only persisted when a frame was also successfully inserted (FK integrity)
-        if tick % FLOW_SAMPLE_INTERVAL == 0 {
-            if let Some(fid) = frame_row_id {
-                self.persist_flows(conn, &session_id, fid, &frame.flows);
-            }
+        // 2) Flow snapshots, only when the frame row was inserted
+        if let (true, Some(fid)) = (write_flows, frame_row_id) {
+            self.persist_flows(conn, session_id, fid, &frame.flows);
         }
 
-        // 3) Update session running totals
-        if tick % TOTALS_UPDATE_INTERVAL == 0 {
-            // Estimate bytes transferred in this interval
-            let interval_secs = TOTALS_UPDATE_INTERVAL as f64;
-            let bytes_up = (frame.net.upload_bps / 8.0) * interval_secs;
-            let bytes_down = (frame.net.download_bps / 8.0) * interval_secs;
-
+        // 3) Session running totals
+        if let Some((bytes_up, bytes_down, new_flows)) = totals {
             if let Err(e) = db::update_session_totals(
                 conn,
-                &session_id,
+                session_id,
                 bytes_up,
                 bytes_down,
                 frame.net.bps,
                 frame.net.active_flows,
                 frame.net.latency_ms,
-                0, // new_unique_flows counted separately
+                new_flows,
             ) {
-                eprintln!("[Abyss][writer] update_session_totals failed: {e}");
+                self.report("update_session_totals failed", e);
             }
+            self.count_budget_usage(conn, None, bytes_up + bytes_down);
+            self.flush_recording_stats(conn, session_id);
         }
 
-        // 4) Upsert destinations
-        if tick % DEST_UPDATE_INTERVAL == 0 {
-            self.upsert_destinations(conn, &session_id, frame.t, &frame.flows);
+        // 4) Destinations
+        if destinations {
+            self.upsert_destinations(conn, session_id, frame.t, &frame.flows);
         }
 
-        // 5) Aggregate per-process usage
-        if tick % PROCESS_AGG_INTERVAL == 0 {
-            self.aggregate_process_usage(conn, &session_id, &now, &frame.flows);
+        // 5) Per-process usage
+        if let Some(attributed) = process_usage {
+            self.aggregate_process_usage(conn, session_id, &timestamp, attributed, &frame.flows);
         }
     }
 
     fn persist_flows(
-        &self,
+        &mut self,
         conn: &Connection,
         session_id: &str,
         frame_id: i64,
         flows: &[GeoFlow],
     ) {
-        // Use a transaction for batching
-        if let Err(e) = conn.execute_batch("BEGIN TRANSACTION;") {
-            eprintln!("[Abyss][writer] begin tx failed: {e}");
-            return;
-        }
-
+        // Runs inside the batch transaction (see `flush`)
+        let mut written = 0;
         for flow in flows {
             let protocol_str = match flow.protocol {
                 1 => "tcp",
@@ -310,15 +1462,32 @@ impl WriterState {
                 _ => "Unknown",
             });
 
+            let (flow_id, src_ip, dst_ip, process) = if self.redact {
+                let dst_ip = self.anonymizer.ip(&flow.dst.ip);
+                (
+                    flow.id.replacen(&flow.dst.ip, &dst_ip, 1),
+                    self.anonymizer.ip(&flow.src.ip),
+                    dst_ip,
+                    None,
+                )
+            } else {
+                (
+                    flow.id.clone(),
+                    flow.src.ip.clone(),
+                    flow.dst.ip.clone(),
+                    flow.process.as_deref(),
+                )
+            };
+
             if let Err(e) = db::insert_flow_snapshot(
                 conn,
                 session_id,
                 frame_id,
-                &flow.id,
-                &flow.src.ip,
+                &flow_id,
+                &src_ip,
                 &flow.src.city,
                 &flow.src.country,
-                &flow.dst.ip,
+                &dst_ip,
                 flow.dst.lat,
                 flow.dst.lng,
                 &flow.dst.city,
@@ -333,15 +1502,146 @@ impl WriterState {
                 flow.port,
                 service_str,
                 flow.started_at,
-                flow.process.as_deref(),
-                flow.pid,
+                process,
+                if self.redact { None } else { flow.pid },
+                if self.redact { None } else { flow.domain.as_deref() },
+                if self.redact { None } else { flow.sni.as_deref() },
+                flow.service_class.map(|c| c.as_str()),
+                flow.threat.as_deref(),
+                if self.redact { None } else { flow.app_group.as_deref() },
+                flow.container
+                    .as_ref()
+                    .filter(|_| !self.redact)
+                    .map(|c| (c.name.as_str(), c.image.as_deref())),
             ) {
-                eprintln!("[Abyss][writer] insert_flow_snapshot failed: {e}");
+                self.report("insert_flow_snapshot failed", e);
+            } else {
+                written += 1;
+            }
+            if let (false, Some(sni)) = (self.redact, flow.sni.as_deref()) {
+                if let Err(e) = db::record_hostname(conn, &dst_ip, sni, "sni") {
+                    self.report("record_hostname failed", e);
+                }
+            }
+        }
+        self.pending_stats.flows_written += written;
+    }
+
+    /// IP → domain mappings are skipped entirely while redacting: the domain
+    /// says more about browsing than the truncated IP it would be keyed on.
+    /// Alerts about excluded processes are dropped, as are excluded flows
+    /// from the rest (and alerts left with none of the flows they matched).
+    fn record_alert(&self, conn: &Connection, mut event: AlertEvent) {
+        if event.context.process.as_deref().is_some_and(|p| self.exclusions.excludes_process(p)) {
+            return;
+        }
+        let matched = event.context.flow_ids.len();
+        event
+            .context
+            .flow_ids
+            .retain(|id| !flow_id_ip(id).is_some_and(|ip| self.exclusions.excludes_ip(ip)));
+        if matched > 0 && event.context.flow_ids.is_empty() {
+            return;
+        }
+        if self.redact {
+            self.anonymizer.alert(&mut event);
+        }
+        if let Err(e) = db::insert_alert_event(conn, &event) {
+            self.report("Failed to record alert", e);
+        }
+    }
+
+    fn record_dns(&self, conn: &Connection, answers: &[DnsAnswer]) {
+        if self.redact || answers.is_empty() {
+            return;
+        }
+        if let Err(e) = conn.execute_batch("BEGIN TRANSACTION;") {
+            self.report("begin dns tx failed", e);
+            return;
+        }
+        for answer in answers {
+            if let Err(e) = db::upsert_dns_mapping(conn, &answer.ip, &answer.domain, answer.ttl, answer.source)
+                .and_then(|_| db::record_hostname(conn, &answer.ip, &answer.domain, "dns"))
+            {
+                self.report(&format!("record_dns failed for {}", answer.ip), e);
+            }
+        }
+        if let Err(e) = conn.execute_batch("COMMIT;") {
+            self.report("commit dns tx failed", e);
+            let _ = conn.execute_batch("ROLLBACK;");
+        }
+    }
+
+    fn record_route_change(&mut self, conn: &Connection, change: RouteChange) {
+        let kind = if change.initial { "start" } else { "change" };
+        self.insert_route_event(conn, &change.timestamp, kind, &change.routes);
+        self.default_routes = change.routes;
+    }
+
+    fn insert_route_event(&self, conn: &Connection, timestamp: &str, kind: &str, routes: &[DefaultRoute]) {
+        let Some(session_id) = &self.current_session_id else {
+            return;
+        };
+        let mut routes = routes.to_vec();
+        if self.redact {
+            for route in &mut routes {
+                route.gateway = route.gateway.as_deref().map(|ip| self.anonymizer.ip(ip));
             }
         }
+        if let Err(e) = db::insert_route_event(conn, session_id, timestamp, kind, &routes) {
+            self.report("Failed to record route change", e);
+        }
+    }
+
+    fn insert_network_change(&self, conn: &Connection, timestamp: &str, kind: &str, change: &NetworkChange) {
+        let Some(session_id) = &self.current_session_id else {
+            return;
+        };
+        let mut change = change.clone();
+        if self.redact {
+            change.ip = self.anonymizer.ip(&change.ip);
+            change.previous_ip = change.previous_ip.as_deref().map(|ip| self.anonymizer.ip(ip));
+        }
+        if let Err(e) = db::insert_network_change(conn, session_id, timestamp, kind, &change) {
+            self.report("Failed to record network change", e);
+        }
+    }
+
+    /// The frame's Wi-Fi reading as stored; the SSID is dropped while
+    /// redacting, as it can pin down where the user was.
+    fn stored_wifi(&self, frame: &TelemetryFrame) -> Option<WifiLink> {
+        let mut wifi = frame.wifi.clone()?;
+        if self.redact {
+            wifi.ssid = None;
+            wifi.bssid = None;
+        }
+        Some(wifi)
+    }
 
+    fn record_flow_events(&self, conn: &Connection, mut events: Vec<FlowEvent>) {
+        events.retain(|e| !self.exclusions.excludes(e.process.as_deref(), &e.remote_ip));
+        if events.is_empty() {
+            return;
+        }
+        if let Err(e) = conn.execute_batch("BEGIN TRANSACTION;") {
+            self.report("begin flow events tx failed", e);
+            return;
+        }
+        for mut event in events {
+            if self.redact {
+                let remote_ip = self.anonymizer.ip(&event.remote_ip);
+                event.flow_id = event.flow_id.replacen(&event.remote_ip, &remote_ip, 1);
+                event.remote_ip = remote_ip;
+                event.local_ip = self.anonymizer.ip(&event.local_ip);
+                event.process = None;
+                event.pid = None;
+            }
+            if let Err(e) = db::insert_flow_event(conn, self.current_session_id.as_deref(), &event) {
+                self.report(&format!("insert_flow_event failed for {}", event.flow_id), e);
+            }
+        }
         if let Err(e) = conn.execute_batch("COMMIT;") {
-            eprintln!("[Abyss][writer] commit failed: {e}");
+            self.report("commit flow events tx failed", e);
             let _ = conn.execute_batch("ROLLBACK;");
         }
     }
@@ -353,15 +1653,6 @@ impl WriterState {
         t: f64,
         flows: &[GeoFlow],
     ) {
-        if flows.is_empty() {
-            return;
-        }
-
-        if let Err(e) = conn.execute_batch("BEGIN TRANSACTION;") {
-            eprintln!("[Abyss][writer] begin dest tx failed: {e}");
-            return;
-        }
-
         for flow in flows {
             let bytes_est = flow.bps / 8.0; // 1-second worth
             let service_str = flow.service.map(|s| match s {
@@ -371,10 +1662,16 @@ impl WriterState {
                 _ => "Other",
             });
 
+            let (dst_ip, process, domain) = if self.redact {
+                (self.anonymizer.ip(&flow.dst.ip), None, None)
+            } else {
+                (flow.dst.ip.clone(), flow.process.as_deref(), flow.domain.as_deref())
+            };
+
             if let Err(e) = db::upsert_destination(
                 conn,
                 session_id,
-                &flow.dst.ip,
+                &dst_ip,
                 &flow.dst.city,
                 &flow.dst.country,
                 flow.dst.asn.as_deref(),
@@ -382,12 +1679,13 @@ impl WriterState {
                 t,
                 bytes_est,
                 service_str,
-                flow.process.as_deref(),
+                process,
+                domain,
             ) {
-                eprintln!("[Abyss][writer] upsert_destination failed for {}: {e}", flow.dst.ip);
+                self.report(&format!("upsert_destination failed for {dst_ip}"), e);
             }
 
-            self.seen_dest_ips.insert(flow.dst.ip.clone(), true);
+            self.seen_dest_ips.insert(dst_ip, true);
         }
 
         // Cap to prevent unbounded growth in long sessions.
@@ -395,18 +1693,17 @@ impl WriterState {
         if self.seen_dest_ips.len() > 5000 {
             self.seen_dest_ips.clear();
         }
-
-        if let Err(e) = conn.execute_batch("COMMIT;") {
-            eprintln!("[Abyss][writer] commit dest tx failed: {e}");
-            let _ = conn.execute_batch("ROLLBACK;");
-        }
     }
 
+    /// Write one `process_usage` row per process: bytes integrated from
+    /// attributed rates when the monitor provided them, else estimated from
+    /// this frame's flows over the aggregation interval.
     fn aggregate_process_usage(
-        &self,
+        &mut self,
         conn: &Connection,
         session_id: &str,
         timestamp: &str,
+        attributed: HashMap<String, (f64, f64)>,
         flows: &[GeoFlow],
     ) {
         // Aggregate by process name
@@ -419,7 +1716,7 @@ impl WriterState {
         }
 
         let mut by_process: HashMap<String, Accum> = HashMap::new();
-        let interval_secs = PROCESS_AGG_INTERVAL as f64;
+        let interval_secs = self.profile.process_interval as f64;
 
         for flow in flows {
             let name = flow
@@ -435,23 +1732,37 @@ impl WriterState {
                 rtt_samples: 0,
             });
 
-            let bytes_per_sec = flow.bps / 8.0;
-            match flow.dir.as_str() {
-                "up" => entry.bytes_up += bytes_per_sec * interval_secs,
-                "down" => entry.bytes_down += bytes_per_sec * interval_secs,
-                _ => {
-                    entry.bytes_up += bytes_per_sec * interval_secs / 2.0;
-                    entry.bytes_down += bytes_per_sec * interval_secs / 2.0;
+            // With attributed bytes, only flow counts and RTT come from flows
+            if attributed.is_empty() {
+                if let (Some(tx), Some(rx)) = (flow.tx_bps, flow.rx_bps) {
+                    entry.bytes_up += tx / 8.0 * interval_secs;
+                    entry.bytes_down += rx / 8.0 * interval_secs;
+                } else {
+                    let bytes_per_sec = flow.bps / 8.0;
+                    match flow.dir.as_str() {
+                        "up" => entry.bytes_up += bytes_per_sec * interval_secs,
+                        "down" => entry.bytes_down += bytes_per_sec * interval_secs,
+                        _ => {
+                            entry.bytes_up += bytes_per_sec * interval_secs / 2.0;
+                            entry.bytes_down += bytes_per_sec * interval_secs / 2.0;
+                        }
+                    }
                 }
             }
             entry.flow_count += 1;
             entry.total_rtt += flow.rtt;
             entry.rtt_samples += 1;
         }
-
-        if let Err(e) = conn.execute_batch("BEGIN TRANSACTION;") {
-            eprintln!("[Abyss][writer] begin process_usage tx failed: {e}");
-            return;
+        for (name, (up, down)) in attributed {
+            let entry = by_process.entry(name).or_insert(Accum {
+                bytes_up: 0.0,
+                bytes_down: 0.0,
+                flow_count: 0,
+                total_rtt: 0.0,
+                rtt_samples: 0,
+            });
+            entry.bytes_up += up;
+            entry.bytes_down += down;
         }
 
         for (process_name, accum) in &by_process {
@@ -466,18 +1777,760 @@ impl WriterState {
                 session_id,
                 timestamp,
                 process_name,
+                self.process_groups.get(process_name).map(String::as_str),
                 accum.bytes_up,
                 accum.bytes_down,
                 accum.flow_count,
                 avg_rtt,
             ) {
-                eprintln!("[Abyss][writer] insert_process_usage failed: {e}");
+                self.report("insert_process_usage failed", e);
             }
+            self.count_budget_usage(conn, Some(process_name), accum.bytes_up + accum.bytes_down);
         }
+    }
+}
 
-        if let Err(e) = conn.execute_batch("COMMIT;") {
-            eprintln!("[Abyss][writer] commit process_usage failed: {e}");
-            let _ = conn.execute_batch("ROLLBACK;");
+// ─── Unique flow filter ─────────────────────────────────────────────────────
+
+/// Fixed-size Bloom filter over flow keys.  Memory stays constant however
+/// long a session runs; the rare false positive undercounts by one flow.
+struct FlowFilter {
+    bits: Vec<u64>,
+}
+
+impl FlowFilter {
+    fn new() -> Self {
+        Self {
+            bits: vec![0; FLOW_FILTER_BITS / 64],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    /// Add `key`; returns true if it was not (probably) present before.
+    fn insert(&mut self, key: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        // Double hashing: probe i is h1 + i*h2
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        let mut new = false;
+        for i in 0..FLOW_FILTER_HASHES {
+            let bit = (h1.wrapping_add(i.wrapping_mul(h2)) as usize) % FLOW_FILTER_BITS;
+            let (word, mask) = (bit / 64, 1u64 << (bit % 64));
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                new = true;
+            }
+        }
+        new
+    }
+}
+
+// ─── Redaction ──────────────────────────────────────────────────────────────
+
+/// Truncate an address to its /24 (IPv4) or /48 (IPv6) network, e.g.
+/// `142.250.72.14` → `142.250.72.0`.  Unparsable input becomes `"redacted"`.
+pub fn truncate_ip(ip: &str) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => {
+            let [a, b, c, _] = v4.octets();
+            format!("{a}.{b}.{c}.0")
+        }
+        Ok(IpAddr::V6(v6)) => {
+            let s = v6.segments();
+            Ipv6Addr::new(s[0], s[1], s[2], 0, 0, 0, 0, 0).to_string()
+        }
+        Err(_) => "redacted".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anonymize::AnonymizeMode;
+    use crate::test_utils::{memory_db, FlowBuilder, FrameBuilder, TestClock};
+    use std::sync::{Arc, Mutex};
+
+    /// A writer on `clock` whose reported errors are collected in the returned list.
+    fn writer(clock: &TestClock) -> (WriterState, Arc<Mutex<Vec<String>>>) {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let sink = errors.clone();
+        let state = WriterState::new(
+            Box::new(move |e| sink.lock().unwrap().push(e.to_string())),
+            Box::new(|_| {}),
+            Box::new(|_| {}),
+            Box::new(|_| {}),
+            Box::new(|_| {}),
+            clock.source(),
+        );
+        (state, errors)
+    }
+
+    fn start(state: &mut WriterState, conn: &Connection, id: &str) {
+        state.apply(
+            conn,
+            WriteCommand::StartSession {
+                id: id.to_string(),
+                name: "Test".to_string(),
+                local_city: "New York".to_string(),
+                local_country: "US".to_string(),
+                local_lat: 40.71,
+                local_lng: -74.01,
+            },
+        );
+    }
+
+    /// Feed one frame per second of monitor time, advancing the clock with
+    /// it, then flush the batch.
+    fn feed(state: &mut WriterState, conn: &Connection, clock: &TestClock, frames: Vec<TelemetryFrame>) {
+        for frame in frames {
+            clock.advance(1);
+            state.apply(conn, WriteCommand::Frame(Box::new(frame)));
+        }
+        state.flush(conn);
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn two_flows() -> Vec<GeoFlow> {
+        vec![
+            FlowBuilder::new("a").process("firefox", 100).build(),
+            FlowBuilder::new("b").dst("1.1.1.1", "AU").udp().port(53).build(),
+        ]
+    }
+
+    /// A process-rate alert on firefox's flow in `two_flows`.
+    fn firefox_alert(session_id: &str) -> Box<AlertEvent> {
+        let mut event = crate::notify::sample_event();
+        event.id = format!("alert-{session_id}");
+        event.subject = "firefox".into();
+        event.message = "firefox is using 12.5 Mbps".into();
+        event.session_id = Some(session_id.into());
+        event.context.process = Some("firefox".into());
+        event.context.flow_ids = vec!["live-93.184.216.34:443:tcp".into()];
+        Box::new(event)
+    }
+
+    /// `(subject, message, context)` of every stored alert.
+    fn stored_alerts(conn: &Connection) -> Vec<(String, String, String)> {
+        let mut stmt = conn.prepare("SELECT subject, message, context FROM alert_events ORDER BY id").unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        rows.filter_map(|r| r.ok()).collect()
+    }
+
+    #[test]
+    fn samples_frames_and_flows_at_their_intervals() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, errors) = writer(&clock);
+        start(&mut state, &conn, "s1");
+
+        let frames = (0..FLOW_SAMPLE_INTERVAL)
+            .map(|t| FrameBuilder::at(t as f64).flows(two_flows()).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+
+        assert_eq!(count(&conn, "frames"), (FLOW_SAMPLE_INTERVAL / FRAME_SAMPLE_INTERVAL) as i64);
+        assert_eq!(count(&conn, "flow_snapshots"), 2);
+        assert_eq!(count(&conn, "destinations"), 2);
+        let stats = db::get_recording_stats(&conn, "s1").unwrap().unwrap();
+        assert_eq!(stats.frames_written, 2);
+        assert_eq!(stats.flows_written, 2);
+        assert_eq!(stats.rows_skipped, 8);
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn buffers_sampled_rows_until_the_batch_is_flushed() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, errors) = writer(&clock);
+        start(&mut state, &conn, "s1");
+
+        for t in 0..FLOW_SAMPLE_INTERVAL {
+            clock.advance(1);
+            let frame = FrameBuilder::at(t as f64).flows(two_flows()).build();
+            state.apply(&conn, WriteCommand::Frame(Box::new(frame)));
+        }
+        assert_eq!(count(&conn, "frames"), 0);
+        assert_eq!(state.pending_rows, 2 + 2 + 2 + 2);
+        assert!(state.wake_interval(IDLE_WAKE) <= BATCH_MAX_AGE);
+
+        state.flush(&conn);
+        assert_eq!(count(&conn, "frames"), 2);
+        assert_eq!(count(&conn, "flow_snapshots"), 2);
+        assert_eq!(state.stats.flushes, 1);
+        assert_eq!(state.stats.rows_flushed, 8);
+        assert_eq!(state.stats.pending_rows, 0);
+        assert_eq!(state.wake_interval(IDLE_WAKE), IDLE_WAKE);
+
+        // Ending the session writes what is still buffered first
+        for t in 10..15 {
+            state.apply(&conn, WriteCommand::Frame(Box::new(FrameBuilder::at(t as f64).build())));
+        }
+        state.apply(&conn, WriteCommand::EndSession { id: "s1".to_string() });
+        assert_eq!(count(&conn, "frames"), 3);
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn persistence_profile_is_reloaded_when_a_session_starts() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, errors) = writer(&clock);
+        let frames = || (0..10).map(|t| FrameBuilder::at(t as f64).flows(two_flows()).build()).collect();
+        start(&mut state, &conn, "s1");
+
+        // Saved mid-session: "s1" keeps sampling with the profile it started with
+        let saved = settings::Settings {
+            persistence: PersistenceProfile::preset(PersistencePreset::Detailed),
+            ..Default::default()
+        };
+        settings::save(&conn, &saved).unwrap();
+        feed(&mut state, &conn, &clock, frames());
+        assert_eq!(count(&conn, "frames"), 2);
+
+        state.apply(&conn, WriteCommand::EndSession { id: "s1".to_string() });
+        start(&mut state, &conn, "s2");
+        feed(&mut state, &conn, &clock, frames());
+        let stats = db::get_recording_stats(&conn, "s2").unwrap().unwrap();
+        assert_eq!(stats.frames_written, 10);
+        assert_eq!(stats.flows_written, 4);
+        assert!(errors.lock().unwrap().is_empty());
+
+        let uneven = PersistenceProfile {
+            preset: PersistencePreset::Custom,
+            frame_interval: 4,
+            flow_interval: 10,
+            ..PersistenceProfile::default()
+        };
+        assert!(uneven.validate().is_err());
+        assert_eq!(uneven.resolved(), uneven);
+    }
+
+    #[test]
+    fn frames_outside_a_session_are_ignored() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _) = writer(&clock);
+
+        let frames = (0..FLOW_SAMPLE_INTERVAL)
+            .map(|t| FrameBuilder::at(t as f64).flows(two_flows()).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+
+        assert_eq!(count(&conn, "frames"), 0);
+        assert_eq!(count(&conn, "flow_snapshots"), 0);
+    }
+
+    #[test]
+    fn integrates_rates_into_session_totals() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _) = writer(&clock);
+        start(&mut state, &conn, "s1");
+
+        // 8 kbit/s up, 16 kbit/s down for 4 s, then a gap that must not be
+        // integrated across, then another 4 s
+        let times = [0.0, 1.0, 2.0, 3.0, 4.0, 30.0, 31.0, 32.0, 33.0, 34.0];
+        let frames = times
+            .iter()
+            .map(|&t| FrameBuilder::at(t).rates(8_000.0, 16_000.0).flows(two_flows()).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+
+        let session = db::get_session(&conn, "s1").unwrap().unwrap();
+        assert_eq!(session.total_bytes_up, 8_000.0);
+        assert_eq!(session.total_bytes_down, 16_000.0);
+        // The same two flows every tick count once
+        assert_eq!(session.total_flows, 2);
+        assert_eq!(session.peak_flows, 2);
+    }
+
+    #[test]
+    fn slow_ticks_are_still_integrated() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _) = writer(&clock);
+        start(&mut state, &conn, "s1");
+
+        // Idle polling on battery: a frame every 20 s
+        let frames = (0..5)
+            .map(|i| {
+                let mut frame = FrameBuilder::at(i as f64 * 20.0).rates(8_000.0, 16_000.0).build();
+                frame.rate = Some(crate::pacing::PollRate {
+                    tick_ms: 20_000,
+                    netstat_poll_ms: 20_000,
+                    idle: true,
+                    power: crate::pacing::PowerSource::Battery,
+                });
+                frame
+            })
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+
+        let session = db::get_session(&conn, "s1").unwrap().unwrap();
+        assert_eq!(session.total_bytes_up, 80_000.0);
+        assert_eq!(session.total_bytes_down, 160_000.0);
+    }
+
+    #[test]
+    fn budgets_report_each_threshold_as_usage_is_recorded() {
+        use crate::alerts::QuotaPeriod;
+        use crate::budgets::BudgetScope;
+
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let mut state = WriterState::new(
+            Box::new(|_| {}),
+            Box::new(|_| {}),
+            Box::new(|_| {}),
+            Box::new(move |e: BudgetEvent| sink.lock().unwrap().push(e.threshold_pct)),
+            Box::new(|_| {}),
+            clock.source(),
+        );
+        db::set_data_budget(&conn, QuotaPeriod::Day, &BudgetScope::Global, 15_000.0, clock.now()).unwrap();
+        start(&mut state, &conn, "s1");
+
+        // 3 kB/s: 12 kB (80%) integrated by the first totals update, 27 kB by the second
+        let frames = (0..10)
+            .map(|t| FrameBuilder::at(t as f64).rates(8_000.0, 16_000.0).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+
+        assert_eq!(*events.lock().unwrap(), vec![80, 100]);
+        let budget = &db::get_data_budgets(&conn, clock.now()).unwrap()[0];
+        assert_eq!(budget.used_bytes, 27_000.0);
+        assert_eq!(budget.notified_pct, 100);
+    }
+
+    #[test]
+    fn stamps_rows_with_the_writer_clock() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _) = writer(&clock);
+        let started = clock.now();
+        start(&mut state, &conn, "s1");
+
+        let frames = (0..FRAME_SAMPLE_INTERVAL).map(|t| FrameBuilder::at(t as f64).build()).collect();
+        feed(&mut state, &conn, &clock, frames);
+        let frame_ts: String = conn
+            .query_row("SELECT timestamp FROM frames", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(frame_ts, clock.now().to_rfc3339());
+
+        clock.advance(55);
+        state.apply(&conn, WriteCommand::EndSession { id: "s1".to_string() });
+
+        let session = db::get_session(&conn, "s1").unwrap().unwrap();
+        assert_eq!(session.started_at, started.to_rfc3339());
+        assert_eq!(session.ended_at, Some(clock.now().to_rfc3339()));
+        assert!((session.duration_secs.unwrap() - 60.0).abs() < 0.01);
+        assert_eq!(session.status, "complete");
+    }
+
+    #[test]
+    fn recovers_sessions_left_open_by_a_crash() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _) = writer(&clock);
+        start(&mut state, &conn, "s1");
+        let frames = (0..FRAME_SAMPLE_INTERVAL).map(|t| FrameBuilder::at(t as f64).build()).collect();
+        feed(&mut state, &conn, &clock, frames);
+        let last_frame_at = clock.now().to_rfc3339();
+
+        // The app dies without ending the session; the next writer finds it
+        drop(state);
+        clock.advance(3600);
+        let (mut state, errors) = writer(&clock);
+        state.start(&conn);
+
+        let session = db::get_session(&conn, "s1").unwrap().unwrap();
+        assert_eq!(session.status, "crashed");
+        assert_eq!(session.ended_at, Some(last_frame_at));
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn replays_journaled_frames_after_a_crash() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let dir = std::env::temp_dir().join(format!("abyss-writer-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("sessions.db");
+        let (mut state, _) = writer(&clock);
+        state.journal = Some(FrameJournal::open(&db_path));
+        start(&mut state, &conn, "s1");
+
+        // Frames 0-4 are committed, 5-7 are only journaled when the app dies
+        let frame = |t: u32| FrameBuilder::at(t as f64).rates(8000.0, 16000.0).build();
+        feed(&mut state, &conn, &clock, (0..5).map(frame).collect());
+        for t in 5..8 {
+            clock.advance(1);
+            state.apply(&conn, WriteCommand::Frame(Box::new(frame(t))));
+        }
+        assert_eq!(count(&conn, "frames"), 1);
+        let committed = db::get_session(&conn, "s1").unwrap().unwrap();
+        drop(state);
+
+        let (mut state, errors) = writer(&clock);
+        state.journal = Some(FrameJournal::open(&db_path));
+        state.start(&conn);
+
+        // The journal starts at frame 4 (stored), so 5-7 become rows...
+        assert_eq!(count(&conn, "frames"), 4);
+        let session = db::get_session(&conn, "s1").unwrap().unwrap();
+        assert_eq!(session.status, "crashed");
+        assert_eq!(session.ended_at, Some(clock.now().to_rfc3339()));
+        // ...and the 3 seconds after it are added to the totals
+        assert!((session.total_bytes_up - committed.total_bytes_up - 3000.0).abs() < 0.01);
+        assert!((session.total_bytes_down - committed.total_bytes_down - 6000.0).abs() < 0.01);
+        assert!(state.journal.as_ref().unwrap().read().is_empty());
+        assert!(errors.lock().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reopen_moves_to_the_new_database_and_replays_deferred_commands() {
+        let dir = std::env::temp_dir().join(format!("abyss-writer-reopen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (old_path, new_path) = (dir.join("sessions.db"), dir.join("restored.db"));
+        let (tx, rx) = create_channel();
+        let thread_path = old_path.clone();
+        let writer = std::thread::spawn(move || {
+            writer_thread(
+                rx,
+                thread_path,
+                Box::new(|_| {}),
+                Box::new(|_| {}),
+                Box::new(|_| {}),
+                Box::new(|_| {}),
+                Box::new(|_| {}),
+                Box::new(|_| {}),
+            )
+        });
+
+        let (ack, done) = oneshot::channel();
+        tx.send(WriteCommand::Pause { ack }).unwrap();
+        done.blocking_recv().unwrap().unwrap();
+        tx.send(WriteCommand::StartSession {
+            id: "s1".to_string(),
+            name: "Test".to_string(),
+            local_city: "New York".to_string(),
+            local_country: "US".to_string(),
+            local_lat: 40.71,
+            local_lng: -74.01,
+        })
+        .unwrap();
+        let (ack, done) = oneshot::channel();
+        tx.send(WriteCommand::Reopen { path: new_path.clone(), ack }).unwrap();
+        done.blocking_recv().unwrap().unwrap();
+        tx.send(WriteCommand::EndSession { id: "s1".to_string() }).unwrap();
+        tx.send(WriteCommand::Shutdown).unwrap();
+        writer.join().unwrap();
+
+        // The session started while paused lands in the database reopened onto
+        let restored = db::open_database(&new_path).unwrap();
+        assert_eq!(db::get_session(&restored, "s1").unwrap().unwrap().status, "complete");
+        let old = db::open_database(&old_path).unwrap();
+        assert!(db::get_session(&old, "s1").unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn redaction_truncates_persisted_addresses() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _) = writer(&clock);
+        state.redact = true;
+        start(&mut state, &conn, "s1");
+
+        let frames = (0..FLOW_SAMPLE_INTERVAL)
+            .map(|t| FrameBuilder::at(t as f64).flows(two_flows()).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+
+        let mut stmt = conn
+            .prepare("SELECT dst_ip, process FROM flow_snapshots ORDER BY flow_id")
+            .unwrap();
+        let rows: Vec<(String, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("93.184.216.0".to_string(), None), ("1.1.1.0".to_string(), None)]
+        );
+
+        state.apply(&conn, WriteCommand::RecordAlert(firefox_alert("s1")));
+        let (subject, message, context) = &stored_alerts(&conn)[0];
+        assert_eq!((subject.as_str(), message.as_str()), ("redacted", "redacted is using 12.5 Mbps"));
+        assert!(context.contains("live-93.184.216.0:443:tcp"));
+        assert!(!context.contains("firefox") && !context.contains("93.184.216.34"));
+    }
+
+    #[test]
+    fn excluded_processes_and_ranges_are_never_persisted() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, errors) = writer(&clock);
+        let key = ExclusionKey::generate();
+        let exclusions = PersistenceExclusions {
+            processes: vec!["Firefox.exe".into()],
+            ip_ranges: vec![],
+        };
+        let saved = settings::Settings {
+            persistence_exclusions: Some(exclusions.seal(&key).unwrap()),
+            ..Default::default()
+        };
+        settings::save(&conn, &saved).unwrap();
+        state.exclusion_key = Some(key);
+        state.start(&conn);
+        start(&mut state, &conn, "s1");
+
+        let frames = (0..FLOW_SAMPLE_INTERVAL)
+            .map(|t| FrameBuilder::at(t as f64).flows(two_flows()).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+        let ips = |sql: &str| -> Vec<String> {
+            let mut stmt = conn.prepare(sql).unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.filter_map(|r| r.ok()).collect()
+        };
+        assert_eq!(ips("SELECT DISTINCT dst_ip FROM flow_snapshots"), ["1.1.1.1"]);
+        assert_eq!(ips("SELECT ip FROM destinations"), ["1.1.1.1"]);
+
+        // Ranges apply from the next frame
+        let exclusions = PersistenceExclusions {
+            processes: vec![],
+            ip_ranges: vec!["1.1.1.0/24".into()],
+        };
+        state.exclusions = ExclusionFilter::new(&exclusions);
+        state.apply(&conn, WriteCommand::EndSession { id: "s1".to_string() });
+        start(&mut state, &conn, "s2");
+        let frames = (0..FLOW_SAMPLE_INTERVAL)
+            .map(|t| FrameBuilder::at(t as f64).flows(two_flows()).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+        assert_eq!(
+            ips("SELECT DISTINCT dst_ip FROM flow_snapshots WHERE session_id = 's2'"),
+            ["93.184.216.34"]
+        );
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn excluded_traffic_is_left_out_of_alerts_and_dns() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, errors) = writer(&clock);
+        state.exclusions = ExclusionFilter::new(&PersistenceExclusions {
+            processes: vec!["Firefox.exe".into()],
+            ip_ranges: vec!["1.1.1.0/24".into()],
+        });
+        start(&mut state, &conn, "s1");
+
+        // About an excluded process, about excluded flows only, and mixed
+        state.apply(&conn, WriteCommand::RecordAlert(firefox_alert("s1")));
+        let mut dns_only = firefox_alert("s2");
+        dns_only.subject = "AU".into();
+        dns_only.message = "1 flow(s) to AU".into();
+        dns_only.context.process = None;
+        dns_only.context.flow_ids = vec!["live-1.1.1.1:53:udp".into()];
+        state.apply(&conn, WriteCommand::RecordAlert(dns_only.clone()));
+        let mut mixed = dns_only;
+        mixed.id = "alert-s3".into();
+        mixed.context.flow_ids.push("live-93.184.216.34:443:tcp".into());
+        state.apply(&conn, WriteCommand::RecordAlert(mixed));
+
+        let alerts = stored_alerts(&conn);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].2.contains("93.184.216.34") && !alerts[0].2.contains("1.1.1.1"));
+
+        let answer = |ip: &str, domain: &str| DnsAnswer {
+            ip: ip.into(),
+            domain: domain.into(),
+            ttl: 60,
+            source: "capture",
+        };
+        state.apply(
+            &conn,
+            WriteCommand::RecordDns(vec![answer("1.1.1.1", "one.one.one.one"), answer("93.184.216.34", "example.com")]),
+        );
+        let ips: Vec<String> = conn
+            .prepare("SELECT ip FROM dns_map")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+        assert_eq!(ips, ["93.184.216.34"]);
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn anonymizing_a_session_rewrites_stored_addresses() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _) = writer(&clock);
+        start(&mut state, &conn, "s1");
+        let frames = (0..FLOW_SAMPLE_INTERVAL)
+            .map(|t| FrameBuilder::at(t as f64).flows(two_flows()).build())
+            .collect();
+        feed(&mut state, &conn, &clock, frames);
+        state.apply(&conn, WriteCommand::RecordAlert(firefox_alert("s1")));
+        state.apply(&conn, WriteCommand::EndSession { id: "s1".to_string() });
+
+        let anonymizer = Anonymizer::new(AnonymizeMode::Hash, Some(AnonymizeKey::generate()));
+        // Both destinations and the local address
+        assert_eq!(db::anonymize_session(&conn, "s1", &|ip| anonymizer.ip(ip)).unwrap(), 3);
+        let ips = |sql: &str| -> Vec<String> {
+            let mut stmt = conn.prepare(sql).unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.filter_map(|r| r.ok()).collect()
+        };
+        let mut expected = vec![anonymizer.ip("1.1.1.1"), anonymizer.ip("93.184.216.34")];
+        expected.sort();
+        for sql in ["SELECT DISTINCT dst_ip FROM flow_snapshots", "SELECT ip FROM destinations"] {
+            let mut stored = ips(sql);
+            stored.sort();
+            assert_eq!(stored, expected);
+        }
+        let (subject, _, context) = &stored_alerts(&conn)[0];
+        assert_eq!(subject, "redacted");
+        assert!(context.contains(&anonymizer.ip("93.184.216.34")));
+        assert!(!context.contains("firefox") && !context.contains("93.184.216.34"));
+
+        // Running it again finds nothing left to replace
+        assert_eq!(db::anonymize_session(&conn, "s1", &|ip| anonymizer.ip(ip)).unwrap(), 0);
+    }
+
+    #[test]
+    fn public_address_is_recorded_again_at_session_start() {
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, errors) = writer(&clock);
+        state.redact = true;
+        state.apply(
+            &conn,
+            WriteCommand::RecordNetworkChange(NetworkChange {
+                timestamp: clock.now().to_rfc3339(),
+                ip: "203.0.113.7".to_string(),
+                previous_ip: None,
+                city: Some("Amsterdam".to_string()),
+                country: Some("NL".to_string()),
+                lat: Some(52.37),
+                lng: Some(4.9),
+                org: None,
+                interface: Some("wg0".to_string()),
+                vpn: true,
+                initial: true,
+            }),
+        );
+        start(&mut state, &conn, "s1");
+
+        let changes = db::get_network_changes(&conn, "s1").unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, "start");
+        assert_eq!(changes[0].ip, "203.0.113.0");
+        assert!(changes[0].vpn);
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    /// Run with `cargo test --lib prepared_statements -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn prepared_statements_cut_flush_time() {
+        let flush_time = |cache_capacity: usize| {
+            let conn = db::PreparedWriter::new(memory_db()).unwrap();
+            conn.set_prepared_statement_cache_capacity(cache_capacity);
+            let clock = TestClock::default();
+            let (mut state, _) = writer(&clock);
+            start(&mut state, &conn, "s1");
+            let flows: Vec<GeoFlow> = (0..25)
+                .map(|i| FlowBuilder::new(&format!("f{i}")).dst(&format!("203.0.113.{i}"), "NL").build())
+                .collect();
+            let frames = (0..2_000)
+                .map(|t| FrameBuilder::at(t as f64).flows(flows.clone()).build())
+                .collect();
+            let started = Instant::now();
+            feed(&mut state, &conn, &clock, frames);
+            started.elapsed()
+        };
+        let reprepared = flush_time(0);
+        let cached = flush_time(64);
+        println!("2000 ticks: {reprepared:?} re-preparing, {cached:?} with cached statements");
+        assert!(cached < reprepared);
+    }
+
+    #[test]
+    fn full_channel_drops_the_oldest_traffic_but_keeps_commands() {
+        let (tx, rx) = channel_with_capacity(3);
+        let frame = |t: f64| WriteCommand::Frame(Box::new(FrameBuilder::at(t).build()));
+        tx.send(WriteCommand::RecordDns(Vec::new())).unwrap();
+        tx.send(frame(0.0)).unwrap();
+        tx.send(WriteCommand::EndSession { id: "s1".to_string() }).unwrap();
+        tx.send(frame(1.0)).unwrap();
+        tx.send(frame(2.0)).unwrap();
+        tx.send(WriteCommand::Shutdown).unwrap();
+        tx.send(WriteCommand::Shutdown).unwrap();
+
+        let queued: Vec<String> = rx
+            .try_iter()
+            .map(|cmd| match cmd {
+                WriteCommand::Frame(f) => format!("frame {}", f.t),
+                WriteCommand::EndSession { .. } => "end".to_string(),
+                WriteCommand::Shutdown => "shutdown".to_string(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(queued, ["end", "shutdown", "shutdown"]);
+        assert_eq!(rx.take_dropped_frames(), 3);
+        assert_eq!(rx.take_dropped_records(), 1);
+        assert_eq!(rx.take_dropped_frames(), 0);
+
+        drop(tx);
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn monitor_only_drops_traffic_but_not_session_commands() {
+        assert!(WriteCommand::Frame(Box::new(FrameBuilder::at(0.0).build())).records_traffic());
+        assert!(WriteCommand::RecordDns(Vec::new()).records_traffic());
+        assert!(!WriteCommand::EndSession { id: "s1".to_string() }.records_traffic());
+        assert!(!WriteCommand::SetMonitorOnly { enabled: false }.records_traffic());
+    }
+
+    #[test]
+    fn closed_database_defers_session_commands_and_drops_traffic() {
+        let mut deferred = Deferred::default();
+        for t in 0..100 {
+            deferred.hold(WriteCommand::Frame(Box::new(FrameBuilder::at(t as f64).build())));
+            deferred.hold(WriteCommand::RecordDns(Vec::new()));
         }
+        deferred.hold(WriteCommand::EndSession { id: "s1".to_string() });
+        assert_eq!(deferred.commands.len(), 1);
+        assert_eq!((deferred.dropped_frames, deferred.dropped_records), (100, 100));
+
+        let conn = memory_db();
+        let clock = TestClock::default();
+        let (mut state, _errors) = writer(&clock);
+        start(&mut state, &conn, "s1");
+        state.replay(&conn, &mut deferred);
+        assert!(state.current_session_id.is_none());
+        assert!(deferred.commands.is_empty());
+        assert_eq!((deferred.dropped_frames, deferred.dropped_records), (0, 0));
     }
 }
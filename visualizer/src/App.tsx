@@ -97,10 +97,12 @@ export default function App() {
 
     setConnected(false);
 
-    import("@tauri-apps/api/event")
-      .then(({ listen }) => {
+    // Window-scoped: a global listen() also receives frames the host
+    // targets at other windows, so each window would see duplicates.
+    import("@tauri-apps/api/webviewWindow")
+      .then(({ getCurrentWebviewWindow }) => {
         if (!active) return;
-        listen<TelemetryFrame>("telemetry-frame", (event) => {
+        getCurrentWebviewWindow().listen<TelemetryFrame>("telemetry-frame", (event) => {
           ingestFrame(event.payload);
           setConnected(true);
         }).then((unlisten) => {